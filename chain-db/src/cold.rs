@@ -0,0 +1,97 @@
+//! Cold storage of old block bodies.
+//!
+//! Transactions of old blocks are moved to a separate RocksDB, which can be put on cheaper and slower disks. Keys
+//! and values are the same as the `transaction` column of ChainDB, all compressed with zstd. Block headers and the
+//! txid index are kept in ChainDB, they are small and hot.
+
+use std::path::Path;
+
+use rocks::prelude::*;
+
+use super::BoxError;
+
+pub(crate) struct ColdStore {
+    db: DB,
+    transaction: ColumnFamily,
+}
+
+impl ColdStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> ColdStore {
+        let db_options = DBOptions::default()
+            .create_if_missing(true)
+            .create_missing_column_families(true)
+            .increase_parallelism(num_cpus::get() as _)
+            .max_open_files(256);
+
+        let column_families = vec![
+            ColumnFamilyDescriptor::new(
+                DEFAULT_COLUMN_FAMILY_NAME,
+                ColumnFamilyOptions::default().optimize_for_small_db(),
+            ),
+            // [block_hash, transaction_index: u64, transaction_hash] => Transaction
+            ColumnFamilyDescriptor::new(
+                "transaction",
+                ColumnFamilyOptions::default()
+                    .prefix_extractor_fixed(32)
+                    .optimize_level_style_compaction(512 * 1024 * 1024)
+                    .compression(CompressionType::ZSTD)
+                    .bottommost_compression(CompressionType::ZSTD),
+            ),
+        ];
+
+        let (db, mut handles) = DB::open_with_column_families(&db_options, db_path, column_families).unwrap();
+        let transaction = handles.pop().unwrap();
+
+        ColdStore { db, transaction }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BoxError> {
+        match self.transaction.get(ReadOptions::default_instance(), key) {
+            Ok(val) => Ok(Some(val.to_vec())),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// All `(key, value)` pairs in `[lower_bound, upper_bound)`.
+    pub fn range(&self, lower_bound: &[u8], upper_bound: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let ropts = ReadOptions::default()
+            .iterate_lower_bound(lower_bound)
+            .iterate_upper_bound(upper_bound);
+        self.transaction
+            .new_iterator(&ropts)
+            .map(|(key, val)| (key.to_vec(), val.to_vec()))
+            .collect()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.transaction
+            .new_iterator(ReadOptions::default_instance())
+            .keys()
+            .map(|key| key.to_vec())
+    }
+
+    /// Write entries, synced before return, so that they can be safely deleted from ChainDB.
+    pub fn put_all(&self, entries: &[(Vec<u8>, Vec<u8>)]) -> Result<(), BoxError> {
+        let mut wb = WriteBatch::with_reserved_bytes(1024);
+        for (key, val) in entries {
+            wb.put_cf(&self.transaction, key, val);
+        }
+        self.db.write(&WriteOptions::default().sync(true), &wb)?;
+        Ok(())
+    }
+
+    pub fn delete_all<K: AsRef<[u8]>>(&self, keys: &[K]) -> Result<(), BoxError> {
+        let mut wb = WriteBatch::with_reserved_bytes(1024);
+        for key in keys {
+            wb.delete_cf(&self.transaction, key.as_ref());
+        }
+        self.db.write(WriteOptions::default_instance(), &wb)?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<(), BoxError> {
+        self.db.flush(&FlushOptions::default())?;
+        Ok(())
+    }
+}
@@ -13,10 +13,17 @@ use std::fs::OpenOptions;
 use std::io;
 use std::io::Write;
 use std::iter::FromIterator;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use self::cold::ColdStore;
+
+mod cold;
 
 pub type BoxError = Box<dyn Error>;
 
+/// Number of transactions moved to cold storage in one write.
+const COLD_STORAGE_BATCH_SIZE: usize = 10_000;
+
 #[derive(Debug)]
 pub enum CheckResult {
     Ok,
@@ -24,12 +31,41 @@ pub enum CheckResult {
     BreakAt(u64),
 }
 
+/// Inconsistencies found when rebuilding an index.
+#[derive(Debug, Default)]
+pub struct ReindexResult {
+    /// Number of source entries checked.
+    pub checked: u64,
+    /// Index entries absent for a source entry.
+    pub missing: u64,
+    /// Index entries pointing to a wrong source entry.
+    pub mismatched: u64,
+    /// Index entries without any source entry.
+    pub stale: u64,
+}
+
+impl ReindexResult {
+    pub fn is_consistent(&self) -> bool {
+        self.missing == 0 && self.mismatched == 0 && self.stale == 0
+    }
+}
+
+/// Storage options of ChainDB.
+#[derive(Debug, Clone, Default)]
+pub struct ChainDBOptions {
+    /// Compress the bottommost level, where old blocks settle, with zstd.
+    pub compress_blocks: bool,
+    /// Path to cold storage, where transactions of old blocks are moved to. Reads fall through to it.
+    pub cold_data_dir: Option<PathBuf>,
+}
+
 pub struct ChainDB {
     db: DB,
     default: ColumnFamily,
     block_header: ColumnFamily,
     transaction: ColumnFamily,
     transaction_block: ColumnFamily,
+    cold: Option<ColdStore>,
 }
 
 impl Drop for ChainDB {
@@ -40,6 +76,18 @@ impl Drop for ChainDB {
 
 impl ChainDB {
     pub fn new<P: AsRef<Path>>(db_path: P) -> ChainDB {
+        ChainDB::with_options(db_path, &ChainDBOptions::default())
+    }
+
+    pub fn with_options<P: AsRef<Path>>(db_path: P, options: &ChainDBOptions) -> ChainDB {
+        let compressed = |cf_options: ColumnFamilyOptions| {
+            if options.compress_blocks {
+                cf_options.bottommost_compression(CompressionType::ZSTD)
+            } else {
+                cf_options
+            }
+        };
+
         let db_options = DBOptions::default()
             .create_if_missing(true)
             .create_missing_column_families(true)
@@ -59,15 +107,17 @@ impl ChainDB {
             // block_hash => BlockHeader
             ColumnFamilyDescriptor::new(
                 "block-header",
-                ColumnFamilyOptions::default().max_write_buffer_number(6),
+                compressed(ColumnFamilyOptions::default().max_write_buffer_number(6)),
             ),
             // [block_hash, transaction_index: u64, transaction_hash] => Transaction
             ColumnFamilyDescriptor::new(
                 "transaction",
-                ColumnFamilyOptions::default()
-                    .prefix_extractor_fixed(32)
-                    .optimize_level_style_compaction(512 * 1024 * 1024)
-                    .max_write_buffer_number(6),
+                compressed(
+                    ColumnFamilyOptions::default()
+                        .prefix_extractor_fixed(32)
+                        .optimize_level_style_compaction(512 * 1024 * 1024)
+                        .max_write_buffer_number(6),
+                ),
             ),
             // transaction_hash => [block_hash, transaction_index: u64]
             // Key and value lengths are fixed
@@ -89,12 +139,18 @@ impl ChainDB {
 
         assert!(handles.is_empty());
 
+        let cold = options.cold_data_dir.as_ref().map(|path| {
+            info!("cold storage opened at {:?}", path);
+            ColdStore::new(path)
+        });
+
         ChainDB {
             db: db,
             default: default,
             block_header: blk,
             transaction: txn,
             transaction_block: txn_blk,
+            cold,
         }
     }
 
@@ -208,7 +264,7 @@ impl ChainDB {
         let mut upper_bound = header.hash.as_bytes().to_vec();
         upper_bound.push(0xFF); // [0xcafebabe00 .. 0xcafebabeff]
 
-        let transactions = self
+        let mut transactions = self
             .transaction
             .new_iterator(
                 &ReadOptions::default()
@@ -219,9 +275,23 @@ impl ChainDB {
                 let txn = Transaction::decode(val)?;
                 Ok(IndexedTransaction::new(H256::from_slice(&key[32 + 8..]), txn))
             })
-            .collect::<Result<Vec<_>, BoxError>>();
+            .collect::<Result<Vec<_>, BoxError>>()?;
+
+        // Read through to cold storage. An empty block is looked up twice, which is cheap.
+        if let Some(ref cold) = self.cold {
+            if transactions.is_empty() {
+                transactions = cold
+                    .range(header.hash.as_bytes(), &upper_bound)
+                    .into_iter()
+                    .map(|(key, val)| {
+                        let txn = Transaction::decode(&*val)?;
+                        Ok(IndexedTransaction::new(H256::from_slice(&key[32 + 8..]), txn))
+                    })
+                    .collect::<Result<Vec<_>, BoxError>>()?;
+            }
+        }
 
-        transactions.map(|txns| IndexedBlock::new(header, txns))
+        Ok(IndexedBlock::new(header, transactions))
     }
 
     /// handles fork
@@ -300,12 +370,19 @@ impl ChainDB {
             .get(ReadOptions::default_instance(), id.as_bytes())?
             .to_vec();
         key.extend_from_slice(id.as_bytes());
-        let txn = self
-            .transaction
-            .get(ReadOptions::default_instance(), &key)
-            .map(|raw| Transaction::decode(&*raw).unwrap())
-            .map(|txn| IndexedTransaction::new(id.clone(), txn))?;
-        Ok(txn)
+        let txn = match self.transaction.get(ReadOptions::default_instance(), &key) {
+            Ok(raw) => Transaction::decode(&*raw)?,
+            Err(e) => match self.cold {
+                Some(ref cold) if e.is_not_found() => {
+                    let raw = cold
+                        .get(&key)?
+                        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "transaction not found"))?;
+                    Transaction::decode(&*raw)?
+                }
+                _ => return Err(e.into()),
+            },
+        };
+        Ok(IndexedTransaction::new(id.clone(), txn))
     }
 
     pub fn get_block_header_by_transaction(&self, txn: &IndexedTransaction) -> Result<IndexedBlockHeader, BoxError> {
@@ -328,6 +405,9 @@ impl ChainDB {
             if e.is_not_found() {
                 wb.deletev_cf(&self.transaction, &[&*block_key, txn.hash.as_bytes()]);
                 wb.delete_cf(&self.transaction_block, txn.hash.as_bytes());
+                if let Some(ref cold) = self.cold {
+                    cold.delete_all(&[[&*block_key, txn.hash.as_bytes()].concat()])?;
+                }
                 return Ok(());
             }
         }
@@ -361,12 +441,91 @@ impl ChainDB {
                 wb.delete_cf(&self.transaction, key);
                 wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
             });
+        if let Some(ref cold) = self.cold {
+            let mut upper_bound = [0u8; 8];
+            BE::write_u64(&mut upper_bound[..], num + 1);
+            let cold_keys: Vec<_> = cold
+                .range(&lower_bound, &upper_bound)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect();
+            for key in &cold_keys {
+                info!("delete transaction {} from cold storage", hex::encode(&key[32 + 8..]));
+                wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
+            }
+            cold.delete_all(&cold_keys)?;
+        }
 
         self.db.write(WriteOptions::default_instance(), &wb)?;
 
         Ok(())
     }
 
+    /// Highest block number below which transactions have been moved to cold storage.
+    pub fn get_cold_block_number(&self) -> u64 {
+        self.default
+            .get(ReadOptions::default_instance(), b"COLD_BLOCK_NUMBER")
+            .map(|val| BE::read_u64(&*val))
+            .unwrap_or(0)
+    }
+
+    /// Move transactions of blocks below `num` to cold storage, returns number of transactions moved.
+    ///
+    /// Each batch is synced to cold storage before deleted from ChainDB, so an interrupted move only leaves
+    /// duplicates, which are moved again by the next run.
+    pub fn move_to_cold_storage(&self, num: u64) -> Result<u64, BoxError> {
+        let cold = self
+            .cold
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cold storage is not configured"))?;
+
+        let mut lower_bound = vec![0u8; 8];
+        BE::write_u64(&mut lower_bound[..], self.get_cold_block_number());
+        let mut upper_bound = [0u8; 8];
+        BE::write_u64(&mut upper_bound[..], num);
+
+        let mut moved = 0;
+        loop {
+            let entries = {
+                let ropts = ReadOptions::default()
+                    .iterate_lower_bound(&lower_bound)
+                    .iterate_upper_bound(&upper_bound[..]);
+                self.transaction
+                    .new_iterator(&ropts)
+                    .take(COLD_STORAGE_BATCH_SIZE)
+                    .map(|(key, val)| (key.to_vec(), val.to_vec()))
+                    .collect::<Vec<_>>()
+            };
+            if entries.is_empty() {
+                break;
+            }
+
+            cold.put_all(&entries)?;
+            let mut wb = WriteBatch::with_reserved_bytes(1024);
+            for (key, _) in &entries {
+                wb.delete_cf(&self.transaction, key);
+            }
+            self.db.write(WriteOptions::default_instance(), &wb)?;
+
+            moved += entries.len() as u64;
+            info!(
+                "moved {} txns to cold storage, block #{}",
+                moved,
+                BE::read_u64(&entries[entries.len() - 1].0[..8])
+            );
+            // Next key of the last moved one.
+            lower_bound = entries.into_iter().last().unwrap().0;
+            lower_bound.push(0);
+        }
+
+        if num > self.get_cold_block_number() {
+            let mut val = [0u8; 8];
+            BE::write_u64(&mut val, num);
+            self.default.put(WriteOptions::default_instance(), b"COLD_BLOCK_NUMBER", &val)?;
+        }
+        Ok(moved)
+    }
+
     pub fn delete_block(&self, block: &IndexedBlock) -> bool {
         let mut wb = WriteBatch::with_reserved_bytes(1024);
 
@@ -381,6 +540,21 @@ impl ChainDB {
                 wb.delete_cf(&self.transaction, &key);
                 wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
             });
+        if let Some(ref cold) = self.cold {
+            let mut upper_bound = header.hash.as_bytes().to_vec();
+            upper_bound.push(0xFF);
+            let cold_keys: Vec<_> = cold
+                .range(header.hash.as_bytes(), &upper_bound)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect();
+            for key in &cold_keys {
+                wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
+            }
+            if cold.delete_all(&cold_keys).is_err() {
+                return false;
+            }
+        }
 
         self.db.write(WriteOptions::default_instance(), &wb).is_ok()
     }
@@ -699,6 +873,69 @@ impl ChainDB {
         Ok(true)
     }
 
+    /// Rebuild the `transaction-block` reverse index(txid index) from the `transaction` column.
+    ///
+    /// A transaction included in forked blocks is indexed to one of them, which is not an inconsistency.
+    pub fn reindex_transactions(&self, dry_run: bool) -> Result<ReindexResult, BoxError> {
+        let mut result = ReindexResult::default();
+        let mut wb = WriteBatch::with_reserved_bytes(1024);
+
+        let has_transaction_key = |key: &[u8]| {
+            self.transaction.get(ReadOptions::default_instance(), key).is_ok() ||
+                self.cold.as_ref().map_or(false, |cold| matches!(cold.get(key), Ok(Some(_))))
+        };
+
+        // [block_hash, transaction_index: u64, transaction_hash] => Transaction
+        let hot_keys = self
+            .transaction
+            .new_iterator(ReadOptions::default_instance())
+            .keys()
+            .map(|key| key.to_vec());
+        let cold_keys = self.cold.iter().flat_map(|cold| cold.keys());
+        for key in hot_keys.chain(cold_keys) {
+            let (block_key, txn_hash) = key.split_at(32 + 8);
+            match self.transaction_block.get(ReadOptions::default_instance(), txn_hash) {
+                Ok(indexed) if &*indexed == block_key => {}
+                Ok(indexed) => {
+                    if !has_transaction_key(&[&*indexed, txn_hash].concat()) {
+                        warn!(
+                            "txn {} is indexed to a wrong block {}",
+                            hex::encode(txn_hash),
+                            hex::encode(&indexed[..32])
+                        );
+                        result.mismatched += 1;
+                        wb.put_cf(&self.transaction_block, txn_hash, block_key);
+                    }
+                }
+                Err(e) if e.is_not_found() => {
+                    warn!("txn {} is not indexed", hex::encode(txn_hash));
+                    result.missing += 1;
+                    wb.put_cf(&self.transaction_block, txn_hash, block_key);
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            result.checked += 1;
+            if result.checked % 1_000_000 == 0 {
+                info!("checked {} txns", result.checked);
+            }
+        }
+
+        // transaction_hash => [block_hash, transaction_index: u64]
+        for (txn_hash, block_key) in self.transaction_block.new_iterator(ReadOptions::default_instance()) {
+            if !has_transaction_key(&[block_key, txn_hash].concat()) {
+                warn!("txn index {} has no transaction", hex::encode(txn_hash));
+                result.stale += 1;
+                wb.delete_cf(&self.transaction_block, txn_hash);
+            }
+        }
+
+        if !dry_run && !result.is_consistent() {
+            self.db.write(WriteOptions::default_instance(), &wb)?;
+        }
+        Ok(result)
+    }
+
     pub fn get_db_property(&self, key: &str) -> u64 {
         self.db.get_int_property(key).unwrap_or_default()
     }
@@ -759,6 +996,16 @@ impl ChainDB {
         Ok(())
     }
 
+    /// Flush memtables and sync WAL, without stopping background work.
+    pub fn flush(&self) -> Result<(), BoxError> {
+        self.db.flush(&FlushOptions::default())?;
+        self.db.sync_wal()?;
+        if let Some(ref cold) = self.cold {
+            cold.flush()?;
+        }
+        Ok(())
+    }
+
     pub unsafe fn prepare_close(&self) {
         info!("flush db ... {:?}", self.db.flush(&FlushOptions::default()));
         self.db.cancel_background_work(/* wait: */ true);
@@ -0,0 +1,105 @@
+//! Canonical ids of transactions and blocks.
+//!
+//! - Transaction id, a.k.a. txid: `sha256(raw_data)`, with raw_data in protobuf encoding. Signatures are not hashed.
+//! - Block id: `sha256(block_header.raw_data)`, with the first 8 bytes replaced by the big-endian block number.
+
+use byteorder::{ByteOrder, BE};
+use crypto::sha256;
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::{block_header, transaction, Transaction};
+
+/// Protobuf encoding of transaction raw_data, the message signed by signers.
+pub fn raw_data_bytes(raw: &transaction::Raw) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(raw.encoded_len());
+    raw.encode(&mut buf).expect("buffer is large enough; qed");
+    buf
+}
+
+/// Transaction id of a raw_data.
+pub fn txid(raw: &transaction::Raw) -> H256 {
+    sha256(&raw_data_bytes(raw))
+}
+
+/// Transaction id of a transaction. Panics if there is no raw_data.
+pub fn transaction_id(txn: &Transaction) -> H256 {
+    txid(txn.raw_data.as_ref().expect("transaction has raw_data"))
+}
+
+/// Block id of a block header raw_data.
+pub fn block_id(raw: &block_header::Raw) -> H256 {
+    let mut buf = Vec::with_capacity(raw.encoded_len());
+    raw.encode(&mut buf).expect("buffer is large enough; qed");
+    let mut id = sha256(&buf);
+    BE::write_i64(&mut id[..8], raw.number);
+    id
+}
+
+/// Block number encoded in a block id.
+pub fn block_id_to_number(id: &[u8]) -> i64 {
+    BE::read_u64(&id[..8]) as i64
+}
+
+/// Leaf hash of the transaction merkle tree, sha256 of the whole transaction, signatures included.
+pub(crate) fn merkle_leaf_hash(txn: &Transaction) -> H256 {
+    let mut buf = Vec::with_capacity(txn.encoded_len());
+    txn.encode(&mut buf).expect("buffer is large enough; qed");
+    sha256(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_txid() {
+        // The TransferContract signed in keys tests.
+        let raw = hex::decode(
+            "0a0246742208f6a72da6712ec2a340d0fecbabf42d5a66080112620a2d747970652\
+             e676f6f676c65617069732e636f6d2f70726f746f636f6c2e5472616e7366657243\
+             6f6e747261637412310a15419cf784b4cc7531f1598c4c322de9afdc597fe760121\
+             541340967e825557559dc46bbf0eabe5ccf99fd134e18e80770cab0c8abf42d",
+        )
+        .unwrap();
+        let raw_data = transaction::Raw::decode(&raw[..]).unwrap();
+        assert_eq!(raw_data_bytes(&raw_data), raw);
+        assert_eq!(
+            hex::encode(txid(&raw_data)),
+            "b1c39ef1f4192d7a25f6e715dca5a3e0d6a23048da910c1fd2a39109399f311d"
+        );
+
+        let txn = Transaction {
+            raw_data: Some(raw_data.clone()),
+            signatures: vec![vec![0x27; 65]],
+            ..Default::default()
+        };
+        assert_eq!(transaction_id(&txn), txid(&raw_data));
+        assert_ne!(merkle_leaf_hash(&txn), txid(&raw_data));
+    }
+
+    #[test]
+    fn test_block_id() {
+        // Mainnet genesis block.
+        let mantra = "A new system must allow existing systems to be linked together without requiring any central \
+                      control or coordination";
+        let raw = block_header::Raw {
+            timestamp: 0,
+            merkle_root_hash: hex::decode("8ef446bf3f395af929c218014f6101ec86576c5f61b2ae3236bf3a2ab5e2fecd").unwrap(),
+            parent_hash: hex::decode("e58f33f9baf9305dc6f82b9f1934ea8f0ade2defb951258d50167028c780351f").unwrap(),
+            number: 0,
+            witness_address: mantra.as_bytes().to_vec(),
+            ..Default::default()
+        };
+        let id = block_id(&raw);
+        assert_eq!(
+            hex::encode(id),
+            "00000000000000001ebf88508a03865c71d452e25f4d51194196a1d22b6653dc"
+        );
+        assert_eq!(block_id_to_number(id.as_bytes()), 0);
+
+        let raw = block_header::Raw { number: 23_000_000, ..raw };
+        let id = block_id(&raw);
+        assert_eq!(block_id_to_number(id.as_bytes()), 23_000_000);
+        assert_eq!(hex::encode(&id[..8]), "00000000015ef3c0");
+    }
+}
@@ -2,8 +2,6 @@ use std::cmp;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 
-use byteorder::{ByteOrder, BE};
-use crypto::sha256;
 use keys::{Address, Public, Signature};
 use primitive_types::H256;
 use prost::Message;
@@ -11,6 +9,7 @@ use proto2::chain::{Block, BlockHeader, Transaction};
 use proto2::common::BlockId;
 use rayon::prelude::*;
 
+use crate::hash;
 use crate::merkle_root::MerkleTree;
 use crate::{IndexedBlockHeader, IndexedTransaction};
 
@@ -40,35 +39,26 @@ impl IndexedBlock {
         }
     }
 
-    pub fn from_header_and_txns(header: BlockHeader, txns: Vec<Transaction>) -> Self {
-        Self::from_raw(Block {
-            block_header: Some(header),
-            transactions: txns,
-        })
+    /// Assemble a new block, i.e. genesis or a produced block.
+    ///
+    /// The merkle root of transactions is computed into the header, before hashing it.
+    pub fn from_header_and_txns(mut header: BlockHeader, txns: Vec<Transaction>) -> Self {
+        let transactions = index_transactions(txns);
+        header.raw_data.as_mut().unwrap().merkle_root_hash = merkle_root(&transactions).as_bytes().to_owned();
+        Self::new(IndexedBlockHeader::from_raw(header), transactions)
     }
 
     /// Explicit conversion of the raw Block into IndexedBlock.
     ///
-    /// Hashes block header + transactions.
+    /// Hashes block header + transactions. The merkle root is kept as is, received blocks must be checked by
+    /// `verify_merkle_root_hash`.
     pub fn from_raw(block: Block) -> Self {
         let Block {
             block_header,
             transactions,
         } = block;
-        // Only compute in parallel if there is enough work to benefit it
-        let transactions: Vec<_> = if transactions.len() > 200 {
-            transactions.into_par_iter().map(IndexedTransaction::from_raw).collect()
-        } else {
-            transactions.into_iter().map(IndexedTransaction::from_raw).collect()
-        };
-        let mut block_header = block_header.unwrap();
-        if block_header.raw_data.as_ref().unwrap().merkle_root_hash.is_empty() {
-            block_header
-                .raw_data
-                .as_mut()
-                .map(|raw| raw.merkle_root_hash = merkle_root(&transactions).as_bytes().to_owned());
-        }
-        Self::new(IndexedBlockHeader::from_raw(block_header), transactions)
+        let transactions = index_transactions(transactions);
+        Self::new(IndexedBlockHeader::from_raw(block_header.unwrap()), transactions)
     }
 
     pub fn hash(&self) -> &H256 {
@@ -76,7 +66,7 @@ impl IndexedBlock {
     }
 
     pub fn number(&self) -> i64 {
-        BE::read_u64(&self.header.hash.as_bytes()[..8]) as i64
+        hash::block_id_to_number(self.header.hash.as_bytes())
     }
 
     pub fn block_id(&self) -> BlockId {
@@ -111,6 +101,14 @@ impl IndexedBlock {
         self.header.raw.raw_data.as_ref().unwrap().version
     }
 
+    /// Index of a transaction in the block, 0 if not found.
+    pub fn transaction_index(&self, txn: &IndexedTransaction) -> u32 {
+        self.transactions
+            .iter()
+            .position(|t| t.hash == txn.hash)
+            .unwrap_or_default() as u32
+    }
+
     pub fn into_raw_block(self) -> Block {
         Block {
             block_header: Some(self.header.raw),
@@ -138,8 +136,7 @@ impl IndexedBlock {
                 patch
                     .get(&txn.hash)
                     .cloned()
-                    .or_else(|| Some(get_transaction_hash_for_merkle_tree(&txn.raw)))
-                    .unwrap()
+                    .unwrap_or_else(|| hash::merkle_leaf_hash(&txn.raw))
             })
             .collect::<Vec<_>>();
         let tree = MerkleTree::from_vec(node_hashes);
@@ -147,20 +144,65 @@ impl IndexedBlock {
     }
 }
 
+fn index_transactions(transactions: Vec<Transaction>) -> Vec<IndexedTransaction> {
+    // Only compute in parallel if there is enough work to benefit it
+    if transactions.len() > 200 {
+        transactions.into_par_iter().map(IndexedTransaction::from_raw).collect()
+    } else {
+        transactions.into_iter().map(IndexedTransaction::from_raw).collect()
+    }
+}
+
 fn merkle_root(transactions: &[IndexedTransaction]) -> H256 {
     let hashes = transactions
         .iter()
-        .map(|txn| get_transaction_hash_for_merkle_tree(&txn.raw))
+        .map(|txn| hash::merkle_leaf_hash(&txn.raw))
         .collect::<Vec<_>>();
     // println!("hashes => {:#?}", hashes);
     let tree = MerkleTree::from_vec(hashes);
     *tree.root_hash()
 }
 
-fn get_transaction_hash_for_merkle_tree(transaction: &Transaction) -> H256 {
-    let mut buf = Vec::with_capacity(255);
-    // won't fail?
-    transaction.encode(&mut buf).unwrap();
-    // println!("raw => {:?}", buf);
-    sha256(&buf)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto2::chain::{block_header, transaction};
+
+    #[test]
+    fn test_verify_merkle_root_hash_with_patch() {
+        let txns = (0..3)
+            .map(|i| Transaction {
+                raw_data: Some(transaction::Raw {
+                    timestamp: i,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let header = BlockHeader {
+            raw_data: Some(block_header::Raw {
+                number: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut block = IndexedBlock::from_header_and_txns(header, txns);
+        assert!(block.verify_merkle_root_hash());
+
+        // The producer hashed the 2nd transaction into another node hash.
+        let patched_txn = block.transactions[1].hash;
+        let node_hash = H256::repeat_byte(0x42);
+        let node_hashes = block
+            .transactions
+            .iter()
+            .map(|txn| if txn.hash == patched_txn { node_hash } else { hash::merkle_leaf_hash(&txn.raw) })
+            .collect();
+        let root = *MerkleTree::from_vec(node_hashes).root_hash();
+        block.header.raw.raw_data.as_mut().unwrap().merkle_root_hash = root.as_bytes().to_vec();
+
+        assert!(!block.verify_merkle_root_hash());
+        assert!(!block.verify_merkle_root_hash_with_patch(&HashMap::new()));
+        let patch = vec![(patched_txn, node_hash)].into_iter().collect();
+        assert!(block.verify_merkle_root_hash_with_patch(&patch));
+    }
 }
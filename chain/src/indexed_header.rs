@@ -1,11 +1,10 @@
-use byteorder::{ByteOrder, BE};
-use crypto::sha256;
 use primitive_types::H256;
-use prost::Message;
 use proto2::chain::BlockHeader;
 use proto2::common::BlockId;
 use std::cmp;
 
+use crate::hash;
+
 #[derive(Clone, Debug)]
 pub struct IndexedBlockHeader {
     pub hash: H256,
@@ -24,11 +23,11 @@ impl IndexedBlockHeader {
     ///
     /// Hashes the contents of block header.
     pub fn from_raw(header: BlockHeader) -> Self {
-        IndexedBlockHeader::new(get_block_header_hash(&header), header)
+        IndexedBlockHeader::new(hash::block_id(header.raw_data.as_ref().unwrap()), header)
     }
 
     pub fn number(&self) -> i64 {
-        BE::read_u64(&self.hash.as_bytes()[..8]) as i64
+        hash::block_id_to_number(self.hash.as_bytes())
     }
 
     pub fn timestamp(&self) -> i64 {
@@ -47,7 +46,7 @@ impl IndexedBlockHeader {
     }
 
     pub fn verify(&self) -> bool {
-        get_block_header_hash(&self.raw) == self.hash
+        hash::block_id(self.raw.raw_data.as_ref().unwrap()) == self.hash
     }
 }
 
@@ -56,15 +55,3 @@ impl cmp::PartialEq for IndexedBlockHeader {
         self.hash == other.hash
     }
 }
-
-fn get_block_header_hash(header: &BlockHeader) -> H256 {
-    let raw_header = header.raw_data.as_ref().unwrap();
-    let block_numer = raw_header.number;
-
-    let mut buf: Vec<u8> = Vec::with_capacity(255);
-    raw_header.encode(&mut buf).unwrap();
-
-    let mut block_hash = sha256(&buf);
-    BE::write_i64(&mut block_hash[..8], block_numer);
-    block_hash
-}
@@ -2,12 +2,14 @@ use std::cmp;
 use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
 
-use crypto::sha256;
 use keys::{Address, Public, Signature};
 use primitive_types::H256;
-use prost::Message;
+use prost::encoding::{decode_key, decode_varint, skip_field, DecodeContext, WireType};
+use prost::DecodeError;
 use proto2::chain::Transaction;
 
+use crate::hash;
+
 #[derive(Default, Clone, Debug)]
 pub struct IndexedTransaction {
     pub hash: H256,
@@ -30,13 +32,12 @@ impl IndexedTransaction {
         Transaction: From<T>,
     {
         let transaction = Transaction::from(transaction);
-        Self::new(get_transaction_hash(&transaction), transaction)
+        Self::new(hash::transaction_id(&transaction), transaction)
     }
 
     /// Recover owner address.
     pub fn recover_owner(&self) -> Result<Vec<Address>, keys::Error> {
-        let mut buf = Vec::with_capacity(255);
-        self.raw.raw_data.as_ref().unwrap().encode(&mut buf).unwrap();
+        let buf = hash::raw_data_bytes(self.raw.raw_data.as_ref().unwrap());
 
         self.raw
             .signatures
@@ -54,7 +55,7 @@ impl IndexedTransaction {
     }
 
     pub fn verify(&self) -> bool {
-        get_transaction_hash(&self.raw) == self.hash
+        hash::transaction_id(&self.raw) == self.hash
     }
 }
 
@@ -72,8 +73,130 @@ impl Hash for IndexedTransaction {
     }
 }
 
-fn get_transaction_hash(transaction: &Transaction) -> H256 {
-    let mut buf = Vec::with_capacity(255);
-    transaction.raw_data.as_ref().unwrap().encode(&mut buf).unwrap(); // won't fail?
-    sha256(&buf)
+/// Field number of `contract` in transaction raw_data.
+const RAW_CONTRACT_FIELD: u32 = 11;
+
+/// Number of contracts in protobuf encoded transaction raw_data.
+///
+/// `contract` was a repeated field, and is decoded as a single message. Decoding merges all contracts into one,
+/// so they can only be counted in the encoded bytes.
+pub fn num_of_contracts(mut raw_data: &[u8]) -> Result<usize, DecodeError> {
+    let mut count = 0;
+    while !raw_data.is_empty() {
+        let (tag, wire_type) = decode_key(&mut raw_data)?;
+        if tag == RAW_CONTRACT_FIELD {
+            count += 1;
+        }
+        skip_field(wire_type, tag, &mut raw_data, DecodeContext::default())?;
+    }
+    Ok(count)
+}
+
+/// Field number of `transactions` in block, and of `raw_data` in transaction.
+const BLOCK_TRANSACTIONS_FIELD: u32 = 1;
+const TRANSACTION_RAW_DATA_FIELD: u32 = 1;
+
+/// Split a length delimited field off the buffer.
+fn split_length_delimited<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = decode_varint(buf)? as usize;
+    if len > buf.len() {
+        return Err(DecodeError::new("buffer underflow"));
+    }
+    let (field, remaining) = buf.split_at(len);
+    *buf = remaining;
+    Ok(field)
+}
+
+/// Number of contracts of each transaction in a protobuf encoded block, in order.
+///
+/// See `num_of_contracts`. A repeated raw_data is merged by decoding as well, so its contracts are summed.
+pub fn num_of_contracts_in_block(mut block: &[u8]) -> Result<Vec<usize>, DecodeError> {
+    let mut counts = vec![];
+    while !block.is_empty() {
+        let (tag, wire_type) = decode_key(&mut block)?;
+        if tag != BLOCK_TRANSACTIONS_FIELD || wire_type != WireType::LengthDelimited {
+            skip_field(wire_type, tag, &mut block, DecodeContext::default())?;
+            continue;
+        }
+        let mut txn = split_length_delimited(&mut block)?;
+        let mut count = 0;
+        while !txn.is_empty() {
+            let (tag, wire_type) = decode_key(&mut txn)?;
+            if tag == TRANSACTION_RAW_DATA_FIELD && wire_type == WireType::LengthDelimited {
+                count += num_of_contracts(split_length_delimited(&mut txn)?)?;
+            } else {
+                skip_field(wire_type, tag, &mut txn, DecodeContext::default())?;
+            }
+        }
+        counts.push(count);
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use proto2::chain::transaction;
+
+    #[test]
+    fn test_num_of_contracts() {
+        // The TransferContract of hash tests.
+        let raw = hex::decode(
+            "0a0246742208f6a72da6712ec2a340d0fecbabf42d5a66080112620a2d747970652\
+             e676f6f676c65617069732e636f6d2f70726f746f636f6c2e5472616e7366657243\
+             6f6e747261637412310a15419cf784b4cc7531f1598c4c322de9afdc597fe760121\
+             541340967e825557559dc46bbf0eabe5ccf99fd134e18e80770cab0c8abf42d",
+        )
+        .unwrap();
+        assert_eq!(num_of_contracts(&raw).unwrap(), 1);
+
+        let cntr = transaction::Raw::decode(&raw[..]).unwrap().contract.unwrap();
+        let mut raw_twice = raw.clone();
+        prost::encoding::message::encode(RAW_CONTRACT_FIELD, &cntr, &mut raw_twice);
+        assert_eq!(num_of_contracts(&raw_twice).unwrap(), 2);
+        // Merged on decoding.
+        assert!(transaction::Raw::decode(&raw_twice[..]).unwrap().contract.is_some());
+
+        assert_eq!(num_of_contracts(&[]).unwrap(), 0);
+        assert!(num_of_contracts(&raw[..raw.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_num_of_contracts_in_block() {
+        use proto2::chain::{Block, Transaction};
+
+        let raw = hex::decode(
+            "0a0246742208f6a72da6712ec2a340d0fecbabf42d5a66080112620a2d747970652\
+             e676f6f676c65617069732e636f6d2f70726f746f636f6c2e5472616e7366657243\
+             6f6e747261637412310a15419cf784b4cc7531f1598c4c322de9afdc597fe760121\
+             541340967e825557559dc46bbf0eabe5ccf99fd134e18e80770cab0c8abf42d",
+        )
+        .unwrap();
+        let cntr = transaction::Raw::decode(&raw[..]).unwrap().contract.unwrap();
+        let mut raw_twice = raw.clone();
+        prost::encoding::message::encode(RAW_CONTRACT_FIELD, &cntr, &mut raw_twice);
+
+        let txn = |raw: &[u8]| Transaction {
+            raw_data: Some(transaction::Raw::decode(raw).unwrap()),
+            signatures: vec![vec![0; 65]],
+            ..Default::default()
+        };
+        let block = Block {
+            transactions: vec![txn(&raw), txn(&raw)],
+            block_header: Some(Default::default()),
+        };
+        let mut buf = vec![];
+        block.encode(&mut buf).unwrap();
+        assert_eq!(num_of_contracts_in_block(&buf).unwrap(), vec![1, 1]);
+
+        // A transaction carrying 2 contracts, which would be merged by decoding.
+        let mut bad_txn = vec![];
+        prost::encoding::bytes::encode(TRANSACTION_RAW_DATA_FIELD, &raw_twice, &mut bad_txn);
+        prost::encoding::bytes::encode(BLOCK_TRANSACTIONS_FIELD, &bad_txn, &mut buf);
+        assert_eq!(num_of_contracts_in_block(&buf).unwrap(), vec![1, 1, 2]);
+        assert_eq!(Block::decode(&buf[..]).unwrap().transactions.len(), 3);
+
+        assert!(num_of_contracts_in_block(&buf[..buf.len() - 1]).is_err());
+    }
 }
@@ -3,8 +3,9 @@ pub use proto2::chain::{Block, BlockHeader, Transaction};
 
 pub use indexed_block::IndexedBlock;
 pub use indexed_header::IndexedBlockHeader;
-pub use indexed_transaction::IndexedTransaction;
+pub use indexed_transaction::{num_of_contracts, num_of_contracts_in_block, IndexedTransaction};
 
+pub mod hash;
 mod indexed_block;
 mod indexed_header;
 mod indexed_transaction;
@@ -0,0 +1,145 @@
+//! Local transaction building.
+
+use prost::Message;
+use prost_types::Any;
+use proto2::chain::{transaction, ContractType, Transaction};
+use proto2::contract as contract_pb;
+
+/// Default expiration, 60s after the timestamp, same as java-tron.
+const DEFAULT_EXPIRATION: i64 = 60_000;
+
+/// A builtin contract, which can be packed into a transaction.
+pub trait BuiltinContract: Message + Sized {
+    fn type_code(&self) -> ContractType;
+
+    /// Proto message name, used in type url of `Any`.
+    fn type_name(&self) -> &'static str;
+
+    fn to_any(&self) -> Any {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf).expect("buffer is large enough; qed");
+        Any {
+            type_url: format!("type.googleapis.com/protocol.{}", self.type_name()),
+            value: buf,
+        }
+    }
+}
+
+macro_rules! impl_builtin_contract_for {
+    ($contract_ty:ident) => {
+        impl_builtin_contract_for!($contract_ty, stringify!($contract_ty));
+    };
+    ($contract_ty:ident, $type_name:expr) => {
+        impl BuiltinContract for contract_pb::$contract_ty {
+            fn type_code(&self) -> ContractType {
+                ContractType::$contract_ty
+            }
+            fn type_name(&self) -> &'static str {
+                $type_name
+            }
+        }
+    };
+}
+
+impl_builtin_contract_for!(AccountCreateContract);
+impl_builtin_contract_for!(AccountUpdateContract);
+impl_builtin_contract_for!(SetAccountIdContract);
+impl_builtin_contract_for!(AccountPermissionUpdateContract);
+impl_builtin_contract_for!(TransferContract);
+impl_builtin_contract_for!(TransferAssetContract);
+impl_builtin_contract_for!(AssetIssueContract);
+impl_builtin_contract_for!(ParticipateAssetIssueContract);
+impl_builtin_contract_for!(UpdateAssetContract);
+impl_builtin_contract_for!(UnfreezeAssetContract);
+impl_builtin_contract_for!(WitnessCreateContract);
+impl_builtin_contract_for!(WitnessUpdateContract);
+impl_builtin_contract_for!(UpdateBrokerageContract);
+impl_builtin_contract_for!(VoteWitnessContract);
+impl_builtin_contract_for!(WithdrawBalanceContract);
+impl_builtin_contract_for!(CreateSmartContract);
+impl_builtin_contract_for!(TriggerSmartContract);
+impl_builtin_contract_for!(UpdateSettingContract);
+impl_builtin_contract_for!(UpdateEnergyLimitContract);
+// prost will rename enum variant to CamelCase.
+impl_builtin_contract_for!(ClearAbiContract, "ClearABIContract");
+impl_builtin_contract_for!(FreezeBalanceContract);
+impl_builtin_contract_for!(UnfreezeBalanceContract);
+impl_builtin_contract_for!(FreezeBalanceV2Contract);
+impl_builtin_contract_for!(UnfreezeBalanceV2Contract);
+impl_builtin_contract_for!(WithdrawExpireUnfreezeContract);
+impl_builtin_contract_for!(DelegateResourceContract);
+impl_builtin_contract_for!(UnDelegateResourceContract);
+impl_builtin_contract_for!(CancelAllUnfreezeV2Contract);
+impl_builtin_contract_for!(ProposalCreateContract);
+impl_builtin_contract_for!(ProposalApproveContract);
+impl_builtin_contract_for!(ProposalDeleteContract);
+impl_builtin_contract_for!(ExchangeCreateContract);
+impl_builtin_contract_for!(ExchangeInjectContract);
+impl_builtin_contract_for!(ExchangeWithdrawContract);
+impl_builtin_contract_for!(ExchangeTransactionContract);
+
+/// Builder of an unsigned transaction.
+pub struct TransactionBuilder {
+    raw: transaction::Raw,
+}
+
+impl TransactionBuilder {
+    pub fn new<C: BuiltinContract>(cntr: &C) -> Self {
+        let raw = transaction::Raw {
+            contract: Some(transaction::Contract {
+                r#type: cntr.type_code() as i32,
+                parameter: Some(cntr.to_any()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        TransactionBuilder { raw }
+    }
+
+    /// Refer to a recent block, so that the transaction is only valid on its chain.
+    pub fn ref_block(mut self, block_number: i64, block_hash: &[u8]) -> Self {
+        self.raw.ref_block_bytes = block_number.to_be_bytes()[6..8].to_vec();
+        self.raw.ref_block_hash = block_hash[8..16].to_vec();
+        self
+    }
+
+    /// Timestamp in ms. Expiration defaults to 60s later if not set.
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.raw.timestamp = timestamp;
+        self
+    }
+
+    /// Expiration timestamp in ms.
+    pub fn expiration(mut self, expiration: i64) -> Self {
+        self.raw.expiration = expiration;
+        self
+    }
+
+    /// Max energy fee of a smart contract transaction, in SUN.
+    pub fn fee_limit(mut self, fee_limit: i64) -> Self {
+        self.raw.fee_limit = fee_limit;
+        self
+    }
+
+    /// Memo, charged by `MemoFee` if the chain parameter is set.
+    pub fn memo<T: Into<Vec<u8>>>(mut self, memo: T) -> Self {
+        self.raw.data = memo.into();
+        self
+    }
+
+    /// Permission id for multisig, 0 for owner, 2 for the default active permission.
+    pub fn permission_id(mut self, permission_id: i32) -> Self {
+        self.raw.contract.as_mut().unwrap().permission_id = permission_id;
+        self
+    }
+
+    pub fn build(mut self) -> Transaction {
+        if self.raw.expiration == 0 && self.raw.timestamp != 0 {
+            self.raw.expiration = self.raw.timestamp + DEFAULT_EXPIRATION;
+        }
+        Transaction {
+            raw_data: Some(self.raw),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Static analysis of TVM bytecode, to catch deployments the chain will reject before fees are burnt.
+//!
+//! Opcodes are found by a linear sweep. Bytes after a terminating opcode are data until the next `JUMPDEST`, i.e.
+//! the runtime code appended to the constructor, and solc metadata is skipped, so that data bytes are not reported
+//! as opcodes. It is a heuristic, reports are warnings.
+
+use std::fmt;
+
+/// TVM upgrades of a chain, each enabling new opcodes. Same as the `AllowTvm*` chain parameters of java-tron.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TvmUpgrade {
+    /// The initial TVM.
+    Odyssey,
+    TransferTrc10,
+    Constantinople,
+    Solidity059,
+    Istanbul,
+    Freeze,
+    Vote,
+    London,
+    /// Enabled by `UnfreezeDelayDays` in java-tron, there's no parameter of its own.
+    FreezeV2,
+    ShangHai,
+    Cancun,
+    Blob,
+}
+
+impl TvmUpgrade {
+    pub const ALL: [TvmUpgrade; 12] = [
+        TvmUpgrade::Odyssey,
+        TvmUpgrade::TransferTrc10,
+        TvmUpgrade::Constantinople,
+        TvmUpgrade::Solidity059,
+        TvmUpgrade::Istanbul,
+        TvmUpgrade::Freeze,
+        TvmUpgrade::Vote,
+        TvmUpgrade::London,
+        TvmUpgrade::FreezeV2,
+        TvmUpgrade::ShangHai,
+        TvmUpgrade::Cancun,
+        TvmUpgrade::Blob,
+    ];
+
+    /// Name of the chain parameter enabling it, as listed by `chainParameters` of a node.
+    pub fn parameter_name(self) -> &'static str {
+        use TvmUpgrade::*;
+
+        match self {
+            Odyssey => "AllowTvm",
+            TransferTrc10 => "AllowTvmTransferTrc10Upgrade",
+            Constantinople => "AllowTvmConstantinopleUpgrade",
+            Solidity059 => "AllowTvmSolidity059Upgrade",
+            Istanbul => "AllowTvmIstanbul",
+            Freeze => "AllowTvmFreeze",
+            Vote => "AllowTvmVote",
+            London => "AllowTvmLondon",
+            FreezeV2 => "AllowTvmFreezeV2",
+            ShangHai => "AllowTvmShangHai",
+            Cancun => "AllowTvmCancun",
+            Blob => "AllowTvmBlob",
+        }
+    }
+
+    /// Upgrades enabled on a chain, from its chain parameters by name.
+    ///
+    /// Parameters unknown to the node are not listed, so upgrades its TVM does not implement are never enabled.
+    pub fn enabled_by<'a, I: IntoIterator<Item = (&'a str, i64)>>(parameters: I) -> Vec<TvmUpgrade> {
+        let parameters: Vec<_> = parameters.into_iter().collect();
+        TvmUpgrade::ALL
+            .iter()
+            .copied()
+            .filter(|upgrade| {
+                parameters
+                    .iter()
+                    .any(|&(name, value)| name == upgrade.parameter_name() && value != 0)
+            })
+            .collect()
+    }
+}
+
+/// Opcodes not in the initial TVM, with the upgrade enabling them.
+const GATED_OPCODES: &[(u8, &str, TvmUpgrade)] = &[
+    (0x1b, "SHL", TvmUpgrade::Constantinople),
+    (0x1c, "SHR", TvmUpgrade::Constantinople),
+    (0x1d, "SAR", TvmUpgrade::Constantinople),
+    (0x3f, "EXTCODEHASH", TvmUpgrade::Constantinople),
+    (0x46, "CHAINID", TvmUpgrade::Istanbul),
+    (0x47, "SELFBALANCE", TvmUpgrade::Istanbul),
+    (0x48, "BASEFEE", TvmUpgrade::London),
+    (0x49, "BLOBHASH", TvmUpgrade::Blob),
+    (0x4a, "BLOBBASEFEE", TvmUpgrade::Blob),
+    (0x5c, "TLOAD", TvmUpgrade::Cancun),
+    (0x5d, "TSTORE", TvmUpgrade::Cancun),
+    (0x5e, "MCOPY", TvmUpgrade::Cancun),
+    (0x5f, "PUSH0", TvmUpgrade::ShangHai),
+    (0xd0, "CALLTOKEN", TvmUpgrade::TransferTrc10),
+    (0xd1, "TOKENBALANCE", TvmUpgrade::TransferTrc10),
+    (0xd2, "CALLTOKENVALUE", TvmUpgrade::TransferTrc10),
+    (0xd3, "CALLTOKENID", TvmUpgrade::TransferTrc10),
+    (0xd4, "ISCONTRACT", TvmUpgrade::Solidity059),
+    (0xd5, "FREEZE", TvmUpgrade::Freeze),
+    (0xd6, "UNFREEZE", TvmUpgrade::Freeze),
+    (0xd7, "FREEZEEXPIRETIME", TvmUpgrade::Freeze),
+    (0xd8, "VOTEWITNESS", TvmUpgrade::Vote),
+    (0xd9, "WITHDRAWREWARD", TvmUpgrade::Vote),
+    (0xda, "FREEZEBALANCEV2", TvmUpgrade::FreezeV2),
+    (0xdb, "UNFREEZEBALANCEV2", TvmUpgrade::FreezeV2),
+    (0xdc, "CANCELALLUNFREEZEV2", TvmUpgrade::FreezeV2),
+    (0xdd, "WITHDRAWEXPIREUNFREEZE", TvmUpgrade::FreezeV2),
+    (0xde, "DELEGATERESOURCE", TvmUpgrade::FreezeV2),
+    (0xdf, "UNDELEGATERESOURCE", TvmUpgrade::FreezeV2),
+    (0xf5, "CREATE2", TvmUpgrade::Constantinople),
+];
+
+const JUMPDEST: u8 = 0x5b;
+/// STOP, JUMP, RETURN, REVERT, INVALID and SELFDESTRUCT.
+const TERMINATING_OPCODES: [u8; 6] = [0x00, 0x56, 0xf3, 0xfd, 0xfe, 0xff];
+/// Start of solc metadata, a CBOR map keyed by `ipfs`, `bzzr0` or `bzzr1`.
+const METADATA_PREFIXES: [&[u8]; 3] = [b"\xa2\x64ipfs", b"\xa1\x65bzzr0", b"\xa2\x65bzzr1"];
+
+/// An opcode not enabled on the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedOpcode {
+    /// Offset in bytecode.
+    pub offset: usize,
+    pub opcode: u8,
+    pub name: &'static str,
+    /// The upgrade enabling it.
+    pub required: TvmUpgrade,
+}
+
+impl fmt::Display for UnsupportedOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at offset {} requires {}",
+            self.name,
+            self.offset,
+            self.required.parameter_name()
+        )
+    }
+}
+
+/// Length of solc metadata at `pos`, including its 2-byte length suffix.
+fn metadata_len(code: &[u8], pos: usize) -> Option<usize> {
+    if !METADATA_PREFIXES.iter().any(|prefix| code[pos..].starts_with(prefix)) {
+        return None;
+    }
+    // The CBOR map is followed by its length in big-endian.
+    (1..=0xff).find_map(|len| match code.get(pos + len..pos + len + 2) {
+        Some(suffix) if u16::from_be_bytes([suffix[0], suffix[1]]) as usize == len => Some(len + 2),
+        _ => None,
+    })
+}
+
+/// Opcodes of bytecode not enabled by the `enabled` upgrades, in order of offset.
+pub fn unsupported_opcodes(code: &[u8], enabled: &[TvmUpgrade]) -> Vec<UnsupportedOpcode> {
+    let mut found = vec![];
+    let mut pos = 0;
+    let mut reachable = true;
+    while pos < code.len() {
+        let opcode = code[pos];
+        if !reachable {
+            if let Some(len) = metadata_len(code, pos) {
+                pos += len;
+                continue;
+            }
+            reachable = opcode == JUMPDEST;
+            pos += 1;
+            continue;
+        }
+
+        if let Some(&(_, name, required)) = GATED_OPCODES.iter().find(|(op, _, _)| *op == opcode) {
+            if !enabled.contains(&required) {
+                found.push(UnsupportedOpcode {
+                    offset: pos,
+                    opcode,
+                    name,
+                    required,
+                });
+            }
+        }
+        reachable = !TERMINATING_OPCODES.contains(&opcode);
+        pos += match opcode {
+            // PUSH1..PUSH32
+            0x60..=0x7f => (opcode - 0x5f) as usize + 1,
+            _ => 1,
+        };
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_opcodes() {
+        // PUSH1 0x1b, SHL, CHAINID, STOP, data 0xff, JUMPDEST, PUSH0, ISCONTRACT
+        let code = hex::decode("601b1b4600ff5b5fd4").unwrap();
+        let found = unsupported_opcodes(&code, &[TvmUpgrade::Odyssey, TvmUpgrade::TransferTrc10]);
+        assert_eq!(
+            found.iter().map(|op| (op.offset, op.name)).collect::<Vec<_>>(),
+            vec![(2, "SHL"), (3, "CHAINID"), (7, "PUSH0"), (8, "ISCONTRACT")]
+        );
+
+        let enabled = &TvmUpgrade::ALL[..=TvmUpgrade::Solidity059 as usize];
+        let found = unsupported_opcodes(&code, enabled);
+        assert_eq!(found.iter().map(|op| op.name).collect::<Vec<_>>(), vec!["CHAINID", "PUSH0"]);
+        assert_eq!(found[0].to_string(), "CHAINID at offset 3 requires AllowTvmIstanbul");
+
+        assert!(unsupported_opcodes(&code, &TvmUpgrade::ALL).is_empty());
+    }
+
+    #[test]
+    fn test_enabled_by() {
+        let parameters = vec![
+            ("AllowTvm", 1),
+            ("AllowTvmTransferTrc10Upgrade", 1),
+            ("AllowTvmConstantinopleUpgrade", 0),
+            ("AllowTvmSolidity059Upgrade", 1),
+            ("EnergyFee", 140),
+        ];
+        assert_eq!(
+            TvmUpgrade::enabled_by(parameters),
+            vec![TvmUpgrade::Odyssey, TvmUpgrade::TransferTrc10, TvmUpgrade::Solidity059]
+        );
+        assert!(TvmUpgrade::enabled_by(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_skip_metadata() {
+        // INVALID, then ipfs metadata containing JUMPDEST and PUSH0 bytes.
+        let mut code = vec![0xfe];
+        let mut metadata = b"\xa2\x64ipfs\x58\x22".to_vec();
+        metadata.extend(&[0x5b, 0x5f]);
+        metadata.extend(&[0u8; 32]);
+        metadata.extend(b"\x64solc\x43\x00\x08\x06");
+        let len = metadata.len() as u16;
+        code.extend(metadata);
+        code.extend(&len.to_be_bytes());
+        assert!(unsupported_opcodes(&code, &[TvmUpgrade::Odyssey]).is_empty());
+
+        // Reachable code after metadata.
+        code.extend(&[0x5b, 0x46]);
+        assert_eq!(unsupported_opcodes(&code, &[TvmUpgrade::Odyssey]).len(), 1);
+    }
+}
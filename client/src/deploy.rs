@@ -0,0 +1,348 @@
+//! Smart contract deployment from solc output.
+
+use ethabi::token::{LenientTokenizer, Tokenizer};
+use ethabi::ParamType;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use keys::{Address, Private};
+use prost::Message;
+use proto2::common::smart_contract::abi::{Entry, EntryType, Param, StateMutabilityType};
+use proto2::common::smart_contract::Abi;
+use proto2::chain::Transaction;
+use proto2::common::SmartContract;
+use proto2::contract::CreateSmartContract;
+use serde_json::Value;
+use sha3::{Digest, Keccak256};
+
+use super::builder::TransactionBuilder;
+use super::bytecode::{unsupported_opcodes, TvmUpgrade};
+use super::rpc::Client;
+use super::{signer, Result};
+
+/// Energy of code deposit, per byte.
+const CODE_DEPOSIT_ENERGY_PER_BYTE: i64 = 200;
+/// Length of a library placeholder in hex bytecode, same as an address.
+const PLACEHOLDER_LEN: usize = 40;
+/// Max size of a transaction accepted by nodes, 500KB.
+const MAX_TRANSACTION_SIZE: usize = 500 * 1024;
+/// Upper bound of ref block, timestamps, fee_limit and a signature in an encoded transaction.
+const SIGNED_TRANSACTION_OVERHEAD: usize = 128;
+
+/// Replace library placeholders in hex bytecode with library addresses.
+///
+/// Both placeholder styles of solc are supported, `__$<hash>$__` of solc >= 0.5, where hash is the first 34 hex
+/// chars of keccak256 of the fully qualified library name(`file.sol:Lib`), and `__<name>____` of older solc.
+pub fn link_libraries(bin: &str, libraries: &[(String, Address)]) -> Result<String> {
+    let mut linked = bin.trim().trim_start_matches("0x").to_owned();
+    for (name, addr) in libraries {
+        let addr_hex = hex::encode(addr.as_tvm_bytes());
+
+        let hash = hex::encode(Keccak256::digest(name.as_bytes()));
+        let placeholder = format!("__${}$__", &hash[..34]);
+        linked = linked.replace(&placeholder, &addr_hex);
+
+        let mut legacy_placeholder = format!("__{}", name);
+        legacy_placeholder.truncate(PLACEHOLDER_LEN - 2);
+        let legacy_placeholder = format!("{:_<width$}", legacy_placeholder, width = PLACEHOLDER_LEN);
+        linked = linked.replace(&legacy_placeholder, &addr_hex);
+    }
+
+    if let Some(pos) = linked.find("__") {
+        let end = (pos + PLACEHOLDER_LEN).min(linked.len());
+        return Err(format!("unlinked library placeholder {}", &linked[pos..end]).into());
+    }
+    Ok(linked)
+}
+
+/// Parse a solc JSON ABI into the on-chain ABI.
+pub fn parse_abi(abi_json: &str) -> Result<Abi> {
+    let entries: Vec<Value> = serde_json::from_str(abi_json)?;
+
+    let parse_params = |params: &Value| -> Vec<Param> {
+        params
+            .as_array()
+            .map(|params| {
+                params
+                    .iter()
+                    .map(|param| Param {
+                        indexed: param["indexed"].as_bool().unwrap_or(false),
+                        name: param["name"].as_str().unwrap_or_default().to_owned(),
+                        r#type: param["type"].as_str().unwrap_or_default().to_owned(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            let entry_type = match entry["type"].as_str().unwrap_or("function") {
+                "constructor" => EntryType::Constructor,
+                "function" => EntryType::Function,
+                "event" => EntryType::Event,
+                "fallback" | "receive" => EntryType::Fallback,
+                other => return Err(format!("unknown ABI entry type {:?}", other)),
+            };
+            let state_mutability = match entry["stateMutability"].as_str() {
+                Some("pure") => StateMutabilityType::Pure,
+                Some("view") => StateMutabilityType::View,
+                Some("nonpayable") => StateMutabilityType::Nonpayable,
+                Some("payable") => StateMutabilityType::Payable,
+                _ => StateMutabilityType::UnknownStateMutabilityType,
+            };
+            Ok(Entry {
+                anonymous: entry["anonymous"].as_bool().unwrap_or(false),
+                constant: entry["constant"].as_bool().unwrap_or(false),
+                name: entry["name"].as_str().unwrap_or_default().to_owned(),
+                inputs: parse_params(&entry["inputs"]),
+                outputs: parse_params(&entry["outputs"]),
+                r#type: entry_type as i32,
+                payable: entry["payable"].as_bool().unwrap_or(state_mutability == StateMutabilityType::Payable),
+                state_mutability: state_mutability as i32,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Abi { entries })
+}
+
+/// ABI encode constructor arguments, given as strings.
+///
+/// Addresses can be given in base58check, hex with `41` prefix, or 20-byte hex as in Ethereum.
+pub fn encode_constructor_args(abi_json: &str, args: &[String]) -> Result<Vec<u8>> {
+    let contract = ethabi::Contract::load(abi_json.as_bytes())?;
+    let inputs = contract.constructor.as_ref().map(|c| &c.inputs[..]).unwrap_or(&[]);
+    if inputs.len() != args.len() {
+        return Err(format!("constructor requires {} arguments, {} given", inputs.len(), args.len()).into());
+    }
+
+    let tokens = inputs
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            let arg = match param.kind {
+                ParamType::Address => arg
+                    .parse::<Address>()
+                    .map(|addr| hex::encode(addr.as_tvm_bytes()))
+                    .unwrap_or_else(|_| arg.clone()),
+                _ => arg.clone(),
+            };
+            LenientTokenizer::tokenize(&param.kind, &arg)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ethabi::encode(&tokens))
+}
+
+/// Findings of `Deployment::check`.
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    /// The chain rejects or reverts the deployment.
+    pub errors: Vec<String>,
+    /// The chain might reject or revert the deployment.
+    pub warnings: Vec<String>,
+}
+
+/// A contract to be deployed.
+pub struct Deployment {
+    pub name: String,
+    pub abi: Abi,
+    /// Linked bytecode, with constructor arguments appended.
+    pub bytecode: Vec<u8>,
+    /// Size of linked bytecode, without constructor arguments.
+    pub code_size: usize,
+    pub call_value: i64,
+    /// Percentage of energy paid by callers, the rest is paid by the deployer.
+    pub consume_user_energy_percent: i64,
+    /// Max energy paid by the deployer in each call.
+    pub origin_energy_limit: i64,
+}
+
+impl Deployment {
+    /// Prepare a deployment from solc output, i.e. `--abi` and `--bin`.
+    pub fn from_solc(
+        name: &str,
+        abi_json: &str,
+        bin: &str,
+        libraries: &[(String, Address)],
+        constructor_args: &[String],
+    ) -> Result<Self> {
+        let mut bytecode = hex::decode(link_libraries(bin, libraries)?)?;
+        let code_size = bytecode.len();
+        bytecode.extend(encode_constructor_args(abi_json, constructor_args)?);
+
+        Ok(Deployment {
+            name: name.to_owned(),
+            abi: parse_abi(abi_json)?,
+            bytecode,
+            code_size,
+            call_value: 0,
+            consume_user_energy_percent: 100,
+            origin_energy_limit: 10_000_000,
+        })
+    }
+
+    /// Estimated energy of code deposit. Constructor execution is not included, so the actual usage is higher.
+    pub fn estimate_energy(&self) -> i64 {
+        self.bytecode.len() as i64 * CODE_DEPOSIT_ENERGY_PER_BYTE
+    }
+
+    /// Static checks of a deployment the chain would reject or revert, to be shown before broadcasting.
+    ///
+    /// Transaction size and call value to a non-payable constructor are errors. Opcodes not enabled by `enabled`
+    /// upgrades are warnings, since they are found by a heuristic sweep. The constructor is not executed, reverts of
+    /// its own logic are not found.
+    pub fn check(&self, enabled: &[TvmUpgrade]) -> CheckReport {
+        let mut report = CheckReport::default();
+
+        let txn = TransactionBuilder::new(&self.to_contract(Address::default())).build();
+        let txn_size = txn.encoded_len() + SIGNED_TRANSACTION_OVERHEAD;
+        if txn_size > MAX_TRANSACTION_SIZE {
+            report.errors.push(format!(
+                "transaction size {} exceeds the limit of {} bytes",
+                txn_size, MAX_TRANSACTION_SIZE
+            ));
+        }
+
+        // The default constructor of solc is non-payable.
+        let payable = self
+            .abi
+            .entries
+            .iter()
+            .find(|entry| entry.r#type == EntryType::Constructor as i32)
+            .map(|entry| entry.payable)
+            .unwrap_or(false);
+        if self.call_value > 0 && !payable {
+            report.errors.push(format!(
+                "constructor is not payable, call value {} reverts the deployment",
+                self.call_value
+            ));
+        }
+
+        if !enabled.contains(&TvmUpgrade::Odyssey) {
+            report
+                .warnings
+                .push(format!("TVM is not enabled by {}", TvmUpgrade::Odyssey.parameter_name()));
+        }
+        for opcode in unsupported_opcodes(&self.bytecode[..self.code_size], enabled) {
+            report.warnings.push(opcode.to_string());
+        }
+        report
+    }
+
+    pub fn to_contract(&self, owner: Address) -> CreateSmartContract {
+        CreateSmartContract {
+            owner_address: owner.as_bytes().to_vec(),
+            new_contract: Some(SmartContract {
+                origin_address: owner.as_bytes().to_vec(),
+                abi: Some(self.abi.clone()),
+                bytecode: self.bytecode.clone(),
+                call_value: self.call_value,
+                consume_user_energy_percent: self.consume_user_energy_percent,
+                name: self.name.clone(),
+                origin_energy_limit: self.origin_energy_limit,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl Client {
+    /// TVM upgrades enabled on the node's chain, for `Deployment::check`.
+    pub async fn tvm_upgrades(&self) -> Result<Vec<TvmUpgrade>> {
+        let parameters = self.chain_parameters().await?;
+        Ok(TvmUpgrade::enabled_by(
+            parameters.iter().map(|param| (param.name.as_str(), param.value as i64)),
+        ))
+    }
+
+    /// Build an unsigned deployment transaction, referring to the latest block.
+    pub async fn deploy_transaction(
+        &self,
+        deployment: &Deployment,
+        owner: Address,
+        fee_limit: i64,
+    ) -> Result<Transaction> {
+        let ref_block = self.latest_block().await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        Ok(TransactionBuilder::new(&deployment.to_contract(owner))
+            .ref_block(ref_block.number, &ref_block.id()?)
+            .timestamp(now)
+            .fee_limit(fee_limit)
+            .build())
+    }
+
+    /// Deploy a contract, returns the transaction id.
+    ///
+    /// The contract address can be found in the transaction receipt, after the transaction is included in a block.
+    pub async fn deploy(&self, deployment: &Deployment, private: &Private, fee_limit: i64) -> Result<String> {
+        let mut txn = self
+            .deploy_transaction(deployment, Address::from_private(private), fee_limit)
+            .await?;
+        signer::sign(&mut txn, private)?;
+        self.broadcast(&txn).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABI: &str = r#"[
+        {"inputs":[{"name":"owner","type":"address"},{"name":"supply","type":"uint256"}],
+         "stateMutability":"nonpayable","type":"constructor"},
+        {"inputs":[],"name":"totalSupply","outputs":[{"name":"","type":"uint256"}],
+         "stateMutability":"view","type":"function"}
+    ]"#;
+
+    #[test]
+    fn test_link_libraries() {
+        let addr: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let name = "contracts/Math.sol:Math".to_owned();
+        let hash = hex::encode(Keccak256::digest(name.as_bytes()));
+        let bin = format!("6060__${}$__6000", &hash[..34]);
+
+        let linked = link_libraries(&bin, &[(name, addr)]).unwrap();
+        assert_eq!(linked, format!("6060{}6000", hex::encode(addr.as_tvm_bytes())));
+
+        assert!(link_libraries(&bin, &[]).is_err());
+    }
+
+    #[test]
+    fn test_encode_constructor_args() {
+        let args = vec!["TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".to_owned(), "1000".to_owned()];
+        let encoded = encode_constructor_args(ABI, &args).unwrap();
+        assert_eq!(encoded.len(), 64);
+        assert_eq!(encoded[63], 0xe8);
+
+        assert!(encode_constructor_args(ABI, &args[..1]).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let args = vec!["TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".to_owned(), "1000".to_owned()];
+        // PUSH1 0x80, PUSH1 0x40, MSTORE, CHAINID
+        let mut deployment = Deployment::from_solc("Test", ABI, "608060405246", &[], &args).unwrap();
+        assert_eq!(deployment.code_size, 6);
+        let enabled = &TvmUpgrade::ALL[..=TvmUpgrade::Solidity059 as usize];
+        let report = deployment.check(enabled);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+        assert!(deployment.check(&TvmUpgrade::ALL).warnings.is_empty());
+
+        deployment.call_value = 1;
+        let report = deployment.check(enabled);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_abi() {
+        let abi = parse_abi(ABI).unwrap();
+        assert_eq!(abi.entries.len(), 2);
+        assert_eq!(abi.entries[0].r#type, EntryType::Constructor as i32);
+        assert_eq!(abi.entries[1].name, "totalSupply");
+        assert_eq!(abi.entries[1].state_mutability, StateMutabilityType::View as i32);
+    }
+}
@@ -0,0 +1,45 @@
+//! Tron SDK for Rust dApps, without linking the whole node.
+//!
+//! - [`TransactionBuilder`] builds transactions of builtin contracts locally
+//! - [`sign`] signs transactions with a private key, [`hash`] computes transaction and block ids
+//! - [`Sponsorship`] wraps a transaction with a fee payer's resource delegation
+//! - [`Deployment`] links, checks and deploys solc output as a smart contract
+//! - [`Client`] talks to an OpenTron node via its GraphQL API
+//!
+//! ```ignore
+//! use opentron_client::{contract, Client, TransactionBuilder};
+//!
+//! let client = Client::new("http://127.0.0.1:3000");
+//! let cntr = contract::TransferContract {
+//!     owner_address: from.as_bytes().to_vec(),
+//!     to_address: to.as_bytes().to_vec(),
+//!     amount: 1_000_000,
+//! };
+//! let ref_block = client.latest_block().await?;
+//! let mut txn = TransactionBuilder::new(&cntr)
+//!     .ref_block(ref_block.number, &ref_block.id()?)
+//!     .build();
+//! opentron_client::sign(&mut txn, &private)?;
+//! client.broadcast(&txn).await?;
+//! ```
+
+pub use chain::hash;
+pub use keys;
+pub use proto2;
+pub use proto2::contract;
+
+pub use builder::{BuiltinContract, TransactionBuilder};
+pub use deploy::{CheckReport, Deployment};
+pub use rpc::Client;
+pub use signer::{sign, transaction_id};
+pub use sponsor::{SponsoredBundle, Sponsorship};
+
+pub mod builder;
+pub mod bytecode;
+pub mod deploy;
+pub mod rpc;
+pub mod signer;
+pub mod sponsor;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T, E = Error> = std::result::Result<T, E>;
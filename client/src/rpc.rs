@@ -0,0 +1,202 @@
+//! Async client of OpenTron's GraphQL API.
+
+use prost::Message;
+use proto2::chain::Transaction;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::signer::raw_data_bytes;
+use super::Result;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfo {
+    pub code_version: String,
+    pub syncing: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockHeader {
+    /// Block hash, in hex.
+    pub id: String,
+    pub number: i64,
+    pub timestamp: String,
+    pub parent_hash: String,
+    pub witness: String,
+}
+
+impl BlockHeader {
+    /// Block hash, in bytes.
+    pub fn id(&self) -> Result<Vec<u8>> {
+        Ok(hex::decode(&self.id)?)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionInfo {
+    /// Transaction hash, in hex.
+    pub id: String,
+    #[serde(default)]
+    pub signatures: Vec<String>,
+    pub contract_return: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChainParameter {
+    pub key: i32,
+    pub name: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedTransaction {
+    pub id: String,
+    /// Hex encoded raw transaction, to be signed.
+    pub raw: String,
+}
+
+impl UnsignedTransaction {
+    pub fn into_transaction(self) -> Result<Transaction> {
+        let raw = proto2::chain::transaction::Raw::decode(&hex::decode(&self.raw)?[..])?;
+        Ok(Transaction {
+            raw_data: Some(raw),
+            ..Default::default()
+        })
+    }
+}
+
+const BLOCK_HEADER_FIELDS: &str = "id number timestamp parentHash witness";
+
+pub struct Client {
+    endpoint: String,
+    admin_token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Client {
+    /// Create a client of a node, e.g. `http://127.0.0.1:3000`.
+    pub fn new(node_url: &str) -> Self {
+        Client {
+            endpoint: format!("{}/graphql", node_url.trim_end_matches('/')),
+            admin_token: None,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Token for the admin namespace, i.e. `graphql.admin-token` of the node.
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token = Some(token.to_owned());
+        self
+    }
+
+    /// Run a raw GraphQL query, returns the `data` object.
+    pub async fn query<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        let mut req = self
+            .http
+            .post(&self.endpoint)
+            .json(&json!({ "query": query, "variables": variables }));
+        if let Some(ref token) = self.admin_token {
+            req = req.bearer_auth(token);
+        }
+        let mut resp: Value = req.send().await?.error_for_status()?.json().await?;
+
+        if let Some(errors) = resp.get("errors").and_then(Value::as_array) {
+            let messages: Vec<_> = errors
+                .iter()
+                .filter_map(|err| err.get("message").and_then(Value::as_str))
+                .collect();
+            return Err(messages.join("; ").into());
+        }
+        Ok(serde_json::from_value(resp["data"].take())?)
+    }
+
+    /// Run a query whose data has a single field, returns the field.
+    async fn query_field<T: DeserializeOwned>(&self, field: &str, query: &str, variables: Value) -> Result<T> {
+        let mut data: Value = self.query(query, variables).await?;
+        Ok(serde_json::from_value(data[field].take())?)
+    }
+
+    pub async fn node_info(&self) -> Result<NodeInfo> {
+        self.query_field("nodeInfo", "{ nodeInfo { codeVersion syncing } }", json!({}))
+            .await
+    }
+
+    /// Chain parameters and their live values, ordered by key.
+    pub async fn chain_parameters(&self) -> Result<Vec<ChainParameter>> {
+        self.query_field("chainParameters", "{ chainParameters { key name value } }", json!({}))
+            .await
+    }
+
+    pub async fn latest_block(&self) -> Result<BlockHeader> {
+        let query = format!("{{ block {{ {} }} }}", BLOCK_HEADER_FIELDS);
+        self.query_field("block", &query, json!({})).await
+    }
+
+    pub async fn block_by_number(&self, num: i64) -> Result<BlockHeader> {
+        let query = format!("query($num: Int) {{ block(num: $num) {{ {} }} }}", BLOCK_HEADER_FIELDS);
+        self.query_field("block", &query, json!({ "num": num })).await
+    }
+
+    pub async fn block_by_id(&self, id: &str) -> Result<BlockHeader> {
+        let query = format!("query($id: String) {{ block(id: $id) {{ {} }} }}", BLOCK_HEADER_FIELDS);
+        self.query_field("block", &query, json!({ "id": id })).await
+    }
+
+    pub async fn transaction(&self, id: &str) -> Result<TransactionInfo> {
+        let query = "query($id: String!) { transaction(id: $id) { id signatures contractReturn } }";
+        self.query_field("transaction", query, json!({ "id": id })).await
+    }
+
+    /// Address of an account by its on-chain name, in base58check.
+    pub async fn account_by_name(&self, name: &str) -> Result<Option<String>> {
+        let query = "query($name: String!) { accountByName(name: $name) }";
+        self.query_field("accountByName", query, json!({ "name": name })).await
+    }
+
+    /// Let the node fill ref block and expiration of a transaction.
+    pub async fn build_transaction(&self, txn: &Transaction) -> Result<UnsignedTransaction> {
+        let buf = raw_data_bytes(txn)?;
+
+        let query = "mutation($raw: String!) { buildTransaction(raw: $raw) { id raw } }";
+        self.query_field("buildTransaction", query, json!({ "raw": hex::encode(buf) }))
+            .await
+    }
+
+    /// Broadcast a signed transaction, returns the transaction id.
+    pub async fn broadcast(&self, txn: &Transaction) -> Result<String> {
+        let buf = raw_data_bytes(txn)?;
+        let signatures: Vec<_> = txn.signatures.iter().map(hex::encode).collect();
+
+        let query = concat!(
+            "mutation($raw: String!, $signatures: [String!]!) ",
+            "{ broadcast(raw: $raw, signatures: $signatures) { id } }"
+        );
+        let ret: TransactionInfo = self
+            .query_field("broadcast", query, json!({ "raw": hex::encode(buf), "signatures": signatures }))
+            .await?;
+        Ok(ret.id)
+    }
+
+    /// Broadcast a sponsored bundle, returns ids of its transactions.
+    pub async fn broadcast_bundle(&self, txns: &[Transaction]) -> Result<Vec<String>> {
+        let mut transactions = Vec::with_capacity(txns.len());
+        for txn in txns {
+            let buf = raw_data_bytes(txn)?;
+            let signatures: Vec<_> = txn.signatures.iter().map(hex::encode).collect();
+            transactions.push(json!({ "raw": hex::encode(buf), "signatures": signatures }));
+        }
+
+        let query = concat!(
+            "mutation($transactions: [SignedTransaction!]!) ",
+            "{ broadcastBundle(transactions: $transactions) { id } }"
+        );
+        let ret: Vec<TransactionInfo> = self
+            .query_field("broadcastBundle", query, json!({ "transactions": transactions }))
+            .await?;
+        Ok(ret.into_iter().map(|txn| txn.id).collect())
+    }
+}
@@ -0,0 +1,66 @@
+//! Local transaction signing.
+
+use chain::hash;
+use keys::{Address, Private};
+use primitive_types::H256;
+use proto2::chain::Transaction;
+
+use super::Result;
+
+pub(crate) fn raw_data_bytes(txn: &Transaction) -> Result<Vec<u8>> {
+    let raw = txn.raw_data.as_ref().ok_or("transaction has no raw_data")?;
+    Ok(hash::raw_data_bytes(raw))
+}
+
+/// Transaction id, i.e. sha256 of raw_data. See [`hash`](crate::hash) for more helpers.
+pub fn transaction_id(txn: &Transaction) -> H256 {
+    hash::transaction_id(txn)
+}
+
+/// Sign a transaction, appending to its existing signatures. Returns the signer address.
+pub fn sign(txn: &mut Transaction, private: &Private) -> Result<Address> {
+    let signature = private.sign(&raw_data_bytes(txn)?)?;
+    txn.signatures.push(signature.as_bytes().to_vec());
+    Ok(Address::from_private(private))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::TransactionBuilder;
+    use chain::IndexedTransaction;
+    use keys::KeyPair;
+    use proto2::contract::TransferContract;
+
+    #[test]
+    fn test_build_and_sign() {
+        let kp = KeyPair::generate();
+        let cntr = TransferContract {
+            owner_address: kp.address().as_bytes().to_vec(),
+            to_address: KeyPair::generate().address().as_bytes().to_vec(),
+            amount: 1_000_000,
+        };
+        let mut block_hash = [0u8; 32];
+        block_hash[..8].copy_from_slice(&1234_i64.to_be_bytes());
+        block_hash[8..16].copy_from_slice(&[0xab; 8]);
+
+        let mut txn = TransactionBuilder::new(&cntr)
+            .ref_block(1234, &block_hash)
+            .timestamp(1_600_000_000_000)
+            .memo("hello")
+            .build();
+        let raw = txn.raw_data.as_ref().unwrap();
+        assert_eq!(raw.ref_block_bytes, vec![0x04, 0xd2]);
+        assert_eq!(raw.ref_block_hash, vec![0xab; 8]);
+        assert_eq!(raw.expiration, 1_600_000_060_000);
+
+        let txid = transaction_id(&txn);
+        let signer = sign(&mut txn, kp.private()).unwrap();
+        assert_eq!(signer, kp.address());
+        // Signing does not change txid.
+        assert_eq!(transaction_id(&txn), txid);
+
+        let signers = IndexedTransaction::from_raw(txn).recover_owner().unwrap();
+        assert_eq!(signers, vec![kp.address()]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Fee delegation, a.k.a. sponsor mode.
+//!
+//! A fee payer, the sponsor, delegates frozen bandwidth or energy to the sender just before a transaction, and
+//! optionally reclaims it just after. The transactions are broadcast together as a bundle, which a producing node
+//! packs into the same block in order, or not at all.
+//!
+//! ```ignore
+//! let target = TransactionBuilder::new(&trigger)
+//!     .ref_block(ref_block.number, &ref_block.id()?)
+//!     .timestamp(now)
+//!     .fee_limit(10_000_000)
+//!     .build();
+//! let mut bundle = Sponsorship::new(sponsor, ResourceCode::Energy, 100_000_000).bundle(target)?;
+//! bundle.sign_sponsor(&sponsor_private)?;
+//! bundle.sign_sender(&sender_private)?;
+//! client.broadcast_bundle(&bundle.into_transactions()).await?;
+//! ```
+
+use keys::{Address, Private};
+use prost::Message;
+use proto2::chain::{transaction, Transaction};
+use proto2::common::ResourceCode;
+use proto2::contract::{DelegateResourceContract, UnDelegateResourceContract};
+
+use super::builder::BuiltinContract;
+use super::{signer, Result};
+
+/// `owner_address` is always the first field of builtin contracts.
+#[derive(Clone, PartialEq, prost::Message)]
+struct ContractOwner {
+    #[prost(bytes, tag = "1")]
+    owner_address: Vec<u8>,
+}
+
+/// Resource delegated by a sponsor, in SUN of frozen balance.
+pub struct Sponsorship {
+    sponsor: Address,
+    resource: ResourceCode,
+    balance: i64,
+    reclaim: bool,
+}
+
+impl Sponsorship {
+    pub fn new(sponsor: Address, resource: ResourceCode, balance: i64) -> Self {
+        Sponsorship {
+            sponsor,
+            resource,
+            balance,
+            reclaim: true,
+        }
+    }
+
+    /// Leave the resource delegated to the sender, instead of reclaiming it in the same bundle.
+    pub fn keep_delegation(mut self) -> Self {
+        self.reclaim = false;
+        self
+    }
+
+    /// Wrap a transaction of the sender. Delegation transactions share ref block, timestamp and expiration of it.
+    pub fn bundle(&self, target: Transaction) -> Result<SponsoredBundle> {
+        let raw = target.raw_data.as_ref().ok_or("transaction has no raw_data")?;
+        let param = raw
+            .contract
+            .as_ref()
+            .and_then(|cntr| cntr.parameter.as_ref())
+            .ok_or("transaction has no contract")?;
+        let sender = ContractOwner::decode(&param.value[..])?.owner_address;
+        if sender == self.sponsor.as_bytes() {
+            return Err("sponsor is the sender".into());
+        }
+
+        let wrap = |cntr: transaction::Contract| Transaction {
+            raw_data: Some(transaction::Raw {
+                ref_block_bytes: raw.ref_block_bytes.clone(),
+                ref_block_hash: raw.ref_block_hash.clone(),
+                timestamp: raw.timestamp,
+                expiration: raw.expiration,
+                contract: Some(cntr),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let delegate = DelegateResourceContract {
+            owner_address: self.sponsor.as_bytes().to_vec(),
+            resource: self.resource as i32,
+            balance: self.balance,
+            receiver_address: sender.clone(),
+            ..Default::default()
+        };
+        let undelegate = UnDelegateResourceContract {
+            owner_address: self.sponsor.as_bytes().to_vec(),
+            resource: self.resource as i32,
+            balance: self.balance,
+            receiver_address: sender,
+        };
+
+        let delegate = wrap(contract_of(&delegate));
+        let undelegate = if self.reclaim {
+            Some(wrap(contract_of(&undelegate)))
+        } else {
+            None
+        };
+        Ok(SponsoredBundle {
+            delegate,
+            target,
+            undelegate,
+        })
+    }
+}
+
+fn contract_of<C: BuiltinContract>(cntr: &C) -> transaction::Contract {
+    transaction::Contract {
+        r#type: cntr.type_code() as i32,
+        parameter: Some(cntr.to_any()),
+        ..Default::default()
+    }
+}
+
+/// A sponsored transaction with its resource delegation, to be broadcast by `Client::broadcast_bundle`.
+pub struct SponsoredBundle {
+    pub delegate: Transaction,
+    pub target: Transaction,
+    pub undelegate: Option<Transaction>,
+}
+
+impl SponsoredBundle {
+    /// Sign the delegation transactions with the sponsor's key.
+    pub fn sign_sponsor(&mut self, private: &Private) -> Result<()> {
+        signer::sign(&mut self.delegate, private)?;
+        if let Some(ref mut undelegate) = self.undelegate {
+            signer::sign(undelegate, private)?;
+        }
+        Ok(())
+    }
+
+    /// Sign the sponsored transaction with the sender's key.
+    pub fn sign_sender(&mut self, private: &Private) -> Result<()> {
+        signer::sign(&mut self.target, private)?;
+        Ok(())
+    }
+
+    /// Transactions in packing order.
+    pub fn into_transactions(self) -> Vec<Transaction> {
+        let mut txns = vec![self.delegate, self.target];
+        txns.extend(self.undelegate);
+        txns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::TransactionBuilder;
+    use crate::signer::transaction_id;
+    use keys::KeyPair;
+    use proto2::contract::TransferContract;
+
+    #[test]
+    fn test_sponsored_bundle() {
+        let sponsor = KeyPair::generate();
+        let sender = KeyPair::generate();
+        let cntr = TransferContract {
+            owner_address: sender.address().as_bytes().to_vec(),
+            to_address: sponsor.address().as_bytes().to_vec(),
+            amount: 1_000_000,
+        };
+        let target = TransactionBuilder::new(&cntr)
+            .ref_block(1234, &[0xab; 32])
+            .timestamp(1_600_000_000_000)
+            .build();
+
+        let mut bundle = Sponsorship::new(sponsor.address(), ResourceCode::Bandwidth, 10_000_000)
+            .bundle(target.clone())
+            .unwrap();
+        bundle.sign_sponsor(sponsor.private()).unwrap();
+        bundle.sign_sender(sender.private()).unwrap();
+        let txns = bundle.into_transactions();
+        assert_eq!(txns.len(), 3);
+        assert_eq!(transaction_id(&txns[1]), transaction_id(&target));
+        assert_ne!(transaction_id(&txns[0]), transaction_id(&txns[2]));
+
+        let raw = txns[0].raw_data.as_ref().unwrap();
+        assert_eq!(raw.expiration, 1_600_000_060_000);
+        let param = raw.contract.as_ref().unwrap().parameter.as_ref().unwrap();
+        let delegate = DelegateResourceContract::decode(&param.value[..]).unwrap();
+        assert_eq!(delegate.receiver_address, sender.address().as_bytes());
+        assert_eq!(delegate.balance, 10_000_000);
+
+        let bundle = Sponsorship::new(sponsor.address(), ResourceCode::Energy, 1)
+            .keep_delegation()
+            .bundle(target.clone())
+            .unwrap();
+        assert_eq!(bundle.into_transactions().len(), 2);
+
+        assert!(Sponsorship::new(sender.address(), ResourceCode::Energy, 1)
+            .bundle(target)
+            .is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Local address book, for named addresses.
+//!
+//! CLI arguments and config files reference an address by `@name` instead of copy-pasting base58 strings. The
+//! address book is a TOML file of `name = "address"` entries, editable by hand or by `opentron address-book`:
+//!
+//! ```toml
+//! treasury = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8"
+//! ```
+//!
+//! `@chain:NAME` refers to an on-chain account name, which is resolved by a node, not by the address book.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use keys::Address;
+
+/// Prefix of on-chain account names, after `@`.
+pub const ON_CHAIN_PREFIX: &str = "chain:";
+
+#[derive(Debug, Clone, Default)]
+pub struct AddressBook {
+    entries: BTreeMap<String, Address>,
+}
+
+impl AddressBook {
+    /// Load an address book, an empty one if the file does not exist.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(&path) {
+            Ok(content) => Self::load_from_str(&content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn load_from_str(content: &str) -> Result<Self, Box<dyn Error>> {
+        let raw: BTreeMap<String, String> = toml::from_str(content)?;
+        let mut book = AddressBook::default();
+        for (name, addr) in raw {
+            let addr: Address = addr
+                .parse()
+                .map_err(|_| format!("invalid address of @{}: {:?}", name, addr))?;
+            book.insert(&name, addr)?;
+        }
+        Ok(book)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let raw: BTreeMap<&str, String> = self.iter().map(|(name, addr)| (name, addr.to_string())).collect();
+        fs::write(path, toml::to_string(&raw)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Address> {
+        self.entries.get(name)
+    }
+
+    /// Add or replace an entry, returns the replaced address.
+    pub fn insert(&mut self, name: &str, addr: Address) -> Result<Option<Address>, Box<dyn Error>> {
+        if name.is_empty() ||
+            !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return Err(format!("invalid name {:?}, only letters, digits, '_', '-' and '.' are allowed", name).into());
+        }
+        Ok(self.entries.insert(name.to_owned(), addr))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Address> {
+        self.entries.remove(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Address)> {
+        self.entries.iter().map(|(name, addr)| (&**name, addr))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Resolve `@name` by the address book, or parse as an address. `@chain:NAME` is an error, it requires a node.
+    pub fn resolve(&self, input: &str) -> Result<Address, Box<dyn Error>> {
+        match alias_of(input) {
+            Some(name) if name.starts_with(ON_CHAIN_PREFIX) => {
+                Err(format!("on-chain account name {:?} requires a node to resolve", input).into())
+            }
+            Some(name) => self
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("@{} not found in address book", name).into()),
+            None => Ok(input.parse()?),
+        }
+    }
+}
+
+/// Name of an alias, i.e. `name` of `@name`.
+pub fn alias_of(input: &str) -> Option<&str> {
+    input.strip_prefix('@')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_book() {
+        let mut book = AddressBook::load_from_str(
+            r#"
+            treasury = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8"
+            "hot-wallet" = "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(book.len(), 2);
+
+        let treasury: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        assert_eq!(book.resolve("@treasury").unwrap(), treasury);
+        assert_eq!(book.resolve("TJRabPrwbZy45sbavfcjinPJC18kjpRTv8").unwrap(), treasury);
+        assert!(book.resolve("@cold-wallet").is_err());
+        assert!(book.resolve("@chain:treasury").is_err());
+        assert!(book.resolve("treasury").is_err());
+
+        assert!(book.insert("chain:treasury", treasury).is_err());
+        assert_eq!(book.insert("cold-wallet", treasury).unwrap(), None);
+        assert_eq!(
+            book.remove("hot-wallet").unwrap().to_string(),
+            "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs"
+        );
+        assert_eq!(book.iter().map(|(name, _)| name).collect::<Vec<_>>(), vec!["cold-wallet", "treasury"]);
+
+        assert!(AddressBook::load_from_str(r#"treasury = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv9""#).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+//! Effective config: environment variable overrides, range validation, and the annotated dump logged at startup.
+//!
+//! `OPENTRON_<KEY>` overrides a top-level key, and `__` separates nested keys, with `_` in key names for `-`, e.g.
+//! `OPENTRON_LOG_LEVEL=debug` or `OPENTRON_PROTOCOL__CHANNEL__MAX_ACTIVE_CONNECTIONS=8`. Values are parsed as TOML
+//! values, falling back to plain strings.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use proto2::state::ChainParameter;
+use toml::value::{Table, Value};
+
+use super::Config;
+
+const ENV_PREFIX: &str = "OPENTRON_";
+
+/// Keys never printed in the dump.
+const SECRET_KEYS: [&str; 4] = [
+    "witness.private-key",
+    "graphql.admin-token",
+    "protocol.channel.secret.private-key",
+    "indexer.shielded-viewing-keys",
+];
+
+const LOG_LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "critical"];
+
+/// Where config values come from.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    /// The config file, with environment overrides applied.
+    raw: Table,
+    /// Dotted key => environment variable.
+    env: BTreeMap<String, String>,
+}
+
+/// Key path of an environment variable, `None` if not an override.
+fn env_key_path(name: &str) -> Option<Vec<String>> {
+    if !name.starts_with(ENV_PREFIX) {
+        return None;
+    }
+    let path = name[ENV_PREFIX.len()..]
+        .split("__")
+        .map(|part| part.to_ascii_lowercase().replace('_', "-"))
+        .collect::<Vec<_>>();
+    if path.iter().any(|part| part.is_empty()) {
+        return None;
+    }
+    Some(path)
+}
+
+fn parse_env_value(value: &str) -> Value {
+    toml::from_str::<Table>(&format!("v = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .unwrap_or_else(|| Value::String(value.into()))
+}
+
+/// Apply `OPENTRON_*` variables to the parsed config file.
+pub fn apply_env_overrides<I>(raw: &mut Table, vars: I) -> Result<BTreeMap<String, String>, Box<dyn Error>>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut applied = BTreeMap::new();
+    for (name, value) in vars {
+        let path = match env_key_path(&name) {
+            Some(path) => path,
+            None => continue,
+        };
+        let (last, parents) = path.split_last().unwrap();
+        let mut table = &mut *raw;
+        for part in parents {
+            table = match table.entry(part.clone()).or_insert(Value::Table(Table::new())) {
+                Value::Table(inner) => inner,
+                _ => return Err(format!("{}: {} is not a table", name, part).into()),
+            };
+        }
+        table.insert(last.clone(), parse_env_value(&value));
+        applied.insert(path.join("."), name);
+    }
+    Ok(applied)
+}
+
+impl Config {
+    /// Deserialize from a parsed config file, with environment overrides from `vars`.
+    pub fn load_from_table<I>(mut raw: Table, vars: I) -> Result<Self, Box<dyn Error>>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let env = apply_env_overrides(&mut raw, vars)?;
+        let mut config: Config = Value::Table(raw.clone()).try_into()?;
+        config.sources = ConfigSources { raw, env };
+        Ok(config)
+    }
+
+    /// Keys in the config file or environment that are not known by any section, and ignored.
+    pub fn unknown_keys(&self) -> Vec<String> {
+        let mut keys = vec![];
+        if let Ok(Value::Table(effective)) = Value::try_from(self) {
+            collect_unknown_keys("", &self.sources.raw, &effective, &mut keys);
+        }
+        keys
+    }
+
+    /// Check value ranges, reporting all invalid keys at once.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let mut errors = vec![];
+
+        check(&mut errors, LOG_LEVELS.contains(&&*self.log_level), "log-level", &self.log_level, "unknown level");
+        for (module, level) in &self.log_filters {
+            let key = format!("log-filters.{:?}", module);
+            check(&mut errors, LOG_LEVELS.contains(&&**level), &key, level, "unknown level");
+        }
+        let log_format = &self.log_format;
+        check(&mut errors, log_format == "term" || log_format == "json", "log-format", log_format, "term or json");
+
+        let parameter = &self.chain.parameter;
+        let key = "chain.parameter.maintenance-interval";
+        check(&mut errors, parameter.maintenance_interval > 0, key, parameter.maintenance_interval, "must be positive");
+        let fee = parameter.energy_fee;
+        check(&mut errors, fee > 0, "chain.parameter.energy-fee", fee, "must be positive");
+        let duration = self.chain.proposal_expiration_duration;
+        let key = "chain.proposal-expiration-duration";
+        check(&mut errors, duration > 0, key, duration, "must be positive");
+        for (i, param_override) in self.chain.parameter_overrides.iter().enumerate() {
+            let code = param_override.parameter;
+            let known = i32::try_from(code).ok().and_then(ChainParameter::from_i32).is_some();
+            let key = format!("chain.parameter-override-file: override[{}].parameter", i);
+            check(&mut errors, known, &key, code, "unknown chain parameter");
+        }
+        for (i, patch) in self.chain.merkle_tree_patch.iter().enumerate() {
+            let is_hash = |s: &str| hex::decode(s).map(|raw| raw.len() == 32).unwrap_or(false);
+            let key = format!("chain.merkle-tree-patch[{}].txn", i);
+            check(&mut errors, is_hash(&patch.txn), &key, &patch.txn, "must be a 32-byte hex hash");
+            let key = format!("chain.merkle-tree-patch[{}].tree-node-hash", i);
+            let hash = &patch.tree_node_hash;
+            check(&mut errors, is_hash(hash), &key, hash, "must be a 32-byte hex hash");
+        }
+
+        let storage = &self.storage;
+        let interval = storage.state_checkpoint_interval;
+        let key = "storage.state-checkpoint-interval";
+        check(&mut errors, interval > 0, key, interval, "must be positive");
+
+        let protocol = &self.protocol;
+        let valid = ["none", "", "upnp", "nat-pmp"].contains(&&*protocol.nat);
+        check(&mut errors, valid, "protocol.nat", &protocol.nat, "none, upnp or nat-pmp");
+        let batch_size = protocol.channel.sync_batch_size;
+        let key = "protocol.channel.sync-batch-size";
+        check(&mut errors, batch_size > 0, key, batch_size, "must be positive");
+
+        let graphql = &self.graphql;
+        let page_size = graphql.max_page_size;
+        check(&mut errors, page_size > 0, "graphql.max-page-size", page_size, "must be positive");
+        let threads = graphql.worker_threads;
+        check(&mut errors, threads > 0, "graphql.worker-threads", threads, "must be positive");
+        let heavy = graphql.max_concurrent_heavy_queries;
+        check(&mut errors, heavy > 0, "graphql.max-concurrent-heavy-queries", heavy, "must be positive");
+
+        let execution = &self.execution;
+        let threshold = execution.slow_transaction_threshold;
+        check(&mut errors, threshold >= 0, "execution.slow-transaction-threshold", threshold, "must not be negative");
+        let limit = execution.constant_call_energy_limit;
+        check(&mut errors, limit > 0, "execution.constant-call-energy-limit", limit, "must be positive");
+        let timeout = execution.constant_call_timeout;
+        check(&mut errors, timeout > 0, "execution.constant-call-timeout", timeout, "must be positive");
+        let memory = execution.constant_call_max_memory;
+        check(&mut errors, memory > 0, "execution.constant-call-max-memory", memory, "must be positive");
+        let batch_size = execution.constant_call_max_batch_size;
+        check(&mut errors, batch_size > 0, "execution.constant-call-max-batch-size", batch_size, "must be positive");
+
+        let producer = &self.producer;
+        let ordering = &producer.transaction_ordering;
+        let valid = ordering == "fifo" || ordering == "fee-priority";
+        check(&mut errors, valid, "producer.transaction-ordering", ordering, "fifo or fee-priority");
+        let bandwidth = producer.max_block_bandwidth;
+        check(&mut errors, bandwidth > 0, "producer.max-block-bandwidth", bandwidth, "must be positive");
+        let energy = producer.max_block_energy;
+        check(&mut errors, energy >= 0, "producer.max-block-energy", energy, "must not be negative");
+        let slots = producer.max_slots_behind;
+        check(&mut errors, slots >= 0, "producer.max-slots-behind", slots, "must not be negative");
+        let ha = &producer.ha;
+        if ha.enable {
+            check(&mut errors, !ha.lease_file.is_empty(), "producer.ha.lease-file", &ha.lease_file, "required");
+            let interval = ha.heartbeat_interval;
+            check(&mut errors, interval > 0, "producer.ha.heartbeat-interval", interval, "must be positive");
+            let valid = ha.lease_timeout > ha.heartbeat_interval as i64;
+            let key = "producer.ha.lease-timeout";
+            check(&mut errors, valid, key, ha.lease_timeout, "must be longer than heartbeat-interval");
+        }
+
+        let time = &self.time;
+        let valid = time.ntp_servers.is_empty() || time.check_interval > 0;
+        check(&mut errors, valid, "time.check-interval", time.check_interval, "must be positive");
+        check(&mut errors, time.max_drift > 0, "time.max-drift", time.max_drift, "must be positive");
+
+        let webhook = &self.event.webhook;
+        let valid = webhook.is_empty() || webhook.starts_with("http://") || webhook.starts_with("https://");
+        check(&mut errors, valid, "event.webhook", webhook, "must be an http(s) URL");
+
+        let rebroadcast = self.mempool.rebroadcast_after_blocks;
+        let key = "mempool.rebroadcast-after-blocks";
+        check(&mut errors, rebroadcast >= 0, key, rebroadcast, "must not be negative");
+
+        let amount = self.dev.faucet_max_amount;
+        check(&mut errors, amount > 0, "dev.faucet-max-amount", amount, "must be positive");
+
+        let metrics = &self.metrics;
+        if metrics.enable {
+            let exporters = ["prometheus", "statsd", "influx"];
+            let exporter = &metrics.exporter;
+            let valid = exporters.contains(&&**exporter);
+            check(&mut errors, valid, "metrics.exporter", exporter, "prometheus, statsd or influx");
+            let interval = metrics.push_interval;
+            check(&mut errors, interval > 0, "metrics.push-interval", interval, "must be positive");
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("invalid config:\n  {}", errors.join("\n  ")).into())
+        }
+    }
+
+    /// The effective config in TOML, noting values from defaults and environment variables. Secrets are redacted.
+    pub fn dump(&self) -> String {
+        let effective = match Value::try_from(self) {
+            Ok(Value::Table(table)) => table,
+            _ => return String::new(),
+        };
+        let mut out = String::new();
+        dump_table(&mut out, "", &effective, Some(&self.sources.raw), &self.sources.env);
+        out
+    }
+}
+
+fn check<T: fmt::Debug>(errors: &mut Vec<String>, valid: bool, key: &str, value: T, expected: &str) {
+    if !valid {
+        errors.push(format!("{} = {:?}, {}", key, value, expected));
+    }
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+fn collect_unknown_keys(prefix: &str, raw: &Table, effective: &Table, keys: &mut Vec<String>) {
+    for (key, value) in raw {
+        let path = join_key(prefix, key);
+        match (value, effective.get(key)) {
+            (_, None) => keys.push(path),
+            (Value::Table(raw), Some(Value::Table(effective))) => collect_unknown_keys(&path, raw, effective, keys),
+            _ => {}
+        }
+    }
+}
+
+fn format_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        key.to_owned()
+    } else {
+        format!("{:?}", key)
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Array(items) => format!("[{}]", items.iter().map(format_value).collect::<Vec<_>>().join(", ")),
+        Value::Table(table) => format!(
+            "{{ {} }}",
+            table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", format_key(key), format_value(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Datetime(dt) => dt.to_string(),
+    }
+}
+
+fn dump_table(out: &mut String, prefix: &str, table: &Table, raw: Option<&Table>, env: &BTreeMap<String, String>) {
+    for (key, value) in table.iter().filter(|(_, value)| !value.is_table()) {
+        let path = join_key(prefix, key);
+        let is_empty = match value {
+            Value::String(s) => s.is_empty(),
+            Value::Array(items) => items.is_empty(),
+            _ => false,
+        };
+        let formatted = if SECRET_KEYS.contains(&&*path) && !is_empty {
+            "\"<redacted>\"".to_owned()
+        } else {
+            format_value(value)
+        };
+        out.push_str(&format!("{} = {}", format_key(key), formatted));
+        if let Some(name) = env.get(&path) {
+            out.push_str(&format!("  # env {}", name));
+        } else if raw.map(|raw| !raw.contains_key(key)).unwrap_or(true) {
+            out.push_str("  # default");
+        }
+        out.push('\n');
+    }
+    for (key, value) in table {
+        if let Value::Table(inner) = value {
+            let path = join_key(prefix, key);
+            let raw_inner = raw.and_then(|raw| raw.get(key)).and_then(Value::as_table);
+            out.push_str(&format!("\n[{}]\n", path.split('.').map(format_key).collect::<Vec<_>>().join(".")));
+            dump_table(out, &path, inner, raw_inner, env);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParameterOverride;
+
+    fn load(vars: Vec<(&str, &str)>) -> Result<Config, Box<dyn Error>> {
+        let raw = toml::from_str(include_str!("../conf.toml")).unwrap();
+        Config::load_from_table(raw, vars.into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())))
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let config = load(vec![
+            ("OPENTRON_LOG_LEVEL", "debug"),
+            ("OPENTRON_GRAPHQL__ENDPOINT", "127.0.0.1:3000"),
+            ("OPENTRON_PROTOCOL__CHANNEL__MAX_ACTIVE_CONNECTIONS", "8"),
+            ("OPENTRON_INDEXER__NFT", "true"),
+            ("PATH", "/usr/bin"),
+        ])
+        .unwrap();
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.graphql.endpoint, "127.0.0.1:3000");
+        assert_eq!(config.protocol.channel.max_active_connections, 8);
+        assert!(config.indexer.nft);
+
+        // Typed as the field is.
+        assert!(load(vec![("OPENTRON_GRAPHQL__MAX_PAGE_SIZE", "many")]).is_err());
+        // Sections deny unknown fields.
+        assert!(load(vec![("OPENTRON_GRAPHQL__ENDPIONT", "127.0.0.1:3000")]).is_err());
+        assert!(load(vec![("OPENTRON_GRAPHQL", "on")]).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = load(vec![]).unwrap();
+        config.validate().unwrap();
+
+        config.log_level = "verbose".into();
+        config.graphql.max_page_size = 0;
+        config.producer.ha.enable = true;
+        config.event.webhook = "127.0.0.1:8080/tron-events".into();
+        config.chain.parameter_overrides = ParameterOverride::load_from_str(
+            r#"
+            [[override]]
+            parameter = 26
+            value = 1
+            block = 1_000
+
+            [[override]]
+            parameter = 9999
+            value = 1
+            block = 1_000
+            "#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("log-level = \"verbose\""));
+        assert!(err.contains("graphql.max-page-size = 0"));
+        assert!(err.contains("producer.ha.lease-file"));
+        assert!(err.contains("event.webhook = \"127.0.0.1:8080/tron-events\""));
+        assert!(err.contains("override[1].parameter = 9999"));
+        assert!(!err.contains("override[0]"));
+        assert!(!err.contains("metrics.exporter"));
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut config = load(vec![("OPENTRON_GRAPHQL__ENDPOINT", "127.0.0.1:3000")]).unwrap();
+        config.witness.private_key = "deadbeef".into();
+        let dump = config.dump();
+
+        assert!(dump.contains("endpoint = \"127.0.0.1:3000\"  # env OPENTRON_GRAPHQL__ENDPOINT\n"));
+        assert!(dump.contains("[protocol.channel.secret]\n"));
+        assert!(dump.contains("private-key = \"<redacted>\"\n"));
+        assert!(!dump.contains("deadbeef"));
+        assert!(dump.contains("cold-block-age = 864000\n"));
+        // commented out in conf.toml
+        assert!(dump.contains("state-rpc-endpoint = \"\"  # default\n"));
+        assert!(dump.contains("check-interval = 600\n"));
+
+        // [server] and [rocksdb] are not used by opentron.
+        assert!(!dump.contains("[server]"));
+        assert_eq!(
+            config.unknown_keys(),
+            vec![
+                "protocol.channel.passive-nodes",
+                "protocol.discovery.active-nodes",
+                "protocol.discovery.persist",
+                "rocksdb",
+                "server",
+            ]
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::fs;
 use std::path::Path;
 
 use chain::IndexedBlock;
-use keys::Address;
+use keys::{Address, KeyPair};
 use prost::Message;
 use prost_types::Any;
 use proto2::chain::{
@@ -23,8 +23,89 @@ pub struct Witness {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Alloc {
     pub address: String,
+    /// Account name, indexed if not empty.
+    #[serde(default)]
     pub name: String,
     pub balance: i64,
+    /// Frozen for bandwidth, as Stake 2.0. Not included in the genesis block, only in genesis state.
+    #[serde(rename = "frozenForBandwidth", default, skip_serializing_if = "is_zero")]
+    pub frozen_for_bandwidth: i64,
+    /// Frozen for energy, as Stake 2.0. Not included in the genesis block, only in genesis state.
+    #[serde(rename = "frozenForEnergy", default, skip_serializing_if = "is_zero")]
+    pub frozen_for_energy: i64,
+}
+
+/// Block id of the mainnet genesis block.
+const MAINNET_GENESIS_BLOCK_ID: &str = "00000000000000001ebf88508a03865c71d452e25f4d51194196a1d22b6653dc";
+
+/// Restriction of contract deployment and asset issuance, for consortium chains. Not allowed on mainnet.
+///
+/// An address is allowed if it is in the allow list, or the allow list is empty, and it is not in the deny list.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ChainPolicy {
+    /// Addresses allowed to deploy contracts, by CreateSmartContract.
+    #[serde(rename = "deployAllowList", default, skip_serializing_if = "Vec::is_empty")]
+    pub deploy_allow_list: Vec<String>,
+    #[serde(rename = "deployDenyList", default, skip_serializing_if = "Vec::is_empty")]
+    pub deploy_deny_list: Vec<String>,
+    /// Addresses allowed to issue TRC10 assets, by AssetIssueContract.
+    #[serde(rename = "issueAllowList", default, skip_serializing_if = "Vec::is_empty")]
+    pub issue_allow_list: Vec<String>,
+    #[serde(rename = "issueDenyList", default, skip_serializing_if = "Vec::is_empty")]
+    pub issue_deny_list: Vec<String>,
+}
+
+fn is_listed(list: &[String], addr: &Address) -> bool {
+    list.iter()
+        .any(|entry| entry.parse::<Address>().map(|listed| listed == *addr).unwrap_or(false))
+}
+
+impl ChainPolicy {
+    pub fn can_deploy(&self, addr: &Address) -> bool {
+        (self.deploy_allow_list.is_empty() || is_listed(&self.deploy_allow_list, addr)) &&
+            !is_listed(&self.deploy_deny_list, addr)
+    }
+
+    pub fn can_issue(&self, addr: &Address) -> bool {
+        (self.issue_allow_list.is_empty() || is_listed(&self.issue_allow_list, addr)) &&
+            !is_listed(&self.issue_deny_list, addr)
+    }
+
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        for entry in self
+            .deploy_allow_list
+            .iter()
+            .chain(&self.deploy_deny_list)
+            .chain(&self.issue_allow_list)
+            .chain(&self.issue_deny_list)
+        {
+            entry
+                .parse::<Address>()
+                .map_err(|_| format!("invalid address {:?} in policy", entry))?;
+        }
+        Ok(())
+    }
+}
+
+/// Deterministic pre-funded accounts, for devnets. Not allowed on mainnet.
+///
+/// Keys are derived by `dev_account`, so that every node and test suite knows them without a mnemonic.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DevAccounts {
+    pub count: usize,
+    /// Balance of each account, in SUN.
+    pub balance: i64,
+}
+
+/// Key of the `index`-th devnet account. The first one is the faucet.
+pub fn dev_account(index: usize) -> KeyPair {
+    KeyPair::from_seed(format!("opentron devnet account {}", index).as_bytes()).expect("valid devnet key; qed")
+}
+
+fn is_zero(val: &i64) -> bool {
+    *val == 0
 }
 
 impl Alloc {
@@ -68,16 +149,117 @@ pub struct GenesisConfig {
     creator: String,
     pub witnesses: Vec<Witness>,
     pub allocs: Vec<Alloc>,
+    /// Slot duration in ms, for private chains. Defaults to 3s of mainnet.
+    #[serde(
+        rename = "blockInterval",
+        default = "default_block_interval",
+        skip_serializing_if = "is_default_block_interval"
+    )]
+    pub block_interval: i64,
+    /// Number of active witnesses producing blocks, for private chains. Defaults to 27 of mainnet.
+    #[serde(
+        rename = "activeWitnesses",
+        default = "default_active_witnesses",
+        skip_serializing_if = "is_default_active_witnesses"
+    )]
+    pub active_witnesses: usize,
+    /// Maintenance interval in ms, overrides `maintenance-interval` of `[chain.parameter]` in config.
+    #[serde(rename = "maintenanceInterval", default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_interval: Option<i64>,
+    /// Deploy and issue policy, for private chains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ChainPolicy>,
+    /// Deterministic pre-funded accounts, for devnets. Appended to `allocs` in the genesis block.
+    #[serde(rename = "devAccounts", default, skip_serializing_if = "Option::is_none")]
+    pub dev_accounts: Option<DevAccounts>,
+}
+
+fn default_block_interval() -> i64 {
+    constants::BLOCK_PRODUCING_INTERVAL
+}
+
+fn is_default_block_interval(val: &i64) -> bool {
+    *val == constants::BLOCK_PRODUCING_INTERVAL
+}
+
+fn default_active_witnesses() -> usize {
+    constants::MAX_NUM_OF_ACTIVE_WITNESSES
+}
+
+fn is_default_active_witnesses(val: &usize) -> bool {
+    *val == constants::MAX_NUM_OF_ACTIVE_WITNESSES
 }
 
 impl GenesisConfig {
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
         let content = fs::read_to_string(path)?;
-        Ok(serde_json::from_str(&content)?)
+        Self::load_from_str(&content)
     }
 
     pub fn load_from_str(content: &str) -> Result<Self, Box<dyn Error>> {
-        Ok(serde_json::from_str(&content)?)
+        let genesis: GenesisConfig = serde_json::from_str(&content)?;
+        genesis.check()?;
+        Ok(genesis)
+    }
+
+    /// Check chain timing parameters of private chains.
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.block_interval < 1_000 {
+            return Err("blockInterval must be at least 1000ms".into());
+        }
+        if self.active_witnesses == 0 || self.active_witnesses > constants::MAX_NUM_OF_STANDBY_WITNESSES {
+            return Err(format!(
+                "activeWitnesses must be in 1..={}",
+                constants::MAX_NUM_OF_STANDBY_WITNESSES
+            )
+            .into());
+        }
+        if let Some(interval) = self.maintenance_interval {
+            if interval <= 0 || interval % self.block_interval != 0 {
+                return Err("maintenanceInterval must be a positive multiple of blockInterval".into());
+            }
+        }
+        if let Some(policy) = self.policy.as_ref() {
+            policy.check()?;
+            if self.is_mainnet()? {
+                return Err("policy is not allowed on mainnet".into());
+            }
+        }
+        if let Some(dev) = self.dev_accounts.as_ref() {
+            if dev.count == 0 || dev.balance <= 0 {
+                return Err("devAccounts must have a positive count and balance".into());
+            }
+            if self.is_mainnet()? {
+                return Err("devAccounts is not allowed on mainnet".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether this is the mainnet genesis, ignoring `devAccounts`.
+    fn is_mainnet(&self) -> Result<bool, Box<dyn Error>> {
+        let block = self.build_block(&self.allocs)?;
+        Ok(hex::encode(block.hash()) == MAINNET_GENESIS_BLOCK_ID)
+    }
+
+    /// Allocs of `devAccounts`, named `dev-<index>`.
+    pub fn dev_allocs(&self) -> Vec<Alloc> {
+        match self.dev_accounts.as_ref() {
+            Some(dev) => (0..dev.count)
+                .map(|i| Alloc {
+                    address: dev_account(i).address().to_string(),
+                    name: format!("dev-{}", i),
+                    balance: dev.balance,
+                    frozen_for_bandwidth: 0,
+                    frozen_for_energy: 0,
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn to_json_string(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
     }
 
     fn to_block_header(&self) -> BlockHeader {
@@ -96,9 +278,17 @@ impl GenesisConfig {
     }
 
     pub fn to_indexed_block(&self) -> Result<IndexedBlock, Box<dyn Error>> {
+        if self.dev_accounts.is_some() {
+            let allocs: Vec<_> = self.allocs.iter().cloned().chain(self.dev_allocs()).collect();
+            self.build_block(&allocs)
+        } else {
+            self.build_block(&self.allocs)
+        }
+    }
+
+    fn build_block(&self, allocs: &[Alloc]) -> Result<IndexedBlock, Box<dyn Error>> {
         let sender = keys::b58decode_check(&self.creator)?;
-        let transactions = self
-            .allocs
+        let transactions = allocs
             .iter()
             .map(|alloc| alloc.to_transaction(&sender))
             .collect::<Result<Vec<Transaction>, Box<dyn Error>>>()?;
@@ -144,4 +334,80 @@ mod tests {
 
         println!("block_id => {:?}", hex::encode(block.merkle_root_hash()));
     }
+
+    #[test]
+    fn test_chain_timing() {
+        let content = include_str!("../genesis.json");
+        let conf = GenesisConfig::load_from_str(&content).unwrap();
+        assert_eq!(conf.block_interval, 3_000);
+        assert_eq!(conf.active_witnesses, 27);
+        assert_eq!(conf.maintenance_interval, None);
+        // Defaults are not written back.
+        assert!(!conf.to_json_string().unwrap().contains("blockInterval"));
+
+        let mut value: serde_json::Value = serde_json::from_str(&content).unwrap();
+        value["blockInterval"] = 1_000.into();
+        value["activeWitnesses"] = 4.into();
+        value["maintenanceInterval"] = 60_000.into();
+        let conf = GenesisConfig::load_from_str(&value.to_string()).unwrap();
+        assert_eq!(conf.block_interval, 1_000);
+        assert_eq!(conf.active_witnesses, 4);
+        assert_eq!(conf.maintenance_interval, Some(60_000));
+
+        value["maintenanceInterval"] = 60_500.into();
+        assert!(GenesisConfig::load_from_str(&value.to_string()).is_err());
+        value["maintenanceInterval"] = 60_000.into();
+        value["activeWitnesses"] = 0.into();
+        assert!(GenesisConfig::load_from_str(&value.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_chain_policy() {
+        let content = include_str!("../genesis.json");
+        let mut value: serde_json::Value = serde_json::from_str(&content).unwrap();
+        value["policy"] = serde_json::json!({
+            "deployAllowList": ["THKJYuUmMKKARNf7s2VT51g5uPY6KEqnat", "TVDmPWGYxgi5DNeW8hXrzrhY8Y6zgxPNg4"],
+            "deployDenyList": ["TVDmPWGYxgi5DNeW8hXrzrhY8Y6zgxPNg4"],
+            "issueDenyList": ["THKJYuUmMKKARNf7s2VT51g5uPY6KEqnat"],
+        });
+        // mainnet
+        assert!(GenesisConfig::load_from_str(&value.to_string()).is_err());
+
+        value["timestamp"] = 1_600_000_000_000_i64.into();
+        let conf = GenesisConfig::load_from_str(&value.to_string()).unwrap();
+        let policy = conf.policy.unwrap();
+        let allowed: Address = "THKJYuUmMKKARNf7s2VT51g5uPY6KEqnat".parse().unwrap();
+        let denied: Address = "TVDmPWGYxgi5DNeW8hXrzrhY8Y6zgxPNg4".parse().unwrap();
+        let other: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        assert!(policy.can_deploy(&allowed));
+        assert!(!policy.can_deploy(&denied));
+        assert!(!policy.can_deploy(&other));
+        assert!(!policy.can_issue(&allowed));
+        assert!(policy.can_issue(&other));
+
+        value["policy"]["issueAllowList"] = serde_json::json!(["not-an-address"]);
+        assert!(GenesisConfig::load_from_str(&value.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_dev_accounts() {
+        let content = include_str!("../genesis.json");
+        let mut value: serde_json::Value = serde_json::from_str(&content).unwrap();
+        value["devAccounts"] = serde_json::json!({ "count": 3, "balance": 1_000_000_000_000_i64 });
+        // mainnet
+        assert!(GenesisConfig::load_from_str(&value.to_string()).is_err());
+
+        value["timestamp"] = 1_600_000_000_000_i64.into();
+        let conf = GenesisConfig::load_from_str(&value.to_string()).unwrap();
+        let allocs = conf.dev_allocs();
+        assert_eq!(allocs.len(), 3);
+        assert_eq!(allocs[0].address, dev_account(0).address().to_string());
+        assert_eq!(
+            conf.to_indexed_block().unwrap().transactions.len(),
+            conf.allocs.len() + 3
+        );
+
+        value["devAccounts"]["count"] = 0.into();
+        assert!(GenesisConfig::load_from_str(&value.to_string()).is_err());
+    }
 }
@@ -1,10 +1,15 @@
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+pub use address_book::AddressBook;
 pub use genesis::GenesisConfig;
 
+pub mod address_book;
+pub mod effective;
 pub mod genesis;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -56,7 +61,56 @@ pub struct ChainConfig {
     pub p2p_version: i32,
     #[serde(default = "default_proposal_expiration_duration")]
     pub proposal_expiration_duration: i64,
+    /// Chain id the node must be on to accept broadcasts, i.e. `0x2b6653dc` for mainnet. Not checked if empty.
+    #[serde(default)]
+    pub expected_chain_id: String,
     pub parameter: ChainParameterConfig,
+    /// Chain parameter override file, related to current config file. For private replays only.
+    #[serde(default)]
+    pub parameter_override_file: Option<String>,
+    /// Loaded from `parameter_override_file`.
+    #[serde(skip)]
+    pub parameter_overrides: Vec<ParameterOverride>,
+    /// Merkle tree node hashes of transactions in historical blocks whose merkle roots don't match their bodies.
+    #[serde(default)]
+    pub merkle_tree_patch: Vec<MerkleTreePatch>,
+}
+
+/// A merkle tree node hash used in place of the leaf hash of a transaction, as in the block producing it.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct MerkleTreePatch {
+    /// Transaction id, in hex.
+    pub txn: String,
+    /// Merkle tree node hash, in hex.
+    pub tree_node_hash: String,
+}
+
+/// A chain parameter forced to a value, as if a proposal was activated before the block.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ParameterOverride {
+    /// Parameter code, same as in proposals.
+    pub parameter: i64,
+    pub value: i64,
+    /// Applied before executing transactions of this block.
+    pub block: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParameterOverrideFile {
+    #[serde(rename = "override", default)]
+    overrides: Vec<ParameterOverride>,
+}
+
+impl ParameterOverride {
+    pub fn load_from_str(content: &str) -> Result<Vec<Self>, Box<dyn std::error::Error>> {
+        let file: ParameterOverrideFile = toml::from_str(content)?;
+        Ok(file.overrides)
+    }
 }
 
 fn default_proposal_expiration_duration() -> i64 {
@@ -78,6 +132,28 @@ pub struct StorageConfig {
     pub state_data_dir: String,
     #[serde(default = "default_state_cache_dir")]
     pub state_cache_dir: String,
+    /// Compress old blocks of ChainDB with zstd.
+    #[serde(default)]
+    pub compress_blocks: bool,
+    /// Path to cold storage of old block bodies, on cheaper disks. Reads fall through to it.
+    #[serde(default)]
+    pub cold_data_dir: Option<String>,
+    /// Blocks older than this number of blocks are moved to cold storage by `opentron db tier`.
+    #[serde(default = "default_cold_block_age")]
+    pub cold_block_age: u64,
+    /// Sync WAL of state-db to disk every N blocks. 1 syncs on every block, the most durable and the slowest. 0 leaves
+    /// syncing to the OS, the latest blocks may be lost on power loss, but not on process crash.
+    #[serde(default)]
+    pub state_sync_interval: u64,
+    /// Write state-db without WAL while blocks lag behind wall time by more than an hour, i.e. in initial sync.
+    ///
+    /// About 2-3x faster archive sync. Memtables are flushed every `state_checkpoint_interval` blocks and before WAL
+    /// is turned back on. A crash loses blocks after the last checkpoint, they are applied again from ChainDB.
+    #[serde(default)]
+    pub state_disable_wal_in_sync: bool,
+    /// In blocks. Larger intervals are faster, but lose more blocks on a crash.
+    #[serde(default = "default_state_checkpoint_interval")]
+    pub state_checkpoint_interval: u64,
 }
 
 fn default_data_dir() -> String {
@@ -92,6 +168,15 @@ fn default_state_cache_dir() -> String {
     "./data/cache".into()
 }
 
+fn default_cold_block_age() -> u64 {
+    // 30d
+    864_000
+}
+
+fn default_state_checkpoint_interval() -> u64 {
+    10_000
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct DiscoveryProtoConfig {
@@ -111,16 +196,54 @@ pub struct ChannelProtoConfig {
     pub max_active_connections: u32,
     #[serde(default = "default_sync_batch_size")]
     pub sync_batch_size: usize,
+    #[serde(default = "Default::default")]
+    pub secret: SecretChannelConfig,
+}
+
+/// Encrypted and authenticated channels, for private chains running across data centers.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct SecretChannelConfig {
+    /// x25519 private key in hex. Disabled if empty.
+    #[serde(default = "Default::default")]
+    pub private_key: String,
+    /// Peers that must be connected with encrypted channels.
+    #[serde(default = "Default::default")]
+    pub peers: Vec<SecretPeerConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct SecretPeerConfig {
+    /// IP, or IP and port, of the peer. Inbound connections are matched by IP.
+    pub address: String,
+    /// Pinned x25519 public key of the peer, in hex.
+    pub public_key: String,
 }
 
 fn default_sync_batch_size() -> usize {
     200
 }
 
+fn default_nat() -> String {
+    "none".into()
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct ProtocolConfig {
     pub seed_nodes: Vec<String>,
+    /// Public IP advertised to peers, for nodes behind NAT. Detected if empty. IPv4 or IPv6.
+    #[serde(default = "Default::default")]
+    pub external_ip: String,
+    /// Port mapping on the NAT gateway, "none", "upnp" or "nat-pmp".
+    #[serde(default = "default_nat")]
+    pub nat: String,
+    /// NAT-PMP gateway address. The default gateway if empty.
+    #[serde(default = "Default::default")]
+    pub nat_gateway: String,
     pub discovery: DiscoveryProtoConfig,
     pub channel: ChannelProtoConfig,
 }
@@ -131,21 +254,492 @@ pub struct ProtocolConfig {
 pub struct GraphQLConfig {
     pub enable: bool,
     pub endpoint: String,
+    /// Bearer token for the admin namespace. Admin operations are disabled if empty.
+    #[serde(default = "Default::default")]
+    pub admin_token: String,
+    /// Max number of entries in a page of list queries.
+    #[serde(default = "default_graphql_max_page_size")]
+    pub max_page_size: usize,
+    /// Number of threads executing queries, isolated from the runtime of block sync and production.
+    #[serde(default = "default_graphql_worker_threads")]
+    pub worker_threads: usize,
+    /// Max number of concurrent heavy queries, i.e. state scans. Excess queries are rejected.
+    ///
+    /// Must be positive, 0 would reject every heavy query, so it's refused by `validate`.
+    #[serde(default = "default_graphql_max_concurrent_heavy_queries")]
+    pub max_concurrent_heavy_queries: usize,
+    /// Dir of the node-local contract ABI registry, attached by admins to name functions and events. Disabled if
+    /// empty.
+    #[serde(default = "Default::default")]
+    pub abi_registry_dir: String,
+}
+
+fn default_graphql_max_page_size() -> usize {
+    // Same as the former max limit of storageRange.
+    1024
+}
+
+fn default_graphql_worker_threads() -> usize {
+    4
+}
+
+fn default_graphql_max_concurrent_heavy_queries() -> usize {
+    2
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ExecutionConfig {
+    /// Log transactions whose execution time exceeds this threshold, in ms. 0 to disable.
+    #[serde(default = "default_slow_transaction_threshold")]
+    pub slow_transaction_threshold: i64,
+    /// Energy limit of constant calls, independent of consensus energy limits.
+    #[serde(default = "default_constant_call_energy_limit")]
+    pub constant_call_energy_limit: i64,
+    /// Wall-clock limit of constant calls, in ms.
+    #[serde(default = "default_constant_call_timeout")]
+    pub constant_call_timeout: u64,
+    /// Memory limit of the top-level frame of constant calls, in bytes. Nested frames are bounded by energy.
+    #[serde(default = "default_constant_call_max_memory")]
+    pub constant_call_max_memory: usize,
+    /// Max number of cached constant call results. 0 to disable.
+    #[serde(default = "default_constant_call_cache_size")]
+    pub constant_call_cache_size: usize,
+    /// Lifetime of cached constant call results, in ms. Results are dropped on new blocks as well.
+    #[serde(default = "default_constant_call_cache_ttl")]
+    pub constant_call_cache_ttl: u64,
+    /// Max number of calls in a batch of constant calls.
+    #[serde(default = "default_constant_call_max_batch_size")]
+    pub constant_call_max_batch_size: usize,
+    /// Sweep delegations expired for more than N days from the delegation index at maintenance. 0 to disable.
+    ///
+    /// Off by default, since java-tron keeps the index as is.
+    #[serde(default)]
+    pub expired_delegation_sweep_days: u32,
+    /// Read accounts and contracts of the next block in background while the current block executes, when syncing
+    /// state. Also reports cold/warm account access stats.
+    #[serde(default)]
+    pub prefetch_state: bool,
+}
+
+fn default_slow_transaction_threshold() -> i64 {
+    500
+}
+
+fn default_constant_call_energy_limit() -> i64 {
+    100_000_000
+}
+
+fn default_constant_call_timeout() -> u64 {
+    100
+}
+
+fn default_constant_call_max_memory() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_constant_call_cache_size() -> usize {
+    1024
+}
+
+fn default_constant_call_cache_ttl() -> u64 {
+    3_000
+}
+
+fn default_constant_call_max_batch_size() -> usize {
+    500
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        ExecutionConfig {
+            slow_transaction_threshold: default_slow_transaction_threshold(),
+            constant_call_energy_limit: default_constant_call_energy_limit(),
+            constant_call_timeout: default_constant_call_timeout(),
+            constant_call_max_memory: default_constant_call_max_memory(),
+            constant_call_cache_size: default_constant_call_cache_size(),
+            constant_call_cache_ttl: default_constant_call_cache_ttl(),
+            constant_call_max_batch_size: default_constant_call_max_batch_size(),
+            expired_delegation_sweep_days: 0,
+            prefetch_state: false,
+        }
+    }
+}
+
+/// Chain events, see `manager::events`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct EventConfig {
+    /// Addresses to watch, in base58check or hex. A watch event is emitted when any of them is involved in a
+    /// transaction.
+    #[serde(default = "Default::default")]
+    pub watch_addresses: Vec<String>,
+    /// POST events as JSON to this URL, if not empty. Events are dropped if the endpoint can not keep up.
+    #[serde(default = "Default::default")]
+    pub webhook: String,
+    /// Deliver events only when their blocks are solidified, for consumers that can not handle reverted blocks.
+    #[serde(default = "Default::default")]
+    pub solidified_only: bool,
+}
+
+/// Block assembly policy, when producing blocks.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ProducerConfig {
+    /// Max number of transactions in a block. 0 for no limit.
+    #[serde(default = "Default::default")]
+    pub max_transactions_per_block: usize,
+    /// Max cumulative energy of transactions in a block, estimated by fee limits. 0 for no limit.
+    #[serde(default = "Default::default")]
+    pub max_block_energy: i64,
+    /// Max cumulative bandwidth of transactions in a block, in bytes.
+    #[serde(default = "default_max_block_bandwidth")]
+    pub max_block_bandwidth: i64,
+    /// Transaction ordering, "fifo" or "fee-priority".
+    #[serde(default = "default_transaction_ordering")]
+    pub transaction_ordering: String,
+    /// Contract types allowed in blocks, e.g. "TransferContract". Empty to allow all.
+    #[serde(default = "Default::default")]
+    pub allowed_contract_types: Vec<String>,
+    /// Contract types never packed into blocks.
+    #[serde(default = "Default::default")]
+    pub denied_contract_types: Vec<String>,
+    /// Refuse to produce when the head block is more than N slots behind the wall clock. 0 to disable.
+    #[serde(default = "Default::default")]
+    pub max_slots_behind: i64,
+    /// Record of slots signed by the witness, to refuse double signing across restarts. Empty for `signed-slots`
+    /// next to the state-db, see `Config::signed_slots_path`.
+    #[serde(default = "Default::default")]
+    pub signed_slots_file: String,
+    /// Hot-standby failover between redundant nodes of the same witness.
+    #[serde(default = "Default::default")]
+    pub ha: ProducerHaConfig,
+}
+
+/// Only the holder of a shared lease signs blocks, a standby takes over when the holder stops renewing it.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ProducerHaConfig {
+    pub enable: bool,
+    /// Lease file on storage shared by all nodes of the witness, e.g. an NFS mount.
+    pub lease_file: String,
+    /// Interval of renewing or trying to take the lease, in ms.
+    pub heartbeat_interval: u64,
+    /// Lease duration, in ms. A standby takes over this long after the holder's last renewal.
+    pub lease_timeout: i64,
+}
+
+impl Default for ProducerHaConfig {
+    fn default() -> Self {
+        ProducerHaConfig {
+            enable: false,
+            lease_file: String::new(),
+            heartbeat_interval: 1_000,
+            lease_timeout: 6_000,
+        }
+    }
+}
+
+fn default_max_block_bandwidth() -> i64 {
+    // Same as java-tron's block size limit, 2MB.
+    2_000_000
+}
+
+fn default_transaction_ordering() -> String {
+    "fifo".into()
+}
+
+impl Default for ProducerConfig {
+    fn default() -> Self {
+        ProducerConfig {
+            max_transactions_per_block: 0,
+            max_block_energy: 0,
+            max_block_bandwidth: default_max_block_bandwidth(),
+            transaction_ordering: default_transaction_ordering(),
+            allowed_contract_types: vec![],
+            denied_contract_types: vec![],
+            max_slots_behind: 0,
+            signed_slots_file: String::new(),
+            ha: ProducerHaConfig::default(),
+        }
+    }
+}
+
+/// System clock drift detection against NTP servers.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct TimeConfig {
+    /// NTP servers, as `host:port`, queried in order until one answers. Empty to disable.
+    #[serde(default = "Default::default")]
+    pub ntp_servers: Vec<String>,
+    /// Seconds between drift checks.
+    #[serde(default = "default_check_interval")]
+    pub check_interval: u64,
+    /// Max tolerated clock drift, in ms.
+    #[serde(default = "default_max_drift")]
+    pub max_drift: i64,
+}
+
+fn default_check_interval() -> u64 {
+    600
+}
+
+fn default_max_drift() -> i64 {
+    500
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        TimeConfig {
+            ntp_servers: vec![],
+            check_interval: default_check_interval(),
+            max_drift: default_max_drift(),
+        }
+    }
+}
+
+/// Pending transactions received by broadcast.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct MempoolConfig {
+    /// Rebroadcast a pending transaction to peers, when not included after N blocks since its last broadcast.
+    /// 0 to disable, new and re-injected transactions are still broadcast once.
+    pub rebroadcast_after_blocks: i64,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            rebroadcast_after_blocks: 10,
+        }
+    }
+}
+
+/// Devnet helpers. Never enable on a public node.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct DevConfig {
+    /// Expose the unauthenticated `requestFaucet` mutation, paying from the first of `devAccounts` in genesis.
+    #[serde(default)]
+    pub faucet: bool,
+    /// Max amount of a faucet request, in SUN.
+    pub faucet_max_amount: i64,
+}
+
+impl Default for DevConfig {
+    fn default() -> Self {
+        DevConfig {
+            faucet: false,
+            faucet_max_amount: 10_000 * constants::SUN_PER_TRX,
+        }
+    }
+}
+
+/// Block producer identity.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct WitnessConfig {
+    /// Private key of the witness, in hex. Not a producer if empty.
+    #[serde(default = "Default::default")]
+    pub private_key: String,
+    /// Address of the witness account, when `private_key` is the key of its witness permission, instead of the owner
+    /// key. Defaults to the address of `private_key`.
+    #[serde(default = "Default::default")]
+    pub address: String,
+}
+
+/// Optional indexes maintained during block application, for explorers.
+///
+/// Indexes are built from the block where they are enabled, earlier blocks are not covered.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct IndexerConfig {
+    /// Index TRC721/TRC1155 ownership and transfers.
+    #[serde(default = "Default::default")]
+    pub nft: bool,
+    /// Index transactions of accounts, as sender, receiver, called contract or token counterparty.
+    #[serde(default = "Default::default")]
+    pub account_transactions: bool,
+    /// Full viewing keys in hex, `ak || nk || ovk`, to scan shielded TRC20 notes for. Requires the `shielded` feature.
+    #[serde(default = "Default::default")]
+    pub shielded_viewing_keys: Vec<String>,
+}
+
+/// Rosetta API server, for exchange integrations.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct RosettaConfig {
+    pub enable: bool,
+    pub endpoint: String,
+    /// Network name in network identifiers, i.e. "mainnet", "nile".
+    pub network: String,
+}
+
+impl Default for RosettaConfig {
+    fn default() -> Self {
+        RosettaConfig {
+            enable: false,
+            endpoint: "0.0.0.0:8080".into(),
+            network: "mainnet".into(),
+        }
+    }
+}
+
+/// Metrics exporter. All exporters share the same registry.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enable: bool,
+    /// One of "prometheus", "statsd", "influx".
+    pub exporter: String,
+    /// Listen address of the Prometheus endpoint, or UDP address of the StatsD / InfluxDB (Telegraf) collector.
+    pub endpoint: String,
+    /// Prefix of metric names, or measurement name of Influx lines.
+    pub prefix: String,
+    /// Push interval in seconds, for statsd and influx.
+    pub push_interval: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enable: false,
+            exporter: "prometheus".into(),
+            endpoint: "0.0.0.0:23333".into(),
+            prefix: "opentron".into(),
+            push_interval: 10,
+        }
+    }
+}
+
+/// Light client mode, only block headers are synced.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct LightClientConfig {
+    #[serde(default = "Default::default")]
+    pub enable: bool,
+    /// A java-tron compatible HTTP API endpoint, to refresh witnesses when headers are produced by unknown ones.
+    #[serde(default = "Default::default")]
+    pub state_rpc_endpoint: String,
+}
+
+fn default_log_level() -> String {
+    "info".into()
+}
+
+fn default_log_format() -> String {
+    "term".into()
+}
+
+fn default_address_book() -> String {
+    "address-book.toml".into()
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
+    /// Default log level, one of "trace", "debug", "info", "warn", "error", "critical".
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Log to file instead of stdout, if not empty.
+    #[serde(default = "Default::default")]
+    pub log_file: String,
+    /// Log format, "term" or "json".
+    #[serde(default = "default_log_format")]
+    pub log_format: String,
+    /// Per-module log levels, module path prefix => level.
+    #[serde(default = "Default::default")]
+    pub log_filters: BTreeMap<String, String>,
     pub chain: ChainConfig,
     pub storage: StorageConfig,
     pub protocol: ProtocolConfig,
     pub graphql: GraphQLConfig,
+    #[serde(default = "Default::default")]
+    pub execution: ExecutionConfig,
+    #[serde(default = "Default::default")]
+    pub event: EventConfig,
+    #[serde(default = "Default::default")]
+    pub producer: ProducerConfig,
+    #[serde(default = "Default::default")]
+    pub witness: WitnessConfig,
+    #[serde(default = "Default::default")]
+    pub time: TimeConfig,
+    #[serde(default = "Default::default")]
+    pub mempool: MempoolConfig,
+    #[serde(default = "Default::default")]
+    pub light: LightClientConfig,
+    #[serde(default = "Default::default")]
+    pub indexer: IndexerConfig,
+    #[serde(default = "Default::default")]
+    pub rosetta: RosettaConfig,
+    #[serde(default = "Default::default")]
+    pub metrics: MetricsConfig,
+    #[serde(default = "Default::default")]
+    pub dev: DevConfig,
+    /// Address book file, relative to the config file. `@name` in `event.watch-addresses` and `witness.address` is
+    /// resolved by it.
+    #[serde(default = "default_address_book")]
+    pub address_book: String,
+    /// Config file and `OPENTRON_*` environment overrides, for `dump`.
+    #[serde(skip)]
+    pub sources: effective::ConfigSources,
 }
 
 impl Config {
+    /// Load the config file, with `OPENTRON_*` environment overrides, and validate it.
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        Ok(toml::from_str(&content)?)
+        let content = fs::read_to_string(&path)?;
+        let mut config = Config::load_from_table(toml::from_str(&content)?, env::vars())?;
+        if let Some(ref override_file) = config.chain.parameter_override_file {
+            let override_path = path.as_ref().parent().unwrap().join(override_file);
+            config.chain.parameter_overrides = ParameterOverride::load_from_str(&fs::read_to_string(override_path)?)?;
+        }
+        let book = AddressBook::load_from_file(config.address_book_path(&path))?;
+        config.resolve_aliases(&book)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn address_book_path<P: AsRef<Path>>(&self, config_path: P) -> PathBuf {
+        config_path.as_ref().parent().unwrap().join(&self.address_book)
+    }
+
+    /// `producer.signed-slots-file`, defaults to the parent directory of `storage.state-data-dir`.
+    pub fn signed_slots_path(&self) -> PathBuf {
+        if !self.producer.signed_slots_file.is_empty() {
+            return PathBuf::from(&self.producer.signed_slots_file);
+        }
+        Path::new(&self.storage.state_data_dir)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("signed-slots")
+    }
+
+    /// Replace `@name` addresses with base58check addresses.
+    pub fn resolve_aliases(&mut self, book: &AddressBook) -> Result<(), Box<dyn std::error::Error>> {
+        let addrs = self
+            .event
+            .watch_addresses
+            .iter_mut()
+            .chain(Some(&mut self.witness.address));
+        for addr in addrs {
+            if address_book::alias_of(addr).is_some() {
+                *addr = book.resolve(addr)?.to_string();
+            }
+        }
+        Ok(())
     }
 
     pub fn load_from_str(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
@@ -160,5 +754,64 @@ mod tests {
     #[test]
     fn test_load_default_mainnet_config() {
         assert!(Config::load_from_str(include_str!("../conf.toml")).is_ok());
+        Config::load_from_str(include_str!("../conf.nile.toml"))
+            .unwrap()
+            .validate()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_load_parameter_overrides() {
+        let content = r#"
+            # AllowTvmConstantinopleUpgrade
+            [[override]]
+            parameter = 26
+            value = 1
+            block = 1_000
+        "#;
+        let overrides = ParameterOverride::load_from_str(content).unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].parameter, 26);
+        assert_eq!(overrides[0].block, 1_000);
+        assert!(ParameterOverride::load_from_str("[[override]]\nparameter = 26").is_err());
+    }
+
+    #[test]
+    fn test_resolve_aliases() {
+        let mut config = Config::load_from_str(include_str!("../conf.toml")).unwrap();
+        config.event.watch_addresses = vec!["@treasury".into(), "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs".into()];
+        config.witness.address = "@producer".into();
+
+        let book = AddressBook::load_from_str(r#"treasury = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8""#).unwrap();
+        assert!(config.clone().resolve_aliases(&book).is_err());
+
+        let book = AddressBook::load_from_str(
+            r#"
+            treasury = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8"
+            producer = "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs"
+            "#,
+        )
+        .unwrap();
+        config.resolve_aliases(&book).unwrap();
+        assert_eq!(config.event.watch_addresses[0], "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8");
+        assert_eq!(config.witness.address, "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs");
+    }
+
+    #[test]
+    fn test_signed_slots_path() {
+        let mut config = Config::load_from_str(include_str!("../conf.toml")).unwrap();
+        config.storage.state_data_dir = "/var/lib/opentron/statedb".into();
+        assert_eq!(config.signed_slots_path(), Path::new("/var/lib/opentron/signed-slots"));
+
+        config.producer.signed_slots_file = "/mnt/witness/signed-slots".into();
+        assert_eq!(config.signed_slots_path(), Path::new("/mnt/witness/signed-slots"));
+    }
+
+    #[test]
+    fn test_time_config_defaults() {
+        let time: TimeConfig = toml::from_str(r#"ntp-servers = ["pool.ntp.org:123"]"#).unwrap();
+        assert_eq!(time.ntp_servers.len(), 1);
+        assert_eq!(time.check_interval, 600);
+        assert_eq!(time.max_drift, 500);
     }
 }
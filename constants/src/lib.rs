@@ -40,6 +40,18 @@ pub const DEFAULT_BROKERAGE_RATE: i32 = 20;
 /// Renamed: BLOCK_FILLED_SLOTS_NUMBER
 pub const NUM_OF_BLOCK_FILLED_SLOTS: usize = 128;
 
+/// Active witnesses missing more blocks in a maintenance epoch are reported by the missed blocks penalty rule.
+/// A mainnet epoch has about 266 slots per active witness.
+pub const MAX_NUM_OF_MISSED_BLOCKS_PER_EPOCH: i64 = 100;
+
+//* Units
+
+/// 1 TRX = 1_000_000 SUN. Renamed: TRX_PRECISION in java-tron's Parameter.ChainConstant.
+pub const SUN_PER_TRX: i64 = 1_000_000;
+
+/// 1d, in ms. Renamed: FROZEN_PERIOD in java-tron's Parameter.ChainConstant.
+pub const DAY_IN_MS: i64 = 24 * 60 * 60 * 1_000;
+
 //* Transactions
 
 /// 500KB
@@ -47,6 +59,9 @@ pub const MAX_TRANSACTION_SIZE: usize = 500 * 1024;
 
 pub const MAX_TRANSACTION_RESULT_SIZE: usize = 64;
 
+/// Only one contract per transaction, same as java-tron. `raw_data.contract` is still a repeated field on the wire.
+pub const MAX_NUM_OF_CONTRACTS_PER_TRANSACTION: usize = 1;
+
 /// Max number of votes in a `VoteWitness` is 30.
 pub const MAX_NUM_OF_VOTES: usize = 30;
 
@@ -55,9 +70,20 @@ pub const MAX_TRANSACTION_EXPIRATION: i64 = 24 * 60 * 60 * 1_000;
 
 pub const DEFAULT_ORIGIN_ENERGY_LIMIT: usize = 10_000_000;
 
+/// Min balance of a freeze, delegation or Stake 2.0 freeze, 1 TRX, from java-tron's FreezeBalanceActuator.
+pub const MIN_FROZEN_BALANCE: i64 = SUN_PER_TRX;
+
+/// Renamed: MaxFrozenTime, a node config in java-tron(`block.checkFrozenTime`), not a chain parameter.
 pub const MAX_NUM_OF_FROZEN_DAYS_FOR_RESOURCE: i64 = 3;
 pub const MIN_NUM_OF_FROZEN_DAYS_FOR_RESOURCE: i64 = 3;
 
+/// Max number of unexpired pending unfreezes of an account, in Stake 2.0.
+pub const MAX_NUM_OF_PENDING_UNFREEZES: usize = 32;
+/// Default lock period of Stake 2.0 delegation, 3d, in ms.
+pub const DEFAULT_DELEGATE_LOCK_PERIOD: i64 = 3 * 24 * 60 * 60 * 1_000;
+/// Max value of `MaxDelegateLockPeriod`, 365d, in blocks.
+pub const MAX_DELEGATE_LOCK_PERIOD: i64 = 10_512_000;
+
 /// Max number of `FronzenSupply` in AssetIssue.
 pub const MAX_NUM_OF_FROZEN_SUPPLIES_IN_ASSET_ISSUE: usize = 10;
 
@@ -67,6 +93,10 @@ pub const MIN_NUM_OF_FROZEN_DAYS_IN_ASSET_ISSUE: i64 = 1;
 /// Renamed: OneDayNetLimit, restrict both free_asset_bandwidth_limit and public_free_asset_bandwidth_limit.
 pub const MAX_FREE_BANDWIDTH_IN_ASSET_ISSUE: i64 = 57_600_000_000;
 
+/// TRC10 token ids start after this, ids not greater than it are invalid in TVM calls.
+/// Same as MIN_TOKEN_ID in java-tron's VMConstant.
+pub const MIN_TOKEN_ID: i64 = 1_000_000;
+
 // Renamed: ExchangeBalanceLimit
 pub const MAX_EXCHANGE_BALANCE: usize = 1_000_000_000_000_000;
 
@@ -94,3 +124,24 @@ pub const MAX_NUM_OF_ACTIVE_PERMISSIONS: usize = 8;
 
 // Renamed: TotalSignNum
 pub const MAX_NUM_OF_KEYS_IN_PERMISSION: usize = 5;
+
+//* Default values of fee chain parameters, in SUN, from java-tron's DynamicPropertiesStore.
+//
+// Live values are in `ChainParameter`s of state-db.
+
+pub const DEFAULT_BANDWIDTH_FEE: i64 = 10;
+pub const DEFAULT_ENERGY_FEE: i64 = 100;
+/// 9999 TRX.
+pub const DEFAULT_WITNESS_CREATE_FEE: i64 = 9999 * SUN_PER_TRX;
+/// 0.1 TRX, burnt as bandwidth fee when a new account is created.
+pub const DEFAULT_ACCOUNT_CREATE_FEE: i64 = 100_000;
+/// 100 TRX.
+pub const DEFAULT_ACCOUNT_PERMISSION_UPDATE_FEE: i64 = 100 * SUN_PER_TRX;
+/// 1024 TRX.
+pub const DEFAULT_ASSET_ISSUE_FEE: i64 = 1024 * SUN_PER_TRX;
+/// 1024 TRX.
+pub const DEFAULT_EXCHANGE_CREATE_FEE: i64 = 1024 * SUN_PER_TRX;
+/// 1 TRX.
+pub const DEFAULT_MULTISIG_FEE: i64 = SUN_PER_TRX;
+/// 1000 TRX.
+pub const DEFAULT_MAX_FEE_LIMIT: i64 = 1000 * SUN_PER_TRX;
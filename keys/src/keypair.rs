@@ -1,7 +1,9 @@
 //! A KeyPair type is for generating and saving private/public key pairs.
+use std::convert::TryFrom;
 use std::fmt;
 
 use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
 use secp256k1::{SecretKey, PublicKey};
 
 use crate::address::Address;
@@ -48,6 +50,13 @@ impl KeyPair {
         }
     }
 
+    /// Derives a deterministic KeyPair, the private key is the SHA256 of `seed`. For devnets only, never use it
+    /// for real funds.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        let private = Private::try_from(&Sha256::digest(seed)[..])?;
+        KeyPair::from_private(private)
+    }
+
     /// Generates a new random KeyPair.
     pub fn generate() -> Self {
         let mut rng = OsRng;
@@ -77,4 +86,11 @@ mod tests {
         println!("keypair =>\n{:}", key_pair);
         assert_eq!(&Public::from_private(key_pair.private()).unwrap(), key_pair.public());
     }
+
+    #[test]
+    fn test_keypair_from_seed() {
+        let key_pair = KeyPair::from_seed(b"opentron").unwrap();
+        assert_eq!(key_pair.address(), KeyPair::from_seed(b"opentron").unwrap().address());
+        assert_ne!(key_pair.address(), KeyPair::from_seed(b"opentron!").unwrap().address());
+    }
 }
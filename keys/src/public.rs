@@ -69,6 +69,20 @@ impl Public {
         Ok(Public(key))
     }
 
+    /// Public key in SEC1 format, 33-byte compressed or 65-byte uncompressed.
+    pub fn from_sec1(raw: &[u8]) -> Result<Public, Error> {
+        let pub_key = match raw.len() {
+            33 => PublicKey::parse_slice(raw, Some(PublicKeyFormat::Compressed))?,
+            65 => PublicKey::parse_slice(raw, Some(PublicKeyFormat::Full))?,
+            _ => return Err(Error::InvalidPublic),
+        };
+
+        let mut key = [0u8; 64];
+        key[..].copy_from_slice(&pub_key.serialize()[1..]);
+
+        Ok(Public(key))
+    }
+
     /// As raw public key bytes. Full format without a type prefix.
     pub fn as_bytes(&self) -> &[u8] {
         &self.0[..]
@@ -218,4 +232,18 @@ mod tests {
 
         assert_eq!(pub_key, Public::from_private(&priv_key).unwrap())
     }
+
+    #[test]
+    fn test_public_from_sec1() {
+        let priv_key = "d705fc17c82942f85848ab522e42d986279028d09d12ad881bdc0e1327031976"
+            .parse::<Private>()
+            .unwrap();
+        let pub_key = Public::from_private(&priv_key).unwrap();
+        let secret_key = SecretKey::parse_slice(priv_key.as_bytes()).unwrap();
+        let sec1_key = PublicKey::from_secret_key(&secret_key);
+
+        assert_eq!(Public::from_sec1(&sec1_key.serialize_compressed()).unwrap(), pub_key);
+        assert_eq!(Public::from_sec1(&sec1_key.serialize()).unwrap(), pub_key);
+        assert!(Public::from_sec1(pub_key.as_bytes()).is_err());
+    }
 }
@@ -0,0 +1,431 @@
+//! Node-local registry of verified contract ABIs, see `abi-registry-dir` of `[graphql]`.
+//!
+//! Operators attach ABIs of contracts they verified, like etherscan verification. The registry is outside
+//! consensus, it only names functions and events of contracts in query results. Each contract is saved as
+//! `<address>.json` in the registry dir, `{"abi": [...], "metadata": "..."}`, in the standard Solidity ABI format.
+//!
+//! Params are decoded by ethabi, including arrays and tuples.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use ::keys::Address;
+use ethabi::param_type::Reader;
+use ethabi::{ParamType, Token};
+use log::{info, warn};
+use primitive_types::U256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha3::{Digest, Keccak256};
+
+use crate::util::new_error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Function,
+    Event,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiParam {
+    pub name: String,
+    /// Canonical type, tuples expanded, i.e. `(address,uint256)[]`.
+    pub ty: String,
+    pub indexed: bool,
+}
+
+/// A function or an event of an ABI. Constructors, fallbacks and errors are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiEntry {
+    pub kind: EntryKind,
+    pub name: String,
+    pub inputs: Vec<AbiParam>,
+    pub anonymous: bool,
+}
+
+impl AbiEntry {
+    /// i.e. `transfer(address,uint256)`.
+    pub fn signature(&self) -> String {
+        let types: Vec<_> = self.inputs.iter().map(|param| &param.ty[..]).collect();
+        format!("{}({})", self.name, types.join(","))
+    }
+
+    /// Keccak256 of the signature. The first 4 bytes are the function selector, the whole hash the event topic.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&Keccak256::digest(self.signature().as_bytes()));
+        hash
+    }
+}
+
+/// A decoded argument of a call or a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedParam {
+    pub name: String,
+    pub ty: String,
+    pub value: String,
+}
+
+/// An attached ABI of a contract.
+#[derive(Debug, Clone)]
+pub struct ContractAbi {
+    /// As attached, in JSON.
+    pub abi: String,
+    pub metadata: String,
+    pub entries: Vec<AbiEntry>,
+}
+
+impl ContractAbi {
+    pub fn parse(abi: &str, metadata: &str) -> Result<Self, Box<dyn Error>> {
+        let json: Value = serde_json::from_str(abi)?;
+        let raw_entries = json.as_array().ok_or_else(|| new_error("ABI must be a JSON array"))?;
+
+        let mut entries = vec![];
+        for raw in raw_entries {
+            // `type` can be omitted for functions.
+            let kind = match raw["type"].as_str().map(|ty| ty.to_lowercase()) {
+                Some(ref ty) if ty == "event" => EntryKind::Event,
+                Some(ref ty) if ty == "function" => EntryKind::Function,
+                None => EntryKind::Function,
+                _ => continue,
+            };
+            let name = raw["name"].as_str().ok_or_else(|| new_error("ABI entry without name"))?;
+            let inputs = match raw["inputs"].as_array() {
+                Some(inputs) => inputs.iter().map(parse_param).collect::<Result<Vec<_>, _>>()?,
+                None => vec![],
+            };
+            entries.push(AbiEntry {
+                kind,
+                name: name.to_owned(),
+                inputs,
+                anonymous: raw["anonymous"].as_bool().unwrap_or(false),
+            });
+        }
+
+        Ok(ContractAbi {
+            abi: abi.to_owned(),
+            metadata: metadata.to_owned(),
+            entries,
+        })
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = &AbiEntry> {
+        self.entries.iter().filter(|entry| entry.kind == EntryKind::Function)
+    }
+
+    /// Anonymous events have no signature topic, they are never matched.
+    pub fn events(&self) -> impl Iterator<Item = &AbiEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == EntryKind::Event && !entry.anonymous)
+    }
+
+    /// Decode a call by its 4-byte selector.
+    pub fn decode_call(&self, data: &[u8]) -> Option<(&AbiEntry, Vec<DecodedParam>)> {
+        if data.len() < 4 {
+            return None;
+        }
+        let entry = self.functions().find(|entry| entry.hash()[..4] == data[..4])?;
+        let params = entry.inputs.iter().collect::<Vec<_>>();
+        Some((entry, decode_params(&params, &data[4..])))
+    }
+
+    /// Decode a log by its first topic.
+    pub fn decode_log(&self, topics: &[Vec<u8>], data: &[u8]) -> Option<(&AbiEntry, Vec<DecodedParam>)> {
+        let topic = topics.first()?;
+        let entry = self.events().find(|entry| entry.hash()[..] == topic[..])?;
+
+        let non_indexed: Vec<_> = entry.inputs.iter().filter(|param| !param.indexed).collect();
+        let mut values = decode_params(&non_indexed, data).into_iter();
+        let mut indexed_topics = topics[1..].iter();
+        let params = entry
+            .inputs
+            .iter()
+            .map(|param| {
+                if param.indexed {
+                    let value = match indexed_topics.next() {
+                        Some(topic) => decode_topic(param, topic),
+                        None => String::new(),
+                    };
+                    DecodedParam {
+                        name: param.name.clone(),
+                        ty: param.ty.clone(),
+                        value,
+                    }
+                } else {
+                    values.next().unwrap()
+                }
+            })
+            .collect();
+        Some((entry, params))
+    }
+}
+
+fn parse_param(raw: &Value) -> Result<AbiParam, Box<dyn Error>> {
+    Ok(AbiParam {
+        name: raw["name"].as_str().unwrap_or_default().to_owned(),
+        ty: canonical_type(raw)?,
+        indexed: raw["indexed"].as_bool().unwrap_or(false),
+    })
+}
+
+fn canonical_type(raw: &Value) -> Result<String, Box<dyn Error>> {
+    let ty = raw["type"].as_str().ok_or_else(|| new_error("ABI param without type"))?;
+    if ty.starts_with("tuple") {
+        let components = raw["components"]
+            .as_array()
+            .ok_or_else(|| new_error("tuple without components"))?
+            .iter()
+            .map(canonical_type)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("({}){}", components.join(","), &ty["tuple".len()..]))
+    } else {
+        // `trcToken` is a TRON alias of uint256.
+        Ok(ty.replace("trcToken", "uint256"))
+    }
+}
+
+/// Decode head-tail encoded params. Values are empty when data can not be decoded as the param types.
+fn decode_params(params: &[&AbiParam], data: &[u8]) -> Vec<DecodedParam> {
+    let values = params
+        .iter()
+        .map(|param| Reader::read(&param.ty))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|types| ethabi::decode(&types, data))
+        .map(|tokens| tokens.iter().map(render_token).collect())
+        .unwrap_or_else(|_| vec![String::new(); params.len()]);
+    params
+        .iter()
+        .zip(values)
+        .map(|(param, value)| DecodedParam {
+            name: param.name.clone(),
+            ty: param.ty.clone(),
+            value,
+        })
+        .collect()
+}
+
+/// Decode an indexed param of a log. Non-value types are indexed by their hash, rendered in hex.
+fn decode_topic(param: &AbiParam, topic: &[u8]) -> String {
+    match Reader::read(&param.ty) {
+        Ok(ParamType::String) |
+        Ok(ParamType::Bytes) |
+        Ok(ParamType::Array(_)) |
+        Ok(ParamType::FixedArray(..)) |
+        Ok(ParamType::Tuple(_)) |
+        Err(_) => hex::encode(topic),
+        Ok(ty) => ethabi::decode(&[ty], topic)
+            .ok()
+            .and_then(|tokens| tokens.first().map(render_token))
+            .unwrap_or_else(|| hex::encode(topic)),
+    }
+}
+
+/// Addresses in base58check, integers in decimal, bytes in hex, arrays in `[]` and tuples in `()`.
+fn render_token(token: &Token) -> String {
+    let join = |tokens: &[Token]| tokens.iter().map(render_token).collect::<Vec<_>>().join(",");
+    match token {
+        Token::Address(addr) => Address::from_tvm_bytes(addr.as_bytes()).to_string(),
+        Token::Bool(val) => val.to_string(),
+        Token::Uint(val) => val.to_string(),
+        Token::Int(val) => {
+            // Two's complement in 256 bits.
+            let mut word = [0u8; 32];
+            val.to_big_endian(&mut word);
+            let val = U256::from_big_endian(&word);
+            if word[0] & 0x80 != 0 {
+                format!("-{}", (!val).overflowing_add(U256::one()).0)
+            } else {
+                val.to_string()
+            }
+        }
+        Token::String(val) => val.clone(),
+        Token::Bytes(val) | Token::FixedBytes(val) => hex::encode(val),
+        Token::Array(tokens) | Token::FixedArray(tokens) => format!("[{}]", join(tokens)),
+        Token::Tuple(tokens) => format!("({})", join(tokens)),
+    }
+}
+
+/// Content of a registry file.
+#[derive(Serialize, Deserialize)]
+struct RegistryFile {
+    abi: Value,
+    #[serde(default)]
+    metadata: String,
+}
+
+/// Attached ABIs, loaded from the registry dir on startup.
+pub struct AbiRegistry {
+    /// None if disabled.
+    dir: Option<PathBuf>,
+    contracts: RwLock<HashMap<Address, Arc<ContractAbi>>>,
+}
+
+impl AbiRegistry {
+    /// Load all ABIs in `dir`, disabled if empty. Malformed files are skipped.
+    pub fn open(dir: &str) -> io::Result<Self> {
+        if dir.is_empty() {
+            return Ok(AbiRegistry {
+                dir: None,
+                contracts: RwLock::default(),
+            });
+        }
+        fs::create_dir_all(dir)?;
+
+        let mut contracts = HashMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map(|ext| ext != "json").unwrap_or(true) {
+                continue;
+            }
+            let loaded = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<Address>().ok())
+                .ok_or_else(|| new_error("file name is not an address"))
+                .and_then(|addr| {
+                    let file: RegistryFile = serde_json::from_str(&fs::read_to_string(&path)?)?;
+                    let abi = ContractAbi::parse(&file.abi.to_string(), &file.metadata)?;
+                    Ok((addr, abi))
+                });
+            match loaded {
+                Ok((addr, abi)) => {
+                    contracts.insert(addr, Arc::new(abi));
+                }
+                Err(e) => warn!("skip ABI file {:?}: {}", path, e),
+            }
+        }
+        info!("ABI registry loaded, {} contracts", contracts.len());
+
+        Ok(AbiRegistry {
+            dir: Some(PathBuf::from(dir)),
+            contracts: RwLock::new(contracts),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    pub fn get(&self, addr: &Address) -> Option<Arc<ContractAbi>> {
+        self.contracts.read().unwrap().get(addr).cloned()
+    }
+
+    /// Attach an ABI to a contract, replacing the existing one.
+    pub fn attach(&self, addr: Address, abi: &str, metadata: &str) -> Result<(), Box<dyn Error>> {
+        let dir = self.dir.as_ref().ok_or_else(|| new_error("ABI registry is disabled"))?;
+        let contract_abi = ContractAbi::parse(abi, metadata)?;
+
+        let file = RegistryFile {
+            abi: serde_json::from_str(abi)?,
+            metadata: metadata.to_owned(),
+        };
+        let path = dir.join(format!("{}.json", addr));
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&file)?)?;
+        fs::rename(&tmp_path, &path)?;
+
+        self.contracts.write().unwrap().insert(addr, Arc::new(contract_abi));
+        Ok(())
+    }
+
+    /// Returns false if not attached.
+    pub fn detach(&self, addr: &Address) -> Result<bool, Box<dyn Error>> {
+        let dir = self.dir.as_ref().ok_or_else(|| new_error("ABI registry is disabled"))?;
+        if self.contracts.write().unwrap().remove(addr).is_none() {
+            return Ok(false);
+        }
+        fs::remove_file(dir.join(format!("{}.json", addr)))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRC20_ABI: &str = r#"[
+        {"type": "function", "name": "transfer", "inputs": [
+            {"name": "to", "type": "address"}, {"name": "value", "type": "uint256"}
+        ]},
+        {"type": "event", "name": "Transfer", "inputs": [
+            {"name": "from", "type": "address", "indexed": true},
+            {"name": "to", "type": "address", "indexed": true},
+            {"name": "value", "type": "uint256", "indexed": false}
+        ]},
+        {"type": "event", "name": "Memo", "inputs": [
+            {"name": "delta", "type": "int256"}, {"name": "memo", "type": "string"}
+        ]}
+    ]"#;
+
+    #[test]
+    fn test_decode() {
+        let abi = ContractAbi::parse(TRC20_ABI, "").unwrap();
+        let transfer = abi.functions().next().unwrap();
+        assert_eq!(transfer.signature(), "transfer(address,uint256)");
+        assert_eq!(hex::encode(&transfer.hash()[..4]), "a9059cbb");
+
+        let to: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let mut to_word = [0u8; 32];
+        to_word[12..].copy_from_slice(to.as_tvm_bytes());
+        let mut value_word = [0u8; 32];
+        value_word[31] = 100;
+
+        let call = [&transfer.hash()[..4], &to_word[..], &value_word[..]].concat();
+        let (entry, params) = abi.decode_call(&call).unwrap();
+        assert_eq!(entry.name, "transfer");
+        assert_eq!(params[0].value, to.to_string());
+        assert_eq!(params[1].value, "100");
+
+        let topic = abi.events().next().unwrap().hash().to_vec();
+        let (entry, params) = abi
+            .decode_log(&[topic, to_word.to_vec(), to_word.to_vec()], &value_word)
+            .unwrap();
+        assert_eq!(entry.name, "Transfer");
+        assert_eq!(params[1].value, to.to_string());
+        assert_eq!(params[2].value, "100");
+
+        let memo_topic = abi.events().nth(1).unwrap().hash().to_vec();
+        let mut data = vec![0xffu8; 32];
+        data.extend_from_slice(&[[0u8; 31].to_vec(), vec![64], [0u8; 31].to_vec(), vec![2], b"hi".to_vec()].concat());
+        data.resize(32 * 4, 0);
+        let (_, params) = abi.decode_log(&[memo_topic], &data).unwrap();
+        assert_eq!(params[0].value, "-1");
+        assert_eq!(params[1].value, "hi");
+
+        assert_eq!(abi.decode_call(&call[..4 + 32]).unwrap().1[1].value, "");
+    }
+
+    #[test]
+    fn test_decode_arrays_and_tuples() {
+        let abi = ContractAbi::parse(
+            r#"[{"type": "function", "name": "batch", "inputs": [
+                {"name": "amounts", "type": "uint256[]"},
+                {"name": "order", "type": "tuple", "components": [
+                    {"name": "maker", "type": "address"}, {"name": "filled", "type": "bool"}
+                ]}
+            ]}]"#,
+            "",
+        )
+        .unwrap();
+        let batch = abi.functions().next().unwrap();
+        assert_eq!(batch.signature(), "batch(uint256[],(address,bool))");
+
+        let maker: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let data = ethabi::encode(&[
+            Token::Array(vec![Token::Uint(1u64.into()), Token::Uint(2u64.into())]),
+            Token::Tuple(vec![
+                Token::Address(ethabi::Address::from_slice(maker.as_tvm_bytes())),
+                Token::Bool(true),
+            ]),
+        ]);
+        let call = [&batch.hash()[..4], &data[..]].concat();
+        let (_, params) = abi.decode_call(&call).unwrap();
+        assert_eq!(params[0].value, "[1,2]");
+        assert_eq!(params[1].value, format!("({},true)", maker));
+    }
+}
@@ -1,2 +1,4 @@
 pub mod protocol;
+pub mod secret;
 pub mod server;
+pub mod validation;
@@ -5,13 +5,18 @@ use prost::Message;
 use proto2::chain::Block;
 use proto2::channel::{
     inventory::Type as InventoryType, BlockInventory, ChainInventory, HandshakeDisconnect, HandshakeHello, Inventory,
-    ReasonCode as DisconnectReasonCode, Transactions,
+    ReasonCode as DisconnectReasonCode, StateChunk, StateChunkRequest, StateSnapshotManifest, StateSnapshotRequest,
+    Transactions,
 };
 use std::convert::TryFrom;
 use std::io::{self, Cursor};
+use std::mem;
 use tokio::prelude::*;
 use tokio_util::codec::{Decoder, Encoder, Framed, FramedRead, FramedWrite};
 
+use super::secret::SecretState;
+use super::validation::check_num_of_contracts_in_block;
+
 /// Channel message variations.
 pub enum ChannelMessage {
     Block(Block),
@@ -37,6 +42,15 @@ pub enum ChannelMessage {
 
     Ping,
     Pong,
+
+    // OpenTron only, state snapshot sync. See docs/state-sync.md.
+    StateSnapshotRequest(StateSnapshotRequest),
+    StateSnapshotManifest(StateSnapshotManifest),
+    StateChunkRequest(StateChunkRequest),
+    StateChunk(StateChunk),
+
+    /// Message types not handled by OpenTron, i.e. added by later java-tron releases, skipped by channels.
+    Unknown(u8, Vec<u8>),
 }
 
 impl ChannelMessage {
@@ -58,6 +72,13 @@ impl ChannelMessage {
 
             Ping => 0x22,
             Pong => 0x23,
+
+            StateSnapshotRequest(_) => 0x30,
+            StateSnapshotManifest(_) => 0x31,
+            StateChunkRequest(_) => 0x32,
+            StateChunk(_) => 0x33,
+
+            Unknown(type_code, _) => type_code,
         }
     }
 
@@ -84,6 +105,14 @@ impl ChannelMessage {
             BlockchainInventory(ref chain_inv) => chain_inv.encode(dst),
             HandshakeHello(ref hello) => hello.encode(dst),
             HandshakeDisconnect(ref disconnect) => disconnect.encode(dst),
+            StateSnapshotRequest(ref req) => req.encode(dst),
+            StateSnapshotManifest(ref manifest) => manifest.encode(dst),
+            StateChunkRequest(ref req) => req.encode(dst),
+            StateChunk(ref chunk) => chunk.encode(dst),
+            Unknown(_, ref payload) => {
+                dst.put_slice(payload);
+                Ok(())
+            }
         };
         ret.map_err(From::from)
     }
@@ -103,6 +132,11 @@ impl ChannelMessage {
             BlockchainInventory(ref chain_inv) => chain_inv.encoded_len(),
             HandshakeHello(ref hello) => hello.encoded_len(),
             HandshakeDisconnect(ref disconnect) => disconnect.encoded_len(),
+            StateSnapshotRequest(ref req) => req.encoded_len(),
+            StateSnapshotManifest(ref manifest) => manifest.encoded_len(),
+            StateChunkRequest(ref req) => req.encoded_len(),
+            StateChunk(ref chunk) => chunk.encoded_len(),
+            Unknown(_, ref payload) => payload.len(),
         };
         pb_len + 1
     }
@@ -157,6 +191,33 @@ impl ::std::fmt::Debug for ChannelMessage {
                 hello.timestamp,
             ),
             HandshakeDisconnect(ref disconnect) => write!(f, "HandshakeDisconnect(reason={})", disconnect.reason),
+            StateSnapshotRequest(ref req) => write!(
+                f,
+                "StateSnapshotRequest(block={:?})",
+                req.block_id.as_ref().map(|blk_id| blk_id.number)
+            ),
+            StateSnapshotManifest(ref manifest) => write!(
+                f,
+                "StateSnapshotManifest(block={:?}, |chunks|={})",
+                manifest.block_id.as_ref().map(|blk_id| blk_id.number),
+                manifest.chunks.len()
+            ),
+            StateChunkRequest(ref req) => write!(
+                f,
+                "StateChunkRequest(column={}, start_key={:?})",
+                req.column,
+                hex::encode(&req.start_key)
+            ),
+            StateChunk(ref chunk) => write!(
+                f,
+                "StateChunk(column={}, start_key={:?}, |entries|={})",
+                chunk.column,
+                hex::encode(&chunk.start_key),
+                chunk.entries.len()
+            ),
+            Unknown(type_code, ref payload) => {
+                write!(f, "Unknown(type=0x{:02x}, |payload|={})", type_code, payload.len())
+            }
         }
     }
 }
@@ -170,7 +231,11 @@ impl TryFrom<&[u8]> for ChannelMessage {
         }
 
         match buf[0] {
-            0x02 => Ok(ChannelMessage::Block(Message::decode(&buf[1..])?)),
+            0x02 => {
+                // Must be checked on the encoded block, for both gossiped and syncing blocks.
+                check_num_of_contracts_in_block(&buf[1..])?;
+                Ok(ChannelMessage::Block(Message::decode(&buf[1..])?))
+            }
             0x03 => Ok(ChannelMessage::Transactions(Message::decode(&buf[1..])?)),
             0x06 => {
                 let inv = Inventory::decode(&buf[1..])?;
@@ -201,7 +266,13 @@ impl TryFrom<&[u8]> for ChannelMessage {
                 assert!(buf[1] == 0xC0);
                 Ok(ChannelMessage::Pong)
             }
-            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid data")),
+
+            0x30 => Ok(ChannelMessage::StateSnapshotRequest(Message::decode(&buf[1..])?)),
+            0x31 => Ok(ChannelMessage::StateSnapshotManifest(Message::decode(&buf[1..])?)),
+            0x32 => Ok(ChannelMessage::StateChunkRequest(Message::decode(&buf[1..])?)),
+            0x33 => Ok(ChannelMessage::StateChunk(Message::decode(&buf[1..])?)),
+            // Frames are length delimited, so messages of unknown types can be skipped without decoding.
+            type_code => Ok(ChannelMessage::Unknown(type_code, buf[1..].to_vec())),
         }
     }
 }
@@ -215,12 +286,26 @@ enum DecodeState {
 pub struct ChannelMessageCodec {
     // Read state
     state: DecodeState,
+    // Encrypted channel
+    secret: Option<SecretState>,
+    // Decrypted data not yet decoded, of an encrypted channel
+    plaintext: BytesMut,
 }
 
 impl ChannelMessageCodec {
     pub fn new() -> Self {
         Self {
             state: DecodeState::Head,
+            secret: None,
+            plaintext: BytesMut::new(),
+        }
+    }
+
+    /// Codec of an encrypted channel.
+    pub fn with_secret(secret: SecretState) -> Self {
+        Self {
+            secret: Some(secret),
+            ..Self::new()
         }
     }
 
@@ -288,13 +373,8 @@ impl ChannelMessageCodec {
 
         Ok(Some(ChannelMessage::try_from(&*src.split_to(n))?))
     }
-}
-
-impl Decoder for ChannelMessageCodec {
-    type Item = ChannelMessage;
-    type Error = io::Error;
 
-    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<ChannelMessage>> {
+    fn decode_plain(&mut self, src: &mut BytesMut) -> io::Result<Option<ChannelMessage>> {
         let n = match self.state {
             DecodeState::Head => match self.decode_head(src)? {
                 Some(n) => {
@@ -316,6 +396,23 @@ impl Decoder for ChannelMessageCodec {
             None => Ok(None),
         }
     }
+}
+
+impl Decoder for ChannelMessageCodec {
+    type Item = ChannelMessage;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<ChannelMessage>> {
+        if let Some(ref mut secret) = self.secret {
+            secret.decrypt(src, &mut self.plaintext)?;
+            let mut plaintext = mem::take(&mut self.plaintext);
+            let ret = self.decode_plain(&mut plaintext);
+            self.plaintext = plaintext;
+            ret
+        } else {
+            self.decode_plain(src)
+        }
+    }
 
     fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         match self.decode(buf)? {
@@ -340,6 +437,13 @@ impl Encoder<ChannelMessage> for ChannelMessageCodec {
 
         let n = data.encoded_len();
 
+        if let Some(ref mut secret) = self.secret {
+            let mut plaintext = BytesMut::with_capacity(ESTIMATED_PACKET_PREFIX_LEN + n);
+            prost::encode_length_delimiter(n, &mut plaintext)?;
+            data.encode_to(&mut plaintext)?;
+            return secret.encrypt(&plaintext, dst);
+        }
+
         // Reserve capacity in the destination buffer to fit the frame and length field
         dst.reserve(ESTIMATED_PACKET_PREFIX_LEN + n);
 
@@ -357,3 +461,76 @@ impl Default for ChannelMessageCodec {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_message() {
+        // ItemNotFound of java-tron, not handled by OpenTron.
+        let msg = ChannelMessage::try_from(&[0x10, 0x08, 0x01][..]).unwrap();
+        match msg {
+            ChannelMessage::Unknown(0x10, ref payload) => assert_eq!(payload, &[0x08, 0x01]),
+            _ => panic!("unexpected {:?}", msg),
+        }
+        assert_eq!(msg.encoded_len(), 3);
+
+        let mut buf = BytesMut::new();
+        msg.encode_to(&mut buf).unwrap();
+        assert_eq!(&buf[..], &[0x10, 0x08, 0x01]);
+
+        let mut codec = ChannelMessageCodec::new();
+        let mut src = BytesMut::from(&[0x02, 0x42, 0xC0, 0x02, 0x22, 0xC0][..]);
+        assert!(matches!(codec.decode(&mut src).unwrap(), Some(ChannelMessage::Unknown(0x42, _))));
+        assert!(matches!(codec.decode(&mut src).unwrap(), Some(ChannelMessage::Ping)));
+    }
+    #[test]
+    fn test_state_sync_messages() {
+        use proto2::channel::state_chunk::Entry;
+        use proto2::common::BlockId;
+
+        let block_id = BlockId {
+            number: 42,
+            hash: vec![0x42; 32],
+        };
+        let msgs = vec![
+            ChannelMessage::StateSnapshotRequest(StateSnapshotRequest {
+                block_id: Some(block_id.clone()),
+            }),
+            ChannelMessage::StateSnapshotManifest(StateSnapshotManifest {
+                block_id: Some(block_id.clone()),
+                db_version: 1,
+                chunks: vec![],
+                root_hash: vec![0x01; 32],
+            }),
+            ChannelMessage::StateChunkRequest(StateChunkRequest {
+                block_id: Some(block_id.clone()),
+                column: 1,
+                start_key: b"key".to_vec(),
+            }),
+            ChannelMessage::StateChunk(StateChunk {
+                block_id: Some(block_id),
+                column: 1,
+                start_key: b"key".to_vec(),
+                entries: vec![Entry {
+                    key: b"key".to_vec(),
+                    value: b"value".to_vec(),
+                }],
+            }),
+        ];
+
+        for (msg, type_code) in msgs.iter().zip(0x30..) {
+            assert_eq!(msg.type_code(), type_code);
+
+            let mut buf = BytesMut::new();
+            msg.encode_to(&mut buf).unwrap();
+            assert_eq!(buf[0], type_code);
+            assert_eq!(buf.len(), msg.encoded_len());
+
+            let decoded = ChannelMessage::try_from(&buf[..]).unwrap();
+            assert_eq!(decoded.type_code(), type_code);
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", msg));
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Encrypted channels, selected per peer by `[protocol.channel.secret]` in config.
+//!
+//! Both sides pin the static public key of each other, and run a `Noise_KK` handshake right after the TCP
+//! connection is established. A key mismatch fails the handshake. Channel messages are then encrypted in frames of
+//! a 2-byte big-endian length and the ciphertext.
+
+use std::error::Error;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use bytes::{Buf, BufMut, BytesMut};
+use config::SecretChannelConfig;
+use snow::params::DHChoice;
+use snow::resolvers::{CryptoResolver, DefaultResolver};
+use snow::StatelessTransportState;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::util::new_error;
+
+const NOISE_PARAMS: &str = "Noise_KK_25519_ChaChaPoly_BLAKE2s";
+const MAX_NOISE_MESSAGE_LEN: usize = 65535;
+const TAG_LEN: usize = 16;
+const MAX_PLAINTEXT_LEN: usize = MAX_NOISE_MESSAGE_LEN - TAG_LEN;
+
+fn to_io_error(e: snow::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn parse_key(key: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let key = hex::decode(key)?;
+    if key.len() != 32 {
+        return Err(new_error("x25519 key must be 32 bytes"));
+    }
+    Ok(key)
+}
+
+struct SecretPeer {
+    ip: IpAddr,
+    port: Option<u16>,
+    public_key: Vec<u8>,
+}
+
+pub struct SecretChannel {
+    private_key: Vec<u8>,
+    peers: Vec<SecretPeer>,
+}
+
+impl SecretChannel {
+    /// None if encrypted channels are disabled.
+    pub fn from_config(config: &SecretChannelConfig) -> Result<Option<Self>, Box<dyn Error>> {
+        if config.private_key.is_empty() {
+            return Ok(None);
+        }
+        let peers = config
+            .peers
+            .iter()
+            .map(|peer| {
+                let (ip, port) = match peer.address.parse::<SocketAddr>() {
+                    Ok(addr) => (addr.ip(), Some(addr.port())),
+                    Err(_) => (peer.address.parse::<IpAddr>()?, None),
+                };
+                Ok(SecretPeer {
+                    ip,
+                    port,
+                    public_key: parse_key(&peer.public_key)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        Ok(Some(SecretChannel {
+            private_key: parse_key(&config.private_key)?,
+            peers,
+        }))
+    }
+
+    /// Public key of this node, to be pinned by peers.
+    pub fn public_key(&self) -> Vec<u8> {
+        let mut dh = DefaultResolver
+            .resolve_dh(&DHChoice::Curve25519)
+            .expect("curve25519 is supported");
+        dh.set(&self.private_key);
+        dh.pubkey().to_vec()
+    }
+
+    /// Pinned public key of a peer, if the peer must be connected with an encrypted channel.
+    ///
+    /// Inbound connections come from random ports, so only IP is matched.
+    pub fn pinned_key(&self, peer_addr: &SocketAddr, inbound: bool) -> Option<&[u8]> {
+        self.peers
+            .iter()
+            .find(|peer| peer.ip == peer_addr.ip() && (inbound || peer.port.map_or(true, |p| p == peer_addr.port())))
+            .map(|peer| &peer.public_key[..])
+    }
+
+    /// Run the handshake over a connected socket.
+    pub async fn handshake(
+        &self,
+        sock: &mut TcpStream,
+        remote_key: &[u8],
+        initiator: bool,
+    ) -> Result<Arc<StatelessTransportState>, Box<dyn Error>> {
+        let builder = snow::Builder::new(NOISE_PARAMS.parse()?)
+            .local_private_key(&self.private_key)
+            .remote_public_key(remote_key);
+        let mut noise = if initiator {
+            builder.build_initiator()?
+        } else {
+            builder.build_responder()?
+        };
+
+        let mut buf = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        let mut payload = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        // KK: -> e, es, ss; <- e, ee, se
+        let mut sending = initiator;
+        while !noise.is_handshake_finished() {
+            if sending {
+                let n = noise.write_message(&[], &mut buf)?;
+                sock.write_all(&(n as u16).to_be_bytes()).await?;
+                sock.write_all(&buf[..n]).await?;
+            } else {
+                let n = sock.read_u16().await? as usize;
+                sock.read_exact(&mut buf[..n]).await?;
+                noise.read_message(&buf[..n], &mut payload)?;
+            }
+            sending = !sending;
+        }
+        Ok(Arc::new(noise.into_stateless_transport_mode()?))
+    }
+}
+
+/// Encryption state of one direction of a channel.
+pub struct SecretState {
+    transport: Arc<StatelessTransportState>,
+    nonce: u64,
+}
+
+impl SecretState {
+    pub fn new(transport: Arc<StatelessTransportState>) -> Self {
+        SecretState { transport, nonce: 0 }
+    }
+
+    /// Decrypt all complete frames in `src` into `plaintext`.
+    pub fn decrypt(&mut self, src: &mut BytesMut, plaintext: &mut BytesMut) -> io::Result<()> {
+        let mut buf = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        while src.len() >= 2 {
+            let len = u16::from_be_bytes([src[0], src[1]]) as usize;
+            if src.len() < 2 + len {
+                src.reserve(2 + len - src.len());
+                break;
+            }
+            src.advance(2);
+            let frame = src.split_to(len);
+            let n = self
+                .transport
+                .read_message(self.nonce, &frame, &mut buf)
+                .map_err(to_io_error)?;
+            self.nonce += 1;
+            plaintext.extend_from_slice(&buf[..n]);
+        }
+        Ok(())
+    }
+
+    pub fn encrypt(&mut self, plaintext: &[u8], dst: &mut BytesMut) -> io::Result<()> {
+        let mut buf = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        for chunk in plaintext.chunks(MAX_PLAINTEXT_LEN) {
+            let n = self
+                .transport
+                .write_message(self.nonce, chunk, &mut buf)
+                .map_err(to_io_error)?;
+            self.nonce += 1;
+            dst.reserve(2 + n);
+            dst.put_u16(n as u16);
+            dst.extend_from_slice(&buf[..n]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::SecretPeerConfig;
+
+    #[test]
+    fn test_secret_channel_config() {
+        let config = SecretChannelConfig {
+            private_key: "11".repeat(32),
+            peers: vec![
+                SecretPeerConfig {
+                    address: "10.0.0.2:18888".into(),
+                    public_key: "22".repeat(32),
+                },
+                SecretPeerConfig {
+                    address: "10.0.0.3".into(),
+                    public_key: "33".repeat(32),
+                },
+            ],
+        };
+        let secret = SecretChannel::from_config(&config).unwrap().unwrap();
+        assert_eq!(secret.public_key().len(), 32);
+
+        let addr: SocketAddr = "10.0.0.2:18888".parse().unwrap();
+        assert_eq!(secret.pinned_key(&addr, false), Some(&[0x22; 32][..]));
+        let addr: SocketAddr = "10.0.0.2:50000".parse().unwrap();
+        assert!(secret.pinned_key(&addr, false).is_none());
+        assert!(secret.pinned_key(&addr, true).is_some());
+        let addr: SocketAddr = "10.0.0.3:18888".parse().unwrap();
+        assert_eq!(secret.pinned_key(&addr, false), Some(&[0x33; 32][..]));
+        let addr: SocketAddr = "10.0.0.4:18888".parse().unwrap();
+        assert!(secret.pinned_key(&addr, true).is_none());
+
+        assert!(SecretChannel::from_config(&SecretChannelConfig::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_secret_state() {
+        let params: snow::params::NoiseParams = NOISE_PARAMS.parse().unwrap();
+        let initiator_keys = snow::Builder::new(params.clone()).generate_keypair().unwrap();
+        let responder_keys = snow::Builder::new(params.clone()).generate_keypair().unwrap();
+        let mut initiator = snow::Builder::new(params.clone())
+            .local_private_key(&initiator_keys.private)
+            .remote_public_key(&responder_keys.public)
+            .build_initiator()
+            .unwrap();
+        let mut responder = snow::Builder::new(params)
+            .local_private_key(&responder_keys.private)
+            .remote_public_key(&initiator_keys.public)
+            .build_responder()
+            .unwrap();
+
+        let mut buf = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        let mut payload = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        let n = initiator.write_message(&[], &mut buf).unwrap();
+        responder.read_message(&buf[..n], &mut payload).unwrap();
+        let n = responder.write_message(&[], &mut buf).unwrap();
+        initiator.read_message(&buf[..n], &mut payload).unwrap();
+
+        let mut sender = SecretState::new(Arc::new(initiator.into_stateless_transport_mode().unwrap()));
+        let mut receiver = SecretState::new(Arc::new(responder.into_stateless_transport_mode().unwrap()));
+
+        // spans 2 frames
+        let message = vec![0xab; MAX_PLAINTEXT_LEN + 100];
+        let mut wire = BytesMut::new();
+        sender.encrypt(&message, &mut wire).unwrap();
+        assert_eq!(wire.len(), message.len() + 2 * (2 + TAG_LEN));
+
+        let mut plaintext = BytesMut::new();
+        let mut partial = wire.split_to(MAX_NOISE_MESSAGE_LEN + 10);
+        receiver.decrypt(&mut partial, &mut plaintext).unwrap();
+        assert_eq!(plaintext.len(), MAX_PLAINTEXT_LEN);
+        partial.extend_from_slice(&wire);
+        receiver.decrypt(&mut partial, &mut plaintext).unwrap();
+        assert!(partial.is_empty());
+        assert_eq!(&plaintext[..], &message[..]);
+
+        // tampered
+        let mut wire = BytesMut::new();
+        sender.encrypt(b"hello", &mut wire).unwrap();
+        let last = wire.len() - 1;
+        wire[last] ^= 1;
+        assert!(receiver.decrypt(&mut wire, &mut plaintext).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use super::protocol::{ChannelMessage, ChannelMessageCodec};
+use super::secret::{SecretChannel, SecretState};
+use super::validation::{fast_validate_block, verify_merkle_root};
 use chain::IndexedBlock;
 use chrono::Utc;
 use futures::channel::oneshot;
@@ -11,10 +13,10 @@ use keys::b58encode_check;
 use log::{debug, error, info, warn};
 use primitive_types::H256;
 use proto2::channel::{
-    BlockInventory, ChainInventory, HandshakeDisconnect, HandshakeHello, Inventory, ReasonCode as DisconnectReasonCode,
-    Transactions,
+    handshake_hello::NodeType, BlockInventory, ChainInventory, HandshakeDisconnect, HandshakeHello, Inventory,
+    ReasonCode as DisconnectReasonCode, Transactions,
 };
-use proto2::common::{BlockId, Endpoint};
+use proto2::common::BlockId;
 use slog::{o, slog_info};
 use slog_scope_futures::FutureExt as SlogFutureExt;
 use std::error::Error;
@@ -26,11 +28,14 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio::stream::StreamExt;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
 use tokio::time::{delay_for, timeout};
+use tokio_util::codec::{FramedRead, FramedWrite};
 
-use crate::context::AppContext;
-use crate::util::block_hash_to_number;
+use crate::context::{AppContext, PeerInfo};
+use crate::light;
+use crate::metrics;
+use crate::util::{advertised_endpoint, block_hash_to_number};
 
 pub async fn channel_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
     let config = &ctx.config.protocol.channel;
@@ -40,6 +45,10 @@ pub async fn channel_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<()
         return Ok(());
     }
 
+    if let Some(secret) = SecretChannel::from_config(&config.secret)? {
+        info!("encrypted channel public key {}", hex::encode(secret.public_key()));
+    }
+
     let incomming_service = {
         let ctx = ctx.clone();
         let logger = slog_scope::logger().new(o!("direction" => "incomming"));
@@ -87,7 +96,7 @@ async fn passive_channel_service(
                             Some(Ok(sock)) => {
                                 let ctx = ctx.clone();
                                 tokio::spawn(async move {
-                                    let _ = handshake_handler(ctx, sock).await;
+                                    let _ = handshake_handler(ctx, sock, true).await;
                                 });
                             },
                             Some(Err(e)) => error!("accept failed = {:?}", e),
@@ -138,7 +147,7 @@ async fn active_channel_service(ctx: Arc<AppContext>) -> Result<(), Box<dyn Erro
                         Ok(sock) => {
                             ctx.num_active_connections.fetch_add(1, Ordering::SeqCst);
                             tokio::spawn(async move {
-                                let _ = handshake_handler(ctx.clone(), sock).await;
+                                let _ = handshake_handler(ctx.clone(), sock, false).await;
                                 ctx.num_active_connections.fetch_sub(1, Ordering::SeqCst);
                             });
                         }
@@ -156,40 +165,44 @@ async fn active_channel_service(ctx: Arc<AppContext>) -> Result<(), Box<dyn Erro
     Ok(())
 }
 
-async fn handshake_handler(ctx: Arc<AppContext>, sock: TcpStream) -> Result<(), Box<dyn Error>> {
+async fn handshake_handler(ctx: Arc<AppContext>, sock: TcpStream, inbound: bool) -> Result<(), Box<dyn Error>> {
     let peer_addr = sock.peer_addr()?;
     let logger = slog_scope::logger().new(o!(
         "peer_addr" => peer_addr,
     ));
-    inner_handshake_handler(ctx, sock).with_logger(logger).await
+    inner_handshake_handler(ctx, peer_addr, sock, inbound).with_logger(logger).await
 }
 
-async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> Result<(), Box<dyn Error>> {
+async fn inner_handshake_handler(
+    ctx: Arc<AppContext>,
+    peer_addr: SocketAddr,
+    mut sock: TcpStream,
+    inbound: bool,
+) -> Result<(), Box<dyn Error>> {
+    // encrypted channel, if the peer is configured with a pinned key
+    let secret = SecretChannel::from_config(&ctx.config.protocol.channel.secret)?;
+    let transport = match secret.as_ref().and_then(|s| s.pinned_key(&peer_addr, inbound).map(|key| (s, key))) {
+        Some((secret, remote_key)) => {
+            let transport = timeout(Duration::from_secs(10), secret.handshake(&mut sock, remote_key, !inbound)).await??;
+            info!("encrypted channel established");
+            Some(transport)
+        }
+        None => None,
+    };
+
     let (reader, writer) = sock.split();
 
-    let mut reader = ChannelMessageCodec::new_read(reader);
-    let mut writer = ChannelMessageCodec::new_write(writer);
+    let (mut reader, mut writer) = match transport {
+        Some(transport) => (
+            FramedRead::new(reader, ChannelMessageCodec::with_secret(SecretState::new(transport.clone()))),
+            FramedWrite::new(writer, ChannelMessageCodec::with_secret(SecretState::new(transport))),
+        ),
+        None => (ChannelMessageCodec::new_read(reader), ChannelMessageCodec::new_write(writer)),
+    };
 
     let p2p_version = ctx.config.chain.p2p_version;
 
-    let channel_conf = &ctx.config.protocol.channel;
-    let advertised_endpoint = channel_conf
-        .advertised_endpoint
-        .parse::<SocketAddr>()
-        .map(|addr| Endpoint {
-            address: addr.ip().to_string(),
-            port: addr.port() as _,
-            node_id: ctx.node_id.clone(),
-        })
-        .unwrap_or_else(|_| Endpoint {
-            address: ctx.outbound_ip.clone(),
-            port: channel_conf
-                .endpoint
-                .parse::<SocketAddr>()
-                .map(|addr| addr.port())
-                .unwrap_or(18888) as _,
-            node_id: ctx.node_id.clone(),
-        });
+    let advertised_endpoint = advertised_endpoint(&ctx);
 
     let block_height = ctx.chain_db.get_block_height();
     let block_headers = ctx.chain_db.get_block_headers_by_number(block_height as u64);
@@ -209,6 +222,12 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
         ctx.genesis_block_id.clone()
     };
 
+    // Light nodes keep block headers only, no full block is served.
+    let (node_type, lowest_block_num) = if ctx.config.light.enable {
+        (NodeType::Lite, block_height + 1)
+    } else {
+        (NodeType::Full, 0)
+    };
     let hello = HandshakeHello {
         from: Some(advertised_endpoint),
         version: p2p_version,
@@ -216,9 +235,13 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
         genesis_block_id: ctx.genesis_block_id.clone(),
         head_block_id: head_block_id.clone(),
         solid_block_id: ctx.genesis_block_id.clone(), // solid_block_id.clone(),
+        node_type: node_type as i32,
+        lowest_block_num,
+        code_version: format!("opentron/{}", env!("CARGO_PKG_VERSION")).into_bytes(),
         ..Default::default()
     };
 
+    let hello_sent_at = Instant::now();
     writer.send(hello.into()).await?;
 
     while let Ok(payload) = timeout(Duration::from_secs(10), reader.next()).await {
@@ -229,14 +252,20 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
 
         match payload.unwrap() {
             Ok(ChannelMessage::HandshakeHello(HandshakeHello {
+                from: peer_endpoint,
                 version,
                 genesis_block_id: peer_genesis_block_id,
                 head_block_id: peer_head_block_id,
                 solid_block_id: _peer_solid_block_id,
+                node_type: peer_node_type,
+                lowest_block_num: peer_lowest_block_num,
+                code_version: peer_code_version,
                 ..
             })) => {
+                let peer_code_version = String::from_utf8_lossy(&peer_code_version).into_owned();
                 slog_info!(slog_scope::logger(), "handshake request";
                     "version" => version,
+                    "code_version" => &peer_code_version,
                     "genesis_block" => hex::encode(&peer_genesis_block_id.as_ref().unwrap().hash),
                     "head_block" => peer_head_block_id.as_ref().unwrap().number,
                 );
@@ -261,16 +290,39 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
                 }
 
                 // only syncing if remote >= local?
-                let need_syncing =
+                let mut need_syncing =
                     peer_head_block_id.as_ref().unwrap().number >= head_block_id.as_ref().unwrap().number;
+                // A lite peer without our next block can not be synced from, but is kept for block gossip.
+                if need_syncing &&
+                    peer_node_type == NodeType::Lite as i32 &&
+                    peer_lowest_block_num > block_height + 1
+                {
+                    info!("lite peer, lowest block = {}, skip syncing", peer_lowest_block_num);
+                    need_syncing = false;
+                }
 
                 info!("handshake finished, need sync = {}", need_syncing);
+                let peer_endpoint = peer_endpoint.unwrap_or_default();
+                let peer_info = PeerInfo {
+                    remote_addr: peer_addr,
+                    advertised_address: peer_endpoint.address,
+                    advertised_port: peer_endpoint.port,
+                    node_id: peer_endpoint.node_id,
+                    p2p_version: version,
+                    code_version: peer_code_version,
+                    head_block_number: peer_head_block_id.as_ref().unwrap().number,
+                    latency: hello_sent_at.elapsed().as_millis() as _,
+                    connected_at: Utc::now().timestamp_millis(),
+                };
+                ctx.peer_infos.write().unwrap().insert(peer_addr, peer_info);
+
                 let logger = slog_scope::logger().new(o!(
                     "protocol" => "channel"
                 ));
-                let ret = sync_channel_handler(ctx, need_syncing, reader, writer)
+                let ret = sync_channel_handler(ctx.clone(), peer_addr, need_syncing, reader, writer)
                     .with_logger(logger)
                     .await;
+                ctx.peer_infos.write().unwrap().remove(&peer_addr);
                 match ret {
                     Ok(_) => info!("channel finished"),
                     Err(e) => warn!("channel finished with error={:?}", e),
@@ -288,6 +340,10 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
                 error!("error: {:?}", e);
                 return Ok(());
             }
+            Ok(ChannelMessage::Unknown(type_code, _)) => {
+                ctx.metrics.inc(&metrics::UNKNOWN_MESSAGES, 1);
+                debug!("skip unknown message before handshake, type=0x{:02x}", type_code);
+            }
             Ok(message) => {
                 error!("unhandled message {:?}", &message);
                 return Ok(());
@@ -302,6 +358,7 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
 
 async fn sync_channel_handler(
     ctx: Arc<AppContext>,
+    peer_addr: SocketAddr,
     mut syncing: bool,
     mut reader: impl Stream<Item = Result<ChannelMessage, io::Error>> + Unpin,
     mut writer: impl Sink<ChannelMessage, Error = io::Error> + Unpin,
@@ -341,19 +398,23 @@ async fn sync_channel_handler(
     }
 
     let mut syncing_block_ids: Vec<Vec<u8>> = vec![];
-    let mut pinged = false;
+    let mut pinged_at: Option<Instant> = None;
     let (mut tx, mut rx) = mpsc::channel::<ChannelMessage>(1000);
+    let mut relay_rx = ctx.block_relay.subscribe();
+    let mut txn_relay_rx = ctx.txn_relay.subscribe();
 
     loop {
         let mut next_packet = reader.next().fuse();
         let mut sending_packet = rx.next().fuse();
+        let mut relaying_block = relay_rx.recv().fuse();
+        let mut relaying_txns = txn_relay_rx.recv().fuse();
         let mut timeout = delay_for(Duration::from_secs(18)).fuse();
         select! {
             _ = timeout => {
-                if !pinged {
+                if pinged_at.is_none() {
                     warn!("timeout, try ping remote");
                     writer.send(ChannelMessage::Ping).await?;
-                    pinged = true;
+                    pinged_at = Some(Instant::now());
                 } else {
                     warn!("timeout without replying to ping");
                     return Ok(());
@@ -388,6 +449,11 @@ async fn sync_channel_handler(
                     },
                     Ok(ChannelMessage::Pong) => {
                         debug!("pong");
+                        if let Some(pinged_at) = pinged_at.take() {
+                            if let Some(info) = ctx.peer_infos.write().unwrap().get_mut(&peer_addr) {
+                                info.latency = pinged_at.elapsed().as_millis() as _;
+                            }
+                        }
                     },
                     Ok(ChannelMessage::TransactionInventory(inv)) => {
                         let Inventory { mut ids, r#type } = inv;
@@ -404,8 +470,20 @@ async fn sync_channel_handler(
                         }
                         */
                     }
-                    Ok(ChannelMessage::FetchTransactionInventory(inv)) => {
-                        info!("fetch transactions {:?}", inv);
+                    Ok(ChannelMessage::FetchTransactionInventory(Inventory { ids, .. })) => {
+                        // Only pending transactions of this node are served.
+                        let transactions: Vec<_> = {
+                            let pool = ctx.pending_pool.read().unwrap();
+                            ids.iter()
+                                .filter(|raw| raw.len() == 32)
+                                .filter_map(|raw| pool.get(&H256::from_slice(raw)))
+                                .map(|txn| txn.raw.clone())
+                                .collect()
+                        };
+                        debug!("fetch transactions, requested={}, found={}", ids.len(), transactions.len());
+                        if !transactions.is_empty() {
+                            writer.send(ChannelMessage::Transactions(Transactions { transactions })).await?;
+                        }
                     }
                     Ok(ChannelMessage::Transactions(Transactions { transactions })) => {
                         for txn in &transactions {
@@ -469,6 +547,11 @@ async fn sync_channel_handler(
                     }
                     Ok(ChannelMessage::Block(block)) => {
                         let block = IndexedBlock::from_raw(block);
+                        // Logs of validating and saving the block are tagged with block number, as in the manager.
+                        let block_logger = slog_scope::logger().new(o!("block" => block.number()));
+                        if let Some(info) = ctx.peer_infos.write().unwrap().get_mut(&peer_addr) {
+                            info.head_block_number = info.head_block_number.max(block.number());
+                        }
                         if !ctx.recent_blk_ids.read().unwrap().contains(&block.header.hash) {
                             if syncing {
                                 if block.number() % 100 == 0 {
@@ -491,9 +574,39 @@ async fn sync_channel_handler(
                             }
 
                             ctx.recent_blk_ids.write().unwrap().insert(block.header.hash);
+                            if !syncing && !ctx.config.light.enable {
+                                // Relay before saving, to reduce propagation latency.
+                                if let Err(e) = slog_scope::scope(&block_logger, || fast_validate_block(&ctx, &block)) {
+                                    warn!("invalid block, number={}, error={}", block.number(), e);
+                                    writer.send(
+                                        ChannelMessage::disconnect_with_reason(DisconnectReasonCode::BadBlock))
+                                    .await?;
+                                    return Ok(());
+                                }
+                                let _ = ctx.block_relay.send((peer_addr, block.header.hash));
+                            } else if syncing && !ctx.config.light.enable && !verify_merkle_root(&ctx, &block) {
+                                warn!("merkle root hash mismatch, number={}", block.number());
+                                writer.send(
+                                    ChannelMessage::disconnect_with_reason(DisconnectReasonCode::BadBlock))
+                                .await?;
+                                return Ok(());
+                            }
                             if !ctx.chain_db.has_block(&block)  {
-                                ctx.chain_db.insert_block(&block)?;
+                                if ctx.config.light.enable {
+                                    if let Err(e) = light::validate_block_header(&ctx, &block, syncing).await {
+                                        warn!("invalid block header, number={}, error={}", block.number(), e);
+                                        writer.send(
+                                            ChannelMessage::disconnect_with_reason(DisconnectReasonCode::BadBlock))
+                                        .await?;
+                                        return Ok(());
+                                    }
+                                    let header = light::header_only(&block);
+                                    slog_scope::scope(&block_logger, || ctx.chain_db.insert_block(&header))?;
+                                } else {
+                                    slog_scope::scope(&block_logger, || ctx.chain_db.insert_block(&block))?;
+                                }
                                 ctx.chain_db.update_block_height(block.number());
+                                ctx.metrics.inc(&metrics::BLOCKS_RECEIVED, 1);
                             } else {
                                 warn!("block exists in db");
                             }
@@ -531,6 +644,14 @@ async fn sync_channel_handler(
                     // handle remote sync
                     Ok(ChannelMessage::SyncBlockchain(blk_inv)) => {
                         const SYNC_FETCH_BATCH_NUM: i64 = 2000;
+                        if ctx.config.light.enable {
+                            // NOTE: Light node only has block headers, unable to serve full blocks.
+                            warn!("light node refuses to serve sync request");
+                            writer.send(
+                                ChannelMessage::disconnect_with_reason(DisconnectReasonCode::SyncFail))
+                            .await?;
+                            return Ok(());
+                        }
                         let BlockInventory { mut ids, .. } = blk_inv;
                         info!("sync request {:?}", ids.iter().map(|blk_id| blk_id.number).collect::<Vec<_>>());
                         let unfork_id = ids.iter()
@@ -584,6 +705,20 @@ async fn sync_channel_handler(
                         }
                         info!("sent {} blocks", ids.len());
                     }
+                    Ok(msg @ ChannelMessage::StateSnapshotRequest(_)) |
+                    Ok(msg @ ChannelMessage::StateChunkRequest(_)) => {
+                        // NOTE: Serving needs a primary state-db, the node only has a read-only secondary.
+                        debug!("state snapshot is not served, skip {:?}", msg);
+                    }
+                    Ok(msg @ ChannelMessage::StateSnapshotManifest(_)) | Ok(msg @ ChannelMessage::StateChunk(_)) => {
+                        // Never requested, see docs/state-sync.md.
+                        debug!("skip unrequested {:?}", msg);
+                    }
+                    Ok(ChannelMessage::Unknown(type_code, _)) => {
+                        // Newer java-tron messages are skipped instead of disconnecting.
+                        ctx.metrics.inc(&metrics::UNKNOWN_MESSAGES, 1);
+                        debug!("skip unknown message, type=0x{:02x}", type_code);
+                    }
                     Ok(msg) => {
                         error!("unhandled message {:?}", msg);
                         return Ok(());
@@ -596,6 +731,30 @@ async fn sync_channel_handler(
                     writer.send(msg).await?;
                 }
             }
+            relayed = relaying_block => {
+                // Lagged receivers skip missed blocks, peers will fetch them by inventory of others.
+                if let Ok((from_addr, block_id)) = relayed {
+                    if from_addr != peer_addr && !syncing {
+                        let block_inv = Inventory {
+                            r#type: 1, // BLOCK
+                            ids: vec![block_id.as_bytes().to_vec()],
+                        };
+                        writer.send(ChannelMessage::BlockInventory(block_inv)).await?;
+                    }
+                }
+            }
+            relayed = relaying_txns => {
+                // Lagged receivers skip missed transactions, they will be rebroadcast.
+                if let Ok(txn_ids) = relayed {
+                    if !syncing {
+                        let txn_inv = Inventory {
+                            r#type: 0, // TRX
+                            ids: txn_ids.iter().map(|id| id.as_bytes().to_vec()).collect(),
+                        };
+                        writer.send(ChannelMessage::TransactionInventory(txn_inv)).await?;
+                    }
+                }
+            }
         }
     }
 
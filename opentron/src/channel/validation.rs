@@ -0,0 +1,99 @@
+//! Fast validation of gossiped blocks, before they are saved and relayed to other peers.
+//!
+//! Only checks without block execution: number of contracts per transaction, header hash, parent linkage, timestamp
+//! slot, producer signature and merkle root. Blocks are fully validated by the manager when applied.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io;
+
+use chain::IndexedBlock;
+use chrono::Utc;
+use keys::Address;
+use primitive_types::H256;
+use state::keys as state_keys;
+
+use crate::context::AppContext;
+use crate::manager::signing_key_of;
+use crate::util::{block_hash_to_number, new_error};
+
+/// Check every transaction of an encoded block carries exactly one contract, before extra contracts are merged by
+/// decoding. See `mempool::check_num_of_contracts`.
+pub fn check_num_of_contracts_in_block(buf: &[u8]) -> io::Result<()> {
+    for (i, num_of_contracts) in chain::num_of_contracts_in_block(buf)?.into_iter().enumerate() {
+        if num_of_contracts != constants::MAX_NUM_OF_CONTRACTS_PER_TRANSACTION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("block transaction #{} has {} contracts", i, num_of_contracts),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Validate a gossiped block against its header and the current chain-db head.
+///
+/// The producer is checked against the witness schedule only when state-db is at the parent block, since the
+/// schedule might be stale otherwise.
+pub fn fast_validate_block(ctx: &AppContext, block: &IndexedBlock) -> Result<(), Box<dyn Error>> {
+    if !block.header.verify() {
+        return Err(new_error("block header hash mismatch"));
+    }
+
+    if block_hash_to_number(block.parent_hash()) != block.number() - 1 {
+        return Err(new_error("block number is not continuous with parent"));
+    }
+    if !ctx.chain_db.has_block_id(&H256::from_slice(block.parent_hash())) {
+        return Err(new_error("parent block not found"));
+    }
+
+    let timestamp = block.timestamp();
+    if (timestamp - ctx.genesis_config.timestamp) % ctx.genesis_config.block_interval != 0 {
+        return Err(new_error("block timestamp is not at a slot"));
+    }
+    if timestamp > Utc::now().timestamp_millis() + ctx.genesis_config.block_interval {
+        return Err(new_error("block timestamp is in the future"));
+    }
+
+    let witness = Address::try_from(block.witness()).map_err(|_| new_error("invalid block witness address"))?;
+    let recovered = block.recover_witness()?;
+    let mut signing_key = None;
+    let mut is_state_at_parent = false;
+    if let Some(ref state_db) = ctx.state_db {
+        let state_head = state_db.get(&state_keys::DynamicProperty::LatestBlockNumber)?;
+        is_state_at_parent = state_head == Some(block.number() - 1);
+        if is_state_at_parent {
+            let schedule = state_db.get(&state_keys::WitnessSchedule)?.unwrap_or_default();
+            let is_active = schedule
+                .iter()
+                .take(ctx.genesis_config.active_witnesses)
+                .any(|wit| wit.0 == witness);
+            if !schedule.is_empty() && !is_active {
+                return Err(new_error("block producer is not an active witness"));
+            }
+        }
+        let allow_multisig = state_db.get(&state_keys::ChainParameter::AllowMultisig)? == Some(1);
+        let wit = state_db.get(&state_keys::Witness(witness))?;
+        signing_key = Some(signing_key_of(witness, wit.as_ref(), allow_multisig));
+    }
+    // Same check as the manager when state-db is at the parent block. Otherwise the signing key might have been
+    // rotated since, the witness itself is accepted as well.
+    let is_valid = match signing_key {
+        Some(key) if is_state_at_parent => recovered == key,
+        Some(key) => recovered == key || recovered == witness,
+        None => recovered == witness,
+    };
+    if !is_valid {
+        return Err(new_error("verifying block witness signature failed"));
+    }
+
+    if !verify_merkle_root(ctx, block) {
+        return Err(new_error("block merkle root hash mismatch"));
+    }
+    Ok(())
+}
+
+/// Verify the merkle root of a received block, historical mismatches are accepted by `chain.merkle-tree-patch`.
+pub fn verify_merkle_root(ctx: &AppContext, block: &IndexedBlock) -> bool {
+    block.verify_merkle_root_hash() || block.verify_merkle_root_hash_with_patch(&ctx.merkle_tree_patch)
+}
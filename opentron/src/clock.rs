@@ -0,0 +1,191 @@
+//! System clock drift detection, against NTP servers in `[time]` config.
+//!
+//! DPoS slot validation and transaction expiration checks are sensitive to clock skew. A drifted clock makes a
+//! producer miss its slots, or produce blocks rejected by other nodes.
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::future::FutureExt;
+use futures::select;
+use log::{debug, info, warn};
+use tokio::net::{self, UdpSocket};
+use tokio::sync::broadcast;
+use tokio::time::{delay_for, timeout};
+
+use crate::context::AppContext;
+use crate::util::new_error;
+
+const NTP_PACKET_SIZE: usize = 48;
+/// Seconds from 1900-01-01, the NTP epoch, to 1970-01-01.
+const NTP_UNIX_EPOCH_DIFF: i64 = 2_208_988_800;
+const NTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Result of the latest drift check.
+#[derive(Debug, Clone)]
+pub struct ClockDrift {
+    /// NTP server answered.
+    pub server: String,
+    /// Offset of the NTP server's clock to the local clock, in ms. Positive if the local clock is behind.
+    pub offset: i64,
+    /// Round trip delay, in ms.
+    pub delay: i64,
+    pub checked_at: i64,
+}
+
+fn to_ntp_timestamp(unix_millis: i64) -> [u8; 8] {
+    let secs = unix_millis.div_euclid(1_000) + NTP_UNIX_EPOCH_DIFF;
+    let frac = (unix_millis.rem_euclid(1_000) << 32) / 1_000;
+    let mut buf = [0u8; 8];
+    buf[..4].copy_from_slice(&(secs as u32).to_be_bytes());
+    buf[4..].copy_from_slice(&(frac as u32).to_be_bytes());
+    buf
+}
+
+fn from_ntp_timestamp(buf: &[u8]) -> i64 {
+    let mut raw = [0u8; 4];
+    raw.copy_from_slice(&buf[..4]);
+    let secs = u32::from_be_bytes(raw) as i64;
+    raw.copy_from_slice(&buf[4..8]);
+    let frac = u32::from_be_bytes(raw) as i64;
+    (secs - NTP_UNIX_EPOCH_DIFF) * 1_000 + ((frac * 1_000 + (1 << 31)) >> 32)
+}
+
+/// An SNTP v3 client request, sent at `now`.
+fn request_packet(now: i64) -> [u8; NTP_PACKET_SIZE] {
+    let mut packet = [0u8; NTP_PACKET_SIZE];
+    // LI = 0, VN = 3, Mode = 3(client)
+    packet[0] = 0x1b;
+    packet[40..48].copy_from_slice(&to_ntp_timestamp(now));
+    packet
+}
+
+/// Parse a server response to the request sent at `sent_at`, received at `received_at`.
+///
+/// Returns `(offset, delay)` in ms, as of RFC 4330.
+fn parse_response(packet: &[u8], sent_at: i64, received_at: i64) -> Result<(i64, i64), Box<dyn Error>> {
+    if packet.len() < NTP_PACKET_SIZE {
+        return Err(new_error("short NTP packet"));
+    }
+    if packet[0] & 0x7 != 4 {
+        return Err(new_error("not an NTP server response"));
+    }
+    if packet[1] == 0 {
+        return Err(new_error("NTP kiss-o'-death"));
+    }
+    // originate timestamp echoes transmit timestamp of the request
+    if packet[24..32] != to_ntp_timestamp(sent_at) {
+        return Err(new_error("NTP response does not match request"));
+    }
+    let server_received_at = from_ntp_timestamp(&packet[32..40]);
+    let server_sent_at = from_ntp_timestamp(&packet[40..48]);
+
+    let offset = ((server_received_at - sent_at) + (server_sent_at - received_at)) / 2;
+    let delay = (received_at - sent_at) - (server_sent_at - server_received_at);
+    Ok((offset, delay))
+}
+
+/// Query clock offset of an NTP server, as `host:port`.
+pub async fn query_ntp_server(server: &str) -> Result<ClockDrift, Box<dyn Error>> {
+    let server_addr = net::lookup_host(server)
+        .await?
+        .next()
+        .ok_or_else(|| new_error("NTP server not resolved"))?;
+    let bind_addr: SocketAddr = if server_addr.is_ipv6() {
+        "[::]:0".parse()?
+    } else {
+        "0.0.0.0:0".parse()?
+    };
+    let mut socket = UdpSocket::bind(bind_addr).await?;
+
+    let sent_at = Utc::now().timestamp_millis();
+    socket.send_to(&request_packet(sent_at), server_addr).await?;
+    let mut buf = [0u8; NTP_PACKET_SIZE];
+    let (n, _) = timeout(NTP_TIMEOUT, socket.recv_from(&mut buf)).await??;
+    let received_at = Utc::now().timestamp_millis();
+
+    let (offset, delay) = parse_response(&buf[..n], sent_at, received_at)?;
+    Ok(ClockDrift {
+        server: server.to_owned(),
+        offset,
+        delay,
+        checked_at: received_at,
+    })
+}
+
+async fn check_clock_drift(ctx: &AppContext) {
+    let config = &ctx.config.time;
+    for server in &config.ntp_servers {
+        match query_ntp_server(server).await {
+            Ok(drift) => {
+                if drift.offset.abs() > config.max_drift {
+                    warn!(
+                        "system clock drift {}ms against {}, exceeds max-drift {}ms",
+                        drift.offset, drift.server, config.max_drift
+                    );
+                    if !ctx.config.witness.private_key.is_empty() {
+                        warn!("running as witness, blocks might be produced out of slot, fix system clock ASAP");
+                    }
+                } else {
+                    debug!("system clock drift {}ms against {}", drift.offset, drift.server);
+                }
+                *ctx.clock_drift.write().unwrap() = Some(drift);
+                return;
+            }
+            Err(e) => debug!("NTP server {} failed: {}", server, e),
+        }
+    }
+    warn!("no NTP server available, system clock drift unknown");
+}
+
+/// Check clock drift periodically.
+pub async fn clock_service(ctx: Arc<AppContext>, mut signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
+    let config = &ctx.config.time;
+    if config.ntp_servers.is_empty() {
+        warn!("clock drift check disabled");
+        return Ok(());
+    }
+    info!("checking clock drift against {:?}", config.ntp_servers);
+
+    loop {
+        check_clock_drift(&ctx).await;
+        select! {
+            _ = signal.recv().fuse() => {
+                warn!("clock service closed");
+                break;
+            }
+            _ = delay_for(Duration::from_secs(config.check_interval)).fuse() => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ntp_response() {
+        let sent_at = 1_600_000_000_000;
+        let ts = to_ntp_timestamp(sent_at + 123);
+        assert_eq!(from_ntp_timestamp(&ts), sent_at + 123);
+
+        // server clock is 1s ahead, 20ms each way, 10ms processing
+        let mut packet = [0u8; NTP_PACKET_SIZE];
+        packet[0] = 0x1c;
+        packet[1] = 2;
+        packet[24..32].copy_from_slice(&request_packet(sent_at)[40..48]);
+        packet[32..40].copy_from_slice(&to_ntp_timestamp(sent_at + 1_020));
+        packet[40..48].copy_from_slice(&to_ntp_timestamp(sent_at + 1_030));
+        let (offset, delay) = parse_response(&packet, sent_at, sent_at + 50).unwrap();
+        assert_eq!(offset, 1_000);
+        assert_eq!(delay, 40);
+
+        assert!(parse_response(&packet, sent_at + 1, sent_at + 50).is_err());
+        packet[1] = 0;
+        assert!(parse_response(&packet, sent_at, sent_at + 50).is_err());
+    }
+}
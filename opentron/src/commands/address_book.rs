@@ -0,0 +1,93 @@
+//! Local address book tools.
+//!
+//! Entries are referenced as `@name` by address arguments of the CLI, and by `event.watch-addresses` and
+//! `witness.address` of config. `@chain:NAME` is resolved by a node, from on-chain account names.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use config::address_book::{alias_of, AddressBook, ON_CHAIN_PREFIX};
+use config::Config;
+use keys::Address;
+use log::info;
+use opentron_client::Client;
+
+use crate::util::new_error;
+
+fn book_path<P: AsRef<Path>>(config_path: P) -> Result<PathBuf, Box<dyn Error>> {
+    // NOTE: `Config::load_from_file` resolves aliases, which may refer to entries not added yet.
+    let config = Config::load_from_str(&fs::read_to_string(&config_path)?)?;
+    Ok(config.address_book_path(config_path))
+}
+
+/// Resolve an address argument: `@name` by the address book, `@chain:NAME` by the node, or a plain address.
+pub async fn resolve_address<P: AsRef<Path>>(
+    config_path: P,
+    client: &Client,
+    input: &str,
+) -> Result<Address, Box<dyn Error>> {
+    if let Some(acct_name) = alias_of(input).and_then(|name| name.strip_prefix(ON_CHAIN_PREFIX)) {
+        let addr = client
+            .account_by_name(acct_name)
+            .await
+            .map_err(|e| new_error(&e.to_string()))?
+            .ok_or_else(|| new_error(&format!("account name {:?} not found on chain", acct_name)))?;
+        info!("resolved {} => {}", input, addr);
+        return Ok(addr.parse()?);
+    }
+    match alias_of(input) {
+        Some(_) => AddressBook::load_from_file(book_path(config_path)?)?.resolve(input),
+        None => Ok(input.parse()?),
+    }
+}
+
+fn list(path: &Path) -> Result<(), Box<dyn Error>> {
+    let book = AddressBook::load_from_file(path)?;
+    for (name, addr) in book.iter() {
+        println!("@{}\t{}", name, addr);
+    }
+    info!("{} entries in {:?}", book.len(), path);
+    Ok(())
+}
+
+fn add(path: &Path, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let name = matches.value_of("NAME").expect("required in cli.yml; qed");
+    let addr: Address = matches.value_of("ADDRESS").expect("required in cli.yml; qed").parse()?;
+
+    let mut book = AddressBook::load_from_file(path)?;
+    match book.insert(name, addr)? {
+        Some(old_addr) => info!("@{} replaced, {} => {}", name, old_addr, addr),
+        None => info!("@{} added => {}", name, addr),
+    }
+    book.save_to_file(path)
+}
+
+fn remove(path: &Path, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let name = matches.value_of("NAME").expect("required in cli.yml; qed");
+
+    let mut book = AddressBook::load_from_file(path)?;
+    let addr = book
+        .remove(name)
+        .ok_or_else(|| new_error(&format!("@{} not found in address book", name)))?;
+    info!("@{} removed, was {}", name, addr);
+    book.save_to_file(path)
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let path = book_path(&config_path)?;
+    match matches.subcommand() {
+        ("list", Some(_)) => list(&path),
+        ("add", Some(arg_matches)) => add(&path, arg_matches),
+        ("remove", Some(arg_matches)) => remove(&path, arg_matches),
+        ("resolve", Some(arg_matches)) => {
+            let client = Client::new(arg_matches.value_of("node").expect("has default in cli.yml; qed"));
+            let input = arg_matches.value_of("ADDRESS").expect("required in cli.yml; qed");
+            let addr = resolve_address(&config_path, &client, input).await?;
+            println!("{}", addr);
+            Ok(())
+        }
+        _ => unreachable!("checked by SubcommandRequiredElseHelp; qed"),
+    }
+}
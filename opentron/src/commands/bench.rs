@@ -0,0 +1,126 @@
+//! Transaction throughput benchmark, on a throwaway state-db inited from genesis.
+//!
+//! With `--generate` or `--replay`, a deterministic test chain is written to or applied from a block file instead.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use clap::ArgMatches;
+use config::genesis::GenesisConfig;
+use config::Config;
+use log::info;
+
+use crate::manager::bench::{Benchmark, TransactionKind};
+use crate::manager::testchain::TestChain;
+use crate::manager::Manager;
+
+/// Peak and current resident memory of the process, from procfs.
+fn report_memory_usage() {
+    if let Ok(status) = fs::read_to_string("/proc/self/status") {
+        for line in status.lines().filter(|line| line.starts_with("VmHWM") || line.starts_with("VmRSS")) {
+            info!("memory {}", line.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+fn soak(
+    manager: &mut Manager,
+    path: &str,
+    num_accounts: usize,
+    rate: &str,
+    seed: &str,
+) -> Result<(), Box<dyn Error>> {
+    let rate: f64 = rate.parse()?;
+    let seed: u64 = seed.parse()?;
+    if rate < 0.0 || rate > 1.0 {
+        return Err("fault rate must be in [0, 1]".into());
+    }
+    let mut chain = TestChain::new(manager, num_accounts)?;
+    info!("soak testing test chain from {}, fault rate={} seed={}", path, rate, seed);
+    let report = chain.soak(path, seed, rate)?;
+    info!(
+        "{} faults in {} writes, {} of {} blocks failed and rolled back",
+        report.num_faults, report.num_writes, report.num_failed_blocks, report.num_blocks
+    );
+    info!("state commitment => {:?}", report.state_commitment);
+    Ok(())
+}
+
+#[cfg(not(feature = "fault-injection"))]
+fn soak(_: &mut Manager, _: &str, _: usize, _: &str, _: &str) -> Result<(), Box<dyn Error>> {
+    Err("--fault-rate needs the fault-injection feature".into())
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let num_blocks: usize = matches.value_of("blocks").expect("has default in cli.yml; qed").parse()?;
+    let txns_per_block: usize = matches
+        .value_of("txns-per-block")
+        .expect("has default in cli.yml; qed")
+        .parse()?;
+    let num_accounts: usize = matches.value_of("accounts").expect("has default in cli.yml; qed").parse()?;
+    let kinds = matches
+        .value_of("kinds")
+        .expect("has default in cli.yml; qed")
+        .split(',')
+        .map(|s| s.trim().parse::<TransactionKind>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut config = Config::load_from_file(&config_path)?;
+    let genesis_path = config_path.as_ref().parent().unwrap().join(&config.chain.genesis);
+    let genesis_config = GenesisConfig::load_from_file(&genesis_path)?;
+
+    let bench_dir = std::env::temp_dir().join(format!("opentron-bench-{}", std::process::id()));
+    config.storage.state_data_dir = bench_dir.join("state-data").to_string_lossy().into_owned();
+    config.storage.state_cache_dir = bench_dir.join("state-cache").to_string_lossy().into_owned();
+    info!("bench state-db => {:?}", bench_dir);
+
+    let ret = {
+        let mut manager = Manager::new(&config, &genesis_config);
+        if let Some(path) = matches.value_of("generate") {
+            TestChain::new(&mut manager, num_accounts)
+                .and_then(|mut chain| {
+                    info!(
+                        "generating {} blocks, {} txns per block, {} accounts => {}",
+                        num_blocks, txns_per_block, num_accounts, path
+                    );
+                    chain.generate(path, num_blocks, txns_per_block)
+                })
+                .map(|report| {
+                    info!(
+                        "{} txns in {} blocks, {} steps replaced by transfers",
+                        report.num_transactions, report.num_blocks, report.num_fallbacks
+                    );
+                    manager.execution_stats().report();
+                })
+        } else if let Some(rate) = matches.value_of("fault-rate") {
+            let path = matches.value_of("replay").expect("required by fault-rate in cli.yml; qed");
+            let seed = matches.value_of("fault-seed").expect("has default in cli.yml; qed");
+            soak(&mut manager, path, num_accounts, rate, seed)
+        } else {
+            let ret = if let Some(path) = matches.value_of("replay") {
+                TestChain::new(&mut manager, num_accounts).and_then(|mut chain| {
+                    info!("replaying test chain from {}, {} accounts", path, num_accounts);
+                    chain.replay(path)
+                })
+            } else {
+                Benchmark::new(&mut manager, num_accounts, kinds).and_then(|mut bench| {
+                    info!(
+                        "benchmarking {} blocks, {} txns per block, {} accounts",
+                        num_blocks, txns_per_block, num_accounts
+                    );
+                    bench.run(num_blocks, txns_per_block)
+                })
+            };
+            ret.map(|report| {
+                report.report();
+                manager.execution_stats().report();
+                report_memory_usage();
+            })
+        }
+    };
+    fs::remove_dir_all(&bench_dir)?;
+
+    ret
+}
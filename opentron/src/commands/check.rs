@@ -2,13 +2,15 @@ use std::path::Path;
 
 use clap::ArgMatches;
 use log::info;
-use chain_db::{ChainDB, CheckResult};
+use chain_db::CheckResult;
 use config::Config;
 
+use crate::util::{merkle_tree_patch, open_chain_db};
+
 pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::load_from_file(config_path)?;
     info!("config file loaded");
-    let db = ChainDB::new(&config.storage.data_dir);
+    let db = open_chain_db(&config.storage);
     info!("db opened");
 
     db.await_background_jobs();
@@ -20,23 +22,7 @@ pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> R
             println!("compact => {:?}", ret);
         }
         Some("merkle_tree") => {
-            /*
-            let patch = config
-                .merkle_tree_patch
-                .map(|patch| {
-                    patch
-                        .iter()
-                        .map(|patch| {
-                            (
-                                H256::from_slice(&hex::decode(&patch.txn).unwrap()),
-                                H256::from_slice(&hex::decode(&patch.tree_node_hash).unwrap()),
-                            )
-                        })
-                        .collect()
-                })
-                .unwrap_or_default();
-            */
-            db.verify_merkle_tree(&Default::default())?;
+            db.verify_merkle_tree(&merkle_tree_patch(&config.chain))?;
         }
         Some("parent_hash") => {
             while let CheckResult::ForkAt(pos) = db.verify_parent_hashes()? {
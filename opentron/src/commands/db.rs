@@ -0,0 +1,184 @@
+//! Database maintenance tools. The node must be stopped, since dbs are opened as primary instances.
+
+use std::error::Error;
+use std::path::Path;
+
+use clap::ArgMatches;
+use config::genesis::GenesisConfig;
+use config::Config;
+use log::{info, warn};
+use state::db::StateDB;
+use state::reconcile::{self, ReconcileResult};
+use state::reindex;
+use state::stats::{self, ASSET_COUNT_BUCKETS};
+
+use crate::util::open_chain_db;
+
+fn report(index: &str, checked: u64, missing: u64, mismatched: u64, stale: u64, dry_run: bool) {
+    if missing == 0 && mismatched == 0 && stale == 0 {
+        info!("✅ {} index: {} entries checked, consistent", index, checked);
+    } else {
+        warn!(
+            "❌ {} index: {} entries checked, missing={} mismatched={} stale={}{}",
+            index,
+            checked,
+            missing,
+            mismatched,
+            stale,
+            if dry_run { "" } else { ", fixed" }
+        );
+    }
+}
+
+/// Rebuild secondary indexes of chain-db and state-db, reporting inconsistencies.
+async fn reindex<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_from_file(config_path)?;
+    let dry_run = matches.is_present("dry-run");
+
+    {
+        let chain_db = open_chain_db(&config.storage);
+        info!("chain-db opened");
+        chain_db.await_background_jobs();
+
+        let ret = chain_db.reindex_transactions(dry_run)?;
+        report("txid", ret.checked, ret.missing, ret.mismatched, ret.stale, dry_run);
+    }
+
+    let mut state_db = StateDB::new(&config.storage.state_data_dir);
+    info!("state-db opened");
+
+    let ret = reindex::reindex_account_names(&mut state_db, dry_run)?;
+    report("account name", ret.checked, ret.missing, ret.mismatched, ret.stale, dry_run);
+    let ret = reindex::reindex_delegations(&mut state_db, dry_run)?;
+    report("delegation", ret.checked, ret.missing, ret.mismatched, ret.stale, dry_run);
+    let ret = reindex::reindex_delegation_reverse_index(&mut state_db, dry_run)?;
+    report("delegator", ret.checked, ret.missing, ret.mismatched, ret.stale, dry_run);
+    let ret = reindex::reindex_delegation_v2_reverse_index(&mut state_db, dry_run)?;
+    report("delegator v2", ret.checked, ret.missing, ret.mismatched, ret.stale, dry_run);
+    // Verify only, nothing to fix.
+    let ret = reindex::check_asset_names(&state_db)?;
+    report("asset name", ret.checked, ret.missing, ret.mismatched, ret.stale, true);
+
+    Ok(())
+}
+
+fn report_mismatches(what: &str, ret: &ReconcileResult, fix: bool) {
+    if ret.is_consistent() {
+        info!("✅ {}: {} entries checked, consistent", what, ret.checked);
+        return;
+    }
+    warn!(
+        "❌ {}: {} entries checked, {} mismatches{}",
+        what,
+        ret.checked,
+        ret.mismatches.len(),
+        if fix { ", fixed" } else { "" }
+    );
+    for mismatch in &ret.mismatches {
+        println!(
+            "{} {} stored={} expected={}",
+            mismatch.address, mismatch.field, mismatch.stored, mismatch.expected
+        );
+    }
+}
+
+/// Recompute account fields derived from delegations and votes, reporting inconsistencies.
+async fn reconcile<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_from_file(&config_path)?;
+    let genesis_path = config_path.as_ref().parent().unwrap().join(&config.chain.genesis);
+    let genesis_config = GenesisConfig::load_from_file(&genesis_path)?;
+    let fix = matches.is_present("fix");
+
+    let mut state_db = StateDB::new(&config.storage.state_data_dir);
+    info!("state-db opened");
+
+    // Delegations first, TRON Power of votes depends on them.
+    let ret = reconcile::reconcile_delegations(&mut state_db, !fix)?;
+    report_mismatches("delegation", &ret, fix);
+    let ret = reconcile::reconcile_votes(&mut state_db, &genesis_config, !fix)?;
+    report_mismatches("votes", &ret, fix);
+
+    Ok(())
+}
+
+/// Move transactions of old blocks to cold storage, and compact ChainDB to reclaim disk space.
+async fn tier<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_from_file(config_path)?;
+    if config.storage.cold_data_dir.is_none() {
+        return Err("cold-data-dir is not configured".into());
+    }
+    let age = match matches.value_of("age") {
+        Some(age) => age.parse::<u64>()?,
+        None => config.storage.cold_block_age,
+    };
+
+    let chain_db = open_chain_db(&config.storage);
+    info!("chain-db opened");
+    chain_db.await_background_jobs();
+
+    let height = chain_db.get_block_height() as u64;
+    let num = height.saturating_sub(age);
+    if num <= chain_db.get_cold_block_number() {
+        info!("✅ blocks below #{} are already in cold storage", chain_db.get_cold_block_number());
+        return Ok(());
+    }
+    let moved = chain_db.move_to_cold_storage(num)?;
+    info!("✅ {} txns of blocks below #{} moved to cold storage", moved, num);
+
+    chain_db.flush()?;
+    if !matches.is_present("no-compact") {
+        info!("compacting chain-db");
+        chain_db.compact_db()?;
+    }
+    Ok(())
+}
+
+/// Report accounts by number of TRC10 tokens and serialized size, to find the biggest accounts.
+async fn account_stats<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_from_file(config_path)?;
+    let top: usize = matches
+        .value_of("top")
+        .expect("has default in cli.yml; qed")
+        .parse()
+        .map_err(|_| "invalid --top")?;
+
+    let state_db = StateDB::new(&config.storage.state_data_dir);
+    info!("state-db opened");
+
+    let stats = stats::account_stats(&state_db, top);
+    info!(
+        "{} accounts, {} token balances, {} bytes in total",
+        stats.num_accounts, stats.num_asset_entries, stats.total_size
+    );
+    if stats.num_empty_asset_entries > 0 {
+        warn!("❌ {} zero token balances not pruned", stats.num_empty_asset_entries);
+    }
+
+    println!("tokens\taccounts");
+    let mut lower = 0;
+    for (i, count) in stats.by_asset_count.iter().enumerate() {
+        match ASSET_COUNT_BUCKETS.get(i) {
+            Some(&upper) if upper == lower => println!("{}\t{}", upper, count),
+            Some(&upper) => println!("{}-{}\t{}", lower, upper, count),
+            None => println!("{}+\t{}", lower, count),
+        }
+        lower = ASSET_COUNT_BUCKETS.get(i).map(|upper| upper + 1).unwrap_or(lower);
+    }
+
+    println!();
+    println!("address\ttokens\tbytes");
+    for account in &stats.biggest {
+        println!("{}\t{}\t{}", account.address, account.num_assets, account.size);
+    }
+    Ok(())
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        ("reindex", Some(arg_matches)) => reindex(config_path, arg_matches).await,
+        ("reconcile", Some(arg_matches)) => reconcile(config_path, arg_matches).await,
+        ("tier", Some(arg_matches)) => tier(config_path, arg_matches).await,
+        ("stats", Some(arg_matches)) => account_stats(config_path, arg_matches).await,
+        _ => unreachable!("checked by SubcommandRequiredElseHelp; qed"),
+    }
+}
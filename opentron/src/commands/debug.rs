@@ -0,0 +1,96 @@
+//! Debugging tools. The node must be stopped, since state-db is opened as primary instance.
+
+use std::error::Error;
+use std::path::Path;
+
+use clap::ArgMatches;
+use log::info;
+use proto2::chain::transaction::result::ContractStatus;
+
+use crate::context::AppContext;
+use crate::manager::Manager;
+
+fn hex_or_none(value: &Option<Vec<u8>>) -> String {
+    value.as_ref().map(hex::encode).unwrap_or_else(|| "<none>".into())
+}
+
+/// Re-execute a block and print per-transaction traces, without persisting anything.
+async fn replay_block<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let block_number: u64 = matches.value_of("NUM").expect("required in cli.yml; qed").parse()?;
+    let print_diff = matches.is_present("diff");
+
+    let ctx = AppContext::from_config(config_path)?;
+    let mut db_manager = Manager::new(&ctx.config, &ctx.genesis_config);
+    let ref_block_hashes = ctx
+        .chain_db
+        .ref_block_hashes_of_block_num(db_manager.latest_block_number());
+    db_manager.init_ref_blocks(ref_block_hashes);
+
+    let block = ctx.chain_db.get_block_by_number(block_number)?;
+    info!("replaying block #{} txns={}", block.number(), block.transactions.len());
+    let report = db_manager.replay_block(&block)?;
+
+    println!("block #{} {:?}", report.block_number, block.hash());
+    for (i, trace) in report.traces.iter().enumerate() {
+        match trace.receipt {
+            Some(ref receipt) => {
+                let (energy, bandwidth) = receipt
+                    .resource_receipt
+                    .as_ref()
+                    .map(|r| (r.energy_usage + r.origin_energy_usage, r.bandwidth_usage))
+                    .unwrap_or_default();
+                let vm_status = ContractStatus::from_i32(receipt.vm_status).unwrap_or_default();
+                let mismatch = trace.expected_status != ContractStatus::Default && trace.expected_status != vm_status;
+                println!(
+                    "#{:<4} {:?} {:?} success={} fee={} energy={} bandwidth={} vm_status={:?}{}",
+                    i,
+                    trace.hash,
+                    trace.contract_type,
+                    receipt.success,
+                    receipt.fee,
+                    energy,
+                    bandwidth,
+                    vm_status,
+                    if mismatch {
+                        format!(" MISMATCH expected={:?}", trace.expected_status)
+                    } else {
+                        "".into()
+                    }
+                );
+                if !receipt.vm_message.is_empty() {
+                    println!("      vm_message={}", String::from_utf8_lossy(&receipt.vm_message));
+                }
+            }
+            None => println!("#{:<4} {:?} {:?} not executed", i, trace.hash, trace.contract_type),
+        }
+    }
+
+    if print_diff {
+        println!("{} state changes", report.changes.len());
+        for change in &report.changes {
+            println!(
+                "col={} key={}\n  - {}\n  + {}",
+                change.col,
+                hex::encode(&change.key),
+                hex_or_none(&change.old),
+                hex_or_none(&change.new)
+            );
+        }
+    }
+
+    if let Some(commitment) = report.state_commitment {
+        println!("state commitment: {:?}", commitment);
+    }
+    match report.error {
+        Some(e) => println!("❌ block aborted: {}", e),
+        None => println!("✅ block executed, all changes discarded"),
+    }
+    Ok(())
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        ("replay-block", Some(arg_matches)) => replay_block(config_path, arg_matches).await,
+        _ => unreachable!("checked by SubcommandRequiredElseHelp; qed"),
+    }
+}
@@ -23,11 +23,26 @@ pub async fn main<P: AsRef<Path>>(config_path: P, _matches: &ArgMatches<'_>) ->
     let mut n_blocks = 0;
 
     let start_block = db_manager.latest_block_number() as u64 + 1;
+    // State-db head is checked against chain-db when the context is created, the gap is applied here.
+    info!(
+        "applying chain-db blocks from #{}, chain-db head #{}",
+        start_block,
+        ctx.chain_db.get_block_height()
+    );
 
     // 741457, first AssetIssueContract
     // 768881, first CreateSmartContract
+    let mut next_blk = None;
     for i in start_block.. {
-        let blk = ctx.chain_db.get_block_by_number(i)?;
+        let blk = match next_blk.take() {
+            Some(blk) => blk,
+            None => ctx.chain_db.get_block_by_number(i)?,
+        };
+        // Read ahead, so that state of the next block is prefetched while this one executes.
+        next_blk = ctx.chain_db.get_block_by_number(i + 1).ok();
+        if let Some(ref next) = next_blk {
+            db_manager.prefetch_block(next);
+        }
 
         db_manager.push_block(&blk)?;
 
@@ -38,6 +53,12 @@ pub async fn main<P: AsRef<Path>>(config_path: P, _matches: &ArgMatches<'_>) ->
             info!("speed => {}blocks/s", 1_000 * 1_000 / (now - start_time));
             start_time = Utc::now().timestamp_millis();
         }
+        if n_blocks % 100_000 == 0 {
+            db_manager.execution_stats().report();
+            if let Some(prefetcher) = db_manager.prefetcher() {
+                prefetcher.report();
+            }
+        }
     }
 
     Ok(())
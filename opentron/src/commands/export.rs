@@ -0,0 +1,104 @@
+//! Export blocks to a portable block file, or accounts as a rich list.
+//!
+//! The file is a sequence of varint length-delimited `Block` protobuf messages, the same layout as java-protobuf's
+//! `writeDelimitedTo`/`parseDelimitedFrom`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use bytes::BytesMut;
+use clap::ArgMatches;
+use config::Config;
+use log::info;
+use prost::Message;
+use proto2::chain::Block;
+use state::db::StateDB;
+use state::stats;
+
+use crate::util::open_chain_db;
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    if let ("accounts", Some(arg_matches)) = matches.subcommand() {
+        return export_accounts(config_path, arg_matches);
+    }
+
+    let config = Config::load_from_file(config_path)?;
+    info!("config file loaded");
+    let db = open_chain_db(&config.storage);
+    info!("db opened");
+
+    let block_height = db.get_block_height() as u64;
+    let from: u64 = matches.value_of("from").map(|v| v.parse().expect("height number")).unwrap_or(0);
+    let to: u64 = matches
+        .value_of("to")
+        .map(|v| v.parse().expect("height number"))
+        .unwrap_or(block_height)
+        .min(block_height);
+    let path = matches.value_of("FILE").expect("required in cli.yml; qed");
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut buf = BytesMut::with_capacity(4 * 1024);
+
+    info!("export blocks {}..={} to {}", from, to, path);
+    for num in from..=to {
+        let block: Block = db.get_block_by_number(num)?.into();
+        buf.clear();
+        block.encode_length_delimited(&mut buf)?;
+        writer.write_all(&buf)?;
+
+        if num % 10_000 == 0 {
+            info!("exported block #{}", num);
+        }
+    }
+    writer.flush()?;
+    info!("export finished, {} blocks", (to + 1).saturating_sub(from));
+
+    Ok(())
+}
+
+/// Export the `--top` accounts owning at least `--min-balance`, with their frozen amounts and TRC10 holdings.
+fn export_accounts<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load_from_file(config_path)?;
+    let min_balance: i64 = matches
+        .value_of("min-balance")
+        .expect("has default in cli.yml; qed")
+        .parse()
+        .map_err(|_| "invalid --min-balance")?;
+    let top: usize = matches
+        .value_of("top")
+        .expect("has default in cli.yml; qed")
+        .parse()
+        .map_err(|_| "invalid --top")?;
+
+    let state_db = StateDB::new(&config.storage.state_data_dir);
+    info!("state-db opened");
+
+    let accounts = stats::rich_list(&state_db, min_balance, top);
+    let mut writer: Box<dyn Write> = match matches.value_of("out") {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    writeln!(writer, "address\tbalance\tfrozen\ttotal\tassets")?;
+    for account in &accounts {
+        let assets = account
+            .assets
+            .iter()
+            .map(|(token_id, balance)| format!("{}:{}", token_id, balance))
+            .collect::<Vec<_>>()
+            .join(";");
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}",
+            account.address,
+            account.balance,
+            account.frozen,
+            account.total(),
+            assets
+        )?;
+    }
+    writer.flush()?;
+    info!("export finished, {} accounts", accounts.len());
+
+    Ok(())
+}
@@ -2,13 +2,14 @@ use std::path::Path;
 
 use clap::ArgMatches;
 use log::info;
-use chain_db::ChainDB;
 use config::Config;
 
+use crate::util::open_chain_db;
+
 pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::load_from_file(config_path)?;
     info!("config file loaded");
-    let db = ChainDB::new(&config.storage.data_dir);
+    let db = open_chain_db(&config.storage);
     info!("db opened");
 
     db.await_background_jobs();
@@ -0,0 +1,142 @@
+//! Genesis tools for devnets.
+//!
+//! `genesis from-snapshot` turns an account snapshot exported from mainnet into a genesis config, so that a devnet
+//! starts with mainnet-like balances, without replaying the real chain.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use clap::ArgMatches;
+use config::genesis::{Alloc, GenesisConfig};
+use config::Config;
+use keys::Address;
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::util::new_error;
+
+/// An account in a snapshot file. Amounts are in SUN.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotEntry {
+    address: String,
+    balance: i64,
+    #[serde(default, alias = "frozen")]
+    frozen_for_bandwidth: i64,
+    #[serde(default)]
+    frozen_for_energy: i64,
+}
+
+/// CSV columns: address, balance[, frozen_for_bandwidth[, frozen_for_energy]]. An optional header line starts with
+/// `address`.
+fn parse_csv(content: &str) -> Result<Vec<SnapshotEntry>, Box<dyn Error>> {
+    let mut entries = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || (i == 0 && line.starts_with("address")) {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        if fields.len() < 2 || fields.len() > 4 {
+            return Err(new_error(&format!("line {}: expected 2 to 4 columns", i + 1)));
+        }
+        let amount_at = |pos: usize| -> Result<i64, Box<dyn Error>> {
+            match fields.get(pos) {
+                Some(field) if !field.is_empty() => field
+                    .parse()
+                    .map_err(|_| new_error(&format!("line {}: invalid amount {:?}", i + 1, field))),
+                _ => Ok(0),
+            }
+        };
+        entries.push(SnapshotEntry {
+            address: fields[0].to_owned(),
+            balance: amount_at(1)?,
+            frozen_for_bandwidth: amount_at(2)?,
+            frozen_for_energy: amount_at(3)?,
+        });
+    }
+    Ok(entries)
+}
+
+fn load_snapshot(path: &str) -> Result<Vec<SnapshotEntry>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    if path.ends_with(".json") {
+        Ok(serde_json::from_str(&content)?)
+    } else {
+        parse_csv(&content)
+    }
+}
+
+/// Append snapshot accounts to the allocs of a template genesis, i.e. the one in config.
+///
+/// Witnesses and named allocs of the template, like Blackhole, are kept. Snapshot accounts already in the template
+/// are skipped.
+async fn from_snapshot<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let snapshot_path = matches.value_of("SNAPSHOT").expect("required in cli.yml; qed");
+    let template_path = match matches.value_of("template") {
+        Some(path) => Path::new(path).to_owned(),
+        None => {
+            let config = Config::load_from_file(&config_path)?;
+            config_path.as_ref().parent().unwrap().join(&config.chain.genesis)
+        }
+    };
+    let mut genesis = GenesisConfig::load_from_file(&template_path)?;
+    info!("genesis template loaded from {:?}", template_path);
+
+    let entries = load_snapshot(snapshot_path)?;
+    info!("{} accounts loaded from snapshot {}", entries.len(), snapshot_path);
+
+    let mut seen = HashSet::new();
+    for alloc in &genesis.allocs {
+        seen.insert(alloc.address.parse::<Address>()?);
+    }
+    let mut total_balance = 0_i64;
+    let mut total_frozen = 0_i64;
+    for entry in entries {
+        let addr: Address = entry
+            .address
+            .parse()
+            .map_err(|_| new_error(&format!("invalid address {:?}", entry.address)))?;
+        if entry.balance < 0 || entry.frozen_for_bandwidth < 0 || entry.frozen_for_energy < 0 {
+            return Err(new_error(&format!("negative amount of {}", addr)));
+        }
+        if !seen.insert(addr) {
+            warn!("duplicated account {}, skipped", addr);
+            continue;
+        }
+        total_balance += entry.balance;
+        total_frozen += entry.frozen_for_bandwidth + entry.frozen_for_energy;
+        genesis.allocs.push(Alloc {
+            address: addr.to_string(),
+            name: String::new(),
+            balance: entry.balance,
+            frozen_for_bandwidth: entry.frozen_for_bandwidth,
+            frozen_for_energy: entry.frozen_for_energy,
+        });
+    }
+    info!(
+        "genesis allocs={} snapshot balance={} frozen={}",
+        genesis.allocs.len(),
+        total_balance,
+        total_frozen
+    );
+
+    let content = genesis.to_json_string()?;
+    match matches.value_of("out") {
+        Some(path) => {
+            fs::write(path, content)?;
+            info!("genesis written to {}", path);
+        }
+        None => println!("{}", content),
+    }
+    Ok(())
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        ("from-snapshot", Some(arg_matches)) => from_snapshot(config_path, arg_matches).await,
+        _ => unreachable!("checked by SubcommandRequiredElseHelp; qed"),
+    }
+}
@@ -0,0 +1,61 @@
+//! Import blocks from a portable block file, see `commands::export` for the file format.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use chain::IndexedBlock;
+use clap::ArgMatches;
+use config::Config;
+use log::{info, warn};
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::Block;
+
+use crate::util::{open_chain_db, read_varint};
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load_from_file(config_path)?;
+    info!("config file loaded");
+    let db = open_chain_db(&config.storage);
+    info!("db opened");
+
+    let path = matches.value_of("FILE").expect("required in cli.yml; qed");
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buf = Vec::with_capacity(4 * 1024);
+
+    info!("import blocks from {}, block height => {}", path, db.get_block_height());
+    let mut n_imported = 0;
+    while let Some(len) = read_varint(&mut reader)? {
+        buf.resize(len as usize, 0);
+        reader.read_exact(&mut buf)?;
+        let block = IndexedBlock::from_raw(Block::decode(&buf[..])?);
+
+        if db.has_block(&block) {
+            continue;
+        }
+        if block.number() > 0 && !db.has_block_id(&H256::from_slice(block.parent_hash())) {
+            warn!("parent block of #{} not found, stop importing", block.number());
+            break;
+        }
+        if !block.verify_merkle_root_hash() {
+            warn!("merkle root hash mismatch for block #{}, stop importing", block.number());
+            break;
+        }
+
+        db.insert_block(&block)?;
+        db.update_block_height(block.number());
+        n_imported += 1;
+
+        if block.number() % 10_000 == 0 {
+            info!("imported block #{}", block.number());
+        }
+    }
+    info!(
+        "import finished, {} blocks, block height => {}",
+        n_imported,
+        db.get_block_height()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use std::path::Path;
+
+use clap::ArgMatches;
+use config::Config;
+use log::{info, warn};
+use state::db::StateDB;
+use state::migration::run_migrations;
+use state::DynamicProperty;
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load_from_file(config_path)?;
+    info!("config file loaded");
+    let mut state_db = StateDB::new(&config.storage.state_data_dir);
+    info!("state-db opened");
+
+    if state_db.get(&DynamicProperty::DbVersion)?.is_none() {
+        warn!("state-db is not inited, nothing to migrate");
+        return Ok(());
+    }
+
+    let dry_run = matches.is_present("dry-run");
+    let version = run_migrations(&mut state_db, dry_run)?;
+    info!("state-db version => {}", version);
+
+    Ok(())
+}
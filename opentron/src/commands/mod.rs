@@ -1,3 +1,12 @@
+pub mod address_book;
+pub mod bench;
 pub mod check;
+pub mod db;
+pub mod debug;
 pub mod dev;
+pub mod export;
 pub mod fix;
+pub mod genesis;
+pub mod import;
+pub mod migrate;
+pub mod tx;
@@ -0,0 +1,198 @@
+//! Offline transaction tools, for air-gapped signing and inspection.
+//!
+//! Transaction files are serialized `Transaction` protobuf messages. Signing appends a signature, so a multisig
+//! transaction can be signed on different machines one by one.
+//!
+//! `deploy` is the only command that talks to a node, to refer the latest block and to broadcast. Bytecode is checked
+//! before, see `Deployment::check`.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use chain::{hash, IndexedTransaction};
+use chrono::{TimeZone, Utc};
+use clap::ArgMatches;
+use keys::{Address, Private};
+use log::{info, warn};
+use opentron_client::{Client, Deployment};
+use prost::Message;
+use proto2::chain::Transaction;
+
+use super::address_book::resolve_address;
+use crate::util::new_error;
+
+fn read_transaction(path: &str) -> Result<IndexedTransaction, Box<dyn Error>> {
+    let raw = fs::read(path)?;
+    let txn = Transaction::decode(&raw[..])?;
+    if txn.raw_data.is_none() {
+        return Err(new_error("transaction has no raw_data"));
+    }
+    Ok(IndexedTransaction::from_raw(txn))
+}
+
+fn raw_data_bytes(txn: &IndexedTransaction) -> Vec<u8> {
+    hash::raw_data_bytes(txn.raw.raw_data.as_ref().unwrap())
+}
+
+/// Read a hex private key from a file, or from stdin if the path is `-`.
+///
+/// Never from the command line, which leaks to shell history and `ps`.
+fn read_private_key(path: &str) -> Result<Private, Box<dyn Error>> {
+    let mut key = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut key)?;
+    } else {
+        key = fs::read_to_string(path)?;
+    }
+    Ok(key.trim().parse()?)
+}
+
+fn sign(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let private = read_private_key(matches.value_of("key-file").expect("required in cli.yml; qed"))?;
+    let in_path = matches.value_of("in").expect("required in cli.yml; qed");
+    let out_path = matches.value_of("out").expect("required in cli.yml; qed");
+
+    let mut txn = read_transaction(in_path)?;
+    let signer = Address::from_private(&private);
+
+    let signers = txn.recover_owner()?;
+    if signers.contains(&signer) {
+        return Err(new_error(&format!("transaction is already signed by {}", signer)));
+    }
+
+    let signature = private.sign(&raw_data_bytes(&txn))?;
+    txn.raw.signatures.push(signature.as_bytes().to_vec());
+
+    let mut buf = Vec::with_capacity(txn.raw.encoded_len());
+    txn.raw.encode(&mut buf)?;
+    fs::write(out_path, &buf)?;
+
+    info!(
+        "signed txn {} by {}, {} signature(s) => {}",
+        hex::encode(txn.hash.as_bytes()),
+        signer,
+        txn.raw.signatures.len(),
+        out_path
+    );
+    Ok(())
+}
+
+fn verify(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let in_path = matches.value_of("in").expect("required in cli.yml; qed");
+    let txn = read_transaction(in_path)?;
+    let raw_data = txn.raw.raw_data.as_ref().unwrap();
+
+    println!("txid: {}", hex::encode(txn.hash.as_bytes()));
+    if let Some(cntr) = raw_data.contract.as_ref() {
+        println!("contract type: {}, permission id: {}", cntr.r#type, cntr.permission_id);
+    }
+    let expiration = Utc.timestamp_millis(raw_data.expiration);
+    println!("expiration: {}", expiration);
+    if expiration < Utc::now() {
+        warn!("transaction is expired");
+    }
+
+    if txn.raw.signatures.is_empty() {
+        return Err(new_error("transaction is not signed"));
+    }
+    let signers = txn.recover_owner()?;
+    for (i, signer) in signers.iter().enumerate() {
+        println!("signature #{}: {}", i, signer);
+    }
+
+    let mut deduped = signers.clone();
+    deduped.sort();
+    deduped.dedup();
+    if deduped.len() != signers.len() {
+        return Err(new_error("duplicate signature"));
+    }
+
+    // NOTE: Permission weights and threshold depend on account state, which is checked by the node.
+    println!("all {} signature(s) are valid", signers.len());
+    Ok(())
+}
+
+fn decode(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let in_path = matches.value_of("in").expect("required in cli.yml; qed");
+    let txn = read_transaction(in_path)?;
+
+    let mut json = serde_json::to_value(&txn.raw)?;
+    json["txID"] = hex::encode(txn.hash.as_bytes()).into();
+    json["raw_data_hex"] = hex::encode(raw_data_bytes(&txn)).into();
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
+async fn deploy<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let abi = fs::read_to_string(matches.value_of("abi").expect("required in cli.yml; qed"))?;
+    let bin = fs::read_to_string(matches.value_of("bin").expect("required in cli.yml; qed"))?;
+    let name = matches.value_of("name").expect("has default in cli.yml; qed");
+    let fee_limit: i64 = matches.value_of("fee-limit").expect("has default in cli.yml; qed").parse()?;
+    let args: Vec<String> = matches
+        .values_of("arg")
+        .map(|vals| vals.map(ToOwned::to_owned).collect())
+        .unwrap_or_default();
+    let client = Client::new(matches.value_of("node").expect("has default in cli.yml; qed"));
+    let mut libs = vec![];
+    for lib in matches.values_of("lib").into_iter().flatten() {
+        let pos = lib.rfind('=').ok_or_else(|| new_error("library must be in NAME=ADDRESS format"))?;
+        let addr = resolve_address(&config_path, &client, &lib[pos + 1..]).await?;
+        libs.push((lib[..pos].to_owned(), addr));
+    }
+
+    let deployment = Deployment::from_solc(name, &abi, &bin, &libs, &args).map_err(|e| new_error(&e.to_string()))?;
+    info!(
+        "bytecode size: {} bytes, estimated code deposit energy: {}",
+        deployment.bytecode.len(),
+        deployment.estimate_energy()
+    );
+
+    let upgrades = client.tvm_upgrades().await.map_err(|e| new_error(&e.to_string()))?;
+    let report = deployment.check(&upgrades);
+    for warning in &report.warnings {
+        warn!("⚠️  {}", warning);
+    }
+    for error in &report.errors {
+        warn!("❌ {}", error);
+    }
+    if !report.errors.is_empty() && !matches.is_present("force") {
+        return Err(new_error("deployment would fail on chain, use --force to deploy anyway"));
+    }
+
+    if let Some(key_path) = matches.value_of("key-file") {
+        let private = read_private_key(key_path)?;
+        let txid = client
+            .deploy(&deployment, &private, fee_limit)
+            .await
+            .map_err(|e| new_error(&e.to_string()))?;
+        info!("broadcast txn {} by {}", txid, Address::from_private(&private));
+    } else {
+        let owner = matches
+            .value_of("owner")
+            .ok_or_else(|| new_error("--owner is required for an unsigned transaction"))?;
+        let owner = resolve_address(&config_path, &client, owner).await?;
+        let out_path = matches.value_of("out").expect("required unless key-file in cli.yml; qed");
+        let txn = client
+            .deploy_transaction(&deployment, owner, fee_limit)
+            .await
+            .map_err(|e| new_error(&e.to_string()))?;
+
+        let mut buf = Vec::with_capacity(txn.encoded_len());
+        txn.encode(&mut buf)?;
+        fs::write(out_path, &buf)?;
+        info!("unsigned txn {} => {}", hex::encode(opentron_client::transaction_id(&txn).as_bytes()), out_path);
+    }
+    Ok(())
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        ("sign", Some(arg_matches)) => sign(arg_matches),
+        ("verify", Some(arg_matches)) => verify(arg_matches),
+        ("decode", Some(arg_matches)) => decode(arg_matches),
+        ("deploy", Some(arg_matches)) => deploy(config_path, arg_matches).await,
+        _ => unreachable!("checked by SubcommandRequiredElseHelp; qed"),
+    }
+}
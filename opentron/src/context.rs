@@ -1,17 +1,51 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
+use std::net::SocketAddr;
 use std::path::Path;
-use std::sync::atomic::{AtomicBool, AtomicU32};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32};
+use std::sync::{Arc, Mutex, RwLock};
 
+use chain::IndexedTransaction;
 use chain_db::ChainDB;
+use chrono::Utc;
 use futures::channel::oneshot;
-use log::info;
+use log::{info, warn};
 use primitive_types::H256;
 use proto2::common::BlockId;
+use tokio::sync::broadcast;
+use state::db::ReadOnlySolidStateDB;
+use state::keys as state_keys;
 use config::Config;
 use config::genesis::GenesisConfig;
 
+use crate::abi::AbiRegistry;
+use crate::clock::ClockDrift;
+use crate::light::KnownWitnesses;
+use crate::manager::constant::ConstantCallService;
+use crate::mempool::{broadcast_pending_transactions, check_sponsored_bundle, TransactionPool};
+use crate::metrics::{self, Registry};
+use crate::supervisor::ServiceHealth;
+use crate::util::{merkle_tree_patch, open_chain_db};
+
+/// A connected channel peer.
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    /// Remote socket address of the connection.
+    pub remote_addr: SocketAddr,
+    /// Advertised endpoint in handshake.
+    pub advertised_address: String,
+    pub advertised_port: i32,
+    pub node_id: Vec<u8>,
+    pub p2p_version: i32,
+    /// Software version in handshake, empty for older java-tron peers.
+    pub code_version: String,
+    /// Highest block number known of the peer.
+    pub head_block_number: i64,
+    /// Round trip time of the latest handshake or ping, in ms.
+    pub latency: i64,
+    pub connected_at: i64,
+}
+
 pub struct AppContext {
     pub outbound_ip: String,
     pub node_id: Vec<u8>,
@@ -19,11 +53,124 @@ pub struct AppContext {
     pub config: Config,
     pub genesis_config: GenesisConfig,
     pub chain_db: ChainDB,
+    /// Read-only view of the state-db, as a RocksDB secondary instance. None if state-db is not created.
+    pub state_db: Option<ReadOnlySolidStateDB>,
     pub running: Arc<AtomicBool>,
     pub num_active_connections: AtomicU32,
     pub recent_blk_ids: RwLock<HashSet<H256>>,
     pub syncing: RwLock<bool>,
     pub peers: RwLock<Vec<oneshot::Sender<()>>>,
+    /// Connected channel peers, by remote address.
+    pub peer_infos: RwLock<HashMap<SocketAddr, PeerInfo>>,
+    /// Stop request from admin API, handled by the node runner.
+    pub stop_request: Mutex<Option<oneshot::Sender<()>>>,
+    /// Pending transactions, received by broadcast.
+    pub pending_pool: RwLock<TransactionPool>,
+    /// Latest system clock drift check.
+    pub clock_drift: RwLock<Option<ClockDrift>>,
+    /// Ids of fast-validated gossip blocks, with the peer they came from, to be relayed to other peers.
+    pub block_relay: broadcast::Sender<(SocketAddr, H256)>,
+    /// Ids of pending transactions to be announced to peers.
+    pub txn_relay: broadcast::Sender<Vec<H256>>,
+    /// Health of supervised services, by name.
+    pub services: RwLock<BTreeMap<&'static str, ServiceHealth>>,
+    /// Metrics registry, shared by exporters.
+    pub metrics: Registry,
+    /// Expiration of the producer HA lease held by this node, in ms. 0 if not held.
+    pub producer_lease: AtomicI64,
+    /// Verified contract ABIs, node-local.
+    pub abi_registry: AbiRegistry,
+    /// Witnesses checked against block headers, in light mode.
+    pub light_witnesses: Option<KnownWitnesses>,
+    /// Constant calls of the API. None unless GraphQL is enabled and state-db is created.
+    pub constant_calls: Option<ConstantCallService>,
+    /// Node hashes of `chain.merkle-tree-patch`, by transaction id.
+    pub merkle_tree_patch: HashMap<H256, H256>,
+}
+
+/// Head block of state-db, by its dynamic properties.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StateHead {
+    number: i64,
+    timestamp: Option<i64>,
+    hash: Option<H256>,
+}
+
+/// Compare state-db head with the block of the same number in chain-db, `(hash, timestamp)` or `None` if not found.
+///
+/// Returns the number of chain-db blocks not applied to state-db yet.
+fn compare_state_head(state: &StateHead, chain_height: i64, chain_block: Option<(H256, i64)>) -> Result<i64, String> {
+    if state.number > chain_height {
+        return Err(format!(
+            "state-db is at block #{}, ahead of chain-db head #{}, chain-db lost blocks in a crash?",
+            state.number, chain_height
+        ));
+    }
+    // Genesis state has no block hash.
+    if state.number == 0 {
+        return Ok(chain_height);
+    }
+    let state_timestamp = state
+        .timestamp
+        .ok_or_else(|| format!("state-db head #{} has no block timestamp, state-db is corrupted", state.number))?;
+    let (hash, timestamp) =
+        chain_block.ok_or_else(|| format!("block #{} of state-db not found in chain-db", state.number))?;
+    if state.hash.map(|state_hash| state_hash != hash).unwrap_or(false) || state_timestamp != timestamp {
+        return Err(format!(
+            "state-db head #{} {:?} at {} is not the chain-db block {:?} at {}, state-db is on a fork",
+            state.number,
+            state.hash.unwrap_or_default(),
+            state_timestamp,
+            hash,
+            timestamp
+        ));
+    }
+    Ok(chain_height - state.number)
+}
+
+/// Refuse to serve state of a block that is not in chain-db, i.e. after a crash losing chain-db writes.
+fn check_state_db_head(
+    chain_db: &ChainDB,
+    state_db: &ReadOnlySolidStateDB,
+    state_data_dir: &str,
+) -> Result<(), Box<dyn Error>> {
+    let number = match state_db.get(&state_keys::DynamicProperty::LatestBlockNumber)? {
+        Some(number) => number,
+        // Not inited yet.
+        None => return Ok(()),
+    };
+    let state = StateHead {
+        number,
+        timestamp: state_db.get(&state_keys::DynamicProperty::LatestBlockTimestamp)?,
+        hash: state_db.get(&state_keys::LatestBlockHash)?,
+    };
+    let chain_height = chain_db.get_block_height();
+    let chain_block = if number <= chain_height {
+        chain_db
+            .get_block_by_number(number as u64)
+            .ok()
+            .map(|block| (*block.hash(), block.timestamp()))
+    } else {
+        None
+    };
+
+    match compare_state_head(&state, chain_height, chain_block) {
+        Ok(0) => Ok(()),
+        Ok(gap) => {
+            warn!(
+                "state-db is {} blocks behind chain-db head #{}, stale until they are applied by `opentron dev`, \
+                 see stateBlockGap of nodeInfo",
+                gap, chain_height
+            );
+            Ok(())
+        }
+        Err(reason) => Err(format!(
+            "{}. Import the missing blocks with `opentron import` then restart, or move {} away to rebuild state-db \
+             from genesis with `opentron dev`",
+            reason, state_data_dir
+        )
+        .into()),
+    }
 }
 
 impl AppContext {
@@ -35,7 +182,7 @@ impl AppContext {
         let genesis_config = GenesisConfig::load_from_file(&genesis_path)?;
         let genesis_blk = genesis_config.to_indexed_block()?;
 
-        let chain_db = ChainDB::new(&config.storage.data_dir);
+        let chain_db = open_chain_db(&config.storage);
 
         if !chain_db.has_block(&genesis_blk) {
             if let Ok(_) = chain_db.get_genesis_block() {
@@ -51,14 +198,41 @@ impl AppContext {
             hash: genesis_blk.header.hash.as_ref().to_owned(),
         };
 
+        let state_db = if Path::new(&config.storage.state_data_dir).join("CURRENT").exists() {
+            info!("state-db opened as secondary instance");
+            Some(ReadOnlySolidStateDB::new(
+                &config.storage.state_data_dir,
+                &config.storage.state_cache_dir,
+            ))
+        } else {
+            None
+        };
+        if let Some(ref state_db) = state_db {
+            check_state_db_head(&chain_db, state_db, &config.storage.state_data_dir)?;
+        }
+
         let node_id = chain_db.get_node_id();
         info!("node id => {}", hex::encode(&node_id));
         info!("p2p version => {}", config.chain.p2p_version);
         info!("genesis block id => {}", hex::encode(&genesis_block_id.hash));
+        info!(
+            "chain id => 0x{}",
+            hex::encode(&genesis_block_id.hash[genesis_block_id.hash.len() - 4..])
+        );
         info!("chain-db loaded");
 
+        let abi_registry = AbiRegistry::open(&config.graphql.abi_registry_dir)?;
+        let light_witnesses = if config.light.enable {
+            Some(KnownWitnesses::new(&config.light, &genesis_config, state_db.as_ref())?)
+        } else {
+            None
+        };
+
+        let merkle_tree_patch = merkle_tree_patch(&config.chain);
+
         Ok(AppContext {
             chain_db,
+            state_db,
             config,
             genesis_config,
             node_id,
@@ -69,6 +243,157 @@ impl AppContext {
             recent_blk_ids: RwLock::new(HashSet::new()),
             syncing: RwLock::new(true),
             peers: RwLock::default(),
+            peer_infos: RwLock::default(),
+            stop_request: Mutex::default(),
+            pending_pool: RwLock::default(),
+            clock_drift: RwLock::default(),
+            block_relay: broadcast::channel(64).0,
+            txn_relay: broadcast::channel(64).0,
+            services: RwLock::default(),
+            metrics: Registry::new(),
+            producer_lease: AtomicI64::new(0),
+            abi_registry,
+            light_witnesses,
+            constant_calls: None,
+            merkle_tree_patch,
         })
     }
+
+    /// Drop expired transactions and those already in a block from the pending pool, re-inject those dropped from
+    /// the chain.
+    pub fn prune_pending_pool(&self) {
+        let now = Utc::now().timestamp_millis();
+        let chain_db = &self.chain_db;
+        self.pending_pool.write().unwrap().prune(now, |id| {
+            chain_db
+                .get_transaction_by_id(id)
+                .and_then(|txn| chain_db.get_block_header_by_transaction(&txn))
+                .map(|header| header.number())
+                .ok()
+        });
+    }
+
+    /// Basic checks of a signed transaction before it enters the pending pool.
+    fn check_pending_transaction(&self, txn: &IndexedTransaction) -> Result<(), Box<dyn Error>> {
+        let raw = txn.raw.raw_data.as_ref().ok_or("transaction has no raw_data")?;
+        if raw.contract.is_none() {
+            return Err("transaction has no contract".into());
+        }
+        if txn.raw.signatures.is_empty() {
+            return Err("transaction is not signed".into());
+        }
+        txn.recover_owner()?;
+        if txn.expiration() <= Utc::now().timestamp_millis() {
+            return Err("transaction expired".into());
+        }
+        if self.chain_db.get_transaction_by_id(&txn.hash).is_ok() {
+            return Err("transaction is already in a block".into());
+        }
+
+        let expected_chain_id = &self.config.chain.expected_chain_id;
+        if !expected_chain_id.is_empty() && !expected_chain_id.eq_ignore_ascii_case(&self.chain_id()) {
+            return Err(format!(
+                "node is on chain {}, but {} is expected by config",
+                self.chain_id(),
+                expected_chain_id
+            )
+            .into());
+        }
+        self.check_tapos(txn)
+    }
+
+    /// Chain id, the last 4 bytes of the genesis block hash. Same as `eth_chainId` of java-tron.
+    pub fn chain_id(&self) -> String {
+        let genesis_hash = &self.genesis_block_id.as_ref().unwrap().hash;
+        format!("0x{}", hex::encode(&genesis_hash[genesis_hash.len() - 4..]))
+    }
+
+    /// The block referenced by TaPoS must be on this chain, so that transactions signed for another network,
+    /// i.e. Nile, are never relayed. Not checked while syncing, the block might not be received yet.
+    fn check_tapos(&self, txn: &IndexedTransaction) -> Result<(), Box<dyn Error>> {
+        if *self.syncing.read().unwrap() {
+            return Ok(());
+        }
+        let raw = txn.raw.raw_data.as_ref().unwrap();
+        if raw.ref_block_bytes.len() != 2 || raw.ref_block_hash.len() != 8 {
+            return Err("malformed TaPoS reference block".into());
+        }
+
+        // The latest block with the same lower 16 bits of block number, TaPoS only covers 65536 blocks.
+        let head = self.chain_db.get_block_height();
+        let ref_low_bits = u16::from_be_bytes([raw.ref_block_bytes[0], raw.ref_block_bytes[1]]) as i64;
+        let ref_block_number = head - (head - ref_low_bits).rem_euclid(65536);
+        let found = ref_block_number >= 0 &&
+            self.chain_db
+                .get_block_headers_by_number(ref_block_number as u64)
+                .iter()
+                .any(|header| header.hash.as_bytes()[8..16] == raw.ref_block_hash[..]);
+        if !found {
+            return Err("TaPoS reference block is not on this chain, signed for another network?".into());
+        }
+        Ok(())
+    }
+
+    /// Add a signed transaction to the pending pool, after basic checks.
+    pub fn add_pending_transaction(&self, txn: IndexedTransaction) -> Result<(), Box<dyn Error>> {
+        self.check_pending_transaction(&txn)?;
+
+        self.prune_pending_pool();
+        self.pending_pool.write().unwrap().insert(txn)?;
+        self.metrics.inc(&metrics::TRANSACTIONS_RECEIVED, 1);
+        broadcast_pending_transactions(self);
+        Ok(())
+    }
+
+    /// Add a sponsored bundle to the pending pool, to be packed into the same block in order.
+    pub fn add_pending_bundle(&self, txns: Vec<IndexedTransaction>) -> Result<(), Box<dyn Error>> {
+        check_sponsored_bundle(&txns)?;
+        for txn in &txns {
+            self.check_pending_transaction(txn)?;
+        }
+
+        self.prune_pending_pool();
+        let num_txns = txns.len() as i64;
+        self.pending_pool.write().unwrap().insert_bundle(txns)?;
+        self.metrics.inc(&metrics::TRANSACTIONS_RECEIVED, num_txns);
+        broadcast_pending_transactions(self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_state_head() {
+        let hash = H256::repeat_byte(0xaa);
+        let state = StateHead {
+            number: 100,
+            timestamp: Some(1_600_000_000_000),
+            hash: Some(hash),
+        };
+        let chain_block = Some((hash, 1_600_000_000_000));
+
+        assert_eq!(compare_state_head(&state, 100, chain_block), Ok(0));
+        assert_eq!(compare_state_head(&state, 120, chain_block), Ok(20));
+        assert!(compare_state_head(&state, 99, None).unwrap_err().contains("ahead of chain-db"));
+        assert!(compare_state_head(&state, 120, None).is_err());
+
+        let forked = Some((H256::repeat_byte(0xbb), 1_600_000_000_000));
+        assert!(compare_state_head(&state, 120, forked).unwrap_err().contains("on a fork"));
+        let forked = Some((hash, 1_600_000_003_000));
+        assert!(compare_state_head(&state, 120, forked).is_err());
+
+        let corrupted = StateHead { timestamp: None, ..state };
+        let err = compare_state_head(&corrupted, 120, chain_block).unwrap_err();
+        assert!(err.contains("no block timestamp"), "{}", err);
+
+        let genesis = StateHead {
+            number: 0,
+            timestamp: None,
+            hash: None,
+        };
+        assert_eq!(compare_state_head(&genesis, 10, None), Ok(10));
+    }
 }
@@ -18,7 +18,17 @@ use tokio::sync::broadcast;
 
 use super::protocol::{DiscoveryMessage, DiscoveryMessageTransport};
 use crate::context::AppContext;
-use crate::util::Peer;
+use crate::util::{advertised_endpoint, canonical_ip, endpoint_to_socket_addr, Peer};
+
+/// Address to send to from the local socket. IPv4 peers are reachable from a dual-stack IPv6 socket, as IPv4-mapped
+/// addresses, while IPv6 peers are unreachable from an IPv4 socket.
+fn reachable_addr(local_addr: &SocketAddr, peer_addr: SocketAddr) -> Option<SocketAddr> {
+    match (local_addr, peer_addr) {
+        (SocketAddr::V4(_), SocketAddr::V6(_)) => None,
+        (SocketAddr::V6(_), SocketAddr::V4(v4)) => Some(SocketAddr::new(v4.ip().to_ipv6_mapped().into(), v4.port())),
+        (_, peer_addr) => Some(peer_addr),
+    }
+}
 
 fn common_prefix_bits(a: &[u8], b: &[u8]) -> u32 {
     let mut acc = 0;
@@ -41,35 +51,19 @@ pub async fn discovery_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<
         return Ok(());
     }
 
-    let channel_config = &ctx.config.protocol.channel;
     let my_ip = &ctx.outbound_ip;
     let p2p_version = ctx.config.chain.p2p_version;
 
     let endpoint = &config.endpoint;
 
     let socket = UdpSocket::bind(endpoint).await?;
-    info!(logger, "bind to udp socket {}", socket.local_addr()?);
+    let local_addr = socket.local_addr()?;
+    info!(logger, "bind to udp socket {}", local_addr);
 
     let peers_data = std::fs::read_to_string("./peers.json").unwrap_or("[]".to_string());
     let mut peers_db: HashSet<Peer> = serde_json::from_str(&peers_data)?;
 
-    let my_endpoint = channel_config
-        .advertised_endpoint
-        .parse::<SocketAddr>()
-        .map(|addr| Endpoint {
-            address: addr.ip().to_string(),
-            port: addr.port() as _,
-            node_id: ctx.node_id.clone(),
-        })
-        .unwrap_or_else(|_| Endpoint {
-            address: ctx.outbound_ip.clone(),
-            port: channel_config
-                .endpoint
-                .parse::<SocketAddr>()
-                .map(|addr| addr.port())
-                .unwrap_or(18888) as _,
-            node_id: ctx.node_id.clone(),
-        });
+    let my_endpoint = advertised_endpoint(&ctx);
     info!(
         logger,
         "advertised endpoint {}:{}", &my_endpoint.address, my_endpoint.port
@@ -77,11 +71,15 @@ pub async fn discovery_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<
     let mut transport = DiscoveryMessageTransport::new(socket);
 
     for peer in &ctx.config.protocol.seed_nodes {
-        if let Some(peer_addr) = net::lookup_host(peer).await.ok().and_then(|mut it| it.next()) {
+        let peer_addr = net::lookup_host(peer)
+            .await
+            .ok()
+            .and_then(|mut it| it.find_map(|addr| reachable_addr(&local_addr, addr)));
+        if let Some(peer_addr) = peer_addr {
             let ping = Ping {
                 from: Some(my_endpoint.clone()),
                 to: Some(Endpoint {
-                    address: peer_addr.ip().to_string(),
+                    address: canonical_ip(peer_addr.ip()).to_string(),
                     port: peer_addr.port() as _,
                     node_id: vec![63u8; 64],
                 }),
@@ -126,7 +124,8 @@ pub async fn discovery_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<
                         let mut random_id = vec![0u8; 32];
                         rng.fill(&mut random_id[..]);
                         debug!(logger, "find peers target={}", hex::encode(&random_id); "peer_addr" => peer_addr);
-                        if ["127.0.0.1", my_ip, "192.168.1.1"].contains(&&*peer_addr.ip().to_string()) {
+                        let peer_ip = canonical_ip(peer_addr.ip()).to_string();
+                        if ["127.0.0.1", "::1", my_ip, "192.168.1.1"].contains(&&*peer_ip) {
                             continue;
                         }
                         let find = FindPeers {
@@ -160,10 +159,12 @@ pub async fn discovery_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<
                     }
                     Ok((DiscoveryMessage::Peers(peers), _)) => {
                         for peer in &peers.peers {
-                            if ["127.0.0.1", my_ip, "192.168.1.1"].contains(&&*peer.address) {
+                            if ["127.0.0.1", "::1", my_ip, "192.168.1.1"].contains(&&*peer.address) {
                                 continue;
                             }
-                            if let Ok(peer_addr) = format!("{}:{}", peer.address, peer.port).parse() {
+                            let peer_addr =
+                                endpoint_to_socket_addr(peer).and_then(|addr| reachable_addr(&local_addr, addr));
+                            if let Some(peer_addr) = peer_addr {
                                 debug!(logger, "ping"; "peer_addr" => peer_addr);
                                 let ping = Ping {
                                     from: Some(my_endpoint.clone()),
@@ -188,7 +189,7 @@ pub async fn discovery_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<
                             version: pong.echo_version,
                             advertised_ip: ep.address.clone(),
                             advertised_port: ep.port as _,
-                            received_ip: peer_addr.ip().to_string(),
+                            received_ip: canonical_ip(peer_addr.ip()).to_string(),
                             received_port: peer_addr.port(),
                         };
                         if !peers_db.contains(&peer) {
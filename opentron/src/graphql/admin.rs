@@ -0,0 +1,78 @@
+//! The admin namespace, only accessible with the configured admin token.
+
+use ::keys::Address;
+use juniper::graphql_value;
+use juniper::{FieldError, FieldResult};
+use log::{info, warn};
+
+use super::model::Context;
+
+pub(crate) struct AdminMutation;
+
+#[juniper::graphql_object(Context = Context)]
+impl AdminMutation {
+    /// Stop the node gracefully.
+    fn stop_node(ctx: &Context) -> FieldResult<bool> {
+        match ctx.app.stop_request.lock().unwrap().take() {
+            Some(stop) => {
+                warn!("stop node requested by admin");
+                Ok(stop.send(()).is_ok())
+            }
+            None => Err("node is not running or already stopping".into()),
+        }
+    }
+
+    /// Flush in-memory state to disk.
+    fn flush_state(ctx: &Context) -> FieldResult<bool> {
+        ctx.app.chain_db.flush().map_err(|e| {
+            FieldError::new(
+                "fail to flush db",
+                graphql_value!({
+                    "internal_error": (e.to_string())
+                }),
+            )
+        })?;
+        ctx.app.chain_db.report_status();
+        Ok(true)
+    }
+
+    /// Attach a verified ABI to a contract in the node-local registry, replacing the existing one.
+    #[graphql(arguments(
+        address(description = "contract address"),
+        abi(description = "ABI in JSON, the standard Solidity format"),
+        metadata(description = "free-form metadata, i.e. compiler version and source URL")
+    ))]
+    fn attach_contract_abi(ctx: &Context, address: String, abi: String, metadata: Option<String>) -> FieldResult<bool> {
+        let addr: Address = address.parse()?;
+        ctx.app
+            .abi_registry
+            .attach(addr, &abi, &metadata.unwrap_or_default())
+            .map_err(|e| {
+                FieldError::new(
+                    "fail to attach ABI",
+                    graphql_value!({
+                        "internal_error": (e.to_string())
+                    }),
+                )
+            })?;
+        info!("ABI attached to {}", addr);
+        Ok(true)
+    }
+
+    /// Detach the ABI of a contract, returns false if not attached.
+    #[graphql(arguments(address(description = "contract address")))]
+    fn detach_contract_abi(ctx: &Context, address: String) -> FieldResult<bool> {
+        let addr: Address = address.parse()?;
+        ctx.app
+            .abi_registry
+            .detach(&addr)
+            .map_err(|e| {
+                FieldError::new(
+                    "fail to detach ABI",
+                    graphql_value!({
+                        "internal_error": (e.to_string())
+                    }),
+                )
+            })
+    }
+}
@@ -115,6 +115,48 @@ pub struct UnfreezeBalanceContract {
     resource: ResourceCode,
 }
 
+#[derive(juniper::GraphQLObject)]
+pub struct FreezeBalanceV2Contract {
+    owner_address: String,
+    resource: ResourceCode,
+    frozen_balance: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct UnfreezeBalanceV2Contract {
+    owner_address: String,
+    resource: ResourceCode,
+    unfreeze_balance: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct WithdrawExpireUnfreezeContract {
+    owner_address: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct DelegateResourceContract {
+    owner_address: String,
+    receiver_address: String,
+    resource: ResourceCode,
+    balance: f64,
+    lock: bool,
+    lock_period: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct UnDelegateResourceContract {
+    owner_address: String,
+    receiver_address: String,
+    resource: ResourceCode,
+    balance: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct CancelAllUnfreezeV2Contract {
+    owner_address: String,
+}
+
 #[derive(juniper::GraphQLObject)]
 pub struct Parameter {
     key: i32,
@@ -286,6 +328,12 @@ pub enum Contract {
     VoteWitnessContract(VoteWitnessContract),
     FreezeBalanceContract(FreezeBalanceContract),
     UnfreezeBalanceContract(UnfreezeBalanceContract),
+    FreezeBalanceV2Contract(FreezeBalanceV2Contract),
+    UnfreezeBalanceV2Contract(UnfreezeBalanceV2Contract),
+    WithdrawExpireUnfreezeContract(WithdrawExpireUnfreezeContract),
+    DelegateResourceContract(DelegateResourceContract),
+    UnDelegateResourceContract(UnDelegateResourceContract),
+    CancelAllUnfreezeV2Contract(CancelAllUnfreezeV2Contract),
     ProposalCreateContract(ProposalCreateContract),
     ProposalApproveContract(ProposalApproveContract),
     ProposalDeleteContract(ProposalDeleteContract),
@@ -409,6 +457,76 @@ impl From<ContractPb> for Contract {
                 };
                 Contract::UnfreezeBalanceContract(inner)
             }
+            Some(ContractType::FreezeBalanceV2Contract) => {
+                let cntr = contract_pb::FreezeBalanceV2Contract::decode(raw).unwrap();
+                let inner = FreezeBalanceV2Contract {
+                    owner_address: b58encode_check(&cntr.owner_address),
+                    frozen_balance: cntr.frozen_balance as _,
+                    resource: if cntr.resource == 0 {
+                        ResourceCode::Bandwidth
+                    } else {
+                        ResourceCode::Energy
+                    },
+                };
+                Contract::FreezeBalanceV2Contract(inner)
+            }
+            Some(ContractType::UnfreezeBalanceV2Contract) => {
+                let cntr = contract_pb::UnfreezeBalanceV2Contract::decode(raw).unwrap();
+                let inner = UnfreezeBalanceV2Contract {
+                    owner_address: b58encode_check(&cntr.owner_address),
+                    unfreeze_balance: cntr.unfreeze_balance as _,
+                    resource: if cntr.resource == 0 {
+                        ResourceCode::Bandwidth
+                    } else {
+                        ResourceCode::Energy
+                    },
+                };
+                Contract::UnfreezeBalanceV2Contract(inner)
+            }
+            Some(ContractType::WithdrawExpireUnfreezeContract) => {
+                let cntr = contract_pb::WithdrawExpireUnfreezeContract::decode(raw).unwrap();
+                let inner = WithdrawExpireUnfreezeContract {
+                    owner_address: b58encode_check(&cntr.owner_address),
+                };
+                Contract::WithdrawExpireUnfreezeContract(inner)
+            }
+            Some(ContractType::DelegateResourceContract) => {
+                let cntr = contract_pb::DelegateResourceContract::decode(raw).unwrap();
+                let inner = DelegateResourceContract {
+                    owner_address: b58encode_check(&cntr.owner_address),
+                    receiver_address: b58encode_check(&cntr.receiver_address),
+                    balance: cntr.balance as _,
+                    lock: cntr.lock,
+                    lock_period: cntr.lock_period as _,
+                    resource: if cntr.resource == 0 {
+                        ResourceCode::Bandwidth
+                    } else {
+                        ResourceCode::Energy
+                    },
+                };
+                Contract::DelegateResourceContract(inner)
+            }
+            Some(ContractType::UnDelegateResourceContract) => {
+                let cntr = contract_pb::UnDelegateResourceContract::decode(raw).unwrap();
+                let inner = UnDelegateResourceContract {
+                    owner_address: b58encode_check(&cntr.owner_address),
+                    receiver_address: b58encode_check(&cntr.receiver_address),
+                    balance: cntr.balance as _,
+                    resource: if cntr.resource == 0 {
+                        ResourceCode::Bandwidth
+                    } else {
+                        ResourceCode::Energy
+                    },
+                };
+                Contract::UnDelegateResourceContract(inner)
+            }
+            Some(ContractType::CancelAllUnfreezeV2Contract) => {
+                let cntr = contract_pb::CancelAllUnfreezeV2Contract::decode(raw).unwrap();
+                let inner = CancelAllUnfreezeV2Contract {
+                    owner_address: b58encode_check(&cntr.owner_address),
+                };
+                Contract::CancelAllUnfreezeV2Contract(inner)
+            }
             Some(ContractType::WitnessCreateContract) => {
                 let cntr = contract_pb::WitnessCreateContract::decode(raw).unwrap();
                 let inner = WitnessCreateContract {
@@ -0,0 +1,176 @@
+//! Coverage of java-tron's `Wallet` and `WalletSolidity` gRPC services by the GraphQL API.
+//!
+//! OpenTron serves GraphQL only. This table tracks which java-tron API methods have a GraphQL counterpart, so that
+//! SDK authors know what is missing before porting. Update it along with new query and mutation fields.
+
+/// `(service, method, GraphQL field)`, in the order of java-tron's `api.proto`.
+const WALLET_API_METHODS: &[(&str, &str, Option<&str>)] = &[
+    ("Wallet", "GetAccount", None),
+    ("Wallet", "GetAccountById", None),
+    ("Wallet", "CreateTransaction", Some("buildTransaction")),
+    ("Wallet", "BroadcastTransaction", Some("broadcast")),
+    ("Wallet", "UpdateAccount", Some("buildTransaction")),
+    ("Wallet", "SetAccountId", None),
+    ("Wallet", "VoteWitnessAccount", Some("buildTransaction")),
+    ("Wallet", "UpdateSetting", Some("buildTransaction")),
+    ("Wallet", "UpdateEnergyLimit", Some("buildTransaction")),
+    ("Wallet", "CreateAssetIssue", Some("buildTransaction")),
+    ("Wallet", "UpdateWitness", Some("buildTransaction")),
+    ("Wallet", "CreateAccount", Some("buildTransaction")),
+    ("Wallet", "CreateWitness", Some("buildTransaction")),
+    ("Wallet", "TransferAsset", Some("buildTransaction")),
+    ("Wallet", "ParticipateAssetIssue", Some("buildTransaction")),
+    ("Wallet", "FreezeBalance", Some("buildTransaction")),
+    ("Wallet", "UnfreezeBalance", Some("buildTransaction")),
+    ("Wallet", "UnfreezeAsset", Some("buildTransaction")),
+    ("Wallet", "WithdrawBalance", Some("buildTransaction")),
+    ("Wallet", "UpdateAsset", Some("buildTransaction")),
+    ("Wallet", "ProposalCreate", Some("buildTransaction")),
+    ("Wallet", "ProposalApprove", Some("buildTransaction")),
+    ("Wallet", "ProposalDelete", Some("buildTransaction")),
+    ("Wallet", "ExchangeCreate", Some("buildTransaction")),
+    ("Wallet", "ExchangeInject", Some("buildTransaction")),
+    ("Wallet", "ExchangeWithdraw", Some("buildTransaction")),
+    ("Wallet", "ExchangeTransaction", Some("buildTransaction")),
+    ("Wallet", "DeployContract", Some("buildTransaction")),
+    ("Wallet", "TriggerContract", Some("buildTransaction")),
+    ("Wallet", "TriggerConstantContract", Some("constantCall")),
+    ("Wallet", "ClearContractABI", Some("buildTransaction")),
+    ("Wallet", "AccountPermissionUpdate", Some("buildTransaction")),
+    ("Wallet", "UpdateBrokerage", Some("buildTransaction")),
+    ("Wallet", "ListNodes", Some("nodes")),
+    ("Wallet", "GetNodeInfo", Some("nodeInfo")),
+    ("Wallet", "GetAssetIssueByAccount", None),
+    ("Wallet", "GetAccountNet", None),
+    ("Wallet", "GetAccountResource", None),
+    ("Wallet", "GetAssetIssueByName", None),
+    ("Wallet", "GetAssetIssueListByName", None),
+    ("Wallet", "GetAssetIssueById", None),
+    ("Wallet", "GetAssetIssueList", Some("assets")),
+    ("Wallet", "GetPaginatedAssetIssueList", Some("assets")),
+    ("Wallet", "GetNowBlock", Some("block")),
+    ("Wallet", "GetBlockByNum", Some("block")),
+    ("Wallet", "GetBlockById", Some("block")),
+    ("Wallet", "GetBlockByLimitNext", None),
+    ("Wallet", "GetBlockByLatestNum", None),
+    ("Wallet", "GetTransactionCountByBlockNum", None),
+    ("Wallet", "GetTransactionById", Some("transaction")),
+    ("Wallet", "GetTransactionInfoById", Some("resourceReceipt")),
+    ("Wallet", "GetTransactionInfoByBlockNum", None),
+    ("Wallet", "GetContract", None),
+    ("Wallet", "GetContractInfo", None),
+    ("Wallet", "ListWitnesses", Some("witnesses")),
+    ("Wallet", "GetDelegatedResource", Some("delegations")),
+    ("Wallet", "GetDelegatedResourceV2", Some("delegations")),
+    ("Wallet", "GetDelegatedResourceAccountIndex", Some("delegationAccounts")),
+    ("Wallet", "GetDelegatedResourceAccountIndexV2", Some("delegationAccounts")),
+    ("Wallet", "GetCanDelegatedMaxSize", Some("delegationSummary")),
+    ("Wallet", "GetAvailableUnfreezeCount", None),
+    ("Wallet", "GetCanWithdrawUnfreezeAmount", Some("claimable")),
+    ("Wallet", "ListProposals", Some("proposals")),
+    ("Wallet", "GetPaginatedProposalList", Some("proposals")),
+    ("Wallet", "GetProposalById", Some("proposal")),
+    ("Wallet", "ListExchanges", None),
+    ("Wallet", "GetPaginatedExchangeList", None),
+    ("Wallet", "GetExchangeById", None),
+    ("Wallet", "GetChainParameters", Some("chainParameters")),
+    ("Wallet", "TotalTransaction", None),
+    ("Wallet", "GetNextMaintenanceTime", None),
+    ("Wallet", "GetTransactionSignWeight", None),
+    ("Wallet", "GetTransactionApprovedList", None),
+    ("Wallet", "GetRewardInfo", None),
+    ("Wallet", "GetBrokerageInfo", None),
+    ("Wallet", "GetBurnTrx", None),
+    ("Wallet", "GetMarketOrderByAccount", Some("marketOrdersByAccount")),
+    ("Wallet", "GetMarketPairList", Some("marketPairs")),
+    ("Wallet", "GetMarketPriceByPair", Some("marketPricesByPair")),
+    ("Wallet", "GetBandwidthPrices", Some("bandwidthPrices")),
+    ("Wallet", "GetEnergyPrices", Some("energyPrices")),
+    ("Wallet", "GetTransactionFromPending", Some("pendingTransaction")),
+    ("Wallet", "GetTransactionListFromPending", Some("pendingTransactions")),
+    ("Wallet", "GetPendingSize", Some("pendingPool")),
+    ("WalletSolidity", "GetAccount", None),
+    ("WalletSolidity", "GetAccountById", None),
+    ("WalletSolidity", "ListWitnesses", Some("witnesses")),
+    ("WalletSolidity", "GetAssetIssueList", Some("assets")),
+    ("WalletSolidity", "GetPaginatedAssetIssueList", Some("assets")),
+    ("WalletSolidity", "GetAssetIssueByName", None),
+    ("WalletSolidity", "GetAssetIssueListByName", None),
+    ("WalletSolidity", "GetAssetIssueById", None),
+    ("WalletSolidity", "GetNowBlock", Some("block")),
+    ("WalletSolidity", "GetBlockByNum", Some("block")),
+    ("WalletSolidity", "GetTransactionCountByBlockNum", None),
+    ("WalletSolidity", "GetDelegatedResource", Some("delegations")),
+    ("WalletSolidity", "GetDelegatedResourceV2", Some("delegations")),
+    ("WalletSolidity", "GetDelegatedResourceAccountIndex", Some("delegationAccounts")),
+    ("WalletSolidity", "GetDelegatedResourceAccountIndexV2", Some("delegationAccounts")),
+    ("WalletSolidity", "GetExchangeById", None),
+    ("WalletSolidity", "ListExchanges", None),
+    ("WalletSolidity", "GetTransactionById", Some("transaction")),
+    ("WalletSolidity", "GetTransactionInfoById", Some("resourceReceipt")),
+    ("WalletSolidity", "GetTransactionInfoByBlockNum", None),
+    ("WalletSolidity", "TriggerConstantContract", Some("constantCall")),
+    ("WalletSolidity", "GetRewardInfo", None),
+    ("WalletSolidity", "GetBrokerageInfo", None),
+    ("WalletSolidity", "GetBurnTrx", None),
+    ("WalletSolidity", "GetMarketOrderByAccount", Some("marketOrdersByAccount")),
+    ("WalletSolidity", "GetMarketPairList", Some("marketPairs")),
+    ("WalletSolidity", "GetMarketPriceByPair", Some("marketPricesByPair")),
+];
+
+#[derive(juniper::GraphQLObject)]
+/// A java-tron API method, and its GraphQL counterpart if implemented.
+pub struct ApiMethod {
+    /// gRPC service, `Wallet` or `WalletSolidity`.
+    pub service: String,
+    pub method: String,
+    /// GraphQL field serving this method, none if not implemented yet.
+    pub graphql_field: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Coverage of java-tron APIs.
+pub struct ApiCoverage {
+    pub num_implemented: i32,
+    pub num_unimplemented: i32,
+    pub methods: Vec<ApiMethod>,
+}
+
+pub fn api_coverage() -> ApiCoverage {
+    let methods: Vec<_> = WALLET_API_METHODS
+        .iter()
+        .map(|&(service, method, field)| ApiMethod {
+            service: service.into(),
+            method: method.into(),
+            graphql_field: field.map(Into::into),
+        })
+        .collect();
+    let num_implemented = methods.iter().filter(|m| m.graphql_field.is_some()).count() as i32;
+
+    ApiCoverage {
+        num_implemented,
+        num_unimplemented: methods.len() as i32 - num_implemented,
+        methods,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_api_coverage() {
+        let mut seen = HashSet::new();
+        for &(service, method, _) in WALLET_API_METHODS {
+            assert!(seen.insert((service, method)), "duplicated {}/{}", service, method);
+        }
+
+        let coverage = api_coverage();
+        assert_eq!(coverage.num_implemented + coverage.num_unimplemented, WALLET_API_METHODS.len() as i32);
+        assert!(coverage
+            .methods
+            .iter()
+            .any(|m| m.method == "BroadcastTransaction" && m.graphql_field.as_deref() == Some("broadcast")));
+    }
+}
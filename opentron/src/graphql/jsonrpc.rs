@@ -0,0 +1,125 @@
+//! Minimal Ethereum JSON-RPC, for EVM tooling that insists on querying gas prices.
+//!
+//! Served at `/jsonrpc` of the GraphQL endpoint. Only `eth_gasPrice` and `eth_feeHistory` are implemented, by the
+//! `gasPrice` and `feeHistory` queries. Everything else is served by GraphQL.
+
+use serde_json::{json, Value};
+
+use super::model::Context;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+struct RpcError(i64, String);
+
+/// Handle a request body, a single call or a batch.
+pub fn handle(ctx: &Context, body: &[u8]) -> Value {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Array(calls)) if !calls.is_empty() => {
+            Value::Array(calls.iter().map(|call| handle_call(ctx, call)).collect())
+        }
+        Ok(Value::Array(_)) => error_response(Value::Null, RpcError(INVALID_REQUEST, "empty batch".into())),
+        Ok(call) => handle_call(ctx, &call),
+        Err(e) => error_response(Value::Null, RpcError(PARSE_ERROR, e.to_string())),
+    }
+}
+
+fn handle_call(ctx: &Context, call: &Value) -> Value {
+    let id = call.get("id").cloned().unwrap_or(Value::Null);
+    let method = match call.get("method").and_then(Value::as_str) {
+        Some(method) => method,
+        None => return error_response(id, RpcError(INVALID_REQUEST, "missing method".into())),
+    };
+    let params = call.get("params").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+
+    let ret = match method {
+        "eth_gasPrice" => gas_price(ctx),
+        "eth_feeHistory" => fee_history(ctx, params),
+        _ => Err(RpcError(METHOD_NOT_FOUND, format!("method {} is not supported", method))),
+    };
+    match ret {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => error_response(id, e),
+    }
+}
+
+fn error_response(id: Value, RpcError(code, message): RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn gas_price(ctx: &Context) -> Result<Value, RpcError> {
+    let price = ctx
+        .get_gas_price()
+        .map_err(|e| RpcError(INTERNAL_ERROR, e.message().to_owned()))?;
+    Ok(quantity(price.gas_price as u64))
+}
+
+/// `[blockCount, newestBlock, rewardPercentiles]`. Percentiles are ignored, there's no priority fee on TRON.
+fn fee_history(ctx: &Context, params: &[Value]) -> Result<Value, RpcError> {
+    let block_count = params
+        .get(0)
+        .and_then(parse_quantity)
+        .ok_or_else(|| RpcError(INVALID_PARAMS, "invalid blockCount".into()))?;
+    let newest_block = match params.get(1) {
+        Some(tag) => parse_block_tag(tag).ok_or_else(|| RpcError(INVALID_PARAMS, "invalid newestBlock".into()))?,
+        None => None,
+    };
+    if block_count > i32::max_value() as u64 || newest_block.map_or(false, |num| num > i32::max_value() as u64) {
+        return Err(RpcError(INVALID_PARAMS, "block number overflow".into()));
+    }
+
+    let history = ctx
+        .get_fee_history(block_count as i32, newest_block.map(|num| num as i32))
+        .map_err(|e| RpcError(INVALID_PARAMS, e.message().to_owned()))?;
+    Ok(json!({
+        "oldestBlock": quantity(history.oldest_block as u64),
+        "baseFeePerGas": history.base_fee_per_gas.iter().map(|&price| quantity(price as u64)).collect::<Vec<_>>(),
+        "gasUsedRatio": history.gas_used_ratio,
+        "mapping": history.mapping,
+    }))
+}
+
+fn quantity(val: u64) -> Value {
+    Value::String(format!("0x{:x}", val))
+}
+
+/// A hex quantity, or a plain number as accepted by most clients.
+fn parse_quantity(val: &Value) -> Option<u64> {
+    match val {
+        Value::String(s) => u64::from_str_radix(s.strip_prefix("0x")?, 16).ok(),
+        Value::Number(n) => n.as_u64(),
+        _ => None,
+    }
+}
+
+/// Block number of a block tag, None for the latest block.
+fn parse_block_tag(val: &Value) -> Option<Option<u64>> {
+    match val.as_str() {
+        Some("latest") | Some("pending") | Some("safe") | Some("finalized") => Some(None),
+        Some("earliest") => Some(Some(0)),
+        _ => parse_quantity(val).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_params() {
+        assert_eq!(parse_quantity(&json!("0x1f")), Some(31));
+        assert_eq!(parse_quantity(&json!(31)), Some(31));
+        assert_eq!(parse_quantity(&json!("31")), None);
+        assert_eq!(parse_quantity(&json!(-1)), None);
+
+        assert_eq!(parse_block_tag(&json!("latest")), Some(None));
+        assert_eq!(parse_block_tag(&json!("earliest")), Some(Some(0)));
+        assert_eq!(parse_block_tag(&json!("0x10")), Some(Some(16)));
+        assert_eq!(parse_block_tag(&json!("newest")), None);
+
+        assert_eq!(quantity(420), json!("0x1a4"));
+    }
+}
@@ -1,4 +1,7 @@
+pub mod admin;
 pub mod contract;
+pub mod coverage;
+pub mod jsonrpc;
 pub mod model;
 pub mod schema;
 pub mod server;
@@ -1,14 +1,21 @@
-use chain::{IndexedBlock, IndexedTransaction};
+use chain::{hash, IndexedBlock, IndexedTransaction};
 use chrono::{DateTime, TimeZone, Utc};
 use juniper::FieldResult;
 use keys::Address;
-use primitive_types::H256;
+use primitive_types::{H256, U256};
+use prost::Message;
+use proto2::chain::{transaction::Raw as RawTransactionPb, ContractType, Transaction as TransactionPb};
+use proto2::state::PermissionKey as PermissionKeyPb;
 use std::convert::TryFrom;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use super::contract::Contract;
 use crate::context::AppContext;
+use crate::manager::constant::{ConstantCall, ConstantCallResult};
+use crate::metrics::{self, Registry};
+use crate::supervisor;
 
 #[derive(juniper::GraphQLEnum, PartialEq, Eq)]
 #[repr(i32)]
@@ -45,6 +52,7 @@ pub struct RawTransaction {
     pub ref_block_hash: String,
     pub permission_id: i32,
     pub fee_limit: i32,
+    /// Memo(data) of the transaction, in UTF-8 when printable, hex otherwise.
     pub memo: Option<String>,
 }
 
@@ -53,6 +61,8 @@ pub struct RawTransaction {
 pub struct UnsignedTransaction {
     /// Transaction hash.
     pub id: String,
+    /// Hex encoded raw transaction, to be signed.
+    pub raw: String,
     /// Inner transaction.
     pub inner: RawTransaction,
 }
@@ -69,6 +79,14 @@ pub struct Transaction {
     contract_return: Option<ContractReturn>,
 }
 
+/// Memo as UTF-8 when printable, hex otherwise.
+fn memo_to_string(data: &[u8]) -> String {
+    match str::from_utf8(data) {
+        Ok(s) if s.chars().all(|c| !c.is_control() || c.is_whitespace()) => s.to_owned(),
+        _ => hex::encode(data),
+    }
+}
+
 impl From<IndexedTransaction> for Transaction {
     fn from(txn: IndexedTransaction) -> Self {
         let IndexedTransaction { hash, mut raw } = txn;
@@ -91,10 +109,7 @@ impl From<IndexedTransaction> for Transaction {
             permission_id,
             fee_limit: raw_txn.fee_limit as _,
             memo: if !raw_txn.data.is_empty() {
-                str::from_utf8(&raw_txn.data)
-                    .map(|s| s.to_owned())
-                    .ok()
-                    .or_else(|| Some(hex::encode(&raw_txn.data)))
+                Some(memo_to_string(&raw_txn.data))
             } else {
                 None
             },
@@ -108,6 +123,25 @@ impl From<IndexedTransaction> for Transaction {
     }
 }
 
+impl From<IndexedTransaction> for UnsignedTransaction {
+    fn from(txn: IndexedTransaction) -> Self {
+        let raw = hash::raw_data_bytes(txn.raw.raw_data.as_ref().unwrap());
+        let Transaction { id, inner, .. } = txn.into();
+        UnsignedTransaction {
+            id,
+            raw: hex::encode(&raw),
+            inner,
+        }
+    }
+}
+
+/// `owner_address` is always the first field of builtin contracts.
+#[derive(Clone, PartialEq, prost::Message)]
+struct ContractOwner {
+    #[prost(bytes, tag = "1")]
+    owner_address: Vec<u8>,
+}
+
 #[derive(juniper::GraphQLObject)]
 /// A block, on the block chain.
 pub struct Block {
@@ -129,6 +163,23 @@ pub struct Block {
     witness_signature: String,
     /// The transactions in this block.
     transactions: Vec<Transaction>,
+    /// Resources consumed by transactions of the block. Null for empty blocks, blocks not executed yet, or executed
+    /// by an older version.
+    resource_usage: Option<BlockResourceUsage>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Resource consumption and fees of all transactions in a block, in SUN.
+pub struct BlockResourceUsage {
+    num_transactions: i32,
+    net_usage: f64,
+    net_fee: f64,
+    /// Total energy consumed, frozen and burnt, the input of adaptive energy.
+    energy_usage_total: f64,
+    /// Energy paid by frozen energy, of callers and contract origins.
+    energy_usage: f64,
+    /// Energy paid by burning TRX.
+    energy_fee: f64,
 }
 
 #[derive(juniper::GraphQLObject)]
@@ -148,17 +199,752 @@ pub struct NodeInfo {
     is_write_stopped: bool,
     /// Total size (bytes) of all SST files belong to the latest LSM tree.
     total_size: f64,
+    /// System clock drift against NTP servers, in ms. Null if not checked yet.
+    clock_drift: Option<i32>,
+    /// Age of the state view served by queries, in ms. Null if state-db is not available.
+    state_snapshot_age: Option<f64>,
+    /// Number of chain-db blocks not applied to state-db yet, i.e. how stale state queries are. Null if state-db is
+    /// not available.
+    state_block_gap: Option<f64>,
+    /// Number of running heavy queries.
+    running_heavy_queries: i32,
+    /// Health of supervised services.
+    services: Vec<Service>,
+}
+
+#[derive(juniper::GraphQLEnum)]
+enum ServiceState {
+    Running,
+    /// Failed, waiting for restart.
+    Restarting,
+    Stopped,
+    /// Failed too many times, gave up.
+    Failed,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A supervised long-running service of the node.
+pub struct Service {
+    name: String,
+    state: ServiceState,
+    /// Total number of restarts.
+    restarts: i32,
+    /// Error of the latest failure.
+    last_error: Option<String>,
+    /// Latest start time.
+    started_at: DateTime<Utc>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A connected channel peer.
+pub struct Peer {
+    /// Remote address of the connection.
+    remote_address: String,
+    /// Advertised address in handshake.
+    address: String,
+    /// Advertised port in handshake.
+    port: i32,
+    /// Node id, in hex.
+    node_id: String,
+    /// P2P version.
+    p2p_version: i32,
+    /// Software version, empty if not advertised.
+    code_version: String,
+    /// Highest block number known of the peer.
+    head_block_number: i32,
+    /// Round trip time of the latest handshake or ping, in ms.
+    latency: i32,
+    /// Connected since.
+    connected_at: DateTime<Utc>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A witness(SR or SR candidate).
+pub struct Witness {
+    /// Witness address.
+    address: String,
+    /// Witness URL.
+    url: String,
+    /// Is an active witness(SR) in current epoch.
+    is_active: bool,
+    /// Number of votes.
+    vote_count: f64,
+    /// Total number of produced blocks.
+    total_produced: i32,
+    /// Total number of missed blocks.
+    total_missed: i32,
+    /// Number of the latest produced block.
+    latest_block_number: i32,
+    /// Absolute slot of the latest produced block.
+    latest_slot_number: f64,
+    /// Brokerage ratio, 0-100.
+    brokerage: i32,
+}
+
+impl From<proto2::state::Witness> for Witness {
+    fn from(wit: proto2::state::Witness) -> Self {
+        Witness {
+            address: Address::try_from(&wit.address)
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|_| hex::encode(&wit.address)),
+            url: wit.url,
+            is_active: wit.is_active,
+            vote_count: wit.vote_count as _,
+            total_produced: wit.total_produced as _,
+            total_missed: wit.total_missed as _,
+            latest_block_number: wit.latest_block_number as _,
+            latest_slot_number: wit.latest_slot_number as _,
+            brokerage: wit.brokerage,
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of witnesses, sorted by votes.
+pub struct WitnessPage {
+    witnesses: Vec<Witness>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A TRC10 asset.
+pub struct Asset {
+    /// Token id.
+    id: f64,
+    owner_address: String,
+    name: String,
+    abbr: String,
+    total_supply: f64,
+    precision: i32,
+    url: String,
+    /// Description, lossy converted from raw bytes.
+    description: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of TRC10 assets, ordered by token id.
+pub struct AssetPage {
+    assets: Vec<Asset>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLEnum, PartialEq, Eq)]
+pub enum ProposalState {
+    /// Open for approvals, until expiration.
+    Pending,
+    /// Expired without enough approvals.
+    Disapproved,
+    /// Approved, parameters are changed at the next maintenance.
+    Approved,
+    Cancelled,
+}
+
+impl ProposalState {
+    fn from_pb(state: i32) -> Self {
+        use proto2::state::proposal::State;
+
+        match State::from_i32(state) {
+            Some(State::Disapproved) => ProposalState::Disapproved,
+            Some(State::Approved) => ProposalState::Approved,
+            Some(State::Cancelled) => ProposalState::Cancelled,
+            _ => ProposalState::Pending,
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A chain parameter change of a proposal.
+pub struct ProposalParameter {
+    key: i32,
+    /// Name of the chain parameter, null if unknown to this node.
+    name: Option<String>,
+    value: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A chain parameter and its live value.
+pub struct ChainParameterValue {
+    key: i32,
+    name: String,
+    value: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A governance proposal.
+pub struct Proposal {
+    id: f64,
+    proposer_address: String,
+    parameters: Vec<ProposalParameter>,
+    /// In ms.
+    creation_time: f64,
+    /// In ms, approvals are counted at the first maintenance after expiration.
+    expiration_time: f64,
+    approver_addresses: Vec<String>,
+    state: ProposalState,
+}
+
+impl Proposal {
+    fn new(proposal: &proto2::state::Proposal) -> Self {
+        let to_address = |raw: &[u8]| {
+            Address::try_from(raw)
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|_| hex::encode(raw))
+        };
+        let mut parameters: Vec<_> = proposal
+            .parameters
+            .iter()
+            .map(|(&key, &value)| ProposalParameter {
+                key: key as _,
+                name: state::keys::ChainParameter::from_i32(key as _).map(|param| format!("{:?}", param)),
+                value: value as _,
+            })
+            .collect();
+        parameters.sort_by_key(|param| param.key);
+        Proposal {
+            id: proposal.proposal_id as _,
+            proposer_address: to_address(&proposal.proposer_address),
+            parameters,
+            creation_time: proposal.creation_time as _,
+            expiration_time: proposal.expiration_time as _,
+            approver_addresses: proposal.approver_addresses.iter().map(|raw| to_address(raw)).collect(),
+            state: ProposalState::from_pb(proposal.state),
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of proposals, ordered by id.
+pub struct ProposalPage {
+    proposals: Vec<Proposal>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Token balance of an account.
+pub struct AssetBalance {
+    /// Token id.
+    id: f64,
+    balance: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// An account of the rich list, amounts in SUN.
+pub struct RichAccount {
+    address: String,
+    balance: f64,
+    /// Frozen by Stake 1.0 and 2.0, including amounts delegated out and pending unfreezes.
+    frozen: f64,
+    /// Balance plus frozen.
+    total: f64,
+    /// Non-zero TRC10 balances, ordered by token id.
+    assets: Vec<AssetBalance>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of the rich list, ordered by balance plus frozen in descending order, then by address.
+pub struct RichAccountPage {
+    accounts: Vec<RichAccount>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Resource delegated from one account to another.
+pub struct Delegation {
+    from_address: String,
+    to_address: String,
+    amount_for_bandwidth: f64,
+    expiration_timestamp_for_bandwidth: f64,
+    amount_for_energy: f64,
+    expiration_timestamp_for_energy: f64,
+    /// Stake 2.0 only, is the delegation locked.
+    locked: Option<bool>,
+}
+
+impl Delegation {
+    fn new(from: &Address, to: &Address, locked: Option<bool>, del: &proto2::state::ResourceDelegation) -> Self {
+        Delegation {
+            from_address: from.to_string(),
+            to_address: to.to_string(),
+            amount_for_bandwidth: del.amount_for_bandwidth as _,
+            expiration_timestamp_for_bandwidth: del.expiration_timestamp_for_bandwidth as _,
+            amount_for_energy: del.amount_for_energy as _,
+            expiration_timestamp_for_energy: del.expiration_timestamp_for_energy as _,
+            locked,
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of delegations from an account, ordered by receiver address.
+pub struct DelegationPage {
+    delegations: Vec<Delegation>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of accounts an account delegates resources to, or receives delegated resources from, in delegation order.
+pub struct DelegationAccountPage {
+    addresses: Vec<String>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Aggregate resource delegations of an account, and its frozen balance available for delegation, at the latest
+/// block. For resource rental platforms.
+pub struct DelegationSummary {
+    address: String,
+    /// Timestamp of the latest block, in ms.
+    timestamp: f64,
+    /// Stake 1.0 delegations to others.
+    delegated_out_amount_for_bandwidth: f64,
+    delegated_out_amount_for_energy: f64,
+    /// Stake 2.0 delegations to others.
+    delegated_v2_out_amount_for_bandwidth: f64,
+    delegated_v2_out_amount_for_energy: f64,
+    /// Stake 2.0 delegations to others still in lock period, can not be undelegated now.
+    locked_v2_out_amount_for_bandwidth: f64,
+    locked_v2_out_amount_for_energy: f64,
+    /// Number of receivers of Stake 1.0 and 2.0 delegations.
+    num_of_receivers: i32,
+    /// Stake 1.0 delegations from others.
+    delegated_in_amount_for_bandwidth: f64,
+    delegated_in_amount_for_energy: f64,
+    /// Stake 2.0 delegations from others.
+    delegated_v2_in_amount_for_bandwidth: f64,
+    delegated_v2_in_amount_for_energy: f64,
+    /// Stake 2.0 frozen balance not delegated, the max of `DelegateResource`.
+    available_amount_for_bandwidth: f64,
+    available_amount_for_energy: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Frozen balance of an account that can be claimed back now, i.e. its freeze or delegation has expired by the
+/// latest block. Claimed by `UnfreezeBalance` or `WithdrawExpireUnfreeze`, no resource is unfrozen automatically.
+pub struct Claimable {
+    address: String,
+    /// Timestamp of the latest block, in ms.
+    timestamp: f64,
+    /// Frozen for oneself, unfreezable by `UnfreezeBalance`.
+    frozen_amount_for_bandwidth: f64,
+    frozen_amount_for_energy: f64,
+    /// Delegated to others, unfreezable by `UnfreezeBalance` with a receiver.
+    delegated_amount_for_bandwidth: f64,
+    delegated_amount_for_energy: f64,
+    /// Stake 2.0 pending unfreezes, withdrawable by `WithdrawExpireUnfreeze`.
+    withdrawable_amount: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Resource consumption and fees of a transaction, in SUN. Same breakdown as `receipt` of java-tron's
+/// TransactionInfo.
+pub struct ResourceReceipt {
+    /// Total fee burnt from the caller.
+    fee: f64,
+    net_usage: f64,
+    /// Bandwidth fee, including the account creation fee.
+    net_fee: f64,
+    /// Total energy consumed, frozen and burnt, the input of adaptive energy.
+    energy_usage_total: f64,
+    /// Energy paid by frozen energy of the caller.
+    energy_usage: f64,
+    /// Energy paid by burning TRX of the caller.
+    energy_fee: f64,
+    /// Energy paid by frozen energy of the contract origin.
+    origin_energy_usage: f64,
+    /// Fee of AssetIssue, ExchangeCreate, WitnessCreate, AccountPermissionUpdate etc.
+    contract_fee: f64,
+    multisig_fee: f64,
+    memo_fee: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A TRC721/TRC1155 token held by an owner.
+pub struct Nft {
+    contract_address: String,
+    /// Token id, uint256 in decimal.
+    token_id: String,
+    /// Amount, uint256 in decimal, always 1 for TRC721.
+    amount: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of NFTs of an owner, ordered by contract and token id.
+pub struct NftPage {
+    nfts: Vec<Nft>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A TRC721/TRC1155 transfer.
+pub struct NftTransfer {
+    block_number: i32,
+    transaction_id: String,
+    /// Null for mint.
+    from_address: Option<String>,
+    /// Null for burn.
+    to_address: Option<String>,
+    /// Token id, uint256 in decimal.
+    token_id: String,
+    /// Amount, uint256 in decimal, always 1 for TRC721.
+    amount: String,
+    is_trc1155: bool,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of transfers of a contract, in block order.
+pub struct NftTransferPage {
+    transfers: Vec<NftTransfer>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A shielded TRC20 note of a viewing key.
+pub struct ShieldedNote {
+    contract_address: String,
+    /// Position in the note commitment tree.
+    position: f64,
+    block_number: i32,
+    transaction_id: String,
+    /// In units of the scaling factor of the contract.
+    value: f64,
+    payment_address: String,
+    rcm: String,
+    memo: String,
+    nullifier: String,
+    /// Transaction spending the note, null if unspent.
+    spent_by: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of shielded notes of a viewing key, ordered by contract and position.
+pub struct ShieldedNotePage {
+    notes: Vec<ShieldedNote>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+// Market types follow java-tron's `MarketOrder`, `MarketOrderPair` and `MarketPrice`. There are no market actuators
+// or order book state yet, so orders, pairs and prices are never constructed.
+
+#[derive(juniper::GraphQLEnum)]
+#[allow(dead_code)]
+enum MarketOrderState {
+    Active,
+    Inactive,
+    Canceled,
+}
+
+#[derive(juniper::GraphQLObject)]
+#[allow(dead_code)]
+/// An order of the on-chain DEX. Token ids are `_` for TRX, asset ids otherwise.
+pub struct MarketOrder {
+    order_id: String,
+    owner_address: String,
+    create_time: DateTime<Utc>,
+    sell_token_id: String,
+    sell_token_quantity: f64,
+    buy_token_id: String,
+    /// Min quantity to buy, defines the price of the order.
+    buy_token_quantity: f64,
+    sell_token_quantity_remain: f64,
+    /// Quantity returned to the owner when the order is canceled or too small to match.
+    sell_token_quantity_return: f64,
+    state: MarketOrderState,
+    /// Previous order at the same price.
+    prev: Option<String>,
+    /// Next order at the same price.
+    next: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+#[allow(dead_code)]
+/// A trading pair of the on-chain DEX, with orders.
+pub struct MarketOrderPair {
+    sell_token_id: String,
+    buy_token_id: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+#[allow(dead_code)]
+/// A price level, as the ratio of quantities.
+pub struct MarketPrice {
+    sell_token_quantity: f64,
+    buy_token_quantity: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Price levels of a trading pair, best price first.
+pub struct MarketPriceList {
+    sell_token_id: String,
+    buy_token_id: String,
+    prices: Vec<MarketPrice>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of transactions of an account, latest first.
+pub struct TransactionPage {
+    transactions: Vec<Transaction>,
+    /// Opaque cursor of the next page, null if there's no more.
+    next_cursor: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// An event log emitted by a contract.
+pub struct Log {
+    block_number: i32,
+    transaction_id: String,
+    /// Index of the log in its transaction.
+    log_index: i32,
+    /// Contract address.
+    address: String,
+    /// Topics, 32 bytes in hex.
+    topics: Vec<String>,
+    /// Non-indexed data in hex.
+    data: String,
+    /// Decoded by the ABI attached to the contract in the node-local registry, null if not attached or not matched.
+    event: Option<DecodedEntry>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A function call or an event, decoded by an attached ABI.
+pub struct DecodedEntry {
+    name: String,
+    /// i.e. `transfer(address,uint256)`.
+    signature: String,
+    params: Vec<DecodedParam>,
+}
+
+impl DecodedEntry {
+    fn new(entry: &crate::abi::AbiEntry, params: Vec<crate::abi::DecodedParam>) -> Self {
+        DecodedEntry {
+            name: entry.name.clone(),
+            signature: entry.signature(),
+            params: params
+                .into_iter()
+                .map(|param| DecodedParam {
+                    name: param.name,
+                    ty: param.ty,
+                    value: param.value,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A decoded argument. Numbers in decimal, addresses in base58, bytes and undecoded values in hex.
+pub struct DecodedParam {
+    name: String,
+    #[graphql(name = "type")]
+    ty: String,
+    value: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A function or an event of an attached ABI.
+pub struct AbiSignature {
+    signature: String,
+    /// Function selector, 4 bytes in hex, or event topic, 32 bytes in hex.
+    hash: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Verified ABI attached to a contract, in the node-local registry.
+pub struct ContractAbi {
+    address: String,
+    /// As attached, in JSON.
+    abi: String,
+    metadata: String,
+    functions: Vec<AbiSignature>,
+    events: Vec<AbiSignature>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Commitment of all state changes up to a block, chained block by block.
+pub struct StateCommitment {
+    block_number: i32,
+    /// 32 bytes in hex.
+    commitment: String,
+}
+
+/// How gas of EVM tooling maps to TRON resources, returned with gas prices.
+const GAS_PRICE_MAPPING: &str = "1 gas = 1 energy, gas price = EnergyFee chain parameter in SUN per energy";
+
+#[derive(juniper::GraphQLObject)]
+/// Result of a constant contract call.
+pub struct ConstantCallOutput {
+    success: bool,
+    exit_reason: String,
+    energy_usage: f64,
+    /// Return data, in hex.
+    result: String,
+}
+
+impl ConstantCallOutput {
+    fn new(result: ConstantCallResult) -> Self {
+        ConstantCallOutput {
+            success: result.success,
+            exit_reason: result.exit_reason,
+            energy_usage: result.energy_usage as _,
+            result: hex::encode(&result.result),
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A call of a batch, either completed or failed before completion, i.e. timeout.
+pub struct ConstantCallBatchEntry {
+    output: Option<ConstantCallOutput>,
+    error: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Gas price for EVM tooling. Same as `eth_gasPrice` of java-tron's JSON-RPC.
+pub struct GasPrice {
+    /// In SUN per energy.
+    pub gas_price: f64,
+    pub mapping: String,
+}
+
+/// Max number of blocks of a fee history query, same as geth.
+const MAX_FEE_HISTORY_BLOCKS: i32 = 1024;
+
+#[derive(juniper::GraphQLObject)]
+/// Fee history for EVM tooling, shaped as `eth_feeHistory`. There's no priority fee on TRON, so no reward.
+pub struct FeeHistory {
+    pub oldest_block: i32,
+    /// Energy price of each block, plus the block after the newest one.
+    pub base_fee_per_gas: Vec<f64>,
+    /// Energy used by each block, relative to `TotalEnergyTargetLimit` of adaptive energy. Can be greater than 1.
+    /// 0 for empty blocks, or blocks executed by an older version.
+    pub gas_used_ratio: Vec<f64>,
+    pub mapping: String,
+}
+
+/// Max number of blocks of a log query.
+const MAX_LOG_QUERY_BLOCK_RANGE: i64 = 100_000;
+
+#[derive(juniper::GraphQLObject)]
+/// A storage slot of a contract.
+pub struct StorageEntry {
+    /// Storage key, 32 bytes in hex.
+    key: String,
+    /// Storage value, 32 bytes in hex.
+    value: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of contract storage, ordered by key.
+pub struct StorageRange {
+    entries: Vec<StorageEntry>,
+    /// Start key of the next page, null if there's no more.
+    next_key: Option<String>,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Pending transaction pool.
+pub struct PendingPool {
+    /// Number of pending transactions.
+    size: i32,
+    /// Max number of pending transactions.
+    limit: i32,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Lifecycle of a transaction submitted to this node.
+pub struct PendingTransactionStatus {
+    first_seen: DateTime<Utc>,
+    /// Latest announcement to peers, null if not yet.
+    last_broadcast: Option<DateTime<Utc>>,
+    broadcast_count: i32,
+    /// Number of the including block, null if pending.
+    included_in: Option<i32>,
+    /// Times re-injected to the pool after dropped from the chain, i.e. by a fork switch.
+    reinjected_count: i32,
+}
+
+/// Page size of list queries when limit is not given.
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Opaque cursor of a page, the hex encoded db key of the first entry.
+fn decode_cursor(cursor: &str, len: usize) -> FieldResult<Vec<u8>> {
+    match hex::decode(cursor) {
+        Ok(raw) if raw.len() == len => Ok(raw),
+        _ => Err("invalid cursor".into()),
+    }
+}
+
+/// Caps the number of concurrent heavy queries, shared by all requests.
+///
+/// A max of 0 rejects every heavy query, refused by config validation.
+pub(crate) struct HeavyQueryLimiter {
+    running: AtomicUsize,
+    max: usize,
+}
+
+impl HeavyQueryLimiter {
+    pub fn new(max: usize) -> Self {
+        HeavyQueryLimiter {
+            running: AtomicUsize::new(0),
+            max,
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Rejects instead of waiting, so that worker threads are not pinned by queued scans.
+    fn acquire(&self, registry: &Registry) -> FieldResult<HeavyQueryGuard<'_>> {
+        if self.running.fetch_add(1, Ordering::SeqCst) >= self.max {
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            registry.inc(&metrics::HEAVY_QUERIES_REJECTED, 1);
+            return Err("too many concurrent heavy queries, try again later".into());
+        }
+        Ok(HeavyQueryGuard(&self.running))
+    }
+}
+
+struct HeavyQueryGuard<'a>(&'a AtomicUsize);
+
+impl Drop for HeavyQueryGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 #[derive(Clone)]
 pub(crate) struct Context {
     pub app: Arc<AppContext>,
+    /// Request is authorized to use the admin namespace.
+    pub is_admin: bool,
+    pub heavy_queries: Arc<HeavyQueryLimiter>,
 }
 
 // To make our context usable by Juniper, we have to implement a marker trait.
 impl juniper::Context for Context {}
 
 impl Context {
+    /// Page size of list queries, capped by `graphql.max-page-size` in config.
+    fn page_size(&self, limit: Option<i32>) -> FieldResult<usize> {
+        let max_page_size = self.app.config.graphql.max_page_size;
+        match limit {
+            Some(n) if n <= 0 || n as usize > max_page_size => {
+                Err(format!("limit must be in [1, {}]", max_page_size).into())
+            }
+            Some(n) => Ok(n as usize),
+            None => Ok(DEFAULT_PAGE_SIZE.min(max_page_size)),
+        }
+    }
+
     pub fn get_node_info(&self) -> NodeInfo {
         let ref db = self.app.chain_db;
         NodeInfo {
@@ -169,6 +955,40 @@ impl Context {
             num_immutable_mem_table: db.get_accumulated_db_property("rocksdb.num-immutable-mem-table") as _,
             is_write_stopped: db.get_accumulated_db_property("rocksdb.is-write-stopped") > 0,
             total_size: db.get_accumulated_db_property("rocksdb.live-sst-files-size") as _,
+            clock_drift: self.app.clock_drift.read().unwrap().as_ref().map(|drift| drift.offset as _),
+            state_snapshot_age: self
+                .app
+                .state_db
+                .as_ref()
+                .map(|state_db| state_db.snapshot_age().as_millis() as _),
+            state_block_gap: self.app.state_db.as_ref().and_then(|state_db| {
+                state_db.catch_up_with_primary();
+                let state_height = state_db
+                    .get(&state::keys::DynamicProperty::LatestBlockNumber)
+                    .ok()?
+                    .unwrap_or(0);
+                Some((db.get_block_height() - state_height).max(0) as _)
+            }),
+            running_heavy_queries: self.heavy_queries.running() as _,
+            services: self
+                .app
+                .services
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(name, health)| Service {
+                    name: name.to_string(),
+                    state: match health.state {
+                        supervisor::ServiceState::Running => ServiceState::Running,
+                        supervisor::ServiceState::Restarting => ServiceState::Restarting,
+                        supervisor::ServiceState::Stopped => ServiceState::Stopped,
+                        supervisor::ServiceState::Failed => ServiceState::Failed,
+                    },
+                    restarts: health.restarts as _,
+                    last_error: health.last_error.clone(),
+                    started_at: Utc.timestamp_millis(health.started_at),
+                })
+                .collect(),
         }
     }
 
@@ -183,6 +1003,23 @@ impl Context {
             (None, None) => self.app.chain_db.highest_block()?,
         };
 
+        let resource_usage = match self.app.state_db {
+            Some(ref state_db) => {
+                state_db.catch_up_with_primary();
+                state_db
+                    .get(&state::keys::BlockResourceUsage(block.number()))?
+                    .map(|usage| BlockResourceUsage {
+                        num_transactions: usage.num_transactions as _,
+                        net_usage: usage.bandwidth_usage as _,
+                        net_fee: usage.bandwidth_fee as _,
+                        energy_usage_total: usage.energy as _,
+                        energy_usage: usage.energy_usage as _,
+                        energy_fee: usage.energy_fee as _,
+                    })
+            }
+            None => None,
+        };
+
         let IndexedBlock { header, transactions } = block;
         let raw_header = header.raw.raw_data.as_ref().unwrap();
 
@@ -200,12 +1037,1418 @@ impl Context {
             version: raw_header.version,
             witness_signature: hex::encode(&header.raw.witness_signature),
             transactions: transactions,
+            resource_usage,
         })
     }
 
+    /// All connected channel peers. Same as `ListNodes` of java-tron, but only active connections.
+    pub fn get_peers(&self) -> Vec<Peer> {
+        let mut peers: Vec<_> = self.app.peer_infos.read().unwrap().values().cloned().collect();
+        peers.sort_by_key(|peer| peer.connected_at);
+        peers
+            .into_iter()
+            .map(|peer| Peer {
+                remote_address: peer.remote_addr.to_string(),
+                address: peer.advertised_address,
+                port: peer.advertised_port,
+                node_id: hex::encode(&peer.node_id),
+                p2p_version: peer.p2p_version,
+                code_version: peer.code_version,
+                head_block_number: peer.head_block_number as _,
+                latency: peer.latency as _,
+                connected_at: Utc.timestamp_millis(peer.connected_at),
+            })
+            .collect()
+    }
+
+    /// All witnesses in state-db, sorted by votes.
+    fn sorted_witnesses(&self) -> FieldResult<Vec<proto2::state::Witness>> {
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let mut witnesses: Vec<proto2::state::Witness> =
+            state_db.iter::<_, state::keys::Witness>().map(|(_, wit)| wit).collect();
+        witnesses.sort_by(|a, b| b.vote_count.cmp(&a.vote_count).then_with(|| a.address.cmp(&b.address)));
+        Ok(witnesses)
+    }
+
+    /// All witnesses, sorted by votes. Same as `ListWitnesses` of java-tron.
+    pub fn get_witnesses(&self) -> FieldResult<Vec<Witness>> {
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        Ok(self.sorted_witnesses()?.into_iter().map(Witness::from).collect())
+    }
+
+    /// A page of witnesses, sorted by votes.
+    ///
+    /// Sorting by votes needs all witnesses, which is a small column, only the output is paginated.
+    pub fn get_witness_page(&self, limit: Option<i32>, after: Option<String>) -> FieldResult<WitnessPage> {
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let limit = self.page_size(limit)?;
+        let witnesses = self.sorted_witnesses()?;
+
+        // Cursor of a witness: <<vote_count: i64, address>>
+        let cursor_of =
+            |wit: &proto2::state::Witness| hex::encode([&wit.vote_count.to_be_bytes()[..], &wit.address].concat());
+        let skipped = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, 8 + 21)?;
+                let mut vote_count = [0u8; 8];
+                vote_count.copy_from_slice(&raw[..8]);
+                let vote_count = i64::from_be_bytes(vote_count);
+                witnesses
+                    .iter()
+                    .take_while(|wit| (-wit.vote_count, &wit.address[..]) < (-vote_count, &raw[8..]))
+                    .count()
+            }
+            None => 0,
+        };
+        let mut witnesses: Vec<_> = witnesses.into_iter().skip(skipped).take(limit + 1).collect();
+        let next_cursor = if witnesses.len() > limit {
+            witnesses.pop().as_ref().map(cursor_of)
+        } else {
+            None
+        };
+
+        let witnesses = witnesses.into_iter().map(Witness::from).collect();
+        Ok(WitnessPage { witnesses, next_cursor })
+    }
+
+    /// TRC10 assets, ordered by token id. Same as `GetAssetIssueList` of java-tron, paginated.
+    pub fn get_assets(&self, limit: Option<i32>, after: Option<String>) -> FieldResult<AssetPage> {
+        use state::keys::{Asset as AssetKey, Key};
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+        let start = match after {
+            Some(cursor) => decode_cursor(&cursor, 8)?,
+            None => vec![],
+        };
+
+        let mut assets = Vec::with_capacity(limit);
+        let mut next_cursor = None;
+        // Take one more entry to find the start of the next page.
+        state_db.for_each_with_prefix_from(&[], &start, |key: &AssetKey, asset: &proto2::state::Asset| {
+            if assets.len() == limit {
+                next_cursor = Some(hex::encode(key.key()));
+                return false;
+            }
+            assets.push(Asset {
+                id: key.0 as _,
+                owner_address: Address::try_from(&asset.owner_address)
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_else(|_| hex::encode(&asset.owner_address)),
+                name: asset.name.clone(),
+                abbr: asset.abbr.clone(),
+                total_supply: asset.total_supply as _,
+                precision: asset.precision,
+                url: asset.url.clone(),
+                description: String::from_utf8_lossy(&asset.description).into_owned(),
+            });
+            true
+        });
+
+        Ok(AssetPage { assets, next_cursor })
+    }
+
+    /// Proposals, ordered by id, optionally of a state. Same as `ListProposals` and `GetPaginatedProposalList` of
+    /// java-tron, paginated by cursor.
+    pub fn get_proposals(
+        &self,
+        state: Option<ProposalState>,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<ProposalPage> {
+        use state::keys::{Key, Proposal as ProposalKey};
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+        let start = match after {
+            Some(cursor) => decode_cursor(&cursor, 8)?,
+            None => vec![],
+        };
+
+        let mut proposals = Vec::with_capacity(limit);
+        let mut next_cursor = None;
+        // Take one more entry to find the start of the next page.
+        state_db.for_each_with_prefix_from(&[], &start, |key: &ProposalKey, proposal: &proto2::state::Proposal| {
+            let proposal = Proposal::new(proposal);
+            if state.as_ref().map(|state| *state != proposal.state).unwrap_or(false) {
+                return true;
+            }
+            if proposals.len() == limit {
+                next_cursor = Some(hex::encode(key.key()));
+                return false;
+            }
+            proposals.push(proposal);
+            true
+        });
+
+        Ok(ProposalPage { proposals, next_cursor })
+    }
+
+    /// A proposal by id. Same as `GetProposalById` of java-tron.
+    pub fn get_proposal(&self, id: i32) -> FieldResult<Option<Proposal>> {
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        Ok(state_db
+            .get(&state::keys::Proposal(id as _))?
+            .map(|proposal| Proposal::new(&proposal)))
+    }
+
+    /// All chain parameters known to this node, ordered by key. Same as `GetChainParameters` of java-tron.
+    pub fn get_chain_parameters(&self) -> FieldResult<Vec<ChainParameterValue>> {
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let mut parameters = vec![];
+        for (param, default) in state::parameter::default_parameters() {
+            parameters.push(ChainParameterValue {
+                key: param as i32,
+                name: format!("{:?}", param),
+                value: state_db.get(&param)?.unwrap_or(default) as _,
+            });
+        }
+        parameters.sort_by_key(|param| param.key);
+        Ok(parameters)
+    }
+
+    /// Accounts owning at least `min_balance`, balance plus frozen, richest first.
+    ///
+    /// All accounts are scanned for each page, token balances are only loaded for accounts of the page.
+    pub fn get_rich_list(
+        &self,
+        min_balance: f64,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<RichAccountPage> {
+        use state::keys::{Account as AccountKey, AccountAsset};
+        use state::stats::{RichAccount as RichEntry, RichList};
+        use std::cmp::Reverse;
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+        if min_balance < 0.0 {
+            return Err("minBalance must not be negative".into());
+        }
+        let min_balance = min_balance as i64;
+
+        // Cursor is the rank of the first entry of the page, total in big-endian plus address.
+        let start = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, 8 + 21)?;
+                let mut total = [0u8; 8];
+                total.copy_from_slice(&raw[..8]);
+                let total = u64::from_be_bytes(total) as i64;
+                let addr = Address::try_from(&raw[8..]).map_err(|_| "invalid cursor")?;
+                Some((Reverse(total), addr))
+            }
+            None => None,
+        };
+
+        // One more for the cursor of the next page.
+        let mut list = RichList::new(limit + 1);
+        state_db.for_each(|key: &AccountKey, acct: &proto2::state::Account| {
+            let entry = RichEntry::new(key.0, acct);
+            if entry.total() >= min_balance && start.map(|start| entry.rank() >= start).unwrap_or(true) {
+                list.add(entry);
+            }
+        });
+        let mut entries = list.into_sorted_vec();
+
+        let next_cursor = entries.get(limit).map(|entry| {
+            let mut raw = (entry.total() as u64).to_be_bytes().to_vec();
+            raw.extend_from_slice(entry.address.as_bytes());
+            hex::encode(raw)
+        });
+        entries.truncate(limit);
+
+        let accounts = entries
+            .into_iter()
+            .map(|entry| {
+                let mut assets = vec![];
+                state_db.scan_prefix_from(
+                    &entry.address,
+                    entry.address.as_bytes(),
+                    |key: &AccountAsset, balance: &i64| {
+                        if *balance != 0 {
+                            assets.push(AssetBalance {
+                                id: key.1 as _,
+                                balance: *balance as _,
+                            });
+                        }
+                        true
+                    },
+                );
+                RichAccount {
+                    address: entry.address.to_string(),
+                    balance: entry.balance as _,
+                    frozen: entry.frozen as _,
+                    total: entry.total() as _,
+                    assets,
+                }
+            })
+            .collect();
+
+        Ok(RichAccountPage { accounts, next_cursor })
+    }
+
+    /// Resource delegations from an account, ordered by receiver. Stake 2.0 delegations if `v2`.
+    pub fn get_delegations(
+        &self,
+        address: String,
+        v2: bool,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<DelegationPage> {
+        use proto2::state::ResourceDelegation;
+        use state::keys::{Key, ResourceDelegation as DelegationKey, ResourceDelegationV2 as DelegationV2Key};
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+
+        let addr: Address = address.parse()?;
+        let prefix = addr.as_bytes();
+        let start = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, if v2 { 21 * 2 + 1 } else { 21 * 2 })?;
+                if !raw.starts_with(prefix) {
+                    return Err("cursor does not match address".into());
+                }
+                raw
+            }
+            None => prefix.to_vec(),
+        };
+
+        let mut delegations = Vec::with_capacity(limit);
+        let mut next_cursor = None;
+        // Take one more entry to find the start of the next page.
+        if v2 {
+            state_db.scan_prefix_from(&addr, &start, |key: &DelegationV2Key, del: &ResourceDelegation| {
+                if delegations.len() == limit {
+                    next_cursor = Some(hex::encode(key.key()));
+                    return false;
+                }
+                delegations.push(Delegation::new(&key.0, &key.1, Some(key.2), del));
+                true
+            });
+        } else {
+            state_db.scan_prefix_from(&addr, &start, |key: &DelegationKey, del: &ResourceDelegation| {
+                if delegations.len() == limit {
+                    next_cursor = Some(hex::encode(key.key()));
+                    return false;
+                }
+                delegations.push(Delegation::new(&key.0, &key.1, None, del));
+                true
+            });
+        }
+
+        Ok(DelegationPage {
+            delegations,
+            next_cursor,
+        })
+    }
+
+    /// Receivers of resource delegations from an account, or delegators to it if `to_this`. Stake 2.0 delegations
+    /// if `v2`, whose receivers are ordered by address.
+    pub fn get_delegation_accounts(
+        &self,
+        address: String,
+        to_this: bool,
+        v2: bool,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<DelegationAccountPage> {
+        use proto2::state::ResourceDelegation;
+        use state::keys::{
+            ResourceDelegationIndex, ResourceDelegationReverseIndex, ResourceDelegationV2 as DelegationV2Key,
+            ResourceDelegationV2ReverseIndex,
+        };
+
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+
+        let addr: Address = address.parse()?;
+        let indexed_addrs = match (to_this, v2) {
+            (true, false) => state_db.get(&ResourceDelegationReverseIndex(addr))?.unwrap_or_default(),
+            (false, false) => state_db.get(&ResourceDelegationIndex(addr))?.unwrap_or_default(),
+            (true, true) => state_db.get(&ResourceDelegationV2ReverseIndex(addr))?.unwrap_or_default(),
+            (false, true) => {
+                // Locked and unlocked delegations to the same receiver are adjacent.
+                let mut to_addrs: Vec<Address> = vec![];
+                state_db.scan_prefix_from(&addr, addr.as_bytes(), |key: &DelegationV2Key, _: &ResourceDelegation| {
+                    if to_addrs.last() != Some(&key.1) {
+                        to_addrs.push(key.1);
+                    }
+                    true
+                });
+                to_addrs
+            }
+        };
+
+        let start = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, 21)?;
+                indexed_addrs
+                    .iter()
+                    .position(|indexed| indexed.as_bytes() == &raw[..])
+                    .ok_or("cursor does not match address")?
+            }
+            None => 0,
+        };
+        let end = indexed_addrs.len().min(start + limit);
+
+        Ok(DelegationAccountPage {
+            addresses: indexed_addrs[start..end].iter().map(|addr| addr.to_string()).collect(),
+            next_cursor: indexed_addrs.get(end).map(|addr| hex::encode(addr.as_bytes())),
+        })
+    }
+
+    /// Aggregate delegations of an account, from its delegation records.
+    pub fn get_delegation_summary(&self, address: String) -> FieldResult<DelegationSummary> {
+        use proto2::state::ResourceDelegation;
+        use state::keys::{
+            DynamicProperty, ResourceDelegation as DelegationKey, ResourceDelegationReverseIndex,
+            ResourceDelegationV2 as DelegationV2Key, ResourceDelegationV2ReverseIndex,
+        };
+        use std::collections::HashSet;
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let addr: Address = address.parse()?;
+        let acct = state_db
+            .get(&state::keys::Account(addr))?
+            .ok_or("account not found")?;
+        let now = state_db
+            .get(&DynamicProperty::LatestBlockTimestamp)?
+            .ok_or("latest block timestamp not found")?;
+
+        let mut summary = DelegationSummary {
+            address: addr.to_string(),
+            timestamp: now as _,
+            delegated_out_amount_for_bandwidth: 0.0,
+            delegated_out_amount_for_energy: 0.0,
+            delegated_v2_out_amount_for_bandwidth: 0.0,
+            delegated_v2_out_amount_for_energy: 0.0,
+            locked_v2_out_amount_for_bandwidth: 0.0,
+            locked_v2_out_amount_for_energy: 0.0,
+            num_of_receivers: 0,
+            delegated_in_amount_for_bandwidth: 0.0,
+            delegated_in_amount_for_energy: 0.0,
+            delegated_v2_in_amount_for_bandwidth: 0.0,
+            delegated_v2_in_amount_for_energy: 0.0,
+            available_amount_for_bandwidth: acct.frozen_v2_amount_for_bandwidth as _,
+            available_amount_for_energy: acct.frozen_v2_amount_for_energy as _,
+        };
+        let mut receivers = HashSet::new();
+
+        // NOTE: Freezes for oneself are saved as delegations to oneself.
+        state_db.scan_prefix_from(&addr, addr.as_bytes(), |key: &DelegationKey, del: &ResourceDelegation| {
+            if key.1 != addr {
+                summary.delegated_out_amount_for_bandwidth += del.amount_for_bandwidth as f64;
+                summary.delegated_out_amount_for_energy += del.amount_for_energy as f64;
+                receivers.insert(key.1);
+            }
+            true
+        });
+        state_db.scan_prefix_from(&addr, addr.as_bytes(), |key: &DelegationV2Key, del: &ResourceDelegation| {
+            summary.delegated_v2_out_amount_for_bandwidth += del.amount_for_bandwidth as f64;
+            summary.delegated_v2_out_amount_for_energy += del.amount_for_energy as f64;
+            if key.2 && del.expiration_timestamp_for_bandwidth > now {
+                summary.locked_v2_out_amount_for_bandwidth += del.amount_for_bandwidth as f64;
+            }
+            if key.2 && del.expiration_timestamp_for_energy > now {
+                summary.locked_v2_out_amount_for_energy += del.amount_for_energy as f64;
+            }
+            receivers.insert(key.1);
+            true
+        });
+        summary.num_of_receivers = receivers.len() as _;
+
+        for from in state_db.get(&ResourceDelegationReverseIndex(addr))?.unwrap_or_default() {
+            if let Some(del) = state_db.get(&DelegationKey(from, addr))? {
+                summary.delegated_in_amount_for_bandwidth += del.amount_for_bandwidth as f64;
+                summary.delegated_in_amount_for_energy += del.amount_for_energy as f64;
+            }
+        }
+        for from in state_db.get(&ResourceDelegationV2ReverseIndex(addr))?.unwrap_or_default() {
+            for &lock in &[false, true] {
+                if let Some(del) = state_db.get(&DelegationV2Key(from, addr, lock))? {
+                    summary.delegated_v2_in_amount_for_bandwidth += del.amount_for_bandwidth as f64;
+                    summary.delegated_v2_in_amount_for_energy += del.amount_for_energy as f64;
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Frozen balance of an account claimable by the latest block.
+    pub fn get_claimable(&self, address: String) -> FieldResult<Claimable> {
+        use proto2::state::ResourceDelegation;
+        use state::keys::{DynamicProperty, ResourceDelegation as DelegationKey};
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let addr: Address = address.parse()?;
+        let acct = state_db
+            .get(&state::keys::Account(addr))?
+            .ok_or("account not found")?;
+        let now = state_db
+            .get(&DynamicProperty::LatestBlockTimestamp)?
+            .ok_or("latest block timestamp not found")?;
+
+        let mut claimable = Claimable {
+            address: addr.to_string(),
+            timestamp: now as _,
+            frozen_amount_for_bandwidth: 0.0,
+            frozen_amount_for_energy: 0.0,
+            delegated_amount_for_bandwidth: 0.0,
+            delegated_amount_for_energy: 0.0,
+            withdrawable_amount: 0.0,
+        };
+
+        // NOTE: Freezes for oneself are saved as delegations to oneself, with the frozen amount in account.
+        state_db.scan_prefix_from(&addr, addr.as_bytes(), |key: &DelegationKey, del: &ResourceDelegation| {
+            if key.1 == addr {
+                if acct.frozen_amount_for_bandwidth > 0 && del.expiration_timestamp_for_bandwidth <= now {
+                    claimable.frozen_amount_for_bandwidth = acct.frozen_amount_for_bandwidth as _;
+                }
+                if acct.frozen_amount_for_energy > 0 && del.expiration_timestamp_for_energy <= now {
+                    claimable.frozen_amount_for_energy = acct.frozen_amount_for_energy as _;
+                }
+            } else {
+                if del.amount_for_bandwidth > 0 && del.expiration_timestamp_for_bandwidth <= now {
+                    claimable.delegated_amount_for_bandwidth += del.amount_for_bandwidth as f64;
+                }
+                if del.amount_for_energy > 0 && del.expiration_timestamp_for_energy <= now {
+                    claimable.delegated_amount_for_energy += del.amount_for_energy as f64;
+                }
+            }
+            true
+        });
+
+        claimable.withdrawable_amount = acct
+            .unfrozen_v2
+            .iter()
+            .filter(|unfreeze| unfreeze.expiration_timestamp <= now)
+            .map(|unfreeze| unfreeze.amount as f64)
+            .sum();
+
+        Ok(claimable)
+    }
+
+    /// Address of an account by its name, set by `UpdateAccount`.
+    pub fn get_account_by_name(&self, name: String) -> FieldResult<Option<String>> {
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        Ok(state_db
+            .get(&state::keys::AccountIndex(name))?
+            .map(|addr| addr.to_string()))
+    }
+
+    /// State commitment of the latest block of state-db, to be compared with other nodes.
+    pub fn get_state_commitment(&self) -> FieldResult<Option<StateCommitment>> {
+        use state::keys::DynamicProperty;
+
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let commitment = match state_db.get(&state::keys::StateCommitment)? {
+            Some(commitment) => commitment,
+            // never computed, e.g. no block applied since upgrade
+            None => return Ok(None),
+        };
+        let block_number = state_db
+            .get(&DynamicProperty::LatestBlockNumber)?
+            .ok_or("latest block number not found")?;
+        Ok(Some(StateCommitment {
+            block_number: block_number as _,
+            commitment: hex::encode(commitment.as_bytes()),
+        }))
+    }
+
+    /// Price history of `EnergyFee` or `BandwidthFee`. Same as `GetEnergyPrices` and `GetBandwidthPrices` of java-tron.
+    pub fn get_price_history(&self, param: state::keys::ChainParameter) -> FieldResult<String> {
+        use state::keys::PriceHistory;
+
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let history = match state_db.get(&PriceHistory(param))? {
+            Some(history) => history,
+            // never changed by proposals
+            None => vec![(0, 0, state_db.get(&param)?.ok_or("chain parameter not found")?)],
+        };
+        Ok(PriceHistory::to_java_string(&history))
+    }
+
+    /// ABI attached to a contract in the node-local registry.
+    pub fn get_contract_abi(&self, address: String) -> FieldResult<Option<ContractAbi>> {
+        let addr: Address = address.parse()?;
+        let abi = match self.app.abi_registry.get(&addr) {
+            Some(abi) => abi,
+            None => return Ok(None),
+        };
+        Ok(Some(ContractAbi {
+            address: addr.to_string(),
+            abi: abi.abi.clone(),
+            metadata: abi.metadata.clone(),
+            functions: abi
+                .functions()
+                .map(|entry| AbiSignature {
+                    signature: entry.signature(),
+                    hash: hex::encode(&entry.hash()[..4]),
+                })
+                .collect(),
+            events: abi
+                .events()
+                .map(|entry| AbiSignature {
+                    signature: entry.signature(),
+                    hash: hex::encode(&entry.hash()),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Decode call data of a contract by its attached ABI.
+    pub fn decode_call(&self, address: String, data: String) -> FieldResult<Option<DecodedEntry>> {
+        let addr: Address = address.parse()?;
+        let data = hex::decode(&data)?;
+        Ok(self.app.abi_registry.get(&addr).and_then(|abi| {
+            abi.decode_call(&data)
+                .map(|(entry, params)| DecodedEntry::new(entry, params))
+        }))
+    }
+
+    /// Call a contract against the latest state, without changing it. Same as `TriggerConstantContract` of java-tron.
+    pub fn constant_call(
+        &self,
+        caller: String,
+        contract: String,
+        data: String,
+        call_value: Option<f64>,
+    ) -> FieldResult<ConstantCallOutput> {
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let service = self.app.constant_calls.as_ref().ok_or("state-db is not available")?;
+
+        let call = ConstantCall {
+            caller: caller.parse()?,
+            contract: contract.parse()?,
+            data: hex::decode(&data)?,
+            call_value: call_value.unwrap_or_default() as _,
+        };
+        if call.call_value < 0 {
+            return Err("callValue must not be negative".into());
+        }
+        Ok(ConstantCallOutput::new(service.call(call)?))
+    }
+
+    /// Execute constant calls against the same state, in order. A failed call does not stop the rest.
+    pub fn constant_call_batch(&self, calls: Vec<ConstantCall>) -> FieldResult<Vec<ConstantCallBatchEntry>> {
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let service = self.app.constant_calls.as_ref().ok_or("state-db is not available")?;
+
+        if calls.iter().any(|call| call.call_value < 0) {
+            return Err("callValue must not be negative".into());
+        }
+        Ok(service
+            .call_batch(calls)?
+            .into_iter()
+            .map(|ret| match ret {
+                Ok(result) => ConstantCallBatchEntry {
+                    output: Some(ConstantCallOutput::new(result)),
+                    error: None,
+                },
+                Err(e) => ConstantCallBatchEntry {
+                    output: None,
+                    error: Some(e),
+                },
+            })
+            .collect())
+    }
+
+    /// Current energy price as gas price.
+    pub fn get_gas_price(&self) -> FieldResult<GasPrice> {
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let energy_fee = state_db
+            .get(&state::keys::ChainParameter::EnergyFee)?
+            .ok_or("chain parameter not found")?;
+        Ok(GasPrice {
+            gas_price: energy_fee as _,
+            mapping: GAS_PRICE_MAPPING.to_owned(),
+        })
+    }
+
+    /// Energy prices and usage of `block_count` blocks up to `newest_block`, the latest block of state-db by default.
+    pub fn get_fee_history(&self, block_count: i32, newest_block: Option<i32>) -> FieldResult<FeeHistory> {
+        use state::keys::{BlockResourceUsage, ChainParameter, DynamicProperty, PriceHistory};
+
+        if block_count <= 0 || block_count > MAX_FEE_HISTORY_BLOCKS {
+            return Err(format!("blockCount must be in [1, {}]", MAX_FEE_HISTORY_BLOCKS).into());
+        }
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let latest_block_number = state_db
+            .get(&DynamicProperty::LatestBlockNumber)?
+            .ok_or("latest block number not found")?;
+        let newest_block = match newest_block {
+            Some(num) if num < 0 || num as i64 > latest_block_number => {
+                return Err("newestBlock is not executed yet".into())
+            }
+            Some(num) => num as i64,
+            None => latest_block_number,
+        };
+        let oldest_block = (newest_block - block_count as i64 + 1).max(0);
+
+        let current_price = state_db
+            .get(&ChainParameter::EnergyFee)?
+            .ok_or("chain parameter not found")?;
+        let history = state_db.get(&PriceHistory(ChainParameter::EnergyFee))?.unwrap_or_default();
+        // Price changes at a maintenance block apply to the block after.
+        let price_at = |block_number: i64| {
+            history
+                .iter()
+                .rev()
+                .find(|&&(changed_at, _, _)| changed_at < block_number)
+                .map(|&(_, _, price)| price)
+                .unwrap_or_else(|| history.first().map(|&(_, _, price)| price).unwrap_or(current_price))
+        };
+        let target_limit = state_db.get(&DynamicProperty::TotalEnergyTargetLimit)?.unwrap_or(0);
+
+        let mut base_fee_per_gas = vec![];
+        let mut gas_used_ratio = vec![];
+        for block_number in oldest_block..=newest_block {
+            base_fee_per_gas.push(price_at(block_number) as f64);
+            let energy = state_db
+                .get(&BlockResourceUsage(block_number))?
+                .map(|usage| usage.energy)
+                .unwrap_or(0);
+            gas_used_ratio.push(if target_limit > 0 {
+                energy as f64 / target_limit as f64
+            } else {
+                0.0
+            });
+        }
+        base_fee_per_gas.push(price_at(newest_block + 1) as f64);
+
+        Ok(FeeHistory {
+            oldest_block: oldest_block as _,
+            base_fee_per_gas,
+            gas_used_ratio,
+            mapping: GAS_PRICE_MAPPING.to_owned(),
+        })
+    }
+
+    /// Storage of a contract, from `start`(inclusive). Same as `debug_storageRangeAt` of geth, on the solid state.
+    pub fn get_storage_range(
+        &self,
+        address: String,
+        start: Option<String>,
+        limit: Option<i32>,
+    ) -> FieldResult<StorageRange> {
+        use state::keys::{ContractStorage, Key};
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let addr: Address = address.parse()?;
+        let start = match start {
+            Some(start) => {
+                let raw = hex::decode(&start)?;
+                if raw.len() != 32 {
+                    return Err("start key must be 32 bytes".into());
+                }
+                H256::from_slice(&raw)
+            }
+            None => H256::zero(),
+        };
+        let limit = self.page_size(limit)?;
+
+        let start_key = ContractStorage(addr, start).key();
+
+        let mut entries = Vec::with_capacity(limit);
+        let mut next_key = None;
+        // Take one more entry to find the start of the next page.
+        state_db.scan_prefix_from(&addr, &start_key, |key: &ContractStorage, value: &H256| {
+            if entries.len() == limit {
+                next_key = Some(hex::encode(key.1.as_bytes()));
+                return false;
+            }
+            entries.push(StorageEntry {
+                key: hex::encode(key.1.as_bytes()),
+                value: hex::encode(value.as_bytes()),
+            });
+            true
+        });
+
+        Ok(StorageRange { entries, next_key })
+    }
+
+    fn nft_index_enabled(&self) -> FieldResult<()> {
+        if !self.app.config.indexer.nft {
+            return Err("NFT indexer is disabled, requires indexer.nft in config".into());
+        }
+        Ok(())
+    }
+
+    /// TRC721/TRC1155 tokens of an owner, from the optional NFT index.
+    pub fn get_nfts_by_owner(&self, owner: String, limit: Option<i32>, after: Option<String>) -> FieldResult<NftPage> {
+        use state::keys::{Key, NftBalance};
+
+        self.nft_index_enabled()?;
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+
+        let owner: Address = owner.parse()?;
+        let prefix = NftBalance::prefix_of(&owner);
+        let start = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, 1 + 21 + 21 + 32)?;
+                if !raw.starts_with(&prefix) {
+                    return Err("cursor does not match owner".into());
+                }
+                raw
+            }
+            None => prefix.clone(),
+        };
+
+        let mut nfts = Vec::with_capacity(limit);
+        let mut next_cursor = None;
+        // Take one more entry to find the start of the next page.
+        state_db.for_each_with_prefix_from(&prefix, &start, |key: &NftBalance, amount: &H256| {
+            if nfts.len() == limit {
+                next_cursor = Some(hex::encode(key.key()));
+                return false;
+            }
+            nfts.push(Nft {
+                contract_address: key.1.to_string(),
+                token_id: U256::from_big_endian(key.2.as_bytes()).to_string(),
+                amount: U256::from_big_endian(amount.as_bytes()).to_string(),
+            });
+            true
+        });
+
+        Ok(NftPage { nfts, next_cursor })
+    }
+
+    /// Owner of a TRC721 token, from the optional NFT index.
+    pub fn get_nft_owner(&self, contract: String, token_id: String) -> FieldResult<Option<String>> {
+        self.nft_index_enabled()?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let contract: Address = contract.parse()?;
+        let token_id = U256::from_dec_str(&token_id).map_err(|_| "invalid token id")?;
+        let mut raw = H256::zero();
+        token_id.to_big_endian(raw.as_bytes_mut());
+        Ok(state_db
+            .get(&state::keys::NftOwner(contract, raw))?
+            .map(|owner| owner.to_string()))
+    }
+
+    /// Transfers of a TRC721/TRC1155 contract, from the optional NFT index.
+    pub fn get_nft_transfers(
+        &self,
+        contract: String,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<NftTransferPage> {
+        use state::keys::{Key, NftTransfer as NftTransferKey};
+
+        self.nft_index_enabled()?;
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+
+        let contract: Address = contract.parse()?;
+        let prefix = NftTransferKey::prefix_of(&contract);
+        let start = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, 1 + 21 + 8 + 4 * 3)?;
+                if !raw.starts_with(&prefix) {
+                    return Err("cursor does not match contract".into());
+                }
+                raw
+            }
+            None => prefix.clone(),
+        };
+
+        let to_address = |raw: &[u8]| Address::try_from(raw).ok().map(|addr| addr.to_string());
+        let mut transfers = Vec::with_capacity(limit);
+        let mut next_cursor = None;
+        // Take one more entry to find the start of the next page.
+        state_db.for_each_with_prefix_from(
+            &prefix,
+            &start,
+            |key: &NftTransferKey, transfer: &proto2::state::NftTransfer| {
+                if transfers.len() == limit {
+                    next_cursor = Some(hex::encode(key.key()));
+                    return false;
+                }
+                transfers.push(NftTransfer {
+                    block_number: key.1 as _,
+                    transaction_id: hex::encode(&transfer.transaction_id),
+                    from_address: to_address(&transfer.from_address),
+                    to_address: to_address(&transfer.to_address),
+                    token_id: U256::from_big_endian(&transfer.token_id).to_string(),
+                    amount: U256::from_big_endian(&transfer.amount).to_string(),
+                    is_trc1155: transfer.is_trc1155,
+                });
+                true
+            },
+        );
+
+        Ok(NftTransferPage {
+            transfers,
+            next_cursor,
+        })
+    }
+
+    /// Key id of a configured viewing key, the sha256 of its raw bytes.
+    fn shielded_key_id(&self, viewing_key: &str) -> FieldResult<H256> {
+        let keys = &self.app.config.indexer.shielded_viewing_keys;
+        if keys.is_empty() {
+            return Err("shielded note scanner is disabled, requires indexer.shielded-viewing-keys in config".into());
+        }
+        let raw = hex::decode(viewing_key).map_err(|_| "invalid viewing key hex")?;
+        if !keys.iter().any(|key| hex::decode(key).map(|key| key == raw).unwrap_or(false)) {
+            return Err("viewing key is not in indexer.shielded-viewing-keys".into());
+        }
+        Ok(crypto::sha256(&raw))
+    }
+
+    /// Shielded TRC20 notes of a viewing key, from the optional shielded note scanner.
+    pub fn get_shielded_notes(
+        &self,
+        viewing_key: String,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<ShieldedNotePage> {
+        use state::keys::{Key, ShieldedNote as ShieldedNoteKey};
+
+        let key_id = self.shielded_key_id(&viewing_key)?;
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+
+        let prefix = ShieldedNoteKey::prefix_of(&key_id);
+        let start = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, 1 + 32 + 21 + 8)?;
+                if !raw.starts_with(&prefix) {
+                    return Err("cursor does not match viewing key".into());
+                }
+                raw
+            }
+            None => prefix.clone(),
+        };
+
+        let mut notes = Vec::with_capacity(limit);
+        let mut next_cursor = None;
+        // Take one more entry to find the start of the next page.
+        state_db.for_each_with_prefix_from(
+            &prefix,
+            &start,
+            |key: &ShieldedNoteKey, note: &proto2::state::ShieldedNote| {
+                if notes.len() == limit {
+                    next_cursor = Some(hex::encode(key.key()));
+                    return false;
+                }
+                notes.push(ShieldedNote {
+                    contract_address: key.1.to_string(),
+                    position: key.2 as _,
+                    block_number: note.block_number as _,
+                    transaction_id: hex::encode(&note.transaction_id),
+                    value: note.value as _,
+                    payment_address: note.payment_address.clone(),
+                    rcm: hex::encode(&note.rcm),
+                    memo: hex::encode(&note.memo),
+                    nullifier: hex::encode(&note.nullifier),
+                    spent_by: Some(&note.spent_by)
+                        .filter(|txn_id| !txn_id.is_empty())
+                        .map(hex::encode),
+                });
+                true
+            },
+        );
+
+        Ok(ShieldedNotePage { notes, next_cursor })
+    }
+
+    /// Sum of unspent shielded TRC20 notes of a viewing key in a contract, from the optional shielded note scanner.
+    pub fn get_shielded_balance(&self, viewing_key: String, contract: String) -> FieldResult<f64> {
+        use state::keys::ShieldedNote as ShieldedNoteKey;
+
+        let key_id = self.shielded_key_id(&viewing_key)?;
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let contract: Address = contract.parse()?;
+        let prefix = [&ShieldedNoteKey::prefix_of(&key_id)[..], contract.as_bytes()].concat();
+        let mut balance = 0_i64;
+        state_db.for_each_with_prefix_from(
+            &prefix,
+            &prefix,
+            |_: &ShieldedNoteKey, note: &proto2::state::ShieldedNote| {
+                if note.spent_by.is_empty() {
+                    balance += note.value;
+                }
+                true
+            },
+        );
+        Ok(balance as _)
+    }
+
+    /// Orders of an account on the on-chain DEX.
+    ///
+    /// Same as `GetMarketOrderByAccount` of java-tron. Always empty, there's no order book state yet.
+    pub fn get_market_orders_by_account(&self, address: String) -> FieldResult<Vec<MarketOrder>> {
+        let _: Address = address.parse()?;
+        Ok(vec![])
+    }
+
+    /// Trading pairs with orders on the on-chain DEX.
+    ///
+    /// Same as `GetMarketPairList` of java-tron. Always empty, there's no order book state yet.
+    pub fn get_market_pairs(&self) -> FieldResult<Vec<MarketOrderPair>> {
+        Ok(vec![])
+    }
+
+    /// Price levels of a trading pair on the on-chain DEX.
+    ///
+    /// Same as `GetMarketPriceByPair` of java-tron. Always empty, there's no order book state yet.
+    pub fn get_market_prices_by_pair(
+        &self,
+        sell_token_id: String,
+        buy_token_id: String,
+    ) -> FieldResult<MarketPriceList> {
+        let is_valid_token_id = |id: &str| id == "_" || id.parse::<i64>().map(|id| id > 0).unwrap_or(false);
+        if !is_valid_token_id(&sell_token_id) || !is_valid_token_id(&buy_token_id) {
+            return Err("token id must be `_` for TRX, or an asset id".into());
+        }
+        if sell_token_id == buy_token_id {
+            return Err("cannot trade a token for itself".into());
+        }
+        Ok(MarketPriceList {
+            sell_token_id,
+            buy_token_id,
+            prices: vec![],
+        })
+    }
+
+    /// Transactions sent by an account, or received by it, from the optional account transaction index.
+    ///
+    /// Same as `GetTransactionsFromThis` and `GetTransactionsToThis` of java-tron.
+    pub fn get_account_transactions(
+        &self,
+        address: String,
+        to_this: bool,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<TransactionPage> {
+        use state::keys::{AccountTransaction, Key};
+
+        if !self.app.config.indexer.account_transactions {
+            return Err("account transaction index is disabled, requires indexer.account-transactions in config".into());
+        }
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let limit = self.page_size(limit)?;
+
+        let address: Address = address.parse()?;
+        let prefix = AccountTransaction::prefix_of(&address, to_this);
+        let start = match after {
+            Some(cursor) => {
+                let raw = decode_cursor(&cursor, 21 + 1 + 8 + 4)?;
+                if !raw.starts_with(&prefix) {
+                    return Err("cursor does not match address".into());
+                }
+                raw
+            }
+            None => prefix.clone(),
+        };
+
+        let mut txn_ids = Vec::with_capacity(limit);
+        let mut next_cursor = None;
+        // Take one more entry to find the start of the next page.
+        state_db.for_each_with_prefix_from(&prefix, &start, |key: &AccountTransaction, txn_id: &H256| {
+            if txn_ids.len() == limit {
+                next_cursor = Some(hex::encode(key.key()));
+                return false;
+            }
+            txn_ids.push(*txn_id);
+            true
+        });
+
+        let transactions = txn_ids
+            .iter()
+            .map(|txn_id| self.app.chain_db.get_transaction_by_id(txn_id).map(From::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TransactionPage {
+            transactions,
+            next_cursor,
+        })
+    }
+
+    /// Logs in a block range, filtered by contract address and topics. Same as `eth_getLogs`.
+    ///
+    /// Topics are matched by position, null matches any. Only blocks whose log bloom may match are loaded.
+    pub fn get_logs(
+        &self,
+        from_block: i32,
+        to_block: Option<i32>,
+        address: Option<String>,
+        topics: Vec<Option<String>>,
+    ) -> FieldResult<Vec<Log>> {
+        use state::bloom::LogBloom;
+        use state::keys::{Key, LogBloom as LogBloomKey, LogBloomIndexedFrom, TransactionReceipt};
+
+        let _guard = self.heavy_queries.acquire(&self.app.metrics)?;
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let max_logs = self.app.config.graphql.max_page_size;
+
+        let from_block = from_block as i64;
+        let to_block = match to_block {
+            Some(num) => num as i64,
+            None => state_db.get(&state::keys::DynamicProperty::LatestBlockNumber)?.unwrap_or(0),
+        };
+        if from_block < 0 || to_block < from_block || to_block - from_block >= MAX_LOG_QUERY_BLOCK_RANGE {
+            return Err(format!("block range must be within {} blocks", MAX_LOG_QUERY_BLOCK_RANGE).into());
+        }
+        match state_db.get(&LogBloomIndexedFrom)? {
+            Some(indexed_from) if indexed_from <= from_block => {}
+            Some(indexed_from) => return Err(format!("blocks before #{} are not indexed", indexed_from).into()),
+            None => return Err("log bloom is not indexed".into()),
+        }
+
+        let address = address.map(|addr| addr.parse::<Address>()).transpose()?;
+        let topics = topics
+            .into_iter()
+            .map(|topic| topic.map(|t| hex::decode(t)).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut block_numbers = vec![];
+        let start = LogBloomKey(from_block).key();
+        state_db.for_each_with_prefix_from(&[], &start, |key: &LogBloomKey, bloom: &LogBloom| {
+            if key.0 > to_block {
+                return false;
+            }
+            let address_matched = address.as_ref().map_or(true, |addr| bloom.contains(addr.as_bytes()));
+            let topics_matched = topics.iter().flatten().all(|topic| bloom.contains(topic));
+            if address_matched && topics_matched {
+                block_numbers.push(key.0);
+            }
+            true
+        });
+
+        let mut logs = vec![];
+        for num in block_numbers {
+            let block = self.app.chain_db.get_block_by_number(num as _)?;
+            for txn in &block.transactions {
+                let receipt = match state_db.get(&TransactionReceipt(txn.hash))? {
+                    Some(receipt) => receipt,
+                    None => continue,
+                };
+                for (i, log) in receipt.vm_logs.into_iter().enumerate() {
+                    if address.as_ref().map_or(false, |addr| addr.as_bytes() != &log.address[..]) {
+                        continue;
+                    }
+                    let topics_matched = topics.iter().enumerate().all(|(pos, topic)| match topic {
+                        Some(topic) => log.topics.get(pos) == Some(topic),
+                        None => pos < log.topics.len(),
+                    });
+                    if !topics_matched {
+                        continue;
+                    }
+                    if logs.len() == max_logs {
+                        return Err(format!("more than {} logs, narrow the block range", max_logs).into());
+                    }
+                    let contract = Address::try_from(&log.address).ok();
+                    let event = contract
+                        .and_then(|addr| self.app.abi_registry.get(&addr))
+                        .and_then(|abi| {
+                            abi.decode_log(&log.topics, &log.data)
+                                .map(|(entry, params)| DecodedEntry::new(entry, params))
+                        });
+                    logs.push(Log {
+                        block_number: num as _,
+                        transaction_id: hex::encode(txn.hash.as_bytes()),
+                        log_index: i as _,
+                        address: contract
+                            .map(|addr| addr.to_string())
+                            .unwrap_or_else(|| hex::encode(&log.address)),
+                        topics: log.topics.iter().map(hex::encode).collect(),
+                        data: hex::encode(&log.data),
+                        event,
+                    });
+                }
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Fill a raw transaction with ref block, expiration and contract options.
+    ///
+    /// When `permission_id` is given, `signer` must be a key of the permission, so that multisig transactions
+    /// are built with the right permission.
+    pub fn build_transaction(
+        &self,
+        mut raw: RawTransactionPb,
+        permission_id: Option<i32>,
+        memo: Option<String>,
+        fee_limit: Option<i32>,
+        signer: Option<String>,
+    ) -> FieldResult<UnsignedTransaction> {
+        let cntr = raw.contract.as_mut().ok_or("missing contract")?;
+        if let Some(permission_id) = permission_id {
+            let signer = signer
+                .ok_or("signer is required when permission_id is given")?
+                .parse::<Address>()?;
+            let parameter = cntr.parameter.as_ref().ok_or("missing contract parameter")?;
+            let owner = ContractOwner::decode(&parameter.value[..])?;
+            let owner_address = Address::try_from(&owner.owner_address)?;
+            self.check_permission_key(owner_address, permission_id, cntr.r#type, signer)?;
+            cntr.permission_id = permission_id;
+        }
+
+        if let Some(memo) = memo {
+            raw.data = memo.into_bytes();
+        }
+        if let Some(fee_limit) = fee_limit {
+            raw.fee_limit = fee_limit as _;
+        }
+        if raw.ref_block_hash.is_empty() {
+            let ref_block = self.app.chain_db.highest_block()?;
+            raw.ref_block_bytes = (ref_block.number() as u64).to_be_bytes()[6..8].to_vec();
+            raw.ref_block_hash = ref_block.hash().as_bytes()[8..16].to_vec();
+        }
+        let now = Utc::now().timestamp_millis();
+        if raw.timestamp == 0 {
+            raw.timestamp = now;
+        }
+        if raw.expiration == 0 {
+            raw.expiration = now + 60_000;
+        }
+
+        let txn = IndexedTransaction::from_raw(TransactionPb {
+            raw_data: Some(raw),
+            ..Default::default()
+        });
+        Ok(txn.into())
+    }
+
+    /// Check that `signer` is a key of the permission of `owner_address`, against current state.
+    fn check_permission_key(
+        &self,
+        owner_address: Address,
+        permission_id: i32,
+        contract_type: i32,
+        signer: Address,
+    ) -> FieldResult<()> {
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+        let acct = state_db
+            .get(&state::keys::Account(owner_address))?
+            .ok_or("owner account not exists")?;
+
+        let is_key = |keys: &[PermissionKeyPb]| keys.iter().any(|key| key.address == signer.as_bytes());
+        let ok = match permission_id {
+            0 => match acct.owner_permission {
+                Some(ref perm) => is_key(&perm.keys),
+                // default owner
+                None => signer == owner_address,
+            },
+            1 => return Err("witness permission can only be used for block producing".into()),
+            n if n >= 2 => match acct.active_permissions.get(n as usize - 2) {
+                Some(perm) => {
+                    let enabled = ContractType::from_i32(contract_type)
+                        .map_or(false, |ty| ty.is_operation_enabled(&perm.operations));
+                    if !enabled {
+                        return Err("operation of contract type is disabled in the permission".into());
+                    }
+                    is_key(&perm.keys)
+                }
+                // default active
+                None if permission_id == 2 &&
+                    contract_type != ContractType::AccountPermissionUpdateContract as i32 =>
+                {
+                    signer == owner_address
+                }
+                None => return Err(format!("permission {} not found", permission_id).into()),
+            },
+            _ => return Err("invalid permission_id".into()),
+        };
+        if !ok {
+            return Err(format!("{} is not a key of permission {}", signer, permission_id).into());
+        }
+        Ok(())
+    }
+
     pub fn get_transaction(&self, id: String) -> FieldResult<Transaction> {
         let txn_id = H256::from_slice(&hex::decode(&id)?);
         let txn = self.app.chain_db.get_transaction_by_id(&txn_id).map(From::from)?;
         Ok(txn)
     }
+
+    /// Resource receipt of a transaction, from state-db.
+    pub fn get_resource_receipt(&self, id: String) -> FieldResult<Option<ResourceReceipt>> {
+        use state::keys::TransactionReceipt;
+
+        let state_db = self.app.state_db.as_ref().ok_or("state-db is not available")?;
+        state_db.catch_up_with_primary();
+
+        let txn_id = H256::from_slice(&hex::decode(&id)?);
+        Ok(state_db.get(&TransactionReceipt(txn_id))?.map(|receipt| {
+            let r = receipt.resource_receipt.unwrap_or_default();
+            ResourceReceipt {
+                fee: receipt.fee as _,
+                net_usage: r.bandwidth_usage as _,
+                net_fee: r.bandwidth_fee as _,
+                energy_usage_total: r.energy as _,
+                energy_usage: r.energy_usage as _,
+                energy_fee: r.energy_fee as _,
+                origin_energy_usage: r.origin_energy_usage as _,
+                contract_fee: r.contract_fee as _,
+                multisig_fee: r.multisig_fee as _,
+                memo_fee: r.memo_fee as _,
+            }
+        }))
+    }
+
+    /// Remove expired and included transactions from the pending pool.
+    fn prune_pending_pool(&self) {
+        self.app.prune_pending_pool();
+    }
+
+    /// Size and limit of the pending pool. Same as `GetPendingSize` of java-tron.
+    pub fn get_pending_pool(&self) -> PendingPool {
+        self.prune_pending_pool();
+        let pool = self.app.pending_pool.read().unwrap();
+        PendingPool {
+            size: pool.len() as _,
+            limit: pool.limit() as _,
+        }
+    }
+
+    /// Ids of pending transactions, in arrival order. Same as `GetTransactionListFromPending` of java-tron.
+    pub fn get_pending_transactions(&self, offset: Option<i32>, limit: Option<i32>) -> FieldResult<Vec<String>> {
+        let offset = offset.unwrap_or(0);
+        if offset < 0 {
+            return Err("offset must not be negative".into());
+        }
+        let limit = self.page_size(limit)?;
+
+        self.prune_pending_pool();
+        let pool = self.app.pending_pool.read().unwrap();
+        Ok(pool
+            .ids(offset as usize, limit)
+            .iter()
+            .map(|id| hex::encode(id.as_bytes()))
+            .collect())
+    }
+
+    /// A pending transaction. Same as `GetTransactionFromPending` of java-tron.
+    pub fn get_pending_transaction(&self, id: String) -> FieldResult<Transaction> {
+        let txn_id = H256::from_slice(&hex::decode(&id)?);
+        self.prune_pending_pool();
+        let pool = self.app.pending_pool.read().unwrap();
+        let txn = pool.get(&txn_id).ok_or("transaction not found in pending pool")?;
+        Ok(txn.clone().into())
+    }
+
+    /// Status of a transaction submitted to this node, until its expiration. Null if unknown.
+    pub fn get_pending_transaction_status(&self, id: String) -> FieldResult<Option<PendingTransactionStatus>> {
+        let txn_id = H256::from_slice(&hex::decode(&id)?);
+        self.prune_pending_pool();
+        let pool = self.app.pending_pool.read().unwrap();
+        Ok(pool.status(&txn_id).map(|status| PendingTransactionStatus {
+            first_seen: Utc.timestamp_millis(status.first_seen),
+            last_broadcast: status.last_broadcast.map(|ts| Utc.timestamp_millis(ts)),
+            broadcast_count: status.broadcast_count as _,
+            included_in: status.included_in.map(|num| num as _),
+            reinjected_count: status.reinjected_count as _,
+        }))
+    }
+
+    /// Put a signed transaction into the pending pool.
+    pub fn add_pending_transaction(&self, txn: IndexedTransaction) -> FieldResult<()> {
+        self.app.add_pending_transaction(txn)?;
+        Ok(())
+    }
+
+    /// Transfer from the first devnet account of genesis, see `dev.faucet` in config.
+    pub fn request_faucet(&self, to: Address, amount: i64) -> FieldResult<Transaction> {
+        use crate::manager::actuators::BuiltinContractExt;
+        use proto2::chain::transaction::Contract as ContractPb;
+        use proto2::contract::TransferContract;
+
+        let dev_conf = &self.app.config.dev;
+        if !dev_conf.faucet || self.app.genesis_config.dev_accounts.is_none() {
+            return Err("faucet is disabled".into());
+        }
+        if amount <= 0 || amount > dev_conf.faucet_max_amount {
+            return Err(format!("amount must be in (0, {}]", dev_conf.faucet_max_amount).into());
+        }
+
+        let faucet = config::genesis::dev_account(0);
+        let cntr = TransferContract {
+            owner_address: faucet.address().as_bytes().to_vec(),
+            to_address: to.as_bytes().to_vec(),
+            amount,
+        };
+        let ref_block = self.app.chain_db.highest_block()?;
+        let now = Utc::now().timestamp_millis();
+        let raw = RawTransactionPb {
+            ref_block_bytes: (ref_block.number() as u64).to_be_bytes()[6..8].to_vec(),
+            ref_block_hash: ref_block.hash().as_bytes()[8..16].to_vec(),
+            timestamp: now,
+            expiration: now + 60_000,
+            contract: Some(ContractPb {
+                r#type: cntr.type_code() as i32,
+                parameter: cntr.to_any(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let signature = faucet.private().sign(&hash::raw_data_bytes(&raw))?;
+        let txn = IndexedTransaction::from_raw(TransactionPb {
+            raw_data: Some(raw),
+            signatures: vec![signature.as_bytes().to_vec()],
+            ..Default::default()
+        });
+        self.add_pending_transaction(txn.clone())?;
+        Ok(txn.into())
+    }
+
+    /// Put a sponsored bundle into the pending pool.
+    pub fn add_pending_bundle(&self, txns: Vec<IndexedTransaction>) -> FieldResult<()> {
+        self.app.add_pending_bundle(txns)?;
+        Ok(())
+    }
 }
@@ -3,7 +3,17 @@
 use juniper::graphql_value;
 use juniper::{FieldError, FieldResult};
 
-use super::model::{Block, Context, NodeInfo, Transaction};
+use super::admin::AdminMutation;
+use super::coverage::{self, ApiCoverage};
+use super::model::{
+    AssetPage, Block, ChainParameterValue, Claimable, ConstantCallBatchEntry, ConstantCallOutput, Context, ContractAbi,
+    DecodedEntry, DelegationAccountPage, DelegationPage, DelegationSummary, FeeHistory, GasPrice, Log, MarketOrder,
+    MarketOrderPair, MarketPriceList, NftPage, NftTransferPage, NodeInfo, Peer, PendingPool, PendingTransactionStatus,
+    Proposal, ProposalPage, ProposalState, ResourceReceipt, RichAccountPage, ShieldedNotePage, StateCommitment,
+    StorageRange, Transaction, TransactionPage, UnsignedTransaction, Witness, WitnessPage,
+};
+use crate::manager::constant::ConstantCall;
+use crate::mempool::check_num_of_contracts;
 
 pub(crate) struct Query;
 
@@ -19,6 +29,11 @@ impl Query {
         ctx.get_node_info()
     }
 
+    /// java-tron wallet API methods, and their GraphQL counterparts if implemented
+    fn api_coverage() -> ApiCoverage {
+        coverage::api_coverage()
+    }
+
     /// Get a block
     #[graphql(arguments(id(description = "hash of the block"), num(description = "block height")))]
     fn block(ctx: &Context, id: Option<String>, num: Option<i32>) -> FieldResult<Block> {
@@ -30,6 +45,385 @@ impl Query {
     fn transaction(ctx: &Context, id: String) -> FieldResult<Transaction> {
         ctx.get_transaction(id)
     }
+
+    /// Resource usage and fee breakdown of a transaction, null if not found in state-db
+    #[graphql(arguments(id(description = "transaction hash")))]
+    fn resource_receipt(ctx: &Context, id: String) -> FieldResult<Option<ResourceReceipt>> {
+        ctx.get_resource_receipt(id)
+    }
+
+    /// Size and limit of the pending transaction pool
+    fn pending_pool(ctx: &Context) -> PendingPool {
+        ctx.get_pending_pool()
+    }
+
+    /// List ids of pending transactions, in arrival order
+    #[graphql(arguments(
+        offset(description = "number of transactions to skip, default 0"),
+        limit(description = "max number of transactions, default 100")
+    ))]
+    fn pending_transactions(ctx: &Context, offset: Option<i32>, limit: Option<i32>) -> FieldResult<Vec<String>> {
+        ctx.get_pending_transactions(offset, limit)
+    }
+
+    /// Get a pending transaction
+    #[graphql(arguments(id(description = "transaction hash")))]
+    fn pending_transaction(ctx: &Context, id: String) -> FieldResult<Transaction> {
+        ctx.get_pending_transaction(id)
+    }
+
+    /// Status of a transaction submitted to this node: first seen, last broadcast and inclusion. Null if unknown or
+    /// expired
+    #[graphql(arguments(id(description = "transaction hash")))]
+    fn pending_transaction_status(ctx: &Context, id: String) -> FieldResult<Option<PendingTransactionStatus>> {
+        ctx.get_pending_transaction_status(id)
+    }
+
+    /// List connected peers
+    fn nodes(ctx: &Context) -> Vec<Peer> {
+        ctx.get_peers()
+    }
+
+    /// List all witnesses, sorted by votes
+    fn witnesses(ctx: &Context) -> FieldResult<Vec<Witness>> {
+        ctx.get_witnesses()
+    }
+
+    /// List witnesses, sorted by votes, paginated
+    #[graphql(arguments(
+        limit(description = "max number of witnesses, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn witness_page(ctx: &Context, limit: Option<i32>, after: Option<String>) -> FieldResult<WitnessPage> {
+        ctx.get_witness_page(limit, after)
+    }
+
+    /// List TRC10 assets, ordered by token id
+    #[graphql(arguments(
+        limit(description = "max number of assets, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn assets(ctx: &Context, limit: Option<i32>, after: Option<String>) -> FieldResult<AssetPage> {
+        ctx.get_assets(limit, after)
+    }
+
+    /// List governance proposals, ordered by id
+    #[graphql(arguments(
+        state(description = "only proposals of the state, default all"),
+        limit(description = "max number of proposals, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn proposals(
+        ctx: &Context,
+        state: Option<ProposalState>,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<ProposalPage> {
+        ctx.get_proposals(state, limit, after)
+    }
+
+    /// Chain parameters and their live values, ordered by key
+    fn chain_parameters(ctx: &Context) -> FieldResult<Vec<ChainParameterValue>> {
+        ctx.get_chain_parameters()
+    }
+
+    /// Governance proposal by id, null if not found
+    #[graphql(arguments(id(description = "proposal id")))]
+    fn proposal(ctx: &Context, id: i32) -> FieldResult<Option<Proposal>> {
+        ctx.get_proposal(id)
+    }
+
+    /// List accounts owning at least the given balance plus frozen, richest first
+    #[graphql(arguments(
+        min_balance(description = "minimum balance plus frozen, in SUN, default 0"),
+        limit(description = "max number of accounts, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn rich_list(
+        ctx: &Context,
+        min_balance: Option<f64>,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<RichAccountPage> {
+        ctx.get_rich_list(min_balance.unwrap_or(0.0), limit, after)
+    }
+
+    /// List resource delegations from an account, ordered by receiver
+    #[graphql(arguments(
+        address(description = "address of the delegator"),
+        v2(description = "list Stake 2.0 delegations, default false"),
+        limit(description = "max number of delegations, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn delegations(
+        ctx: &Context,
+        address: String,
+        v2: Option<bool>,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<DelegationPage> {
+        ctx.get_delegations(address, v2.unwrap_or(false), limit, after)
+    }
+
+    /// List receivers of resource delegations from an account, or delegators to it, in delegation order
+    #[graphql(arguments(
+        address(description = "address of the account"),
+        to_this(description = "list delegators to the account instead of receivers, default false"),
+        v2(description = "list accounts of Stake 2.0 delegations, default false"),
+        limit(description = "max number of addresses, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn delegation_accounts(
+        ctx: &Context,
+        address: String,
+        to_this: Option<bool>,
+        v2: Option<bool>,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<DelegationAccountPage> {
+        ctx.get_delegation_accounts(address, to_this.unwrap_or(false), v2.unwrap_or(false), limit, after)
+    }
+
+    /// Aggregate resource delegations of an account, and its frozen balance available for delegation
+    #[graphql(arguments(address(description = "address of the account")))]
+    fn delegation_summary(ctx: &Context, address: String) -> FieldResult<DelegationSummary> {
+        ctx.get_delegation_summary(address)
+    }
+
+    /// Frozen balance of an account claimable now, by expiration of freezes and delegations at the latest block
+    #[graphql(arguments(address(description = "address of the account")))]
+    fn claimable(ctx: &Context, address: String) -> FieldResult<Claimable> {
+        ctx.get_claimable(address)
+    }
+
+    /// Address of an account by its on-chain name, null if not found
+    #[graphql(arguments(name(description = "account name, set by UpdateAccount")))]
+    fn account_by_name(ctx: &Context, name: String) -> FieldResult<Option<String>> {
+        ctx.get_account_by_name(name)
+    }
+
+    /// Commitment of all state changes up to the latest block of state-db, null if not computed yet
+    fn state_commitment(ctx: &Context) -> FieldResult<Option<StateCommitment>> {
+        ctx.get_state_commitment()
+    }
+
+    /// Energy price history, in java-tron's `timestamp:price,timestamp:price` format
+    fn energy_prices(ctx: &Context) -> FieldResult<String> {
+        ctx.get_price_history(state::keys::ChainParameter::EnergyFee)
+    }
+
+    /// Bandwidth price history, in java-tron's `timestamp:price,timestamp:price` format
+    fn bandwidth_prices(ctx: &Context) -> FieldResult<String> {
+        ctx.get_price_history(state::keys::ChainParameter::BandwidthFee)
+    }
+
+    /// Verified ABI attached to a contract in the node-local registry, null if not attached
+    #[graphql(arguments(address(description = "contract address")))]
+    fn contract_abi(ctx: &Context, address: String) -> FieldResult<Option<ContractAbi>> {
+        ctx.get_contract_abi(address)
+    }
+
+    /// Decode call data by the ABI attached to the contract, null if not attached or not matched
+    #[graphql(arguments(
+        address(description = "contract address"),
+        data(description = "call data in hex, with the function selector")
+    ))]
+    fn decode_call(ctx: &Context, address: String, data: String) -> FieldResult<Option<DecodedEntry>> {
+        ctx.decode_call(address, data)
+    }
+
+    /// Call a contract against the latest state without changing it, under node-local limits
+    #[graphql(arguments(
+        caller(description = "caller address"),
+        contract(description = "contract address"),
+        data(description = "call data in hex, with the function selector"),
+        call_value(description = "TRX sent along with the call, in SUN, default 0")
+    ))]
+    fn constant_call(
+        ctx: &Context,
+        caller: String,
+        contract: String,
+        data: String,
+        call_value: Option<f64>,
+    ) -> FieldResult<ConstantCallOutput> {
+        ctx.constant_call(caller, contract, data, call_value)
+    }
+
+    /// Constant calls against the same state in one round trip, i.e. TRC20 balances of a portfolio
+    fn constant_call_batch(ctx: &Context, calls: Vec<ConstantCallInput>) -> FieldResult<Vec<ConstantCallBatchEntry>> {
+        let calls = calls
+            .into_iter()
+            .map(|call| -> FieldResult<_> {
+                Ok(ConstantCall {
+                    caller: call.caller.parse()?,
+                    contract: call.contract.parse()?,
+                    data: hex::decode(&call.data)?,
+                    call_value: call.call_value.unwrap_or_default() as _,
+                })
+            })
+            .collect::<FieldResult<Vec<_>>>()?;
+        ctx.constant_call_batch(calls)
+    }
+
+    /// Energy price as gas price, for EVM tooling. Same as `eth_gasPrice`
+    fn gas_price(ctx: &Context) -> FieldResult<GasPrice> {
+        ctx.get_gas_price()
+    }
+
+    /// Energy prices and usage of recent blocks, for EVM tooling. Shaped as `eth_feeHistory`
+    #[graphql(arguments(
+        block_count(description = "number of blocks, at most 1024"),
+        newest_block(description = "newest block number, default the latest block")
+    ))]
+    fn fee_history(ctx: &Context, block_count: i32, newest_block: Option<i32>) -> FieldResult<FeeHistory> {
+        ctx.get_fee_history(block_count, newest_block)
+    }
+
+    /// Iterate storage of a contract by key, for debugging
+    #[graphql(arguments(
+        address(description = "contract address"),
+        start(description = "hex encoded start key, inclusive"),
+        limit(description = "max number of entries, default 100")
+    ))]
+    fn storage_range(
+        ctx: &Context,
+        address: String,
+        start: Option<String>,
+        limit: Option<i32>,
+    ) -> FieldResult<StorageRange> {
+        ctx.get_storage_range(address, start, limit)
+    }
+
+    /// List TRC721/TRC1155 tokens of an owner, requires indexer.nft in config
+    #[graphql(arguments(
+        owner(description = "owner address"),
+        limit(description = "max number of tokens, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn nfts_by_owner(ctx: &Context, owner: String, limit: Option<i32>, after: Option<String>) -> FieldResult<NftPage> {
+        ctx.get_nfts_by_owner(owner, limit, after)
+    }
+
+    /// Owner of a TRC721 token, requires indexer.nft in config
+    #[graphql(arguments(
+        contract(description = "contract address"),
+        token_id(description = "token id in decimal")
+    ))]
+    fn nft_owner(ctx: &Context, contract: String, token_id: String) -> FieldResult<Option<String>> {
+        ctx.get_nft_owner(contract, token_id)
+    }
+
+    /// List transfers of a TRC721/TRC1155 contract, requires indexer.nft in config
+    #[graphql(arguments(
+        contract(description = "contract address"),
+        limit(description = "max number of transfers, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn nft_transfers(
+        ctx: &Context,
+        contract: String,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<NftTransferPage> {
+        ctx.get_nft_transfers(contract, limit, after)
+    }
+
+    /// List shielded TRC20 notes of a viewing key, requires indexer.shielded-viewing-keys in config
+    #[graphql(arguments(
+        viewing_key(description = "full viewing key in hex, ak || nk || ovk"),
+        limit(description = "max number of notes, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn shielded_notes(
+        ctx: &Context,
+        viewing_key: String,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<ShieldedNotePage> {
+        ctx.get_shielded_notes(viewing_key, limit, after)
+    }
+
+    /// Sum of unspent shielded TRC20 notes of a viewing key, in units of the scaling factor of the contract, requires
+    /// indexer.shielded-viewing-keys in config
+    #[graphql(arguments(
+        viewing_key(description = "full viewing key in hex, ak || nk || ovk"),
+        contract(description = "shielded TRC20 contract address")
+    ))]
+    fn shielded_balance(ctx: &Context, viewing_key: String, contract: String) -> FieldResult<f64> {
+        ctx.get_shielded_balance(viewing_key, contract)
+    }
+
+    /// List transactions sent by an account, latest first, requires indexer.account-transactions in config
+    #[graphql(arguments(
+        address(description = "account address"),
+        limit(description = "max number of transactions, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn transactions_from_this(
+        ctx: &Context,
+        address: String,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<TransactionPage> {
+        ctx.get_account_transactions(address, false, limit, after)
+    }
+
+    /// List transactions to an account, as receiver, called contract or token counterparty, latest first, requires
+    /// indexer.account-transactions in config
+    #[graphql(arguments(
+        address(description = "account address"),
+        limit(description = "max number of transactions, default 100"),
+        after(description = "cursor of the page, from nextCursor of the previous page")
+    ))]
+    fn transactions_to_this(
+        ctx: &Context,
+        address: String,
+        limit: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<TransactionPage> {
+        ctx.get_account_transactions(address, true, limit, after)
+    }
+
+    /// Query logs of contracts in a block range
+    #[graphql(arguments(
+        from_block(description = "start block number, inclusive"),
+        to_block(description = "end block number, inclusive, default latest"),
+        address(description = "contract address"),
+        topics(description = "hex encoded topics, matched by position, null matches any")
+    ))]
+    fn logs(
+        ctx: &Context,
+        from_block: i32,
+        to_block: Option<i32>,
+        address: Option<String>,
+        topics: Option<Vec<Option<String>>>,
+    ) -> FieldResult<Vec<Log>> {
+        ctx.get_logs(from_block, to_block, address, topics.unwrap_or_default())
+    }
+
+    /// List orders of an account on the on-chain DEX
+    #[graphql(arguments(address(description = "address of the account")))]
+    fn market_orders_by_account(ctx: &Context, address: String) -> FieldResult<Vec<MarketOrder>> {
+        ctx.get_market_orders_by_account(address)
+    }
+
+    /// List trading pairs with orders on the on-chain DEX
+    fn market_pairs(ctx: &Context) -> FieldResult<Vec<MarketOrderPair>> {
+        ctx.get_market_pairs()
+    }
+
+    /// Price levels of a trading pair on the on-chain DEX, best price first
+    #[graphql(arguments(
+        sell_token_id(description = "token to sell, `_` for TRX or an asset id"),
+        buy_token_id(description = "token to buy, `_` for TRX or an asset id")
+    ))]
+    fn market_prices_by_pair(
+        ctx: &Context,
+        sell_token_id: String,
+        buy_token_id: String,
+    ) -> FieldResult<MarketPriceList> {
+        ctx.get_market_prices_by_pair(sell_token_id, buy_token_id)
+    }
 }
 
 #[derive(juniper::GraphQLInputObject)]
@@ -39,12 +433,67 @@ struct ContractOptions {
     fee_limit: Option<i32>,
 }
 
+#[derive(juniper::GraphQLInputObject)]
+/// A constant call of a batch. Call data in hex, call value in SUN.
+struct ConstantCallInput {
+    caller: String,
+    contract: String,
+    data: String,
+    call_value: Option<f64>,
+}
+
+#[derive(juniper::GraphQLInputObject)]
+/// A transaction with its signatures, all in hex.
+struct SignedTransaction {
+    raw: String,
+    signatures: Vec<String>,
+}
+
 pub(crate) struct Mutation;
 
 #[juniper::graphql_object(Context = Context)]
 impl Mutation {
-    /// Broadcast a transaction with its signatures.
-    fn broadcast(_ctx: &Context, raw: String, signatures: Vec<String>) -> FieldResult<Transaction> {
+    /// Admin operations, requires `Authorization: Bearer <admin-token>`.
+    fn admin(ctx: &Context) -> FieldResult<AdminMutation> {
+        if !ctx.is_admin {
+            return Err("admin access denied".into());
+        }
+        Ok(AdminMutation)
+    }
+
+    /// Build an unsigned transaction from a raw transaction with its contract.
+    ///
+    /// Ref block and expiration are filled if missing. When `permission_id` is given in options,
+    /// `signer` must be a key of the permission.
+    #[graphql(arguments(
+        raw(description = "hex encoded raw transaction"),
+        signer(description = "address of the signing key")
+    ))]
+    fn build_transaction(
+        ctx: &Context,
+        raw: String,
+        options: Option<ContractOptions>,
+        signer: Option<String>,
+    ) -> FieldResult<UnsignedTransaction> {
+        use prost::Message;
+        use proto2::chain::transaction::Raw as RawTransaction;
+
+        let raw = RawTransaction::decode(&hex::decode(&raw)?[..])?;
+        let ContractOptions {
+            memo,
+            permission_id,
+            fee_limit,
+        } = options.unwrap_or(ContractOptions {
+            memo: None,
+            permission_id: None,
+            fee_limit: None,
+        });
+        ctx.build_transaction(raw, permission_id, memo, fee_limit, signer)
+    }
+
+    /// Broadcast a transaction with its signatures. The transaction is kept in the pending pool until included
+    /// in a block or expired.
+    fn broadcast(ctx: &Context, raw: String, signatures: Vec<String>) -> FieldResult<Transaction> {
         use chain::IndexedTransaction;
         use prost::Message;
         use proto2::chain::{transaction::Raw as RawTransaction, Transaction};
@@ -59,6 +508,14 @@ impl Mutation {
         })?;
 
         let buf = &raw[..];
+        check_num_of_contracts(buf).map_err(|e| {
+            FieldError::new(
+                "invalid transaction",
+                graphql_value!({
+                    "internal_error": (e)
+                }),
+            )
+        })?;
 
         let raw_txn = RawTransaction::decode(buf).map_err(|e| {
             FieldError::new(
@@ -86,9 +543,46 @@ impl Mutation {
             ..Default::default()
         };
         let txn = IndexedTransaction::from_raw(txn);
-        // TODO: broadcast
+        ctx.add_pending_transaction(txn.clone())?;
         Ok(txn.into())
     }
+
+    /// Request TRX from the devnet faucet. Only available when `dev.faucet` is enabled and genesis has
+    /// `devAccounts`.
+    #[graphql(arguments(address(description = "address of the receiver"), amount(description = "amount in TRX")))]
+    fn request_faucet(ctx: &Context, address: String, amount: f64) -> FieldResult<Transaction> {
+        ctx.request_faucet(address.parse()?, (amount * constants::SUN_PER_TRX as f64).round() as i64)
+    }
+
+    /// Broadcast a sponsored bundle: a DelegateResource from the fee payer to the sender, the sponsored transaction,
+    /// and an optional UnDelegateResource reclaiming the delegation. A producing node packs the bundle into the same
+    /// block in order, or not at all.
+    fn broadcast_bundle(ctx: &Context, transactions: Vec<SignedTransaction>) -> FieldResult<Vec<Transaction>> {
+        use chain::IndexedTransaction;
+        use prost::Message;
+        use proto2::chain::{transaction::Raw as RawTransaction, Transaction};
+
+        let txns = transactions
+            .into_iter()
+            .map(|txn| -> FieldResult<_> {
+                let raw = hex::decode(&txn.raw)?;
+                check_num_of_contracts(&raw)?;
+                let raw = RawTransaction::decode(&raw[..])?;
+                let signatures = txn
+                    .signatures
+                    .iter()
+                    .map(hex::decode)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(IndexedTransaction::from_raw(Transaction {
+                    raw_data: Some(raw),
+                    signatures,
+                    ..Default::default()
+                }))
+            })
+            .collect::<FieldResult<Vec<_>>>()?;
+        ctx.add_pending_bundle(txns.clone())?;
+        Ok(txns.into_iter().map(From::from).collect())
+    }
 }
 
 // A root schema consists of a query and a mutation.
@@ -1,17 +1,22 @@
 use futures::future::FutureExt;
 use hyper::{
+    header,
+    server::conn::AddrStream,
     service::{make_service_fn, service_fn},
-    Body, Method, Response, Server, StatusCode,
+    Body, Method, Request, Response, Server, StatusCode,
 };
 use juniper::{EmptySubscription, RootNode};
 use log::{info, warn};
-use slog::slog_info;
+use slog::slog_debug;
+use slog_scope_futures::FutureExt as SlogFutureExt;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 
-use super::model::Context;
+use super::jsonrpc;
+use super::model::{Context, HeavyQueryLimiter};
 use super::schema::{Mutation, Query, Schema};
 use crate::context::AppContext;
+use crate::request_log::RequestLog;
 
 pub async fn graphql_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast::Receiver<()>) {
     let config = &ctx.config.graphql;
@@ -24,38 +29,102 @@ pub async fn graphql_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast
     let addr = config.endpoint.parse().expect("malformed endpoint address");
 
     let root_node: Arc<Schema> = Arc::new(RootNode::new(Query, Mutation, EmptySubscription::new()));
-    let ctx = Arc::new(Context { app: ctx });
+    let heavy_queries = Arc::new(HeavyQueryLimiter::new(config.max_concurrent_heavy_queries));
 
-    let graphql_service = make_service_fn(move |_| {
+    // Resolvers read state-db synchronously. Run them in a dedicated pool, so that heavy queries can't occupy the
+    // runtime threads of block sync and production.
+    let worker_pool = tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .core_threads(config.worker_threads.max(1))
+        .thread_name("graphql-pool")
+        .enable_all()
+        .build()
+        .expect("graphql worker pool");
+    let worker_handle = worker_pool.handle().clone();
+
+    let graphql_service = make_service_fn(move |conn: &AddrStream| {
         let root_node = root_node.clone();
         let ctx = ctx.clone();
-        let logger = slog_scope::logger();
+        let heavy_queries = heavy_queries.clone();
+        let worker_handle = worker_handle.clone();
+        let remote_addr = conn.remote_addr();
 
         async move {
             Ok::<_, hyper::Error>(service_fn(move |req| {
                 let root_node = root_node.clone();
                 let ctx = ctx.clone();
+                let heavy_queries = heavy_queries.clone();
+                let worker_handle = worker_handle.clone();
 
-                slog_info!(
-                    logger,
-                    "{:?} {} {:?} {:?}",
+                let request_log = RequestLog::new("graphql", &req, remote_addr);
+                slog_debug!(
+                    request_log.logger(),
+                    "{} {} user-agent={:?}",
                     req.method(),
                     req.uri(),
-                    req.headers().get("user-agent").unwrap(),
-                    req.headers().get("x-forwarded-for"),
+                    req.headers().get("user-agent"),
                 );
+                let is_admin = is_admin_request(&ctx.config.graphql.admin_token, &req);
+                let ctx = Arc::new(Context {
+                    app: ctx,
+                    is_admin,
+                    heavy_queries,
+                });
                 async move {
-                    match (req.method(), req.uri().path()) {
+                    let ret = match (req.method(), req.uri().path()) {
                         (&Method::GET, "/") => juniper_hyper::graphiql("/graphql", None).await,
                         (&Method::GET, "/playground") => juniper_hyper::playground("/graphql", None).await,
                         (&Method::GET, "/graphql") | (&Method::POST, "/graphql") => {
-                            juniper_hyper::graphql(root_node, ctx, req).await
+                            // Resolvers log with the request id.
+                            let query =
+                                juniper_hyper::graphql(root_node, ctx, req).with_logger(request_log.logger().clone());
+                            match worker_handle.spawn(query).await {
+                                Ok(resp) => resp,
+                                Err(e) => {
+                                    warn!("graphql worker error: {}", e);
+                                    let mut response = Response::new(Body::empty());
+                                    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                                    Ok(response)
+                                }
+                            }
                         }
+                        (&Method::POST, "/jsonrpc") => match hyper::body::to_bytes(req.into_body()).await {
+                            Ok(body) => {
+                                let call = async move { jsonrpc::handle(&ctx, &body) };
+                                match worker_handle.spawn(call.with_logger(request_log.logger().clone())).await {
+                                    Ok(resp) => {
+                                        let mut response = Response::new(Body::from(resp.to_string()));
+                                        response.headers_mut().insert(
+                                            header::CONTENT_TYPE,
+                                            header::HeaderValue::from_static("application/json"),
+                                        );
+                                        Ok(response)
+                                    }
+                                    Err(e) => {
+                                        warn!("jsonrpc worker error: {}", e);
+                                        let mut response = Response::new(Body::empty());
+                                        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                                        Ok(response)
+                                    }
+                                }
+                            }
+                            Err(e) => Err(e),
+                        },
                         _ => {
                             let mut response = Response::new(Body::empty());
                             *response.status_mut() = StatusCode::NOT_FOUND;
                             Ok(response)
                         }
+                    };
+                    match ret {
+                        Ok(mut response) => {
+                            request_log.finish(&mut response);
+                            Ok(response)
+                        }
+                        Err(e) => {
+                            request_log.fail(&e);
+                            Err(e)
+                        }
                     }
                 }
             }))
@@ -66,4 +135,27 @@ pub async fn graphql_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast
     info!("listening on http://{}", addr);
 
     let _ = server.with_graceful_shutdown(shutdown_signal.recv().map(|_| ())).await;
+    // Dropping a runtime blocks until its threads exit, which must not happen in an async context.
+    std::thread::spawn(move || drop(worker_pool));
+}
+
+/// Check `Authorization: Bearer <token>` against the configured admin token.
+fn is_admin_request(admin_token: &str, req: &Request<Body>) -> bool {
+    if admin_token.is_empty() {
+        return false;
+    }
+    req.headers()
+        .get("authorization")
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.strip_prefix("Bearer "))
+        .map(|token| constant_time_eq(token.as_bytes(), admin_token.as_bytes()))
+        .unwrap_or(false)
+}
+
+/// Compare in time independent of the position of the first mismatch, so that the token can not be guessed by timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
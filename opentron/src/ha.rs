@@ -0,0 +1,199 @@
+//! Hot-standby failover of block producers, see `[producer.ha]` in config.
+//!
+//! Redundant nodes of the same witness share a lease file. Only the holder signs blocks, and renews the lease every
+//! heartbeat. When the holder stops renewing, a standby takes the lease once it expires. No consensus is involved,
+//! a lease is taken in two heartbeats: a claim is written, then confirmed if no other node overwrote it.
+//!
+//! The holder stops signing `max-drift` of `[time]` before its lease expires, so that clock skew between nodes does
+//! not make them sign in the same slot. The double-sign record of each node is the last line of defense.
+//!
+//! NOTE: Blocks are not produced yet, the lease is only held and exported as the `producer_lease` metric. The block
+//! production loop must check `may_produce` before each slot, see `manager::producer`.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::future::FutureExt;
+use futures::select;
+use log::{info, warn};
+use tokio::sync::broadcast;
+use tokio::time::delay_for;
+
+use crate::context::AppContext;
+use crate::util::new_error;
+
+/// Content of the lease file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    /// Node id of the holder, in hex.
+    pub holder: String,
+    /// In ms.
+    pub expires_at: i64,
+}
+
+impl Lease {
+    fn parse(s: &str) -> Option<Lease> {
+        let mut parts = s.split_whitespace();
+        let holder = parts.next()?.to_owned();
+        let expires_at = parts.next()?.parse().ok()?;
+        Some(Lease { holder, expires_at })
+    }
+}
+
+pub struct LeaseFile {
+    path: PathBuf,
+    holder: String,
+    timeout: i64,
+}
+
+impl LeaseFile {
+    pub fn new<P: AsRef<Path>>(path: P, holder: &str, timeout: i64) -> Self {
+        LeaseFile {
+            path: path.as_ref().to_owned(),
+            holder: holder.to_owned(),
+            timeout,
+        }
+    }
+
+    /// Current lease, None if never taken. A malformed file is treated as an expired lease.
+    pub fn read(&self) -> io::Result<Option<Lease>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(Some(Lease::parse(&content).unwrap_or(Lease {
+                holder: String::new(),
+                expires_at: 0,
+            }))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, expires_at: i64) -> io::Result<()> {
+        // Replaced by rename, so that other nodes never read a partial lease.
+        let tmp_path = self.path.with_extension(format!("{}.tmp", self.holder));
+        fs::write(&tmp_path, format!("{} {}\n", self.holder, expires_at))?;
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    /// One heartbeat at `now`. Returns expiration of the lease held by this node, 0 if not held.
+    pub fn heartbeat(&self, now: i64) -> io::Result<i64> {
+        match self.read()? {
+            // Renew, or confirm the claim of the last heartbeat.
+            Some(ref lease) if lease.holder == self.holder => {
+                let expires_at = now + self.timeout;
+                self.write(expires_at)?;
+                Ok(expires_at)
+            }
+            Some(ref lease) if lease.expires_at > now => Ok(0),
+            // Expired or never taken, claim it. Held only if the claim survives until the next heartbeat.
+            _ => {
+                self.write(now + self.timeout)?;
+                Ok(0)
+            }
+        }
+    }
+
+    /// Expire the lease if held, for a standby to take over immediately.
+    pub fn release(&self) -> io::Result<()> {
+        match self.read()? {
+            Some(ref lease) if lease.holder == self.holder => self.write(0),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether this node may sign a block at `now`. Always true without HA.
+pub fn may_produce(ctx: &AppContext, now: i64) -> bool {
+    if !ctx.config.producer.ha.enable {
+        return true;
+    }
+    now < ctx.producer_lease.load(Ordering::SeqCst) - ctx.config.time.max_drift
+}
+
+/// Renew or take the producer lease every heartbeat.
+pub async fn ha_service(ctx: Arc<AppContext>, mut signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
+    let config = &ctx.config.producer.ha;
+    if !config.enable {
+        warn!("producer HA disabled");
+        return Ok(());
+    }
+    if ctx.config.witness.private_key.is_empty() {
+        return Err(new_error("producer HA requires a witness private key"));
+    }
+    if config.lease_file.is_empty() {
+        return Err(new_error("producer HA requires a lease file"));
+    }
+    if config.lease_timeout <= ctx.config.time.max_drift + config.heartbeat_interval as i64 {
+        return Err(new_error("lease-timeout must be longer than max-drift plus heartbeat-interval"));
+    }
+
+    let lease = LeaseFile::new(&config.lease_file, &hex::encode(&ctx.node_id), config.lease_timeout);
+    info!("producer HA lease file {}", config.lease_file);
+    warn!("block production is not implemented, the producer lease is held but no blocks are signed");
+
+    loop {
+        let now = Utc::now().timestamp_millis();
+        // On I/O errors, signing stops at once. Other nodes take over when the lease expires.
+        let expires_at = tokio::task::block_in_place(|| lease.heartbeat(now)).unwrap_or_else(|e| {
+            warn!("producer lease heartbeat failed: {}", e);
+            0
+        });
+        let prev_expires_at = ctx.producer_lease.swap(expires_at, Ordering::SeqCst);
+        if prev_expires_at == 0 && expires_at > 0 {
+            info!("producer lease taken");
+        } else if prev_expires_at > 0 && expires_at == 0 {
+            warn!("producer lease lost, standing by");
+        }
+
+        select! {
+            _ = signal.recv().fuse() => {
+                warn!("producer HA service closed");
+                break;
+            }
+            _ = delay_for(Duration::from_millis(config.heartbeat_interval)).fuse() => {}
+        }
+    }
+
+    ctx.producer_lease.store(0, Ordering::SeqCst);
+    tokio::task::block_in_place(|| lease.release())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_file() {
+        let path = std::env::temp_dir().join(format!("opentron-lease-{}", std::process::id()));
+        let primary = LeaseFile::new(&path, "aa", 6_000);
+        let standby = LeaseFile::new(&path, "bb", 6_000);
+
+        let now = 1_600_000_000_000;
+        assert_eq!(primary.heartbeat(now).unwrap(), 0);
+        assert_eq!(standby.heartbeat(now + 500).unwrap(), 0);
+        assert_eq!(primary.heartbeat(now + 1_000).unwrap(), now + 7_000);
+        assert_eq!(standby.heartbeat(now + 1_500).unwrap(), 0);
+
+        // The primary stops renewing, the standby claims the expired lease, then holds it.
+        assert_eq!(standby.heartbeat(now + 7_500).unwrap(), 0);
+        assert_eq!(standby.heartbeat(now + 8_500).unwrap(), now + 14_500);
+        assert_eq!(primary.heartbeat(now + 9_000).unwrap(), 0);
+
+        // Released on shutdown, taken without waiting for expiration. A pending claim is respected.
+        standby.release().unwrap();
+        assert_eq!(primary.heartbeat(now + 10_000).unwrap(), 0);
+        assert_eq!(standby.heartbeat(now + 10_200).unwrap(), 0);
+        assert_eq!(primary.heartbeat(now + 11_000).unwrap(), now + 17_000);
+
+        fs::write(&path, "garbage").unwrap();
+        assert_eq!(primary.read().unwrap().unwrap().expires_at, 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,9 +1,22 @@
 #![recursion_limit = "2048"]
 
+pub mod abi;
 pub mod channel;
+pub mod clock;
 pub mod commands;
 pub mod context;
 pub mod discovery;
+#[cfg(feature = "graphql")]
 pub mod graphql;
+#[cfg(feature = "producer")]
+pub mod ha;
+pub mod light;
+pub mod logging;
+pub mod mempool;
+pub mod metrics;
+pub mod nat;
+pub mod request_log;
+pub mod rosetta;
+pub mod supervisor;
 pub mod util;
 pub mod manager;
@@ -0,0 +1,165 @@
+//! Light client mode.
+//!
+//! Only block headers are synced and saved to chain-db. Headers are validated by hash, witness signature, parent
+//! linkage and their producer. Without state, witness schedules can not be computed from votes. Known witnesses
+//! start from state-db, or the genesis witnesses, and are refreshed from a full node's HTTP API when a header is
+//! produced by an unknown witness.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::RwLock;
+
+use chain::IndexedBlock;
+use config::genesis::GenesisConfig;
+use config::LightClientConfig;
+use keys::Address;
+use log::info;
+use primitive_types::H256;
+use serde_json::{json, Value};
+use state::db::ReadOnlySolidStateDB;
+use state::keys as state_keys;
+
+use crate::context::AppContext;
+use crate::util::new_error;
+
+/// Validate a block header received in light mode.
+///
+/// Producers of gossiped headers must be active witnesses. While syncing, old headers might be produced by witnesses
+/// no longer active, so that any known witness is accepted.
+pub async fn validate_block_header(
+    ctx: &AppContext,
+    block: &IndexedBlock,
+    syncing: bool,
+) -> Result<(), Box<dyn Error>> {
+    if !block.header.verify() {
+        return Err(new_error("block header hash mismatch"));
+    }
+
+    let recovered = block.recover_witness()?;
+    if recovered.as_bytes() != block.witness() {
+        return Err(new_error("verifying block witness signature failed"));
+    }
+
+    let parent_hash = H256::from_slice(block.parent_hash());
+    if !ctx.chain_db.has_block_id(&parent_hash) {
+        return Err(new_error("parent block not found"));
+    }
+
+    let witnesses = ctx.light_witnesses.as_ref().ok_or("light mode is not enabled")?;
+    witnesses.check(&recovered, !syncing).await
+}
+
+/// Strip transactions from a block, keeping only the header.
+pub fn header_only(block: &IndexedBlock) -> IndexedBlock {
+    IndexedBlock::new(block.header.clone(), vec![])
+}
+
+/// Witnesses known to a light node.
+pub struct KnownWitnesses {
+    /// Witnesses producing blocks of the current schedule.
+    active: RwLock<HashSet<Address>>,
+    /// All witnesses ever seen, active or not.
+    all: RwLock<HashSet<Address>>,
+    remote: Option<RemoteState>,
+}
+
+impl KnownWitnesses {
+    pub fn new(
+        config: &LightClientConfig,
+        genesis_config: &GenesisConfig,
+        state_db: Option<&ReadOnlySolidStateDB>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut active = HashSet::new();
+        let schedule = match state_db {
+            Some(state_db) => state_db.get(&state_keys::WitnessSchedule)?.unwrap_or_default(),
+            None => vec![],
+        };
+        for (addr, _, _) in schedule.into_iter().take(genesis_config.active_witnesses) {
+            active.insert(addr);
+        }
+        if active.is_empty() {
+            for wit in &genesis_config.witnesses {
+                active.insert(wit.address.parse::<Address>()?);
+            }
+        }
+
+        let remote = if config.state_rpc_endpoint.is_empty() {
+            None
+        } else {
+            Some(RemoteState::new(&config.state_rpc_endpoint))
+        };
+        Ok(KnownWitnesses {
+            all: RwLock::new(active.clone()),
+            active: RwLock::new(active),
+            remote,
+        })
+    }
+
+    fn contains(&self, witness: &Address, active_only: bool) -> bool {
+        if active_only {
+            self.active.read().unwrap().contains(witness)
+        } else {
+            self.all.read().unwrap().contains(witness)
+        }
+    }
+
+    /// Check a block producer is a known witness, refreshing witnesses from the state RPC endpoint if not.
+    pub async fn check(&self, witness: &Address, active_only: bool) -> Result<(), Box<dyn Error>> {
+        if self.contains(witness, active_only) {
+            return Ok(());
+        }
+        if let Some(ref remote) = self.remote {
+            let witnesses = remote.list_witnesses().await?;
+            info!("refreshed {} witnesses from state RPC endpoint", witnesses.len());
+            let mut active = HashSet::new();
+            let mut all = self.all.write().unwrap();
+            for (addr, is_active) in witnesses {
+                if is_active {
+                    active.insert(addr);
+                }
+                all.insert(addr);
+            }
+            *self.active.write().unwrap() = active;
+        }
+        if self.contains(witness, active_only) {
+            Ok(())
+        } else if active_only {
+            Err(new_error("block producer is not an active witness"))
+        } else {
+            Err(new_error("block producer is not a witness"))
+        }
+    }
+}
+
+/// State fetching from a java-tron compatible HTTP API.
+pub struct RemoteState {
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl RemoteState {
+    pub fn new(endpoint: &str) -> Self {
+        RemoteState {
+            endpoint: endpoint.trim_end_matches('/').to_owned(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn post(&self, path: &str, payload: Value) -> Result<Value, Box<dyn Error>> {
+        let url = format!("{}{}", self.endpoint, path);
+        let resp = self.client.post(&url).json(&payload).send().await?;
+        Ok(resp.json::<Value>().await?)
+    }
+
+    /// All witnesses, and whether each is in the current schedule.
+    pub async fn list_witnesses(&self) -> Result<Vec<(Address, bool)>, Box<dyn Error>> {
+        let resp = self.post("/wallet/listwitnesses", json!({ "visible": true })).await?;
+        let mut witnesses = vec![];
+        for wit in resp["witnesses"].as_array().ok_or("invalid listwitnesses response")? {
+            let addr = wit["address"].as_str().ok_or("invalid witness address")?.parse::<Address>()?;
+            // Default values are omitted by java-tron.
+            witnesses.push((addr, wit["isJobs"].as_bool().unwrap_or(false)));
+        }
+        Ok(witnesses)
+    }
+}
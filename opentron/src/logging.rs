@@ -0,0 +1,104 @@
+//! Logger setup, with configurable format, output and per-module levels.
+
+use std::fs::OpenOptions;
+use std::str::FromStr;
+
+use config::Config;
+use slog::{o, Drain, Level, Never, OwnedKVList, Record};
+
+/// Filters records by the longest matching module path prefix, falls back to the default level.
+pub struct ModuleLevelFilter<D> {
+    drain: D,
+    default: Level,
+    // sorted by prefix length, longest first
+    filters: Vec<(String, Level)>,
+}
+
+impl<D: Drain> ModuleLevelFilter<D> {
+    pub fn new(drain: D, default: Level, filters: Vec<(String, Level)>) -> Self {
+        let mut filters = filters;
+        filters.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+        ModuleLevelFilter {
+            drain,
+            default,
+            filters,
+        }
+    }
+
+    fn level_of(&self, module: &str) -> Level {
+        self.filters
+            .iter()
+            .find(|(prefix, _)| module.starts_with(prefix.as_str()))
+            .map(|&(_, level)| level)
+            .unwrap_or(self.default)
+    }
+}
+
+impl<D: Drain> Drain for ModuleLevelFilter<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        if record.level().is_at_least(self.level_of(record.module())) {
+            self.drain.log(record, values).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn parse_level(level: &str) -> Level {
+    Level::from_str(level).unwrap_or_else(|_| {
+        eprintln!("invalid log level {:?}, fallback to info", level);
+        Level::Info
+    })
+}
+
+/// Build the root logger from config. `debug` overrides the default log level.
+pub fn root_logger(config: Option<&Config>, debug: bool) -> slog::Logger {
+    let (log_file, log_format) = config
+        .map(|c| (c.log_file.as_str(), c.log_format.as_str()))
+        .unwrap_or(("", "term"));
+
+    let drain: Box<dyn Drain<Ok = (), Err = Never> + Send> = match (log_format, log_file) {
+        ("json", "") => Box::new(slog_json::Json::default(std::io::stdout()).fuse()),
+        ("json", path) => {
+            let file = open_log_file(path);
+            Box::new(slog_json::Json::default(file).fuse())
+        }
+        (_, "") => {
+            let decorator = slog_term::TermDecorator::new().build();
+            Box::new(slog_term::FullFormat::new(decorator).build().fuse())
+        }
+        (_, path) => {
+            let decorator = slog_term::PlainDecorator::new(open_log_file(path));
+            Box::new(slog_term::FullFormat::new(decorator).build().fuse())
+        }
+    };
+    let drain = slog_async::Async::new(drain).build().fuse();
+
+    let default_level = if debug {
+        Level::Debug
+    } else {
+        config.map(|c| parse_level(&c.log_level)).unwrap_or(Level::Info)
+    };
+    let filters = config
+        .map(|c| {
+            c.log_filters
+                .iter()
+                .map(|(module, level)| (module.clone(), parse_level(level)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let drain = ModuleLevelFilter::new(drain, default_level, filters).fuse();
+
+    slog::Logger::root(drain, o!())
+}
+
+fn open_log_file(path: &str) -> std::fs::File {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .expect("unable to open log file")
+}
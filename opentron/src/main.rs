@@ -7,41 +7,46 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use futures::channel::oneshot;
-use futures::join;
-use log::info;
-use slog::{o, Drain};
+use log::{info, warn};
+use slog::o;
 use slog_scope_futures::FutureExt as SlogFutureExt;
 use tokio::sync::broadcast;
 
 use opentron::channel::server::channel_server;
+use opentron::clock::clock_service;
 use opentron::context::AppContext;
 use opentron::discovery::server::discovery_server;
+#[cfg(feature = "graphql")]
 use opentron::graphql::server::graphql_server;
-use opentron::util::get_my_ip;
+#[cfg(feature = "producer")]
+use opentron::ha::ha_service;
+use opentron::manager::constant::ConstantCallService;
+use opentron::mempool::mempool_service;
+use opentron::metrics::service::metrics_service;
+use opentron::nat::{detect_external_ip, nat_service};
+use opentron::rosetta::server::rosetta_server;
+use opentron::supervisor::Supervisor;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // ! init app command line arguments
     let yaml = clap::load_yaml!("cli.yml");
     let matches = clap::App::from_yaml(yaml).get_matches();
 
+    let config_file = matches.value_of("config").expect("has default in cli.yml; qed");
+
     // ! init loggers
-    let decorator = slog_term::TermDecorator::new().build();
-    // let drain = slog_term::CompactFormat::new(decorator).build().fuse();
-    let drain = slog_term::FullFormat::new(decorator).build().fuse();
-    let drain = slog_async::Async::new(drain).build().fuse();
-    let drain = if matches.is_present("debug") {
-        slog::LevelFilter(drain, slog::Level::Debug).fuse()
-    } else {
-        slog::LevelFilter(drain, slog::Level::Info).fuse()
+    // Client-side tools also run without a usable node config, i.e. before aliases of config are added to the
+    // address book. They log with defaults.
+    let config = match config::Config::load_from_file(config_file) {
+        Ok(config) => Some(config),
+        Err(_) if matches!(matches.subcommand_name(), Some("address-book") | Some("tx") | Some("genesis")) => None,
+        Err(e) => return Err(format!("can not load config {}: {}", config_file, e).into()),
     };
-
-    let logger = slog::Logger::root(drain, o!());
+    let logger = opentron::logging::root_logger(config.as_ref(), matches.is_present("debug"));
 
     let _scope_guard = slog_scope::set_global_logger(logger);
     let _log_guard = slog_stdlog::init().unwrap();
 
-    let config_file = matches.value_of("config").expect("has default in cli.yml; qed");
-
     // ! #[tokio::main] runner
     let mut rt = tokio::runtime::Builder::new()
         .threaded_scheduler()
@@ -55,10 +60,46 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let fut = opentron::commands::check::main(config_file, arg_matches);
             rt.block_on(fut)
         }
+        ("db", Some(arg_matches)) => {
+            let fut = opentron::commands::db::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
         ("fix", Some(arg_matches)) => {
             let fut = opentron::commands::fix::main(config_file, arg_matches);
             rt.block_on(fut)
         }
+        ("genesis", Some(arg_matches)) => {
+            let fut = opentron::commands::genesis::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("export", Some(arg_matches)) => {
+            let fut = opentron::commands::export::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("import", Some(arg_matches)) => {
+            let fut = opentron::commands::import::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("migrate", Some(arg_matches)) => {
+            let fut = opentron::commands::migrate::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("tx", Some(arg_matches)) => {
+            let fut = opentron::commands::tx::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("address-book", Some(arg_matches)) => {
+            let fut = opentron::commands::address_book::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("bench", Some(arg_matches)) => {
+            let fut = opentron::commands::bench::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("debug", Some(arg_matches)) => {
+            let fut = opentron::commands::debug::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
         ("dev", Some(arg_matches)) => {
             let fut = opentron::commands::dev::main(config_file, arg_matches);
             rt.block_on(fut)
@@ -73,60 +114,140 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 // NOTE: #[tokio::main] conflicts with slog_scope, cause data race in global static resource release.
 async fn run<P: AsRef<Path>>(config_file: P) -> Result<(), Box<dyn Error>> {
     let mut ctx = AppContext::from_config(config_file)?;
-    info!("load config => \n{:#?}", ctx.config);
-    ctx.outbound_ip = get_my_ip().await.unwrap_or("127.0.0.1".into());
+    info!("effective config, from file unless noted =>\n{}", ctx.config.dump());
+    for key in ctx.config.unknown_keys() {
+        warn!("unknown config key {:?} is ignored", key);
+    }
+    ctx.outbound_ip = detect_external_ip(&ctx)
+        .await
+        .map(|ip| ip.to_string())
+        .unwrap_or("127.0.0.1".into());
     info!("outbound ip address: {}", ctx.outbound_ip);
+    if cfg!(feature = "graphql") && ctx.config.graphql.enable && ctx.state_db.is_some() {
+        ctx.constant_calls = Some(ConstantCallService::new(&ctx.config, &ctx.genesis_config));
+    }
     let ctx = Arc::new(ctx);
 
-    let (done, _) = broadcast::channel::<()>(1);
-    let (termination_tx, termination_done) = oneshot::channel::<()>();
+    let (done, shutdown_signal) = broadcast::channel::<()>(1);
     let termination_handler = {
         let ctx = ctx.clone();
-        let done = done.clone();
         move || {
             let _ = done.send(());
             while let Some(done) = ctx.peers.write().unwrap().pop() {
                 let _ = done.send(());
             }
             ctx.running.store(false, Ordering::SeqCst);
-            ctx.chain_db.report_status();
-            unsafe {
-                ctx.chain_db.prepare_close();
-            }
-            let _ = termination_tx.send(());
         }
     };
 
-    let f = Mutex::new(Some(termination_handler));
-    ctrlc::set_handler(move || {
-        eprintln!("\nCtrl-C pressed...");
-        if let Ok(mut guard) = f.lock() {
-            let f = guard.take().expect("f can only be taken once");
-            f();
+    let f = Arc::new(Mutex::new(Some(termination_handler)));
+    {
+        let f = f.clone();
+        ctrlc::set_handler(move || {
+            eprintln!("\nCtrl-C pressed...");
+            if let Ok(mut guard) = f.lock() {
+                if let Some(f) = guard.take() {
+                    f();
+                }
+            }
+        })
+        .expect("Error setting Ctrl-C handler");
+    }
+
+    // ! admin API stop request
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    *ctx.stop_request.lock().unwrap() = Some(stop_tx);
+    tokio::spawn(async move {
+        if stop_rx.await.is_ok() {
+            info!("stop requested");
+            if let Ok(mut guard) = f.lock() {
+                if let Some(f) = guard.take() {
+                    f();
+                }
+            }
         }
-    })
-    .expect("Error setting Ctrl-C handler");
+    });
 
-    let graphql_service = {
+    // ! services, stopped by stage: API and p2p ingress first, then outbound helpers.
+    let mut supervisor = Supervisor::new(ctx.clone());
+    #[cfg(feature = "graphql")]
+    {
         let ctx = ctx.clone();
-        let done_signal = done.subscribe();
-        let logger = slog_scope::logger().new(o!("service" => "graphql"));
-        graphql_server(ctx, done_signal).with_logger(logger)
-    };
-
-    let channel_service = {
+        supervisor.add_service("graphql", 0, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "graphql"));
+            let ctx = ctx.clone();
+            async move {
+                graphql_server(ctx, done_signal).await;
+                Ok::<_, Box<dyn Error>>(())
+            }
+            .with_logger(logger)
+        });
+    }
+    {
         let ctx = ctx.clone();
-        let done_signal = done.subscribe();
-        let logger = slog_scope::logger().new(o!("service" => "channel"));
-        channel_server(ctx, done_signal).with_logger(logger)
-    };
-
-    let discovery_service = {
+        supervisor.add_service("rosetta", 0, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "rosetta"));
+            let ctx = ctx.clone();
+            async move {
+                rosetta_server(ctx, done_signal).await;
+                Ok::<_, Box<dyn Error>>(())
+            }
+            .with_logger(logger)
+        });
+    }
+    {
         let ctx = ctx.clone();
-        let done_signal = done.subscribe();
-        discovery_server(ctx, done_signal)
-    };
-    let _ = join!(graphql_service, channel_service, discovery_service);
+        supervisor.add_service("metrics", 0, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "metrics"));
+            metrics_service(ctx.clone(), done_signal).with_logger(logger)
+        });
+    }
+    {
+        let ctx = ctx.clone();
+        supervisor.add_service("discovery", 0, move |done_signal| discovery_server(ctx.clone(), done_signal));
+    }
+    {
+        let ctx = ctx.clone();
+        supervisor.add_service("channel", 1, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "channel"));
+            channel_server(ctx.clone(), done_signal).with_logger(logger)
+        });
+    }
+    {
+        let ctx = ctx.clone();
+        supervisor.add_service("clock", 2, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "clock"));
+            clock_service(ctx.clone(), done_signal).with_logger(logger)
+        });
+    }
+    {
+        let ctx = ctx.clone();
+        supervisor.add_service("mempool", 2, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "mempool"));
+            mempool_service(ctx.clone(), done_signal).with_logger(logger)
+        });
+    }
+    {
+        let ctx = ctx.clone();
+        supervisor.add_service("nat", 2, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "nat"));
+            nat_service(ctx.clone(), done_signal).with_logger(logger)
+        });
+    }
+    #[cfg(feature = "producer")]
+    {
+        let ctx = ctx.clone();
+        supervisor.add_service("ha", 2, move |done_signal| {
+            let logger = slog_scope::logger().new(o!("service" => "ha"));
+            ha_service(ctx.clone(), done_signal).with_logger(logger)
+        });
+    }
+    supervisor.run(shutdown_signal).await;
 
-    Ok(termination_done.await?)
+    // All services are stopped, or timed out.
+    ctx.chain_db.report_status();
+    unsafe {
+        ctx.chain_db.prepare_close();
+    }
+    Ok(())
 }
@@ -5,7 +5,7 @@ use std::convert::TryFrom;
 use ::keys::Address;
 use log::warn;
 use proto2::chain::transaction::Result as TransactionResult;
-use proto2::chain::ContractType;
+use proto2::chain::{ContractType, OPERATIONS_LEN};
 use proto2::common::{permission::PermissionType, AccountType, Permission};
 use proto2::contract as contract_pb;
 use proto2::state::{Account, ActivePermission, OwnerPermission, PermissionKey};
@@ -172,7 +172,7 @@ impl BuiltinContractExecutorExt for contract_pb::AccountPermissionUpdateContract
 
         if ctx.contract_fee != 0 {
             owner_acct.adjust_balance(-ctx.contract_fee).unwrap();
-            manager.add_to_blackhole(ctx.contract_fee).unwrap();
+            manager.add_to_blackhole(ctx.contract_fee).map_err(|_| "db insert error")?;
         }
         manager
             .state_db
@@ -248,7 +248,7 @@ impl BuiltinContractExecutorExt for contract_pb::AccountCreateContract {
 
         if fee != 0 {
             owner_acct.adjust_balance(-fee).unwrap();
-            manager.add_to_blackhole(fee).unwrap();
+            manager.add_to_blackhole(fee).map_err(|_| "db insert error")?;
             manager
                 .state_db
                 .put_key(keys::Account(owner_address), owner_acct)
@@ -333,15 +333,12 @@ fn check_permission(perm: &Permission, perm_type: PermissionType) -> Result<(),
             }
         }
         PermissionType::Active => {
-            if perm.operations.is_empty() || perm.operations.len() != 32 {
+            if perm.operations.len() != OPERATIONS_LEN {
                 return Err("operations vec length must be 32".into());
             }
             // NOTE: The check logic is buggy in java-tron.
-            for type_code in 0..256 {
-                let mask = (perm.operations[type_code / 8] >> (type_code % 8)) & 1;
-                if mask != 0 && ContractType::from_i32(type_code as i32).is_none() {
-                    return Err(format!("operation of {} is undefined", type_code));
-                }
+            if let Err(bit) = ContractType::validate_operations(&perm.operations) {
+                return Err(format!("operation of {} is undefined", bit));
             }
         }
     }
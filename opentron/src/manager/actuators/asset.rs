@@ -152,13 +152,13 @@ impl BuiltinContractExecutorExt for contract_pb::AssetIssueContract {
     }
 
     fn execute(&self, manager: &mut Manager, ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
-        const DAY_IN_MS: i64 = 86_400_000;
 
         let owner_address = Address::try_from(&self.owner_address).unwrap();
         let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_address));
 
         let token_id = manager.state_db.must_get(&keys::DynamicProperty::LatestTokenId) + 1;
         owner_acct.issued_asset_id = token_id;
+        owner_acct.issued_asset_name = self.name.clone();
 
         let allow_same_token_name = manager.state_db.must_get(&keys::ChainParameter::AllowSameTokenName) != 0;
 
@@ -174,7 +174,7 @@ impl BuiltinContractExecutorExt for contract_pb::AssetIssueContract {
                 .iter()
                 .map(|sup| FrozenSupply {
                     frozen_amount: sup.frozen_amount,
-                    frozen_expiry_timestamp: self.start_time + sup.frozen_days * DAY_IN_MS,
+                    frozen_expiry_timestamp: self.start_time + sup.frozen_days * constants::DAY_IN_MS,
                     is_unfrozen: false,
                 })
                 .collect(),
@@ -195,7 +195,13 @@ impl BuiltinContractExecutorExt for contract_pb::AssetIssueContract {
             asset.precision = 0;
         }
         let remain_supply = self.total_supply - self.frozen_supply.iter().map(|sup| sup.frozen_amount).sum::<i64>();
-        owner_acct.token_balance.insert(token_id, remain_supply);
+        // Zero balances are never written.
+        if remain_supply != 0 {
+            manager
+                .state_db
+                .put_key(keys::AccountAsset(owner_address, token_id), remain_supply)
+                .map_err(|_| "db insert error")?;
+        }
 
         manager
             .state_db
@@ -208,7 +214,7 @@ impl BuiltinContractExecutorExt for contract_pb::AssetIssueContract {
 
         if ctx.contract_fee != 0 {
             owner_acct.adjust_balance(-ctx.contract_fee).unwrap();
-            manager.add_to_blackhole(ctx.contract_fee).unwrap();
+            manager.add_to_blackhole(ctx.contract_fee).map_err(|_| "db insert error")?;
         }
         manager
             .state_db
@@ -263,7 +269,9 @@ impl BuiltinContractExecutorExt for contract_pb::TransferAssetContract {
         }
         let owner_acct = maybe_owner_acct.unwrap();
 
-        let token_balance = owner_acct.token_balance.get(&asset.id).copied().unwrap_or(0);
+        let token_balance = manager
+            .get_token_balance(owner_address, asset.id)
+            .map_err(|_| "db query error")?;
         if token_balance < self.amount {
             return Err("insufficient token balance".into());
         }
@@ -278,11 +286,9 @@ impl BuiltinContractExecutorExt for contract_pb::TransferAssetContract {
                 return Err("cannot transfer to a smart contract".into());
             }
 
-            if to_acct
-                .token_balance
-                .get(&asset.id)
-                .copied()
-                .unwrap_or(0)
+            if manager
+                .get_token_balance(to_address, asset.id)
+                .map_err(|_| "db query error")?
                 .checked_add(self.amount)
                 .is_none()
             {
@@ -326,14 +332,15 @@ impl BuiltinContractExecutorExt for contract_pb::TransferAssetContract {
 
         if fee != 0 {
             owner_acct.adjust_balance(-fee).unwrap();
-            manager.add_to_blackhole(fee).unwrap();
+            manager.add_to_blackhole(fee).map_err(|_| "db insert error")?;
         }
 
-        owner_acct
-            .token_balance
-            .entry(token_id)
-            .and_modify(|bal| *bal -= self.amount);
-        *to_acct.token_balance.entry(token_id).or_default() += self.amount;
+        manager
+            .adjust_token_balance(owner_address, token_id, -self.amount)
+            .map_err(|e| e.to_string())?;
+        manager
+            .adjust_token_balance(to_address, token_id, self.amount)
+            .map_err(|e| e.to_string())?;
 
         manager
             .state_db
@@ -415,9 +422,12 @@ impl BuiltinContractExecutorExt for contract_pb::ParticipateAssetIssueContract {
         if maybe_to_acct.is_none() {
             return Err("to account is not on chain".into());
         }
-        let to_acct = maybe_to_acct.unwrap();
 
-        if to_acct.token_balance.get(&asset.id).copied().unwrap_or(0) < exchange_amount {
+        if manager
+            .get_token_balance(to_address, asset.id)
+            .map_err(|_| "db query error")? <
+            exchange_amount
+        {
             return Err("insufficient balance of target asset".into());
         }
 
@@ -448,8 +458,12 @@ impl BuiltinContractExecutorExt for contract_pb::ParticipateAssetIssueContract {
         owner_acct.adjust_balance(-self.amount).unwrap();
         to_acct.adjust_balance(self.amount).unwrap();
 
-        owner_acct.adjust_token_balance(asset.id, exchange_amount).unwrap();
-        to_acct.adjust_token_balance(asset.id, -exchange_amount).unwrap();
+        manager
+            .adjust_token_balance(owner_address, asset.id, exchange_amount)
+            .map_err(|e| e.to_string())?;
+        manager
+            .adjust_token_balance(to_address, asset.id, -exchange_amount)
+            .map_err(|e| e.to_string())?;
 
         manager
             .state_db
@@ -581,7 +595,7 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeAssetContract {
 
     fn execute(&self, manager: &mut Manager, ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
         let owner_address = Address::try_from(&self.owner_address).unwrap();
-        let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_address));
+        let owner_acct = manager.state_db.must_get(&keys::Account(owner_address));
         let mut asset = manager.state_db.must_get(&keys::Asset(owner_acct.issued_asset_id));
 
         let now = manager.latest_block_timestamp();
@@ -595,18 +609,14 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeAssetContract {
         }
 
         ctx.unfrozen_amount = unfrozen_amount;
-        owner_acct
-            .adjust_token_balance(owner_acct.issued_asset_id, unfrozen_amount)
-            .unwrap();
+        manager
+            .adjust_token_balance(owner_address, owner_acct.issued_asset_id, unfrozen_amount)
+            .map_err(|e| e.to_string())?;
 
         manager
             .state_db
             .put_key(keys::Asset(owner_acct.issued_asset_id), asset)
             .map_err(|_| "db insert error")?;
-        manager
-            .state_db
-            .put_key(keys::Account(owner_address), owner_acct)
-            .map_err(|_| "db insert error")?;
 
         Ok(TransactionResult::success())
     }
@@ -15,10 +15,13 @@ mod account;
 pub mod asset;
 mod proposal;
 mod resource;
+mod resource_v2;
 mod smart_contract;
 mod transfer;
 mod witness;
 
+pub use self::smart_contract::generate_created_contract_address;
+
 pub trait BuiltinContractExt: Message + Default + Sized {
     fn owner_address(&self) -> &[u8];
 
@@ -93,9 +96,7 @@ pub trait BuiltinContractExecutorExt: BuiltinContractExt {
             if permission_id >= 2 {
                 // active perm id is counted from 2
                 if let Some(active_perm) = acct.active_permissions.get(permission_id as usize - 2) {
-                    let type_num = self.type_code() as i32 as usize;
-                    let mask = (active_perm.operations[type_num / 8] >> (type_num % 8)) & 1;
-                    if mask == 0 {
+                    if !self.type_code().is_operation_enabled(&active_perm.operations) {
                         return Err(format!("operation bit of {:?} is disabled", self.type_code()));
                     }
 
@@ -204,6 +205,12 @@ impl_contract_ext_for!(UpdateEnergyLimitContract);
 impl_contract_ext_for!(ClearAbiContract, "ClearABIContract");
 impl_contract_ext_for!(FreezeBalanceContract);
 impl_contract_ext_for!(UnfreezeBalanceContract);
+impl_contract_ext_for!(FreezeBalanceV2Contract);
+impl_contract_ext_for!(UnfreezeBalanceV2Contract);
+impl_contract_ext_for!(WithdrawExpireUnfreezeContract);
+impl_contract_ext_for!(DelegateResourceContract);
+impl_contract_ext_for!(UnDelegateResourceContract);
+impl_contract_ext_for!(CancelAllUnfreezeV2Contract);
 impl_contract_ext_for!(ProposalCreateContract);
 impl_contract_ext_for!(ProposalApproveContract);
 impl_contract_ext_for!(ProposalDeleteContract);
@@ -11,6 +11,7 @@ use state::keys;
 
 use super::super::executor::TransactionContext;
 use super::super::governance::reward::RewardController;
+use super::super::governance::votes::sub_votes;
 use super::super::Manager;
 use super::BuiltinContractExecutorExt;
 
@@ -28,7 +29,7 @@ impl BuiltinContractExecutorExt for contract_pb::FreezeBalanceContract {
         }
         let owner_acct = owner_acct.unwrap();
 
-        if self.frozen_balance < 1_000_000 {
+        if self.frozen_balance < constants::MIN_FROZEN_BALANCE {
             return Err("frozen balance must be greater than 1_TRX".into());
         }
         if self.frozen_balance > owner_acct.balance {
@@ -85,15 +86,16 @@ impl BuiltinContractExecutorExt for contract_pb::FreezeBalanceContract {
     }
 
     fn execute(&self, manager: &mut Manager, _ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
-        const DAY_IN_MS: i64 = 86_400_000;
-
         let owner_addr = Address::try_from(&self.owner_address).unwrap();
 
         let now = manager.latest_block_timestamp();
-        let duration = self.frozen_duration * DAY_IN_MS;
+        let duration = self.frozen_duration * constants::DAY_IN_MS;
         let expire_time = now + duration;
 
-        let maybe_recv_addr = Address::try_from(&self.receiver_address).ok();
+        // Receiver is ignored before AllowDelegateResource.
+        let maybe_recv_addr = Address::try_from(&self.receiver_address)
+            .ok()
+            .filter(|_| manager.state_db.must_get(&keys::ChainParameter::AllowDelegateResource) == 1);
 
         // NOTE: In OpenTron, delegate to others and freeze for oneself is handled in the same logic.
         if let Some(resource_type) = ResourceCode::from_i32(self.resource) {
@@ -149,7 +151,20 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeBalanceContract {
                 return Err("receiver account is not on chain".into());
             }
 
-            unimplemented!("TODO: handle un-delegate");
+            let del = state_db
+                .get(&keys::ResourceDelegation(owner_addr, recv_addr))
+                .map_err(|_| "error while querying db")?
+                .ok_or("delegated resource does not exist")?;
+            let (amount, expiration) = match resource_type {
+                ResourceCode::Bandwidth => (del.amount_for_bandwidth, del.expiration_timestamp_for_bandwidth),
+                ResourceCode::Energy => (del.amount_for_energy, del.expiration_timestamp_for_energy),
+            };
+            if amount <= 0 {
+                return Err("no delegated frozen balance".into());
+            }
+            if expiration > now {
+                return Err("delegation is not expired yet, cannot unfreeze".into());
+            }
         } else {
             // NOTE: there will be only 1 freeze!
             let del = state_db.must_get(&keys::ResourceDelegation(owner_addr, owner_addr));
@@ -174,6 +189,12 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeBalanceContract {
             }
         }
 
+        // Rewards are withdrawn into allowance on execution.
+        let reward = RewardController::pending_reward(manager, owner_addr)?;
+        if owner_acct.allowance.checked_add(reward).is_none() {
+            return Err("math overflow while adding reward".into());
+        }
+
         Ok(())
     }
 
@@ -190,7 +211,61 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeBalanceContract {
         if !self.receiver_address.is_empty() &&
             manager.state_db.must_get(&keys::ChainParameter::AllowDelegateResource) == 1
         {
-            unimplemented!("TODO: handle unfreeze after AllowDelegateResource");
+            let recv_addr = Address::try_from(&self.receiver_address).unwrap();
+            let mut del = manager
+                .state_db
+                .must_get(&keys::ResourceDelegation(owner_addr, recv_addr));
+            let mut recv_acct = manager.state_db.must_get(&keys::Account(recv_addr));
+            // NOTE: Since Constantinople, contract accounts might have acquired resource decreased by suicide.
+            let is_contract_receiver = manager
+                .state_db
+                .must_get(&keys::ChainParameter::AllowTvmConstantinopleUpgrade) ==
+                1 &&
+                recv_acct.r#type == AccountType::Contract as i32;
+            match resource_type {
+                ResourceCode::Bandwidth => {
+                    unfrozen_amount += del.amount_for_bandwidth;
+
+                    recv_acct.delegated_frozen_amount_for_bandwidth -= del.amount_for_bandwidth;
+                    if is_contract_receiver && recv_acct.delegated_frozen_amount_for_bandwidth < 0 {
+                        recv_acct.delegated_frozen_amount_for_bandwidth = 0;
+                    }
+                    del.amount_for_bandwidth = 0;
+                    del.expiration_timestamp_for_bandwidth = 0;
+                }
+                ResourceCode::Energy => {
+                    unfrozen_amount += del.amount_for_energy;
+
+                    recv_acct.delegated_frozen_amount_for_energy -= del.amount_for_energy;
+                    if is_contract_receiver && recv_acct.delegated_frozen_amount_for_energy < 0 {
+                        recv_acct.delegated_frozen_amount_for_energy = 0;
+                    }
+                    del.amount_for_energy = 0;
+                    del.expiration_timestamp_for_energy = 0;
+                }
+            }
+            ctx.unfrozen_amount = unfrozen_amount;
+
+            // TRON Power of delegated resource stays with the owner, and is taken back here.
+            owner_acct.delegated_out_amount -= unfrozen_amount;
+            owner_acct.adjust_balance(unfrozen_amount).unwrap();
+
+            manager
+                .state_db
+                .put_key(keys::Account(recv_addr), recv_acct)
+                .map_err(|_| "db insert error")?;
+            if del.amount_for_bandwidth == 0 && del.amount_for_energy == 0 {
+                manager
+                    .state_db
+                    .delete_key(&keys::ResourceDelegation(owner_addr, recv_addr))
+                    .map_err(|_| "db delete error")?;
+                remove_from_delegation_index(manager, owner_addr, recv_addr)?;
+            } else {
+                manager
+                    .state_db
+                    .put_key(keys::ResourceDelegation(owner_addr, recv_addr), del)
+                    .map_err(|_| "db insert error")?;
+            }
         } else {
             let mut del = manager
                 .state_db
@@ -233,29 +308,10 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeBalanceContract {
         let weight = manager.state_db.must_get(&weight_key);
         manager
             .state_db
-            .put_key(weight_key, weight - unfrozen_amount / 1_000_000)
+            .put_key(weight_key, weight - unfrozen_amount / constants::SUN_PER_TRX)
             .map_err(|_| "db insert error")?;
 
-        // clear votes
-        let maybe_votes = manager
-            .state_db
-            .get(&keys::Votes(owner_addr))
-            .map_err(|_| "db query error")?;
-        if let Some(votes) = maybe_votes {
-            for vote in &votes.votes {
-                let wit_addr = Address::try_from(&vote.vote_address).unwrap();
-                let mut wit = manager.state_db.must_get(&keys::Witness(wit_addr));
-                wit.vote_count -= vote.vote_count;
-                manager
-                    .state_db
-                    .put_key(keys::Witness(wit_addr), wit)
-                    .map_err(|_| "db insert error")?;
-            }
-            manager
-                .state_db
-                .delete_key(&keys::Votes(owner_addr))
-                .map_err(|_| "db delete error")?;
-        }
+        clear_votes(manager, owner_addr)?;
 
         // save owner_acct at last
         manager
@@ -268,39 +324,72 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeBalanceContract {
     }
 }
 
-fn add_to_delegation_index(manager: &mut Manager, from: Address, to: Address) -> Result<(), String> {
-    let maybe_indexed_addrs = manager
+/// Remove all votes of an account, and vote counts of the voted witnesses.
+pub(super) fn clear_votes(manager: &mut Manager, owner_addr: Address) -> Result<(), String> {
+    let maybe_votes = manager
         .state_db
-        .get(&keys::ResourceDelegationIndex(from))
+        .get(&keys::Votes(owner_addr))
         .map_err(|_| "db query error")?;
+    if let Some(votes) = maybe_votes {
+        for vote in &votes.votes {
+            let wit_addr = Address::try_from(&vote.vote_address).unwrap();
+            let mut wit = manager.state_db.must_get(&keys::Witness(wit_addr));
+            wit.vote_count = sub_votes(wit.vote_count, vote.vote_count)?;
+            manager
+                .state_db
+                .put_key(keys::Witness(wit_addr), wit)
+                .map_err(|_| "db insert error")?;
+        }
+        manager
+            .state_db
+            .delete_key(&keys::Votes(owner_addr))
+            .map_err(|_| "db delete error")?;
+    }
+    Ok(())
+}
+
+fn add_to_delegation_index(manager: &mut Manager, from: Address, to: Address) -> Result<(), String> {
+    add_to_address_list(manager, keys::ResourceDelegationIndex(from), to)?;
+    add_to_address_list(manager, keys::ResourceDelegationReverseIndex(to), from)
+}
+
+fn remove_from_delegation_index(manager: &mut Manager, from: Address, to: Address) -> Result<(), String> {
+    remove_from_address_list(manager, keys::ResourceDelegationIndex(from), to)?;
+    remove_from_address_list(manager, keys::ResourceDelegationReverseIndex(to), from)
+}
+
+pub(super) fn add_to_address_list<K: keys::Key<Vec<Address>>>(
+    manager: &mut Manager,
+    key: K,
+    addr: Address,
+) -> Result<(), String> {
+    let maybe_indexed_addrs = manager.state_db.get(&key).map_err(|_| "db query error")?;
     let mut indexed_addrs = maybe_indexed_addrs.unwrap_or_default();
-    if !indexed_addrs.contains(&to) {
-        indexed_addrs.push(to);
+    if !indexed_addrs.contains(&addr) {
+        indexed_addrs.push(addr);
         manager
             .state_db
-            .put_key(keys::ResourceDelegationIndex(from), indexed_addrs)
+            .put_key(key, indexed_addrs)
             .map_err(|_| "db insert error")?;
     }
     Ok(())
 }
 
-fn remove_from_delegation_index(manager: &mut Manager, from: Address, to: Address) -> Result<(), String> {
-    let maybe_indexed_addrs = manager
-        .state_db
-        .get(&keys::ResourceDelegationIndex(from))
-        .map_err(|_| "db query error")?;
+pub(super) fn remove_from_address_list<K: keys::Key<Vec<Address>>>(
+    manager: &mut Manager,
+    key: K,
+    addr: Address,
+) -> Result<(), String> {
+    let maybe_indexed_addrs = manager.state_db.get(&key).map_err(|_| "db query error")?;
     let indexed_addrs = maybe_indexed_addrs.unwrap_or_default();
-    let indexed_addrs: Vec<_> = indexed_addrs.into_iter().filter(|addr| addr != &to).collect();
+    let indexed_addrs: Vec<_> = indexed_addrs.into_iter().filter(|indexed| indexed != &addr).collect();
     if !indexed_addrs.is_empty() {
         manager
             .state_db
-            .put_key(keys::ResourceDelegationIndex(from), indexed_addrs)
+            .put_key(key, indexed_addrs)
             .map_err(|_| "db insert error")?;
     } else {
-        manager
-            .state_db
-            .delete_key(&keys::ResourceDelegationIndex(from))
-            .map_err(|_| "db delete eerror")?;
+        manager.state_db.delete_key(&key).map_err(|_| "db delete error")?;
     }
     Ok(())
 }
@@ -347,7 +436,7 @@ fn delegate_resource(
     let old_total_weight = manager.state_db.must_get(&weight_key);
     manager
         .state_db
-        .put_key(weight_key, old_total_weight + amount / 1_000_000)
+        .put_key(weight_key, old_total_weight + amount / constants::SUN_PER_TRX)
         .map_err(|_| "db insert error")?;
 
     // handle delegated-resource-index
@@ -368,7 +457,7 @@ fn delegate_resource(
         .put_key(keys::Account(to), to_acct)
         .map_err(|_| "db insert error")?;
 
-    // handle from_account balance
+    // handle from_account balance, TRON Power of delegated amount stays with the owner.
     let mut from_acct = manager.state_db.must_get(&keys::Account(from));
     from_acct.delegated_out_amount += amount;
     from_acct.adjust_balance(-amount).unwrap();
@@ -420,7 +509,7 @@ fn freeze_resource(
     let old_total_weight = manager.state_db.must_get(&weight_key);
     manager
         .state_db
-        .put_key(weight_key, old_total_weight + amount / 1_000_000)
+        .put_key(weight_key, old_total_weight + amount / constants::SUN_PER_TRX)
         .map_err(|_| "db insert error")?;
 
     // handle delegated-resource-index
@@ -0,0 +1,968 @@
+//! Stake 2.0, freeze, unfreeze and resource delegation, enabled by `UnfreezeDelayDays`.
+
+use std::convert::TryFrom;
+
+use ::keys::Address;
+use proto2::chain::transaction::Result as TransactionResult;
+use proto2::common::{AccountType, ResourceCode};
+use proto2::contract as contract_pb;
+use proto2::state::{Account, PendingUnfreeze, ResourceDelegation};
+use state::keys;
+
+use super::super::executor::TransactionContext;
+use super::super::governance::reward::RewardController;
+use super::super::governance::votes::sum_votes;
+use super::super::resource::adjust_usage;
+use super::super::Manager;
+use super::resource::{add_to_address_list, clear_votes, remove_from_address_list};
+use super::BuiltinContractExecutorExt;
+
+fn support_unfreeze_delay(manager: &Manager) -> bool {
+    manager.state_db.must_get(&keys::ChainParameter::UnfreezeDelayDays) > 0
+}
+
+fn support_delegate_resource_v2(manager: &Manager) -> bool {
+    manager.state_db.must_get(&keys::ChainParameter::AllowDelegateResource) == 1 && support_unfreeze_delay(manager)
+}
+
+/// `MaxDelegateLockPeriod` is raised by proposal, lock period of delegation is customizable.
+fn support_max_delegate_lock_period(manager: &Manager) -> bool {
+    manager.state_db.must_get(&keys::ChainParameter::MaxDelegateLockPeriod) >
+        constants::DEFAULT_DELEGATE_LOCK_PERIOD / manager.block_interval() &&
+        support_unfreeze_delay(manager)
+}
+
+impl BuiltinContractExecutorExt for contract_pb::FreezeBalanceV2Contract {
+    fn validate(&self, manager: &Manager, _ctx: &mut TransactionContext) -> Result<(), String> {
+        if !support_unfreeze_delay(manager) {
+            return Err("freeze v2 is not enabled, requires UnfreezeDelayDays".into());
+        }
+
+        let owner_address = Address::try_from(&self.owner_address).map_err(|_| "invalid owner_address")?;
+        let owner_acct = manager
+            .state_db
+            .get(&keys::Account(owner_address))
+            .map_err(|_| "error while querying db")?
+            .ok_or("owner account is not on chain")?;
+
+        if self.frozen_balance < constants::MIN_FROZEN_BALANCE {
+            return Err("frozen balance must be greater than 1_TRX".into());
+        }
+        if self.frozen_balance > owner_acct.balance {
+            return Err(format!(
+                "insufficient balance, balance={}, required={}",
+                owner_acct.balance, self.frozen_balance
+            ));
+        }
+        if ResourceCode::from_i32(self.resource).is_none() {
+            return Err("resource code is invalid, possible values: [BANDWIDTH, ENERGY]".into());
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, manager: &mut Manager, _ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
+        let owner_addr = Address::try_from(&self.owner_address).unwrap();
+        let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_addr));
+
+        let weight_key = match ResourceCode::from_i32(self.resource).unwrap() {
+            ResourceCode::Bandwidth => {
+                owner_acct.frozen_v2_amount_for_bandwidth += self.frozen_balance;
+                keys::DynamicProperty::TotalBandwidthWeight
+            }
+            ResourceCode::Energy => {
+                owner_acct.frozen_v2_amount_for_energy += self.frozen_balance;
+                keys::DynamicProperty::TotalEnergyWeight
+            }
+        };
+        owner_acct.adjust_balance(-self.frozen_balance).unwrap();
+
+        let weight = manager.state_db.must_get(&weight_key);
+        manager
+            .state_db
+            .put_key(weight_key, weight + self.frozen_balance / constants::SUN_PER_TRX)
+            .map_err(|_| "db insert error")?;
+        manager
+            .state_db
+            .put_key(keys::Account(owner_addr), owner_acct)
+            .map_err(|_| "db insert error")?;
+
+        Ok(TransactionResult::success())
+    }
+}
+
+impl BuiltinContractExecutorExt for contract_pb::UnfreezeBalanceV2Contract {
+    fn validate(&self, manager: &Manager, _ctx: &mut TransactionContext) -> Result<(), String> {
+        if !support_unfreeze_delay(manager) {
+            return Err("unfreeze v2 is not enabled, requires UnfreezeDelayDays".into());
+        }
+
+        let owner_addr = Address::try_from(&self.owner_address).map_err(|_| "invalid owner_address")?;
+        let owner_acct = manager
+            .state_db
+            .get(&keys::Account(owner_addr))
+            .map_err(|_| "error while querying db")?
+            .ok_or("owner account is not on chain")?;
+
+        let resource_type = ResourceCode::from_i32(self.resource)
+            .ok_or("resource code is invalid, possible values: [BANDWIDTH, ENERGY]")?;
+        let frozen_balance = match resource_type {
+            ResourceCode::Bandwidth => owner_acct.frozen_v2_amount_for_bandwidth,
+            ResourceCode::Energy => owner_acct.frozen_v2_amount_for_energy,
+        };
+        if frozen_balance <= 0 {
+            return Err(format!("no frozen v2 balance of {:?}", resource_type));
+        }
+        if self.unfreeze_balance <= 0 || self.unfreeze_balance > frozen_balance {
+            return Err(format!(
+                "unfreeze balance must be in range [1, {}], got {}",
+                frozen_balance, self.unfreeze_balance
+            ));
+        }
+
+        let now = manager.latest_block_timestamp();
+        let num_pending = owner_acct
+            .unfrozen_v2
+            .iter()
+            .filter(|unfreeze| unfreeze.expiration_timestamp > now)
+            .count();
+        if num_pending >= constants::MAX_NUM_OF_PENDING_UNFREEZES {
+            return Err(format!(
+                "too many pending unfreezes, max={}",
+                constants::MAX_NUM_OF_PENDING_UNFREEZES
+            ));
+        }
+        // Expired unfreezes are withdrawn on execution.
+        let withdrawal_amount = owner_acct
+            .unfrozen_v2
+            .iter()
+            .filter(|unfreeze| unfreeze.expiration_timestamp <= now)
+            .try_fold(0_i64, |acc, unfreeze| acc.checked_add(unfreeze.amount))
+            .ok_or("math overflow")?;
+        owner_acct.balance.checked_add(withdrawal_amount).ok_or("math overflow")?;
+
+        // Rewards are withdrawn into allowance on execution.
+        let reward = RewardController::pending_reward(manager, owner_addr)?;
+        if owner_acct.allowance.checked_add(reward).is_none() {
+            return Err("math overflow while adding reward".into());
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, manager: &mut Manager, ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
+        let owner_addr = Address::try_from(&self.owner_address).unwrap();
+        let resource_type = ResourceCode::from_i32(self.resource).unwrap();
+        let now = manager.latest_block_timestamp();
+
+        RewardController::new(manager).withdraw_reward(owner_addr)?;
+
+        let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_addr));
+        let withdrawal_amount = withdraw_expired_unfreezes(&mut owner_acct, now)?;
+        owner_acct.adjust_balance(withdrawal_amount).map_err(|_| "math overflow")?;
+        ctx.withdrawal_amount = withdrawal_amount;
+
+        let weight_key = match resource_type {
+            ResourceCode::Bandwidth => {
+                owner_acct.frozen_v2_amount_for_bandwidth -= self.unfreeze_balance;
+                keys::DynamicProperty::TotalBandwidthWeight
+            }
+            ResourceCode::Energy => {
+                owner_acct.frozen_v2_amount_for_energy -= self.unfreeze_balance;
+                keys::DynamicProperty::TotalEnergyWeight
+            }
+        };
+        let unfreeze_delay_days = manager.state_db.must_get(&keys::ChainParameter::UnfreezeDelayDays);
+        owner_acct.unfrozen_v2.push(PendingUnfreeze {
+            resource: self.resource,
+            amount: self.unfreeze_balance,
+            expiration_timestamp: now + unfreeze_delay_days * constants::DAY_IN_MS,
+        });
+
+        let weight = manager.state_db.must_get(&weight_key);
+        manager
+            .state_db
+            .put_key(weight_key, weight - self.unfreeze_balance / constants::SUN_PER_TRX)
+            .map_err(|_| "db insert error")?;
+
+        // Votes are cleared only when TRON Power is no longer sufficient.
+        let total_votes = match manager
+            .state_db
+            .get(&keys::Votes(owner_addr))
+            .map_err(|_| "db query error")?
+        {
+            Some(votes) => sum_votes(votes.votes.iter().map(|vote| vote.vote_count))?,
+            None => 0,
+        };
+        if total_votes > owner_acct.tron_power() {
+            clear_votes(manager, owner_addr)?;
+        }
+
+        manager
+            .state_db
+            .put_key(keys::Account(owner_addr), owner_acct)
+            .map_err(|_| "db insert error")?;
+
+        Ok(TransactionResult::success())
+    }
+}
+
+impl BuiltinContractExecutorExt for contract_pb::WithdrawExpireUnfreezeContract {
+    fn validate(&self, manager: &Manager, _ctx: &mut TransactionContext) -> Result<(), String> {
+        if !support_unfreeze_delay(manager) {
+            return Err("unfreeze v2 is not enabled, requires UnfreezeDelayDays".into());
+        }
+
+        let owner_addr = Address::try_from(&self.owner_address).map_err(|_| "invalid owner_address")?;
+        let owner_acct = manager
+            .state_db
+            .get(&keys::Account(owner_addr))
+            .map_err(|_| "error while querying db")?
+            .ok_or("owner account is not on chain")?;
+
+        let now = manager.latest_block_timestamp();
+        let withdrawal_amount = owner_acct
+            .unfrozen_v2
+            .iter()
+            .filter(|unfreeze| unfreeze.expiration_timestamp <= now)
+            .try_fold(0_i64, |acc, unfreeze| acc.checked_add(unfreeze.amount))
+            .ok_or("math overflow")?;
+        if withdrawal_amount <= 0 {
+            return Err("no expired unfreeze to withdraw".into());
+        }
+        owner_acct.balance.checked_add(withdrawal_amount).ok_or("math overflow")?;
+
+        Ok(())
+    }
+
+    fn execute(&self, manager: &mut Manager, ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
+        let owner_addr = Address::try_from(&self.owner_address).unwrap();
+        let now = manager.latest_block_timestamp();
+
+        let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_addr));
+        let withdrawal_amount = withdraw_expired_unfreezes(&mut owner_acct, now)?;
+        owner_acct.adjust_balance(withdrawal_amount).map_err(|_| "math overflow")?;
+        ctx.withdrawal_amount = withdrawal_amount;
+
+        manager
+            .state_db
+            .put_key(keys::Account(owner_addr), owner_acct)
+            .map_err(|_| "db insert error")?;
+
+        Ok(TransactionResult::success())
+    }
+}
+
+/// Remove matured pending unfreezes, returns the total amount to be withdrawn.
+fn withdraw_expired_unfreezes(acct: &mut Account, now: i64) -> Result<i64, String> {
+    let mut withdrawal_amount = Some(0_i64);
+    acct.unfrozen_v2.retain(|unfreeze| {
+        if unfreeze.expiration_timestamp <= now {
+            withdrawal_amount = withdrawal_amount.and_then(|amount| amount.checked_add(unfreeze.amount));
+            false
+        } else {
+            true
+        }
+    });
+    withdrawal_amount.ok_or_else(|| "math overflow".into())
+}
+
+impl BuiltinContractExecutorExt for contract_pb::DelegateResourceContract {
+    fn validate(&self, manager: &Manager, _ctx: &mut TransactionContext) -> Result<(), String> {
+        if !support_delegate_resource_v2(manager) {
+            return Err("resource delegation v2 is not enabled".into());
+        }
+
+        let owner_addr = Address::try_from(&self.owner_address).map_err(|_| "invalid owner_address")?;
+        let owner_acct = manager
+            .state_db
+            .get(&keys::Account(owner_addr))
+            .map_err(|_| "error while querying db")?
+            .ok_or("owner account is not on chain")?;
+
+        if self.balance < constants::MIN_FROZEN_BALANCE {
+            return Err("delegate balance must be greater than 1_TRX".into());
+        }
+        let resource_type = ResourceCode::from_i32(self.resource)
+            .ok_or("resource code is invalid, possible values: [BANDWIDTH, ENERGY]")?;
+        // Frozen v2 balance in use by the owner can not be delegated, `getCanDelegatedMaxSize`.
+        let frozen_balance = match resource_type {
+            ResourceCode::Bandwidth => owner_acct.frozen_v2_amount_for_bandwidth,
+            ResourceCode::Energy => owner_acct.frozen_v2_amount_for_energy,
+        };
+        let (limit, weight) = total_limit_and_weight(manager, resource_type);
+        let usage = recovered_usage(&owner_acct, resource_type, manager.get_head_slot());
+        let in_use = frozen_v2_in_use(&owner_acct, resource_type, usage, limit, weight);
+        if self.balance > frozen_balance - in_use {
+            return Err(format!(
+                "delegate balance exceeds available frozen v2 balance of {:?}, frozen={}, in use={}, required={}",
+                resource_type, frozen_balance, in_use, self.balance
+            ));
+        }
+
+        if self.receiver_address == self.owner_address {
+            return Err("the owner and receiver address cannot be the same".into());
+        }
+        let recv_addr = Address::try_from(&self.receiver_address).map_err(|_| "invalid receiver_address")?;
+        let recv_acct = manager
+            .state_db
+            .get(&keys::Account(recv_addr))
+            .map_err(|_| "error while querying db")?
+            .ok_or("receiver account is not on chain")?;
+        if recv_acct.r#type == AccountType::Contract as i32 {
+            return Err("delegate resource to contract address is not allowed".into());
+        }
+
+        if self.lock && support_max_delegate_lock_period(manager) {
+            let max_lock_period = manager.state_db.must_get(&keys::ChainParameter::MaxDelegateLockPeriod);
+            if self.lock_period < 0 || self.lock_period > max_lock_period {
+                return Err(format!("lock period must be in range [0, {}]", max_lock_period));
+            }
+
+            let maybe_locked = manager
+                .state_db
+                .get(&keys::ResourceDelegationV2(owner_addr, recv_addr, true))
+                .map_err(|_| "error while querying db")?;
+            if let Some(locked) = maybe_locked {
+                let expiration = match resource_type {
+                    ResourceCode::Bandwidth => locked.expiration_timestamp_for_bandwidth,
+                    ResourceCode::Energy => locked.expiration_timestamp_for_energy,
+                };
+                let now = manager.latest_block_timestamp();
+                if expiration > now + lock_period_in_ms(manager, self.lock_period) {
+                    return Err(format!(
+                        "lock period of {:?} cannot be less than the remaining time {}ms",
+                        resource_type,
+                        expiration - now
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, manager: &mut Manager, _ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
+        let owner_addr = Address::try_from(&self.owner_address).unwrap();
+        let recv_addr = Address::try_from(&self.receiver_address).unwrap();
+        let resource_type = ResourceCode::from_i32(self.resource).unwrap();
+        let now = manager.latest_block_timestamp();
+
+        unlock_expired_delegation(manager, owner_addr, recv_addr, now)?;
+
+        let key = keys::ResourceDelegationV2(owner_addr, recv_addr, self.lock);
+        let mut delegated = manager
+            .state_db
+            .get(&key)
+            .map_err(|_| "db query error")?
+            .unwrap_or_else(|| ResourceDelegation {
+                to_address: recv_addr.as_bytes().to_vec(),
+                from_address: owner_addr.as_bytes().to_vec(),
+                ..Default::default()
+            });
+        let expiration = if self.lock {
+            now + lock_period_in_ms(manager, self.lock_period)
+        } else {
+            0
+        };
+
+        let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_addr));
+        let mut recv_acct = manager.state_db.must_get(&keys::Account(recv_addr));
+        match resource_type {
+            ResourceCode::Bandwidth => {
+                delegated.amount_for_bandwidth += self.balance;
+                delegated.expiration_timestamp_for_bandwidth = expiration;
+                owner_acct.frozen_v2_amount_for_bandwidth -= self.balance;
+                owner_acct.delegated_v2_out_amount_for_bandwidth += self.balance;
+                recv_acct.delegated_v2_frozen_amount_for_bandwidth += self.balance;
+            }
+            ResourceCode::Energy => {
+                delegated.amount_for_energy += self.balance;
+                delegated.expiration_timestamp_for_energy = expiration;
+                owner_acct.frozen_v2_amount_for_energy -= self.balance;
+                owner_acct.delegated_v2_out_amount_for_energy += self.balance;
+                recv_acct.delegated_v2_frozen_amount_for_energy += self.balance;
+            }
+        }
+
+        manager
+            .state_db
+            .put_key(key, delegated)
+            .map_err(|_| "db insert error")?;
+        add_to_address_list(manager, keys::ResourceDelegationV2ReverseIndex(recv_addr), owner_addr)?;
+        manager
+            .state_db
+            .put_key(keys::Account(recv_addr), recv_acct)
+            .map_err(|_| "db insert error")?;
+        manager
+            .state_db
+            .put_key(keys::Account(owner_addr), owner_acct)
+            .map_err(|_| "db insert error")?;
+
+        Ok(TransactionResult::success())
+    }
+}
+
+impl BuiltinContractExecutorExt for contract_pb::UnDelegateResourceContract {
+    fn validate(&self, manager: &Manager, _ctx: &mut TransactionContext) -> Result<(), String> {
+        if !support_delegate_resource_v2(manager) {
+            return Err("resource delegation v2 is not enabled".into());
+        }
+
+        let owner_addr = Address::try_from(&self.owner_address).map_err(|_| "invalid owner_address")?;
+        manager
+            .state_db
+            .get(&keys::Account(owner_addr))
+            .map_err(|_| "error while querying db")?
+            .ok_or("owner account is not on chain")?;
+
+        if self.receiver_address == self.owner_address {
+            return Err("the owner and receiver address cannot be the same".into());
+        }
+        let recv_addr = Address::try_from(&self.receiver_address).map_err(|_| "invalid receiver_address")?;
+
+        if self.balance <= 0 {
+            return Err("undelegate balance must be greater than 0".into());
+        }
+        let resource_type = ResourceCode::from_i32(self.resource)
+            .ok_or("resource code is invalid, possible values: [BANDWIDTH, ENERGY]")?;
+
+        let maybe_unlocked = manager
+            .state_db
+            .get(&keys::ResourceDelegationV2(owner_addr, recv_addr, false))
+            .map_err(|_| "error while querying db")?;
+        let maybe_locked = manager
+            .state_db
+            .get(&keys::ResourceDelegationV2(owner_addr, recv_addr, true))
+            .map_err(|_| "error while querying db")?;
+        if maybe_unlocked.is_none() && maybe_locked.is_none() {
+            return Err("delegated resource does not exist".into());
+        }
+
+        // Locked delegation is available only after expiration.
+        let now = manager.latest_block_timestamp();
+        let available = match resource_type {
+            ResourceCode::Bandwidth => {
+                maybe_unlocked.as_ref().map_or(0, |del| del.amount_for_bandwidth) +
+                    maybe_locked
+                        .as_ref()
+                        .filter(|del| del.expiration_timestamp_for_bandwidth < now)
+                        .map_or(0, |del| del.amount_for_bandwidth)
+            }
+            ResourceCode::Energy => {
+                maybe_unlocked.as_ref().map_or(0, |del| del.amount_for_energy) +
+                    maybe_locked
+                        .as_ref()
+                        .filter(|del| del.expiration_timestamp_for_energy < now)
+                        .map_or(0, |del| del.amount_for_energy)
+            }
+        };
+        if available < self.balance {
+            return Err(format!(
+                "insufficient delegated balance of {:?}, available={}, required={}",
+                resource_type, available, self.balance
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, manager: &mut Manager, _ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
+        let owner_addr = Address::try_from(&self.owner_address).unwrap();
+        let recv_addr = Address::try_from(&self.receiver_address).unwrap();
+        let resource_type = ResourceCode::from_i32(self.resource).unwrap();
+        let now = manager.latest_block_timestamp();
+
+        unlock_expired_delegation(manager, owner_addr, recv_addr, now)?;
+
+        let key = keys::ResourceDelegationV2(owner_addr, recv_addr, false);
+        let mut unlocked = manager.state_db.must_get(&key);
+        let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_addr));
+        // NOTE: The receiver might be a contract suicided since Constantinople.
+        let mut maybe_recv_acct = manager
+            .state_db
+            .get(&keys::Account(recv_addr))
+            .map_err(|_| "db query error")?;
+
+        // Usage of the receiver is transferred back to the owner, `unDelegateIncrease`.
+        let slot = manager.get_head_slot();
+        let (limit, weight) = total_limit_and_weight(manager, resource_type);
+        let mut transferred_usage = 0;
+        if let Some(recv_acct) = maybe_recv_acct.as_mut() {
+            let usage = recovered_usage(recv_acct, resource_type, slot);
+            let acquired = match resource_type {
+                ResourceCode::Bandwidth => recv_acct.delegated_v2_frozen_amount_for_bandwidth,
+                ResourceCode::Energy => recv_acct.delegated_v2_frozen_amount_for_energy,
+            };
+            let new_acquired = if acquired < self.balance {
+                0
+            } else {
+                // NOTE: Proportion is of all frozen balance before undelegating.
+                transferred_usage = undelegated_usage(recv_acct, resource_type, usage, self.balance, limit, weight);
+                acquired - self.balance
+            };
+            match resource_type {
+                ResourceCode::Bandwidth => recv_acct.delegated_v2_frozen_amount_for_bandwidth = new_acquired,
+                ResourceCode::Energy => recv_acct.delegated_v2_frozen_amount_for_energy = new_acquired,
+            }
+            set_usage(recv_acct, resource_type, usage - transferred_usage, slot);
+        }
+        match resource_type {
+            ResourceCode::Bandwidth => {
+                unlocked.amount_for_bandwidth -= self.balance;
+                owner_acct.delegated_v2_out_amount_for_bandwidth -= self.balance;
+                owner_acct.frozen_v2_amount_for_bandwidth += self.balance;
+            }
+            ResourceCode::Energy => {
+                unlocked.amount_for_energy -= self.balance;
+                owner_acct.delegated_v2_out_amount_for_energy -= self.balance;
+                owner_acct.frozen_v2_amount_for_energy += self.balance;
+            }
+        }
+        if transferred_usage > 0 {
+            let owner_usage = recovered_usage(&owner_acct, resource_type, slot);
+            set_usage(
+                &mut owner_acct,
+                resource_type,
+                adjust_usage(owner_usage, transferred_usage, slot, slot),
+                slot,
+            );
+        }
+
+        if unlocked.amount_for_bandwidth == 0 && unlocked.amount_for_energy == 0 {
+            manager.state_db.delete_key(&key).map_err(|_| "db delete error")?;
+            let has_locked = manager
+                .state_db
+                .get(&keys::ResourceDelegationV2(owner_addr, recv_addr, true))
+                .map_err(|_| "db query error")?
+                .is_some();
+            if !has_locked {
+                remove_from_address_list(manager, keys::ResourceDelegationV2ReverseIndex(recv_addr), owner_addr)?;
+            }
+        } else {
+            manager
+                .state_db
+                .put_key(key, unlocked)
+                .map_err(|_| "db insert error")?;
+        }
+        if let Some(recv_acct) = maybe_recv_acct {
+            manager
+                .state_db
+                .put_key(keys::Account(recv_addr), recv_acct)
+                .map_err(|_| "db insert error")?;
+        }
+        manager
+            .state_db
+            .put_key(keys::Account(owner_addr), owner_acct)
+            .map_err(|_| "db insert error")?;
+
+        Ok(TransactionResult::success())
+    }
+}
+
+impl BuiltinContractExecutorExt for contract_pb::CancelAllUnfreezeV2Contract {
+    fn validate(&self, manager: &Manager, _ctx: &mut TransactionContext) -> Result<(), String> {
+        if manager
+            .state_db
+            .must_get(&keys::ChainParameter::AllowCancelAllUnfreezeV2) ==
+            0 ||
+            !support_unfreeze_delay(manager)
+        {
+            return Err("cancel all unfreeze v2 is not enabled, requires AllowCancelAllUnfreezeV2".into());
+        }
+
+        let owner_addr = Address::try_from(&self.owner_address).map_err(|_| "invalid owner_address")?;
+        let owner_acct = manager
+            .state_db
+            .get(&keys::Account(owner_addr))
+            .map_err(|_| "error while querying db")?
+            .ok_or("owner account is not on chain")?;
+        if owner_acct.unfrozen_v2.is_empty() {
+            return Err("no pending unfreeze to cancel".into());
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, manager: &mut Manager, ctx: &mut TransactionContext) -> Result<TransactionResult, String> {
+        let owner_addr = Address::try_from(&self.owner_address).unwrap();
+        let mut owner_acct = manager.state_db.must_get(&keys::Account(owner_addr));
+        let now = manager.latest_block_timestamp();
+
+        let mut withdrawal_amount = 0;
+        let mut refrozen_for_bandwidth = 0;
+        let mut refrozen_for_energy = 0;
+        for unfreeze in owner_acct.unfrozen_v2.drain(..) {
+            if unfreeze.expiration_timestamp <= now {
+                withdrawal_amount += unfreeze.amount;
+                continue;
+            }
+            // Unexpired ones are frozen again, re-crediting TRON Power.
+            match ResourceCode::from_i32(unfreeze.resource) {
+                Some(ResourceCode::Bandwidth) => refrozen_for_bandwidth += unfreeze.amount,
+                Some(ResourceCode::Energy) => refrozen_for_energy += unfreeze.amount,
+                None => unreachable!("checked by UnfreezeBalanceV2"),
+            }
+        }
+        owner_acct.frozen_v2_amount_for_bandwidth += refrozen_for_bandwidth;
+        owner_acct.frozen_v2_amount_for_energy += refrozen_for_energy;
+        owner_acct.adjust_balance(withdrawal_amount).unwrap();
+        ctx.withdrawal_amount = withdrawal_amount;
+
+        for (weight_key, amount) in vec![
+            (keys::DynamicProperty::TotalBandwidthWeight, refrozen_for_bandwidth),
+            (keys::DynamicProperty::TotalEnergyWeight, refrozen_for_energy),
+        ] {
+            if amount > 0 {
+                let weight = manager.state_db.must_get(&weight_key);
+                manager
+                    .state_db
+                    .put_key(weight_key, weight + amount / constants::SUN_PER_TRX)
+                    .map_err(|_| "db insert error")?;
+            }
+        }
+        manager
+            .state_db
+            .put_key(keys::Account(owner_addr), owner_acct)
+            .map_err(|_| "db insert error")?;
+
+        Ok(TransactionResult::success())
+    }
+}
+
+/// Lock period of a locked delegation, in ms.
+fn lock_period_in_ms(manager: &Manager, lock_period: i64) -> i64 {
+    if support_max_delegate_lock_period(manager) && lock_period != 0 {
+        lock_period * manager.block_interval()
+    } else {
+        constants::DEFAULT_DELEGATE_LOCK_PERIOD
+    }
+}
+
+/// Move expired amounts of the locked delegation into the unlocked one.
+fn unlock_expired_delegation(manager: &mut Manager, from: Address, to: Address, now: i64) -> Result<(), String> {
+    let locked_key = keys::ResourceDelegationV2(from, to, true);
+    let mut locked = match manager.state_db.get(&locked_key).map_err(|_| "db query error")? {
+        Some(locked) => locked,
+        None => return Ok(()),
+    };
+
+    let mut expired_for_bandwidth = 0;
+    let mut expired_for_energy = 0;
+    if locked.expiration_timestamp_for_bandwidth < now {
+        expired_for_bandwidth = locked.amount_for_bandwidth;
+        locked.amount_for_bandwidth = 0;
+        locked.expiration_timestamp_for_bandwidth = 0;
+    }
+    if locked.expiration_timestamp_for_energy < now {
+        expired_for_energy = locked.amount_for_energy;
+        locked.amount_for_energy = 0;
+        locked.expiration_timestamp_for_energy = 0;
+    }
+    if expired_for_bandwidth == 0 && expired_for_energy == 0 {
+        return Ok(());
+    }
+
+    let unlocked_key = keys::ResourceDelegationV2(from, to, false);
+    let mut unlocked = manager
+        .state_db
+        .get(&unlocked_key)
+        .map_err(|_| "db query error")?
+        .unwrap_or_else(|| ResourceDelegation {
+            to_address: to.as_bytes().to_vec(),
+            from_address: from.as_bytes().to_vec(),
+            ..Default::default()
+        });
+    unlocked.amount_for_bandwidth += expired_for_bandwidth;
+    unlocked.amount_for_energy += expired_for_energy;
+    manager
+        .state_db
+        .put_key(unlocked_key, unlocked)
+        .map_err(|_| "db insert error")?;
+
+    if locked.amount_for_bandwidth == 0 && locked.amount_for_energy == 0 {
+        manager
+            .state_db
+            .delete_key(&locked_key)
+            .map_err(|_| "db delete error")?;
+    } else {
+        manager
+            .state_db
+            .put_key(locked_key, locked)
+            .map_err(|_| "db insert error")?;
+    }
+    Ok(())
+}
+
+/// Usage of the resource recovered to the slot `now`.
+fn recovered_usage(acct: &Account, resource_type: ResourceCode, now: i64) -> i64 {
+    let (usage, latest_slot) = acct.resource.as_ref().map_or((0, now), |res| match resource_type {
+        ResourceCode::Bandwidth => (res.frozen_bandwidth_used, res.frozen_bandwidth_latest_slot),
+        ResourceCode::Energy => (res.energy_used, res.energy_latest_slot),
+    });
+    adjust_usage(usage, 0, latest_slot, now)
+}
+
+fn set_usage(acct: &mut Account, resource_type: ResourceCode, usage: i64, now: i64) {
+    let res = acct.resource_mut();
+    match resource_type {
+        ResourceCode::Bandwidth => {
+            res.frozen_bandwidth_used = usage;
+            res.frozen_bandwidth_latest_slot = now;
+        }
+        ResourceCode::Energy => {
+            res.energy_used = usage;
+            res.energy_latest_slot = now;
+        }
+    }
+}
+
+/// Total limit and total weight(in TRX) of the resource.
+fn total_limit_and_weight(manager: &Manager, resource_type: ResourceCode) -> (i64, i64) {
+    match resource_type {
+        ResourceCode::Bandwidth => (
+            manager.state_db.must_get(&keys::DynamicProperty::TotalBandwidthLimit),
+            manager.state_db.must_get(&keys::DynamicProperty::TotalBandwidthWeight),
+        ),
+        ResourceCode::Energy => (
+            manager.state_db.must_get(&keys::ChainParameter::TotalEnergyCurrentLimit),
+            manager.state_db.must_get(&keys::DynamicProperty::TotalEnergyWeight),
+        ),
+    }
+}
+
+/// Frozen v2 balance backing the usage, `getV2NetUsage` and `getV2EnergyUsage`.
+///
+/// Usage is converted to balance by the total weight, then covered by frozen v1 and acquired balances first.
+fn frozen_v2_in_use(acct: &Account, resource_type: ResourceCode, usage: i64, limit: i64, weight: i64) -> i64 {
+    let usage_in_sun = (usage as f64 * constants::SUN_PER_TRX as f64 * (weight as f64 / limit as f64)) as i64;
+    let (all_frozen, frozen_v2) = match resource_type {
+        ResourceCode::Bandwidth => (acct.amount_for_bandwidth(), acct.frozen_v2_amount_for_bandwidth),
+        ResourceCode::Energy => (acct.amount_for_energy(), acct.frozen_v2_amount_for_energy),
+    };
+    (usage_in_sun - (all_frozen - frozen_v2)).max(0)
+}
+
+/// Usage of the receiver transferred back to the owner, in proportion of the undelegated balance to all frozen
+/// balance of the receiver, at most the resource of the undelegated balance.
+fn undelegated_usage(
+    recv_acct: &Account,
+    resource_type: ResourceCode,
+    usage: i64,
+    balance: i64,
+    limit: i64,
+    weight: i64,
+) -> i64 {
+    let all_frozen = match resource_type {
+        ResourceCode::Bandwidth => recv_acct.amount_for_bandwidth(),
+        ResourceCode::Energy => recv_acct.amount_for_energy(),
+    };
+    let max_usage = ((balance / constants::SUN_PER_TRX) as f64 * (limit as f64 / weight as f64)) as i64;
+    let usage = (usage as f64 * (balance as f64 / all_frozen as f64)) as i64;
+    usage.min(max_usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::keys::KeyPair;
+
+    use super::super::super::testing::{empty_block_and_transaction, TestManager};
+    use super::*;
+
+    /// 1_000 energy per TRX, the owner has 1_000 TRX frozen for energy, with half of it in use.
+    fn init_delegation_state(manager: &mut Manager) -> (Address, Address) {
+        use state::keys::ChainParameter::*;
+
+        let owner_addr = KeyPair::from_seed(b"owner").unwrap().address();
+        let recv_addr = KeyPair::from_seed(b"receiver").unwrap().address();
+        let slot = manager.get_head_slot();
+        let state_db = &mut manager.state_db;
+
+        state_db.new_layer();
+        state_db.put_key(AllowDelegateResource, 1).unwrap();
+        state_db.put_key(UnfreezeDelayDays, 14).unwrap();
+        state_db.put_key(TotalEnergyCurrentLimit, 1_000_000).unwrap();
+        state_db.put_key(keys::DynamicProperty::TotalEnergyWeight, 1_000).unwrap();
+
+        let mut owner_acct = Account::new(0);
+        owner_acct.frozen_v2_amount_for_energy = 1_000_000_000;
+        set_usage(&mut owner_acct, ResourceCode::Energy, 500_000, slot);
+        state_db.put_key(keys::Account(owner_addr), owner_acct).unwrap();
+        state_db.put_key(keys::Account(recv_addr), Account::new(0)).unwrap();
+
+        (owner_addr, recv_addr)
+    }
+
+    fn energy_usage(manager: &Manager, addr: Address) -> i64 {
+        manager.state_db.must_get(&keys::Account(addr)).resource().energy_used
+    }
+
+    #[test]
+    fn test_delegate_resource_with_usage() {
+        let mut manager = TestManager::new();
+        let (owner_addr, recv_addr) = init_delegation_state(&mut manager);
+        let (header, txn) = empty_block_and_transaction();
+        let mut ctx = TransactionContext::new(&header, &txn);
+
+        let delegate = |balance| contract_pb::DelegateResourceContract {
+            owner_address: owner_addr.as_bytes().to_vec(),
+            receiver_address: recv_addr.as_bytes().to_vec(),
+            resource: ResourceCode::Energy as i32,
+            balance,
+            ..Default::default()
+        };
+        // 500_TRX in use, by 500_000 energy.
+        assert!(delegate(600_000_000).validate(&manager, &mut ctx).is_err());
+        assert!(delegate(500_000_000).validate(&manager, &mut ctx).is_ok());
+        delegate(500_000_000).execute(&mut manager, &mut ctx).unwrap();
+
+        let owner_acct = manager.state_db.must_get(&keys::Account(owner_addr));
+        assert_eq!(owner_acct.frozen_v2_amount_for_energy, 500_000_000);
+        // No more available.
+        assert!(delegate(1_000_000).validate(&manager, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_undelegate_resource_transfers_usage() {
+        let mut manager = TestManager::new();
+        let (owner_addr, recv_addr) = init_delegation_state(&mut manager);
+        let (header, txn) = empty_block_and_transaction();
+        let mut ctx = TransactionContext::new(&header, &txn);
+
+        contract_pb::DelegateResourceContract {
+            owner_address: owner_addr.as_bytes().to_vec(),
+            receiver_address: recv_addr.as_bytes().to_vec(),
+            resource: ResourceCode::Energy as i32,
+            balance: 500_000_000,
+            ..Default::default()
+        }
+        .execute(&mut manager, &mut ctx)
+        .unwrap();
+
+        let slot = manager.get_head_slot();
+        let mut recv_acct = manager.state_db.must_get(&keys::Account(recv_addr));
+        set_usage(&mut recv_acct, ResourceCode::Energy, 300_000, slot);
+        manager.state_db.put_key(keys::Account(recv_addr), recv_acct).unwrap();
+
+        let undelegate = contract_pb::UnDelegateResourceContract {
+            owner_address: owner_addr.as_bytes().to_vec(),
+            receiver_address: recv_addr.as_bytes().to_vec(),
+            resource: ResourceCode::Energy as i32,
+            balance: 250_000_000,
+        };
+        undelegate.validate(&manager, &mut ctx).unwrap();
+        undelegate.execute(&mut manager, &mut ctx).unwrap();
+
+        // Half of the acquired balance, half of the usage.
+        assert_eq!(energy_usage(&manager, recv_addr), 150_000);
+        assert_eq!(energy_usage(&manager, owner_addr), 650_000);
+        let recv_acct = manager.state_db.must_get(&keys::Account(recv_addr));
+        assert_eq!(recv_acct.delegated_v2_frozen_amount_for_energy, 250_000_000);
+    }
+
+    #[test]
+    fn test_delegation_reverse_index() {
+        let mut manager = TestManager::new();
+        let (owner_addr, recv_addr) = init_delegation_state(&mut manager);
+        let (header, txn) = empty_block_and_transaction();
+        let mut ctx = TransactionContext::new(&header, &txn);
+        let delegators = |manager: &Manager| {
+            manager
+                .state_db
+                .get(&keys::ResourceDelegationV2ReverseIndex(recv_addr))
+                .unwrap()
+        };
+
+        let delegate = |balance, lock| contract_pb::DelegateResourceContract {
+            owner_address: owner_addr.as_bytes().to_vec(),
+            receiver_address: recv_addr.as_bytes().to_vec(),
+            resource: ResourceCode::Energy as i32,
+            balance,
+            lock,
+            ..Default::default()
+        };
+        let undelegate = |balance| contract_pb::UnDelegateResourceContract {
+            owner_address: owner_addr.as_bytes().to_vec(),
+            receiver_address: recv_addr.as_bytes().to_vec(),
+            resource: ResourceCode::Energy as i32,
+            balance,
+        };
+
+        delegate(100_000_000, false).execute(&mut manager, &mut ctx).unwrap();
+        assert_eq!(delegators(&manager), Some(vec![owner_addr]));
+        // Locked and unlocked delegations of the same pair are indexed once.
+        delegate(100_000_000, true).execute(&mut manager, &mut ctx).unwrap();
+        assert_eq!(delegators(&manager), Some(vec![owner_addr]));
+
+        // Still indexed by the locked delegation.
+        undelegate(100_000_000).execute(&mut manager, &mut ctx).unwrap();
+        assert_eq!(delegators(&manager), Some(vec![owner_addr]));
+
+        manager
+            .state_db
+            .delete_key(&keys::ResourceDelegationV2(owner_addr, recv_addr, true))
+            .unwrap();
+        delegate(100_000_000, false).execute(&mut manager, &mut ctx).unwrap();
+        undelegate(100_000_000).execute(&mut manager, &mut ctx).unwrap();
+        assert_eq!(delegators(&manager), None);
+    }
+
+    #[test]
+    fn test_undelegated_usage_is_capped() {
+        let recv_acct = Account {
+            frozen_v2_amount_for_energy: 1_000_000_000,
+            delegated_v2_frozen_amount_for_energy: 1_000_000_000,
+            ..Default::default()
+        };
+        // 100_TRX of 2_000_TRX, at most 100_000 energy of the 100_TRX.
+        assert_eq!(
+            undelegated_usage(&recv_acct, ResourceCode::Energy, 1_000_000, 100_000_000, 1_000_000, 1_000),
+            50_000
+        );
+        assert_eq!(
+            undelegated_usage(&recv_acct, ResourceCode::Energy, 4_000_000, 100_000_000, 1_000_000, 1_000),
+            100_000
+        );
+        assert_eq!(
+            frozen_v2_in_use(&recv_acct, ResourceCode::Energy, 1_500_000, 1_000_000, 1_000),
+            500_000_000
+        );
+    }
+
+    #[test]
+    fn test_withdraw_expired_unfreezes() {
+        let mut acct = Account {
+            unfrozen_v2: vec![
+                PendingUnfreeze {
+                    resource: ResourceCode::Bandwidth as i32,
+                    amount: 1_000_000,
+                    expiration_timestamp: 1_000,
+                },
+                PendingUnfreeze {
+                    resource: ResourceCode::Energy as i32,
+                    amount: 2_000_000,
+                    expiration_timestamp: 3_000,
+                },
+                PendingUnfreeze {
+                    resource: ResourceCode::Energy as i32,
+                    amount: 4_000_000,
+                    expiration_timestamp: 2_000,
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(withdraw_expired_unfreezes(&mut acct, 500), Ok(0));
+        assert_eq!(acct.unfrozen_v2.len(), 3);
+        assert_eq!(withdraw_expired_unfreezes(&mut acct, 2_000), Ok(5_000_000));
+        assert_eq!(acct.unfrozen_v2.len(), 1);
+        assert_eq!(acct.unfrozen_v2[0].expiration_timestamp, 3_000);
+
+        acct.unfrozen_v2[0].amount = i64::MAX;
+        acct.unfrozen_v2.push(PendingUnfreeze {
+            resource: ResourceCode::Energy as i32,
+            amount: 1,
+            expiration_timestamp: 1_000,
+        });
+        assert!(withdraw_expired_unfreezes(&mut acct, 3_000).is_err());
+    }
+}
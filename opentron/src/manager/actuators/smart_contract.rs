@@ -12,7 +12,7 @@ use proto2::chain::transaction::{result::ContractStatus, Result as TransactionRe
 use proto2::contract as contract_pb;
 use proto2::state::{Account, SmartContract};
 use state::keys;
-use tvm::{backend::ApplyBackend, ExitError, ExitReason};
+use tvm::{Capture, ExitError, ExitReason};
 
 use super::super::controllers::ForkController;
 use super::super::executor::TransactionContext;
@@ -23,8 +23,6 @@ use super::super::Manager;
 use super::BuiltinContractExecutorExt;
 
 const MAX_CONTRACT_NAME_LENGTH: usize = 32;
-const MAX_FEE_LIMIT: i64 = 1_000_000_000;
-const MIN_TOKEN_ID: i64 = 1_000_000;
 const SAVE_CODE_ENERGY_PER_BYTE: usize = 200;
 
 // Create a smart contract and deploy it on chain.
@@ -77,9 +75,7 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
         }
 
         log::debug!("fee_limit => {}", ctx.fee_limit);
-        if ctx.fee_limit < 0 || ctx.fee_limit > MAX_FEE_LIMIT {
-            return Err("invalid fee_limit".into());
-        }
+        validate_fee_limit(manager, ctx.fee_limit)?;
 
         let maybe_owner_acct = manager
             .state_db
@@ -117,7 +113,7 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
         if allow_trc10_transfer {
             // NOTE: also checks allowMultiSig
             if manager.state_db.must_get(&keys::ChainParameter::AllowMultisig) != 0 {
-                if call_token_id != 0 && call_token_id <= MIN_TOKEN_ID {
+                if call_token_id != 0 && call_token_id <= constants::MIN_TOKEN_ID {
                     return Err("invalid token id range".into());
                 }
                 if call_token_value > 0 && call_token_id == 0 {
@@ -164,33 +160,36 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
             cntr_acct.adjust_balance(call_value).unwrap();
         }
         if self.call_token_value > 0 {
-            if owner_acct
-                .adjust_token_balance(self.call_token_id, -self.call_token_value)
+            if manager
+                .adjust_token_balance(owner_address, self.call_token_id, -self.call_token_value)
                 .is_err()
             {
                 return Err("insufficient token balance".into()); // validate error
             }
-            cntr_acct
-                .adjust_token_balance(self.call_token_id, self.call_token_value)
-                .unwrap();
+            manager
+                .adjust_token_balance(cntr_address, self.call_token_id, self.call_token_value)
+                .map_err(|e| e.to_string())?;
         }
 
         manager
             .state_db
             .put_key(keys::Account(cntr_address), cntr_acct)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
         manager
             .state_db
             .put_key(keys::Account(owner_address), owner_acct)
-            .unwrap();
-        manager.state_db.put_key(keys::Contract(cntr_address), cntr).unwrap();
+            .map_err(|_| "db insert error")?;
+        manager
+            .state_db
+            .put_key(keys::Contract(cntr_address), cntr)
+            .map_err(|_| "db insert error")?;
         if !allow_tvm_constantinople {
             let code = legacy_get_code(&new_cntr.bytecode);
             log::debug!("legacy code size => {}", code.len());
             manager
                 .state_db
                 .put_key(keys::ContractCode(cntr_address), code.to_vec())
-                .unwrap();
+                .map_err(|_| "db insert error")?;
         }
 
         // execution
@@ -214,9 +213,12 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
         let data = Rc::default();
 
         let mut rt = tvm::Runtime::new(code, data, vm_ctx, &config);
-        let mut exit_reason = executor.execute(&mut rt);
+        let maybe_exit_reason = execute_with_step_limit(&mut rt, &mut executor, energy_limit);
+        let out_of_time = maybe_exit_reason.is_none();
+        // All energy is spent when out of time.
+        let mut exit_reason = maybe_exit_reason.unwrap_or(ExitReason::Error(ExitError::OutOfGas));
         log::debug!("TVM exit code => {:?}", exit_reason);
-        let mut used_energy = executor.used_gas();
+        let mut used_energy = if out_of_time { energy_limit } else { executor.used_gas() };
         let ret_val = rt.machine().return_value();
 
         let (applies, logs) = executor.deconstruct();
@@ -235,7 +237,7 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
             );
         }
 
-        backend.apply(applies, logs, false);
+        backend.apply(applies, logs, false)?;
 
         match exit_reason {
             ExitReason::Succeed(_) => {
@@ -245,7 +247,7 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
                     manager
                         .state_db
                         .put_key(keys::ContractCode(cntr_address), ret_val.clone())
-                        .unwrap();
+                        .map_err(|_| "db insert error")?;
                     ctx.result = ret_val;
                 } else {
                     ctx.result = ret_val;
@@ -258,16 +260,15 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
                     used_energy,
                     save_code_energy
                 );
-                EnergyProcessor::new(manager).consume(
+                consume_energy_of_success(
+                    manager,
                     owner_address,
                     owner_address,
                     energy_usage,
                     0,
                     new_cntr.origin_energy_limit,
                     ctx,
-                )?;
-
-                Ok(TransactionResult::success())
+                )
             }
             ExitReason::Error(ExitError::OutOfGas) => {
                 manager.rollback_layers(1);
@@ -289,8 +290,13 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
                 )?;
 
                 let mut ret = TransactionResult::success();
-                ret.contract_status = ContractStatus::OutOfEnergy as i32;
-                debug!("create contract failed, out out energy");
+                if out_of_time {
+                    ret.contract_status = ContractStatus::OutOfTime as i32;
+                    debug!("create contract failed, out of time");
+                } else {
+                    ret.contract_status = ContractStatus::OutOfEnergy as i32;
+                    debug!("create contract failed, out out energy");
+                }
                 Ok(ret)
             }
             _ => {
@@ -351,7 +357,7 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
         if allow_trc10_transfer {
             // NOTE: also checks allowMultiSig
             if manager.state_db.must_get(&keys::ChainParameter::AllowMultisig) != 0 {
-                if call_token_id != 0 && call_token_id <= MIN_TOKEN_ID {
+                if call_token_id != 0 && call_token_id <= constants::MIN_TOKEN_ID {
                     return Err("invalid token id range".into());
                 }
                 if call_token_value > 0 && call_token_id == 0 {
@@ -366,9 +372,7 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
             .map_err(|_| "db query error")?;
         if code.is_some() && !code.as_ref().unwrap().is_empty() {
             log::debug!("fee_limit => {}", ctx.fee_limit);
-            if ctx.fee_limit < 0 || ctx.fee_limit > MAX_FEE_LIMIT {
-                return Err("invalid fee_limit".into());
-            }
+            validate_fee_limit(manager, ctx.fee_limit)?;
 
             // TODO: check constant call
 
@@ -412,24 +416,24 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
             cntr_acct.adjust_balance(self.call_value).unwrap();
         }
         if self.call_token_value > 0 {
-            if owner_acct
-                .adjust_token_balance(self.call_token_id, -self.call_token_value)
+            if manager
+                .adjust_token_balance(owner_address, self.call_token_id, -self.call_token_value)
                 .is_err()
             {
                 return Err("insufficient token balance".into()); // validate error
             }
-            cntr_acct
-                .adjust_token_balance(self.call_token_id, self.call_token_value)
-                .unwrap();
+            manager
+                .adjust_token_balance(cntr_address, self.call_token_id, self.call_token_value)
+                .map_err(|e| e.to_string())?;
         }
         manager
             .state_db
             .put_key(keys::Account(cntr_address), cntr_acct)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
         manager
             .state_db
             .put_key(keys::Account(owner_address), owner_acct)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
 
         // build execution context
         let code = manager
@@ -459,9 +463,12 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
         };
 
         let mut rt = tvm::Runtime::new(code, data, vm_ctx, &config);
-        let exit_reason = executor.execute(&mut rt);
+        let maybe_exit_reason = execute_with_step_limit(&mut rt, &mut executor, energy_limit);
+        let out_of_time = maybe_exit_reason.is_none();
+        // All energy is spent when out of time.
+        let exit_reason = maybe_exit_reason.unwrap_or(ExitReason::Error(ExitError::OutOfGas));
         log::debug!("TVM exit code => {:?}", exit_reason);
-        let used_energy = executor.used_gas();
+        let used_energy = if out_of_time { energy_limit } else { executor.used_gas() };
         let ret_val = rt.machine().return_value();
         if !ret_val.is_empty() {
             debug!("return value: {:?}", hex::encode(&ret_val));
@@ -469,7 +476,7 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
 
         let (applies, logs) = executor.deconstruct();
 
-        backend.apply(applies, logs, false);
+        backend.apply(applies, logs, false)?;
 
         match exit_reason {
             ExitReason::Succeed(_) => {
@@ -477,16 +484,15 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
                 ctx.energy = energy_usage;
                 ctx.result = ret_val;
                 log::debug!("energy usage: {}/{}", energy_usage, energy_limit);
-                EnergyProcessor::new(manager).consume(
+                consume_energy_of_success(
+                    manager,
                     owner_address,
                     origin_address,
                     energy_usage,
                     cntr.consume_user_energy_percent,
                     cntr.origin_energy_limit,
                     ctx,
-                )?;
-
-                Ok(TransactionResult::success())
+                )
             }
             ExitReason::Error(ExitError::OutOfGas) => {
                 manager.rollback_layers(1);
@@ -508,8 +514,13 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
                 )?;
 
                 let mut ret = TransactionResult::success();
-                ret.contract_status = ContractStatus::OutOfEnergy as i32;
-                debug!("create contract failed, out out energy");
+                if out_of_time {
+                    ret.contract_status = ContractStatus::OutOfTime as i32;
+                    debug!("create contract failed, out of time");
+                } else {
+                    ret.contract_status = ContractStatus::OutOfEnergy as i32;
+                    debug!("create contract failed, out out energy");
+                }
                 Ok(ret)
             }
             ExitReason::Revert(_) => {
@@ -543,6 +554,31 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
     }
 }
 
+/// Run the VM to exit, returns `None` if the step limit derived from `energy_limit` is reached, i.e. out of time.
+///
+/// NOTE: Only steps of the top-level frame are counted, nested calls run to completion inside one step, and are
+/// bounded by energy.
+fn execute_with_step_limit<H: tvm::Handler>(
+    rt: &mut tvm::Runtime,
+    handler: &mut H,
+    energy_limit: usize,
+) -> Option<ExitReason> {
+    let max_steps = tvm::limits::max_steps(energy_limit);
+    let mut steps = 0_usize;
+    loop {
+        match rt.step(handler) {
+            Ok(()) => {}
+            Err(Capture::Exit(reason)) => return Some(reason),
+            Err(Capture::Trap(_)) => unreachable!("StackExecutor handles calls internally; qed"),
+        }
+        steps += 1;
+        if steps >= max_steps {
+            warn!("TVM step limit reached, {} steps with energy limit {}", steps, energy_limit);
+            return None;
+        }
+    }
+}
+
 // NOTE: This is a really bad implementation.
 // It preserves constructor parameters and is inconsistent with save code energy.
 // Anyway, we are not the inventors of bugs, instead, we are copiers.
@@ -567,7 +603,8 @@ fn legacy_get_code(deploy_code: &[u8]) -> &[u8] {
     return &[0u8; 32];
 }
 
-fn generate_created_contract_address(txn_hash: &H256, owner_address: &Address) -> Address {
+/// Address of the contract created by a `CreateSmartContract` transaction.
+pub fn generate_created_contract_address(txn_hash: &H256, owner_address: &Address) -> Address {
     use sha3::Digest;
 
     let mut hasher = sha3::Keccak256::new();
@@ -576,7 +613,51 @@ fn generate_created_contract_address(txn_hash: &H256, owner_address: &Address) -
     Address::from_tvm_bytes(&hasher.finalize()[12..])
 }
 
+/// Consume energy of a successful execution, in the layer of the execution.
+///
+/// When the burnt fee would exceed fee_limit, the execution is reverted as out of energy, still charged with fee_limit.
+fn consume_energy_of_success(
+    manager: &mut Manager,
+    caller: Address,
+    origin: Address,
+    energy_usage: i64,
+    caller_percent: i64,
+    origin_energy_limit: i64,
+    ctx: &mut TransactionContext,
+) -> Result<TransactionResult, String> {
+    let block_energy_usage = manager.block_resource_usage.energy;
+    if EnergyProcessor::new(manager).consume(caller, origin, energy_usage, caller_percent, origin_energy_limit, ctx)? {
+        return Ok(TransactionResult::success());
+    }
+
+    // Drop the execution along with the first charge, and charge again on the state before it.
+    manager.rollback_layers(1);
+    manager.block_resource_usage.energy = block_energy_usage;
+    ctx.energy_usage = 0;
+    ctx.origin_energy_usage = 0;
+    ctx.energy_fee = 0;
+    ctx.result.clear();
+    ctx.logs.clear();
+    EnergyProcessor::new(manager).consume(caller, origin, energy_usage, caller_percent, origin_energy_limit, ctx)?;
+
+    let mut ret = TransactionResult::success();
+    ret.contract_status = ContractStatus::OutOfEnergy as i32;
+    debug!("contract execution failed, energy fee exceeds fee_limit");
+    Ok(ret)
+}
+
 #[inline]
+fn validate_fee_limit(manager: &Manager, fee_limit: i64) -> Result<(), String> {
+    if fee_limit < 0 {
+        return Err("fee_limit must not be negative".into());
+    }
+    let max_fee_limit = manager.state_db.must_get(&keys::ChainParameter::MaxFeeLimit);
+    if fee_limit > max_fee_limit {
+        return Err(format!("fee_limit must not be greater than {}", max_fee_limit));
+    }
+    Ok(())
+}
+
 fn get_account_energy_limit(manager: &Manager, acct: &Account, fee_limit: i64, call_value: i64) -> i64 {
     if ForkController::new(manager)
         .pass_version(BlockVersion::Odyssey3_2_2)
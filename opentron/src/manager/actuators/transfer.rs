@@ -86,7 +86,7 @@ impl BuiltinContractExecutorExt for contract_pb::TransferContract {
 
         if fee != 0 {
             owner_acct.adjust_balance(-fee).unwrap();
-            manager.add_to_blackhole(fee).unwrap();
+            manager.add_to_blackhole(fee).map_err(|_| "db insert error")?;
         }
 
         owner_acct.adjust_balance(-self.amount).unwrap();
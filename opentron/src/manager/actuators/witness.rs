@@ -10,7 +10,8 @@ use proto2::state::{Votes, Witness};
 use state::keys;
 
 use super::super::executor::TransactionContext;
-use super::super::governance::reward::{RewardController, RewardUtil};
+use super::super::governance::reward::RewardController;
+use super::super::governance::votes::adjust_votes;
 use super::super::Manager;
 use super::BuiltinContractExecutorExt;
 
@@ -141,6 +142,12 @@ impl BuiltinContractExecutorExt for contract_pb::VoteWitnessContract {
             ));
         }
 
+        // Rewards are withdrawn into allowance on execution.
+        let reward = RewardController::pending_reward(manager, owner_address)?;
+        if owner_acct.allowance.checked_add(reward).is_none() {
+            return Err("math overflow while adding reward".into());
+        }
+
         Ok(())
     }
 
@@ -162,13 +169,16 @@ impl BuiltinContractExecutorExt for contract_pb::VoteWitnessContract {
         }
 
         for vote in &self.votes {
-            *votes_diff.entry(*Address::from_bytes(&vote.vote_address)).or_default() += vote.vote_count;
+            let count_diff = votes_diff.entry(*Address::from_bytes(&vote.vote_address)).or_default();
+            *count_diff = count_diff
+                .checked_add(vote.vote_count)
+                .ok_or("math overflow while counting votes")?;
         }
 
         // Save votes.
         for (addr, count_diff) in votes_diff {
             let mut wit = manager.state_db.must_get(&keys::Witness(addr));
-            wit.vote_count += count_diff;
+            wit.vote_count = adjust_votes(wit.vote_count, count_diff)?;
 
             manager
                 .state_db
@@ -200,7 +210,6 @@ impl BuiltinContractExecutorExt for contract_pb::VoteWitnessContract {
 // Withdraw block producing reward, standby witness reward, and voting reward.
 impl BuiltinContractExecutorExt for contract_pb::WithdrawBalanceContract {
     fn validate(&self, manager: &Manager, _ctx: &mut TransactionContext) -> Result<(), String> {
-        const DAY_IN_MS: i64 = 86_400_000;
 
         let state_db = &manager.state_db;
 
@@ -226,17 +235,19 @@ impl BuiltinContractExecutorExt for contract_pb::WithdrawBalanceContract {
 
         let latest_withdraw_ts = acct.latest_withdraw_timestamp;
         let now = manager.latest_block_timestamp();
-        let witness_allowance_frozen_time = constants::NUM_OF_FROZEN_DAYS_FOR_WITNESS_ALLOWANCE * DAY_IN_MS;
+        let witness_allowance_frozen_time = constants::NUM_OF_FROZEN_DAYS_FOR_WITNESS_ALLOWANCE * constants::DAY_IN_MS;
 
         if now - latest_withdraw_ts < witness_allowance_frozen_time {
             return Err("latest withdrawal is less than 24 hours ago".into());
         }
 
-        if acct.allowance <= 0 && RewardUtil::new(manager).query_reward(owner_address)? <= 0 {
+        let reward = RewardController::pending_reward(manager, owner_address)?;
+        if acct.allowance <= 0 && reward <= 0 {
             return Err("account does not have any reward".into());
         }
 
-        if acct.balance.checked_add(acct.allowance).is_none() {
+        let allowance = acct.allowance.checked_add(reward).ok_or("math overflow")?;
+        if acct.balance.checked_add(allowance).is_none() {
             return Err("math overflow".into());
         }
 
@@ -0,0 +1,280 @@
+//! Transaction throughput benchmark.
+//!
+//! Synthetic transactions are signed by generated accounts, packed into blocks of scheduled witnesses, and applied
+//! by `Manager::push_block`, i.e. the same path as block syncing, except witness signatures.
+
+use std::time::{Duration, Instant};
+
+use ::keys::{Address, KeyPair};
+use chain::{hash, IndexedBlock};
+use log::info;
+use primitive_types::H256;
+use proto2::chain::{block_header, transaction, BlockHeader, Transaction};
+use proto2::common::{ResourceCode, SmartContract};
+use proto2::contract as contract_pb;
+use proto2::state::Account;
+use state::keys;
+
+use super::actuators::BuiltinContractExt;
+use super::{new_error, Manager, Result};
+
+/// Initial balance of generated accounts, 1M TRX.
+const INITIAL_BALANCE: i64 = 1_000_000_000_000;
+/// Runtime code of a counter contract, `sstore(0, add(sload(0), 1))`.
+pub(super) const COUNTER_CONTRACT_CODE: &str = "60005460010160005500";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    Transfer,
+    Freeze,
+    Trigger,
+}
+
+impl std::str::FromStr for TransactionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "transfer" => Ok(TransactionKind::Transfer),
+            "freeze" => Ok(TransactionKind::Freeze),
+            "trigger" => Ok(TransactionKind::Trigger),
+            _ => Err(format!("unknown transaction kind {:?}", s)),
+        }
+    }
+}
+
+/// Time spent in each stage.
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    pub num_blocks: usize,
+    pub num_transactions: usize,
+    /// Building and signing transactions.
+    pub generate: Duration,
+    /// Hashing transactions, merkle root and block header.
+    pub assemble: Duration,
+    /// Executing blocks.
+    pub apply: Duration,
+}
+
+impl BenchReport {
+    pub fn tps(&self) -> f64 {
+        self.num_transactions as f64 / self.apply.as_secs_f64()
+    }
+
+    pub fn report(&self) {
+        let per_block = |d: Duration| d.as_secs_f64() * 1_000.0 / self.num_blocks.max(1) as f64;
+        info!(
+            "{} txns in {} blocks, TPS={:.1}",
+            self.num_transactions,
+            self.num_blocks,
+            self.tps()
+        );
+        info!(
+            "per block: generate={:.3}ms assemble={:.3}ms apply={:.3}ms",
+            per_block(self.generate),
+            per_block(self.assemble),
+            per_block(self.apply)
+        );
+    }
+}
+
+pub struct Benchmark<'m> {
+    manager: &'m mut Manager,
+    accounts: Vec<KeyPair>,
+    kinds: Vec<TransactionKind>,
+    contract_address: Address,
+    nonce: i64,
+}
+
+impl<'m> Benchmark<'m> {
+    /// Create a benchmark on a freshly inited state-db, generating `num_accounts` funded accounts.
+    pub fn new<'a>(
+        manager: &'a mut Manager,
+        num_accounts: usize,
+        kinds: Vec<TransactionKind>,
+    ) -> Result<Benchmark<'a>> {
+        if manager.latest_block_number() != 0 {
+            return Err(new_error("benchmark must be run on an empty state-db"));
+        }
+        if num_accounts < 2 {
+            return Err(new_error("at least 2 accounts are required"));
+        }
+
+        let accounts: Vec<_> = (0..num_accounts).map(|_| KeyPair::generate()).collect();
+        let contract_address = KeyPair::generate().address();
+
+        // Generated blocks are not signed.
+        manager.skip_block_signature = true;
+        let mut bench = Benchmark {
+            manager,
+            accounts,
+            kinds,
+            contract_address,
+            nonce: 0,
+        };
+        bench.init_state()?;
+        Ok(bench)
+    }
+
+    fn init_state(&mut self) -> Result<()> {
+        let genesis_timestamp = self.manager.genesis_block_timestamp;
+        let state_db = &mut self.manager.state_db;
+
+        state_db.new_layer();
+        for kp in &self.accounts {
+            let mut acct = Account::new(genesis_timestamp);
+            acct.balance = INITIAL_BALANCE;
+            state_db.put_key(keys::Account(kp.address()), acct)?;
+        }
+
+        if self.kinds.contains(&TransactionKind::Trigger) {
+            // A throwaway state-db, TVM can be enabled regardless of the genesis config.
+            state_db.put_key(keys::ChainParameter::AllowTvm, 1)?;
+
+            let code = hex::decode(COUNTER_CONTRACT_CODE).unwrap();
+            let cntr = SmartContract {
+                origin_address: self.accounts[0].address().as_bytes().to_vec(),
+                contract_address: self.contract_address.as_bytes().to_vec(),
+                bytecode: code.clone(),
+                consume_user_energy_percent: 100,
+                name: "Counter".into(),
+                origin_energy_limit: 10_000_000,
+                ..Default::default()
+            };
+            state_db.put_key(keys::Contract(self.contract_address), cntr)?;
+            state_db.put_key(keys::ContractCode(self.contract_address), code)?;
+            state_db.put_key(
+                keys::Account(self.contract_address),
+                Account::new_contract_account(genesis_timestamp),
+            )?;
+        }
+        state_db.solidify_layer();
+        Ok(())
+    }
+
+    /// Apply `num_blocks` blocks, each with `txns_per_block` transactions.
+    pub fn run(&mut self, num_blocks: usize, txns_per_block: usize) -> Result<BenchReport> {
+        let genesis_hash = self.manager.latest_block_hash();
+        // All transactions refer to the genesis block.
+        self.manager.init_ref_blocks(vec![genesis_hash]);
+
+        let mut report = BenchReport::default();
+        for _ in 0..num_blocks {
+            let number = self.manager.latest_block_number() + 1;
+            let timestamp = self.manager.get_slot_timestamp(1);
+            let witness = if number == 1 {
+                // Witness schedule is inited in block #1.
+                self.manager.genesis_config.witnesses[0].address.parse::<Address>()?
+            } else {
+                self.manager.get_scheduled_witness(self.manager.get_slot(timestamp))
+            };
+
+            let started_at = Instant::now();
+            let txns = (0..txns_per_block)
+                .map(|i| self.next_transaction(i, timestamp, &genesis_hash))
+                .collect::<Result<Vec<_>>>()?;
+            report.generate += started_at.elapsed();
+
+            let started_at = Instant::now();
+            let header = BlockHeader {
+                raw_data: Some(block_header::Raw {
+                    number,
+                    timestamp,
+                    parent_hash: self.manager.latest_block_hash().as_bytes().to_vec(),
+                    witness_address: witness.as_bytes().to_vec(),
+                    version: constants::CURRENT_BLOCK_VERSION as i32,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let block = IndexedBlock::from_header_and_txns(header, txns);
+            report.assemble += started_at.elapsed();
+
+            let started_at = Instant::now();
+            if !self.manager.push_block(&block)? {
+                return Err(new_error(&format!("block #{} is rejected", number)));
+            }
+            report.apply += started_at.elapsed();
+
+            report.num_blocks += 1;
+            report.num_transactions += block.transactions.len();
+        }
+
+        Ok(report)
+    }
+
+    fn next_transaction(
+        &mut self,
+        index: usize,
+        block_timestamp: i64,
+        ref_block_hash: &H256,
+    ) -> Result<Transaction> {
+        self.nonce += 1;
+        let kind = self.kinds[index % self.kinds.len()];
+        let sender_idx = index % self.accounts.len();
+        let sender = &self.accounts[sender_idx];
+        let owner_address = sender.address().as_bytes().to_vec();
+
+        let (cntr_type, parameter) = match kind {
+            TransactionKind::Transfer => {
+                let receiver = &self.accounts[(sender_idx + 1) % self.accounts.len()];
+                let cntr = contract_pb::TransferContract {
+                    owner_address,
+                    to_address: receiver.address().as_bytes().to_vec(),
+                    amount: 1,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            TransactionKind::Freeze => {
+                let cntr = contract_pb::FreezeBalanceContract {
+                    owner_address,
+                    frozen_balance: 1_000_000,
+                    frozen_duration: constants::MIN_NUM_OF_FROZEN_DAYS_FOR_RESOURCE,
+                    resource: ResourceCode::Bandwidth as i32,
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            TransactionKind::Trigger => {
+                let cntr = contract_pb::TriggerSmartContract {
+                    owner_address,
+                    contract_address: self.contract_address.as_bytes().to_vec(),
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+        };
+
+        let raw = transaction::Raw {
+            ref_block_bytes: ref_block_hash.as_bytes()[6..8].to_vec(),
+            ref_block_hash: ref_block_hash.as_bytes()[8..16].to_vec(),
+            expiration: block_timestamp + 60_000,
+            // Timestamp makes every transaction unique.
+            timestamp: block_timestamp + self.nonce,
+            fee_limit: if kind == TransactionKind::Trigger {
+                100_000_000
+            } else {
+                0
+            },
+            contract: Some(transaction::Contract {
+                r#type: cntr_type as i32,
+                parameter,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let signature = sender.private().sign(&hash::raw_data_bytes(&raw))?;
+
+        Ok(Transaction {
+            raw_data: Some(raw),
+            signatures: vec![signature.as_bytes().to_vec()],
+            ..Default::default()
+        })
+    }
+}
+
+impl Drop for Benchmark<'_> {
+    fn drop(&mut self) {
+        self.manager.skip_block_signature = false;
+    }
+}
@@ -0,0 +1,477 @@
+//! Constant contract calls, i.e. TriggerConstantContract.
+//!
+//! Constant calls are executed in a throwaway state layer, under node-local limits(energy, wall-clock and memory)
+//! instead of consensus energy limits, so that expensive or endless calls can not exhaust a public node.
+//!
+//! Nested frames are executed inside the VM, out of reach of the step loop. Wall-clock and cancellation are also
+//! checked on every nested CALL, via the precompile hook, failing the call with out of energy.
+//!
+//! Results are cached for the latest block, since wallets tend to poll the same calls, i.e. `balanceOf`, again and
+//! again. Portfolio apps fetching many balances at once use batches, executed against the same state.
+//!
+//! The node does not apply blocks itself, so that API calls are served by `ConstantCallService`, a thread owning a
+//! manager on a secondary instance of state-db.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ::keys::Address;
+use chain::{IndexedBlockHeader, IndexedTransaction};
+use config::{Config, GenesisConfig};
+use log::{debug, info, warn};
+use primitive_types::{H160, H256};
+use proto2::chain::{block_header, transaction, BlockHeader, Transaction};
+use state::keys;
+use tvm::{Capture, ExitError, ExitReason, ExitSucceed};
+
+use super::executor::TransactionContext;
+use super::vm::StateBackend;
+use super::Manager;
+
+/// Check limits every N VM steps, since reading the clock is not free.
+const LIMIT_CHECK_INTERVAL: usize = 1024;
+
+/// Min interval of catching up with the primary state-db, in the service thread.
+const CATCH_UP_INTERVAL: Duration = Duration::from_secs(1);
+
+thread_local! {
+    /// Deadline and cancellation flag of the running constant call, read by `limited_precompile`.
+    static CALL_LIMITS: RefCell<Option<(Instant, Arc<AtomicBool>)>> = RefCell::new(None);
+}
+
+/// Precompile hook of constant calls, consulted by the VM on every nested CALL.
+///
+/// Fails the nested call once the deadline passes or the call is cancelled. The caller frame then sees a failed call,
+/// and the top-level step loop reports the timeout or cancellation.
+fn limited_precompile(
+    address: H160,
+    input: &[u8],
+    target_gas: Option<usize>,
+) -> Option<Result<(ExitSucceed, Vec<u8>, usize), ExitError>> {
+    let exceeded = CALL_LIMITS.with(|limits| match *limits.borrow() {
+        Some((deadline, ref cancelled)) => Instant::now() > deadline || cancelled.load(Ordering::Relaxed),
+        None => false,
+    });
+    if exceeded {
+        return Some(Err(ExitError::OutOfGas));
+    }
+    tvm::precompile::tron_precompile(address, input, target_gas)
+}
+
+/// Sets `CALL_LIMITS` of the current thread, cleared on drop.
+struct CallLimitsGuard;
+
+impl CallLimitsGuard {
+    fn new(deadline: Instant, cancelled: &Arc<AtomicBool>) -> Self {
+        CALL_LIMITS.with(|limits| *limits.borrow_mut() = Some((deadline, cancelled.clone())));
+        CallLimitsGuard
+    }
+}
+
+impl Drop for CallLimitsGuard {
+    fn drop(&mut self) {
+        CALL_LIMITS.with(|limits| *limits.borrow_mut() = None);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstantCallResult {
+    pub success: bool,
+    pub exit_reason: String,
+    pub energy_usage: i64,
+    pub result: Vec<u8>,
+}
+
+/// A call of a batch.
+#[derive(Debug, Clone)]
+pub struct ConstantCall {
+    pub caller: Address,
+    pub contract: Address,
+    pub data: Vec<u8>,
+    pub call_value: i64,
+}
+
+/// caller, contract, data, call_value
+type CallKey = (Address, Address, Vec<u8>, i64);
+
+/// Results of constant calls on the latest block, dropped on new blocks or after TTL.
+pub struct ConstantCallCache {
+    block_hash: H256,
+    entries: HashMap<CallKey, (Instant, ConstantCallResult)>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl ConstantCallCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        ConstantCallCache {
+            block_hash: H256::zero(),
+            entries: HashMap::new(),
+            capacity,
+            ttl,
+        }
+    }
+
+    fn get(&mut self, block_hash: &H256, key: &CallKey) -> Option<ConstantCallResult> {
+        if self.block_hash != *block_hash {
+            self.block_hash = *block_hash;
+            self.entries.clear();
+            return None;
+        }
+        match self.entries.get(key) {
+            Some((cached_at, result)) if cached_at.elapsed() < self.ttl => Some(result.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, block_hash: &H256, key: CallKey, result: ConstantCallResult) {
+        if self.capacity == 0 || self.block_hash != *block_hash {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries.retain(|_, (cached_at, _)| cached_at.elapsed() < ttl);
+            if self.entries.len() >= self.capacity {
+                return;
+            }
+        }
+        self.entries.insert(key, (Instant::now(), result));
+    }
+}
+
+pub struct ConstantCallExecutor<'m> {
+    manager: &'m mut Manager,
+}
+
+impl<'m> ConstantCallExecutor<'m> {
+    pub fn new<'a>(manager: &'a mut Manager) -> ConstantCallExecutor<'a> {
+        ConstantCallExecutor { manager }
+    }
+
+    /// Call a contract without changing state. `cancelled` can be set by another thread to abort the call.
+    ///
+    /// Only completed calls are cached, errors like timeout or cancellation are not.
+    pub fn call(
+        &mut self,
+        caller: Address,
+        contract: Address,
+        data: &[u8],
+        call_value: i64,
+        cancelled: &Arc<AtomicBool>,
+    ) -> Result<ConstantCallResult, String> {
+        if self.manager.state_db.must_get(&keys::ChainParameter::AllowTvm) == 0 {
+            return Err("TVM is not enabled".into());
+        }
+        let block_hash = self.manager.latest_block_hash();
+        let key = (caller, contract, data.to_vec(), call_value);
+        if let Some(result) = self.manager.constant_call_cache.get(&block_hash, &key) {
+            debug!("constant call cache hit, contract={}", contract);
+            return Ok(result);
+        }
+
+        // Executed on top of the latest block.
+        let block_header = IndexedBlockHeader::from_raw(BlockHeader {
+            raw_data: Some(block_header::Raw {
+                number: self.manager.latest_block_number() + 1,
+                timestamp: self.manager.latest_block_timestamp() + self.manager.block_interval(),
+                witness_address: vec![0u8; 21],
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let txn = IndexedTransaction::from_raw(Transaction {
+            raw_data: Some(transaction::Raw::default()),
+            ..Default::default()
+        });
+        let mut ctx = TransactionContext::new(&block_header, &txn);
+
+        self.manager.new_layer();
+        let ret = self.execute(caller, contract, data, call_value, cancelled, &mut ctx);
+        self.manager.rollback_layers(1);
+
+        if let Ok(ref result) = ret {
+            self.manager.constant_call_cache.insert(&block_hash, key, result.clone());
+        }
+        ret
+    }
+
+    /// Execute a batch of calls in order, against the same state, i.e. the latest block. Calls do not see changes of
+    /// each other, and a failed call does not stop the rest.
+    ///
+    /// Limits apply to each call. Cancellation aborts the remaining calls.
+    pub fn call_batch(
+        &mut self,
+        calls: &[ConstantCall],
+        cancelled: &Arc<AtomicBool>,
+    ) -> Result<Vec<Result<ConstantCallResult, String>>, String> {
+        let max_batch_size = self.manager.config.execution.constant_call_max_batch_size;
+        if calls.len() > max_batch_size {
+            return Err(format!("too many calls in a batch, max is {}", max_batch_size));
+        }
+
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err("constant call cancelled".into());
+            }
+            results.push(self.call(call.caller, call.contract, &call.data, call.call_value, cancelled));
+        }
+        Ok(results)
+    }
+
+    fn execute(
+        &mut self,
+        caller: Address,
+        contract: Address,
+        data: &[u8],
+        call_value: i64,
+        cancelled: &Arc<AtomicBool>,
+        ctx: &mut TransactionContext,
+    ) -> Result<ConstantCallResult, String> {
+        let limits = self.manager.config.execution.clone();
+        let manager = &mut *self.manager;
+
+        if manager
+            .state_db
+            .get(&keys::Contract(contract))
+            .map_err(|_| "db query error")?
+            .is_none()
+        {
+            return Err("contract does not exist".into());
+        }
+
+        if call_value > 0 {
+            let mut caller_acct = manager
+                .state_db
+                .get(&keys::Account(caller))
+                .map_err(|_| "db query error")?
+                .ok_or("caller account does not exist")?;
+            let mut cntr_acct = manager.state_db.must_get(&keys::Account(contract));
+            if caller_acct.adjust_balance(-call_value).is_err() {
+                return Err("insufficient balance".into());
+            }
+            cntr_acct.adjust_balance(call_value).unwrap();
+            manager.state_db.put_key(keys::Account(caller), caller_acct).unwrap();
+            manager.state_db.put_key(keys::Account(contract), cntr_acct).unwrap();
+        }
+
+        let code = manager
+            .state_db
+            .get(&keys::ContractCode(contract))
+            .map_err(|_| "db query error")?
+            .unwrap_or_default();
+        let code = Rc::new(code);
+        let data = Rc::new(data.to_vec());
+
+        let energy_limit = limits.constant_call_energy_limit as usize;
+        let timeout = Duration::from_millis(limits.constant_call_timeout);
+
+        let backend = StateBackend::new(caller, manager, ctx);
+        let config = tvm::Config::odyssey_3_7();
+        let mut executor = tvm::StackExecutor::new_with_precompile(&backend, energy_limit, &config, limited_precompile);
+
+        let vm_ctx = tvm::Context {
+            address: H160::from_slice(contract.as_tvm_bytes()),
+            caller: H160::from_slice(caller.as_tvm_bytes()),
+            call_value: call_value.into(),
+            call_token_id: 0.into(),
+            call_token_value: 0.into(),
+        };
+        let mut rt = tvm::Runtime::new(code, data, vm_ctx, &config);
+
+        // NOTE: Limits are checked between steps of the top-level frame, and wall-clock and cancellation on every
+        // nested CALL. Loops inside a nested frame without further calls, and memory of nested frames, are bounded by
+        // the energy limit only.
+        let started_at = Instant::now();
+        let _guard = CallLimitsGuard::new(started_at + timeout, cancelled);
+        let check_limits = |memory_size: usize| -> Result<(), String> {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err("constant call cancelled".into());
+            }
+            if started_at.elapsed() > timeout {
+                return Err(format!("constant call timeout, exceeds {}ms", limits.constant_call_timeout));
+            }
+            if memory_size > limits.constant_call_max_memory {
+                return Err(format!(
+                    "constant call memory limit exceeded, exceeds {} bytes",
+                    limits.constant_call_max_memory
+                ));
+            }
+            Ok(())
+        };
+        let mut steps = 0_usize;
+        let exit_reason = loop {
+            match rt.step(&mut executor) {
+                Ok(()) => {}
+                Err(Capture::Exit(reason)) => break reason,
+                Err(Capture::Trap(_)) => unreachable!("StackExecutor handles calls internally; qed"),
+            }
+
+            steps += 1;
+            if steps % LIMIT_CHECK_INTERVAL == 0 {
+                check_limits(rt.machine().memory().len())?;
+            }
+        };
+        // A nested call failed by limits might let the top-level frame exit normally.
+        check_limits(rt.machine().memory().len())?;
+        debug!(
+            "constant call exit => {:?}, {} steps in {:?}",
+            exit_reason,
+            steps,
+            started_at.elapsed()
+        );
+
+        Ok(ConstantCallResult {
+            success: matches!(exit_reason, ExitReason::Succeed(_)),
+            exit_reason: format!("{:?}", exit_reason),
+            energy_usage: executor.used_gas() as i64,
+            result: rt.machine().return_value(),
+        })
+    }
+}
+
+type BatchResult = Result<Vec<Result<ConstantCallResult, String>>, String>;
+
+enum ConstantCallPayload {
+    Call(ConstantCall, Sender<Result<ConstantCallResult, String>>),
+    Batch(Vec<ConstantCall>, Sender<BatchResult>),
+}
+
+struct ConstantCallRequest {
+    payload: ConstantCallPayload,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Constant calls for API servers, executed one at a time by a dedicated thread.
+pub struct ConstantCallService {
+    sender: Mutex<Sender<ConstantCallRequest>>,
+    timeout: Duration,
+}
+
+impl ConstantCallService {
+    pub fn new(config: &Config, genesis_config: &GenesisConfig) -> Self {
+        let (sender, receiver) = mpsc::channel::<ConstantCallRequest>();
+
+        let thread_config = config.clone();
+        let thread_genesis_config = genesis_config.clone();
+        thread::Builder::new()
+            .name("constant-call".into())
+            .spawn(move || {
+                let mut manager = Manager::new_secondary(&thread_config, &thread_genesis_config);
+                let mut caught_up_at = Instant::now();
+                // Ends when the service is dropped along with the context.
+                for req in receiver {
+                    // Given up by the caller while queued.
+                    if req.cancelled.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    if caught_up_at.elapsed() > CATCH_UP_INTERVAL {
+                        if let Err(e) = manager.catch_up_with_primary() {
+                            warn!("constant call state-db catch up failed: {}", e);
+                        }
+                        caught_up_at = Instant::now();
+                    }
+                    let mut executor = ConstantCallExecutor::new(&mut manager);
+                    match req.payload {
+                        ConstantCallPayload::Call(call, reply) => {
+                            let ret = executor.call(
+                                call.caller,
+                                call.contract,
+                                &call.data,
+                                call.call_value,
+                                &req.cancelled,
+                            );
+                            let _ = reply.send(ret);
+                        }
+                        ConstantCallPayload::Batch(calls, reply) => {
+                            let _ = reply.send(executor.call_batch(&calls, &req.cancelled));
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn constant call thread");
+        info!("constant call service started");
+
+        ConstantCallService {
+            sender: Mutex::new(sender),
+            timeout: Duration::from_millis(config.execution.constant_call_timeout),
+        }
+    }
+
+    /// Call a contract against the latest state, waiting for the result.
+    ///
+    /// Calls are queued, the wait is bounded by twice the timeout. A call given up by the wait is cancelled.
+    pub fn call(&self, call: ConstantCall) -> Result<ConstantCallResult, String> {
+        let (reply, receiver) = mpsc::channel();
+        self.request(ConstantCallPayload::Call(call, reply), receiver, self.timeout * 2)?
+    }
+
+    /// Execute a batch of calls against the same state, waiting for all results.
+    ///
+    /// The wait is bounded by the timeout of each call, plus one for queueing.
+    pub fn call_batch(&self, calls: Vec<ConstantCall>) -> BatchResult {
+        let wait = self.timeout * (calls.len() as u32 + 1);
+        let (reply, receiver) = mpsc::channel();
+        self.request(ConstantCallPayload::Batch(calls, reply), receiver, wait)?
+    }
+
+    fn request<T>(&self, payload: ConstantCallPayload, receiver: Receiver<T>, wait: Duration) -> Result<T, String> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let req = ConstantCallRequest {
+            payload,
+            cancelled: cancelled.clone(),
+        };
+        self.sender
+            .lock()
+            .unwrap()
+            .send(req)
+            .map_err(|_| "constant call service is down")?;
+        match receiver.recv_timeout(wait) {
+            Ok(ret) => Ok(ret),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                cancelled.store(true, Ordering::Relaxed);
+                Err("constant call timeout, too many queued calls".into())
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err("constant call service is down".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_call_cache() {
+        let mut cache = ConstantCallCache::new(1, Duration::from_secs(60));
+        let block1 = H256::from_low_u64_be(1);
+        let block2 = H256::from_low_u64_be(2);
+        let key = |data: u8| (Address::default(), Address::default(), vec![data], 0);
+        let result = ConstantCallResult {
+            success: true,
+            exit_reason: "Succeed(Returned)".into(),
+            energy_usage: 100,
+            result: vec![1],
+        };
+
+        assert!(cache.get(&block1, &key(0)).is_none());
+        cache.insert(&block1, key(0), result.clone());
+        assert_eq!(cache.get(&block1, &key(0)).unwrap().energy_usage, 100);
+        assert!(cache.get(&block1, &key(1)).is_none());
+
+        // full
+        cache.insert(&block1, key(1), result.clone());
+        assert!(cache.get(&block1, &key(1)).is_none());
+
+        // new block
+        assert!(cache.get(&block2, &key(0)).is_none());
+        cache.insert(&block2, key(1), result);
+        assert!(cache.get(&block2, &key(1)).is_some());
+    }
+}
@@ -0,0 +1,590 @@
+//! Chain events, emitted while applying blocks.
+//!
+//! Events are written to the log of this module, and can be filtered with `log-filters` in config.
+//! They can also be POSTed to a webhook, see `[event]` in config.
+//!
+//! Events of a block are delivered after the block is applied, as `Unconfirmed`, and again as `Solidified` once
+//! the block is solidified. Events of a reverted block are delivered as `Removed`, if they were ever delivered.
+//! With `solidified-only`, events are delivered only once solidified, and are never removed.
+//!
+//! Without the `events` feature, events are dropped as they are emitted.
+
+use std::collections::{HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use ::keys::Address;
+use chain::IndexedTransaction;
+use config::EventConfig;
+use log::{error, info, warn};
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::{transaction::Contract, ContractType};
+use proto2::contract as contract_pb;
+use proto2::state::TransactionReceipt;
+use serde_json::json;
+
+use super::actuators::BuiltinContractExt;
+
+/// keccak256 of `Transfer(address,address,uint256)`, the TRC20 transfer event.
+const TRC20_TRANSFER_TOPIC: &str = "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// A resource delegation expired and was swept from the delegation index.
+    DelegationExpired {
+        block_number: i64,
+        from: Address,
+        to: Address,
+        amount_for_bandwidth: i64,
+        expiration_timestamp_for_bandwidth: i64,
+        amount_for_energy: i64,
+        expiration_timestamp_for_energy: i64,
+    },
+    /// A watched address is involved in a transaction.
+    AddressWatched {
+        block_number: i64,
+        transaction_id: H256,
+        contract_type: ContractType,
+        address: Address,
+        role: AddressRole,
+    },
+    /// A scheduled slot of this node's witness was missed.
+    SlotMissed {
+        block_number: i64,
+        witness: Address,
+        slot_timestamp: i64,
+    },
+    /// A witness is found by a penalty rule at maintenance.
+    WitnessPenalized {
+        block_number: i64,
+        witness: Address,
+        rule: &'static str,
+        reason: String,
+    },
+}
+
+impl ChainEvent {
+    pub fn to_json(&self) -> serde_json::Value {
+        match *self {
+            ChainEvent::DelegationExpired {
+                block_number,
+                ref from,
+                ref to,
+                amount_for_bandwidth,
+                expiration_timestamp_for_bandwidth,
+                amount_for_energy,
+                expiration_timestamp_for_energy,
+            } => json!({
+                "event": "DelegationExpired",
+                "blockNumber": block_number,
+                "from": from.to_string(),
+                "to": to.to_string(),
+                "amountForBandwidth": amount_for_bandwidth,
+                "expirationTimestampForBandwidth": expiration_timestamp_for_bandwidth,
+                "amountForEnergy": amount_for_energy,
+                "expirationTimestampForEnergy": expiration_timestamp_for_energy,
+            }),
+            ChainEvent::AddressWatched {
+                block_number,
+                ref transaction_id,
+                contract_type,
+                ref address,
+                role,
+            } => json!({
+                "event": "AddressWatched",
+                "blockNumber": block_number,
+                "transactionId": hex::encode(transaction_id.as_bytes()),
+                "contractType": format!("{:?}", contract_type),
+                "address": address.to_string(),
+                "role": format!("{:?}", role),
+            }),
+            ChainEvent::SlotMissed {
+                block_number,
+                ref witness,
+                slot_timestamp,
+            } => json!({
+                "event": "SlotMissed",
+                "blockNumber": block_number,
+                "witness": witness.to_string(),
+                "slotTimestamp": slot_timestamp,
+            }),
+            ChainEvent::WitnessPenalized {
+                block_number,
+                ref witness,
+                rule,
+                ref reason,
+            } => json!({
+                "event": "WitnessPenalized",
+                "blockNumber": block_number,
+                "witness": witness.to_string(),
+                "rule": rule,
+                "reason": reason,
+            }),
+        }
+    }
+}
+
+/// Confirmation level of a delivered event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    /// The block is applied, but might still be reverted.
+    Unconfirmed,
+    /// The block is confirmed by enough witnesses, and is irreversible.
+    Solidified,
+    /// The block is reverted, like `removed: true` of `eth_getLogs`.
+    Removed,
+}
+
+/// An event as delivered, with its block and confirmation level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRecord {
+    pub block_number: i64,
+    pub block_hash: H256,
+    pub confirmation: Confirmation,
+    pub event: ChainEvent,
+}
+
+impl EventRecord {
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = self.event.to_json();
+        json["blockHash"] = hex::encode(self.block_hash.as_bytes()).into();
+        json["confirmation"] = format!("{:?}", self.confirmation).into();
+        json["removed"] = (self.confirmation == Confirmation::Removed).into();
+        json
+    }
+}
+
+/// Buffers events of blocks until they can be delivered at the configured confirmation level.
+pub struct EventDispatcher {
+    solidified_only: bool,
+    /// Events of the block being applied.
+    current: Vec<ChainEvent>,
+    /// Applied blocks that are not solidified yet, in block order.
+    pending: VecDeque<(i64, H256, Vec<ChainEvent>)>,
+    webhook: Option<mpsc::SyncSender<String>>,
+    /// Events dropped since the webhook queue is full.
+    webhook_dropped: u64,
+}
+
+impl EventDispatcher {
+    pub fn new(config: &EventConfig) -> Self {
+        let webhook = if config.webhook.is_empty() {
+            None
+        } else if !cfg!(feature = "events") {
+            warn!("event webhook is ignored, build with the `events` feature to deliver events");
+            None
+        } else {
+            match spawn_webhook(&config.webhook) {
+                Ok(sender) => Some(sender),
+                Err(e) => {
+                    error!("event webhook is disabled: {}", e);
+                    None
+                }
+            }
+        };
+        EventDispatcher {
+            solidified_only: config.solidified_only,
+            current: vec![],
+            pending: VecDeque::new(),
+            webhook,
+            webhook_dropped: 0,
+        }
+    }
+
+    /// Emit an event of the block being applied.
+    pub fn emit(&mut self, event: ChainEvent) {
+        if cfg!(feature = "events") {
+            self.current.push(event);
+        }
+    }
+
+    /// Drop events of the block being applied, when the block fails.
+    pub fn discard_block(&mut self) {
+        self.current.clear();
+    }
+
+    /// The block being applied is committed.
+    pub fn apply_block(&mut self, block_number: i64, block_hash: H256) -> Vec<EventRecord> {
+        let events = std::mem::replace(&mut self.current, vec![]);
+        let delivered = if self.solidified_only {
+            vec![]
+        } else {
+            self.deliver(block_number, block_hash, &events, Confirmation::Unconfirmed)
+        };
+        self.pending.push_back((block_number, block_hash, events));
+        delivered
+    }
+
+    /// Deliver events of blocks up to the solid block.
+    pub fn solidify(&mut self, solid_block_number: i64) -> Vec<EventRecord> {
+        let mut delivered = vec![];
+        while self.pending.front().map(|blk| blk.0 <= solid_block_number).unwrap_or(false) {
+            let (block_number, block_hash, events) = self.pending.pop_front().unwrap();
+            delivered.extend(self.deliver(block_number, block_hash, &events, Confirmation::Solidified));
+        }
+        delivered
+    }
+
+    /// Revert blocks from `block_number`, on chain reorganization. Solidified blocks can not be reverted.
+    ///
+    /// Must only be called after the blocks are unwound from state-db, never for a rejected block.
+    pub fn revert_blocks_from(&mut self, block_number: i64) -> Vec<EventRecord> {
+        let mut delivered = vec![];
+        while self.pending.back().map(|blk| blk.0 >= block_number).unwrap_or(false) {
+            let (block_number, block_hash, events) = self.pending.pop_back().unwrap();
+            if !self.solidified_only {
+                delivered.extend(self.deliver(block_number, block_hash, &events, Confirmation::Removed));
+            }
+        }
+        delivered
+    }
+
+    fn deliver(
+        &mut self,
+        block_number: i64,
+        block_hash: H256,
+        events: &[ChainEvent],
+        confirmation: Confirmation,
+    ) -> Vec<EventRecord> {
+        let mut records = Vec::with_capacity(events.len());
+        for event in events {
+            let record = EventRecord {
+                block_number,
+                block_hash,
+                confirmation,
+                event: event.clone(),
+            };
+            info!("{:?} {:?}", confirmation, event);
+            if let Some(ref webhook) = self.webhook {
+                if let Err(mpsc::TrySendError::Full(_)) = webhook.try_send(record.to_json().to_string()) {
+                    self.webhook_dropped += 1;
+                    // Logged at 1, 2, 4, 8, ... drops, a stalled webhook must not flood the log.
+                    if self.webhook_dropped.is_power_of_two() {
+                        warn!("event webhook is too slow, {} events dropped", self.webhook_dropped);
+                    }
+                }
+            }
+            records.push(record);
+        }
+        records
+    }
+}
+
+/// Role of an address in a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressRole {
+    /// Owner of the contract, i.e. the sender.
+    Owner,
+    /// Receiver of a TRX or TRC10 transfer.
+    Receiver,
+    /// The smart contract called, created or updated.
+    Contract,
+    /// Receiver of delegated resource.
+    DelegationReceiver,
+    /// Receiver of an internal transaction, i.e. TRX or TRC10 sent by a contract.
+    InternalReceiver,
+    /// Receiver of a TRC20 `Transfer` event.
+    TokenReceiver,
+}
+
+/// `owner_address` of builtin contracts not listed in `involved_addresses`, which is always the first field.
+#[derive(Clone, PartialEq, Message)]
+struct ContractOwner {
+    #[prost(bytes, tag = "1")]
+    owner_address: Vec<u8>,
+}
+
+fn decode<T: BuiltinContractExt>(cntr: &Contract) -> Option<T> {
+    cntr.parameter.as_ref().and_then(T::from_any)
+}
+
+/// Addresses involved in an executed transaction, with their roles.
+pub fn involved_addresses(cntr: &Contract, receipt: &TransactionReceipt) -> Vec<(Address, AddressRole)> {
+    use self::AddressRole::*;
+
+    let involved = match ContractType::from_i32(cntr.r#type).unwrap_or_default() {
+        ContractType::TransferContract => decode::<contract_pb::TransferContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.to_address, Receiver)]),
+        ContractType::TransferAssetContract => decode::<contract_pb::TransferAssetContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.to_address, Receiver)]),
+        ContractType::ParticipateAssetIssueContract => decode::<contract_pb::ParticipateAssetIssueContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.to_address, Receiver)]),
+        ContractType::FreezeBalanceContract => decode::<contract_pb::FreezeBalanceContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.receiver_address, DelegationReceiver)]),
+        ContractType::UnfreezeBalanceContract => decode::<contract_pb::UnfreezeBalanceContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.receiver_address, DelegationReceiver)]),
+        ContractType::DelegateResourceContract => decode::<contract_pb::DelegateResourceContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.receiver_address, DelegationReceiver)]),
+        ContractType::UnDelegateResourceContract => decode::<contract_pb::UnDelegateResourceContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.receiver_address, DelegationReceiver)]),
+        ContractType::CreateSmartContract => decode::<contract_pb::CreateSmartContract>(cntr).map(|cntr| {
+            vec![
+                (cntr.owner_address, Owner),
+                (receipt.vm_created_contract_address.clone(), Contract),
+            ]
+        }),
+        ContractType::TriggerSmartContract => decode::<contract_pb::TriggerSmartContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.contract_address, Contract)]),
+        ContractType::UpdateSettingContract => decode::<contract_pb::UpdateSettingContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.contract_address, Contract)]),
+        ContractType::UpdateEnergyLimitContract => decode::<contract_pb::UpdateEnergyLimitContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.contract_address, Contract)]),
+        ContractType::ClearAbiContract => decode::<contract_pb::ClearAbiContract>(cntr)
+            .map(|cntr| vec![(cntr.owner_address, Owner), (cntr.contract_address, Contract)]),
+        _ => None,
+    };
+    let involved = involved.unwrap_or_else(|| {
+        let owner = cntr
+            .parameter
+            .as_ref()
+            .and_then(|param| ContractOwner::decode(&param.value[..]).ok())
+            .map(|owner| owner.owner_address)
+            .unwrap_or_default();
+        vec![(owner, Owner)]
+    });
+
+    let internal_receivers = receipt
+        .vm_internal_transactions
+        .iter()
+        .map(|itxn| (itxn.to_address.clone(), InternalReceiver));
+    let transfer_topic = hex::decode(TRC20_TRANSFER_TOPIC).unwrap();
+    let token_receivers = receipt
+        .vm_logs
+        .iter()
+        .filter(|log| log.topics.len() == 3 && log.topics[0] == transfer_topic && log.topics[2].len() == 32)
+        .map(|log| (Address::from_tvm_bytes(&log.topics[2][12..]).as_bytes().to_vec(), TokenReceiver));
+
+    involved
+        .into_iter()
+        .chain(internal_receivers)
+        .chain(token_receivers)
+        .filter_map(|(raw, role)| Address::try_from(raw).ok().map(|addr| (addr, role)))
+        .collect()
+}
+
+/// Watch list of addresses, see `[event]` in config.
+pub struct AddressWatcher {
+    addresses: HashSet<Address>,
+}
+
+impl AddressWatcher {
+    pub fn new(config: &EventConfig) -> Self {
+        let addresses = config
+            .watch_addresses
+            .iter()
+            .map(|addr| addr.parse().expect("invalid address in event.watch-addresses"))
+            .collect();
+        AddressWatcher { addresses }
+    }
+
+    /// `AddressWatched` events for watched addresses involved in an executed transaction.
+    pub fn watch_transaction(
+        &self,
+        block_number: i64,
+        txn: &IndexedTransaction,
+        receipt: &TransactionReceipt,
+    ) -> Vec<ChainEvent> {
+        if self.addresses.is_empty() {
+            return vec![];
+        }
+        let cntr = txn.raw.raw_data.as_ref().unwrap().contract.as_ref().unwrap();
+        involved_addresses(cntr, receipt)
+            .into_iter()
+            .filter(|(address, _)| self.addresses.contains(address))
+            .map(|(address, role)| ChainEvent::AddressWatched {
+                block_number,
+                transaction_id: txn.hash,
+                contract_type: ContractType::from_i32(cntr.r#type).unwrap_or_default(),
+                address,
+                role,
+            })
+            .collect()
+    }
+}
+
+/// Max event bodies queued for the webhook. Events are dropped when it's full.
+const WEBHOOK_QUEUE_SIZE: usize = 10_000;
+
+/// POST event bodies to the webhook in a background thread, so that block applying is never blocked.
+fn spawn_webhook(url: &str) -> Result<mpsc::SyncSender<String>, String> {
+    let url = reqwest::Url::parse(url).map_err(|e| format!("invalid url {:?}: {}", url, e))?;
+    let mut rt = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("can not create runtime: {}", e))?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| format!("can not create client: {}", e))?;
+
+    let (sender, receiver) = mpsc::sync_channel::<String>(WEBHOOK_QUEUE_SIZE);
+    thread::Builder::new()
+        .name("event-webhook".into())
+        .spawn(move || {
+            for body in receiver {
+                let req = client
+                    .post(url.clone())
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body);
+                if let Err(e) = rt.block_on(async { req.send().await?.error_for_status() }) {
+                    warn!("event webhook error: {}", e);
+                }
+            }
+        })
+        .map_err(|e| format!("can not spawn thread: {}", e))?;
+    Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto2::state::TransactionLog;
+
+    fn delegation_expired(block_number: i64) -> ChainEvent {
+        ChainEvent::DelegationExpired {
+            block_number,
+            from: Address::default(),
+            to: Address::default(),
+            amount_for_bandwidth: 0,
+            expiration_timestamp_for_bandwidth: 0,
+            amount_for_energy: 0,
+            expiration_timestamp_for_energy: 0,
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "events")]
+    fn test_event_dispatcher() {
+        let mut dispatcher = EventDispatcher::new(&EventConfig::default());
+        for num in 1..=3 {
+            dispatcher.emit(delegation_expired(num));
+            let delivered = dispatcher.apply_block(num, H256::repeat_byte(num as u8));
+            assert_eq!(delivered.len(), 1);
+            assert_eq!(delivered[0].confirmation, Confirmation::Unconfirmed);
+        }
+
+        let delivered = dispatcher.solidify(1);
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].confirmation, Confirmation::Solidified);
+
+        let delivered = dispatcher.revert_blocks_from(2);
+        assert_eq!(delivered.len(), 2);
+        assert!(delivered.iter().all(|rec| rec.confirmation == Confirmation::Removed));
+        assert_eq!(delivered[0].block_number, 3);
+        assert!(dispatcher.solidify(3).is_empty());
+
+        // failed block
+        dispatcher.emit(delegation_expired(2));
+        dispatcher.discard_block();
+        assert!(dispatcher.apply_block(2, H256::zero()).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "events")]
+    fn test_event_dispatcher_solidified_only() {
+        let mut dispatcher = EventDispatcher::new(&EventConfig {
+            solidified_only: true,
+            ..Default::default()
+        });
+        dispatcher.emit(delegation_expired(1));
+        assert!(dispatcher.apply_block(1, H256::zero()).is_empty());
+        dispatcher.emit(delegation_expired(2));
+        assert!(dispatcher.apply_block(2, H256::zero()).is_empty());
+
+        assert!(dispatcher.revert_blocks_from(2).is_empty());
+        let delivered = dispatcher.solidify(2);
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].event, delegation_expired(1));
+        assert_eq!(delivered[0].confirmation, Confirmation::Solidified);
+    }
+
+    #[test]
+    #[cfg(feature = "events")]
+    fn test_bad_block_keeps_head_events() {
+        use chain::IndexedBlock;
+        use proto2::chain::{block_header, BlockHeader};
+
+        use crate::manager::testing::TestManager;
+
+        let mut manager = TestManager::new();
+        manager.skip_block_signature = true;
+        let head = manager.latest_block_number();
+        manager.events.emit(delegation_expired(head));
+        let head_hash = manager.latest_block_hash();
+        manager.events.apply_block(head, head_hash);
+
+        // A block of the next number, not linked to the head.
+        let header = BlockHeader {
+            raw_data: Some(block_header::Raw {
+                number: head + 1,
+                parent_hash: vec![0xff; 32],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let block = IndexedBlock::from_header_and_txns(header, vec![]);
+        assert!(manager.push_block(&block).is_err());
+
+        let removed = manager.events.revert_blocks_from(head);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].event, delegation_expired(head));
+    }
+
+    #[test]
+    #[cfg(feature = "events")]
+    fn test_event_webhook_queue_full() {
+        let mut dispatcher = EventDispatcher::new(&EventConfig::default());
+        let (sender, receiver) = mpsc::sync_channel(1);
+        dispatcher.webhook = Some(sender);
+
+        for num in 1..=3 {
+            dispatcher.emit(delegation_expired(num));
+            assert_eq!(dispatcher.apply_block(num, H256::zero()).len(), 1);
+        }
+        assert_eq!(dispatcher.webhook_dropped, 2);
+        assert_eq!(receiver.try_iter().count(), 1);
+
+        assert!(spawn_webhook("127.0.0.1:8080").is_err());
+    }
+
+    #[test]
+    fn test_involved_addresses() {
+        let owner: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let contract: Address = "TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t".parse().unwrap();
+        let receiver: Address = "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs".parse().unwrap();
+
+        let trigger = contract_pb::TriggerSmartContract {
+            owner_address: owner.as_bytes().to_vec(),
+            contract_address: contract.as_bytes().to_vec(),
+            ..Default::default()
+        };
+        let cntr = Contract {
+            r#type: ContractType::TriggerSmartContract as i32,
+            parameter: trigger.to_any(),
+            ..Default::default()
+        };
+        let mut receiver_topic = vec![0u8; 12];
+        receiver_topic.extend_from_slice(receiver.as_tvm_bytes());
+        let receipt = TransactionReceipt {
+            vm_logs: vec![TransactionLog {
+                address: contract.as_tvm_bytes().to_vec(),
+                topics: vec![hex::decode(TRC20_TRANSFER_TOPIC).unwrap(), vec![0u8; 32], receiver_topic],
+                data: vec![0u8; 32],
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            involved_addresses(&cntr, &receipt),
+            vec![
+                (owner, AddressRole::Owner),
+                (contract, AddressRole::Contract),
+                (receiver, AddressRole::TokenReceiver),
+            ]
+        );
+    }
+}
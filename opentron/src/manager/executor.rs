@@ -1,8 +1,9 @@
 //! Transaction executor.
 
+use std::convert::TryFrom;
 use std::str;
 
-use ::keys::b58encode_check;
+use ::keys::{b58encode_check, Address};
 use chain::{IndexedBlock, IndexedBlockHeader, IndexedTransaction};
 use log::{debug, error};
 use primitive_types::H256;
@@ -26,6 +27,7 @@ pub struct TransactionContext<'a> {
     // Handled by actuator.
     pub contract_fee: i64,
     pub multisig_fee: i64,
+    pub memo_fee: i64,
     // NOTE: Account creation fee will overwrite bandwidth fee.
     // pub account_creation_fee: i64,
     // Set by actuator.valide().
@@ -40,6 +42,8 @@ pub struct TransactionContext<'a> {
     pub energy_fee: i64,
     pub result: Vec<u8>,
     pub logs: Vec<TransactionLog>,
+    // Execution results of contracts, in order. Always one, see `MAX_NUM_OF_CONTRACTS_PER_TRANSACTION`.
+    pub contract_results: Vec<TransactionResult>,
 }
 
 impl<'a> TransactionContext<'a> {
@@ -54,6 +58,7 @@ impl<'a> TransactionContext<'a> {
             bandwidth_fee: 0,
             contract_fee: 0,
             multisig_fee: 0,
+            memo_fee: 0,
             new_account_created: false,
             withdrawal_amount: 0,
             unfrozen_amount: 0,
@@ -66,8 +71,21 @@ impl<'a> TransactionContext<'a> {
             energy_fee: 0,
             result: vec![],
             logs: vec![],
+            contract_results: vec![],
         }
     }
+
+    /// Record the execution result of the current contract, and check it against the one in the block, if any.
+    pub fn record_contract_result(&mut self, exec_result: TransactionResult, expected: &[TransactionResult]) -> bool {
+        let passed = check_transaction_result(&exec_result, &expected.get(self.contract_results.len()));
+        self.contract_results.push(exec_result);
+        passed
+    }
+
+    /// Total fee burnt from the caller, in SUN. Same as `fee` of java-tron's TransactionInfo.
+    pub fn total_fee(&self) -> i64 {
+        self.bandwidth_fee + self.contract_fee + self.multisig_fee + self.memo_fee + self.energy_fee
+    }
 }
 
 impl From<TransactionContext<'_>> for TransactionReceipt {
@@ -79,17 +97,24 @@ impl From<TransactionContext<'_>> for TransactionReceipt {
             block_number: ctx.block_header.number(),
             block_timestamp: ctx.block_header.timestamp(),
 
+            fee: ctx.total_fee(),
             resource_receipt: Some(ResourceReceipt {
                 bandwidth_usage: ctx.bandwidth_usage,
                 bandwidth_fee: ctx.bandwidth_fee,
                 contract_fee: ctx.contract_fee,
+                multisig_fee: ctx.multisig_fee,
+                memo_fee: ctx.memo_fee,
                 ..Default::default()
             }),
+            withdrawal_amount: ctx.withdrawal_amount,
+            unfrozen_amount: ctx.unfrozen_amount,
             ..Default::default()
         };
 
         // TODO: distinguish by builtin contract type
         if ctx.energy_limit > 0 {
+            // `energy` is the total energy used, `energy_usage` the part paid by frozen energy of the caller,
+            // `origin_energy_usage` the part paid by the contract origin.
             receipt.resource_receipt.as_mut().map(|r| {
                 r.energy = ctx.energy;
                 r.energy_usage = ctx.energy_usage;
@@ -111,6 +136,7 @@ impl ::std::fmt::Debug for TransactionContext<'_> {
             .field("bandwidth_fee", &self.bandwidth_fee)
             .field("contract_fee", &self.contract_fee)
             .field("multisig_fee", &self.multisig_fee)
+            .field("memo_fee", &self.memo_fee)
             .field("withdrawal_amount", &self.withdrawal_amount)
             .field("unfrozen_amount", &self.unfrozen_amount)
             .field("new_account_created", &self.new_account_created);
@@ -180,7 +206,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -204,7 +230,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -223,7 +249,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -241,7 +267,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -259,7 +285,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -275,7 +301,8 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
-                check_transaction_result(&cntr.execute(self.manager, &mut ctx)?, &maybe_result);
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -295,7 +322,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -314,7 +341,125 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
+
+                debug!("context => {:?}", ctx);
+                Ok(ctx.into())
+            }
+            ContractType::FreezeBalanceV2Contract => {
+                let cntr = contract_pb::FreezeBalanceV2Contract::from_any(cntr.parameter.as_ref().unwrap()).unwrap();
+
+                debug!(
+                    "=> Freeze Resource V2 {} amount={} resource={:?}",
+                    b58encode_check(cntr.owner_address()),
+                    cntr.frozen_balance,
+                    ResourceCode::from_i32(cntr.resource)
+                );
+
+                let mut ctx = TransactionContext::new(&block.header, &txn);
+                cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                cntr.validate(self.manager, &mut ctx)?;
+                BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
+
+                debug!("context => {:?}", ctx);
+                Ok(ctx.into())
+            }
+            ContractType::UnfreezeBalanceV2Contract => {
+                let cntr =
+                    contract_pb::UnfreezeBalanceV2Contract::from_any(cntr.parameter.as_ref().unwrap()).unwrap();
+
+                debug!(
+                    "=> Unfreeze Resource V2 {} amount={} resource={:?}",
+                    b58encode_check(cntr.owner_address()),
+                    cntr.unfreeze_balance,
+                    ResourceCode::from_i32(cntr.resource)
+                );
+
+                let mut ctx = TransactionContext::new(&block.header, &txn);
+                cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                cntr.validate(self.manager, &mut ctx)?;
+                BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
+
+                debug!("context => {:?}", ctx);
+                Ok(ctx.into())
+            }
+            ContractType::WithdrawExpireUnfreezeContract => {
+                let cntr =
+                    contract_pb::WithdrawExpireUnfreezeContract::from_any(cntr.parameter.as_ref().unwrap()).unwrap();
+
+                debug!("=> Withdraw Expire Unfreeze {}", b58encode_check(cntr.owner_address()));
+
+                let mut ctx = TransactionContext::new(&block.header, &txn);
+                cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                cntr.validate(self.manager, &mut ctx)?;
+                BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
+
+                debug!("context => {:?}", ctx);
+                Ok(ctx.into())
+            }
+            ContractType::DelegateResourceContract => {
+                let cntr = contract_pb::DelegateResourceContract::from_any(cntr.parameter.as_ref().unwrap()).unwrap();
+
+                debug!(
+                    "=> Delegate Resource {} => {} amount={} resource={:?} lock={} lock_period={}",
+                    b58encode_check(cntr.owner_address()),
+                    b58encode_check(&cntr.receiver_address),
+                    cntr.balance,
+                    ResourceCode::from_i32(cntr.resource),
+                    cntr.lock,
+                    cntr.lock_period
+                );
+
+                let mut ctx = TransactionContext::new(&block.header, &txn);
+                cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                cntr.validate(self.manager, &mut ctx)?;
+                BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
+
+                debug!("context => {:?}", ctx);
+                Ok(ctx.into())
+            }
+            ContractType::UnDelegateResourceContract => {
+                let cntr =
+                    contract_pb::UnDelegateResourceContract::from_any(cntr.parameter.as_ref().unwrap()).unwrap();
+
+                debug!(
+                    "=> UnDelegate Resource {} => {} amount={} resource={:?}",
+                    b58encode_check(cntr.owner_address()),
+                    b58encode_check(&cntr.receiver_address),
+                    cntr.balance,
+                    ResourceCode::from_i32(cntr.resource)
+                );
+
+                let mut ctx = TransactionContext::new(&block.header, &txn);
+                cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                cntr.validate(self.manager, &mut ctx)?;
+                BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
+
+                debug!("context => {:?}", ctx);
+                Ok(ctx.into())
+            }
+            ContractType::CancelAllUnfreezeV2Contract => {
+                let cntr =
+                    contract_pb::CancelAllUnfreezeV2Contract::from_any(cntr.parameter.as_ref().unwrap()).unwrap();
+
+                debug!("=> Cancel All Unfreeze V2 {}", b58encode_check(cntr.owner_address()));
+
+                let mut ctx = TransactionContext::new(&block.header, &txn);
+                cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                cntr.validate(self.manager, &mut ctx)?;
+                BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -336,7 +481,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -351,10 +496,11 @@ impl<'m> TransactionExecutor<'m> {
 
                 let mut ctx = TransactionContext::new(&block.header, &txn);
                 cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                self.check_chain_policy(cntr_type, cntr.owner_address())?;
                 cntr.validate(self.manager, &mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 // TODO: Fill TransactionReceipt with newly created asset token_id.
@@ -369,7 +515,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -387,7 +533,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -407,7 +553,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -428,7 +574,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -446,7 +592,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -466,7 +612,7 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate(self.manager, &mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
-                check_transaction_result(&exec_result, &maybe_result);
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -484,7 +630,8 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
-                check_transaction_result(&cntr.execute(self.manager, &mut ctx)?, &maybe_result);
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -498,7 +645,8 @@ impl<'m> TransactionExecutor<'m> {
                 cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
                 cntr.validate(self.manager, &mut ctx)?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
-                check_transaction_result(&cntr.execute(self.manager, &mut ctx)?, &maybe_result);
+                let exec_result = cntr.execute(self.manager, &mut ctx)?;
+                ctx.record_contract_result(exec_result, &txn.raw.result);
 
                 debug!("context => {:?}", ctx);
                 Ok(ctx.into())
@@ -517,12 +665,12 @@ impl<'m> TransactionExecutor<'m> {
                 let mut ctx = TransactionContext::new(&block.header, &txn);
 
                 cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                self.check_chain_policy(cntr_type, cntr.owner_address())?;
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 cntr.validate(self.manager, &mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
                 // NOTE: vm must be strictly checked.
-                if !check_transaction_result(&exec_result, &maybe_result) {
-                    debug!("result => {:?}", exec_result);
+                if !ctx.record_contract_result(exec_result, &txn.raw.result) {
                     return Err("result check not passed!".into());
                 }
 
@@ -547,8 +695,7 @@ impl<'m> TransactionExecutor<'m> {
                 BandwidthProcessor::new(self.manager, txn, &cntr)?.consume(&mut ctx)?;
                 cntr.validate(self.manager, &mut ctx)?;
                 let exec_result = cntr.execute(self.manager, &mut ctx)?;
-                if !check_transaction_result(&exec_result, &maybe_result) {
-                    debug!("result => {:?}", exec_result);
+                if !ctx.record_contract_result(exec_result, &txn.raw.result) {
                     return Err("result check not passed!".into());
                 }
                 debug!("context => {:?}", ctx);
@@ -557,6 +704,25 @@ impl<'m> TransactionExecutor<'m> {
             _ => unimplemented!("TODO: handle contract type {:?}", cntr_type),
         }
     }
+
+    /// Deploy and issue policy of private chains, in genesis config. Violations fail validation with a
+    /// `policy violation` error.
+    fn check_chain_policy(&self, cntr_type: ContractType, owner_address: &[u8]) -> Result<(), String> {
+        let policy = match self.manager.genesis_config.policy.as_ref() {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+        let owner_addr = Address::try_from(owner_address).map_err(|_| "invalid owner_address")?;
+        let allowed = match cntr_type {
+            ContractType::CreateSmartContract => policy.can_deploy(&owner_addr),
+            ContractType::AssetIssueContract => policy.can_issue(&owner_addr),
+            _ => true,
+        };
+        if !allowed {
+            return Err(format!("policy violation: {} is not allowed to {:?}", owner_addr, cntr_type));
+        }
+        Ok(())
+    }
 }
 
 #[inline]
@@ -4,10 +4,13 @@ use ::keys::Address;
 use chain::IndexedBlock;
 use chrono::Utc;
 use log::{debug, info};
-use proto2::state::{Witness, WitnessVoterReward};
+use proto2::state::{ResourceDelegation, Witness, WitnessVoterReward};
 use state::keys;
 
+use super::super::events::ChainEvent;
 use super::super::Manager;
+use super::penalty::evaluate_penalties;
+use super::votes::{pay_by_votes, sub_votes, sum_votes};
 
 /// Massive things done during maintenance.
 pub struct MaintenanceManager<'m> {
@@ -29,10 +32,12 @@ impl MaintenanceManager<'_> {
 
         if is_maintenance {
             if block.number() != 1 {
+                evaluate_penalties(self.manager, block)?;
                 self.do_maintenance()?;
+                self.sweep_expired_delegations(block)?;
             } else {
                 // init schedule on first non-genesis block.
-                self.update_witness_schedule();
+                self.update_witness_schedule()?;
             }
             // updateNextMaintenanceTime
             self.increase_next_maintenance_time(next_maintenance_time, block.timestamp())?;
@@ -42,7 +47,7 @@ impl MaintenanceManager<'_> {
                 .manager
                 .state_db
                 .incr_key(keys::DynamicProperty::CurrentEpoch)
-                .unwrap();
+                .map_err(|_| "db insert error")?;
             // Only update VoterReward when AllowChangeDelegation is enabled.
             if self
                 .manager
@@ -60,7 +65,7 @@ impl MaintenanceManager<'_> {
                                 reward_amount: 0,
                             },
                         )
-                        .unwrap();
+                        .map_err(|_| "db insert error")?;
                 }
             }
 
@@ -74,7 +79,7 @@ impl MaintenanceManager<'_> {
         self.manager
             .state_db
             .put_key(keys::DynamicProperty::IsMaintenance, is_maintenance as _)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
         Ok(())
     }
 
@@ -124,7 +129,7 @@ impl MaintenanceManager<'_> {
                     .map_err(|_| "db insert error")?;
             }
 
-            self.update_witness_schedule();
+            self.update_witness_schedule()?;
 
             let new_active_witnesses = self.manager.get_active_witnesses();
 
@@ -146,25 +151,117 @@ impl MaintenanceManager<'_> {
                 .must_get(&keys::ChainParameter::AllowChangeDelegation) ==
                 0
             {
-                self.legacy_reward_standby_witnesses()
+                self.legacy_reward_standby_witnesses()?;
             }
         }
 
         Ok(())
     }
 
+    /// Remove delegations expired for more than `expired_delegation_sweep_days` from `ResourceDelegationIndex` and
+    /// `ResourceDelegationReverseIndex`.
+    ///
+    /// Delegations themselves are untouched, and can still be unfrozen. Not in java-tron, off by default.
+    fn sweep_expired_delegations(&mut self, block: &IndexedBlock) -> Result<(), String> {
+        let sweep_days = self.manager.config.execution.expired_delegation_sweep_days as i64;
+        if sweep_days == 0 {
+            return Ok(());
+        }
+        let deadline = block.timestamp() - sweep_days * constants::DAY_IN_MS;
+        let is_expired = |amount: i64, expiration: i64| amount == 0 || expiration < deadline;
+
+        let mut indexed: Vec<(Address, Vec<Address>)> = vec![];
+        self.manager
+            .state_db
+            .for_each(|key: &keys::ResourceDelegationIndex, to_addrs: &Vec<Address>| {
+                indexed.push((key.0, to_addrs.clone()));
+            });
+
+        let mut swept: Vec<(Address, Address)> = vec![];
+        for (from, to_addrs) in indexed {
+            let num_indexed = to_addrs.len();
+            let mut kept = Vec::with_capacity(num_indexed);
+            for to in to_addrs {
+                let del: ResourceDelegation = self
+                    .manager
+                    .state_db
+                    .get(&keys::ResourceDelegation(from, to))
+                    .map_err(|_| "db query error")?
+                    .unwrap_or_default();
+                if is_expired(del.amount_for_bandwidth, del.expiration_timestamp_for_bandwidth) &&
+                    is_expired(del.amount_for_energy, del.expiration_timestamp_for_energy)
+                {
+                    self.manager.events.emit(ChainEvent::DelegationExpired {
+                        block_number: block.number(),
+                        from,
+                        to,
+                        amount_for_bandwidth: del.amount_for_bandwidth,
+                        expiration_timestamp_for_bandwidth: del.expiration_timestamp_for_bandwidth,
+                        amount_for_energy: del.amount_for_energy,
+                        expiration_timestamp_for_energy: del.expiration_timestamp_for_energy,
+                    });
+                    swept.push((from, to));
+                } else {
+                    kept.push(to);
+                }
+            }
+
+            if kept.is_empty() {
+                self.manager
+                    .state_db
+                    .delete_key(&keys::ResourceDelegationIndex(from))
+                    .map_err(|_| "db delete error")?;
+            } else if kept.len() != num_indexed {
+                self.manager
+                    .state_db
+                    .put_key(keys::ResourceDelegationIndex(from), kept)
+                    .map_err(|_| "db insert error")?;
+            }
+        }
+
+        let mut swept_by_to: HashMap<Address, Vec<Address>> = HashMap::new();
+        for &(from, to) in &swept {
+            swept_by_to.entry(to).or_default().push(from);
+        }
+        for (to, from_addrs) in swept_by_to {
+            let kept: Vec<Address> = self
+                .manager
+                .state_db
+                .get(&keys::ResourceDelegationReverseIndex(to))
+                .map_err(|_| "db query error")?
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|from| !from_addrs.contains(from))
+                .collect();
+            if kept.is_empty() {
+                self.manager
+                    .state_db
+                    .delete_key(&keys::ResourceDelegationReverseIndex(to))
+                    .map_err(|_| "db delete error")?;
+            } else {
+                self.manager
+                    .state_db
+                    .put_key(keys::ResourceDelegationReverseIndex(to), kept)
+                    .map_err(|_| "db insert error")?;
+            }
+        }
+        if !swept.is_empty() {
+            info!("swept {} expired delegations from index", swept.len());
+        }
+        Ok(())
+    }
+
     /// Executive vote counting.
     ///
     /// NOTE: The implementation is different from java-tron.
     /// The votes are already counted and saved in Witness store.
     fn count_votes(&self) -> Result<HashMap<Address, i64>, String> {
-        let mut votes: HashMap<Address, i64> = HashMap::new();
-        {
-            let votes = &mut votes;
-            self.manager.state_db.for_each(move |_key: &keys::Witness, wit| {
-                votes.insert(*Address::from_bytes(&wit.address), wit.vote_count);
-            });
-        }
+        let votes = self
+            .manager
+            .state_db
+            .iter::<_, keys::Witness>()
+            .map(|(key, wit)| (key.0, wit.vote_count))
+            .collect();
         Ok(votes)
     }
 
@@ -184,7 +281,7 @@ impl MaintenanceManager<'_> {
         self.manager
             .state_db
             .put_key(keys::DynamicProperty::NextMaintenanceTime, next_maintenance_time)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
         Ok(())
     }
 
@@ -199,16 +296,15 @@ impl MaintenanceManager<'_> {
             let addr = gr_wit.address.parse::<Address>().expect("address format error");
 
             let mut witness = self.manager.state_db.must_get(&keys::Witness(addr));
-            witness.vote_count -= gr_wit.votes;
+            witness.vote_count = sub_votes(witness.vote_count, gr_wit.votes)?;
             self.manager
                 .state_db
                 .put_key(keys::Witness(addr), witness)
                 .map_err(|_| "insert db error")?;
 
-            wit_sched
-                .iter_mut()
-                .find(|(sched_addr, _, _)| sched_addr == &addr)
-                .map(|(_, vote_count, _)| *vote_count -= gr_wit.votes);
+            if let Some((_, vote_count, _)) = wit_sched.iter_mut().find(|(sched_addr, _, _)| sched_addr == &addr) {
+                *vote_count = sub_votes(*vote_count, gr_wit.votes)?;
+            }
         }
         self.manager
             .state_db
@@ -222,14 +318,13 @@ impl MaintenanceManager<'_> {
     }
 
     // DposService.updateWitness
-    fn update_witness_schedule(&mut self) {
-        let mut wit_sched: Vec<(Address, i64, u8)> = Vec::new();
-        {
-            let wit_sched = &mut wit_sched;
-            self.manager.state_db.for_each(move |key: &keys::Witness, value| {
-                wit_sched.push((key.0, value.vote_count, value.brokerage as u8));
-            });
-        }
+    fn update_witness_schedule(&mut self) -> Result<(), String> {
+        let mut wit_sched: Vec<(Address, i64, u8)> = self
+            .manager
+            .state_db
+            .iter::<_, keys::Witness>()
+            .map(|(key, wit)| (key.0, wit.vote_count, wit.brokerage as u8))
+            .collect();
 
         // NOTE: This is different from java-tron. In OpenTron, raw address is used as final fallback sorting key.
         wit_sched.sort_by_cached_key(|&(addr, vote_count, _)| {
@@ -244,7 +339,38 @@ impl MaintenanceManager<'_> {
             let _ = wit_sched.split_off(constants::MAX_NUM_OF_STANDBY_WITNESSES);
         }
 
-        self.manager.state_db.put_key(keys::WitnessSchedule, wit_sched).unwrap();
+        let active_addrs: Vec<Address> = wit_sched
+            .iter()
+            .take(self.manager.num_active_witnesses())
+            .map(|&(addr, _, _)| addr)
+            .collect();
+        self.manager
+            .state_db
+            .put_key(keys::WitnessSchedule, wit_sched)
+            .map_err(|_| "db insert error")?;
+
+        self.update_witness_is_active(&active_addrs)
+    }
+
+    /// Update `is_active`(`isJobs` in java-tron) of witnesses, so that witness queries show the same data.
+    fn update_witness_is_active(&mut self, active_addrs: &[Address]) -> Result<(), String> {
+        let changed: Vec<_> = self
+            .manager
+            .state_db
+            .iter::<Witness, keys::Witness>()
+            .map(|(key, wit)| (key.0, active_addrs.contains(&key.0), wit.is_active))
+            .filter(|&(_, is_active, was_active)| is_active != was_active)
+            .collect();
+
+        for (addr, is_active, _) in changed {
+            let mut wit = self.manager.state_db.must_get(&keys::Witness(addr));
+            wit.is_active = is_active;
+            self.manager
+                .state_db
+                .put_key(keys::Witness(addr), wit)
+                .map_err(|_| "db insert error")?;
+        }
+        Ok(())
     }
 
     /// `IncentiveManager.reward`, only when `AllowChangeDelegation = false`.
@@ -252,30 +378,34 @@ impl MaintenanceManager<'_> {
     /// Not used by testnet, but is used on mainnet.
     ///
     /// This is done after vote couting.
-    fn legacy_reward_standby_witnesses(&mut self) {
+    fn legacy_reward_standby_witnesses(&mut self) -> Result<(), String> {
         let addrs = self.manager.get_standby_witnesses();
         let vote_counts: Vec<_> = addrs
             .iter()
             .map(|&addr| self.manager.state_db.must_get(&keys::Witness(addr)).vote_count)
             .collect();
 
-        let total_vote_count: i64 = vote_counts.iter().sum();
+        let total_vote_count = sum_votes(vote_counts.iter().copied())?;
         let total_pay = self
             .manager
             .state_db
             .must_get(&keys::ChainParameter::StandbyWitnessAllowance);
-        let pay_per_vote = total_pay as f64 / total_vote_count as f64;
-
-        if total_pay != 0 {
-            for (addr, vote_weight) in addrs.into_iter().zip(vote_counts.into_iter()) {
-                let pay = (vote_weight as f64 * pay_per_vote) as i64;
-                if pay != 0 {
-                    let mut acct = self.manager.state_db.must_get(&keys::Account(addr));
-                    acct.allowance += pay;
-                    self.manager.state_db.put_key(keys::Account(addr), acct).unwrap();
+
+        // NOTE: When there're no votes at all, none will be paid to standby witnesses.
+        for (addr, vote_weight) in addrs.into_iter().zip(vote_counts.into_iter()) {
+            let pay = pay_by_votes(total_pay, vote_weight, total_vote_count);
+            if pay != 0 {
+                let mut acct = self.manager.state_db.must_get(&keys::Account(addr));
+                if acct.adjust_allowance(pay).is_err() {
+                    return Err("math overflow while adding standby witness allowance".into());
                 }
+                self.manager
+                    .state_db
+                    .put_key(keys::Account(addr), acct)
+                    .map_err(|_| "db insert error")?;
             }
         }
+        Ok(())
     }
 }
 
@@ -1,5 +1,7 @@
 //! Chain governance related.
 
 pub mod maintenance;
+pub mod penalty;
 pub mod proposal;
 pub mod reward;
+pub mod votes;
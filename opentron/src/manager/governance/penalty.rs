@@ -0,0 +1,182 @@
+//! Penalty rules of witnesses, evaluated at maintenance.
+//!
+//! TRON consensus has no penalty yet, so penalties are only reported, as `WitnessPenalized` events. A future
+//! governance change acting on them, i.e. slashing or unscheduling, is a new rule plus its enforcement here.
+
+use std::collections::HashMap;
+
+use ::keys::Address;
+use chain::IndexedBlock;
+use log::warn;
+use state::keys;
+
+use super::super::events::ChainEvent;
+use super::super::Manager;
+
+/// A penalty of a witness, found by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Penalty {
+    pub witness: Address,
+    pub reason: String,
+}
+
+/// A rule evaluated at each maintenance, before the witness schedule is updated.
+///
+/// Rules are part of block processing, so they must only depend on state-db, never on node config or memory.
+pub trait PenaltyRule {
+    fn name(&self) -> &'static str;
+
+    /// Penalties of the epoch ending at the maintenance block.
+    fn evaluate(&self, manager: &mut Manager) -> Result<Vec<Penalty>, String>;
+}
+
+/// All rules, in evaluation order.
+pub fn penalty_rules() -> Vec<Box<dyn PenaltyRule>> {
+    vec![Box::new(MissedBlocksRule::new(constants::MAX_NUM_OF_MISSED_BLOCKS_PER_EPOCH))]
+}
+
+/// Evaluate all rules, and report penalties.
+pub fn evaluate_penalties(manager: &mut Manager, block: &IndexedBlock) -> Result<(), String> {
+    let mut penalties = vec![];
+    for rule in penalty_rules() {
+        let found = rule.evaluate(manager)?;
+        penalties.extend(found.into_iter().map(|penalty| (rule.name(), penalty)));
+    }
+
+    for (rule, penalty) in penalties {
+        warn!(
+            "witness {} penalized by {} at block #{}: {}",
+            penalty.witness,
+            rule,
+            block.number(),
+            penalty.reason
+        );
+        manager.events.emit(ChainEvent::WitnessPenalized {
+            block_number: block.number(),
+            witness: penalty.witness,
+            rule,
+            reason: penalty.reason,
+        });
+    }
+    Ok(())
+}
+
+/// Active witnesses missing more than `threshold` blocks in an epoch.
+///
+/// Missed blocks of an epoch are the increase of `total_missed` since the last maintenance, snapshotted in
+/// `WitnessMissedSnapshot`. Without a snapshot, i.e. the first maintenance, only the snapshot is taken.
+pub struct MissedBlocksRule {
+    threshold: i64,
+}
+
+impl MissedBlocksRule {
+    pub fn new(threshold: i64) -> Self {
+        MissedBlocksRule { threshold }
+    }
+
+    fn check(&self, last_total_missed: &[(Address, i64)], active_witnesses: &[(Address, i64)]) -> Vec<Penalty> {
+        let last_total_missed: HashMap<_, _> = last_total_missed.iter().cloned().collect();
+        let mut penalties = vec![];
+        for &(wit_addr, total_missed) in active_witnesses {
+            // Newly elected witnesses did not produce in the last epoch.
+            if let Some(&last) = last_total_missed.get(&wit_addr) {
+                let missed = total_missed - last;
+                if missed > self.threshold {
+                    penalties.push(Penalty {
+                        witness: wit_addr,
+                        reason: format!("missed {} blocks in epoch, threshold {}", missed, self.threshold),
+                    });
+                }
+            }
+        }
+        penalties
+    }
+}
+
+impl PenaltyRule for MissedBlocksRule {
+    fn name(&self) -> &'static str {
+        "MissedBlocks"
+    }
+
+    fn evaluate(&self, manager: &mut Manager) -> Result<Vec<Penalty>, String> {
+        let mut active_witnesses = vec![];
+        for wit_addr in manager.get_active_witnesses() {
+            let wit = manager
+                .state_db
+                .get(&keys::Witness(wit_addr))
+                .map_err(|_| "db query error")?
+                .ok_or("witness not found")?;
+            active_witnesses.push((wit_addr, wit.total_missed));
+        }
+
+        let maybe_snapshot = manager
+            .state_db
+            .get(&keys::WitnessMissedSnapshot)
+            .map_err(|_| "db query error")?;
+        let penalties = match maybe_snapshot {
+            Some(last_total_missed) => self.check(&last_total_missed, &active_witnesses),
+            None => vec![],
+        };
+        manager
+            .state_db
+            .put_key(keys::WitnessMissedSnapshot, active_witnesses)
+            .map_err(|_| "db insert error")?;
+        Ok(penalties)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missed_blocks_rule() {
+        let wit1: Address = "TKSXDA8HfE9E1y39RczVQ1ZascUEtaSToF".parse().unwrap();
+        let wit2: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let rule = MissedBlocksRule::new(10);
+
+        let penalties = rule.check(&[(wit1, 100)], &[(wit1, 111), (wit2, 50)]);
+        assert_eq!(penalties.len(), 1);
+        assert_eq!(penalties[0].witness, wit1);
+        assert!(rule.check(&[(wit1, 111), (wit2, 50)], &[(wit1, 121), (wit2, 60)]).is_empty());
+    }
+
+    #[test]
+    fn test_missed_blocks_snapshot() {
+        use proto2::state::Witness;
+
+        use super::super::super::testing::TestManager;
+
+        let wit_addr: Address = "TKSXDA8HfE9E1y39RczVQ1ZascUEtaSToF".parse().unwrap();
+        let mut manager = TestManager::new();
+        let set_total_missed = |manager: &mut Manager, total_missed| {
+            let wit = Witness {
+                address: wit_addr.as_bytes().to_vec(),
+                total_missed,
+                ..Default::default()
+            };
+            manager.state_db.put_key(keys::Witness(wit_addr), wit).unwrap();
+        };
+        manager.state_db.new_layer();
+        manager
+            .state_db
+            .put_key(keys::WitnessSchedule, vec![(wit_addr, 0, 0)])
+            .unwrap();
+        let rule = MissedBlocksRule::new(10);
+
+        // The first maintenance only takes a snapshot.
+        set_total_missed(&mut manager, 100);
+        assert!(rule.evaluate(&mut manager).unwrap().is_empty());
+        assert_eq!(
+            manager.state_db.get(&keys::WitnessMissedSnapshot).unwrap(),
+            Some(vec![(wit_addr, 100)])
+        );
+
+        set_total_missed(&mut manager, 111);
+        let penalties = rule.evaluate(&mut manager).unwrap();
+        assert_eq!(penalties.len(), 1);
+        assert_eq!(penalties[0].witness, wit_addr);
+        set_total_missed(&mut manager, 121);
+        assert!(rule.evaluate(&mut manager).unwrap().is_empty());
+    }
+}
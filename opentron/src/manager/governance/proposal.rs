@@ -1,6 +1,7 @@
 //! Proposal controller and validator.
 
 use ::keys::Address;
+use chain::IndexedBlock;
 use constants::block_version::BlockVersion;
 use log::{debug, info};
 use proto2::state::{proposal::State as ProposalState, Proposal};
@@ -20,7 +21,7 @@ impl ProposalController<'_> {
         ProposalController { manager }
     }
 
-    pub fn process_proposals(&mut self) -> Result<(), String> {
+    pub fn process_proposals(&mut self, block: &IndexedBlock) -> Result<(), String> {
         let latest_proposal_id = self.manager.state_db.must_get(&keys::DynamicProperty::LatestProposalId);
         if latest_proposal_id == 0 {
             debug!("no proposal yet");
@@ -51,7 +52,7 @@ impl ProposalController<'_> {
                     "proposal #{} expired, counting votes of active witnesses...",
                     proposal_id
                 );
-                self.process_proposal(proposal)?;
+                self.process_proposal(proposal, block)?;
                 continue;
             }
 
@@ -60,9 +61,9 @@ impl ProposalController<'_> {
         Ok(())
     }
 
-    fn process_proposal(&mut self, mut proposal: Proposal) -> Result<(), String> {
+    fn process_proposal(&mut self, mut proposal: Proposal, block: &IndexedBlock) -> Result<(), String> {
         let active_witnesses = self.manager.get_active_witnesses();
-        if active_witnesses.len() != constants::MAX_NUM_OF_ACTIVE_WITNESSES {
+        if active_witnesses.len() != self.manager.num_active_witnesses() {
             info!("current number of active witnesses: {}", active_witnesses.len());
         }
         let approval_count = proposal
@@ -79,9 +80,13 @@ impl ProposalController<'_> {
             );
             // set dynamic parameters
             for (&param, &value) in proposal.parameters.iter() {
+                let param = keys::ChainParameter::from_i32(param as i32).unwrap();
+                if param == ChainParameter::EnergyFee || param == ChainParameter::BandwidthFee {
+                    self.record_price_history(param, value, proposal.expiration_time, block)?;
+                }
                 self.manager
                     .state_db
-                    .put_key(keys::ChainParameter::from_i32(param as i32).unwrap(), value)
+                    .put_key(param, value)
                     .map_err(|_| "db insert error")?;
             }
             proposal.state = ProposalState::Approved as i32;
@@ -103,6 +108,32 @@ impl ProposalController<'_> {
         }
         Ok(())
     }
+
+    /// Append a price change to `PriceHistory`, which starts with the price before any change, at timestamp 0.
+    fn record_price_history(
+        &mut self,
+        param: ChainParameter,
+        price: i64,
+        timestamp: i64,
+        block: &IndexedBlock,
+    ) -> Result<(), String> {
+        let mut history = match self
+            .manager
+            .state_db
+            .get(&keys::PriceHistory(param))
+            .map_err(|_| "db query error")?
+        {
+            Some(history) => history,
+            None => vec![(0, 0, self.manager.state_db.must_get(&param))],
+        };
+        history.push((block.number(), timestamp, price));
+        debug!("{:?} history: {}", param, keys::PriceHistory::to_java_string(&history));
+        self.manager
+            .state_db
+            .put_key(keys::PriceHistory(param), history)
+            .map_err(|_| "db insert error")?;
+        Ok(())
+    }
 }
 
 /// Proposal validator.
@@ -202,6 +233,40 @@ impl ProposalUtil<'_> {
                 self.require_version(BlockVersion::GreatVoyage4_0_1)?;
                 self.accept_bool(value)
             }
+            MaxFeeLimit => {
+                // NOTE: 4.1 is not a known fork version yet, use the latest one.
+                self.require_version(BlockVersion::GreatVoyage4_0_1)?;
+                self.accept_range_value(value, 0, 10_000_000_000)
+            }
+            MemoFee => {
+                // NOTE: 4.2 is not a known fork version yet, use the latest one.
+                self.require_version(BlockVersion::GreatVoyage4_0_1)?;
+                self.accept_range_value(value, 0, 1_000_000_000)
+            }
+            UnfreezeDelayDays => {
+                // NOTE: 4.7 is not a known fork version yet, use the latest one.
+                self.require_version(BlockVersion::GreatVoyage4_0_1)?;
+                self.accept_range_value(value, 1, 365)
+            }
+            MaxDelegateLockPeriod => {
+                // NOTE: 4.7.4 is not a known fork version yet, use the latest one.
+                self.require_version(BlockVersion::GreatVoyage4_0_1)?;
+                let current = self.manager.state_db.must_get(&ChainParameter::MaxDelegateLockPeriod);
+                if value <= current || value > constants::MAX_DELEGATE_LOCK_PERIOD {
+                    return Err(format!(
+                        "invalid chain parameter, valid range is ({}, {}]",
+                        current,
+                        constants::MAX_DELEGATE_LOCK_PERIOD
+                    ));
+                }
+                self.require_proposal(ChainParameter::UnfreezeDelayDays)
+            }
+            AllowCancelAllUnfreezeV2 => {
+                // NOTE: 4.7.2 is not a known fork version yet, use the latest one.
+                self.require_version(BlockVersion::GreatVoyage4_0_1)?;
+                self.accept_true(value)?;
+                self.require_proposal(ChainParameter::UnfreezeDelayDays)
+            }
         }
     }
 
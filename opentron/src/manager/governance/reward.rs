@@ -7,6 +7,7 @@ use proto2::state::Votes;
 use state::keys;
 
 use super::super::Manager;
+use super::votes::{pay_by_votes, split_brokerage, sum_votes};
 
 /// Controller to handle rewards. Renamed from DelegationService, which is ambiguous.
 ///
@@ -26,12 +27,11 @@ impl RewardController<'_> {
 
         let wit_sched = self.manager.state_db.must_get(&keys::WitnessSchedule);
 
-        let mut wit_accts = Vec::with_capacity(constants::MAX_NUM_OF_ACTIVE_WITNESSES);
-        let mut total_votes = 0_i64;
-        for &(wit_addr, vote_count, _) in &wit_sched {
-            wit_accts.push(self.manager.state_db.must_get(&keys::Account(wit_addr)));
-            total_votes += vote_count;
-        }
+        let wit_accts: Vec<_> = wit_sched
+            .iter()
+            .map(|&(wit_addr, _, _)| self.manager.state_db.must_get(&keys::Account(wit_addr)))
+            .collect();
+        let total_votes = sum_votes(wit_sched.iter().map(|&(_, vote_count, _)| vote_count))?;
 
         let block_reward = self
             .manager
@@ -42,24 +42,19 @@ impl RewardController<'_> {
             .state_db
             .must_get(&keys::ChainParameter::StandbyWitnessPayPerBlock);
 
-        // NOTE: When there're no votes at all, none will be paid to standby witnesses.
-        let standby_pay_per_vote = if total_votes > 0 {
-            standby_reward as f64 / total_votes as f64
-        } else {
-            0.0
-        };
-
         let epoch = self.manager.state_db.must_get(&keys::DynamicProperty::CurrentEpoch);
         // payReward
         for ((wit_addr, vote_count, brokerage), mut wit_acct) in wit_sched.into_iter().zip(wit_accts.into_iter()) {
-            let mut voters_reward = 0;
+            let mut voters_reward = 0_i64;
             // payStandbyWitness
-            let brokerage_rate = brokerage as f64 / 100.0;
-            let pay = (standby_pay_per_vote * vote_count as f64) as i64;
+            // NOTE: When there're no votes at all, none will be paid to standby witnesses.
+            let pay = pay_by_votes(standby_reward, vote_count, total_votes);
 
             if pay > 0 {
-                let brokerage_amount = (pay as f64 * brokerage_rate) as i64;
-                voters_reward += pay - brokerage_amount;
+                let (brokerage_amount, voters_amount) = split_brokerage(pay, brokerage)?;
+                voters_reward = voters_reward
+                    .checked_add(voters_amount)
+                    .ok_or("math overflow while adding voters reward")?;
                 if wit_acct.adjust_allowance(brokerage_amount).is_err() {
                     return Err("math overflow while adding brokerage amount".into());
                 }
@@ -67,15 +62,17 @@ impl RewardController<'_> {
 
             // payBlockReward
             if wit_addr == curr_wit_addr {
-                let brokerage_amount = (block_reward as f64 * brokerage_rate) as i64;
-                voters_reward += block_reward - brokerage_amount;
+                let (brokerage_amount, voters_amount) = split_brokerage(block_reward, brokerage)?;
+                voters_reward = voters_reward
+                    .checked_add(voters_amount)
+                    .ok_or("math overflow while adding voters reward")?;
                 if wit_acct.adjust_allowance(brokerage_amount).is_err() {
                     return Err("math overflow while adding brokerage amount".into());
                 }
             }
             // save reward of voters
             // delegationStore.addReward(cycle, witnessAddress, value);
-            self.add_voter_reward(epoch, wit_addr, voters_reward);
+            self.add_voter_reward(epoch, wit_addr, voters_reward)?;
             self.manager
                 .state_db
                 .put_key(keys::Account(wit_addr), wit_acct)
@@ -85,16 +82,42 @@ impl RewardController<'_> {
         Ok(())
     }
 
-    fn add_voter_reward(&mut self, epoch: i64, wit_addr: Address, amount: i64) {
-        assert!(amount >= 0, "voter reward must be greater than or equal to 0");
+    fn add_voter_reward(&mut self, epoch: i64, wit_addr: Address, amount: i64) -> Result<(), String> {
+        if amount < 0 {
+            return Err(format!("voter reward must be greater than or equal to 0, got {}", amount));
+        }
         let key = keys::VoterReward(epoch, wit_addr);
         let mut reward = self.manager.state_db.must_get(&key);
-        reward.reward_amount += amount;
-        self.manager.state_db.put_key(key, reward).unwrap();
+        reward.reward_amount = reward
+            .reward_amount
+            .checked_add(amount)
+            .ok_or("math overflow while adding voter reward")?;
+        self.manager.state_db.put_key(key, reward).map_err(|_| "db insert error")?;
+        Ok(())
+    }
+
+    // DelegationService.queryReward.
+    /// Dry-run of `withdraw_reward`, the reward to be added to an account's allowance, without touching state.
+    ///
+    /// Takes a shared `Manager`, so that actuators can check the reward in `validate`.
+    pub fn pending_reward(manager: &Manager, addr: Address) -> Result<i64, String> {
+        if manager.state_db.must_get(&keys::ChainParameter::AllowChangeDelegation) == 0 {
+            return Ok(0);
+        }
+
+        let curr_epoch = manager.state_db.must_get(&keys::DynamicProperty::CurrentEpoch);
+        match manager.state_db.get(&keys::Votes(addr)).map_err(|_| "db query error")? {
+            Some(votes) if votes.epoch != curr_epoch => {
+                RewardUtil::new(manager).compute_rewards(addr, &votes, curr_epoch)
+            }
+            _ => Ok(0),
+        }
     }
 
     // withdrawReward
     /// Update an account's allowance and reset voting epoch status.
+    ///
+    /// Must be called before votes change, so that votes of current epoch are snapshotted.
     pub fn withdraw_reward(&mut self, addr: Address) -> Result<(), String> {
         if self
             .manager
@@ -105,13 +128,14 @@ impl RewardController<'_> {
             return Ok(());
         }
 
+        let curr_epoch = self.manager.state_db.must_get(&keys::DynamicProperty::CurrentEpoch);
+
         if let Some(mut votes) = self
             .manager
             .state_db
             .get(&keys::Votes(addr))
             .map_err(|_| "db query error")?
         {
-            let curr_epoch = self.manager.state_db.must_get(&keys::DynamicProperty::CurrentEpoch);
             if votes.epoch == curr_epoch {
                 return Ok(());
             }
@@ -119,19 +143,56 @@ impl RewardController<'_> {
             let mut acct = self.manager.state_db.must_get(&keys::Account(addr));
 
             let begin_epoch = votes.epoch;
-            let mut reward_amount = 0;
-            for epoch in begin_epoch..curr_epoch {
-                reward_amount += RewardUtil::new(self.manager).compute_reward(epoch, &votes)?;
-            }
+            let reward_amount = Self::pending_reward(self.manager, addr)?;
             debug!("withdraw reward={} epochs={}", reward_amount, curr_epoch - begin_epoch);
 
             if reward_amount != 0 {
-                acct.adjust_allowance(reward_amount).unwrap();
-                self.manager.state_db.put_key(keys::Account(addr), acct).unwrap();
+                if acct.adjust_allowance(reward_amount).is_err() {
+                    return Err("math overflow while adding reward".into());
+                }
+                self.manager
+                    .state_db
+                    .put_key(keys::Account(addr), acct)
+                    .map_err(|_| "db insert error")?;
             }
 
+            // The snapshot of begin epoch is consumed.
+            self.manager
+                .state_db
+                .delete_key(&keys::VoteSnapshot(begin_epoch, addr))
+                .map_err(|_| "db delete error")?;
+
             votes.epoch = curr_epoch;
-            self.manager.state_db.put_key(keys::Votes(addr), votes).unwrap();
+            self.manager
+                .state_db
+                .put_key(keys::VoteSnapshot(curr_epoch, addr), votes.clone())
+                .map_err(|_| "db insert error")?;
+            self.manager
+                .state_db
+                .put_key(keys::Votes(addr), votes)
+                .map_err(|_| "db insert error")?;
+        } else {
+            // No votes at the beginning of current epoch, new votes take effect from the next epoch.
+            // NOTE: Votes might be cleared in current epoch, while the snapshot is kept.
+            let snapshot_key = keys::VoteSnapshot(curr_epoch, addr);
+            if self
+                .manager
+                .state_db
+                .get(&snapshot_key)
+                .map_err(|_| "db query error")?
+                .is_none()
+            {
+                self.manager
+                    .state_db
+                    .put_key(
+                        snapshot_key,
+                        Votes {
+                            epoch: curr_epoch,
+                            votes: vec![],
+                        },
+                    )
+                    .map_err(|_| "db insert error")?;
+            }
         }
 
         Ok(())
@@ -147,37 +208,27 @@ impl RewardUtil<'_> {
         RewardUtil { manager }
     }
 
-    // DelegationService.queryReward.
-    pub fn query_reward(&self, addr: Address) -> Result<i64, String> {
-        let allow_change_delegation = self
-            .manager
-            .state_db
-            .must_get(&keys::ChainParameter::AllowChangeDelegation) !=
-            0;
-        if !allow_change_delegation {
+    /// Rewards of epochs in `[votes.epoch, end_epoch)`.
+    ///
+    /// Votes might be changed in the begin epoch, its snapshot is used if any.
+    fn compute_rewards(&self, addr: Address, votes: &Votes, end_epoch: i64) -> Result<i64, String> {
+        let begin_epoch = votes.epoch;
+        if begin_epoch >= end_epoch {
             return Ok(0);
         }
 
-        if let Some(votes) = self
+        let begin_votes = self
             .manager
             .state_db
-            .get(&keys::Votes(addr))
-            .map_err(|_| "db query error")?
-        {
-            let curr_epoch = self.manager.state_db.must_get(&keys::DynamicProperty::CurrentEpoch);
-            if votes.epoch == curr_epoch {
-                return Ok(0);
-            }
-
-            let begin_epoch = votes.epoch;
-            let mut reward_amount = 0;
-            for epoch in begin_epoch..curr_epoch {
-                reward_amount += self.compute_reward(epoch, &votes)?;
-            }
-            Ok(reward_amount)
-        } else {
-            Ok(0)
+            .get(&keys::VoteSnapshot(begin_epoch, addr))
+            .map_err(|_| "db query error")?;
+        let mut reward_amount = self.compute_reward(begin_epoch, begin_votes.as_ref().unwrap_or(votes))?;
+        for epoch in begin_epoch + 1..end_epoch {
+            reward_amount = reward_amount
+                .checked_add(self.compute_reward(epoch, votes)?)
+                .ok_or("math overflow while computing rewards")?;
         }
+        Ok(reward_amount)
     }
 
     fn compute_reward(&self, epoch: i64, votes: &Votes) -> Result<i64, String> {
@@ -194,7 +245,9 @@ impl RewardUtil<'_> {
                     continue;
                 }
                 let vote_rate = vote.vote_count as f64 / total_reward.vote_count as f64;
-                reward_amount += (vote_rate * total_reward.reward_amount as f64) as i64;
+                reward_amount = reward_amount
+                    .checked_add((vote_rate * total_reward.reward_amount as f64) as i64)
+                    .ok_or("math overflow while computing rewards")?;
             }
         }
         Ok(reward_amount)
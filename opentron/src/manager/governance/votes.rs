@@ -0,0 +1,136 @@
+//! Checked arithmetic of vote counts and vote-based rewards.
+//!
+//! Vote counts decide the witness schedule, where a silent wrap-around would corrupt block production. All vote count
+//! updates go through these helpers, which fail with an explicit error instead of overflowing or going negative.
+
+/// Maximum brokerage rate, in percent.
+pub const MAX_BROKERAGE: u8 = 100;
+
+/// Add a (possibly negative) diff to a vote count. The result must be non-negative.
+pub fn adjust_votes(vote_count: i64, diff: i64) -> Result<i64, String> {
+    match vote_count.checked_add(diff) {
+        Some(new_count) if new_count >= 0 => Ok(new_count),
+        Some(new_count) => Err(format!(
+            "vote count becomes negative, {} + {} = {}",
+            vote_count, diff, new_count
+        )),
+        None => Err(format!("vote count overflow, {} + {}", vote_count, diff)),
+    }
+}
+
+/// Remove votes from a vote count. The result must be non-negative.
+pub fn sub_votes(vote_count: i64, votes: i64) -> Result<i64, String> {
+    let diff = votes
+        .checked_neg()
+        .ok_or_else(|| format!("vote count overflow, {} - {}", vote_count, votes))?;
+    adjust_votes(vote_count, diff)
+}
+
+/// Sum of vote counts. Every vote count must be non-negative.
+pub fn sum_votes<I: IntoIterator<Item = i64>>(vote_counts: I) -> Result<i64, String> {
+    vote_counts.into_iter().try_fold(0_i64, |total, vote_count| {
+        if vote_count < 0 {
+            return Err(format!("negative vote count {}", vote_count));
+        }
+        total
+            .checked_add(vote_count)
+            .ok_or_else(|| format!("total vote count overflow, {} + {}", total, vote_count))
+    })
+}
+
+/// Share of `total_pay` for `vote_count` out of `total_votes`.
+///
+/// NOTE: Follows the f64 math of java-tron, so results are identical in range. The result is clamped to
+/// `[0, total_pay]`, which never changes a result of valid inputs, but guards float rounding of extreme ones.
+pub fn pay_by_votes(total_pay: i64, vote_count: i64, total_votes: i64) -> i64 {
+    if total_pay <= 0 || vote_count <= 0 || total_votes <= 0 {
+        return 0;
+    }
+    let pay_per_vote = total_pay as f64 / total_votes as f64;
+    // NOTE: `as` saturates on f64 to i64 conversion.
+    ((pay_per_vote * vote_count as f64) as i64).max(0).min(total_pay)
+}
+
+/// Split a pay by brokerage rate, returns `(brokerage_amount, voters_amount)`, which sum up to `pay`.
+pub fn split_brokerage(pay: i64, brokerage: u8) -> Result<(i64, i64), String> {
+    if pay < 0 {
+        return Err(format!("negative pay {}", pay));
+    }
+    if brokerage > MAX_BROKERAGE {
+        return Err(format!("invalid brokerage {}", brokerage));
+    }
+    let brokerage_rate = brokerage as f64 / 100.0;
+    let brokerage_amount = ((pay as f64 * brokerage_rate) as i64).max(0).min(pay);
+    Ok((brokerage_amount, pay - brokerage_amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_vote_arithmetic() {
+        assert_eq!(adjust_votes(100, -30), Ok(70));
+        assert_eq!(adjust_votes(100, 30), Ok(130));
+        assert!(adjust_votes(100, -101).is_err());
+        assert!(adjust_votes(i64::MAX, 1).is_err());
+        assert_eq!(sub_votes(100, 100), Ok(0));
+        assert!(sub_votes(100, 101).is_err());
+        assert!(sub_votes(0, i64::MIN).is_err());
+
+        assert_eq!(sum_votes(vec![1, 2, 3]), Ok(6));
+        assert_eq!(sum_votes(vec![]), Ok(0));
+        assert!(sum_votes(vec![i64::MAX, 1]).is_err());
+        assert!(sum_votes(vec![1, -1]).is_err());
+    }
+
+    #[test]
+    fn test_reward_math() {
+        // Mainnet parameters: 16 TRX per block for standby witnesses, 20% brokerage.
+        assert_eq!(pay_by_votes(16_000_000, 1, 2), 8_000_000);
+        assert_eq!(pay_by_votes(16_000_000, 0, 2), 0);
+        assert_eq!(pay_by_votes(16_000_000, 1, 0), 0);
+        assert_eq!(split_brokerage(16_000_000, 20), Ok((3_200_000, 12_800_000)));
+        assert_eq!(split_brokerage(16_000_000, 0), Ok((0, 16_000_000)));
+        assert_eq!(split_brokerage(16_000_000, 100), Ok((16_000_000, 0)));
+        assert!(split_brokerage(16_000_000, 101).is_err());
+        assert!(split_brokerage(-1, 20).is_err());
+    }
+
+    const EDGE_VALUES: [i64; 7] = [0, 1, -1, i64::MAX, i64::MIN, 1 << 53, (1 << 53) + 1];
+
+    fn arbitrary_i64<R: Rng>(rng: &mut R) -> i64 {
+        match rng.gen_range(0, 4) {
+            0 => EDGE_VALUES[rng.gen_range(0, EDGE_VALUES.len())],
+            1 => rng.gen_range(0, 1_000_000_000_000_000),
+            2 => rng.gen_range(0, i64::MAX),
+            _ => rng.gen(),
+        }
+    }
+
+    #[test]
+    fn fuzz_reward_math() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..100_000 {
+            let total_pay = arbitrary_i64(&mut rng);
+            let vote_count = arbitrary_i64(&mut rng);
+            let total_votes = arbitrary_i64(&mut rng);
+            let brokerage = rng.gen_range(0, MAX_BROKERAGE + 1);
+
+            let pay = pay_by_votes(total_pay, vote_count, total_votes);
+            assert!(pay >= 0 && pay <= total_pay.max(0));
+
+            let (brokerage_amount, voters_amount) = split_brokerage(pay, brokerage).unwrap();
+            assert!(brokerage_amount >= 0 && voters_amount >= 0);
+            assert_eq!(brokerage_amount + voters_amount, pay);
+
+            match (adjust_votes(vote_count, total_votes), vote_count.checked_add(total_votes)) {
+                (Ok(sum), Some(expected)) => assert_eq!(sum, expected),
+                (Err(_), Some(expected)) => assert!(expected < 0),
+                (result, None) => assert!(result.is_err()),
+            }
+        }
+    }
+}
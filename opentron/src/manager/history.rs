@@ -0,0 +1,38 @@
+//! Optional transaction history of accounts, enabled by `indexer.account-transactions` in config.
+//!
+//! Same as `GetTransactionsFromThis` and `GetTransactionsToThis` of java-tron's old solidity nodes, extended with
+//! called contracts, internal transaction receivers and TRC20 counterparties.
+
+use std::collections::HashSet;
+
+use chain::{IndexedBlock, IndexedTransaction};
+use proto2::state::TransactionReceipt;
+use state::keys;
+
+use super::events::{involved_addresses, AddressRole};
+use super::{Manager, Result};
+
+impl Manager {
+    /// Index an executed transaction for all involved accounts.
+    pub(super) fn index_account_transaction(
+        &mut self,
+        block: &IndexedBlock,
+        txn: &IndexedTransaction,
+        receipt: &TransactionReceipt,
+    ) -> Result<()> {
+        let txn_index = block.transaction_index(txn);
+        let cntr = txn.raw.raw_data.as_ref().unwrap().contract.as_ref().unwrap();
+
+        let mut indexed = HashSet::new();
+        for (addr, role) in involved_addresses(cntr, receipt) {
+            let to_this = role != AddressRole::Owner;
+            if indexed.insert((addr, to_this)) {
+                self.state_db.put_key(
+                    keys::AccountTransaction(addr, to_this, block.number(), txn_index),
+                    txn.hash,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
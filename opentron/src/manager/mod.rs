@@ -1,25 +1,54 @@
-use ::keys::{b58encode_check, Address};
+use ::keys::{b58encode_check, Address, Private};
 use chain::{IndexedBlock, IndexedTransaction};
 use chrono::Utc;
-use config::{Config, GenesisConfig};
+use config::{Config, EventConfig, GenesisConfig};
+use hex::FromHex;
 use log::{debug, info, trace, warn};
 use primitive_types::H256;
 use prost::Message;
-use state::db::StateDB;
+use proto2::chain::ContractType;
+use proto2::state::BlockResourceUsage;
+use slog::o;
+use state::bloom::LogBloom;
+use state::commitment;
+use state::db::{StateDB, WritePolicy};
 use state::keys;
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
 
+use self::constant::ConstantCallCache;
+use self::events::{AddressWatcher, ChainEvent, EventDispatcher};
 use self::executor::TransactionExecutor;
 use self::governance::maintenance::MaintenanceManager;
 use self::governance::proposal::ProposalController;
 use self::governance::reward::RewardController;
+use self::prefetch::StatePrefetcher;
+use self::producer::{AssemblyPolicy, ProducerMonitor, SignedSlots};
 use self::resource::EnergyProcessor;
+use self::stats::ExecutionStats;
 
 pub mod actuators;
+pub mod bench;
+pub mod constant;
 pub mod controllers;
+pub mod events;
 pub mod executor;
 pub mod governance;
+mod history;
+mod nft;
+pub mod prefetch;
+pub mod producer;
+pub mod replay;
 pub mod resource;
+#[cfg(feature = "shielded")]
+mod shielded;
+pub mod stats;
+pub mod testchain;
+#[cfg(test)]
+pub mod testing;
 pub mod vm;
 
 type Error = Box<dyn ::std::error::Error>;
@@ -33,21 +62,50 @@ fn new_error(msg: &str) -> Error {
     Box::new(io::Error::new(io::ErrorKind::Other, msg))
 }
 
+/// Address expected to sign blocks of a witness. The key of its witness permission after AllowMultisig, so that
+/// witnesses can rotate block signing keys without touching the owner key.
+pub fn signing_key_of(witness: Address, wit: Option<&proto2::state::Witness>, allow_multisig: bool) -> Address {
+    match wit {
+        Some(wit) if allow_multisig && !wit.signature_key.is_empty() => *Address::from_bytes(&wit.signature_key),
+        _ => witness,
+    }
+}
+
 /// DB Manager.
 pub struct Manager {
     state_db: StateDB,
     genesis_block_timestamp: i64,
     blackhole: Address,
     my_witness: Vec<u8>,
-
-    block_energy_usage: i64,
+    /// Accept unsigned blocks, for generated chains of benchmarks and tests.
+    skip_block_signature: bool,
+
+    /// Resource usage of the processing block. Its energy is accumulated by `EnergyProcessor`, as the input of
+    /// adaptive energy, the rest is from transaction receipts.
+    block_resource_usage: BlockResourceUsage,
+    /// Log bloom of the processing block.
+    block_log_bloom: LogBloom,
     // TaPoS check, size = 65536, 2MB.
     ref_block_hashes: Vec<H256>,
     config: Config,
     genesis_config: GenesisConfig,
     maintenance_started_at: i64,
+    execution_stats: ExecutionStats,
+    watcher: AddressWatcher,
+    events: EventDispatcher,
+    /// Only when `[witness]` is configured.
+    producer_monitor: Option<ProducerMonitor>,
+    signed_slots: Option<SignedSlots>,
+    constant_call_cache: ConstantCallCache,
+    /// Only when `prefetch-state` is enabled.
+    prefetcher: Option<StatePrefetcher>,
+    /// Viewing keys to scan shielded notes for, with key ids.
+    #[cfg(feature = "shielded")]
+    shielded_keys: Vec<(H256, ztron::scan::ViewingKey)>,
 
     layers: usize,
+    /// Blocks committed since the last WAL sync or checkpoint of state-db.
+    unsynced_blocks: u64,
 }
 
 impl Manager {
@@ -55,8 +113,79 @@ impl Manager {
         let mut state_db = StateDB::new(&config.storage.state_data_dir);
 
         state_db.init_genesis(&genesis_config, &config.chain).unwrap();
-        let genesis_block_timestamp = genesis_config.timestamp;
 
+        // Parameter codes are checked by `Config::validate`.
+        for param_override in &config.chain.parameter_overrides {
+            warn!(
+                "chain parameter override {}={} from block #{}, state will fork from the network",
+                param_override.parameter, param_override.value, param_override.block
+            );
+        }
+
+        let (producer_monitor, signed_slots) = if config.witness.private_key.is_empty() {
+            (None, None)
+        } else if !cfg!(feature = "producer") {
+            warn!("[witness] is ignored, build with the `producer` feature to run as a witness");
+            (None, None)
+        } else {
+            let private = Private::from_hex(&config.witness.private_key).expect("invalid witness private key");
+            let signing_key = Address::from_private(&private);
+            let witness = if config.witness.address.is_empty() {
+                signing_key
+            } else {
+                config.witness.address.parse().expect("invalid witness address")
+            };
+            let allow_multisig = state_db.must_get(&keys::ChainParameter::AllowMultisig) == 1;
+            let expected_key = signing_key_of(
+                witness,
+                state_db.get(&keys::Witness(witness)).unwrap().as_ref(),
+                allow_multisig,
+            );
+            if expected_key != signing_key {
+                warn!(
+                    "private key of witness {} is not its block signing key, expected key of {}",
+                    witness, expected_key
+                );
+            }
+            info!("running as witness {}, signing key {}", witness, signing_key);
+            warn!("block production is not implemented, only missed slots of witness {} are tracked", witness);
+            let signed_slots_path = config.signed_slots_path();
+            let signed_slots = SignedSlots::open(&signed_slots_path)
+                .unwrap_or_else(|e| panic!("can not open {}: {}", signed_slots_path.display(), e));
+            (
+                Some(ProducerMonitor::new(witness, &config.producer, genesis_config.block_interval)),
+                Some(signed_slots),
+            )
+        };
+
+        #[cfg(not(feature = "shielded"))]
+        {
+            if !config.indexer.shielded_viewing_keys.is_empty() {
+                warn!("shielded-viewing-keys is ignored, build with the `shielded` feature to scan shielded notes");
+            }
+        }
+
+        let mut manager = Manager::with_state_db(state_db, config, genesis_config);
+        manager.events = EventDispatcher::new(&config.event);
+        manager.producer_monitor = producer_monitor;
+        manager.signed_slots = signed_slots;
+        if config.execution.prefetch_state {
+            manager.prefetcher = Some(StatePrefetcher::new(config));
+        }
+        manager
+    }
+
+    /// A manager on a secondary instance of state-db, for constant calls while another process applies blocks.
+    ///
+    /// Without producer, prefetcher or event delivery. Call `catch_up_with_primary` to follow the latest block.
+    pub fn new_secondary(config: &Config, genesis_config: &GenesisConfig) -> Self {
+        let tmp_path = Path::new(&config.storage.state_cache_dir).join("constant-call");
+        let _ = fs::create_dir_all(&tmp_path);
+        let state_db = StateDB::new_secondary(&config.storage.state_data_dir, tmp_path);
+        Manager::with_state_db(state_db, config, genesis_config)
+    }
+
+    fn with_state_db(state_db: StateDB, config: &Config, genesis_config: &GenesisConfig) -> Self {
         let blackhole = genesis_config
             .allocs
             .iter()
@@ -68,23 +197,79 @@ impl Manager {
 
         Manager {
             state_db,
-            genesis_block_timestamp,
+            genesis_block_timestamp: genesis_config.timestamp,
             blackhole,
             my_witness: vec![],
-            block_energy_usage: 0,
+            skip_block_signature: false,
+            block_resource_usage: BlockResourceUsage::default(),
+            block_log_bloom: LogBloom::default(),
             ref_block_hashes: Vec::with_capacity(65536),
             config: config.clone(),
             genesis_config: genesis_config.clone(),
             maintenance_started_at: 0,
+            execution_stats: ExecutionStats::default(),
+            watcher: AddressWatcher::new(&config.event),
+            events: EventDispatcher::new(&EventConfig::default()),
+            producer_monitor: None,
+            signed_slots: None,
+            constant_call_cache: ConstantCallCache::new(
+                config.execution.constant_call_cache_size,
+                Duration::from_millis(config.execution.constant_call_cache_ttl),
+            ),
+            prefetcher: None,
+            #[cfg(feature = "shielded")]
+            shielded_keys: shielded::parse_viewing_keys(&config.indexer.shielded_viewing_keys),
             layers: 0,
+            unsynced_blocks: 0,
         }
     }
 
+    /// Follow the primary state-db, only for a manager of `new_secondary`.
+    pub fn catch_up_with_primary(&self) -> Result<()> {
+        self.state_db.catch_up_with_primary()
+    }
+
     pub fn init_ref_blocks(&mut self, hashes: Vec<H256>) {
         debug!("update num of ref_hashes => {:?}", hashes.len());
         self.ref_block_hashes = hashes;
     }
 
+    pub fn execution_stats(&self) -> &ExecutionStats {
+        &self.execution_stats
+    }
+
+    /// Prefetch state of the block to be pushed next, while the current one executes. No-op unless enabled.
+    pub fn prefetch_block(&mut self, block: &IndexedBlock) {
+        if let Some(prefetcher) = self.prefetcher.as_mut() {
+            prefetcher.prefetch(block);
+        }
+    }
+
+    pub fn prefetcher(&self) -> Option<&StatePrefetcher> {
+        self.prefetcher.as_ref()
+    }
+
+    pub fn producer_monitor(&self) -> Option<&ProducerMonitor> {
+        self.producer_monitor.as_ref()
+    }
+
+    /// Pre-slot readiness check of the producer, fails when the node is lagging behind.
+    pub fn check_producer_readiness(&self) -> Result<()> {
+        let monitor = self.producer_monitor.as_ref().ok_or_else(|| new_error("not a witness"))?;
+        monitor
+            .check_readiness(self.latest_block_timestamp(), Utc::now().timestamp_millis())
+            .map_err(|e| new_error(&e))
+    }
+
+    /// Double-sign protection, must succeed right before a produced block is signed.
+    pub fn check_and_record_signing(&mut self, block: &IndexedBlock) -> Result<()> {
+        let slot = self.get_absolute_slot(block.timestamp());
+        let signed_slots = self.signed_slots.as_mut().ok_or_else(|| new_error("not a witness"))?;
+        signed_slots
+            .check_and_record(slot, block.hash())
+            .map_err(|e| new_error(&e))
+    }
+
     fn update_ref_blocks(&mut self, new_hash: H256) {
         if self.ref_block_hashes.len() < 65536 {
             self.ref_block_hashes.push(new_hash);
@@ -103,10 +288,51 @@ impl Manager {
         let key = keys::Account(self.blackhole);
         let mut blackhole_acct = self.state_db.must_get(&key);
         blackhole_acct.balance += fee;
-        self.state_db.put_key(key, blackhole_acct).unwrap();
+        self.state_db.put_key(key, blackhole_acct)?;
         Ok(())
     }
 
+    pub fn get_token_balance(&self, addr: Address, token_id: i64) -> Result<i64> {
+        Ok(self.state_db.get(&keys::AccountAsset(addr, token_id))?.unwrap_or(0))
+    }
+
+    /// Adjust token balance of an account, fails when the balance would be negative.
+    ///
+    /// A balance of zero is deleted instead of written, missing balances read as zero.
+    pub fn adjust_token_balance(&mut self, addr: Address, token_id: i64, diff: i64) -> Result<()> {
+        let balance = self.get_token_balance(addr, token_id)?;
+        match balance.checked_add(diff) {
+            Some(0) => {
+                self.state_db.delete_key(&keys::AccountAsset(addr, token_id))?;
+                Ok(())
+            }
+            Some(new_balance) if new_balance > 0 => {
+                self.state_db.put_key(keys::AccountAsset(addr, token_id), new_balance)?;
+                Ok(())
+            }
+            _ => Err(new_error("insufficient token balance")),
+        }
+    }
+
+    /// All token balances of an account, `token_id => balance`.
+    pub fn get_token_balances(&self, addr: Address) -> BTreeMap<i64, i64> {
+        let mut balances = BTreeMap::new();
+        self.state_db.scan_prefix(&addr, |key: &keys::AccountAsset, balance: &i64| {
+            balances.insert(key.1, *balance);
+        });
+        balances
+    }
+
+    /// Select transactions to be packed into a block, by the `[producer]` assembly policy. Unused until the block
+    /// production loop is implemented, see `producer`.
+    ///
+    /// Candidates are from `TransactionPool::candidates`, a sponsored bundle is packed as a whole, in order.
+    pub fn select_transactions(&self, candidates: Vec<Vec<IndexedTransaction>>) -> Result<Vec<IndexedTransaction>> {
+        let policy = AssemblyPolicy::new(&self.config.producer).map_err(|e| new_error(&e))?;
+        let energy_fee = self.state_db.must_get(&keys::ChainParameter::EnergyFee);
+        Ok(policy.select_bundles(candidates, energy_fee))
+    }
+
     fn new_layer(&mut self) {
         self.layers += 1;
         self.state_db.new_layer();
@@ -119,6 +345,41 @@ impl Manager {
         self.layers = 0;
     }
 
+    /// Durability of state-db writes of a block, by `[storage]` config.
+    fn state_write_policy(&self, block: &IndexedBlock) -> WritePolicy {
+        let storage = &self.config.storage;
+        let lag = Utc::now().timestamp_millis() - block.timestamp();
+        if storage.state_disable_wal_in_sync && lag > INITIAL_SYNC_MIN_LAG {
+            WritePolicy::NoWal
+        } else if storage.state_sync_interval == 1 {
+            WritePolicy::Sync
+        } else {
+            WritePolicy::Default
+        }
+    }
+
+    /// Sync WAL or checkpoint state-db when enough blocks are committed since the last time.
+    fn sync_state_db(&mut self) -> Result<()> {
+        self.unsynced_blocks += 1;
+        let storage = &self.config.storage;
+        match self.state_db.write_policy() {
+            WritePolicy::NoWal if self.unsynced_blocks >= storage.state_checkpoint_interval => {
+                debug!("checkpoint state-db after {} blocks without WAL", self.unsynced_blocks);
+                self.state_db.checkpoint()?;
+                self.unsynced_blocks = 0;
+            }
+            WritePolicy::Default
+                if storage.state_sync_interval > 1 && self.unsynced_blocks >= storage.state_sync_interval =>
+            {
+                self.state_db.sync_wal()?;
+                self.unsynced_blocks = 0;
+            }
+            WritePolicy::Sync => self.unsynced_blocks = 0,
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn rollback_layers(&mut self, n: usize) {
         for _ in 0..n {
             self.state_db.discard_last_layer().unwrap();
@@ -133,12 +394,11 @@ impl Manager {
         }
 
         // 1. verify witness signature
-        if self.my_witness.is_empty() || block.witness() != &*self.my_witness {
+        let is_mine = !self.my_witness.is_empty() && block.witness() == &*self.my_witness;
+        if !self.skip_block_signature && !is_mine {
             let recovered = block.recover_witness()?;
-            if self.state_db.must_get(&keys::ChainParameter::AllowMultisig) == 1 {
-                // warn!("TODO: handle multisig witness");
-            }
-            if recovered.as_bytes() != block.witness() {
+            let witness = Address::try_from(block.witness()).map_err(|_| new_error("invalid block witness address"))?;
+            if recovered != self.block_signing_key(witness)? {
                 return Err(new_error("verifying block witness signature failed"));
             }
         }
@@ -162,6 +422,8 @@ impl Manager {
         }
 
         if block.parent_hash() != self.latest_block_hash().as_bytes() {
+            // NOTE: Events of the head block are kept, it's still applied. A bad or competing block must not revoke
+            // them, only a fork switch unwinding blocks can, by `EventDispatcher::revert_blocks_from`.
             warn!("TODO: handle chain fork!");
             return Err(new_error("chain fork!"));
         }
@@ -178,11 +440,30 @@ impl Manager {
         self.new_layer();
 
         // . applyBlock = processBlock + updateFork
-        self.process_block(block)?;
+        // All logs while processing the block are tagged with block number.
+        let logger = slog_scope::logger().new(o!("block" => block.number()));
+        if let Err(e) = slog_scope::scope(&logger, || self.process_block(block)) {
+            // Partial writes of the block must not be committed along with the next block.
+            self.rollback_layers(self.layers);
+            self.events.discard_block();
+            return Err(e);
+        }
+        self.update_ref_blocks(*block.hash());
 
         // NOTE: OpenTron use different logic to handle verson fork. So `updateFork` is not removed.
         // And no need to updateFork.
+        let write_policy = self.state_write_policy(block);
+        if write_policy != self.state_db.write_policy() {
+            info!("state-db write policy => {:?}", write_policy);
+            self.state_db.set_write_policy(write_policy)?;
+            self.unsynced_blocks = 0;
+        }
         self.commit_current_layers();
+        self.sync_state_db()?;
+
+        self.events.apply_block(block.number(), *block.hash());
+        self.events
+            .solidify(self.state_db.must_get(&keys::DynamicProperty::LatestSolidBlockNumber));
 
         let elapsed = (Utc::now().timestamp_nanos() - started_at) as f64 / 1_000_000.0;
         if !block.transactions.is_empty() {
@@ -212,8 +493,10 @@ impl Manager {
             return Err(new_error("validate witness schedule error"));
         }
 
-        // 2. reset block energy statistics, used in adaptive energy
-        self.block_energy_usage = 0;
+        // 2. reset block resource statistics, energy of which is used in adaptive energy
+        self.block_resource_usage = BlockResourceUsage::default();
+        self.block_log_bloom = LogBloom::default();
+        self.apply_parameter_overrides(block)?;
 
         // NOTE: won't pre-check transaction signature. useless.
 
@@ -228,25 +511,27 @@ impl Manager {
             );
             self.process_transaction(&txn, block)?;
         }
+        self.save_log_bloom(block)?;
 
         // 4. Adaptive energy processor:
         // TODO, no energy implemented
-        if self.block_energy_usage > 0 {
+        if self.block_resource_usage.energy > 0 {
             if self.state_db.must_get(&keys::ChainParameter::AllowAdaptiveEnergy) != 0 {
-                debug!("block energy = {}", self.block_energy_usage);
+                debug!("block energy = {}", self.block_resource_usage.energy);
                 // updateTotalEnergyAverageUsage + updateAdaptiveTotalEnergyLimit
-                EnergyProcessor::new(self).update_adaptive_energy().unwrap();
+                EnergyProcessor::new(self).update_adaptive_energy()?;
             }
         }
+        self.save_block_resource_usage(block)?;
 
         // 5. Block reward
-        self.pay_reward(block);
+        self.pay_reward(block)?;
 
         // 6. Handle proposal if maintenance
         if self.state_db.must_get(&keys::DynamicProperty::NextMaintenanceTime) <= block.timestamp() {
             self.maintenance_started_at = Utc::now().timestamp_nanos();
             info!("beigin maintenance at block #{}", block.number());
-            ProposalController::new(self).process_proposals()?;
+            ProposalController::new(self).process_proposals(block)?;
         }
 
         // 7. consensus.applyBlock (DposService.applyBlock)
@@ -257,8 +542,6 @@ impl Manager {
         MaintenanceManager::new(self).apply_block(block)?;
         self.update_solid_block(block)?;
 
-        self.update_ref_blocks(*block.hash());
-
         // 8. update latest block - updateDynamicProperties
         self.state_db
             .put_key(keys::DynamicProperty::LatestBlockNumber, block.number())?;
@@ -266,6 +549,12 @@ impl Manager {
             .put_key(keys::DynamicProperty::LatestBlockTimestamp, block.timestamp())?;
         self.state_db.put_key(keys::LatestBlockHash, *block.hash())?;
 
+        // 9. chain the state commitment, over all changes of the block
+        let prev_commitment = self.state_db.get(&keys::StateCommitment)?.unwrap_or_default();
+        let changes = self.state_db.layers_diff(self.layers);
+        let commitment = commitment::next_commitment(&prev_commitment, block.number(), &changes);
+        self.state_db.put_key(keys::StateCommitment, commitment)?;
+
         Ok(())
     }
 
@@ -289,11 +578,107 @@ impl Manager {
         // 6.cusumeMultiSigFee (NOTE: move to BandwidthProcessor)
 
         // 7. transaction is executed by TransactionTrace.
-        let txn_receipt = TransactionExecutor::new(self).execute(txn, block)?;
+        let started_at = Utc::now().timestamp_nanos();
+        let logger = slog_scope::logger().new(o!("txid" => hex::encode(txn.hash.as_bytes())));
+        let txn_receipt = slog_scope::scope(&logger, || TransactionExecutor::new(self).execute(txn, block))?;
+        let elapsed = Utc::now().timestamp_nanos() - started_at;
+
+        let cntr_type = txn.raw.raw_data.as_ref().unwrap().contract.as_ref().unwrap().r#type;
+        let cntr_type = ContractType::from_i32(cntr_type).unwrap_or_default();
+        let energy_usage = txn_receipt
+            .resource_receipt
+            .as_ref()
+            .map(|r| r.energy_usage + r.origin_energy_usage)
+            .unwrap_or_default();
+        self.execution_stats.record(cntr_type, elapsed, energy_usage);
+
+        let threshold = self.config.execution.slow_transaction_threshold;
+        if threshold > 0 && elapsed >= threshold * 1_000_000 {
+            warn!(
+                "slow transaction {:?} type={:?} block=#{} energy={} time={}ms",
+                txn.hash,
+                cntr_type,
+                block.number(),
+                energy_usage,
+                elapsed as f64 / 1_000_000.0
+            );
+        }
+
+        if cfg!(feature = "events") {
+            for event in self.watcher.watch_transaction(block.number(), txn, &txn_receipt) {
+                self.events.emit(event);
+            }
+        }
+
+        if let Some(ref r) = txn_receipt.resource_receipt {
+            let usage = &mut self.block_resource_usage;
+            usage.bandwidth_usage += r.bandwidth_usage;
+            usage.bandwidth_fee += r.bandwidth_fee;
+            usage.energy_usage += r.energy_usage + r.origin_energy_usage;
+            usage.energy_fee += r.energy_fee;
+        }
+        self.block_resource_usage.num_transactions += 1;
+        for log in &txn_receipt.vm_logs {
+            self.block_log_bloom.accrue_log(log);
+        }
+        if self.config.indexer.nft && !txn_receipt.vm_logs.is_empty() {
+            self.index_nft_transfers(block, txn, &txn_receipt.vm_logs)?;
+        }
+        if self.config.indexer.account_transactions {
+            self.index_account_transaction(block, txn, &txn_receipt)?;
+        }
+        #[cfg(feature = "shielded")]
+        {
+            if !self.shielded_keys.is_empty() && !txn_receipt.vm_logs.is_empty() {
+                self.scan_shielded_notes(block, txn, &txn_receipt.vm_logs)?;
+            }
+        }
         self.state_db.put_key(keys::TransactionReceipt(txn.hash), txn_receipt)?;
         Ok(())
     }
 
+    fn block_signing_key(&self, witness: Address) -> Result<Address> {
+        let allow_multisig = self.state_db.must_get(&keys::ChainParameter::AllowMultisig) == 1;
+        let wit = self.state_db.get(&keys::Witness(witness))?;
+        Ok(signing_key_of(witness, wit.as_ref(), allow_multisig))
+    }
+
+    /// Force chain parameters from the override file, before transactions of the block. For private replays.
+    fn apply_parameter_overrides(&mut self, block: &IndexedBlock) -> Result<()> {
+        for param_override in &self.config.chain.parameter_overrides {
+            if param_override.block != block.number() {
+                continue;
+            }
+            let param = i32::try_from(param_override.parameter)
+                .ok()
+                .and_then(keys::ChainParameter::from_i32)
+                .ok_or_else(|| new_error("unknown chain parameter in override file"))?;
+            warn!("override chain parameter {:?}={} at block #{}", param, param_override.value, block.number());
+            self.state_db.put_key(param, param_override.value)?;
+        }
+        Ok(())
+    }
+
+    /// Only blocks with logs are saved. Blocks before the first indexed one are applied by an older version.
+    fn save_log_bloom(&mut self, block: &IndexedBlock) -> Result<()> {
+        if self.state_db.get(&keys::LogBloomIndexedFrom)?.is_none() {
+            self.state_db.put_key(keys::LogBloomIndexedFrom, block.number())?;
+        }
+        if !self.block_log_bloom.is_empty() {
+            let bloom = std::mem::take(&mut self.block_log_bloom);
+            self.state_db.put_key(keys::LogBloom(block.number()), bloom)?;
+        }
+        Ok(())
+    }
+
+    fn save_block_resource_usage(&mut self, block: &IndexedBlock) -> Result<()> {
+        if self.block_resource_usage.num_transactions > 0 {
+            let usage = std::mem::take(&mut self.block_resource_usage);
+            self.state_db.put_key(keys::BlockResourceUsage(block.number()), usage)?;
+        }
+        Ok(())
+    }
+
     fn validate_transaction_tapos(&self, txn: &IndexedTransaction) -> bool {
         let ref_block_hash = &txn.raw.raw_data.as_ref().unwrap().ref_block_hash;
         let ref_block_bytes = {
@@ -373,8 +758,8 @@ impl Manager {
         if wit_addrs.is_empty() {
             panic!("no witness found");
         }
-        if wit_addrs.len() > constants::MAX_NUM_OF_ACTIVE_WITNESSES {
-            let _ = wit_addrs.split_off(constants::MAX_NUM_OF_ACTIVE_WITNESSES);
+        if wit_addrs.len() > self.num_active_witnesses() {
+            let _ = wit_addrs.split_off(self.num_active_witnesses());
         }
         let mut block_nums: Vec<_> = wit_addrs
             .into_iter()
@@ -399,34 +784,34 @@ impl Manager {
                 info!("updated solid block number = {}", new_solid_block_num);
             }
             self.state_db
-                .put_key(keys::DynamicProperty::LatestSolidBlockNumber, new_solid_block_num)
-                .unwrap();
+                .put_key(keys::DynamicProperty::LatestSolidBlockNumber, new_solid_block_num)?;
         }
 
         Ok(())
     }
 
     /// Pay block producing reward.
-    fn pay_reward(&mut self, block: &IndexedBlock) {
+    fn pay_reward(&mut self, block: &IndexedBlock) -> Result<()> {
         let allow_change_delegation = self.state_db.must_get(&keys::ChainParameter::AllowChangeDelegation) != 0;
         if allow_change_delegation {
             // So-called new-style reward scheme.
             // 1. delegationService.payBlockReward
             // 2. delegationService.payStandbyWitness
-            RewardController::new(self).pay_reward(block).unwrap();
+            RewardController::new(self).pay_reward(block)?;
         } else {
             // NOTE: In this legacy reward scheme, standby witnesses will be paid during maintenance cycle.
             let wit_key = keys::Account(block.witness().try_into().unwrap());
             let mut wit_acct = self.state_db.must_get(&wit_key);
             let reward_per_block = self.state_db.must_get(&keys::ChainParameter::WitnessPayPerBlock);
             wit_acct.allowance += reward_per_block;
-            self.state_db.put_key(wit_key, wit_acct).unwrap();
+            self.state_db.put_key(wit_key, wit_acct)?;
         }
+        Ok(())
     }
 
     // * DposSlot
     fn get_absolute_slot(&self, timestamp: i64) -> i64 {
-        (timestamp - self.genesis_block_timestamp) / constants::BLOCK_PRODUCING_INTERVAL
+        (timestamp - self.genesis_block_timestamp) / self.block_interval()
     }
 
     fn get_slot(&self, timestamp: i64) -> i64 {
@@ -434,7 +819,7 @@ impl Manager {
         if timestamp < first_slot_ts {
             0
         } else {
-            (timestamp - first_slot_ts) / constants::BLOCK_PRODUCING_INTERVAL + 1
+            (timestamp - first_slot_ts) / self.block_interval() + 1
         }
     }
 
@@ -450,7 +835,7 @@ impl Manager {
         }
 
         if self.state_db.get(&keys::DynamicProperty::LatestBlockNumber).unwrap() == Some(0) {
-            return self.genesis_block_timestamp + slot * constants::BLOCK_PRODUCING_INTERVAL;
+            return self.genesis_block_timestamp + slot * self.block_interval();
         }
 
         if self.is_latest_block_maintenance() {
@@ -458,8 +843,8 @@ impl Manager {
         }
 
         let mut ts = self.latest_block_timestamp();
-        ts -= (ts - self.genesis_block_timestamp) % constants::BLOCK_PRODUCING_INTERVAL;
-        ts + constants::BLOCK_PRODUCING_INTERVAL * slot
+        ts -= (ts - self.genesis_block_timestamp) % self.block_interval();
+        ts + self.block_interval() * slot
     }
 
     fn get_active_witnesses(&self) -> Vec<Address> {
@@ -467,8 +852,8 @@ impl Manager {
         if witnesses.is_empty() {
             panic!("no witness found");
         }
-        if witnesses.len() > constants::MAX_NUM_OF_ACTIVE_WITNESSES {
-            let _ = witnesses.split_off(constants::MAX_NUM_OF_ACTIVE_WITNESSES);
+        if witnesses.len() > self.num_active_witnesses() {
+            let _ = witnesses.split_off(self.num_active_witnesses());
         }
         witnesses.into_iter().map(|wit| wit.0).collect()
     }
@@ -489,8 +874,8 @@ impl Manager {
         if witnesses.is_empty() {
             panic!("no witness found");
         }
-        if witnesses.len() > constants::MAX_NUM_OF_ACTIVE_WITNESSES {
-            let _ = witnesses.split_off(constants::MAX_NUM_OF_ACTIVE_WITNESSES);
+        if witnesses.len() > self.num_active_witnesses() {
+            let _ = witnesses.split_off(self.num_active_witnesses());
         }
         let curr_slot = self.get_absolute_slot(self.latest_block_timestamp()) + slot;
         assert!(curr_slot >= 0, "slot must be positive");
@@ -500,6 +885,18 @@ impl Manager {
         witnesses[idx].0
     }
 
+    /// Slot duration in ms, from genesis config.
+    #[inline]
+    pub fn block_interval(&self) -> i64 {
+        self.genesis_config.block_interval
+    }
+
+    /// Max number of active witnesses, from genesis config.
+    #[inline]
+    fn num_active_witnesses(&self) -> usize {
+        self.genesis_config.active_witnesses
+    }
+
     // consensus
     #[inline]
     fn is_latest_block_maintenance(&self) -> bool {
@@ -522,6 +919,13 @@ impl Manager {
     }
 }
 
+/// Max delay from the slot of a block to applying it, for a live, in-sync node, in slots.
+const MAX_LIVE_BLOCK_DELAY_SLOTS: i64 = 2;
+
+/// Min lag of a block behind wall time, for a node in initial sync, in ms. Far beyond live delays, so that state-db
+/// WAL is not turned on and off around the sync head.
+const INITIAL_SYNC_MIN_LAG: i64 = 3_600_000;
+
 /// Update witnesses' statistics, and BlockFilledSlots.
 pub struct WitnessStatisticManager<'m> {
     manager: &'m mut Manager,
@@ -552,7 +956,15 @@ impl WitnessStatisticManager<'_> {
         // NOTE: This is used for fork controller.
         wit.latest_block_version = block.version();
 
-        self.manager.state_db.put_key(keys::Witness(wit_addr), wit).unwrap();
+        self.manager.state_db.put_key(keys::Witness(wit_addr), wit)?;
+
+        let my_witness = self.manager.producer_monitor.as_ref().map(|monitor| *monitor.witness());
+        if my_witness == Some(wit_addr) {
+            self.manager.producer_monitor.as_mut().unwrap().record_produced();
+        }
+        // Missed slots of a block received long after its slot are caused by lagging sync, not worth a warning.
+        let max_delay = MAX_LIVE_BLOCK_DELAY_SLOTS * self.manager.block_interval();
+        let is_live = Utc::now().timestamp_millis() - block.timestamp() <= max_delay;
 
         let slot = if block.number() != 1 {
             self.manager.get_slot(block.timestamp())
@@ -572,7 +984,26 @@ impl WitnessStatisticManager<'_> {
                 wit_addr,
                 wit.total_missed
             );
-            self.manager.state_db.put_key(keys::Witness(wit_addr), wit).unwrap();
+            self.manager.state_db.put_key(keys::Witness(wit_addr), wit)?;
+
+            if my_witness == Some(wit_addr) {
+                let slot_timestamp = self.manager.get_slot_timestamp(i);
+                self.manager.producer_monitor.as_mut().unwrap().record_missed(slot_timestamp);
+                if is_live {
+                    warn!(
+                        "missed own slot at {}, before block #{}, check clock drift and producer lock contention",
+                        slot_timestamp,
+                        block.number()
+                    );
+                } else {
+                    debug!("missed own slot at {} while syncing", slot_timestamp);
+                }
+                self.manager.events.emit(ChainEvent::SlotMissed {
+                    block_number: block.number(),
+                    witness: wit_addr,
+                    slot_timestamp,
+                });
+            }
 
             self.filled_slots[self.filled_slots_index as usize] = 0;
             self.filled_slots_index = (self.filled_slots_index + 1) % constants::NUM_OF_BLOCK_FILLED_SLOTS as i64;
@@ -583,12 +1014,10 @@ impl WitnessStatisticManager<'_> {
 
         self.manager
             .state_db
-            .put_key(keys::DynamicProperty::BlockFilledSlotsIndex, self.filled_slots_index)
-            .unwrap();
+            .put_key(keys::DynamicProperty::BlockFilledSlotsIndex, self.filled_slots_index)?;
         self.manager
             .state_db
-            .put_key(keys::BlockFilledSlots, self.filled_slots)
-            .unwrap();
+            .put_key(keys::BlockFilledSlots, self.filled_slots)?;
 
         Ok(())
     }
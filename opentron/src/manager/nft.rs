@@ -0,0 +1,257 @@
+//! Optional TRC721/TRC1155 indexer, enabled by `indexer.nft` in config.
+//!
+//! Transfer events are recognized from logs of successful transactions. Balances, TRC721 owners and transfers are
+//! saved in the nft column, along with other state changes of the block, so they are reverted on fork as well.
+
+use ::keys::Address;
+use chain::{IndexedBlock, IndexedTransaction};
+use lazy_static::lazy_static;
+use primitive_types::{H256, U256};
+use proto2::state::{NftTransfer as NftTransferPb, TransactionLog};
+use state::keys;
+
+use super::{Manager, Result};
+
+lazy_static! {
+    /// TRC721 `Transfer(address indexed from, address indexed to, uint256 indexed tokenId)`, TRC20 has 3 topics.
+    static ref TRANSFER_TOPIC: H256 = crypto::keccak256(b"Transfer(address,address,uint256)");
+    /// TRC1155 `TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id,
+    /// uint256 value)`
+    static ref TRANSFER_SINGLE_TOPIC: H256 =
+        crypto::keccak256(b"TransferSingle(address,address,address,uint256,uint256)");
+    /// TRC1155 `TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids,
+    /// uint256[] values)`
+    static ref TRANSFER_BATCH_TOPIC: H256 =
+        crypto::keccak256(b"TransferBatch(address,address,address,uint256[],uint256[])");
+}
+
+#[derive(Debug, PartialEq)]
+struct Transfer {
+    /// None for mint.
+    from: Option<Address>,
+    /// None for burn.
+    to: Option<Address>,
+    token_id: H256,
+    amount: U256,
+    is_trc1155: bool,
+}
+
+/// Address in an ABI word, None for zero address.
+fn address_of_word(word: &[u8]) -> Option<Address> {
+    if word.iter().all(|&b| b == 0) {
+        None
+    } else {
+        Some(Address::from_tvm_bytes(&word[12..32]))
+    }
+}
+
+/// `uint256[]` at the offset of an ABI head word.
+fn decode_uint_array(data: &[u8], head: usize) -> Option<Vec<U256>> {
+    let word = |pos: usize| data.get(pos..pos + 32);
+    let offset = U256::from_big_endian(word(head)?);
+    if offset > U256::from(data.len()) {
+        return None;
+    }
+    let offset = offset.as_usize();
+    let len = U256::from_big_endian(word(offset)?);
+    if len > U256::from(data.len() / 32) {
+        return None;
+    }
+    (0..len.as_usize())
+        .map(|i| word(offset + 32 + i * 32).map(U256::from_big_endian))
+        .collect()
+}
+
+fn decode_transfers(log: &TransactionLog) -> Vec<Transfer> {
+    let topics = &log.topics;
+    if topics.len() != 4 || topics.iter().any(|topic| topic.len() != 32) {
+        return vec![];
+    }
+    let topic0 = H256::from_slice(&topics[0]);
+
+    if topic0 == *TRANSFER_TOPIC {
+        vec![Transfer {
+            from: address_of_word(&topics[1]),
+            to: address_of_word(&topics[2]),
+            token_id: H256::from_slice(&topics[3]),
+            amount: U256::one(),
+            is_trc1155: false,
+        }]
+    } else if topic0 == *TRANSFER_SINGLE_TOPIC && log.data.len() == 64 {
+        vec![Transfer {
+            from: address_of_word(&topics[2]),
+            to: address_of_word(&topics[3]),
+            token_id: H256::from_slice(&log.data[..32]),
+            amount: U256::from_big_endian(&log.data[32..]),
+            is_trc1155: true,
+        }]
+    } else if topic0 == *TRANSFER_BATCH_TOPIC {
+        match (decode_uint_array(&log.data, 0), decode_uint_array(&log.data, 32)) {
+            (Some(ids), Some(values)) if ids.len() == values.len() => ids
+                .into_iter()
+                .zip(values)
+                .map(|(id, value)| {
+                    let mut token_id = H256::zero();
+                    id.to_big_endian(token_id.as_bytes_mut());
+                    Transfer {
+                        from: address_of_word(&topics[2]),
+                        to: address_of_word(&topics[3]),
+                        token_id,
+                        amount: value,
+                        is_trc1155: true,
+                    }
+                })
+                .collect(),
+            _ => vec![],
+        }
+    } else {
+        vec![]
+    }
+}
+
+fn to_h256(val: U256) -> H256 {
+    let mut raw = H256::zero();
+    val.to_big_endian(raw.as_bytes_mut());
+    raw
+}
+
+impl Manager {
+    /// Index NFT transfers in logs of a transaction.
+    pub(super) fn index_nft_transfers(
+        &mut self,
+        block: &IndexedBlock,
+        txn: &IndexedTransaction,
+        logs: &[TransactionLog],
+    ) -> Result<()> {
+        let txn_index = block.transaction_index(txn);
+
+        for (log_index, log) in logs.iter().enumerate() {
+            if log.address.len() != 21 {
+                continue;
+            }
+            let contract = *Address::from_bytes(&log.address);
+            for (batch_index, transfer) in decode_transfers(log).into_iter().enumerate() {
+                if let Some(from) = transfer.from {
+                    let key = keys::NftBalance(from, contract, transfer.token_id);
+                    let balance = self.state_db.get(&key)?.map(|raw| U256::from_big_endian(raw.as_bytes()));
+                    // A sender without balance must be indexed from the middle of the chain.
+                    let balance = balance.unwrap_or_default().saturating_sub(transfer.amount);
+                    if balance.is_zero() {
+                        self.state_db.delete_key(&key)?;
+                    } else {
+                        self.state_db.put_key(key, to_h256(balance))?;
+                    }
+                }
+                if let Some(to) = transfer.to {
+                    let key = keys::NftBalance(to, contract, transfer.token_id);
+                    let balance = self.state_db.get(&key)?.map(|raw| U256::from_big_endian(raw.as_bytes()));
+                    let balance = balance.unwrap_or_default().saturating_add(transfer.amount);
+                    self.state_db.put_key(key, to_h256(balance))?;
+                }
+                if !transfer.is_trc1155 {
+                    match transfer.to {
+                        Some(to) => self.state_db.put_key(keys::NftOwner(contract, transfer.token_id), to)?,
+                        None => self.state_db.delete_key(&keys::NftOwner(contract, transfer.token_id))?,
+                    }
+                }
+
+                let record = NftTransferPb {
+                    transaction_id: txn.hash.as_bytes().to_vec(),
+                    from_address: transfer.from.map(|addr| addr.as_bytes().to_vec()).unwrap_or_default(),
+                    to_address: transfer.to.map(|addr| addr.as_bytes().to_vec()).unwrap_or_default(),
+                    token_id: transfer.token_id.as_bytes().to_vec(),
+                    amount: to_h256(transfer.amount).as_bytes().to_vec(),
+                    is_trc1155: transfer.is_trc1155,
+                };
+                self.state_db.put_key(
+                    keys::NftTransfer(contract, block.number(), txn_index, log_index as _, batch_index as _),
+                    record,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_of_address(addr: u8) -> Vec<u8> {
+        let mut word = vec![0u8; 32];
+        word[31] = addr;
+        word
+    }
+
+    fn word_of_uint(val: u64) -> Vec<u8> {
+        let mut word = [0u8; 32];
+        U256::from(val).to_big_endian(&mut word);
+        word.to_vec()
+    }
+
+    #[test]
+    fn test_decode_transfers() {
+        assert_eq!(
+            hex::encode(TRANSFER_TOPIC.as_bytes()),
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+
+        let trc721_mint = TransactionLog {
+            address: vec![0x41; 21],
+            topics: vec![
+                TRANSFER_TOPIC.as_bytes().to_vec(),
+                vec![0u8; 32],
+                word_of_address(0xaa),
+                word_of_uint(7),
+            ],
+            data: vec![],
+        };
+        let transfers = decode_transfers(&trc721_mint);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].from, None);
+        assert!(transfers[0].to.is_some());
+        assert_eq!(transfers[0].token_id, H256::from_low_u64_be(7));
+        assert!(!transfers[0].is_trc1155);
+
+        // TRC20 transfer, amount in data
+        let trc20 = TransactionLog {
+            topics: trc721_mint.topics[..3].to_vec(),
+            data: word_of_uint(100),
+            ..trc721_mint.clone()
+        };
+        assert!(decode_transfers(&trc20).is_empty());
+
+        let batch = TransactionLog {
+            address: vec![0x41; 21],
+            topics: vec![
+                TRANSFER_BATCH_TOPIC.as_bytes().to_vec(),
+                word_of_address(0xaa),
+                word_of_address(0xaa),
+                word_of_address(0xbb),
+            ],
+            data: [
+                word_of_uint(64),
+                word_of_uint(160),
+                word_of_uint(2),
+                word_of_uint(1),
+                word_of_uint(2),
+                word_of_uint(2),
+                word_of_uint(10),
+                word_of_uint(20),
+            ]
+            .concat(),
+        };
+        let transfers = decode_transfers(&batch);
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[1].token_id, H256::from_low_u64_be(2));
+        assert_eq!(transfers[1].amount, U256::from(20));
+        assert!(transfers[1].is_trc1155);
+
+        // malformed
+        let malformed = TransactionLog {
+            data: word_of_uint(64),
+            ..batch
+        };
+        assert!(decode_transfers(&malformed).is_empty());
+    }
+}
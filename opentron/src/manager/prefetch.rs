@@ -0,0 +1,205 @@
+//! State prefetching while syncing, see `prefetch-state` in config.
+//!
+//! While block N executes, a background thread reads accounts and contracts touched by block N+1 from a secondary
+//! instance of state-db. State-db is opened with mmap reads, so this warms the page cache shared with the primary
+//! instance. Prefetching never blocks execution, blocks are skipped when the thread falls behind.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use ::keys::Address;
+use chain::IndexedBlock;
+use config::Config;
+use log::info;
+use proto2::state::TransactionReceipt;
+use state::db::ReadOnlySolidStateDB;
+use state::keys;
+
+use super::events::{involved_addresses, AddressRole};
+
+/// The secondary instance catches up with the primary every N blocks, to see newly flushed files.
+const CATCH_UP_INTERVAL: u64 = 100;
+/// An account accessed within this number of blocks is warm.
+const WARM_WINDOW: i64 = 100;
+
+/// Accounts touched by transactions of a block, known before execution.
+///
+/// Receivers of internal transactions and TRC20 transfers are only known after execution, so they are missing.
+fn touched_addresses(block: &IndexedBlock) -> Vec<(Address, AddressRole)> {
+    let receipt = TransactionReceipt::default();
+    block
+        .transactions
+        .iter()
+        .filter_map(|txn| txn.raw.raw_data.as_ref().and_then(|raw| raw.contract.as_ref()))
+        .flat_map(|cntr| involved_addresses(cntr, &receipt))
+        .collect()
+}
+
+/// Stats of the prefetch thread.
+#[derive(Debug, Default)]
+pub struct PrefetchStats {
+    pub blocks: AtomicU64,
+    /// Blocks not prefetched, since the thread was behind.
+    pub skipped_blocks: AtomicU64,
+    pub reads: AtomicU64,
+    pub total_nanos: AtomicU64,
+}
+
+/// Access pattern of accounts, by how recently they were touched.
+#[derive(Debug, Default)]
+pub struct AccessStats {
+    /// Accounts touched within `WARM_WINDOW` blocks.
+    pub warm: u64,
+    pub cold: u64,
+    last_seen: HashMap<Address, i64>,
+}
+
+impl AccessStats {
+    fn record(&mut self, block_number: i64, touched: &[(Address, AddressRole)]) {
+        for (addr, _) in touched {
+            match self.last_seen.insert(*addr, block_number) {
+                Some(seen) if block_number - seen <= WARM_WINDOW => self.warm += 1,
+                _ => self.cold += 1,
+            }
+        }
+        if block_number % WARM_WINDOW == 0 {
+            self.last_seen.retain(|_, seen| block_number - *seen <= WARM_WINDOW);
+        }
+    }
+
+    /// Ratio of warm accesses, in [0, 1].
+    pub fn warm_ratio(&self) -> f64 {
+        if self.warm + self.cold == 0 {
+            0.0
+        } else {
+            self.warm as f64 / (self.warm + self.cold) as f64
+        }
+    }
+}
+
+pub struct StatePrefetcher {
+    sender: SyncSender<Vec<(Address, AddressRole)>>,
+    stats: Arc<PrefetchStats>,
+    access_stats: AccessStats,
+}
+
+impl StatePrefetcher {
+    pub fn new(config: &Config) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<(Address, AddressRole)>>(1);
+        let stats = Arc::new(PrefetchStats::default());
+
+        let db_path = config.storage.state_data_dir.clone();
+        // A secondary instance needs its own path, the one of state-cache-dir is used by the API.
+        let tmp_path = Path::new(&config.storage.state_cache_dir).join("prefetch");
+        let thread_stats = stats.clone();
+        thread::Builder::new()
+            .name("state-prefetch".into())
+            .spawn(move || {
+                let _ = fs::create_dir_all(&tmp_path);
+                let db = ReadOnlySolidStateDB::new(&db_path, &tmp_path);
+                // Ends when the sender is dropped along with the manager.
+                for (i, touched) in receiver.into_iter().enumerate() {
+                    if i as u64 % CATCH_UP_INTERVAL == 0 {
+                        db.catch_up_with_primary();
+                    }
+                    let started_at = Instant::now();
+                    let mut reads = 0;
+                    for (addr, role) in touched {
+                        let _ = db.get(&keys::Account(addr));
+                        reads += 1;
+                        if role == AddressRole::Contract {
+                            let _ = db.get(&keys::Contract(addr));
+                            let _ = db.get(&keys::ContractCode(addr));
+                            reads += 2;
+                        }
+                    }
+                    thread_stats.blocks.fetch_add(1, Ordering::Relaxed);
+                    thread_stats.reads.fetch_add(reads, Ordering::Relaxed);
+                    thread_stats
+                        .total_nanos
+                        .fetch_add(started_at.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                }
+            })
+            .expect("failed to spawn state prefetch thread");
+        info!("state prefetching enabled");
+
+        StatePrefetcher {
+            sender,
+            stats,
+            access_stats: AccessStats::default(),
+        }
+    }
+
+    /// Prefetch state of a block to be executed next, in background.
+    pub fn prefetch(&mut self, block: &IndexedBlock) {
+        let touched = touched_addresses(block);
+        self.access_stats.record(block.number(), &touched);
+        if self.sender.try_send(touched).is_err() {
+            self.stats.skipped_blocks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> &PrefetchStats {
+        &self.stats
+    }
+
+    pub fn access_stats(&self) -> &AccessStats {
+        &self.access_stats
+    }
+
+    pub fn report(&self) {
+        let blocks = self.stats.blocks.load(Ordering::Relaxed);
+        let total_nanos = self.stats.total_nanos.load(Ordering::Relaxed);
+        info!(
+            "prefetch stats blocks={} skipped={} reads={} avg={:.3}ms",
+            blocks,
+            self.stats.skipped_blocks.load(Ordering::Relaxed),
+            self.stats.reads.load(Ordering::Relaxed),
+            if blocks == 0 {
+                0.0
+            } else {
+                total_nanos as f64 / blocks as f64 / 1_000_000.0
+            }
+        );
+        info!(
+            "account access stats warm={} cold={} warm-ratio={:.2}% window={}blocks",
+            self.access_stats.warm,
+            self.access_stats.cold,
+            self.access_stats.warm_ratio() * 100.0,
+            WARM_WINDOW
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_stats() {
+        let alice = *Address::from_bytes(&[0x41; 21]);
+        let mut bob_raw = [0x41; 21];
+        bob_raw[20] = 0x42;
+        let bob = *Address::from_bytes(&bob_raw);
+
+        let mut stats = AccessStats::default();
+        stats.record(1, &[(alice, AddressRole::Owner), (bob, AddressRole::Receiver)]);
+        assert_eq!((stats.warm, stats.cold), (0, 2));
+
+        stats.record(2, &[(alice, AddressRole::Owner)]);
+        assert_eq!((stats.warm, stats.cold), (1, 2));
+
+        // Out of the window, and dropped from `last_seen`.
+        stats.record(1 + WARM_WINDOW + 1, &[(bob, AddressRole::Owner)]);
+        assert_eq!((stats.warm, stats.cold), (1, 3));
+        stats.record(2 * WARM_WINDOW, &[]);
+        assert!(!stats.last_seen.contains_key(&alice));
+        assert_eq!(stats.warm_ratio(), 0.25);
+    }
+}
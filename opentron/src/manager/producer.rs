@@ -0,0 +1,418 @@
+//! Block assembly policy and self-monitoring of block producers, see `[producer]` in config.
+//!
+//! NOTE: There is no block production loop yet, only missed slots are tracked, by `ProducerMonitor` while blocks are
+//! applied. The rest are hooks for the loop, which must, for each slot of the witness configured in `[witness]`:
+//!
+//! 1. `Manager::check_producer_readiness`, skip the slot when the node is lagging behind.
+//! 2. `ha::may_produce`, skip the slot unless this node holds the HA lease.
+//! 3. `Manager::select_transactions`, over `TransactionPool::candidates`.
+//! 4. `Manager::check_and_record_signing`, right before signing the block, never sign when it fails.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use ::keys::Address;
+use chain::IndexedTransaction;
+use config::ProducerConfig;
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::ContractType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionOrdering {
+    /// In arrival order.
+    Fifo,
+    /// By fee limit, descending, then in arrival order.
+    FeePriority,
+}
+
+pub struct AssemblyPolicy {
+    max_transactions: usize,
+    max_energy: i64,
+    max_bandwidth: i64,
+    ordering: TransactionOrdering,
+    allowed: HashSet<ContractType>,
+    denied: HashSet<ContractType>,
+}
+
+fn parse_contract_types(names: &[String]) -> Result<HashSet<ContractType>, String> {
+    // NOTE: prost enums have no `FromStr`, contract type numbers are less than 64.
+    let known: Vec<ContractType> = (0..64).filter_map(ContractType::from_i32).collect();
+    names
+        .iter()
+        .map(|name| {
+            known
+                .iter()
+                .find(|ty| format!("{:?}", ty) == *name)
+                .cloned()
+                .ok_or_else(|| format!("unknown contract type {:?}", name))
+        })
+        .collect()
+}
+
+impl AssemblyPolicy {
+    pub fn new(config: &ProducerConfig) -> Result<Self, String> {
+        let ordering = match &*config.transaction_ordering {
+            "fifo" => TransactionOrdering::Fifo,
+            "fee-priority" => TransactionOrdering::FeePriority,
+            other => return Err(format!("unknown transaction ordering {:?}", other)),
+        };
+        Ok(AssemblyPolicy {
+            max_transactions: config.max_transactions_per_block,
+            max_energy: config.max_block_energy,
+            max_bandwidth: config.max_block_bandwidth,
+            ordering,
+            allowed: parse_contract_types(&config.allowed_contract_types)?,
+            denied: parse_contract_types(&config.denied_contract_types)?,
+        })
+    }
+
+    fn is_allowed(&self, cntr_type: ContractType) -> bool {
+        (self.allowed.is_empty() || self.allowed.contains(&cntr_type)) && !self.denied.contains(&cntr_type)
+    }
+
+    /// Select transactions for a block, from candidates in arrival order. Energy is estimated by fee limit and
+    /// current `EnergyFee`, as the upper bound.
+    ///
+    /// Transactions exceeding the remaining budget are skipped, so that smaller ones can still be packed.
+    pub fn select(&self, candidates: Vec<IndexedTransaction>, energy_fee: i64) -> Vec<IndexedTransaction> {
+        self.select_bundles(candidates.into_iter().map(|txn| vec![txn]).collect(), energy_fee)
+    }
+
+    /// Same as `select`, but a candidate is a bundle of transactions, i.e. a sponsored transaction with its resource
+    /// delegation. A bundle is packed as a whole and in order, or skipped.
+    pub fn select_bundles(&self, candidates: Vec<Vec<IndexedTransaction>>, energy_fee: i64) -> Vec<IndexedTransaction> {
+        let mut candidates: Vec<_> = candidates
+            .into_iter()
+            .filter(|bundle| {
+                !bundle.is_empty() &&
+                    bundle.iter().all(|txn| {
+                        let cntr_type = txn
+                            .raw
+                            .raw_data
+                            .as_ref()
+                            .and_then(|raw| raw.contract.as_ref())
+                            .and_then(|cntr| ContractType::from_i32(cntr.r#type));
+                        cntr_type.map(|ty| self.is_allowed(ty)).unwrap_or(false)
+                    })
+            })
+            .collect();
+        if self.ordering == TransactionOrdering::FeePriority {
+            // stable sort, keeps arrival order of the same fee limit
+            candidates.sort_by_key(|bundle| {
+                -bundle
+                    .iter()
+                    .map(|txn| txn.raw.raw_data.as_ref().unwrap().fee_limit)
+                    .max()
+                    .unwrap_or(0)
+            });
+        }
+
+        let mut selected = vec![];
+        let mut energy = 0;
+        let mut bandwidth = 0;
+        for bundle in candidates {
+            if self.max_transactions > 0 && selected.len() >= self.max_transactions {
+                break;
+            }
+            if self.max_transactions > 0 && selected.len() + bundle.len() > self.max_transactions {
+                continue;
+            }
+            let bundle_bandwidth: i64 = bundle.iter().map(|txn| txn.raw.encoded_len() as i64).sum();
+            let bundle_energy: i64 = if energy_fee > 0 {
+                bundle
+                    .iter()
+                    .map(|txn| txn.raw.raw_data.as_ref().unwrap().fee_limit / energy_fee)
+                    .sum()
+            } else {
+                0
+            };
+            if self.max_bandwidth > 0 && bandwidth + bundle_bandwidth > self.max_bandwidth {
+                continue;
+            }
+            if self.max_energy > 0 && energy + bundle_energy > self.max_energy {
+                continue;
+            }
+            bandwidth += bundle_bandwidth;
+            energy += bundle_energy;
+            selected.extend(bundle);
+        }
+        selected
+    }
+}
+
+/// Tracks scheduled slots of this node's witness.
+pub struct ProducerMonitor {
+    witness: Address,
+    max_slots_behind: i64,
+    /// Slot duration, in ms.
+    block_interval: i64,
+    produced: i64,
+    missed: i64,
+    latest_missed_slot_timestamp: i64,
+}
+
+impl ProducerMonitor {
+    pub fn new(witness: Address, config: &ProducerConfig, block_interval: i64) -> Self {
+        ProducerMonitor {
+            witness,
+            max_slots_behind: config.max_slots_behind,
+            block_interval,
+            produced: 0,
+            missed: 0,
+            latest_missed_slot_timestamp: 0,
+        }
+    }
+
+    pub fn witness(&self) -> &Address {
+        &self.witness
+    }
+
+    /// Number of blocks produced by the witness, since the node started.
+    pub fn produced(&self) -> i64 {
+        self.produced
+    }
+
+    /// Number of slots missed by the witness, since the node started.
+    pub fn missed(&self) -> i64 {
+        self.missed
+    }
+
+    pub fn latest_missed_slot_timestamp(&self) -> i64 {
+        self.latest_missed_slot_timestamp
+    }
+
+    pub fn record_produced(&mut self) {
+        self.produced += 1;
+    }
+
+    pub fn record_missed(&mut self, slot_timestamp: i64) {
+        self.missed += 1;
+        self.latest_missed_slot_timestamp = slot_timestamp;
+    }
+
+    /// Pre-slot readiness check. Refuses to produce when the head block is too far behind the wall clock, since the
+    /// produced block would fork from the network.
+    pub fn check_readiness(&self, head_block_timestamp: i64, now: i64) -> Result<(), String> {
+        let slots_behind = (now - head_block_timestamp) / self.block_interval;
+        if self.max_slots_behind > 0 && slots_behind > self.max_slots_behind {
+            return Err(format!(
+                "head block is {} slots behind, max-slots-behind is {}",
+                slots_behind, self.max_slots_behind
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Persistent record of slots signed by the witness, the double-sign protection.
+///
+/// An append-only file of `slot block-hash` lines, synced before a signature is released, so a different block of
+/// the same slot is refused even after a restart or a crash.
+pub struct SignedSlots {
+    file: File,
+    signed: HashMap<i64, H256>,
+}
+
+impl SignedSlots {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        let mut signed = HashMap::new();
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            // A torn line of a crash was never synced, its signature was not released.
+            if let (Some(slot), Some(hash)) = (parts.next(), parts.next()) {
+                if let (Ok(slot), Ok(hash)) = (slot.parse::<i64>(), hex::decode(hash)) {
+                    if hash.len() == 32 {
+                        signed.insert(slot, H256::from_slice(&hash));
+                    }
+                }
+            }
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            // Terminate the torn line, so the next record starts on its own line.
+            writeln!(file)?;
+        }
+        Ok(SignedSlots { file, signed })
+    }
+
+    /// Block hash signed in the slot.
+    pub fn get(&self, slot: i64) -> Option<&H256> {
+        self.signed.get(&slot)
+    }
+
+    /// Record a block to be signed. Re-signing the same block is allowed, a different block of the slot is refused.
+    pub fn check_and_record(&mut self, slot: i64, block_hash: &H256) -> Result<(), String> {
+        match self.signed.get(&slot) {
+            Some(signed_hash) if signed_hash == block_hash => return Ok(()),
+            Some(signed_hash) => {
+                return Err(format!(
+                    "double signing refused, slot {} is signed with block {:?}",
+                    slot, signed_hash
+                ))
+            }
+            None => {}
+        }
+        writeln!(self.file, "{} {}", slot, hex::encode(block_hash.as_bytes()))
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| format!("can not record signed slot: {}", e))?;
+        self.signed.insert(slot, *block_hash);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto2::chain::{transaction, Transaction};
+
+    fn new_transaction(cntr_type: ContractType, fee_limit: i64) -> IndexedTransaction {
+        IndexedTransaction::from_raw(Transaction {
+            raw_data: Some(transaction::Raw {
+                contract: Some(transaction::Contract {
+                    r#type: cntr_type as i32,
+                    ..Default::default()
+                }),
+                fee_limit,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_assembly_policy() {
+        let txns = vec![
+            new_transaction(ContractType::TransferContract, 0),
+            new_transaction(ContractType::TriggerSmartContract, 1_000),
+            new_transaction(ContractType::TriggerSmartContract, 5_000),
+            new_transaction(ContractType::VoteWitnessContract, 0),
+        ];
+
+        let policy = AssemblyPolicy::new(&ProducerConfig {
+            transaction_ordering: "fee-priority".into(),
+            denied_contract_types: vec!["VoteWitnessContract".into()],
+            max_block_energy: 500,
+            ..Default::default()
+        })
+        .unwrap();
+        let selected = policy.select(txns.clone(), 10);
+        assert_eq!(selected.len(), 2);
+        // 500 energy of the highest fee limit is packed, and the other trigger is over budget.
+        assert_eq!(selected[0].hash, txns[2].hash);
+        assert_eq!(selected[1].hash, txns[0].hash);
+
+        let policy = AssemblyPolicy::new(&ProducerConfig {
+            max_transactions_per_block: 2,
+            allowed_contract_types: vec!["TriggerSmartContract".into()],
+            ..Default::default()
+        })
+        .unwrap();
+        let selected = policy.select(txns.clone(), 10);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].hash, txns[1].hash);
+        assert_eq!(selected[1].hash, txns[2].hash);
+
+        // A bundle is packed as a whole, or skipped.
+        let policy = AssemblyPolicy::new(&ProducerConfig {
+            max_transactions_per_block: 3,
+            denied_contract_types: vec!["VoteWitnessContract".into()],
+            ..Default::default()
+        })
+        .unwrap();
+        let bundles = vec![
+            vec![txns[0].clone(), txns[1].clone()],
+            vec![txns[2].clone(), txns[3].clone()],
+            vec![txns[1].clone(), txns[2].clone()],
+        ];
+        let selected = policy.select_bundles(bundles, 10);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].hash, txns[0].hash);
+        assert_eq!(selected[1].hash, txns[1].hash);
+
+        assert!(AssemblyPolicy::new(&ProducerConfig {
+            denied_contract_types: vec!["NoSuchContract".into()],
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_producer_monitor() {
+        let witness: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let mut monitor = ProducerMonitor::new(
+            witness,
+            &ProducerConfig {
+                max_slots_behind: 10,
+                ..Default::default()
+            },
+            3_000,
+        );
+        monitor.record_produced();
+        monitor.record_missed(1_600_000_003_000);
+        assert_eq!(monitor.produced(), 1);
+        assert_eq!(monitor.missed(), 1);
+        assert_eq!(monitor.latest_missed_slot_timestamp(), 1_600_000_003_000);
+
+        let head_ts = 1_600_000_000_000;
+        assert!(monitor.check_readiness(head_ts, head_ts + 30_000).is_ok());
+        assert!(monitor.check_readiness(head_ts, head_ts + 33_000).is_err());
+
+        let monitor = ProducerMonitor::new(witness, &ProducerConfig::default(), 3_000);
+        assert!(monitor.check_readiness(head_ts, head_ts + 3_600_000).is_ok());
+
+        // A private chain with 1s slots.
+        let monitor = ProducerMonitor::new(
+            witness,
+            &ProducerConfig {
+                max_slots_behind: 10,
+                ..Default::default()
+            },
+            1_000,
+        );
+        assert!(monitor.check_readiness(head_ts, head_ts + 10_000).is_ok());
+        assert!(monitor.check_readiness(head_ts, head_ts + 11_000).is_err());
+    }
+
+    #[test]
+    fn test_signed_slots() {
+        let path = std::env::temp_dir().join(format!("opentron-signed-slots-{}", std::process::id()));
+        let block_a = H256::repeat_byte(0xaa);
+        let block_b = H256::repeat_byte(0xbb);
+
+        let mut signed_slots = SignedSlots::open(&path).unwrap();
+        assert!(signed_slots.check_and_record(100, &block_a).is_ok());
+        assert!(signed_slots.check_and_record(100, &block_a).is_ok());
+        assert!(signed_slots.check_and_record(100, &block_b).is_err());
+        assert!(signed_slots.check_and_record(101, &block_b).is_ok());
+        drop(signed_slots);
+
+        // A torn line is ignored, records survive restarts.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"102 aabb")
+            .unwrap();
+        let mut signed_slots = SignedSlots::open(&path).unwrap();
+        assert_eq!(signed_slots.get(100), Some(&block_a));
+        assert_eq!(signed_slots.get(102), None);
+        assert!(signed_slots.check_and_record(101, &block_a).is_err());
+        assert!(signed_slots.check_and_record(101, &block_b).is_ok());
+        assert!(signed_slots.check_and_record(102, &block_b).is_ok());
+        drop(signed_slots);
+
+        let signed_slots = SignedSlots::open(&path).unwrap();
+        assert_eq!(signed_slots.get(102), Some(&block_b));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! Dry-run block re-execution, for debugging consensus mismatches.
+//!
+//! NOTE: state-db keeps only the latest state, without undo or archive data. So only the next block of the current
+//! state-db head can be replayed, against the pre-state it would be applied to. Rewinding is not possible: layers of
+//! unsolidified blocks live in memory of a running manager, and are gone when the node is stopped for debugging.
+//!
+//! To replay an older block, restore a copy of state-db taken at its parent block.
+
+use chain::IndexedBlock;
+use log::debug;
+use primitive_types::H256;
+use proto2::chain::transaction::result::ContractStatus;
+use proto2::chain::ContractType;
+use proto2::state::TransactionReceipt;
+use slog::o;
+use state::keys;
+
+use super::{new_error, Manager, Result};
+
+/// Execution trace of a transaction.
+#[derive(Debug)]
+pub struct TransactionTrace {
+    pub hash: H256,
+    pub contract_type: ContractType,
+    /// Contract status embedded in the block, by the block producer.
+    pub expected_status: ContractStatus,
+    /// None if the transaction is not executed, since an earlier error aborted the block.
+    pub receipt: Option<TransactionReceipt>,
+}
+
+/// A changed state-db entry. None for non-existent or deleted.
+#[derive(Debug)]
+pub struct StateChange {
+    pub col: usize,
+    pub key: Vec<u8>,
+    pub old: Option<Vec<u8>>,
+    pub new: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub struct ReplayReport {
+    pub block_number: i64,
+    pub traces: Vec<TransactionTrace>,
+    pub changes: Vec<StateChange>,
+    /// State commitment after the block, to be compared with other nodes. None if the block is aborted.
+    pub state_commitment: Option<H256>,
+    /// Error that aborted the block, including execution result mismatches.
+    pub error: Option<String>,
+}
+
+impl Manager {
+    /// Execute the block on top of current state, then discard all changes.
+    ///
+    /// State-db changes, pending chain events and TaPoS ref blocks are reverted. Other in-memory states of the manager,
+    /// i.e. execution stats and witness monitor, are left as is.
+    pub fn replay_block(&mut self, block: &IndexedBlock) -> Result<ReplayReport> {
+        let next_block_number = self.latest_block_number() + 1;
+        if block.number() < next_block_number {
+            return Err(new_error(&format!(
+                "block #{} is already applied, state-db keeps no undo data to rewind to its pre-state, \
+                 only block #{} can be replayed",
+                block.number(),
+                next_block_number
+            )));
+        }
+        if block.number() > next_block_number {
+            return Err(new_error(&format!(
+                "block #{} is ahead of state-db head #{}, only block #{} can be replayed",
+                block.number(),
+                next_block_number - 1,
+                next_block_number
+            )));
+        }
+        if block.parent_hash() != self.latest_block_hash().as_bytes() {
+            return Err(new_error("parent hash mismatch"));
+        }
+
+        self.new_layer();
+
+        let logger = slog_scope::logger().new(o!("block" => block.number(), "replay" => true));
+        let error = slog_scope::scope(&logger, || self.process_block(block))
+            .err()
+            .map(|e| e.to_string());
+
+        let mut traces = vec![];
+        for txn in &block.transactions {
+            let raw = txn.raw.raw_data.as_ref().unwrap();
+            let contract_type = raw
+                .contract
+                .as_ref()
+                .and_then(|cntr| ContractType::from_i32(cntr.r#type))
+                .unwrap_or_default();
+            let expected_status = txn
+                .raw
+                .result
+                .get(0)
+                .and_then(|ret| ContractStatus::from_i32(ret.contract_status))
+                .unwrap_or_default();
+            let receipt = self.state_db.get(&keys::TransactionReceipt(txn.hash))?;
+            traces.push(TransactionTrace {
+                hash: txn.hash,
+                contract_type,
+                expected_status,
+                receipt,
+            });
+        }
+
+        let changes = self
+            .state_db
+            .layers_diff(self.layers)
+            .into_iter()
+            .map(|(col, key, old, new)| StateChange { col, key, old, new })
+            .collect::<Vec<_>>();
+        debug!("block #{} replayed, {} state changes", block.number(), changes.len());
+        let state_commitment = if error.is_none() {
+            self.state_db.get(&keys::StateCommitment)?
+        } else {
+            None
+        };
+
+        self.events.discard_block();
+        self.rollback_layers(self.layers);
+
+        Ok(ReplayReport {
+            block_number: block.number(),
+            traces,
+            changes,
+            state_commitment,
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proto2::chain::{block_header, Block, BlockHeader};
+
+    use super::*;
+    use crate::manager::testing::TestManager;
+
+    fn block_of_number(number: i64) -> IndexedBlock {
+        let raw_block = Block {
+            block_header: Some(BlockHeader {
+                raw_data: Some(block_header::Raw {
+                    number,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        IndexedBlock::from_raw(raw_block)
+    }
+
+    #[test]
+    fn test_replay_block_out_of_range() {
+        let mut manager = TestManager::new();
+        let head = manager.latest_block_number();
+
+        let err = manager.replay_block(&block_of_number(head)).unwrap_err();
+        assert!(err.to_string().contains("already applied"), "{}", err);
+
+        let err = manager.replay_block(&block_of_number(head + 2)).unwrap_err();
+        assert!(err.to_string().contains("ahead of state-db head"), "{}", err);
+    }
+}
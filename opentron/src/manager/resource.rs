@@ -24,15 +24,6 @@ pub struct BandwidthProcessor<'a, C> {
     acct: Account,
 }
 
-impl<C> Drop for BandwidthProcessor<'_, C> {
-    fn drop(&mut self) {
-        self.manager
-            .state_db
-            .put_key(keys::Account(self.addr), self.acct.clone())
-            .expect("error while saving bandwidth");
-    }
-}
-
 impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
     pub fn new<'a>(
         manager: &'a mut Manager,
@@ -55,7 +46,18 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
         })
     }
 
+    /// Consume bandwidth of the owner, then save the owner account.
     pub fn consume(mut self, ctx: &mut TransactionContext) -> Result<(), String> {
+        let ret = self.consume_bandwidth(ctx);
+        // NOTE: The owner account is saved even if bandwidth is insufficient.
+        self.manager
+            .state_db
+            .put_key(keys::Account(self.addr), self.acct)
+            .map_err(|_| "db insert error")?;
+        ret
+    }
+
+    fn consume_bandwidth(&mut self, ctx: &mut TransactionContext) -> Result<(), String> {
         let byte_size = if self.manager.state_db.must_get(&keys::ChainParameter::AllowTvm) == 1 {
             if self.txn.raw.result.is_empty() {
                 self.txn.raw.encoded_len() + constants::MAX_TRANSACTION_RESULT_SIZE
@@ -78,6 +80,19 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
                 .map_err(|_| "insufficient balance to multisig")?;
         }
 
+        // Renamed: consumeMemoFee
+        let memo_fee = self.manager.state_db.must_get(&keys::ChainParameter::MemoFee);
+        if memo_fee > 0 && !self.txn.raw.raw_data.as_ref().unwrap().data.is_empty() {
+            debug!("consume memo fee");
+            self.acct
+                .adjust_balance(-memo_fee)
+                .map_err(|_| "insufficient balance to memo fee")?;
+            self.manager
+                .add_to_blackhole(memo_fee)
+                .map_err(|_| "db insert error")?;
+            ctx.memo_fee = memo_fee;
+        }
+
         // NOTE: `now` is not a timestamp, it's a `slot`.
         let now = self.manager.get_head_slot();
 
@@ -95,7 +110,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
 
         // NOTE: Since Rust has no simple downcast support, use unsafe here.
         if self.cntr.type_code() == ContractType::TransferAssetContract &&
-            self.consume_asset_bandwidth(unsafe { std::mem::transmute(self.cntr) }, byte_size, now, ctx)
+            self.consume_asset_bandwidth(unsafe { std::mem::transmute(self.cntr) }, byte_size, now, ctx)?
         {
             return Ok(());
         }
@@ -106,7 +121,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
             return Ok(());
         }
 
-        if self.consume_free_bandwidth(byte_size, now, ctx) {
+        if self.consume_free_bandwidth(byte_size, now, ctx)? {
             return Ok(());
         }
 
@@ -164,7 +179,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
     }
 
     // Renamed: useFreeNet.
-    fn consume_free_bandwidth(&mut self, nbytes: i64, now: i64, _ctx: &mut TransactionContext) -> bool {
+    fn consume_free_bandwidth(&mut self, nbytes: i64, now: i64, _ctx: &mut TransactionContext) -> Result<bool, String> {
         let free_bw_limit = constants::FREE_BANDWIDTH;
         let free_bw_usage = self.acct.resource().free_bandwidth_used;
         let mut free_bw_latest_slot = self.acct.resource().free_bandwidth_latest_slot;
@@ -175,7 +190,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
                 "free BW is insufficient {}/{}, require {}, will burn",
                 new_free_bw_usage, free_bw_limit, nbytes
             );
-            return false;
+            return Ok(false);
         }
 
         // global free bandwidth
@@ -195,7 +210,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
         let mut new_g_bw_usage = adjust_usage(g_bw_usage, 0, g_bw_latest_slot, now);
         if nbytes > g_bw_limit - new_g_bw_usage {
             debug!("global free BW is insufficient");
-            return false;
+            return Ok(false);
         }
 
         free_bw_latest_slot = now;
@@ -214,13 +229,13 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
         self.manager
             .state_db
             .put_key(keys::DynamicProperty::GlobalFreeBandwidthUsed, new_g_bw_usage)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
         self.manager
             .state_db
             .put_key(keys::DynamicProperty::GlobalFreeBandwidthLatestSlot, g_bw_latest_slot)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
 
-        true
+        Ok(true)
     }
 
     // useAssetAccountNet
@@ -230,7 +245,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
         nbytes: i64,
         now: i64,
         _ctx: &mut TransactionContext,
-    ) -> bool {
+    ) -> Result<bool, String> {
         let allow_same_token_name = self
             .manager
             .state_db
@@ -251,7 +266,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
             // Avoid calling `consume_frozen_bandwidth` twice.
             //
             // return self.consume_frozen_bandwidth(addr, acct, nbytes, now, ctx);
-            return false;
+            return Ok(false);
         }
 
         // check public limit
@@ -263,7 +278,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
         );
         if nbytes > asset.public_free_asset_bandwidth_limit - new_public_free_asset_bw_usage {
             debug!("asset {} public free BW is insufficient", token_id);
-            return false;
+            return Ok(false);
         }
 
         // check pre-account-limit
@@ -286,7 +301,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
 
         if nbytes > asset.free_asset_bandwidth_limit - new_free_asset_bw_usage {
             debug!("asset {} free BW is insufficient", token_id);
-            return false;
+            return Ok(false);
         }
 
         // check issuer's frozen bw
@@ -303,7 +318,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
 
         if nbytes > issuer_bw_limit - new_issuer_bw_usage {
             debug!("asset {} issuer bandwidth is insufficient", token_id);
-            return false;
+            return Ok(false);
         }
 
         // now consume
@@ -345,10 +360,13 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
         self.manager
             .state_db
             .put_key(keys::Account(issuer_addr), issuer_acct)
-            .unwrap();
-        self.manager.state_db.put_key(keys::Asset(token_id), asset).unwrap();
+            .map_err(|_| "db insert error")?;
+        self.manager
+            .state_db
+            .put_key(keys::Asset(token_id), asset)
+            .map_err(|_| "db insert error")?;
 
-        true
+        Ok(true)
     }
 
     /// `consumeFeeForCreateNewAccount`
@@ -449,7 +467,7 @@ fn divide_ceil(numerator: i64, denominator: i64) -> i64 {
 }
 
 // Renamed: increase.
-fn adjust_usage(latest_usage: i64, new_usage: i64, latest_slot: i64, new_slot: i64) -> i64 {
+pub(super) fn adjust_usage(latest_usage: i64, new_usage: i64, latest_slot: i64, new_slot: i64) -> i64 {
     const WINDOW_SIZE: i64 = constants::RESOURCE_WINDOW_SIZE / constants::BLOCK_PRODUCING_INTERVAL;
     const PRECISION: i64 = constants::RESOURCE_PRECISION;
 
@@ -482,6 +500,8 @@ impl EnergyProcessor<'_> {
         EnergyProcessor { manager }
     }
 
+    /// Consume energy of a contract execution. Returns false if the burnt fee is capped at `fee_limit`, i.e. the
+    /// execution runs out of energy.
     pub fn consume(
         &mut self,
         caller: Address,
@@ -490,9 +510,9 @@ impl EnergyProcessor<'_> {
         caller_percent: i64,
         origin_energy_limit: i64,
         ctx: &mut TransactionContext,
-    ) -> Result<(), String> {
+    ) -> Result<bool, String> {
         if energy_used <= 0 {
-            return Ok(());
+            return Ok(true);
         }
 
         let now = self.manager.get_head_slot();
@@ -509,15 +529,16 @@ impl EnergyProcessor<'_> {
         let caller_usage = energy_used - origin_usage;
 
         if origin_usage > 0 {
-            assert!(self.consume_frozen_energy(origin, origin_acct, origin_usage, now));
+            assert!(self.consume_frozen_energy(origin, origin_acct, origin_usage, now)?);
             ctx.origin_energy_usage = origin_usage;
         }
+        let mut within_fee_limit = true;
         if caller_usage > 0 {
-            self.consume_energy(caller, caller_acct, caller_usage, now, ctx)?;
+            within_fee_limit = self.consume_energy(caller, caller_acct, caller_usage, now, ctx)?;
         }
         debug!("E usage: caller={} origin={}", caller_usage, origin_usage);
 
-        Ok(())
+        Ok(within_fee_limit)
     }
 
     fn consume_energy(
@@ -527,43 +548,47 @@ impl EnergyProcessor<'_> {
         energy_used: i64,
         now: i64,
         ctx: &mut TransactionContext,
-    ) -> Result<(), String> {
-        if self.consume_frozen_energy(addr, acct.clone(), energy_used, now) {
+    ) -> Result<bool, String> {
+        if self.consume_frozen_energy(addr, acct.clone(), energy_used, now)? {
             ctx.energy_usage = energy_used;
-            return Ok(());
+            return Ok(true);
         }
 
+        // Validate the burnt part before writing, so that a failed transaction leaves no partial consumption.
         let consumed = self.consume_remain_frozen_energy(&mut acct, now);
-        if consumed > 0 {
-            self.manager
-                .state_db
-                .put_key(keys::Account(addr), acct.clone())
-                .unwrap();
-        }
         assert!(consumed < energy_used);
 
         // Will consume burnt energy
         let energy_price = self.manager.state_db.must_get(&keys::ChainParameter::EnergyFee);
         let energy_fee = (energy_used - consumed) * energy_price;
 
-        ctx.energy_fee = energy_fee;
-        ctx.energy_usage = consumed;
-
+        // Never burn more than the caller authorized, the execution runs out of energy instead.
+        let within_fee_limit = energy_fee <= ctx.fee_limit;
+        let energy_fee = energy_fee.min(ctx.fee_limit);
         if acct.adjust_balance(-energy_fee).is_err() {
             return Err("insufficient balance to burn for energy".into());
         }
 
+        ctx.energy_fee = energy_fee;
+        ctx.energy_usage = consumed;
+
         debug!("E usage: frozen={} burnt={}", consumed, energy_used - consumed);
 
-        self.manager.add_to_blackhole(energy_fee).unwrap();
-        self.manager.state_db.put_key(keys::Account(addr), acct).unwrap();
+        self.manager
+            .add_to_blackhole(energy_fee)
+            .map_err(|_| "db insert error")?;
+        self.manager
+            .state_db
+            .put_key(keys::Account(addr), acct)
+            .map_err(|_| "db insert error")?;
 
-        self.manager.block_energy_usage += energy_used - consumed;
+        // Both frozen and burnt energy.
+        self.manager.block_resource_usage.energy += energy_used;
 
-        Ok(())
+        Ok(within_fee_limit)
     }
 
-    // Consume all remain E, return how much it consumed.
+    // Consume all remain E of the account, return how much it consumed. Not written to state-db.
     fn consume_remain_frozen_energy(&mut self, acct: &mut Account, now: i64) -> i64 {
         let e_usage = acct.resource().energy_used;
         let e_latest_slot = acct.resource().energy_latest_slot;
@@ -590,13 +615,17 @@ impl EnergyProcessor<'_> {
         acct.resource_mut().energy_used = new_e_usage;
         acct.resource_mut().energy_latest_slot = now;
 
-        self.manager.block_energy_usage += energy_remain;
-
         energy_remain
     }
 
     // useEnergy
-    fn consume_frozen_energy(&mut self, addr: Address, mut acct: Account, energy_used: i64, now: i64) -> bool {
+    fn consume_frozen_energy(
+        &mut self,
+        addr: Address,
+        mut acct: Account,
+        energy_used: i64,
+        now: i64,
+    ) -> Result<bool, String> {
         let e_usage = acct.resource().energy_used;
         let e_latest_slot = acct.resource().energy_latest_slot;
         let e_limit = EnergyUtil::new(self.manager).calculate_global_energy_limit(&acct);
@@ -604,7 +633,7 @@ impl EnergyProcessor<'_> {
         let mut new_e_usage = adjust_usage(e_usage, 0, e_latest_slot, now);
 
         if energy_used > (e_limit - new_e_usage) {
-            return false;
+            return Ok(false);
         }
 
         let latest_op_ts = self
@@ -618,10 +647,13 @@ impl EnergyProcessor<'_> {
         acct.resource_mut().energy_latest_slot = now;
         debug!("E usage: {}/{} (+{})", new_e_usage, e_limit, energy_used);
 
-        self.manager.state_db.put_key(keys::Account(addr), acct).unwrap();
-        self.manager.block_energy_usage += energy_used;
+        self.manager
+            .state_db
+            .put_key(keys::Account(addr), acct)
+            .map_err(|_| "db insert error")?;
+        self.manager.block_resource_usage.energy += energy_used;
 
-        true
+        Ok(true)
     }
 
     // updateTotalEnergyAverageUsage + updateAdaptiveTotalEnergyLimit
@@ -629,7 +661,7 @@ impl EnergyProcessor<'_> {
         // updateTotalEnergyAverageUsage
         let now = self.manager.get_head_slot();
 
-        let block_energy_usage = self.manager.block_energy_usage;
+        let block_energy_usage = self.manager.block_resource_usage.energy;
         let total_energy_average_usage = self
             .manager
             .state_db
@@ -657,11 +689,11 @@ impl EnergyProcessor<'_> {
                 keys::DynamicProperty::TotalEnergyAverageUsage,
                 new_total_energy_average_usage,
             )
-            .unwrap();
+            .map_err(|_| "db insert error")?;
         self.manager
             .state_db
             .put_key(keys::DynamicProperty::TotalEnergyAverageSlot, now)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
 
         // updateAdaptiveTotalEnergyLimit
         let total_energy_target_limit = self
@@ -691,7 +723,7 @@ impl EnergyProcessor<'_> {
         self.manager
             .state_db
             .put_key(keys::ChainParameter::TotalEnergyCurrentLimit, new_curr_limit)
-            .unwrap();
+            .map_err(|_| "db insert error")?;
 
         debug!(
             "total energy current limit update: {} => {}",
@@ -758,3 +790,62 @@ impl EnergyUtil<'_> {
         return (e_weight as f64 * (total_e_limit as f64 / total_e_weight as f64)) as i64;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{empty_block_and_transaction, TestManager};
+    use super::*;
+    use state::keys::DynamicProperty;
+
+    #[test]
+    fn test_adaptive_energy_input() {
+        let mut manager = TestManager::new();
+        manager.state_db.new_layer();
+        let owner: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let mut acct = Account::new(0);
+        acct.frozen_amount_for_energy = 100_000_000_000;
+        manager.state_db.put_key(keys::Account(owner), acct.clone()).unwrap();
+        manager
+            .state_db
+            .put_key(DynamicProperty::TotalEnergyWeight, 100_000)
+            .unwrap();
+
+        // Energy consumed by accounts is the block energy usage, recorded and fed into adaptive energy alike.
+        assert!(EnergyProcessor::new(&mut manager)
+            .consume_frozen_energy(owner, acct, 1_000, 0)
+            .unwrap());
+        assert_eq!(manager.block_resource_usage.energy, 1_000);
+
+        let average_usage = manager.state_db.must_get(&DynamicProperty::TotalEnergyAverageUsage);
+        EnergyProcessor::new(&mut manager).update_adaptive_energy().unwrap();
+        assert!(manager.state_db.must_get(&DynamicProperty::TotalEnergyAverageUsage) > average_usage);
+    }
+
+    #[test]
+    fn test_energy_fee_capped_at_fee_limit() {
+        let mut manager = TestManager::new();
+        manager.state_db.new_layer();
+        let owner: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let mut acct = Account::new(0);
+        acct.balance = 1_000_000;
+        manager.state_db.put_key(keys::Account(owner), acct).unwrap();
+        manager.state_db.put_key(keys::ChainParameter::EnergyFee, 100).unwrap();
+
+        let (header, txn) = empty_block_and_transaction();
+        let mut ctx = TransactionContext::new(&header, &txn);
+        ctx.fee_limit = 5_000;
+
+        // 100 energy burns 10_000 sun, over fee_limit. Out of energy, not a failed block.
+        assert!(!EnergyProcessor::new(&mut manager)
+            .consume(owner, owner, 100, 0, 0, &mut ctx)
+            .unwrap());
+        assert_eq!(ctx.energy_fee, 5_000);
+        assert_eq!(manager.state_db.must_get(&keys::Account(owner)).balance, 995_000);
+
+        ctx.fee_limit = 10_000;
+        assert!(EnergyProcessor::new(&mut manager)
+            .consume(owner, owner, 100, 0, 0, &mut ctx)
+            .unwrap());
+        assert_eq!(ctx.energy_fee, 10_000);
+    }
+}
@@ -0,0 +1,183 @@
+//! Optional shielded TRC20 note scanner, enabled by `indexer.shielded-viewing-keys` in config and the `shielded`
+//! feature.
+//!
+//! Outputs in `*NewLeaf` events are trial decrypted with each viewing key, detected notes are saved in the
+//! shielded-note column, and marked as spent when their nullifiers show up in `NoteSpent` events. Like other
+//! indexes, they are saved along with state changes of the block, so they are reverted on fork as well.
+
+use ::keys::Address;
+use chain::{IndexedBlock, IndexedTransaction};
+use lazy_static::lazy_static;
+use log::info;
+use primitive_types::{H256, U256};
+use proto2::state::{ShieldedNote as ShieldedNotePb, TransactionLog};
+use state::keys;
+use ztron::scan::{try_decrypt_note, ViewingKey};
+
+use super::{Manager, Result};
+
+lazy_static! {
+    /// `MintNewLeaf(uint256 position, bytes32 cm, bytes32 cv, bytes32 epk, bytes32[21] c)`, and the same for
+    /// transfer and burn outputs.
+    static ref NEW_LEAF_TOPICS: [H256; 3] = [
+        crypto::keccak256(b"MintNewLeaf(uint256,bytes32,bytes32,bytes32,bytes32[21])"),
+        crypto::keccak256(b"TransferNewLeaf(uint256,bytes32,bytes32,bytes32,bytes32[21])"),
+        crypto::keccak256(b"BurnNewLeaf(uint256,bytes32,bytes32,bytes32,bytes32[21])"),
+    ];
+    /// `NoteSpent(bytes32 nf)`
+    static ref NOTE_SPENT_TOPIC: H256 = crypto::keccak256(b"NoteSpent(bytes32)");
+}
+
+/// position, cm, cv, epk, then `c` of 21 words, `enc_ciphertext || out_ciphertext` with padding.
+const NEW_LEAF_DATA_SIZE: usize = 32 * 4 + 32 * 21;
+
+#[derive(Debug, PartialEq)]
+enum ShieldedEvent<'a> {
+    NewLeaf {
+        position: u64,
+        cm: &'a [u8],
+        epk: &'a [u8],
+        c: &'a [u8],
+    },
+    NoteSpent {
+        nullifier: H256,
+    },
+}
+
+fn decode_shielded_event(log: &TransactionLog) -> Option<ShieldedEvent> {
+    if log.topics.len() != 1 || log.topics[0].len() != 32 {
+        return None;
+    }
+    let topic0 = H256::from_slice(&log.topics[0]);
+    let data = &log.data;
+
+    if NEW_LEAF_TOPICS.contains(&topic0) && data.len() == NEW_LEAF_DATA_SIZE {
+        let position = U256::from_big_endian(&data[..32]);
+        if position > U256::from(u64::max_value()) {
+            return None;
+        }
+        Some(ShieldedEvent::NewLeaf {
+            position: position.as_u64(),
+            cm: &data[32..64],
+            epk: &data[96..128],
+            c: &data[128..],
+        })
+    } else if topic0 == *NOTE_SPENT_TOPIC && data.len() == 32 {
+        Some(ShieldedEvent::NoteSpent {
+            nullifier: H256::from_slice(data),
+        })
+    } else {
+        None
+    }
+}
+
+/// Parse configured viewing keys, with their key ids.
+pub(super) fn parse_viewing_keys(keys: &[String]) -> Vec<(H256, ViewingKey)> {
+    keys.iter()
+        .map(|key| {
+            let raw = hex::decode(key).expect("invalid shielded viewing key hex");
+            let viewing_key = ViewingKey::from_slice(&raw).expect("invalid shielded viewing key");
+            let key_id = crypto::sha256(&raw);
+            info!("scanning shielded notes of viewing key {:?}", key_id);
+            (key_id, viewing_key)
+        })
+        .collect()
+}
+
+impl Manager {
+    /// Detect incoming notes and spent nullifiers of configured viewing keys, in logs of a transaction.
+    pub(super) fn scan_shielded_notes(
+        &mut self,
+        block: &IndexedBlock,
+        txn: &IndexedTransaction,
+        logs: &[TransactionLog],
+    ) -> Result<()> {
+        for log in logs {
+            if log.address.len() != 21 {
+                continue;
+            }
+            let contract = *Address::from_bytes(&log.address);
+            match decode_shielded_event(log) {
+                Some(ShieldedEvent::NewLeaf { position, cm, epk, c }) => {
+                    for (key_id, viewing_key) in &self.shielded_keys {
+                        let note = match try_decrypt_note(viewing_key, position, cm, epk, c) {
+                            Some(note) => note,
+                            None => continue,
+                        };
+                        let key = keys::ShieldedNote(*key_id, contract, position);
+                        self.state_db
+                            .put_key(keys::ShieldedNullifier(H256::from_slice(&note.nullifier)), key.clone())?;
+                        let record = ShieldedNotePb {
+                            transaction_id: txn.hash.as_bytes().to_vec(),
+                            block_number: block.number(),
+                            value: note.value as i64,
+                            payment_address: note.payment_address.to_string(),
+                            rcm: note.rcm,
+                            memo: note.memo,
+                            cm: cm.to_vec(),
+                            nullifier: note.nullifier,
+                            spent_by: vec![],
+                        };
+                        self.state_db.put_key(key, record)?;
+                    }
+                }
+                Some(ShieldedEvent::NoteSpent { nullifier }) => {
+                    if let Some(key) = self.state_db.get(&keys::ShieldedNullifier(nullifier))? {
+                        if let Some(mut record) = self.state_db.get(&key)? {
+                            record.spent_by = txn.hash.as_bytes().to_vec();
+                            self.state_db.put_key(key, record)?;
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_shielded_event() {
+        let mut data = vec![0u8; NEW_LEAF_DATA_SIZE];
+        data[31] = 5;
+        data[32..64].copy_from_slice(&[0xcc; 32]);
+        data[96..128].copy_from_slice(&[0xee; 32]);
+        let new_leaf = TransactionLog {
+            address: vec![0x41; 21],
+            topics: vec![NEW_LEAF_TOPICS[1].as_bytes().to_vec()],
+            data,
+        };
+        match decode_shielded_event(&new_leaf) {
+            Some(ShieldedEvent::NewLeaf { position, cm, epk, c }) => {
+                assert_eq!(position, 5);
+                assert_eq!(cm, &[0xcc; 32][..]);
+                assert_eq!(epk, &[0xee; 32][..]);
+                assert_eq!(c.len(), 32 * 21);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let spent = TransactionLog {
+            topics: vec![NOTE_SPENT_TOPIC.as_bytes().to_vec()],
+            data: vec![0xaa; 32],
+            ..new_leaf.clone()
+        };
+        assert_eq!(
+            decode_shielded_event(&spent),
+            Some(ShieldedEvent::NoteSpent {
+                nullifier: H256::repeat_byte(0xaa)
+            })
+        );
+
+        // truncated
+        let malformed = TransactionLog {
+            data: vec![0u8; 64],
+            ..new_leaf
+        };
+        assert_eq!(decode_shielded_event(&malformed), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! Execution statistics, grouped by contract type.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use log::info;
+use proto2::chain::ContractType;
+
+/// Upper bounds of execution time histogram buckets, in ms. The last bucket is unbounded.
+const HISTOGRAM_BUCKETS_MS: [i64; 5] = [1, 10, 100, 1_000, 10_000];
+
+#[derive(Debug, Default, Clone)]
+pub struct ContractStats {
+    pub count: u64,
+    pub total_nanos: i64,
+    pub max_nanos: i64,
+    pub total_energy_usage: i64,
+    /// Counts of each bucket in `HISTOGRAM_BUCKETS_MS`, plus one overflow bucket.
+    pub histogram: [u64; HISTOGRAM_BUCKETS_MS.len() + 1],
+}
+
+impl ContractStats {
+    fn record(&mut self, elapsed_nanos: i64, energy_usage: i64) {
+        self.count += 1;
+        self.total_nanos += elapsed_nanos;
+        self.total_energy_usage += energy_usage;
+        if elapsed_nanos > self.max_nanos {
+            self.max_nanos = elapsed_nanos;
+        }
+        let elapsed_ms = elapsed_nanos / 1_000_000;
+        let idx = HISTOGRAM_BUCKETS_MS
+            .iter()
+            .position(|&upper| elapsed_ms < upper)
+            .unwrap_or(HISTOGRAM_BUCKETS_MS.len());
+        self.histogram[idx] += 1;
+    }
+
+    pub fn avg_millis(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_nanos as f64 / self.count as f64 / 1_000_000.0
+        }
+    }
+}
+
+impl fmt::Display for ContractStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count={} avg={:.3}ms max={:.3}ms energy={} histogram=[",
+            self.count,
+            self.avg_millis(),
+            self.max_nanos as f64 / 1_000_000.0,
+            self.total_energy_usage
+        )?;
+        for (i, n) in self.histogram.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match HISTOGRAM_BUCKETS_MS.get(i) {
+                Some(upper) => write!(f, "<{}ms:{}", upper, n)?,
+                None => write!(f, ">={}ms:{}", HISTOGRAM_BUCKETS_MS[i - 1], n)?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+/// Per contract type execution statistics.
+#[derive(Debug, Default)]
+pub struct ExecutionStats {
+    by_type: HashMap<ContractType, ContractStats>,
+}
+
+impl ExecutionStats {
+    pub fn record(&mut self, cntr_type: ContractType, elapsed_nanos: i64, energy_usage: i64) {
+        self.by_type
+            .entry(cntr_type)
+            .or_default()
+            .record(elapsed_nanos, energy_usage);
+    }
+
+    pub fn get(&self, cntr_type: ContractType) -> Option<&ContractStats> {
+        self.by_type.get(&cntr_type)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ContractType, &ContractStats)> {
+        self.by_type.iter()
+    }
+
+    pub fn reset(&mut self) {
+        self.by_type.clear();
+    }
+
+    /// Log stats of all contract types, ordered by total execution time.
+    pub fn report(&self) {
+        let mut all = self.by_type.iter().collect::<Vec<_>>();
+        all.sort_by_key(|(_, stats)| -stats.total_nanos);
+        for (cntr_type, stats) in all {
+            info!("execution stats {:?} {}", cntr_type, stats);
+        }
+    }
+}
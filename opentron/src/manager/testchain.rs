@@ -0,0 +1,821 @@
+//! Deterministic test chain, for regression benchmarks of the executor and state-db across releases.
+//!
+//! Accounts are derived from fixed seeds, and every account walks through `SCRIPT`, one step per appearance, so the
+//! generated blocks exercise all implemented builtin contracts. A step that would fail at its position, i.e. time
+//! gated ones, is replaced by a TRX transfer. Every `AGING_INTERVAL` blocks, one day of slots is skipped so that
+//! unfreezes, withdrawals and frozen supplies mature.
+//!
+//! Blocks are serialized in the format of `commands::export`. State setup is not in the blocks, so a corpus is
+//! replayed by `TestChain::replay`, with the same genesis and number of accounts.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use ::keys::{Address, KeyPair};
+use bytes::BytesMut;
+use chain::{hash, IndexedBlock, IndexedBlockHeader, IndexedTransaction};
+use log::{debug, info};
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::{block_header, transaction, Block, BlockHeader, Transaction};
+use proto2::common::{permission, AccountType, Permission, ResourceCode, SmartContract, Vote};
+use proto2::contract as contract_pb;
+use proto2::state::Account;
+use state::keys;
+
+use super::actuators::BuiltinContractExt;
+use super::bench::{BenchReport, COUNTER_CONTRACT_CODE};
+use super::executor::TransactionExecutor;
+use super::{new_error, Manager, Result};
+use crate::util::read_varint;
+
+/// Initial balance of generated accounts, 10M TRX.
+const INITIAL_BALANCE: i64 = 10_000_000_000_000;
+/// Accounts take roles by index: witness, asset issuer, and two plain holders.
+const NUM_OF_ROLES: usize = 4;
+/// One day of slots is skipped every N blocks.
+const AGING_INTERVAL: i64 = 1_000;
+/// Init code of the counter contract, returning `COUNTER_CONTRACT_CODE` as runtime code.
+const COUNTER_INIT_CODE: &str = "600a600c600039600a6000f3";
+/// `ActivePermissionMask`, all operations except UpdateAccountPermission.
+const ACTIVE_OPERATIONS: &str = "7fff1fc0033e0000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    UpdatePermission,
+    UpdateName,
+    /// Become a witness, issue an asset, or create an account, by role.
+    Setup,
+    Transfer,
+    FreezeV2Bandwidth,
+    FreezeV2Energy,
+    Vote,
+    Delegate,
+    Trigger,
+    UnDelegate,
+    Participate,
+    TransferAsset,
+    UnfreezeV2,
+    Deploy,
+    CreateAccount,
+    FreezeV1,
+    /// Update witness, update asset, or update name, by role.
+    Update,
+    /// Update brokerage, unfreeze asset, or withdraw expired unfreezes, by role.
+    Maturity,
+    /// Create or approve a proposal as a witness, or withdraw reward.
+    Governance,
+    UnfreezeV1,
+    CancelAllUnfreezeV2,
+}
+
+/// Steps of each account, the first `NUM_OF_ONE_OFF_STEPS` are taken once.
+const SCRIPT: [Step; 21] = [
+    Step::UpdatePermission,
+    Step::UpdateName,
+    Step::Setup,
+    Step::Transfer,
+    Step::FreezeV2Bandwidth,
+    Step::FreezeV2Energy,
+    Step::Vote,
+    Step::Delegate,
+    Step::Trigger,
+    Step::UnDelegate,
+    Step::Participate,
+    Step::TransferAsset,
+    Step::UnfreezeV2,
+    Step::Deploy,
+    Step::CreateAccount,
+    Step::FreezeV1,
+    Step::Update,
+    Step::Maturity,
+    Step::Governance,
+    Step::UnfreezeV1,
+    Step::CancelAllUnfreezeV2,
+];
+const NUM_OF_ONE_OFF_STEPS: usize = 3;
+
+/// Stats of a soak test.
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Default)]
+pub struct SoakReport {
+    pub num_blocks: usize,
+    /// Blocks failed by injected faults, then pushed again.
+    pub num_failed_blocks: usize,
+    /// State-db writes checked while faults are enabled.
+    pub num_writes: u64,
+    pub num_faults: u64,
+    pub state_commitment: H256,
+}
+
+/// Stats of a generated chain.
+#[derive(Debug, Default)]
+pub struct GenerateReport {
+    pub num_blocks: usize,
+    pub num_transactions: usize,
+    /// Steps replaced by transfers.
+    pub num_fallbacks: usize,
+}
+
+pub struct TestChain<'m> {
+    manager: &'m mut Manager,
+    accounts: Vec<KeyPair>,
+    /// Next step of each account.
+    cursors: Vec<usize>,
+    contract_address: Address,
+    nonce: i64,
+}
+
+impl<'m> TestChain<'m> {
+    /// Create a test chain on a freshly inited state-db, with `num_accounts` seeded accounts.
+    pub fn new<'a>(manager: &'a mut Manager, num_accounts: usize) -> Result<TestChain<'a>> {
+        if manager.latest_block_number() != 0 {
+            return Err(new_error("test chain must be built on an empty state-db"));
+        }
+        if num_accounts < 2 * NUM_OF_ROLES || num_accounts % NUM_OF_ROLES != 0 {
+            return Err(new_error(&format!(
+                "number of accounts must be a multiple of {}, at least {}",
+                NUM_OF_ROLES,
+                2 * NUM_OF_ROLES
+            )));
+        }
+
+        let accounts = (0..num_accounts)
+            .map(|i| KeyPair::from_seed(format!("opentron testchain account {}", i).as_bytes()))
+            .collect::<::std::result::Result<Vec<_>, _>>()?;
+        let contract_address = KeyPair::from_seed(b"opentron testchain contract")?.address();
+
+        // Blocks of test chains are not signed.
+        manager.skip_block_signature = true;
+        let mut chain = TestChain {
+            manager,
+            cursors: vec![0; accounts.len()],
+            accounts,
+            contract_address,
+            nonce: 0,
+        };
+        chain.init_state()?;
+        Ok(chain)
+    }
+
+    fn init_state(&mut self) -> Result<()> {
+        use state::keys::ChainParameter::*;
+
+        let genesis_timestamp = self.manager.genesis_block_timestamp;
+        let state_db = &mut self.manager.state_db;
+
+        state_db.new_layer();
+        for kp in &self.accounts {
+            let mut acct = Account::new(genesis_timestamp);
+            acct.balance = INITIAL_BALANCE;
+            state_db.put_key(keys::Account(kp.address()), acct)?;
+        }
+
+        // A throwaway state-db, features are enabled regardless of the genesis config.
+        for &param in &[
+            AllowTvm,
+            AllowMultisig,
+            AllowUpdateAccountName,
+            AllowSameTokenName,
+            AllowDelegateResource,
+            AllowChangeDelegation,
+            AllowCancelAllUnfreezeV2,
+            UnfreezeDelayDays,
+        ] {
+            state_db.put_key(param, 1)?;
+        }
+
+        let code = hex::decode(COUNTER_CONTRACT_CODE).unwrap();
+        let cntr = SmartContract {
+            origin_address: self.accounts[0].address().as_bytes().to_vec(),
+            contract_address: self.contract_address.as_bytes().to_vec(),
+            bytecode: code.clone(),
+            consume_user_energy_percent: 100,
+            name: "Counter".into(),
+            origin_energy_limit: 10_000_000,
+            ..Default::default()
+        };
+        state_db.put_key(keys::Contract(self.contract_address), cntr)?;
+        state_db.put_key(keys::ContractCode(self.contract_address), code)?;
+        state_db.put_key(
+            keys::Account(self.contract_address),
+            Account::new_contract_account(genesis_timestamp),
+        )?;
+        state_db.solidify_layer();
+        Ok(())
+    }
+
+    /// Generate `num_blocks` blocks, each with `txns_per_block` transactions, and write them to `path`.
+    pub fn generate<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        num_blocks: usize,
+        txns_per_block: usize,
+    ) -> Result<GenerateReport> {
+        if txns_per_block > self.accounts.len() {
+            return Err(new_error("an account can send at most one transaction per block"));
+        }
+        let genesis_hash = self.manager.latest_block_hash();
+        // All transactions refer to the genesis block.
+        self.manager.init_ref_blocks(vec![genesis_hash]);
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut buf = BytesMut::with_capacity(64 * 1024);
+        let mut report = GenerateReport::default();
+        let mut next_sender = 0;
+        for _ in 0..num_blocks {
+            let number = self.manager.latest_block_number() + 1;
+            let slots = if number > 1 && number % AGING_INTERVAL == 0 {
+                constants::DAY_IN_MS / self.manager.block_interval() + 1
+            } else {
+                1
+            };
+            let timestamp = self.manager.get_slot_timestamp(slots);
+            let witness = if number == 1 {
+                // Witness schedule is inited in block #1.
+                self.manager.genesis_config.witnesses[0].address.parse::<Address>()?
+            } else {
+                self.manager.get_scheduled_witness(self.manager.get_slot(timestamp))
+            };
+            let header = BlockHeader {
+                raw_data: Some(block_header::Raw {
+                    number,
+                    timestamp,
+                    parent_hash: self.manager.latest_block_hash().as_bytes().to_vec(),
+                    witness_address: witness.as_bytes().to_vec(),
+                    version: constants::CURRENT_BLOCK_VERSION as i32,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+
+            // Candidates are executed on top of each other, in layers discarded before the block is pushed.
+            let draft = IndexedBlock::new(IndexedBlockHeader::from_raw(header.clone()), vec![]);
+            let mut txns = Vec::with_capacity(txns_per_block);
+            for _ in 0..txns_per_block {
+                let index = next_sender;
+                next_sender = (next_sender + 1) % self.accounts.len();
+
+                let step = SCRIPT[self.cursors[index]];
+                self.cursors[index] += 1;
+                if self.cursors[index] == SCRIPT.len() {
+                    self.cursors[index] = NUM_OF_ONE_OFF_STEPS;
+                }
+
+                let txn = self.transaction_of_step(index, step, timestamp, &genesis_hash)?;
+                if self.try_execute(&txn, &draft) {
+                    txns.push(txn.raw);
+                    continue;
+                }
+                report.num_fallbacks += 1;
+                let txn = self.transaction_of_step(index, Step::Transfer, timestamp, &genesis_hash)?;
+                if self.try_execute(&txn, &draft) {
+                    txns.push(txn.raw);
+                }
+            }
+            for _ in 0..txns.len() {
+                self.manager.state_db.discard_last_layer()?;
+            }
+
+            let block = IndexedBlock::from_header_and_txns(header, txns);
+            if !self.manager.push_block(&block)? {
+                return Err(new_error(&format!("block #{} is rejected", number)));
+            }
+            report.num_blocks += 1;
+            report.num_transactions += block.transactions.len();
+
+            let raw_block: Block = block.into_raw_block();
+            buf.clear();
+            raw_block.encode_length_delimited(&mut buf)?;
+            writer.write_all(&buf)?;
+
+            if number % 1_000 == 0 {
+                info!("generated block #{}", number);
+            }
+        }
+        writer.flush()?;
+
+        Ok(report)
+    }
+
+    /// Apply blocks of a generated test chain, decoding into `generate` and hashing into `assemble` of the report.
+    pub fn replay<P: AsRef<Path>>(&mut self, path: P) -> Result<BenchReport> {
+        let genesis_hash = self.manager.latest_block_hash();
+        self.manager.init_ref_blocks(vec![genesis_hash]);
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut buf = Vec::with_capacity(64 * 1024);
+        let mut report = BenchReport::default();
+        loop {
+            let started_at = Instant::now();
+            let raw_block = match read_block(&mut reader, &mut buf)? {
+                Some(raw_block) => raw_block,
+                None => break,
+            };
+            report.generate += started_at.elapsed();
+
+            let started_at = Instant::now();
+            let block = IndexedBlock::from_raw(raw_block);
+            report.assemble += started_at.elapsed();
+
+            let started_at = Instant::now();
+            if !self.manager.push_block(&block)? {
+                return Err(new_error(&format!("block #{} is rejected", block.number())));
+            }
+            report.apply += started_at.elapsed();
+
+            report.num_blocks += 1;
+            report.num_transactions += block.transactions.len();
+        }
+        info!(
+            "state commitment => {:?}",
+            self.manager.state_db.get(&keys::StateCommitment)?.unwrap_or_default()
+        );
+
+        Ok(report)
+    }
+
+    /// Apply blocks of a generated test chain, with state-db writes failing at `rate`. A block failed by an injected
+    /// fault must leave the state untouched, then it is pushed again without faults.
+    ///
+    /// The final state commitment is the same as of `replay` when all error paths roll back cleanly.
+    #[cfg(feature = "fault-injection")]
+    pub fn soak<P: AsRef<Path>>(&mut self, path: P, seed: u64, rate: f64) -> Result<SoakReport> {
+        use state::fault::FaultInjector;
+
+        let genesis_hash = self.manager.latest_block_hash();
+        self.manager.init_ref_blocks(vec![genesis_hash]);
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut buf = Vec::with_capacity(64 * 1024);
+        let mut report = SoakReport::default();
+        let mut faults = FaultInjector::new(seed, rate);
+        while let Some(raw_block) = read_block(&mut reader, &mut buf)? {
+            let block = IndexedBlock::from_raw(raw_block);
+            let commitment = self.manager.state_db.get(&keys::StateCommitment)?;
+            let latest_block_hash = self.manager.latest_block_hash();
+            self.manager.state_db.set_fault_injector(Some(faults));
+            let ret = self.manager.push_block(&block);
+            faults = self.manager.state_db.fault_injector().cloned().unwrap();
+            self.manager.state_db.set_fault_injector(None);
+
+            match ret {
+                Ok(true) => {}
+                Ok(false) => return Err(new_error(&format!("block #{} is rejected", block.number()))),
+                Err(e) => {
+                    debug!("block #{} failed: {}", block.number(), e);
+                    report.num_failed_blocks += 1;
+                    if self.manager.layers != 0 ||
+                        self.manager.latest_block_hash() != latest_block_hash ||
+                        self.manager.state_db.get(&keys::StateCommitment)? != commitment
+                    {
+                        return Err(new_error(&format!(
+                            "block #{} is not rolled back after error: {}",
+                            block.number(),
+                            e
+                        )));
+                    }
+                    if !self.manager.push_block(&block)? {
+                        return Err(new_error(&format!("block #{} is rejected", block.number())));
+                    }
+                }
+            }
+            report.num_blocks += 1;
+        }
+        report.num_writes = faults.num_writes;
+        report.num_faults = faults.num_faults;
+        report.state_commitment = self.manager.state_db.get(&keys::StateCommitment)?.unwrap_or_default();
+        Ok(report)
+    }
+
+    /// Execute a candidate transaction in a new layer, which is kept only on success.
+    fn try_execute(&mut self, txn: &IndexedTransaction, draft: &IndexedBlock) -> bool {
+        self.manager.state_db.new_layer();
+        match TransactionExecutor::new(self.manager).execute(txn, draft) {
+            Ok(_) => true,
+            Err(e) => {
+                debug!("fallback to transfer, {:?} is invalid: {}", txn.hash, e);
+                self.manager.state_db.discard_last_layer().unwrap();
+                false
+            }
+        }
+    }
+
+    /// Account of the same group with a given role.
+    fn peer_of(&self, index: usize, role: usize) -> usize {
+        let base = index / NUM_OF_ROLES * NUM_OF_ROLES;
+        if base + role == index {
+            (index + NUM_OF_ROLES) % self.accounts.len()
+        } else {
+            base + role
+        }
+    }
+
+    fn transaction_of_step(
+        &mut self,
+        index: usize,
+        step: Step,
+        block_timestamp: i64,
+        ref_block_hash: &H256,
+    ) -> Result<IndexedTransaction> {
+        self.nonce += 1;
+        let role = index % NUM_OF_ROLES;
+        let sender = &self.accounts[index];
+        let owner_address = sender.address().as_bytes().to_vec();
+        let receiver_address = self.accounts[(index + 1) % self.accounts.len()]
+            .address()
+            .as_bytes()
+            .to_vec();
+        let issuer = self.accounts[self.peer_of(index, 1)].address();
+        let token_id = self
+            .manager
+            .state_db
+            .get(&keys::Account(issuer))?
+            .map_or(0, |acct| acct.issued_asset_id)
+            .to_string();
+        let latest_timestamp = self.manager.latest_block_timestamp();
+
+        let (cntr_type, parameter) = match step {
+            Step::UpdatePermission => {
+                let key = permission::Key {
+                    address: owner_address.clone(),
+                    weight: 1,
+                };
+                let cntr = contract_pb::AccountPermissionUpdateContract {
+                    owner: Some(Permission {
+                        r#type: permission::PermissionType::Owner as i32,
+                        id: 0,
+                        name: "owner".into(),
+                        threshold: 1,
+                        keys: vec![key.clone()],
+                        ..Default::default()
+                    }),
+                    actives: vec![Permission {
+                        r#type: permission::PermissionType::Active as i32,
+                        id: 2,
+                        name: "active".into(),
+                        threshold: 1,
+                        operations: hex::decode(ACTIVE_OPERATIONS).unwrap(),
+                        keys: vec![key],
+                        ..Default::default()
+                    }],
+                    owner_address,
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::UpdateName | Step::Update if step == Step::UpdateName || role >= 2 => {
+                let cntr = contract_pb::AccountUpdateContract {
+                    owner_address,
+                    account_name: format!("testchain-{}-{}", index, self.nonce),
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Setup if role == 0 => {
+                let cntr = contract_pb::WitnessCreateContract {
+                    owner_address,
+                    url: format!("https://witness-{}.testchain.opentron.org", index).into_bytes(),
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Setup if role == 1 => {
+                let cntr = contract_pb::AssetIssueContract {
+                    owner_address,
+                    name: format!("TestChain{}", index),
+                    abbr: "TC".into(),
+                    total_supply: 1_000_000_000_000_000,
+                    frozen_supply: vec![contract_pb::asset_issue_contract::FrozenSupply {
+                        frozen_amount: 1_000_000_000,
+                        frozen_days: constants::MIN_NUM_OF_FROZEN_DAYS_IN_ASSET_ISSUE,
+                    }],
+                    trx_num: 1,
+                    num: 1,
+                    precision: 6,
+                    start_time: latest_timestamp + 1,
+                    end_time: latest_timestamp + 365 * constants::DAY_IN_MS,
+                    url: "https://testchain.opentron.org".into(),
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Setup | Step::CreateAccount => {
+                let seed = format!("opentron testchain new account {}", self.nonce);
+                let new_account = KeyPair::from_seed(seed.as_bytes())?;
+                let cntr = contract_pb::AccountCreateContract {
+                    owner_address,
+                    account_address: new_account.address().as_bytes().to_vec(),
+                    r#type: AccountType::Normal as i32,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Transfer => {
+                let cntr = contract_pb::TransferContract {
+                    owner_address,
+                    to_address: receiver_address,
+                    amount: 1,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::FreezeV2Bandwidth | Step::FreezeV2Energy => {
+                let cntr = contract_pb::FreezeBalanceV2Contract {
+                    owner_address,
+                    frozen_balance: 10 * constants::SUN_PER_TRX,
+                    resource: if step == Step::FreezeV2Energy {
+                        ResourceCode::Energy as i32
+                    } else {
+                        ResourceCode::Bandwidth as i32
+                    },
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Vote => {
+                let genesis_witnesses = &self.manager.genesis_config.witnesses;
+                let genesis_witness = &genesis_witnesses[index % genesis_witnesses.len()];
+                let cntr = contract_pb::VoteWitnessContract {
+                    owner_address,
+                    votes: vec![
+                        Vote {
+                            vote_address: genesis_witness.address.parse::<Address>()?.as_bytes().to_vec(),
+                            vote_count: 1,
+                        },
+                        Vote {
+                            vote_address: self.accounts[self.peer_of(index, 0)].address().as_bytes().to_vec(),
+                            vote_count: 1,
+                        },
+                    ],
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Delegate => {
+                let cntr = contract_pb::DelegateResourceContract {
+                    owner_address,
+                    receiver_address,
+                    resource: ResourceCode::Bandwidth as i32,
+                    balance: constants::MIN_FROZEN_BALANCE,
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::UnDelegate => {
+                let cntr = contract_pb::UnDelegateResourceContract {
+                    owner_address,
+                    receiver_address,
+                    resource: ResourceCode::Bandwidth as i32,
+                    balance: constants::MIN_FROZEN_BALANCE,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Trigger => {
+                let cntr = contract_pb::TriggerSmartContract {
+                    owner_address,
+                    contract_address: self.contract_address.as_bytes().to_vec(),
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Participate => {
+                let cntr = contract_pb::ParticipateAssetIssueContract {
+                    owner_address,
+                    to_address: issuer.as_bytes().to_vec(),
+                    asset_name: token_id,
+                    amount: constants::SUN_PER_TRX,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::TransferAsset => {
+                let cntr = contract_pb::TransferAssetContract {
+                    owner_address,
+                    to_address: receiver_address,
+                    asset_name: token_id,
+                    amount: 1,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::UnfreezeV2 => {
+                let cntr = contract_pb::UnfreezeBalanceV2Contract {
+                    owner_address,
+                    unfreeze_balance: constants::SUN_PER_TRX,
+                    resource: ResourceCode::Bandwidth as i32,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Deploy => {
+                let cntr = contract_pb::CreateSmartContract {
+                    new_contract: Some(SmartContract {
+                        origin_address: owner_address.clone(),
+                        bytecode: hex::decode(format!("{}{}", COUNTER_INIT_CODE, COUNTER_CONTRACT_CODE)).unwrap(),
+                        consume_user_energy_percent: 100,
+                        name: "Counter".into(),
+                        origin_energy_limit: 10_000_000,
+                        ..Default::default()
+                    }),
+                    owner_address,
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::FreezeV1 => {
+                let cntr = contract_pb::FreezeBalanceContract {
+                    owner_address,
+                    frozen_balance: 10 * constants::SUN_PER_TRX,
+                    frozen_duration: constants::MIN_NUM_OF_FROZEN_DAYS_FOR_RESOURCE,
+                    resource: ResourceCode::Energy as i32,
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::UnfreezeV1 => {
+                let cntr = contract_pb::UnfreezeBalanceContract {
+                    owner_address,
+                    resource: ResourceCode::Energy as i32,
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Update if role == 0 => {
+                let cntr = contract_pb::WitnessUpdateContract {
+                    owner_address,
+                    update_url: format!("https://witness-{}-{}.testchain.opentron.org", index, self.nonce)
+                        .into_bytes(),
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Update => {
+                let cntr = contract_pb::UpdateAssetContract {
+                    owner_address,
+                    url: "https://testchain.opentron.org".into(),
+                    description: format!("updated by #{}", self.nonce).into_bytes(),
+                    ..Default::default()
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Maturity if role == 0 => {
+                let cntr = contract_pb::UpdateBrokerageContract {
+                    owner_address,
+                    brokerage: (self.nonce % 100) as i32,
+                };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Maturity if role == 1 => {
+                let cntr = contract_pb::UnfreezeAssetContract { owner_address };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Maturity => {
+                let cntr = contract_pb::WithdrawExpireUnfreezeContract { owner_address };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::Governance if role == 0 => {
+                let latest_proposal_id = self
+                    .manager
+                    .state_db
+                    .must_get(&keys::DynamicProperty::LatestProposalId);
+                if latest_proposal_id > 0 && self.nonce % 2 == 0 {
+                    let cntr = contract_pb::ProposalApproveContract {
+                        owner_address,
+                        proposal_id: latest_proposal_id,
+                        is_approval: true,
+                    };
+                    (cntr.type_code(), cntr.to_any())
+                } else {
+                    // A no-op proposal, the current value.
+                    let bandwidth_fee = self
+                        .manager
+                        .state_db
+                        .must_get(&keys::ChainParameter::BandwidthFee);
+                    let cntr = contract_pb::ProposalCreateContract {
+                        owner_address,
+                        parameters: vec![(keys::ChainParameter::BandwidthFee as i64, bandwidth_fee)]
+                            .into_iter()
+                            .collect(),
+                    };
+                    (cntr.type_code(), cntr.to_any())
+                }
+            }
+            Step::Governance => {
+                let cntr = contract_pb::WithdrawBalanceContract { owner_address };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::CancelAllUnfreezeV2 => {
+                let cntr = contract_pb::CancelAllUnfreezeV2Contract { owner_address };
+                (cntr.type_code(), cntr.to_any())
+            }
+            Step::UpdateName => unreachable!("matched above"),
+        };
+
+        let raw = transaction::Raw {
+            ref_block_bytes: ref_block_hash.as_bytes()[6..8].to_vec(),
+            ref_block_hash: ref_block_hash.as_bytes()[8..16].to_vec(),
+            expiration: block_timestamp + 60_000,
+            // Timestamp makes every transaction unique.
+            timestamp: block_timestamp + self.nonce,
+            fee_limit: if step == Step::Trigger || step == Step::Deploy {
+                100_000_000
+            } else {
+                0
+            },
+            contract: Some(transaction::Contract {
+                r#type: cntr_type as i32,
+                parameter,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let signature = self.accounts[index].private().sign(&hash::raw_data_bytes(&raw))?;
+
+        Ok(IndexedTransaction::from_raw(Transaction {
+            raw_data: Some(raw),
+            signatures: vec![signature.as_bytes().to_vec()],
+            ..Default::default()
+        }))
+    }
+}
+
+impl Drop for TestChain<'_> {
+    fn drop(&mut self) {
+        self.manager.skip_block_signature = false;
+    }
+}
+
+/// Read the next length delimited block, `None` at the end of file.
+fn read_block<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> Result<Option<Block>> {
+    let len = match read_varint(reader)? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+    buf.resize(len as usize, 0);
+    reader.read_exact(buf)?;
+    Ok(Some(Block::decode(&buf[..])?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::TestManager;
+    use super::*;
+
+    const NUM_OF_ACCOUNTS: usize = 8;
+    const NUM_OF_BLOCKS: usize = 50;
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let paths: Vec<_> = (0..2)
+            .map(|i| std::env::temp_dir().join(format!("opentron-testchain-{}-{}.bin", std::process::id(), i)))
+            .collect();
+
+        let mut commitments = vec![];
+        for path in &paths {
+            let mut manager = TestManager::new();
+            let report = TestChain::new(&mut manager, NUM_OF_ACCOUNTS)
+                .and_then(|mut chain| chain.generate(path, NUM_OF_BLOCKS, NUM_OF_ACCOUNTS))
+                .unwrap();
+            assert_eq!(report.num_blocks, NUM_OF_BLOCKS);
+            assert!(!manager.skip_block_signature);
+            commitments.push(manager.state_db.get(&keys::StateCommitment).unwrap());
+        }
+        let chains: Vec<_> = paths.iter().map(|path| std::fs::read(path).unwrap()).collect();
+
+        let mut manager = TestManager::new();
+        TestChain::new(&mut manager, NUM_OF_ACCOUNTS)
+            .and_then(|mut chain| chain.replay(&paths[0]))
+            .unwrap();
+        for path in &paths {
+            let _ = std::fs::remove_file(path);
+        }
+
+        assert!(!chains[0].is_empty());
+        assert!(chains[0] == chains[1], "generated chains differ");
+        assert!(commitments[0].is_some());
+        assert_eq!(commitments[0], commitments[1]);
+        assert_eq!(manager.state_db.get(&keys::StateCommitment).unwrap(), commitments[0]);
+    }
+
+    #[test]
+    #[cfg(feature = "fault-injection")]
+    fn test_soak_with_injected_faults() {
+        let path = std::env::temp_dir().join(format!("opentron-soak-{}.bin", std::process::id()));
+
+        let mut manager = TestManager::new();
+        TestChain::new(&mut manager, NUM_OF_ACCOUNTS)
+            .and_then(|mut chain| chain.generate(&path, NUM_OF_BLOCKS, NUM_OF_ACCOUNTS))
+            .unwrap();
+
+        let mut manager = TestManager::new();
+        TestChain::new(&mut manager, NUM_OF_ACCOUNTS)
+            .and_then(|mut chain| chain.replay(&path))
+            .unwrap();
+        let commitment = manager.state_db.get(&keys::StateCommitment).unwrap().unwrap_or_default();
+
+        let mut manager = TestManager::new();
+        let report = TestChain::new(&mut manager, NUM_OF_ACCOUNTS)
+            .and_then(|mut chain| chain.soak(&path, 42, 0.01))
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(report.num_blocks, NUM_OF_BLOCKS);
+        assert!(report.num_faults > 0);
+        assert!(report.num_failed_blocks > 0);
+        assert_eq!(report.state_commitment, commitment);
+    }
+}
@@ -0,0 +1,74 @@
+//! Throwaway managers for unit tests, on a temporary state-db inited from the mainnet genesis.
+
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chain::{IndexedBlockHeader, IndexedTransaction};
+use config::{Config, GenesisConfig};
+use proto2::chain::{BlockHeader, Transaction};
+
+use super::Manager;
+
+static NUM_OF_MANAGERS: AtomicUsize = AtomicUsize::new(0);
+
+/// A manager whose state-db is removed on drop.
+pub struct TestManager {
+    manager: Option<Manager>,
+    dir: PathBuf,
+}
+
+impl TestManager {
+    pub fn new() -> TestManager {
+        let mut config = Config::load_from_str(include_str!("../../../config/conf.toml")).unwrap();
+        let genesis_config = GenesisConfig::load_from_str(include_str!("../../../config/genesis.json")).unwrap();
+
+        let dir = std::env::temp_dir().join(format!(
+            "opentron-test-{}-{}",
+            std::process::id(),
+            NUM_OF_MANAGERS.fetch_add(1, Ordering::SeqCst)
+        ));
+        config.storage.state_data_dir = dir.join("state-data").to_string_lossy().into_owned();
+        config.storage.state_cache_dir = dir.join("state-cache").to_string_lossy().into_owned();
+
+        TestManager {
+            manager: Some(Manager::new(&config, &genesis_config)),
+            dir,
+        }
+    }
+}
+
+impl Deref for TestManager {
+    type Target = Manager;
+
+    fn deref(&self) -> &Manager {
+        self.manager.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for TestManager {
+    fn deref_mut(&mut self) -> &mut Manager {
+        self.manager.as_mut().unwrap()
+    }
+}
+
+impl Drop for TestManager {
+    fn drop(&mut self) {
+        // Close the state-db first.
+        self.manager.take();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// An empty block header and transaction, for a `TransactionContext` of builtin contracts.
+pub fn empty_block_and_transaction() -> (IndexedBlockHeader, IndexedTransaction) {
+    let header = BlockHeader {
+        raw_data: Some(Default::default()),
+        ..Default::default()
+    };
+    let txn = Transaction {
+        raw_data: Some(Default::default()),
+        ..Default::default()
+    };
+    (IndexedBlockHeader::from_raw(header), IndexedTransaction::from_raw(txn))
+}
@@ -1,11 +1,14 @@
 //! The TVM backend.
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
 use ::keys::Address;
 use primitive_types::{H160, H256, U256};
 use proto2::state::{Account, AccountType, TransactionLog};
 use state::db::StateDB;
 use state::keys;
-use tvm::backend::{Apply, ApplyBackend, Backend, Basic, Log};
+use tvm::backend::{Apply, Backend, Basic, Log};
 
 use super::executor::TransactionContext;
 use super::Manager;
@@ -15,12 +18,21 @@ pub struct StateBackend<'m, 'c, 'ctx> {
     manager: &'m mut Manager,
     ctx: &'c mut TransactionContext<'ctx>,
     sender: Address,
+    /// Token balances of accounts loaded by `basic`, a prefix scan of account-asset column each.
+    ///
+    /// State-db is only changed by `apply` after execution, so balances never go stale during execution.
+    token_balances: RefCell<HashMap<Address, BTreeMap<i64, i64>>>,
 }
 
 impl<'m, 'c, 'ctx> StateBackend<'m, 'c, 'ctx> {
     /// Create a new StateDB backend.
     pub fn new(sender: Address, manager: &'m mut Manager, ctx: &'c mut TransactionContext<'ctx>) -> Self {
-        Self { manager, ctx, sender }
+        Self {
+            manager,
+            ctx,
+            sender,
+            token_balances: RefCell::default(),
+        }
     }
 
     /// Get the underlying `StateDB` storing the state.
@@ -83,7 +95,15 @@ impl Backend for StateBackend<'_, '_, '_> {
             .map(|a| Basic {
                 balance: a.balance.into(),
                 nonce: 0.into(),
-                token_balance: a.token_balance.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+                // Token balances are lazily loaded from account-asset column, only when the account is touched.
+                token_balance: self
+                    .token_balances
+                    .borrow_mut()
+                    .entry(addr)
+                    .or_insert_with(|| self.manager.get_token_balances(addr))
+                    .iter()
+                    .map(|(&k, &v)| (k.into(), v.into()))
+                    .collect(),
             })
             .unwrap_or_default()
     }
@@ -120,13 +140,16 @@ impl Backend for StateBackend<'_, '_, '_> {
     }
 }
 
-impl ApplyBackend for StateBackend<'_, '_, '_> {
-    fn apply<A, I, L>(&mut self, values: A, logs: L, delete_empty: bool)
+// NOTE: Not `ApplyBackend`, so that state-db write errors fail the transaction instead of panicking.
+impl StateBackend<'_, '_, '_> {
+    /// Apply changes of an execution to state-db, and logs to the transaction context.
+    pub fn apply<A, I, L>(&mut self, values: A, logs: L, delete_empty: bool) -> Result<(), String>
     where
         A: IntoIterator<Item = Apply<I>>,
         I: IntoIterator<Item = (H256, H256)>,
         L: IntoIterator<Item = Log>,
     {
+        self.token_balances.get_mut().clear();
         for apply in values {
             match apply {
                 Apply::Modify {
@@ -154,13 +177,20 @@ impl ApplyBackend for StateBackend<'_, '_, '_> {
 
                     account.balance = basic.balance.as_u64() as i64;
                     for (token_id, token_value) in basic.token_balance {
-                        account
-                            .adjust_token_balance(token_id.as_u64() as i64, token_value.as_u64() as i64)
-                            .unwrap();
+                        let key = keys::AccountAsset(addr, token_id.as_u64() as i64);
+                        if token_value.is_zero() {
+                            self.state_mut().delete_key(&key).map_err(|_| "db delete error")?;
+                        } else {
+                            self.state_mut()
+                                .put_key(key, token_value.as_u64() as i64)
+                                .map_err(|_| "db insert error")?;
+                        }
                     }
                     // account.nonce = basic.nonce;
                     if let Some(code) = code {
-                        self.state_mut().put_key(keys::ContractCode(addr), code).unwrap();
+                        self.state_mut()
+                            .put_key(keys::ContractCode(addr), code)
+                            .map_err(|_| "db insert error")?;
                         account.r#type = AccountType::Contract as i32;
                     }
 
@@ -173,17 +203,19 @@ impl ApplyBackend for StateBackend<'_, '_, '_> {
                         if value == H256::default() {
                             self.state_mut()
                                 .delete_key(&keys::ContractStorage(addr, index))
-                                .unwrap();
+                                .map_err(|_| "db delete error")?;
                         } else {
                             self.state_mut()
                                 .put_key(keys::ContractStorage(addr, index), value)
-                                .unwrap();
+                                .map_err(|_| "db insert error")?;
                         }
                     }
                 }
                 Apply::Delete { address } => {
                     let addr = Address::from_tvm_bytes(address.as_bytes());
-                    self.state_mut().delete_key(&keys::Account(addr)).unwrap();
+                    self.state_mut()
+                        .delete_key(&keys::Account(addr))
+                        .map_err(|_| "db delete error")?;
                     unimplemented!("TODO: delete account")
                 }
             }
@@ -197,5 +229,6 @@ impl ApplyBackend for StateBackend<'_, '_, '_> {
                 data: data,
             });
         }
+        Ok(())
     }
 }
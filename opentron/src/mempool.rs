@@ -0,0 +1,543 @@
+//! Pool of pending transactions, received by `broadcast` and not yet included in a block.
+//!
+//! Transactions of a sponsored bundle, i.e. a fee-payer's DelegateResource followed by the sponsored transaction,
+//! are kept together, so that a producing node packs them into the same block in order.
+//!
+//! Pending transactions are announced to peers, and rebroadcast when not included after some blocks. Included
+//! transactions are kept until expiration, and re-injected when dropped from the chain, i.e. by a fork switch.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chain::IndexedTransaction;
+use chrono::Utc;
+use futures::future::FutureExt;
+use futures::select;
+use log::{info, warn};
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::ContractType;
+use proto2::contract::{DelegateResourceContract, UnDelegateResourceContract};
+use tokio::sync::broadcast;
+use tokio::time::delay_for;
+
+use crate::context::AppContext;
+use crate::manager::actuators::BuiltinContractExt;
+
+/// Same as java-tron's default `node.maxTransactionPendingSize`.
+pub const MAX_PENDING_TRANSACTIONS: usize = 2_000;
+
+/// Lifecycle of a transaction submitted to this node, all timestamps in ms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionStatus {
+    pub first_seen: i64,
+    /// None if never announced to peers, or re-injected since.
+    pub last_broadcast: Option<i64>,
+    pub broadcast_count: u32,
+    /// Number of the block including the transaction, None if pending.
+    pub included_in: Option<i64>,
+    /// Times the transaction is re-injected after dropped from the chain.
+    pub reinjected_count: u32,
+}
+
+pub struct TransactionPool {
+    txns: HashMap<H256, IndexedTransaction>,
+    /// Transaction ids in arrival order, for paging.
+    ids: VecDeque<H256>,
+    /// Member ids of bundles, by the first member id.
+    bundles: HashMap<H256, Vec<H256>>,
+    /// Included transactions, kept until expiration to detect drops from the chain.
+    included: HashMap<H256, IndexedTransaction>,
+    statuses: HashMap<H256, TransactionStatus>,
+    limit: usize,
+}
+
+/// `owner_address` is always the first field of builtin contracts.
+#[derive(Clone, PartialEq, prost::Message)]
+struct ContractOwner {
+    #[prost(bytes, tag = "1")]
+    owner_address: Vec<u8>,
+}
+
+fn contract_of<T: BuiltinContractExt>(txn: &IndexedTransaction, cntr_type: ContractType) -> Option<T> {
+    txn.raw
+        .raw_data
+        .as_ref()
+        .and_then(|raw| raw.contract.as_ref())
+        .filter(|cntr| cntr.r#type == cntr_type as i32)
+        .and_then(|cntr| cntr.parameter.as_ref())
+        .and_then(T::from_any)
+}
+
+/// Check a broadcast raw_data carries exactly one contract, before extra contracts are merged by decoding.
+///
+/// Same policy as java-tron. Multi-contract transactions would need per-contract results in `TransactionContext`,
+/// and are rejected for all chains.
+pub fn check_num_of_contracts(raw_data: &[u8]) -> Result<(), String> {
+    let num_of_contracts = chain::num_of_contracts(raw_data).map_err(|e| e.to_string())?;
+    if num_of_contracts == 0 {
+        return Err("transaction has no contract".into());
+    }
+    if num_of_contracts > constants::MAX_NUM_OF_CONTRACTS_PER_TRANSACTION {
+        return Err(format!(
+            "transaction has {} contracts, only {} is allowed",
+            num_of_contracts,
+            constants::MAX_NUM_OF_CONTRACTS_PER_TRANSACTION
+        ));
+    }
+    Ok(())
+}
+
+/// Check the shape of a sponsored bundle: DelegateResource from the sponsor to the sender, the sponsored
+/// transaction, and an optional UnDelegateResource reclaiming the same delegation.
+pub fn check_sponsored_bundle(txns: &[IndexedTransaction]) -> Result<(), &'static str> {
+    if txns.len() != 2 && txns.len() != 3 {
+        return Err("a sponsored bundle has 2 or 3 transactions");
+    }
+    let delegate: DelegateResourceContract = contract_of(&txns[0], ContractType::DelegateResourceContract)
+        .ok_or("first transaction of a sponsored bundle must be a DelegateResourceContract")?;
+    let target = txns[1]
+        .raw
+        .raw_data
+        .as_ref()
+        .and_then(|raw| raw.contract.as_ref())
+        .ok_or("missing contract")?;
+    if target.r#type == ContractType::DelegateResourceContract as i32 ||
+        target.r#type == ContractType::UnDelegateResourceContract as i32
+    {
+        return Err("sponsored transaction must not be a delegation");
+    }
+    let sender = target
+        .parameter
+        .as_ref()
+        .and_then(|param| ContractOwner::decode(&param.value[..]).ok())
+        .ok_or("invalid sponsored contract")?;
+    if sender.owner_address != delegate.receiver_address {
+        return Err("resource must be delegated to the sender of the sponsored transaction");
+    }
+    if let Some(txn) = txns.get(2) {
+        let undelegate: UnDelegateResourceContract = contract_of(txn, ContractType::UnDelegateResourceContract)
+            .ok_or("last transaction of a sponsored bundle must be an UnDelegateResourceContract")?;
+        if delegate.lock {
+            return Err("locked delegation can not be reclaimed in the same bundle");
+        }
+        if undelegate.owner_address != delegate.owner_address ||
+            undelegate.receiver_address != delegate.receiver_address ||
+            undelegate.resource != delegate.resource ||
+            undelegate.balance != delegate.balance
+        {
+            return Err("UnDelegateResourceContract must reclaim the same delegation");
+        }
+    }
+    Ok(())
+}
+
+impl Default for TransactionPool {
+    fn default() -> Self {
+        TransactionPool::new(MAX_PENDING_TRANSACTIONS)
+    }
+}
+
+impl TransactionPool {
+    pub fn new(limit: usize) -> Self {
+        TransactionPool {
+            txns: HashMap::new(),
+            ids: VecDeque::new(),
+            bundles: HashMap::new(),
+            included: HashMap::new(),
+            statuses: HashMap::new(),
+            limit,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    fn track(&mut self, id: H256) {
+        self.statuses.insert(
+            id,
+            TransactionStatus {
+                first_seen: Utc::now().timestamp_millis(),
+                last_broadcast: None,
+                broadcast_count: 0,
+                included_in: None,
+                reinjected_count: 0,
+            },
+        );
+    }
+
+    pub fn insert(&mut self, txn: IndexedTransaction) -> Result<(), &'static str> {
+        if self.txns.contains_key(&txn.hash) || self.included.contains_key(&txn.hash) {
+            return Err("duplicate transaction");
+        }
+        if self.len() >= self.limit {
+            return Err("transaction pool is full");
+        }
+        self.track(txn.hash);
+        self.ids.push_back(txn.hash);
+        self.txns.insert(txn.hash, txn);
+        Ok(())
+    }
+
+    /// Insert transactions to be packed together, in order. Either all or none are inserted.
+    pub fn insert_bundle(&mut self, txns: Vec<IndexedTransaction>) -> Result<(), &'static str> {
+        if txns.is_empty() {
+            return Err("empty bundle");
+        }
+        for (i, txn) in txns.iter().enumerate() {
+            if self.txns.contains_key(&txn.hash) ||
+                self.included.contains_key(&txn.hash) ||
+                txns[..i].iter().any(|prev| prev.hash == txn.hash)
+            {
+                return Err("duplicate transaction");
+            }
+        }
+        if self.len() + txns.len() > self.limit {
+            return Err("transaction pool is full");
+        }
+        let member_ids: Vec<H256> = txns.iter().map(|txn| txn.hash).collect();
+        self.bundles.insert(member_ids[0], member_ids);
+        for txn in txns {
+            self.track(txn.hash);
+            self.ids.push_back(txn.hash);
+            self.txns.insert(txn.hash, txn);
+        }
+        Ok(())
+    }
+
+    /// Candidates for block assembly, in arrival order. Members of a bundle are grouped at the position of the first
+    /// member.
+    pub fn candidates(&self) -> Vec<Vec<IndexedTransaction>> {
+        let in_bundle: HashSet<&H256> = self.bundles.values().flat_map(|members| &members[1..]).collect();
+        self.ids
+            .iter()
+            .filter(|id| !in_bundle.contains(id))
+            .map(|id| match self.bundles.get(id) {
+                Some(members) => members.iter().filter_map(|id| self.txns.get(id)).cloned().collect(),
+                None => vec![self.txns[id].clone()],
+            })
+            .collect()
+    }
+
+    pub fn get(&self, id: &H256) -> Option<&IndexedTransaction> {
+        self.txns.get(id)
+    }
+
+    /// Transaction ids in arrival order.
+    pub fn ids(&self, offset: usize, limit: usize) -> Vec<H256> {
+        self.ids.iter().skip(offset).take(limit).cloned().collect()
+    }
+
+    /// Status of a pending, or an included but not yet expired, transaction.
+    pub fn status(&self, id: &H256) -> Option<&TransactionStatus> {
+        self.statuses.get(id)
+    }
+
+    /// Pending transactions not broadcast since `before`.
+    pub fn due_for_broadcast(&self, before: i64) -> Vec<H256> {
+        let statuses = &self.statuses;
+        self.ids
+            .iter()
+            .filter(|id| {
+                statuses
+                    .get(id)
+                    .map(|status| status.last_broadcast.map(|ts| ts <= before).unwrap_or(true))
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn mark_broadcast(&mut self, ids: &[H256], now: i64) {
+        for id in ids {
+            if let Some(status) = self.statuses.get_mut(id) {
+                status.last_broadcast = Some(now);
+                status.broadcast_count += 1;
+            }
+        }
+    }
+
+    /// Remove pending transactions expired at `now`, and set aside those included in blocks, by `included_in`
+    /// returning the block number. Included transactions dropped from the chain are re-injected, as standalone
+    /// transactions. Returns number of removed pending transactions.
+    pub fn prune<F: Fn(&H256) -> Option<i64>>(&mut self, now: i64, included_in: F) -> usize {
+        let txns = &mut self.txns;
+        let included = &mut self.included;
+        let statuses = &mut self.statuses;
+        let len = self.ids.len();
+        self.ids.retain(|id| {
+            let expired = txns.get(id).map(|txn| txn.expiration() <= now).unwrap_or(true);
+            if expired {
+                txns.remove(id);
+                statuses.remove(id);
+                return false;
+            }
+            match included_in(id) {
+                Some(block_number) => {
+                    included.insert(*id, txns.remove(id).unwrap());
+                    if let Some(status) = statuses.get_mut(id) {
+                        status.included_in = Some(block_number);
+                    }
+                    false
+                }
+                None => true,
+            }
+        });
+        let num_removed = len - self.ids.len();
+
+        let mut dropped = vec![];
+        self.included.retain(|id, txn| {
+            if txn.expiration() <= now {
+                statuses.remove(id);
+                return false;
+            }
+            match included_in(id) {
+                Some(block_number) => {
+                    if let Some(status) = statuses.get_mut(id) {
+                        status.included_in = Some(block_number);
+                    }
+                    true
+                }
+                None => {
+                    dropped.push(*id);
+                    false
+                }
+            }
+        });
+        // NOTE: Dropped transactions can't be applied yet, so they are re-injected even if the pool is full.
+        for id in dropped {
+            warn!("transaction {:?} dropped from the chain, re-injected", id);
+            let txn = self.included.remove(&id);
+            if let Some(status) = self.statuses.get_mut(&id) {
+                status.included_in = None;
+                status.last_broadcast = None;
+                status.reinjected_count += 1;
+            }
+            if let Some(txn) = txn {
+                self.ids.push_back(id);
+                self.txns.insert(id, txn);
+            }
+        }
+
+        // Remaining members of a partly included bundle are packed as standalone transactions.
+        let txns = &self.txns;
+        self.bundles.retain(|_, members| members.iter().all(|id| txns.contains_key(id)));
+        num_removed
+    }
+}
+
+/// Announce pending transactions to peers, i.e. newly submitted ones and those not included for a while.
+pub fn broadcast_pending_transactions(ctx: &AppContext) {
+    let rebroadcast_after_blocks = ctx.config.mempool.rebroadcast_after_blocks;
+    let now = Utc::now().timestamp_millis();
+    // Only never broadcast transactions, if rebroadcast is disabled.
+    let before = if rebroadcast_after_blocks > 0 {
+        now - rebroadcast_after_blocks * ctx.genesis_config.block_interval
+    } else {
+        i64::MIN
+    };
+    let mut pool = ctx.pending_pool.write().unwrap();
+    let ids = pool.due_for_broadcast(before);
+    if ids.is_empty() {
+        return;
+    }
+    // No receiver if no peer is connected, the transactions will be broadcast later.
+    if ctx.txn_relay.send(ids.clone()).is_ok() {
+        pool.mark_broadcast(&ids, now);
+    }
+}
+
+/// Rebroadcast pending transactions, and re-inject dropped ones, once every block.
+///
+/// Dropped transactions are still re-injected and announced when rebroadcast is disabled.
+pub async fn mempool_service(ctx: Arc<AppContext>, mut signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
+    if ctx.config.mempool.rebroadcast_after_blocks <= 0 {
+        warn!("transaction rebroadcast disabled");
+    } else {
+        info!(
+            "rebroadcast pending transactions after {} blocks",
+            ctx.config.mempool.rebroadcast_after_blocks
+        );
+    }
+
+    let block_interval = Duration::from_millis(ctx.genesis_config.block_interval as u64);
+    loop {
+        select! {
+            _ = signal.recv().fuse() => {
+                warn!("mempool service closed");
+                break;
+            }
+            _ = delay_for(block_interval).fuse() => {}
+        }
+        ctx.prune_pending_pool();
+        broadcast_pending_transactions(&ctx);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::Address;
+    use proto2::chain::{transaction::Contract, transaction::Raw as RawTransaction, Transaction};
+    use proto2::contract::TransferContract;
+
+    fn new_transaction(expiration: i64) -> IndexedTransaction {
+        IndexedTransaction::from_raw(Transaction {
+            raw_data: Some(RawTransaction {
+                expiration,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_transaction_pool() {
+        let mut pool = TransactionPool::new(2);
+        let txn1 = new_transaction(1_000);
+        let txn2 = new_transaction(2_000);
+        pool.insert(txn1.clone()).unwrap();
+        assert!(pool.insert(txn1.clone()).is_err());
+        pool.insert(txn2.clone()).unwrap();
+        assert!(pool.insert(new_transaction(3_000)).is_err());
+
+        assert_eq!(pool.ids(0, 10), vec![txn1.hash, txn2.hash]);
+        assert_eq!(pool.ids(1, 10), vec![txn2.hash]);
+
+        // txn1 expires
+        assert_eq!(pool.prune(1_000, |_| None), 1);
+        assert!(pool.get(&txn1.hash).is_none());
+        // txn2 is included
+        assert_eq!(pool.prune(1_000, |id| if *id == txn2.hash { Some(1) } else { None }), 1);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_rebroadcast_and_reinject() {
+        let mut pool = TransactionPool::new(2);
+        let txn1 = new_transaction(10_000);
+        let txn2 = new_transaction(20_000);
+        pool.insert(txn1.clone()).unwrap();
+        pool.insert(txn2.clone()).unwrap();
+        assert_eq!(pool.due_for_broadcast(0), vec![txn1.hash, txn2.hash]);
+        pool.mark_broadcast(&[txn1.hash, txn2.hash], 1_000);
+        assert!(pool.due_for_broadcast(999).is_empty());
+        pool.mark_broadcast(&[txn2.hash], 2_000);
+        assert_eq!(pool.due_for_broadcast(1_000), vec![txn1.hash]);
+        assert_eq!(pool.status(&txn2.hash).unwrap().broadcast_count, 2);
+
+        // txn1 is included in block 100
+        assert_eq!(pool.prune(3_000, |id| if *id == txn1.hash { Some(100) } else { None }), 1);
+        assert_eq!(pool.ids(0, 10), vec![txn2.hash]);
+        assert_eq!(pool.status(&txn1.hash).unwrap().included_in, Some(100));
+        // included transactions are still duplicates
+        assert!(pool.insert(txn1.clone()).is_err());
+
+        // txn1 is dropped by a fork switch, and re-injected
+        assert_eq!(pool.prune(4_000, |_| None), 0);
+        assert_eq!(pool.ids(0, 10), vec![txn2.hash, txn1.hash]);
+        let status = pool.status(&txn1.hash).unwrap();
+        assert_eq!(status.included_in, None);
+        assert_eq!(status.last_broadcast, None);
+        assert_eq!(status.reinjected_count, 1);
+        assert_eq!(pool.due_for_broadcast(1_000), vec![txn1.hash]);
+
+        // included transactions are forgotten after expiration
+        assert_eq!(pool.prune(4_000, |_| Some(101)), 2);
+        assert!(pool.status(&txn1.hash).is_some());
+        assert_eq!(pool.prune(20_000, |_| Some(101)), 0);
+        assert!(pool.status(&txn1.hash).is_none());
+        assert!(pool.status(&txn2.hash).is_none());
+    }
+
+    #[test]
+    fn test_bundle() {
+        let mut pool = TransactionPool::new(3);
+        let txn1 = new_transaction(1_000);
+        let delegate = new_transaction(2_000);
+        let sponsored = new_transaction(2_001);
+        pool.insert(txn1.clone()).unwrap();
+        assert!(pool.insert_bundle(vec![delegate.clone(), txn1.clone()]).is_err());
+        assert!(pool.insert_bundle(vec![delegate.clone(), delegate.clone()]).is_err());
+        pool.insert_bundle(vec![delegate.clone(), sponsored.clone()]).unwrap();
+        // all or none
+        assert!(pool.insert_bundle(vec![new_transaction(3_000), new_transaction(3_001)]).is_err());
+        assert_eq!(pool.len(), 3);
+
+        let candidates = pool.candidates();
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0][0].hash, txn1.hash);
+        assert_eq!(
+            candidates[1].iter().map(|txn| txn.hash).collect::<Vec<_>>(),
+            vec![delegate.hash, sponsored.hash]
+        );
+
+        // delegation is included, the sponsored transaction becomes standalone
+        assert_eq!(pool.prune(0, |id| if *id == delegate.hash { Some(1) } else { None }), 1);
+        let candidates = pool.candidates();
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[1].len(), 1);
+        assert_eq!(candidates[1][0].hash, sponsored.hash);
+    }
+
+    fn new_contract_transaction<T: BuiltinContractExt>(cntr: &T) -> IndexedTransaction {
+        IndexedTransaction::from_raw(Transaction {
+            raw_data: Some(RawTransaction {
+                contract: Some(Contract {
+                    r#type: cntr.type_code() as i32,
+                    parameter: cntr.to_any(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_check_sponsored_bundle() {
+        let sponsor: Address = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8".parse().unwrap();
+        let sender: Address = "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs".parse().unwrap();
+
+        let delegate = new_contract_transaction(&DelegateResourceContract {
+            owner_address: sponsor.as_bytes().to_vec(),
+            balance: 1_000_000,
+            receiver_address: sender.as_bytes().to_vec(),
+            ..Default::default()
+        });
+        let transfer = |owner: Address| {
+            new_contract_transaction(&TransferContract {
+                owner_address: owner.as_bytes().to_vec(),
+                to_address: sponsor.as_bytes().to_vec(),
+                amount: 1,
+            })
+        };
+        let undelegate = |balance: i64| {
+            new_contract_transaction(&UnDelegateResourceContract {
+                owner_address: sponsor.as_bytes().to_vec(),
+                balance,
+                receiver_address: sender.as_bytes().to_vec(),
+                ..Default::default()
+            })
+        };
+
+        assert!(check_sponsored_bundle(&[delegate.clone(), transfer(sender)]).is_ok());
+        assert!(check_sponsored_bundle(&[delegate.clone(), transfer(sender), undelegate(1_000_000)]).is_ok());
+        assert!(check_sponsored_bundle(&[delegate.clone(), transfer(sponsor)]).is_err());
+        assert!(check_sponsored_bundle(&[delegate.clone(), transfer(sender), undelegate(1)]).is_err());
+        assert!(check_sponsored_bundle(&[transfer(sender), delegate.clone()]).is_err());
+        assert!(check_sponsored_bundle(&[delegate]).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Wire formats of exporters.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use config::MetricsConfig;
+
+use super::{MetricKind, Sample};
+
+/// An exporter encodes snapshots of the registry.
+pub trait Exporter: Send {
+    /// Pull exporters are scraped over HTTP, others push to the collector by UDP every `push-interval`.
+    fn is_pull(&self) -> bool {
+        false
+    }
+
+    /// Encode a snapshot, taken at `timestamp` in ms.
+    fn encode(&mut self, samples: &[Sample], timestamp: i64) -> String;
+}
+
+pub fn new_exporter(config: &MetricsConfig) -> Result<Box<dyn Exporter>, String> {
+    match &*config.exporter {
+        "prometheus" => Ok(Box::new(Prometheus::new(&config.prefix))),
+        "statsd" => Ok(Box::new(StatsD::new(&config.prefix))),
+        "influx" => Ok(Box::new(Influx::new(&config.prefix))),
+        other => Err(format!("unknown metrics exporter {:?}", other)),
+    }
+}
+
+/// Prometheus text exposition format.
+pub struct Prometheus {
+    prefix: String,
+}
+
+impl Prometheus {
+    pub fn new(prefix: &str) -> Self {
+        Prometheus {
+            prefix: prefix.to_owned(),
+        }
+    }
+}
+
+impl Exporter for Prometheus {
+    fn is_pull(&self) -> bool {
+        true
+    }
+
+    fn encode(&mut self, samples: &[Sample], _timestamp: i64) -> String {
+        let mut buf = String::new();
+        for sample in samples {
+            let kind = match sample.kind {
+                MetricKind::Counter => "counter",
+                MetricKind::Gauge => "gauge",
+            };
+            let _ = writeln!(buf, "# HELP {}_{} {}", self.prefix, sample.name, sample.help);
+            let _ = writeln!(buf, "# TYPE {}_{} {}", self.prefix, sample.name, kind);
+            let _ = writeln!(buf, "{}_{} {}", self.prefix, sample.name, sample.value);
+        }
+        buf
+    }
+}
+
+/// StatsD plain text format. Counters are sent as increments since the last push.
+pub struct StatsD {
+    prefix: String,
+    last_counters: HashMap<&'static str, i64>,
+}
+
+impl StatsD {
+    pub fn new(prefix: &str) -> Self {
+        StatsD {
+            prefix: prefix.to_owned(),
+            last_counters: HashMap::new(),
+        }
+    }
+}
+
+impl Exporter for StatsD {
+    fn encode(&mut self, samples: &[Sample], _timestamp: i64) -> String {
+        let mut buf = String::new();
+        for sample in samples {
+            match sample.kind {
+                MetricKind::Counter => {
+                    let last = self.last_counters.insert(sample.name, sample.value).unwrap_or(0);
+                    let _ = writeln!(buf, "{}.{}:{}|c", self.prefix, sample.name, sample.value - last);
+                }
+                MetricKind::Gauge => {
+                    let _ = writeln!(buf, "{}.{}:{}|g", self.prefix, sample.name, sample.value);
+                }
+            }
+        }
+        buf
+    }
+}
+
+/// InfluxDB line protocol, one line per snapshot with a field per metric.
+pub struct Influx {
+    measurement: String,
+}
+
+impl Influx {
+    pub fn new(measurement: &str) -> Self {
+        Influx {
+            measurement: measurement.to_owned(),
+        }
+    }
+}
+
+impl Exporter for Influx {
+    fn encode(&mut self, samples: &[Sample], timestamp: i64) -> String {
+        if samples.is_empty() {
+            return String::new();
+        }
+        let fields = samples
+            .iter()
+            .map(|sample| format!("{}={}i", sample.name, sample.value))
+            .collect::<Vec<_>>()
+            .join(",");
+        // Timestamp in ns, the default precision.
+        format!("{} {} {}\n", self.measurement, fields, timestamp * 1_000_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(received: i64) -> Vec<Sample> {
+        vec![
+            Sample {
+                name: "blocks_received_total",
+                help: "Blocks received",
+                kind: MetricKind::Counter,
+                value: received,
+            },
+            Sample {
+                name: "peers",
+                help: "Number of peers",
+                kind: MetricKind::Gauge,
+                value: 3,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_prometheus() {
+        let mut exporter = Prometheus::new("opentron");
+        assert!(exporter.is_pull());
+        let out = exporter.encode(&samples(10), 0);
+        assert!(out.contains("# TYPE opentron_blocks_received_total counter\nopentron_blocks_received_total 10\n"));
+        assert!(out.contains("# HELP opentron_peers Number of peers\n# TYPE opentron_peers gauge\nopentron_peers 3\n"));
+    }
+
+    #[test]
+    fn test_statsd() {
+        let mut exporter = StatsD::new("opentron");
+        assert_eq!(
+            exporter.encode(&samples(10), 0),
+            "opentron.blocks_received_total:10|c\nopentron.peers:3|g\n"
+        );
+        assert_eq!(
+            exporter.encode(&samples(15), 0),
+            "opentron.blocks_received_total:5|c\nopentron.peers:3|g\n"
+        );
+    }
+
+    #[test]
+    fn test_influx() {
+        let mut exporter = Influx::new("opentron");
+        assert_eq!(
+            exporter.encode(&samples(10), 1_600_000_000_000),
+            "opentron blocks_received_total=10i,peers=3i 1600000000000000000\n"
+        );
+        assert_eq!(exporter.encode(&[], 0), "");
+    }
+}
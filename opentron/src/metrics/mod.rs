@@ -0,0 +1,268 @@
+//! Node metrics, exported by the exporter selected in `[metrics]` config.
+//!
+//! Counters are updated where events happen, gauges are collected from the app context right before each export.
+//! Prometheus scrapes the registry over HTTP, StatsD and InfluxDB (or Telegraf) receive it by UDP push.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+
+use log::warn;
+use state::keys::{BlockResourceUsage, DynamicProperty};
+
+use crate::context::AppContext;
+
+mod exporter;
+pub mod service;
+
+pub use exporter::{new_exporter, Exporter, Influx, Prometheus, StatsD};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Monotonically increasing.
+    Counter,
+    Gauge,
+}
+
+/// Static description of a metric.
+#[derive(Debug)]
+pub struct MetricDesc {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: MetricKind,
+}
+
+pub static BLOCK_HEIGHT: MetricDesc = MetricDesc {
+    name: "block_height",
+    help: "Highest block number in chain-db",
+    kind: MetricKind::Gauge,
+};
+pub static STATE_BLOCK_HEIGHT: MetricDesc = MetricDesc {
+    name: "state_block_height",
+    help: "Latest block number applied to state-db",
+    kind: MetricKind::Gauge,
+};
+pub static SYNCING: MetricDesc = MetricDesc {
+    name: "syncing",
+    help: "1 if the node is syncing",
+    kind: MetricKind::Gauge,
+};
+pub static PEERS: MetricDesc = MetricDesc {
+    name: "peers",
+    help: "Number of connected channel peers",
+    kind: MetricKind::Gauge,
+};
+pub static ACTIVE_CONNECTIONS: MetricDesc = MetricDesc {
+    name: "active_connections",
+    help: "Number of active outbound connections",
+    kind: MetricKind::Gauge,
+};
+pub static PRODUCER_LEASE: MetricDesc = MetricDesc {
+    name: "producer_lease",
+    help: "1 if this node holds the producer HA lease",
+    kind: MetricKind::Gauge,
+};
+pub static BLOCK_BANDWIDTH_USAGE: MetricDesc = MetricDesc {
+    name: "block_bandwidth_usage",
+    help: "Bandwidth consumed by transactions of the latest block in state-db",
+    kind: MetricKind::Gauge,
+};
+pub static BLOCK_ENERGY_USAGE: MetricDesc = MetricDesc {
+    name: "block_energy_usage",
+    help: "Energy used by transactions of the latest block in state-db",
+    kind: MetricKind::Gauge,
+};
+pub static PENDING_TRANSACTIONS: MetricDesc = MetricDesc {
+    name: "pending_transactions",
+    help: "Number of transactions in the pending pool",
+    kind: MetricKind::Gauge,
+};
+pub static BLOCKS_RECEIVED: MetricDesc = MetricDesc {
+    name: "blocks_received_total",
+    help: "Blocks received from peers and inserted into chain-db",
+    kind: MetricKind::Counter,
+};
+pub static TRANSACTIONS_RECEIVED: MetricDesc = MetricDesc {
+    name: "transactions_received_total",
+    help: "Transactions accepted into the pending pool",
+    kind: MetricKind::Counter,
+};
+pub static UNKNOWN_MESSAGES: MetricDesc = MetricDesc {
+    name: "unknown_messages_total",
+    help: "Channel messages of unknown types, skipped",
+    kind: MetricKind::Counter,
+};
+pub static CLOCK_DRIFT: MetricDesc = MetricDesc {
+    name: "clock_drift_ms",
+    help: "System clock drift against NTP servers, positive if the local clock is behind",
+    kind: MetricKind::Gauge,
+};
+pub static HEAVY_QUERIES_REJECTED: MetricDesc = MetricDesc {
+    name: "heavy_queries_rejected_total",
+    help: "GraphQL heavy queries rejected by max-concurrent-heavy-queries",
+    kind: MetricKind::Counter,
+};
+
+/// All metrics, registered up front so that they are exported before their first update.
+pub static ALL_METRICS: &[&MetricDesc] = &[
+    &BLOCK_HEIGHT,
+    &STATE_BLOCK_HEIGHT,
+    &SYNCING,
+    &PEERS,
+    &ACTIVE_CONNECTIONS,
+    &PRODUCER_LEASE,
+    &BLOCK_BANDWIDTH_USAGE,
+    &BLOCK_ENERGY_USAGE,
+    &PENDING_TRANSACTIONS,
+    &BLOCKS_RECEIVED,
+    &TRANSACTIONS_RECEIVED,
+    &UNKNOWN_MESSAGES,
+    &CLOCK_DRIFT,
+    &HEAVY_QUERIES_REJECTED,
+];
+
+/// Value of a metric at export time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: MetricKind,
+    pub value: i64,
+}
+
+struct Metric {
+    desc: &'static MetricDesc,
+    value: AtomicI64,
+}
+
+/// Registry of all metrics, shared by exporters.
+#[derive(Default)]
+pub struct Registry {
+    metrics: RwLock<BTreeMap<&'static str, Metric>>,
+}
+
+impl Registry {
+    /// A registry with all metrics of `ALL_METRICS`.
+    pub fn new() -> Self {
+        let metrics = ALL_METRICS
+            .iter()
+            .map(|&desc| {
+                let metric = Metric {
+                    desc,
+                    value: AtomicI64::new(0),
+                };
+                (desc.name, metric)
+            })
+            .collect();
+        Registry {
+            metrics: RwLock::new(metrics),
+        }
+    }
+
+    fn with_metric<F: FnOnce(&AtomicI64)>(&self, desc: &'static MetricDesc, f: F) {
+        if let Some(metric) = self.metrics.read().unwrap().get(desc.name) {
+            return f(&metric.value);
+        }
+        let mut metrics = self.metrics.write().unwrap();
+        let metric = metrics.entry(desc.name).or_insert_with(|| Metric {
+            desc,
+            value: AtomicI64::new(0),
+        });
+        f(&metric.value)
+    }
+
+    /// Increase a counter.
+    pub fn inc(&self, desc: &'static MetricDesc, by: i64) {
+        debug_assert_eq!(desc.kind, MetricKind::Counter);
+        self.with_metric(desc, |value| {
+            value.fetch_add(by, Ordering::Relaxed);
+        })
+    }
+
+    /// Set a gauge.
+    pub fn set(&self, desc: &'static MetricDesc, val: i64) {
+        debug_assert_eq!(desc.kind, MetricKind::Gauge);
+        self.with_metric(desc, |value| value.store(val, Ordering::Relaxed))
+    }
+
+    /// Current values of all metrics, ordered by name.
+    pub fn snapshot(&self) -> Vec<Sample> {
+        self.metrics
+            .read()
+            .unwrap()
+            .values()
+            .map(|metric| Sample {
+                name: metric.desc.name,
+                help: metric.desc.help,
+                kind: metric.desc.kind,
+                value: metric.value.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Update gauges from the app context, then take a snapshot.
+pub fn collect(ctx: &AppContext) -> Vec<Sample> {
+    let metrics = &ctx.metrics;
+    metrics.set(&BLOCK_HEIGHT, ctx.chain_db.get_block_height());
+    if let Some(ref state_db) = ctx.state_db {
+        state_db.catch_up_with_primary();
+        let usage = state_db
+            .get(&DynamicProperty::LatestBlockNumber)
+            .and_then(|num| {
+                let num = num.unwrap_or(0);
+                metrics.set(&STATE_BLOCK_HEIGHT, num);
+                state_db.get(&BlockResourceUsage(num))
+            });
+        match usage {
+            Ok(usage) => {
+                let usage = usage.unwrap_or_default();
+                metrics.set(&BLOCK_BANDWIDTH_USAGE, usage.bandwidth_usage);
+                metrics.set(&BLOCK_ENERGY_USAGE, usage.energy);
+            }
+            Err(e) => warn!("can not read state-db: {}", e),
+        }
+    }
+    metrics.set(&SYNCING, *ctx.syncing.read().unwrap() as i64);
+    metrics.set(&PEERS, ctx.peer_infos.read().unwrap().len() as i64);
+    metrics.set(&ACTIVE_CONNECTIONS, ctx.num_active_connections.load(Ordering::SeqCst) as i64);
+    metrics.set(&PENDING_TRANSACTIONS, ctx.pending_pool.read().unwrap().len() as i64);
+    if let Some(ref drift) = *ctx.clock_drift.read().unwrap() {
+        metrics.set(&CLOCK_DRIFT, drift.offset);
+    }
+    if ctx.config.producer.ha.enable {
+        metrics.set(&PRODUCER_LEASE, (ctx.producer_lease.load(Ordering::SeqCst) > 0) as i64);
+    }
+    metrics.snapshot()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::default();
+        registry.inc(&BLOCKS_RECEIVED, 2);
+        registry.inc(&BLOCKS_RECEIVED, 3);
+        registry.set(&PEERS, 7);
+        registry.set(&PEERS, 5);
+
+        let samples = registry.snapshot();
+        assert_eq!(samples.len(), 2);
+        assert_eq!((samples[0].name, samples[0].value), ("blocks_received_total", 5));
+        assert_eq!((samples[1].name, samples[1].value), ("peers", 5));
+        assert_eq!(samples[1].kind, MetricKind::Gauge);
+    }
+
+    #[test]
+    fn test_registry_new() {
+        let registry = Registry::new();
+        let samples = registry.snapshot();
+        assert_eq!(samples.len(), ALL_METRICS.len());
+        assert!(samples.iter().all(|sample| sample.value == 0));
+
+        registry.inc(&HEAVY_QUERIES_REJECTED, 1);
+        assert_eq!(registry.snapshot().len(), ALL_METRICS.len());
+    }
+}
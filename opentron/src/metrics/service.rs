@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::future::FutureExt;
+use futures::select;
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Response, Server, StatusCode,
+};
+use log::{debug, info, warn};
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+use tokio::time::delay_for;
+
+use super::{collect, new_exporter, Exporter};
+use crate::context::AppContext;
+
+/// Export metrics, by serving the Prometheus endpoint or pushing to a StatsD / InfluxDB collector.
+pub async fn metrics_service(ctx: Arc<AppContext>, signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
+    let config = &ctx.config.metrics;
+    if !config.enable {
+        warn!("metrics exporter disabled");
+        return Ok(());
+    }
+    let exporter = new_exporter(config)?;
+    if exporter.is_pull() {
+        serve(ctx.clone(), exporter, signal).await
+    } else {
+        push(ctx.clone(), exporter, signal).await
+    }
+}
+
+async fn serve(
+    ctx: Arc<AppContext>,
+    exporter: Box<dyn Exporter>,
+    mut signal: broadcast::Receiver<()>,
+) -> Result<(), Box<dyn Error>> {
+    let addr = ctx.config.metrics.endpoint.parse()?;
+    let exporter = Arc::new(Mutex::new(exporter));
+
+    let metrics_service = make_service_fn(move |_| {
+        let ctx = ctx.clone();
+        let exporter = exporter.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                let ctx = ctx.clone();
+                let exporter = exporter.clone();
+                async move {
+                    let mut response = Response::new(Body::empty());
+                    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+                        *response.status_mut() = StatusCode::NOT_FOUND;
+                        return Ok::<_, hyper::Error>(response);
+                    }
+                    let samples = tokio::task::block_in_place(|| collect(&ctx));
+                    let body = exporter.lock().unwrap().encode(&samples, Utc::now().timestamp_millis());
+                    *response.body_mut() = Body::from(body);
+                    response.headers_mut().insert(
+                        header::CONTENT_TYPE,
+                        header::HeaderValue::from_static("text/plain; version=0.0.4"),
+                    );
+                    Ok(response)
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr).serve(metrics_service);
+    info!("prometheus metrics listening on http://{}/metrics", addr);
+
+    let _ = server.with_graceful_shutdown(signal.recv().map(|_| ())).await;
+    Ok(())
+}
+
+async fn push(
+    ctx: Arc<AppContext>,
+    mut exporter: Box<dyn Exporter>,
+    mut signal: broadcast::Receiver<()>,
+) -> Result<(), Box<dyn Error>> {
+    let config = &ctx.config.metrics;
+    let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+    info!("pushing {} metrics to {}", config.exporter, config.endpoint);
+
+    loop {
+        select! {
+            _ = signal.recv().fuse() => {
+                warn!("metrics service closed");
+                break;
+            }
+            _ = delay_for(Duration::from_secs(config.push_interval)).fuse() => {}
+        }
+        let samples = tokio::task::block_in_place(|| collect(&ctx));
+        let payload = exporter.encode(&samples, Utc::now().timestamp_millis());
+        // Pushes are fire-and-forget, a lost one is not retried.
+        if let Err(e) = socket.send_to(payload.as_bytes(), &*config.endpoint).await {
+            debug!("push metrics failed: {}", e);
+        }
+    }
+    Ok(())
+}
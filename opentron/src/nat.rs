@@ -0,0 +1,389 @@
+//! NAT traversal, port mapping on the gateway by UPnP IGD or NAT-PMP, see `nat` in `[protocol]` config.
+//!
+//! Mapped ports are the TCP port of channel and the UDP port of discovery, so that nodes behind NAT can accept
+//! inbound peers. Mappings are leased and renewed periodically.
+
+use std::error::Error;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::FutureExt;
+use futures::select;
+use log::{info, warn};
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+use tokio::time::{delay_for, timeout};
+
+use crate::context::AppContext;
+use crate::util::{get_my_ip, new_error};
+
+const NAT_PMP_PORT: u16 = 5351;
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+const NAT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Lease of port mappings, in seconds. Renewed at half of the lease.
+const MAPPING_LIFETIME: u32 = 3600;
+
+const WAN_SERVICE_TYPES: &[&str] = &[
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Udp,
+    Tcp,
+}
+
+impl Protocol {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Protocol::Udp => "UDP",
+            Protocol::Tcp => "TCP",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NatMethod {
+    Upnp,
+    NatPmp(Ipv4Addr),
+}
+
+impl NatMethod {
+    /// Parse from `[protocol]` config. None if NAT traversal is disabled.
+    pub fn from_config(ctx: &AppContext) -> Result<Option<Self>, Box<dyn Error>> {
+        let config = &ctx.config.protocol;
+        match &*config.nat {
+            "none" | "" => Ok(None),
+            "upnp" => Ok(Some(NatMethod::Upnp)),
+            "nat-pmp" => {
+                let gateway = if config.nat_gateway.is_empty() {
+                    default_gateway()?
+                } else {
+                    config.nat_gateway.parse()?
+                };
+                Ok(Some(NatMethod::NatPmp(gateway)))
+            }
+            other => Err(new_error(&format!("unknown NAT method {:?}", other))),
+        }
+    }
+
+    /// Query the external IP of the gateway.
+    pub async fn external_ip(&self) -> Result<IpAddr, Box<dyn Error>> {
+        match self {
+            NatMethod::Upnp => {
+                let gateway = UpnpGateway::discover().await?;
+                gateway.external_ip().await
+            }
+            NatMethod::NatPmp(gateway) => {
+                let resp = nat_pmp_request(*gateway, &[0, 0]).await?;
+                parse_nat_pmp_external_ip(&resp).map(IpAddr::V4)
+            }
+        }
+    }
+
+    /// Map a port on the gateway to the same port of this host, returns the external port.
+    pub async fn add_port_mapping(&self, protocol: Protocol, port: u16) -> Result<u16, Box<dyn Error>> {
+        match self {
+            NatMethod::Upnp => {
+                let gateway = UpnpGateway::discover().await?;
+                gateway.add_port_mapping(protocol, port).await?;
+                Ok(port)
+            }
+            NatMethod::NatPmp(gateway) => {
+                let opcode = match protocol {
+                    Protocol::Udp => 1,
+                    Protocol::Tcp => 2,
+                };
+                let mut req = vec![0, opcode, 0, 0];
+                req.extend_from_slice(&port.to_be_bytes());
+                req.extend_from_slice(&port.to_be_bytes());
+                req.extend_from_slice(&MAPPING_LIFETIME.to_be_bytes());
+                let resp = nat_pmp_request(*gateway, &req).await?;
+                parse_nat_pmp_mapping(&resp, opcode)
+            }
+        }
+    }
+}
+
+/// Default IPv4 gateway, from the Linux routing table.
+fn default_gateway() -> Result<Ipv4Addr, Box<dyn Error>> {
+    let routes = fs::read_to_string("/proc/net/route")?;
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(parse_default_route)
+        .next()
+        .ok_or_else(|| new_error("default gateway not found, set nat-gateway in config"))
+}
+
+fn parse_default_route(line: &str) -> Option<Ipv4Addr> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 3 || fields[1] != "00000000" {
+        return None;
+    }
+    // in host byte order, i.e. little endian
+    let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+    Some(Ipv4Addr::from(gateway.to_le_bytes()))
+}
+
+async fn nat_pmp_request(gateway: Ipv4Addr, req: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.send_to(req, SocketAddr::new(gateway.into(), NAT_PMP_PORT)).await?;
+    let mut buf = [0u8; 16];
+    let (n, _) = timeout(NAT_TIMEOUT, socket.recv_from(&mut buf)).await??;
+    Ok(buf[..n].to_vec())
+}
+
+fn check_nat_pmp_response(resp: &[u8], opcode: u8, len: usize) -> Result<(), Box<dyn Error>> {
+    if resp.len() < len || resp[0] != 0 || resp[1] != 128 + opcode {
+        return Err(new_error("invalid NAT-PMP response"));
+    }
+    let result_code = u16::from_be_bytes([resp[2], resp[3]]);
+    if result_code != 0 {
+        return Err(new_error(&format!("NAT-PMP request failed, result code {}", result_code)));
+    }
+    Ok(())
+}
+
+fn parse_nat_pmp_external_ip(resp: &[u8]) -> Result<Ipv4Addr, Box<dyn Error>> {
+    check_nat_pmp_response(resp, 0, 12)?;
+    Ok(Ipv4Addr::new(resp[8], resp[9], resp[10], resp[11]))
+}
+
+fn parse_nat_pmp_mapping(resp: &[u8], opcode: u8) -> Result<u16, Box<dyn Error>> {
+    check_nat_pmp_response(resp, opcode, 16)?;
+    Ok(u16::from_be_bytes([resp[10], resp[11]]))
+}
+
+/// Find the text of the first `<tag>` element after `from`.
+fn find_element<'a>(xml: &'a str, tag: &str, from: usize) -> Option<(&'a str, usize)> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml[from..].find(&open)? + from + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some((xml[start..end].trim(), end))
+}
+
+fn parse_ssdp_location(resp: &str) -> Option<&str> {
+    resp.lines().find_map(|line| {
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next()?;
+        if name.trim().eq_ignore_ascii_case("location") {
+            Some(parts.next()?.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Find `(service type, control URL)` of the WAN connection service, in the device description.
+fn parse_control_url(description: &str, location: &str) -> Option<(&'static str, String)> {
+    for &service_type in WAN_SERVICE_TYPES {
+        let pos = match description.find(&format!("<serviceType>{}</serviceType>", service_type)) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let (control_url, _) = find_element(description, "controlURL", pos)?;
+        if control_url.starts_with("http://") {
+            return Some((service_type, control_url.to_owned()));
+        }
+        // relative to the root of location
+        let root_end = location.find("://").and_then(|i| location[i + 3..].find('/').map(|j| i + 3 + j))?;
+        let sep = if control_url.starts_with('/') { "" } else { "/" };
+        return Some((service_type, format!("{}{}{}", &location[..root_end], sep, control_url)));
+    }
+    None
+}
+
+struct UpnpGateway {
+    control_url: String,
+    service_type: &'static str,
+    /// IP of this host, as seen by the gateway.
+    local_ip: IpAddr,
+    client: reqwest::Client,
+}
+
+impl UpnpGateway {
+    async fn discover() -> Result<Self, Box<dyn Error>> {
+        let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\
+             ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n",
+            SSDP_ADDR
+        );
+        socket.send_to(search.as_bytes(), SSDP_ADDR.parse::<SocketAddr>()?).await?;
+        let mut buf = [0u8; 1500];
+        let (n, gateway_addr) = timeout(NAT_TIMEOUT, socket.recv_from(&mut buf)).await??;
+        let resp = String::from_utf8_lossy(&buf[..n]);
+        let location = parse_ssdp_location(&resp).ok_or_else(|| new_error("no location in SSDP response"))?;
+
+        let client = reqwest::Client::builder().timeout(NAT_TIMEOUT).build()?;
+        let description = client.get(location).send().await?.text().await?;
+        let (service_type, control_url) =
+            parse_control_url(&description, location).ok_or_else(|| new_error("no WAN connection service"))?;
+
+        let local_ip = {
+            let probe = std::net::UdpSocket::bind("0.0.0.0:0")?;
+            probe.connect(gateway_addr)?;
+            probe.local_addr()?.ip()
+        };
+
+        Ok(UpnpGateway {
+            control_url,
+            service_type,
+            local_ip,
+            client,
+        })
+    }
+
+    async fn soap_request(&self, action: &str, args: &str) -> Result<String, Box<dyn Error>> {
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+             s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{action} xmlns:u=\"{service}\">{args}</u:{action}></s:Body></s:Envelope>",
+            action = action,
+            service = self.service_type,
+            args = args,
+        );
+        let resp = self
+            .client
+            .post(&self.control_url)
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header("SOAPAction", format!("\"{}#{}\"", self.service_type, action))
+            .body(body)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(new_error(&format!("UPnP {} failed, status {}", action, resp.status())));
+        }
+        Ok(resp.text().await?)
+    }
+
+    async fn external_ip(&self) -> Result<IpAddr, Box<dyn Error>> {
+        let resp = self.soap_request("GetExternalIPAddress", "").await?;
+        let (ip, _) =
+            find_element(&resp, "NewExternalIPAddress", 0).ok_or_else(|| new_error("no external IP in response"))?;
+        Ok(ip.parse()?)
+    }
+
+    async fn add_port_mapping(&self, protocol: Protocol, port: u16) -> Result<(), Box<dyn Error>> {
+        let args = format!(
+            "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort>\
+             <NewProtocol>{protocol}</NewProtocol><NewInternalPort>{port}</NewInternalPort>\
+             <NewInternalClient>{client}</NewInternalClient><NewEnabled>1</NewEnabled>\
+             <NewPortMappingDescription>opentron</NewPortMappingDescription>\
+             <NewLeaseDuration>{lifetime}</NewLeaseDuration>",
+            port = port,
+            protocol = protocol.as_str(),
+            client = self.local_ip,
+            lifetime = MAPPING_LIFETIME,
+        );
+        self.soap_request("AddPortMapping", &args).await?;
+        Ok(())
+    }
+}
+
+/// Ports to be mapped, channel TCP and discovery UDP.
+fn ports_to_map(ctx: &AppContext) -> Vec<(Protocol, u16)> {
+    let config = &ctx.config.protocol;
+    let mut ports = vec![];
+    if let Ok(addr) = config.channel.endpoint.parse::<SocketAddr>() {
+        ports.push((Protocol::Tcp, addr.port()));
+    }
+    if config.discovery.enable {
+        if let Ok(addr) = config.discovery.endpoint.parse::<SocketAddr>() {
+            ports.push((Protocol::Udp, addr.port()));
+        }
+    }
+    ports
+}
+
+async fn map_ports(method: &NatMethod, ctx: &AppContext) {
+    for (protocol, port) in ports_to_map(ctx) {
+        match method.add_port_mapping(protocol, port).await {
+            Ok(external_port) if external_port != port => warn!(
+                "{} port {} mapped to external port {}, which is not advertised",
+                protocol.as_str(),
+                port,
+                external_port
+            ),
+            Ok(_) => info!("{} port {} mapped by {:?}", protocol.as_str(), port, method),
+            Err(e) => warn!("{} port {} mapping failed: {}", protocol.as_str(), port, e),
+        }
+    }
+}
+
+/// IP address advertised to peers. `external-ip` in config, or queried from the NAT gateway, or detected by a
+/// public service.
+pub async fn detect_external_ip(ctx: &AppContext) -> Result<IpAddr, Box<dyn Error>> {
+    let config = &ctx.config.protocol;
+    if !config.external_ip.is_empty() {
+        return Ok(config.external_ip.parse()?);
+    }
+    if let Some(method) = NatMethod::from_config(ctx)? {
+        match method.external_ip().await {
+            Ok(ip) => return Ok(ip),
+            Err(e) => warn!("query external ip from NAT gateway failed: {}", e),
+        }
+    }
+    Ok(get_my_ip().await?.parse()?)
+}
+
+/// Keep port mappings on the gateway.
+pub async fn nat_service(ctx: Arc<AppContext>, mut signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
+    let method = match NatMethod::from_config(&ctx)? {
+        Some(method) => method,
+        None => return Ok(()),
+    };
+
+    loop {
+        map_ports(&method, &ctx).await;
+        select! {
+            _ = signal.recv().fuse() => {
+                warn!("nat service closed");
+                break;
+            }
+            _ = delay_for(Duration::from_secs(MAPPING_LIFETIME as u64 / 2)).fuse() => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nat_pmp() {
+        let resp = [0, 128, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7];
+        assert_eq!(parse_nat_pmp_external_ip(&resp).unwrap(), Ipv4Addr::new(203, 0, 113, 7));
+        let resp = [0, 130, 0, 3, 0, 0, 0, 1, 0x49, 0xd8, 0x49, 0xd9, 0, 0, 0x0e, 0x10];
+        assert!(parse_nat_pmp_mapping(&resp, 2).is_err());
+        let resp = [0, 130, 0, 0, 0, 0, 0, 1, 0x49, 0xd8, 0x49, 0xd9, 0, 0, 0x0e, 0x10];
+        assert_eq!(parse_nat_pmp_mapping(&resp, 2).unwrap(), 18905);
+
+        let line = "eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0";
+        assert_eq!(parse_default_route(line), Some(Ipv4Addr::new(192, 168, 1, 1)));
+    }
+
+    #[test]
+    fn test_parse_upnp() {
+        let resp = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\n\
+                    Location: http://192.168.1.1:5000/rootDesc.xml\r\n";
+        let location = parse_ssdp_location(resp).unwrap();
+        assert_eq!(location, "http://192.168.1.1:5000/rootDesc.xml");
+
+        let description = "<service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+                           <controlURL>/ctl/IPConn</controlURL></service>";
+        let (service_type, control_url) = parse_control_url(description, location).unwrap();
+        assert_eq!(service_type, WAN_SERVICE_TYPES[0]);
+        assert_eq!(control_url, "http://192.168.1.1:5000/ctl/IPConn");
+
+        let resp = "<u:GetExternalIPAddressResponse><NewExternalIPAddress>203.0.113.7</NewExternalIPAddress>";
+        assert_eq!(find_element(resp, "NewExternalIPAddress", 0).unwrap().0, "203.0.113.7");
+    }
+}
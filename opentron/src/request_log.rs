@@ -0,0 +1,131 @@
+//! Request logging of API servers, with correlation ids.
+//!
+//! Each request gets an id, from the `X-Request-Id` header if the client sets a sane one, or a random one. The id is
+//! echoed in the response header, and tagged to all logs while handling the request, so that a slow request can be
+//! linked to the db operations it caused.
+
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use hyper::header::HeaderValue;
+use hyper::{Body, Method, Request, Response};
+use rand::Rng;
+use slog::{o, slog_info, slog_warn};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Max length of a client provided request id.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Request id set by the client, or a random one.
+pub fn request_id<B>(req: &Request<B>) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|val| val.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(|id| id.to_owned())
+        .unwrap_or_else(|| hex::encode(rand::thread_rng().gen::<[u8; 8]>()))
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() &&
+        id.len() <= MAX_REQUEST_ID_LEN &&
+        id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Client address, the first `X-Forwarded-For` entry if behind a proxy.
+pub fn caller<B>(req: &Request<B>, remote_addr: SocketAddr) -> String {
+    req.headers()
+        .get("x-forwarded-for")
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.split(',').next())
+        .map(|addr| addr.trim().to_owned())
+        .filter(|addr| !addr.is_empty())
+        .unwrap_or_else(|| remote_addr.to_string())
+}
+
+/// A request being handled.
+pub struct RequestLog {
+    logger: slog::Logger,
+    request_id: String,
+    method: Method,
+    path: String,
+    caller: String,
+    started_at: Instant,
+}
+
+impl RequestLog {
+    pub fn new<B>(server: &'static str, req: &Request<B>, remote_addr: SocketAddr) -> Self {
+        let request_id = request_id(req);
+        RequestLog {
+            logger: slog_scope::logger().new(o!("server" => server, "request_id" => request_id.clone())),
+            request_id,
+            method: req.method().clone(),
+            path: req.uri().path().to_owned(),
+            caller: caller(req, remote_addr),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Logger tagged with the request id, to be used while handling the request.
+    pub fn logger(&self) -> &slog::Logger {
+        &self.logger
+    }
+
+    /// Log the result, and echo the request id in the response.
+    pub fn finish(&self, response: &mut Response<Body>) {
+        let elapsed = self.started_at.elapsed().as_micros() as f64 / 1_000.0;
+        slog_info!(
+            self.logger,
+            "{} {} caller={} status={} time={}ms",
+            self.method,
+            self.path,
+            self.caller,
+            response.status().as_u16(),
+            elapsed
+        );
+        if let Ok(val) = HeaderValue::from_str(&self.request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, val);
+        }
+    }
+
+    /// Log a request failed without a response, i.e. connection errors.
+    pub fn fail<E: std::fmt::Display>(&self, error: &E) {
+        let elapsed = self.started_at.elapsed().as_micros() as f64 / 1_000.0;
+        slog_warn!(
+            self.logger,
+            "{} {} caller={} error={} time={}ms",
+            self.method,
+            self.path,
+            self.caller,
+            error,
+            elapsed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id() {
+        let req = Request::builder()
+            .header(REQUEST_ID_HEADER, "trace-42")
+            .header("x-forwarded-for", "1.2.3.4, 10.0.0.1")
+            .body(())
+            .unwrap();
+        let remote_addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        assert_eq!(request_id(&req), "trace-42");
+        assert_eq!(caller(&req, remote_addr), "1.2.3.4");
+
+        let req = Request::builder()
+            .header(REQUEST_ID_HEADER, "evil\"id with spaces")
+            .body(())
+            .unwrap();
+        let id = request_id(&req);
+        assert_eq!(id.len(), 16);
+        assert_ne!(id, request_id(&req));
+        assert_eq!(caller(&req, remote_addr), "127.0.0.1:8080");
+    }
+}
@@ -0,0 +1,969 @@
+//! Endpoint handlers, reading blocks from chain-db and balances from the read-only state-db.
+
+use std::convert::TryFrom;
+
+use ::keys::{Address, Public};
+use chain::{hash, IndexedBlock, IndexedTransaction};
+use chrono::Utc;
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::transaction::{self, Contract};
+use proto2::chain::{transaction::result::ContractStatus, ContractType, Transaction as TransactionPb};
+use proto2::contract as contract_pb;
+use proto2::state::TransactionReceipt;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use state::db::ReadOnlySolidStateDB;
+use state::keys;
+
+use super::types::*;
+use crate::context::AppContext;
+use crate::manager::actuators::{generate_created_contract_address, BuiltinContractExt};
+use crate::manager::events::{involved_addresses, AddressRole};
+
+/// Expiration of constructed transactions, after the reference block is fetched.
+const CONSTRUCTION_EXPIRATION: i64 = 60_000;
+
+#[derive(Debug)]
+pub enum ApiError {
+    NotFound,
+    InvalidNetwork,
+    InvalidRequest(String),
+    BlockNotFound,
+    StateUnavailable,
+    HistoricalBalanceUnsupported,
+    InvalidOperations(&'static str),
+    TransactionRejected(String),
+    Internal(String),
+    TransactionNotFound,
+}
+
+impl ApiError {
+    /// All errors returned by endpoints, for `/network/options`.
+    fn all() -> Vec<Error> {
+        vec![
+            ApiError::InvalidNetwork,
+            ApiError::InvalidRequest(String::new()),
+            ApiError::BlockNotFound,
+            ApiError::StateUnavailable,
+            ApiError::HistoricalBalanceUnsupported,
+            ApiError::InvalidOperations(""),
+            ApiError::TransactionRejected(String::new()),
+            ApiError::Internal(String::new()),
+            ApiError::TransactionNotFound,
+        ]
+        .into_iter()
+        .map(|err| Error { details: None, ..err.to_error() })
+        .collect()
+    }
+
+    pub fn to_error(&self) -> Error {
+        let (code, message, retriable, details) = match self {
+            ApiError::NotFound => (0, "endpoint not found", false, None),
+            ApiError::InvalidNetwork => (1, "invalid network identifier", false, None),
+            ApiError::InvalidRequest(msg) => (2, "invalid request", false, Some(msg.as_str())),
+            ApiError::BlockNotFound => (3, "block not found", true, None),
+            ApiError::StateUnavailable => (4, "state-db is not available", true, None),
+            ApiError::HistoricalBalanceUnsupported => (5, "historical balance lookup is not supported", false, None),
+            ApiError::InvalidOperations(msg) => (6, "invalid operations", false, Some(*msg)),
+            ApiError::TransactionRejected(msg) => (7, "transaction rejected", false, Some(msg.as_str())),
+            ApiError::Internal(msg) => (8, "internal error", true, Some(msg.as_str())),
+            ApiError::TransactionNotFound => (9, "transaction not found", true, None),
+        };
+        Error {
+            code,
+            message,
+            retriable,
+            details: details.map(|msg| json!({ "error": msg })),
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|e| ApiError::InvalidRequest(e.to_string()))
+}
+
+fn respond<T: Serialize>(resp: T) -> Result<Value, ApiError> {
+    serde_json::to_value(resp).map_err(|e| ApiError::Internal(e.to_string()))
+}
+
+fn internal<E: ToString>(e: E) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+/// Handle a POST request of an endpoint path.
+pub fn handle(ctx: &AppContext, path: &str, body: &[u8]) -> Result<Value, ApiError> {
+    match path {
+        "/network/list" => respond(network_list(ctx)),
+        "/network/status" => respond(network_status(ctx, parse(body)?)?),
+        "/network/options" => respond(network_options(ctx, parse(body)?)?),
+        "/block" => respond(block(ctx, parse(body)?)?),
+        "/account/balance" => respond(account_balance(ctx, parse(body)?)?),
+        "/mempool" => respond(mempool(ctx, parse(body)?)?),
+        "/mempool/transaction" => respond(mempool_transaction(ctx, parse(body)?)?),
+        "/construction/derive" => respond(construction_derive(ctx, parse(body)?)?),
+        "/construction/preprocess" => respond(construction_preprocess(ctx, parse(body)?)?),
+        "/construction/metadata" => respond(construction_metadata(ctx, parse(body)?)?),
+        "/construction/payloads" => respond(construction_payloads(ctx, parse(body)?)?),
+        "/construction/combine" => respond(construction_combine(ctx, parse(body)?)?),
+        "/construction/parse" => respond(construction_parse(ctx, parse(body)?)?),
+        "/construction/hash" => respond(construction_hash(ctx, parse(body)?)?),
+        "/construction/submit" => respond(construction_submit(ctx, parse(body)?)?),
+        _ => Err(ApiError::NotFound),
+    }
+}
+
+fn network_identifier(ctx: &AppContext) -> NetworkIdentifier {
+    NetworkIdentifier {
+        blockchain: BLOCKCHAIN.into(),
+        network: ctx.config.rosetta.network.clone(),
+    }
+}
+
+fn check_network(ctx: &AppContext, network: &NetworkIdentifier) -> Result<(), ApiError> {
+    if *network != network_identifier(ctx) {
+        return Err(ApiError::InvalidNetwork);
+    }
+    Ok(())
+}
+
+fn block_identifier_of(block: &IndexedBlock) -> BlockIdentifier {
+    BlockIdentifier {
+        index: block.number(),
+        hash: hex::encode(block.hash().as_bytes()),
+    }
+}
+
+fn decode_hex(raw: &str) -> Result<Vec<u8>, ApiError> {
+    hex::decode(raw.trim_start_matches("0x")).map_err(|e| ApiError::InvalidRequest(e.to_string()))
+}
+
+fn network_list(ctx: &AppContext) -> NetworkListResponse {
+    NetworkListResponse {
+        network_identifiers: vec![network_identifier(ctx)],
+    }
+}
+
+fn network_status(ctx: &AppContext, req: NetworkRequest) -> Result<NetworkStatusResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let head = ctx.chain_db.highest_block().map_err(internal)?;
+    let genesis = ctx.chain_db.get_genesis_block().map_err(internal)?;
+    let peers = ctx
+        .peer_infos
+        .read()
+        .unwrap()
+        .values()
+        .map(|peer| Peer {
+            peer_id: hex::encode(&peer.node_id),
+        })
+        .collect();
+    Ok(NetworkStatusResponse {
+        current_block_identifier: block_identifier_of(&head),
+        current_block_timestamp: head.timestamp(),
+        genesis_block_identifier: block_identifier_of(&genesis),
+        peers,
+    })
+}
+
+fn network_options(ctx: &AppContext, req: NetworkRequest) -> Result<NetworkOptionsResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    Ok(NetworkOptionsResponse {
+        version: Version {
+            rosetta_version: ROSETTA_VERSION,
+            node_version: env!("CARGO_PKG_VERSION"),
+        },
+        allow: Allow {
+            operation_statuses: vec![
+                OperationStatus {
+                    status: STATUS_SUCCESS,
+                    successful: true,
+                },
+                OperationStatus {
+                    status: STATUS_FAILED,
+                    successful: false,
+                },
+            ],
+            operation_types: OPERATION_TYPES.to_vec(),
+            errors: ApiError::all(),
+            historical_balance_lookup: false,
+        },
+    })
+}
+
+fn operation(index: i64, type_: &str, status: &str, address: &[u8], amount: Option<Amount>) -> Operation {
+    Operation {
+        operation_identifier: OperationIdentifier { index },
+        related_operations: vec![],
+        type_: type_.into(),
+        status: Some(status.into()),
+        account: Some(AccountIdentifier {
+            address: Address::try_from(address)
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|_| hex::encode(address)),
+        }),
+        amount,
+        metadata: None,
+    }
+}
+
+/// Operations of a transaction, indexed in order.
+struct Operations {
+    ops: Vec<Operation>,
+    status: &'static str,
+}
+
+impl Operations {
+    /// A change of liquid balance, skipped if zero.
+    fn change(&mut self, type_: &str, address: &[u8], amount: i64, currency: Currency) {
+        if amount != 0 {
+            let amount = Amount::new(amount, currency);
+            self.ops.push(operation(self.ops.len() as _, type_, self.status, address, Some(amount)));
+        }
+    }
+
+    /// A debit and its related credit, skipped if not positive.
+    fn transfer(&mut self, from: &[u8], to: &[u8], amount: i64, currency: Currency) {
+        if amount <= 0 {
+            return;
+        }
+        let debit = self.ops.len() as i64;
+        self.change(OP_TRANSFER, from, -amount, currency.clone());
+        self.change(OP_TRANSFER, to, amount, currency);
+        self.ops.last_mut().unwrap().related_operations = vec![OperationIdentifier { index: debit }];
+    }
+}
+
+fn decode<T: BuiltinContractExt>(cntr: &Contract) -> Option<T> {
+    cntr.parameter.as_ref().and_then(T::from_any)
+}
+
+/// Currency of a TRC10 token, with its precision from state-db, 0 if not found.
+fn trc10_currency(state_db: Option<&ReadOnlySolidStateDB>, token_id: i64) -> Currency {
+    let precision = state_db
+        .and_then(|state_db| state_db.get(&keys::Asset(token_id)).ok().flatten())
+        .map_or(0, |asset| asset.precision);
+    Currency::trc10(token_id, precision)
+}
+
+/// Liquid balance changes of a contract, by its parameters and the amounts recorded in the receipt.
+///
+/// TRC10 tokens referenced by name, before AllowSameTokenName, are not resolved.
+fn balance_changes(
+    ops: &mut Operations,
+    txn: &IndexedTransaction,
+    cntr: &Contract,
+    owner: &[u8],
+    receipt: &TransactionReceipt,
+    state_db: Option<&ReadOnlySolidStateDB>,
+) {
+    let trx = Currency::trx;
+    match ContractType::from_i32(cntr.r#type).unwrap_or_default() {
+        ContractType::TransferContract => {
+            if let Some(cntr) = decode::<contract_pb::TransferContract>(cntr) {
+                ops.transfer(&cntr.owner_address, &cntr.to_address, cntr.amount, trx());
+            }
+        }
+        ContractType::TransferAssetContract => {
+            if let Some(cntr) = decode::<contract_pb::TransferAssetContract>(cntr) {
+                if let Ok(token_id) = cntr.asset_name.parse() {
+                    let currency = trc10_currency(state_db, token_id);
+                    ops.transfer(&cntr.owner_address, &cntr.to_address, cntr.amount, currency);
+                }
+            }
+        }
+        ContractType::ParticipateAssetIssueContract => {
+            if let Some(cntr) = decode::<contract_pb::ParticipateAssetIssueContract>(cntr) {
+                ops.transfer(&cntr.owner_address, &cntr.to_address, cntr.amount, trx());
+                // Same as the actuator, tokens bought at the issuing price.
+                let asset = cntr
+                    .asset_name
+                    .parse()
+                    .ok()
+                    .and_then(|token_id| state_db?.get(&keys::Asset(token_id)).ok().flatten())
+                    .filter(|asset| asset.trx_num > 0);
+                if let Some(asset) = asset {
+                    let amount = cntr.amount.saturating_mul(asset.num as i64) / asset.trx_num as i64;
+                    let currency = Currency::trc10(asset.id, asset.precision);
+                    ops.transfer(&cntr.to_address, &cntr.owner_address, amount, currency);
+                }
+            }
+        }
+        ContractType::UnfreezeAssetContract => {
+            let token_id = state_db
+                .and_then(|state_db| state_db.get(&keys::Account(*Address::from_bytes(owner))).ok().flatten())
+                .map(|acct| acct.issued_asset_id);
+            if let Some(token_id) = token_id {
+                ops.change(OP_UNFREEZE, owner, receipt.unfrozen_amount, trc10_currency(state_db, token_id));
+            }
+        }
+        ContractType::CreateSmartContract => {
+            if let Some(cntr) = decode::<contract_pb::CreateSmartContract>(cntr) {
+                let cntr_address = generate_created_contract_address(&txn.hash, Address::from_bytes(owner));
+                let call_value = cntr.new_contract.as_ref().map_or(0, |new_cntr| new_cntr.call_value);
+                ops.transfer(owner, cntr_address.as_bytes(), call_value, trx());
+                if cntr.call_token_value > 0 {
+                    let currency = trc10_currency(state_db, cntr.call_token_id);
+                    ops.transfer(owner, cntr_address.as_bytes(), cntr.call_token_value, currency);
+                }
+            }
+        }
+        ContractType::TriggerSmartContract => {
+            if let Some(cntr) = decode::<contract_pb::TriggerSmartContract>(cntr) {
+                ops.transfer(&cntr.owner_address, &cntr.contract_address, cntr.call_value, trx());
+                if cntr.call_token_value > 0 {
+                    let currency = trc10_currency(state_db, cntr.call_token_id);
+                    ops.transfer(&cntr.owner_address, &cntr.contract_address, cntr.call_token_value, currency);
+                }
+            }
+        }
+        ContractType::FreezeBalanceContract => {
+            if let Some(cntr) = decode::<contract_pb::FreezeBalanceContract>(cntr) {
+                ops.change(OP_FREEZE, owner, -cntr.frozen_balance, trx());
+            }
+        }
+        ContractType::FreezeBalanceV2Contract => {
+            if let Some(cntr) = decode::<contract_pb::FreezeBalanceV2Contract>(cntr) {
+                ops.change(OP_FREEZE, owner, -cntr.frozen_balance, trx());
+            }
+        }
+        ContractType::UnfreezeBalanceContract => ops.change(OP_UNFREEZE, owner, receipt.unfrozen_amount, trx()),
+        // Unfrozen TRX of Stake 2.0 is pending until withdrawn, expired ones are withdrawn along.
+        ContractType::UnfreezeBalanceV2Contract |
+        ContractType::WithdrawExpireUnfreezeContract |
+        ContractType::CancelAllUnfreezeV2Contract => {
+            ops.change(OP_UNFREEZE, owner, receipt.withdrawal_amount, trx())
+        }
+        ContractType::WithdrawBalanceContract => ops.change(OP_REWARD, owner, receipt.withdrawal_amount, trx()),
+        // Fees only, or no balance change at all.
+        _ => {}
+    }
+}
+
+/// Operations of a transaction, balance changes of all contract types and fees.
+///
+/// Amounts of unfreezing and withdrawals, and fees, require the receipt in state-db. Without one, the status falls
+/// back to the result in the block. Internal transactions of smart contracts are not recorded, so not represented.
+fn operations_of(
+    txn: &IndexedTransaction,
+    receipt: Option<&TransactionReceipt>,
+    state_db: Option<&ReadOnlySolidStateDB>,
+) -> Vec<Operation> {
+    let cntr = match txn.raw.raw_data.as_ref().and_then(|raw| raw.contract.as_ref()) {
+        Some(cntr) => cntr,
+        None => return vec![],
+    };
+    let success = match receipt {
+        Some(receipt) => receipt.success,
+        None => txn
+            .raw
+            .result
+            .get(0)
+            .and_then(|ret| ContractStatus::from_i32(ret.contract_status))
+            .map_or(true, |status| status == ContractStatus::Default || status == ContractStatus::Success),
+    };
+    let mut ops = Operations {
+        ops: vec![],
+        status: if success { STATUS_SUCCESS } else { STATUS_FAILED },
+    };
+    let owner = match involved_addresses(cntr, &TransactionReceipt::default())
+        .into_iter()
+        .find(|(_, role)| *role == AddressRole::Owner)
+    {
+        Some((owner, _)) => owner,
+        None => return vec![],
+    };
+
+    let no_receipt = TransactionReceipt::default();
+    balance_changes(&mut ops, txn, cntr, owner.as_bytes(), receipt.unwrap_or(&no_receipt), state_db);
+    if ops.ops.is_empty() {
+        let contract_type = ContractType::from_i32(cntr.r#type).unwrap_or_default();
+        let mut op = operation(0, OP_CONTRACT, ops.status, owner.as_bytes(), None);
+        op.metadata = Some(json!({ "contract_type": format!("{:?}", contract_type) }));
+        ops.ops.push(op);
+    }
+
+    if let Some(receipt) = receipt.filter(|receipt| receipt.fee > 0) {
+        // Fees are charged even if the transaction failed.
+        let fee = operation(
+            ops.ops.len() as _,
+            OP_FEE,
+            STATUS_SUCCESS,
+            owner.as_bytes(),
+            Some(Amount::sun(-receipt.fee)),
+        );
+        ops.ops.push(fee);
+    }
+    ops.ops
+}
+
+fn block(ctx: &AppContext, req: BlockRequest) -> Result<BlockResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let block = match (req.block_identifier.index, req.block_identifier.hash.as_ref()) {
+        (_, Some(hash)) => {
+            let hash = decode_hex(hash)?;
+            if hash.len() != 32 {
+                return Err(ApiError::InvalidRequest("invalid block hash".into()));
+            }
+            ctx.chain_db.get_block_by_hash(&H256::from_slice(&hash))
+        }
+        (Some(num), None) if num >= 0 => ctx.chain_db.get_block_by_number(num as _),
+        (Some(_), None) => return Err(ApiError::InvalidRequest("negative block index".into())),
+        (None, None) => ctx.chain_db.highest_block(),
+    }
+    .map_err(|_| ApiError::BlockNotFound)?;
+    if let (Some(num), Some(_)) = (req.block_identifier.index, req.block_identifier.hash.as_ref()) {
+        if num != block.number() {
+            return Err(ApiError::BlockNotFound);
+        }
+    }
+
+    let parent_block_identifier = if block.number() == 0 {
+        block_identifier_of(&block)
+    } else {
+        BlockIdentifier {
+            index: block.number() - 1,
+            hash: hex::encode(block.parent_hash()),
+        }
+    };
+
+    let state_db = ctx.state_db.as_ref();
+    if let Some(state_db) = state_db {
+        state_db.catch_up_with_primary();
+    }
+    let mut transactions = Vec::with_capacity(block.transactions.len());
+    for txn in &block.transactions {
+        let receipt = match state_db {
+            Some(state_db) => state_db.get(&keys::TransactionReceipt(txn.hash)).map_err(internal)?,
+            None => None,
+        };
+        transactions.push(Transaction {
+            transaction_identifier: TransactionIdentifier {
+                hash: hex::encode(txn.hash.as_bytes()),
+            },
+            operations: operations_of(txn, receipt.as_ref(), state_db),
+        });
+    }
+
+    Ok(BlockResponse {
+        block: Block {
+            block_identifier: block_identifier_of(&block),
+            parent_block_identifier,
+            timestamp: block.timestamp(),
+            transactions,
+        },
+    })
+}
+
+/// Balance at the state head. Historical lookups are rejected, since state-db keeps no history.
+fn account_balance(ctx: &AppContext, req: AccountBalanceRequest) -> Result<AccountBalanceResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let state_db = ctx.state_db.as_ref().ok_or(ApiError::StateUnavailable)?;
+    state_db.catch_up_with_primary();
+
+    let address: Address = req
+        .account_identifier
+        .address
+        .parse()
+        .map_err(|_| ApiError::InvalidRequest("invalid address".into()))?;
+
+    let head_number = state_db
+        .get(&keys::DynamicProperty::LatestBlockNumber)
+        .map_err(internal)?
+        .unwrap_or(0);
+    let head_hash = state_db
+        .get(&keys::LatestBlockHash)
+        .map_err(internal)?
+        .unwrap_or_default();
+    if let Some(at) = req.block_identifier {
+        let is_head = at.index.map_or(true, |num| num == head_number) &&
+            at.hash.map_or(true, |hash| hash.trim_start_matches("0x") == hex::encode(head_hash.as_bytes()));
+        if !is_head {
+            return Err(ApiError::HistoricalBalanceUnsupported);
+        }
+    }
+
+    let balance = state_db
+        .get(&keys::Account(address))
+        .map_err(internal)?
+        .map_or(0, |acct| acct.balance);
+    let currencies = if req.currencies.is_empty() {
+        vec![Currency::trx()]
+    } else {
+        req.currencies
+    };
+    let mut balances = Vec::with_capacity(currencies.len());
+    for currency in currencies {
+        if currency == Currency::trx() {
+            balances.push(Amount::sun(balance));
+            continue;
+        }
+        let token_id = currency
+            .symbol
+            .parse()
+            .map_err(|_| ApiError::InvalidRequest(format!("unknown currency {}", currency.symbol)))?;
+        let token_balance = state_db
+            .get(&keys::AccountAsset(address, token_id))
+            .map_err(internal)?
+            .unwrap_or(0);
+        balances.push(Amount::new(token_balance, currency));
+    }
+    // Read again, the state head might move while reading.
+    if state_db.get(&keys::LatestBlockHash).map_err(internal)? != Some(head_hash) {
+        return Err(ApiError::Internal("state head changed, retry".into()));
+    }
+
+    Ok(AccountBalanceResponse {
+        block_identifier: BlockIdentifier {
+            index: head_number,
+            hash: hex::encode(head_hash.as_bytes()),
+        },
+        balances,
+    })
+}
+
+fn mempool(ctx: &AppContext, req: NetworkRequest) -> Result<MempoolResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let pool = ctx.pending_pool.read().unwrap();
+    Ok(MempoolResponse {
+        transaction_identifiers: pool
+            .ids(0, pool.len())
+            .into_iter()
+            .map(|id| TransactionIdentifier {
+                hash: hex::encode(id.as_bytes()),
+            })
+            .collect(),
+    })
+}
+
+/// A pending transaction, operations without status since it is not executed yet.
+fn mempool_transaction(
+    ctx: &AppContext,
+    req: MempoolTransactionRequest,
+) -> Result<MempoolTransactionResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let hash = decode_hex(&req.transaction_identifier.hash)?;
+    if hash.len() != 32 {
+        return Err(ApiError::InvalidRequest("invalid transaction hash".into()));
+    }
+    let txn = ctx
+        .pending_pool
+        .read()
+        .unwrap()
+        .get(&H256::from_slice(&hash))
+        .cloned()
+        .ok_or(ApiError::TransactionNotFound)?;
+
+    let mut operations = operations_of(&txn, None, ctx.state_db.as_ref());
+    for op in &mut operations {
+        op.status = None;
+    }
+    Ok(MempoolTransactionResponse {
+        transaction: Transaction {
+            transaction_identifier: req.transaction_identifier,
+            operations,
+        },
+    })
+}
+
+/// A TRX transfer, from a debit and a credit operation of the same amount.
+fn transfer_of_operations(ops: &[Operation]) -> Result<contract_pb::TransferContract, ApiError> {
+    if ops.len() != 2 || ops.iter().any(|op| op.type_ != OP_TRANSFER) {
+        return Err(ApiError::InvalidOperations("expected 2 TRANSFER operations"));
+    }
+    let mut legs = vec![];
+    for op in ops {
+        let address: Address = op
+            .account
+            .as_ref()
+            .and_then(|acct| acct.address.parse().ok())
+            .ok_or(ApiError::InvalidOperations("invalid account"))?;
+        let amount = op
+            .amount
+            .as_ref()
+            .filter(|amount| amount.currency == Currency::trx())
+            .and_then(|amount| amount.value.parse::<i64>().ok())
+            .ok_or(ApiError::InvalidOperations("invalid amount, expected TRX in SUN"))?;
+        legs.push((address, amount));
+    }
+    legs.sort_by_key(|&(_, amount)| amount);
+    let (from, debit) = legs[0];
+    let (to, credit) = legs[1];
+    if debit >= 0 || debit.checked_neg() != Some(credit) {
+        return Err(ApiError::InvalidOperations("amounts must be opposite"));
+    }
+    if from == to {
+        return Err(ApiError::InvalidOperations("transfer to self"));
+    }
+    Ok(contract_pb::TransferContract {
+        owner_address: from.as_bytes().to_vec(),
+        to_address: to.as_bytes().to_vec(),
+        amount: credit,
+    })
+}
+
+/// Account of a secp256k1 public key.
+fn construction_derive(
+    ctx: &AppContext,
+    req: ConstructionDeriveRequest,
+) -> Result<ConstructionDeriveResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    if req.public_key.curve_type != "secp256k1" {
+        return Err(ApiError::InvalidRequest("only secp256k1 keys are supported".into()));
+    }
+    let public = Public::from_sec1(&decode_hex(&req.public_key.hex_bytes)?)
+        .map_err(|_| ApiError::InvalidRequest("invalid public key".into()))?;
+    Ok(ConstructionDeriveResponse {
+        account_identifier: AccountIdentifier {
+            address: Address::from_public(&public).to_string(),
+        },
+    })
+}
+
+/// Checks operations only. Metadata needs no options, and signers are recovered from signatures.
+fn construction_preprocess(
+    ctx: &AppContext,
+    req: ConstructionPreprocessRequest,
+) -> Result<ConstructionPreprocessResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    transfer_of_operations(&req.operations)?;
+    Ok(ConstructionPreprocessResponse { options: json!({}) })
+}
+
+/// Reference block and expiration, the same as `buildTransaction` of GraphQL.
+fn construction_metadata(
+    ctx: &AppContext,
+    req: ConstructionMetadataRequest,
+) -> Result<ConstructionMetadataResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let ref_block = ctx.chain_db.highest_block().map_err(internal)?;
+    let now = Utc::now().timestamp_millis();
+    Ok(ConstructionMetadataResponse {
+        metadata: ConstructionMetadata {
+            ref_block_bytes: hex::encode(&(ref_block.number() as u64).to_be_bytes()[6..8]),
+            ref_block_hash: hex::encode(&ref_block.hash().as_bytes()[8..16]),
+            timestamp: now,
+            expiration: now + CONSTRUCTION_EXPIRATION,
+        },
+    })
+}
+
+/// Build an unsigned transfer. The payload to sign is the transaction id.
+fn construction_payloads(
+    ctx: &AppContext,
+    req: ConstructionPayloadsRequest,
+) -> Result<ConstructionPayloadsResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let transfer = transfer_of_operations(&req.operations)?;
+    let owner = *Address::from_bytes(&transfer.owner_address);
+
+    let metadata = match req.metadata {
+        Some(metadata) => metadata,
+        None => {
+            construction_metadata(
+                ctx,
+                ConstructionMetadataRequest {
+                    network_identifier: req.network_identifier,
+                },
+            )?
+            .metadata
+        }
+    };
+    let ref_block_bytes = decode_hex(&metadata.ref_block_bytes)?;
+    let ref_block_hash = decode_hex(&metadata.ref_block_hash)?;
+    if ref_block_bytes.len() != 2 || ref_block_hash.len() != 8 {
+        return Err(ApiError::InvalidRequest("malformed reference block in metadata".into()));
+    }
+    let raw = transaction::Raw {
+        ref_block_bytes,
+        ref_block_hash,
+        timestamp: metadata.timestamp,
+        expiration: metadata.expiration,
+        contract: Some(Contract {
+            r#type: transfer.type_code() as i32,
+            parameter: transfer.to_any(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let txid = hash::txid(&raw);
+
+    Ok(ConstructionPayloadsResponse {
+        unsigned_transaction: hex::encode(hash::raw_data_bytes(&raw)),
+        payloads: vec![SigningPayload {
+            account_identifier: AccountIdentifier {
+                address: owner.to_string(),
+            },
+            hex_bytes: hex::encode(txid.as_bytes()),
+            signature_type: "ecdsa_recovery",
+        }],
+    })
+}
+
+fn construction_combine(
+    ctx: &AppContext,
+    req: ConstructionCombineRequest,
+) -> Result<ConstructionCombineResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let raw = transaction::Raw::decode(&decode_hex(&req.unsigned_transaction)?[..])
+        .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    let signatures = req
+        .signatures
+        .iter()
+        .map(|sig| decode_hex(&sig.hex_bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+    if signatures.is_empty() || signatures.iter().any(|sig| sig.len() != 65) {
+        return Err(ApiError::InvalidRequest("expected 65-byte recoverable signatures".into()));
+    }
+
+    let txn = TransactionPb {
+        raw_data: Some(raw),
+        signatures,
+        ..Default::default()
+    };
+    let mut buf = Vec::with_capacity(txn.encoded_len());
+    txn.encode(&mut buf).map_err(internal)?;
+    Ok(ConstructionCombineResponse {
+        signed_transaction: hex::encode(&buf),
+    })
+}
+
+/// Operations of a constructed transaction, unsigned raw data or signed.
+fn construction_parse(
+    ctx: &AppContext,
+    req: ConstructionParseRequest,
+) -> Result<ConstructionParseResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let raw = decode_hex(&req.transaction)?;
+    let txn = if req.signed {
+        TransactionPb::decode(&raw[..])
+    } else {
+        transaction::Raw::decode(&raw[..]).map(|raw| TransactionPb {
+            raw_data: Some(raw),
+            ..Default::default()
+        })
+    }
+    .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    if txn.raw_data.is_none() {
+        return Err(ApiError::InvalidRequest("missing raw data".into()));
+    }
+    let txn = IndexedTransaction::from_raw(txn);
+
+    let mut operations = operations_of(&txn, None, ctx.state_db.as_ref());
+    for op in &mut operations {
+        op.status = None;
+    }
+    let account_identifier_signers = if req.signed {
+        txn.recover_owner()
+            .map_err(|e| ApiError::InvalidRequest(e.to_string()))?
+            .into_iter()
+            .map(|addr| AccountIdentifier {
+                address: addr.to_string(),
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+    Ok(ConstructionParseResponse {
+        operations,
+        account_identifier_signers,
+    })
+}
+
+fn construction_hash(
+    ctx: &AppContext,
+    req: ConstructionHashRequest,
+) -> Result<TransactionIdentifierResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let txn = TransactionPb::decode(&decode_hex(&req.signed_transaction)?[..])
+        .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    let raw = txn
+        .raw_data
+        .as_ref()
+        .ok_or_else(|| ApiError::InvalidRequest("missing raw data".into()))?;
+    Ok(TransactionIdentifierResponse {
+        transaction_identifier: TransactionIdentifier {
+            hash: hex::encode(hash::txid(raw).as_bytes()),
+        },
+    })
+}
+
+/// Put a signed transaction into the pending pool, same as the `broadcast` mutation of GraphQL.
+fn construction_submit(
+    ctx: &AppContext,
+    req: ConstructionSubmitRequest,
+) -> Result<TransactionIdentifierResponse, ApiError> {
+    check_network(ctx, &req.network_identifier)?;
+
+    let txn = TransactionPb::decode(&decode_hex(&req.signed_transaction)?[..])
+        .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    if txn.raw_data.is_none() {
+        return Err(ApiError::InvalidRequest("missing raw data".into()));
+    }
+    let txn = IndexedTransaction::from_raw(txn);
+    let hash = hex::encode(txn.hash.as_bytes());
+    ctx.add_pending_transaction(txn)
+        .map_err(|e| ApiError::TransactionRejected(e.to_string()))?;
+
+    Ok(TransactionIdentifierResponse {
+        transaction_identifier: TransactionIdentifier { hash },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer_op(index: i64, address: &str, value: &str) -> Operation {
+        Operation {
+            operation_identifier: OperationIdentifier { index },
+            related_operations: vec![],
+            type_: OP_TRANSFER.into(),
+            status: None,
+            account: Some(AccountIdentifier { address: address.into() }),
+            amount: Some(Amount {
+                value: value.into(),
+                currency: Currency::trx(),
+            }),
+            metadata: None,
+        }
+    }
+
+    fn transaction_of<T: BuiltinContractExt>(cntr: T) -> IndexedTransaction {
+        IndexedTransaction::from_raw(TransactionPb {
+            raw_data: Some(transaction::Raw {
+                contract: Some(Contract {
+                    r#type: cntr.type_code() as i32,
+                    parameter: cntr.to_any(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// `TYPE status address amount` of each operation.
+    fn summary(ops: &[Operation]) -> Vec<String> {
+        ops.iter()
+            .map(|op| {
+                format!(
+                    "{} {} {} {}",
+                    op.type_,
+                    op.status.as_deref().unwrap_or("-"),
+                    op.account.as_ref().unwrap().address,
+                    op.amount.as_ref().map_or("-", |amount| amount.value.as_str())
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_operations_of() {
+        let from = "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs";
+        let to = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8";
+        let owner_address = from.parse::<Address>().unwrap().as_bytes().to_vec();
+        let success = TransactionReceipt {
+            success: true,
+            ..Default::default()
+        };
+
+        let txn = transaction_of(contract_pb::TransferContract {
+            owner_address: owner_address.clone(),
+            to_address: to.parse::<Address>().unwrap().as_bytes().to_vec(),
+            amount: 100,
+        });
+        let receipt = TransactionReceipt { fee: 10, ..success.clone() };
+        let ops = operations_of(&txn, Some(&receipt), None);
+        assert_eq!(
+            summary(&ops),
+            vec![
+                format!("TRANSFER SUCCESS {} -100", from),
+                format!("TRANSFER SUCCESS {} 100", to),
+                format!("FEE SUCCESS {} -10", from),
+            ]
+        );
+        assert_eq!(ops[1].related_operations[0].index, 0);
+
+        let txn = transaction_of(contract_pb::FreezeBalanceV2Contract {
+            owner_address: owner_address.clone(),
+            frozen_balance: 1_000,
+            ..Default::default()
+        });
+        let ops = operations_of(&txn, Some(&success), None);
+        assert_eq!(summary(&ops), vec![format!("FREEZE SUCCESS {} -1000", from)]);
+
+        let txn = transaction_of(contract_pb::WithdrawBalanceContract {
+            owner_address: owner_address.clone(),
+        });
+        let receipt = TransactionReceipt {
+            withdrawal_amount: 50,
+            ..success.clone()
+        };
+        let ops = operations_of(&txn, Some(&receipt), None);
+        assert_eq!(summary(&ops), vec![format!("REWARD SUCCESS {} 50", from)]);
+
+        // Failed, the fee is still charged.
+        let txn = transaction_of(contract_pb::TriggerSmartContract {
+            owner_address: owner_address.clone(),
+            contract_address: to.parse::<Address>().unwrap().as_bytes().to_vec(),
+            call_value: 7,
+            ..Default::default()
+        });
+        let receipt = TransactionReceipt {
+            success: false,
+            fee: 3,
+            ..Default::default()
+        };
+        let ops = operations_of(&txn, Some(&receipt), None);
+        assert_eq!(
+            summary(&ops),
+            vec![
+                format!("TRANSFER FAILED {} -7", from),
+                format!("TRANSFER FAILED {} 7", to),
+                format!("FEE SUCCESS {} -3", from),
+            ]
+        );
+
+        // No balance change.
+        let txn = transaction_of(contract_pb::VoteWitnessContract {
+            owner_address,
+            ..Default::default()
+        });
+        let ops = operations_of(&txn, Some(&success), None);
+        assert_eq!(summary(&ops), vec![format!("CONTRACT SUCCESS {} -", from)]);
+        assert_eq!(ops[0].metadata, Some(json!({ "contract_type": "VoteWitnessContract" })));
+    }
+
+    #[test]
+    fn test_transfer_of_operations() {
+        let from = "TKHuVq1oKVruCGLvqVexFs6dawKv6fQgFs";
+        let to = "TJRabPrwbZy45sbavfcjinPJC18kjpRTv8";
+
+        let cntr = transfer_of_operations(&[transfer_op(0, to, "100"), transfer_op(1, from, "-100")]).unwrap();
+        assert_eq!(cntr.owner_address, from.parse::<Address>().unwrap().as_bytes());
+        assert_eq!(cntr.to_address, to.parse::<Address>().unwrap().as_bytes());
+        assert_eq!(cntr.amount, 100);
+
+        assert!(transfer_of_operations(&[transfer_op(0, from, "-100"), transfer_op(1, to, "99")]).is_err());
+        assert!(transfer_of_operations(&[transfer_op(0, from, "100"), transfer_op(1, to, "100")]).is_err());
+        assert!(transfer_of_operations(&[transfer_op(0, from, "-100"), transfer_op(1, from, "100")]).is_err());
+        assert!(transfer_of_operations(&[transfer_op(0, from, "-100")]).is_err());
+
+        let mut fee = transfer_op(1, to, "100");
+        fee.type_ = OP_FEE.into();
+        assert!(transfer_of_operations(&[transfer_op(0, from, "-100"), fee]).is_err());
+    }
+}
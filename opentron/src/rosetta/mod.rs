@@ -0,0 +1,11 @@
+//! Rosetta API, for exchange integrations.
+//!
+//! Implemented endpoints: `/network/{list,status,options}`, `/block`, `/account/balance`, `/mempool`,
+//! `/mempool/transaction` and `/construction/{derive,preprocess,metadata,payloads,combine,parse,hash,submit}`.
+//! Operations represent liquid balance changes of all contract types, TRX and TRC10, and fees. Internal transactions
+//! are not represented. Construction covers TRX transfers. Balances are read at the state head, historical lookup is
+//! not supported.
+
+mod api;
+pub mod server;
+mod types;
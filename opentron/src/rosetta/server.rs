@@ -0,0 +1,89 @@
+use futures::future::FutureExt;
+use hyper::{
+    header,
+    server::conn::AddrStream,
+    service::{make_service_fn, service_fn},
+    Body, Method, Response, Server, StatusCode,
+};
+use log::{info, warn};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use super::api::{self, ApiError};
+use crate::context::AppContext;
+use crate::request_log::RequestLog;
+
+fn json_response(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
+    response
+}
+
+pub async fn rosetta_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast::Receiver<()>) {
+    let config = &ctx.config.rosetta;
+
+    if !config.enable {
+        warn!("rosetta server disabled");
+        return;
+    }
+
+    let addr = config.endpoint.parse().expect("malformed endpoint address");
+
+    let rosetta_service = make_service_fn(move |conn: &AddrStream| {
+        let ctx = ctx.clone();
+        let remote_addr = conn.remote_addr();
+
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                let ctx = ctx.clone();
+
+                let request_log = RequestLog::new("rosetta", &req, remote_addr);
+                async move {
+                    if req.method() != Method::POST {
+                        let mut response = Response::new(Body::empty());
+                        *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                        request_log.finish(&mut response);
+                        return Ok::<_, hyper::Error>(response);
+                    }
+                    let path = req.uri().path().to_owned();
+                    let body = match hyper::body::to_bytes(req.into_body()).await {
+                        Ok(body) => body,
+                        Err(e) => {
+                            request_log.fail(&e);
+                            return Err(e);
+                        }
+                    };
+                    // Handlers read chain-db and state-db synchronously, logging with the request id.
+                    let logger = request_log.logger().clone();
+                    let ret = tokio::task::spawn_blocking(move || {
+                        slog_scope::scope(&logger, || api::handle(&ctx, &path, &body))
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(ApiError::Internal(e.to_string())));
+                    let mut response = match ret {
+                        Ok(resp) => json_response(StatusCode::OK, &resp),
+                        Err(ApiError::NotFound) => {
+                            let mut response = Response::new(Body::empty());
+                            *response.status_mut() = StatusCode::NOT_FOUND;
+                            response
+                        }
+                        Err(e) => {
+                            let error = serde_json::to_value(e.to_error()).unwrap_or_default();
+                            json_response(StatusCode::INTERNAL_SERVER_ERROR, &error)
+                        }
+                    };
+                    request_log.finish(&mut response);
+                    Ok(response)
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr).serve(rosetta_service);
+    info!("rosetta listening on http://{}", addr);
+
+    let _ = server.with_graceful_shutdown(shutdown_signal.recv().map(|_| ())).await;
+}
@@ -0,0 +1,362 @@
+//! Request and response objects of the Rosetta API, a subset of the spec fields used by the implemented
+//! endpoints.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const BLOCKCHAIN: &str = "tron";
+pub const ROSETTA_VERSION: &str = "1.4.10";
+
+pub const OP_TRANSFER: &str = "TRANSFER";
+pub const OP_FEE: &str = "FEE";
+/// TRX frozen for resources, out of the liquid balance.
+pub const OP_FREEZE: &str = "FREEZE";
+/// Unfrozen or withdrawn TRX, back to the liquid balance.
+pub const OP_UNFREEZE: &str = "UNFREEZE";
+/// Voting and block rewards withdrawn.
+pub const OP_REWARD: &str = "REWARD";
+/// A contract not changing liquid balances, i.e. voting, with its type in metadata.
+pub const OP_CONTRACT: &str = "CONTRACT";
+pub const OPERATION_TYPES: [&str; 6] = [OP_TRANSFER, OP_FEE, OP_FREEZE, OP_UNFREEZE, OP_REWARD, OP_CONTRACT];
+pub const STATUS_SUCCESS: &str = "SUCCESS";
+pub const STATUS_FAILED: &str = "FAILED";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetworkIdentifier {
+    pub blockchain: String,
+    pub network: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockIdentifier {
+    pub index: i64,
+    pub hash: String,
+}
+
+/// Either field, or none for the latest block.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PartialBlockIdentifier {
+    pub index: Option<i64>,
+    pub hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionIdentifier {
+    pub hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountIdentifier {
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Currency {
+    pub symbol: String,
+    pub decimals: u32,
+}
+
+impl Currency {
+    pub fn trx() -> Self {
+        Currency {
+            symbol: "TRX".into(),
+            decimals: 6,
+        }
+    }
+
+    /// A TRC10 token, by its id.
+    pub fn trc10(token_id: i64, precision: i32) -> Self {
+        Currency {
+            symbol: token_id.to_string(),
+            decimals: precision as u32,
+        }
+    }
+}
+
+/// Value is a signed integer string, in the smallest unit, i.e. SUN.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Amount {
+    pub value: String,
+    pub currency: Currency,
+}
+
+impl Amount {
+    pub fn new(value: i64, currency: Currency) -> Self {
+        Amount {
+            value: value.to_string(),
+            currency,
+        }
+    }
+
+    pub fn sun(value: i64) -> Self {
+        Amount::new(value, Currency::trx())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OperationIdentifier {
+    pub index: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Operation {
+    pub operation_identifier: OperationIdentifier,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related_operations: Vec<OperationIdentifier>,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// Empty in construction requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    pub account: Option<AccountIdentifier>,
+    pub amount: Option<Amount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Transaction {
+    pub transaction_identifier: TransactionIdentifier,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Block {
+    pub block_identifier: BlockIdentifier,
+    pub parent_block_identifier: BlockIdentifier,
+    /// In ms.
+    pub timestamp: i64,
+    pub transactions: Vec<Transaction>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Peer {
+    pub peer_id: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Version {
+    pub rosetta_version: &'static str,
+    pub node_version: &'static str,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Allow {
+    pub operation_statuses: Vec<OperationStatus>,
+    pub operation_types: Vec<&'static str>,
+    pub errors: Vec<Error>,
+    pub historical_balance_lookup: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OperationStatus {
+    pub status: &'static str,
+    pub successful: bool,
+}
+
+/// Reference block and expiration of a transaction to construct, from the node.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConstructionMetadata {
+    /// Hex encoded.
+    pub ref_block_bytes: String,
+    /// Hex encoded.
+    pub ref_block_hash: String,
+    /// In ms.
+    pub timestamp: i64,
+    /// In ms.
+    pub expiration: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SigningPayload {
+    pub account_identifier: AccountIdentifier,
+    pub hex_bytes: String,
+    pub signature_type: &'static str,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Error {
+    pub code: u32,
+    pub message: &'static str,
+    pub retriable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+// Requests
+
+#[derive(Deserialize, Debug)]
+pub struct NetworkRequest {
+    pub network_identifier: NetworkIdentifier,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BlockRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub block_identifier: PartialBlockIdentifier,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AccountBalanceRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub account_identifier: AccountIdentifier,
+    pub block_identifier: Option<PartialBlockIdentifier>,
+    /// TRX only if empty.
+    #[serde(default)]
+    pub currencies: Vec<Currency>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MempoolTransactionRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub transaction_identifier: TransactionIdentifier,
+}
+
+/// Only secp256k1 keys, 33-byte compressed or 65-byte uncompressed.
+#[derive(Deserialize, Debug)]
+pub struct PublicKey {
+    pub hex_bytes: String,
+    pub curve_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionDeriveRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub public_key: PublicKey,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionPreprocessRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionMetadataRequest {
+    pub network_identifier: NetworkIdentifier,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionPayloadsRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub operations: Vec<Operation>,
+    /// From `/construction/metadata`, the latest block if missing.
+    pub metadata: Option<ConstructionMetadata>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionParseRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub signed: bool,
+    /// Hex encoded, raw data if unsigned.
+    pub transaction: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionHashRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub signed_transaction: String,
+}
+
+/// Only `hex_bytes` of signatures is used, a 65-byte recoverable signature of the payload.
+#[derive(Deserialize, Debug)]
+pub struct Signature {
+    pub hex_bytes: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionCombineRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub unsigned_transaction: String,
+    pub signatures: Vec<Signature>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConstructionSubmitRequest {
+    pub network_identifier: NetworkIdentifier,
+    pub signed_transaction: String,
+}
+
+// Responses
+
+#[derive(Serialize, Debug)]
+pub struct NetworkListResponse {
+    pub network_identifiers: Vec<NetworkIdentifier>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NetworkStatusResponse {
+    pub current_block_identifier: BlockIdentifier,
+    pub current_block_timestamp: i64,
+    pub genesis_block_identifier: BlockIdentifier,
+    pub peers: Vec<Peer>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NetworkOptionsResponse {
+    pub version: Version,
+    pub allow: Allow,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BlockResponse {
+    pub block: Block,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AccountBalanceResponse {
+    pub block_identifier: BlockIdentifier,
+    pub balances: Vec<Amount>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MempoolResponse {
+    pub transaction_identifiers: Vec<TransactionIdentifier>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MempoolTransactionResponse {
+    pub transaction: Transaction,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConstructionDeriveResponse {
+    pub account_identifier: AccountIdentifier,
+}
+
+/// Nothing to look up by operations, options are always empty.
+#[derive(Serialize, Debug)]
+pub struct ConstructionPreprocessResponse {
+    pub options: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConstructionMetadataResponse {
+    pub metadata: ConstructionMetadata,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConstructionPayloadsResponse {
+    /// Hex encoded raw transaction.
+    pub unsigned_transaction: String,
+    pub payloads: Vec<SigningPayload>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConstructionCombineResponse {
+    /// Hex encoded transaction, with signatures.
+    pub signed_transaction: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConstructionParseResponse {
+    pub operations: Vec<Operation>,
+    /// Recovered from signatures, empty if unsigned.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub account_identifier_signers: Vec<AccountIdentifier>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TransactionIdentifierResponse {
+    pub transaction_identifier: TransactionIdentifier,
+}
@@ -0,0 +1,228 @@
+//! Supervision of long-running services.
+//!
+//! A service may exit normally, i.e. when disabled in config. A failed service, by error or panic, is restarted with
+//! exponential backoff, instead of leaving the node half-alive. A service failing too often stops the whole node.
+//! On shutdown, services are stopped stage by stage, in ascending order of their stages.
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use futures::future::{join, join_all, FutureExt};
+use futures::select;
+use log::{error, info, warn};
+use tokio::sync::broadcast;
+use tokio::time::delay_for;
+
+use crate::context::AppContext;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A service running longer than this is considered recovered, the backoff and restart count are reset.
+const BACKOFF_RESET_AFTER: Duration = Duration::from_secs(300);
+/// Max number of restarts without recovery, before stopping the node.
+const MAX_RESTARTS: u32 = 10;
+/// Max time to wait for services of a stage to stop.
+const STAGE_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceState {
+    Running,
+    /// Failed, waiting for restart.
+    Restarting,
+    Stopped,
+    /// Failed too many times, gave up.
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceHealth {
+    pub state: ServiceState,
+    /// Total number of restarts.
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// Latest start time, in ms.
+    pub started_at: i64,
+}
+
+type ServiceFuture = Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>>>>;
+
+struct Service {
+    name: &'static str,
+    stage: u32,
+    factory: Box<dyn Fn(broadcast::Receiver<()>) -> ServiceFuture>,
+    signal: broadcast::Sender<()>,
+}
+
+pub struct Supervisor {
+    ctx: Arc<AppContext>,
+    services: Vec<Service>,
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        format!("panicked: {}", msg)
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        format!("panicked: {}", msg)
+    } else {
+        "panicked".into()
+    }
+}
+
+impl Supervisor {
+    pub fn new(ctx: Arc<AppContext>) -> Self {
+        Supervisor { ctx, services: vec![] }
+    }
+
+    /// Add a service. `factory` is called on every (re)start, with a shutdown signal receiver.
+    pub fn add_service<F, Fut>(&mut self, name: &'static str, stage: u32, factory: F)
+    where
+        F: Fn(broadcast::Receiver<()>) -> Fut + 'static,
+        Fut: Future<Output = Result<(), Box<dyn Error>>> + 'static,
+    {
+        let (signal, _) = broadcast::channel(1);
+        self.services.push(Service {
+            name,
+            stage,
+            factory: Box::new(move |signal| Box::pin(factory(signal))),
+            signal,
+        });
+    }
+
+    fn set_health(&self, name: &'static str, f: impl FnOnce(&mut ServiceHealth)) {
+        let mut services = self.ctx.services.write().unwrap();
+        let health = services.entry(name).or_insert_with(|| ServiceHealth {
+            state: ServiceState::Stopped,
+            restarts: 0,
+            last_error: None,
+            started_at: 0,
+        });
+        f(health);
+    }
+
+    fn is_stopped(&self, name: &'static str) -> bool {
+        self.ctx
+            .services
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|health| health.state == ServiceState::Stopped || health.state == ServiceState::Failed)
+            .unwrap_or(true)
+    }
+
+    /// Same as the stop request of admin API.
+    fn request_stop(&self) {
+        if let Some(stop_tx) = self.ctx.stop_request.lock().unwrap().take() {
+            let _ = stop_tx.send(());
+        }
+    }
+
+    async fn supervise(&self, service: &Service, shutting_down: &Cell<bool>) {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut num_restarts = 0;
+
+        while !shutting_down.get() {
+            self.set_health(service.name, |health| {
+                health.state = ServiceState::Running;
+                health.started_at = Utc::now().timestamp_millis();
+            });
+            let started_at = Instant::now();
+            let ret = AssertUnwindSafe((service.factory)(service.signal.subscribe()))
+                .catch_unwind()
+                .await;
+            let err = match ret {
+                Ok(Ok(())) => {
+                    info!("service {} exited", service.name);
+                    break;
+                }
+                Ok(Err(e)) => e.to_string(),
+                Err(panic) => panic_message(&panic),
+            };
+            if shutting_down.get() {
+                warn!("service {} failed on shutdown: {}", service.name, err);
+                self.set_health(service.name, |health| health.last_error = Some(err));
+                break;
+            }
+
+            if started_at.elapsed() > BACKOFF_RESET_AFTER {
+                backoff = INITIAL_BACKOFF;
+                num_restarts = 0;
+            }
+            num_restarts += 1;
+            if num_restarts > MAX_RESTARTS {
+                error!("service {} failed {} times, stopping node: {}", service.name, MAX_RESTARTS, err);
+                self.set_health(service.name, |health| {
+                    health.state = ServiceState::Failed;
+                    health.last_error = Some(err);
+                });
+                self.request_stop();
+                break;
+            }
+
+            warn!("service {} failed, restart in {:?}: {}", service.name, backoff, err);
+            self.set_health(service.name, |health| {
+                health.state = ServiceState::Restarting;
+                health.restarts += 1;
+                health.last_error = Some(err);
+            });
+            let mut signal = service.signal.subscribe();
+            select! {
+                _ = signal.recv().fuse() => {}
+                _ = delay_for(backoff).fuse() => {}
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+
+        self.set_health(service.name, |health| {
+            if health.state != ServiceState::Failed {
+                health.state = ServiceState::Stopped;
+            }
+        });
+    }
+
+    /// Stop services stage by stage, waiting for each stage to stop.
+    async fn shutdown(&self, shutting_down: &Cell<bool>) {
+        shutting_down.set(true);
+
+        let stages = self
+            .services
+            .iter()
+            .fold(BTreeMap::<u32, Vec<&Service>>::new(), |mut stages, service| {
+                stages.entry(service.stage).or_default().push(service);
+                stages
+            });
+        for (stage, services) in stages {
+            for service in &services {
+                let _ = service.signal.send(());
+            }
+            let started_at = Instant::now();
+            while !services.iter().all(|service| self.is_stopped(service.name)) {
+                if started_at.elapsed() > STAGE_SHUTDOWN_TIMEOUT {
+                    warn!("shutdown stage {} timeout, continue", stage);
+                    break;
+                }
+                delay_for(Duration::from_millis(100)).await;
+            }
+            info!("shutdown stage {} done", stage);
+        }
+    }
+
+    /// Run all services until `done` is signaled and all services are stopped.
+    pub async fn run(self, mut done: broadcast::Receiver<()>) {
+        let shutting_down = Cell::new(false);
+
+        let services = join_all(self.services.iter().map(|service| self.supervise(service, &shutting_down)));
+        let controller = async {
+            let _ = done.recv().await;
+            self.shutdown(&shutting_down).await;
+        };
+        join(services, controller).await;
+    }
+}
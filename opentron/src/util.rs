@@ -1,7 +1,21 @@
-use byteorder::{ByteOrder, BE};
+use chain_db::{ChainDB, ChainDBOptions};
+use config::{ChainConfig, StorageConfig};
+use primitive_types::H256;
 use proto2::common::Endpoint;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use crate::context::AppContext;
+
+/// A boxed error with a message, for functions returning `Box<dyn Error>`.
+#[inline]
+pub fn new_error(msg: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(io::ErrorKind::Other, msg))
+}
 
 #[derive(Debug, Hash, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Peer {
@@ -33,6 +47,87 @@ pub async fn get_my_ip() -> Result<String, Box<dyn Error>> {
     Ok(ip.origin)
 }
 
+/// Endpoint advertised to peers, `advertised-endpoint` of channel in config, or the external ip with channel port.
+pub fn advertised_endpoint(ctx: &AppContext) -> Endpoint {
+    let channel_config = &ctx.config.protocol.channel;
+    channel_config
+        .advertised_endpoint
+        .parse::<SocketAddr>()
+        .map(|addr| Endpoint {
+            address: addr.ip().to_string(),
+            port: addr.port() as _,
+            node_id: ctx.node_id.clone(),
+        })
+        .unwrap_or_else(|_| Endpoint {
+            address: ctx.outbound_ip.clone(),
+            port: channel_config
+                .endpoint
+                .parse::<SocketAddr>()
+                .map(|addr| addr.port())
+                .unwrap_or(18888) as _,
+            node_id: ctx.node_id.clone(),
+        })
+}
+
+/// IPv4-mapped IPv6 addresses, received on dual-stack sockets, as IPv4.
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, ..] => IpAddr::V4(v6.to_ipv4().unwrap()),
+            _ => ip,
+        },
+        ip => ip,
+    }
+}
+
+/// Socket address of an endpoint, IPv4 or IPv6.
+pub fn endpoint_to_socket_addr(endpoint: &Endpoint) -> Option<SocketAddr> {
+    let ip = endpoint.address.parse::<IpAddr>().ok()?;
+    Some(SocketAddr::new(ip, endpoint.port as _))
+}
+
 pub fn block_hash_to_number(hash: &[u8]) -> i64 {
-    BE::read_u64(&hash[..8]) as _
+    chain::hash::block_id_to_number(hash)
+}
+
+/// Open ChainDB with storage options of config.
+pub fn open_chain_db(config: &StorageConfig) -> ChainDB {
+    let options = ChainDBOptions {
+        compress_blocks: config.compress_blocks,
+        cold_data_dir: config.cold_data_dir.as_ref().map(PathBuf::from),
+    };
+    ChainDB::with_options(&config.data_dir, &options)
+}
+
+/// Node hashes of `chain.merkle-tree-patch`, by transaction id. Hex strings are checked by `Config::validate`.
+pub fn merkle_tree_patch(config: &ChainConfig) -> HashMap<H256, H256> {
+    config
+        .merkle_tree_patch
+        .iter()
+        .map(|patch| {
+            (
+                H256::from_slice(&hex::decode(&patch.txn).unwrap()),
+                H256::from_slice(&hex::decode(&patch.tree_node_hash).unwrap()),
+            )
+        })
+        .collect()
+}
+
+/// Read a protobuf varint, `None` on a clean EOF.
+pub fn read_varint<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut value = 0u64;
+    let mut byte = [0u8; 1];
+    for i in 0..10 {
+        if reader.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated varint"));
+        }
+        value |= ((byte[0] & 0x7f) as u64) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "malformed varint"))
 }
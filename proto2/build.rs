@@ -1,8 +1,173 @@
+/// Types with JSON support, see `src/json.rs`.
+const JSON_TYPES: &[&str] = &[
+    ".proto.chain.Block",
+    ".proto.chain.BlockHeader",
+    ".proto.chain.BlockHeader.Raw",
+    ".proto.chain.AccountId",
+    ".proto.chain.Authority",
+    ".proto.chain.Transaction",
+    ".proto.chain.Transaction.Contract",
+    ".proto.chain.Transaction.Result",
+    ".proto.chain.Transaction.Raw",
+    ".proto.state.AccountResource",
+    ".proto.state.PermissionKey",
+    ".proto.state.OwnerPermission",
+    ".proto.state.ActivePermission",
+    ".proto.state.Account",
+    ".proto.state.PendingUnfreeze",
+];
+
+/// (field, serde attribute) of JSON types.
+const JSON_FIELD_ATTRIBUTES: &[(&str, &str)] = &[
+    // Block
+    (".proto.chain.BlockHeader.Raw.timestamp", r#"#[serde(with = "crate::json::int64")]"#),
+    (
+        ".proto.chain.BlockHeader.Raw.merkle_root_hash",
+        r#"#[serde(rename = "txTrieRoot", with = "crate::json::bytes")]"#,
+    ),
+    (
+        ".proto.chain.BlockHeader.Raw.parent_hash",
+        r#"#[serde(rename = "parentHash", with = "crate::json::bytes")]"#,
+    ),
+    (".proto.chain.BlockHeader.Raw.number", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.chain.BlockHeader.Raw.witness_address", r#"#[serde(with = "crate::json::bytes")]"#),
+    (
+        ".proto.chain.BlockHeader.Raw.account_state_root",
+        r#"#[serde(rename = "accountStateRoot", with = "crate::json::bytes")]"#,
+    ),
+    (".proto.chain.BlockHeader.witness_signature", r#"#[serde(with = "crate::json::bytes")]"#),
+    // Transaction
+    (".proto.chain.AccountId.name", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.chain.AccountId.address", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.chain.Authority.permission_name", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.chain.Transaction.Contract.type", r#"#[serde(with = "crate::json::contract_type")]"#),
+    (".proto.chain.Transaction.Contract.parameter", r#"#[serde(with = "crate::json::any")]"#),
+    (".proto.chain.Transaction.Contract.provider", r#"#[serde(with = "crate::json::bytes")]"#),
+    (
+        ".proto.chain.Transaction.Contract.contract_name",
+        r#"#[serde(rename = "ContractName", with = "crate::json::bytes")]"#,
+    ),
+    (".proto.chain.Transaction.Contract.permission_id", r#"#[serde(rename = "Permission_id")]"#),
+    (".proto.chain.Transaction.Result.fee", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.chain.Transaction.Result.status", r#"#[serde(rename = "ret")]"#),
+    (
+        ".proto.chain.Transaction.Result.contract_status",
+        r#"#[serde(rename = "contractRet", with = "crate::json::contract_status")]"#,
+    ),
+    (".proto.chain.Transaction.Result.asset_issue_id", r#"#[serde(rename = "assetIssueID")]"#),
+    (".proto.chain.Transaction.Result.withdraw_amount", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.chain.Transaction.Result.unfreeze_amount", r#"#[serde(with = "crate::json::int64")]"#),
+    (
+        ".proto.chain.Transaction.Result.exchange_received_amount",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (
+        ".proto.chain.Transaction.Result.exchange_inject_another_amount",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (
+        ".proto.chain.Transaction.Result.exchange_withdraw_another_amount",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (".proto.chain.Transaction.Result.exchange_id", r#"#[serde(with = "crate::json::int64")]"#),
+    (
+        ".proto.chain.Transaction.Result.shielded_transaction_fee",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (".proto.chain.Transaction.Result.OBSOLETE_resource_receipt_wrapper", "#[serde(skip)]"),
+    (".proto.chain.Transaction.Raw.ref_block_bytes", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.chain.Transaction.Raw.ref_block_num", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.chain.Transaction.Raw.ref_block_hash", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.chain.Transaction.Raw.expiration", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.chain.Transaction.Raw.data", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.chain.Transaction.Raw.contract", r#"#[serde(with = "crate::json::option_as_list")]"#),
+    (".proto.chain.Transaction.Raw.scripts", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.chain.Transaction.Raw.timestamp", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.chain.Transaction.Raw.fee_limit", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.chain.Transaction.Raw.OBSOLETE_max_cpu_usage_wrapper", "#[serde(skip)]"),
+    (".proto.chain.Transaction.signatures", r#"#[serde(rename = "signature", with = "crate::json::bytes_list")]"#),
+    (".proto.chain.Transaction.result", r#"#[serde(rename = "ret")]"#),
+    (".proto.chain.Transaction.OBSOLETE_raw_wrapper", "#[serde(skip)]"),
+    (".proto.chain.Transaction.OBSOLETE_txid_WRAPPER", "#[serde(skip)]"),
+    // Account
+    (".proto.state.AccountResource.free_bandwidth_used", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.AccountResource.free_bandwidth_latest_slot", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.AccountResource.frozen_bandwidth_used", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.AccountResource.frozen_bandwidth_limit", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.AccountResource.frozen_bandwidth_latest_slot", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.AccountResource.energy_used", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.AccountResource.energy_latest_slot", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.AccountResource.asset_bandwidth_used", r#"#[serde(with = "crate::json::int64_map")]"#),
+    (".proto.state.AccountResource.asset_bandwidth_latest_slot", r#"#[serde(with = "crate::json::int64_map")]"#),
+    (".proto.state.PermissionKey.address", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.state.PermissionKey.weight", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.OwnerPermission.threshold", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.ActivePermission.threshold", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.ActivePermission.operations", r#"#[serde(with = "crate::json::bytes")]"#),
+    (".proto.state.Account.type", r#"#[serde(with = "crate::json::account_type")]"#),
+    (".proto.state.Account.creation_time", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.Account.balance", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.Account.token_balance", r#"#[serde(with = "crate::json::int64_map")]"#),
+    (".proto.state.Account.latest_operation_timestamp", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.Account.frozen_amount_for_bandwidth", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.Account.frozen_amount_for_energy", r#"#[serde(with = "crate::json::int64")]"#),
+    (
+        ".proto.state.Account.delegated_frozen_amount_for_bandwidth",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (
+        ".proto.state.Account.delegated_frozen_amount_for_energy",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (".proto.state.Account.delegated_out_amount", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.Account.issued_asset_id", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.Account.latest_withdraw_timestamp", r#"#[serde(with = "crate::json::int64")]"#),
+    (".proto.state.Account.allowance", r#"#[serde(with = "crate::json::int64")]"#),
+    (
+        ".proto.state.Account.frozen_v2_amount_for_bandwidth",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (".proto.state.Account.frozen_v2_amount_for_energy", r#"#[serde(with = "crate::json::int64")]"#),
+    (
+        ".proto.state.Account.delegated_v2_out_amount_for_bandwidth",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (
+        ".proto.state.Account.delegated_v2_out_amount_for_energy",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (
+        ".proto.state.Account.delegated_v2_frozen_amount_for_bandwidth",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (
+        ".proto.state.Account.delegated_v2_frozen_amount_for_energy",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+    (".proto.state.PendingUnfreeze.resource", r#"#[serde(with = "crate::json::resource_code")]"#),
+    (".proto.state.PendingUnfreeze.amount", r#"#[serde(with = "crate::json::int64")]"#),
+    (
+        ".proto.state.PendingUnfreeze.expiration_timestamp",
+        r#"#[serde(with = "crate::json::int64")]"#,
+    ),
+];
+
 fn main() {
-    prost_build::Config::new()
+    let mut config = prost_build::Config::new();
+    config
         .type_attribute("proto.common.SmartContract.ABI", "#[derive(serde::Serialize)]")
         .type_attribute("proto.common.SmartContract.ABI.Entry", "#[derive(serde::Serialize)]")
-        .type_attribute("proto.common.SmartContract.ABI.Param", "#[derive(serde::Serialize)]")
+        .type_attribute("proto.common.SmartContract.ABI.Param", "#[derive(serde::Serialize)]");
+    for ty in JSON_TYPES {
+        config.type_attribute(
+            ty,
+            "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]",
+        );
+    }
+    for (field, attr) in JSON_FIELD_ATTRIBUTES {
+        config.field_attribute(field, attr);
+    }
+    config
         .compile_protos(
             &[
                 "proto/common.proto",
@@ -0,0 +1,243 @@
+//! Serde helpers for JSON of chain types, following java-tron's HTTP API conventions.
+//!
+//! - `bytes` are hex encoded
+//! - `int64` are stringified, since JavaScript has no 64-bit integers
+//! - enums are encoded as their names in proto files
+//! - `google.protobuf.Any` is encoded as `{"type_url": ..., "value": <hex>}`
+//!
+//! The attributes are attached to generated types in `build.rs`.
+
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::Serializer;
+
+/// `bytes` as hex string.
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(val: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(val))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(&s).map_err(D::Error::custom)
+    }
+}
+
+/// `repeated bytes` as list of hex strings.
+pub mod bytes_list {
+    use super::*;
+    use serde::ser::SerializeSeq;
+
+    pub fn serialize<S: Serializer>(val: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(val.len()))?;
+        for item in val {
+            seq.serialize_element(&hex::encode(item))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| hex::decode(s).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// `int64` as decimal string. Numbers are also accepted when deserializing.
+pub mod int64 {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(i64),
+    }
+
+    pub fn serialize<S: Serializer>(val: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&val.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        match StringOrNumber::deserialize(deserializer)? {
+            StringOrNumber::String(s) => s.parse().map_err(D::Error::custom),
+            StringOrNumber::Number(n) => Ok(n),
+        }
+    }
+}
+
+/// `map<int64, int64>` with stringified values.
+pub mod int64_map {
+    use super::*;
+    use serde::ser::SerializeMap;
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(val: &HashMap<i64, i64>, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<_> = val.iter().collect();
+        entries.sort();
+        let mut map = serializer.serialize_map(Some(entries.len()))?;
+        for (k, v) in entries {
+            map.serialize_entry(&k.to_string(), &v.to_string())?;
+        }
+        map.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<i64, i64>, D::Error> {
+        HashMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(k, v)| Ok((k.parse().map_err(D::Error::custom)?, v.parse().map_err(D::Error::custom)?)))
+            .collect()
+    }
+}
+
+/// Optional message as a list of at most one element, i.e. `Transaction.raw_data.contract`.
+pub mod option_as_list {
+    use super::*;
+    use serde::Serialize;
+
+    pub fn serialize<S: Serializer, T: Serialize>(val: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        val.iter().collect::<Vec<_>>().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+        let mut list = Vec::<T>::deserialize(deserializer)?;
+        if list.len() > 1 {
+            return Err(D::Error::custom("at most one element is allowed"));
+        }
+        Ok(list.pop())
+    }
+}
+
+/// `google.protobuf.Any`, value in hex.
+pub mod any {
+    use super::*;
+    use prost_types::Any;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct JsonAny {
+        type_url: String,
+        value: String,
+    }
+
+    pub fn serialize<S: Serializer>(val: &Option<Any>, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::Serialize;
+
+        val.as_ref()
+            .map(|any| JsonAny {
+                type_url: any.type_url.clone(),
+                value: hex::encode(&any.value),
+            })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Any>, D::Error> {
+        Option::<JsonAny>::deserialize(deserializer)?
+            .map(|any| {
+                Ok(Any {
+                    type_url: any.type_url,
+                    value: hex::decode(&any.value).map_err(D::Error::custom)?,
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Proto name of an enum variant, from its Rust name. i.e. `OutOfEnergy` => `OUT_OF_ENERGY`.
+fn screaming_snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i != 0 {
+            out.push('_');
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+    out
+}
+
+macro_rules! impl_enum_name {
+    ($mod_name:ident, $enum_ty:path, $max_value:expr, $to_name:expr) => {
+        /// Enum as its proto name.
+        pub mod $mod_name {
+            use super::*;
+            use $enum_ty as EnumType;
+
+            fn name_of(val: i32) -> Option<String> {
+                EnumType::from_i32(val).map(|v| ($to_name)(&format!("{:?}", v)))
+            }
+
+            pub fn serialize<S: Serializer>(val: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+                match name_of(*val) {
+                    Some(name) => serializer.serialize_str(&name),
+                    None => serializer.serialize_i32(*val),
+                }
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+                let name = String::deserialize(deserializer)?;
+                (0..=$max_value)
+                    .find(|&val| name_of(val).as_ref() == Some(&name))
+                    .ok_or_else(|| D::Error::custom(format!("unknown enum name {}", name)))
+            }
+        }
+    };
+}
+
+impl_enum_name!(contract_type, crate::chain::ContractType, 64, |s: &str| s.to_owned());
+impl_enum_name!(
+    contract_status,
+    crate::chain::transaction::result::ContractStatus,
+    14,
+    screaming_snake_case
+);
+impl_enum_name!(account_type, crate::common::AccountType, 2, |s: &str| s.to_owned());
+impl_enum_name!(resource_code, crate::common::ResourceCode, 1, screaming_snake_case);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::{transaction, ContractType, Transaction};
+
+    #[test]
+    fn test_screaming_snake_case() {
+        assert_eq!(screaming_snake_case("Success"), "SUCCESS");
+        assert_eq!(screaming_snake_case("OutOfEnergy"), "OUT_OF_ENERGY");
+        assert_eq!(screaming_snake_case("JvmStackOverFlow"), "JVM_STACK_OVER_FLOW");
+    }
+
+    #[test]
+    fn test_transaction_json_roundtrip() {
+        let txn = Transaction {
+            raw_data: Some(transaction::Raw {
+                ref_block_bytes: vec![0x12, 0x34],
+                ref_block_hash: vec![0xab; 8],
+                expiration: 1_600_000_060_000,
+                contract: Some(transaction::Contract {
+                    r#type: ContractType::TransferContract as i32,
+                    parameter: Some(prost_types::Any {
+                        type_url: "type.googleapis.com/protocol.TransferContract".into(),
+                        value: vec![0x0a, 0x01, 0x41],
+                    }),
+                    ..Default::default()
+                }),
+                timestamp: 1_600_000_000_000,
+                fee_limit: 1_000_000,
+                ..Default::default()
+            }),
+            signatures: vec![vec![0xcd; 65]],
+            result: vec![transaction::Result::success()],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&txn).unwrap();
+        assert_eq!(json["raw_data"]["ref_block_bytes"], "1234");
+        assert_eq!(json["raw_data"]["expiration"], "1600000060000");
+        assert_eq!(json["raw_data"]["contract"][0]["type"], "TransferContract");
+        assert_eq!(json["raw_data"]["contract"][0]["parameter"]["value"], "0a0141");
+        assert_eq!(json["ret"][0]["contractRet"], "SUCCESS");
+
+        let decoded: Transaction = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, txn);
+    }
+}
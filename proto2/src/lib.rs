@@ -45,6 +45,44 @@ pub mod chain {
         }
     }
 
+    /// Length of `Permission.operations`, a 256-bit bitmap indexed by contract type.
+    pub const OPERATIONS_LEN: usize = 32;
+
+    impl ContractType {
+        /// Bit index in `Permission.operations` of active permissions.
+        pub fn operation_bit(self) -> usize {
+            self as i32 as usize
+        }
+
+        /// Whether the operation of the contract type is enabled in an operations bitmap.
+        pub fn is_operation_enabled(self, operations: &[u8]) -> bool {
+            let bit = self.operation_bit();
+            operations.get(bit / 8).map_or(false, |op| (op >> (bit % 8)) & 1 == 1)
+        }
+
+        /// Operations bitmap of all defined contract types, including obsolete ones.
+        pub fn all_operations() -> [u8; OPERATIONS_LEN] {
+            let mut operations = [0u8; OPERATIONS_LEN];
+            for ty in (0..OPERATIONS_LEN as i32 * 8).filter_map(ContractType::from_i32) {
+                let bit = ty.operation_bit();
+                operations[bit / 8] |= 1 << (bit % 8);
+            }
+            operations
+        }
+
+        /// Check an operations bitmap, returns the first undefined operation bit as error.
+        pub fn validate_operations(operations: &[u8]) -> Result<(), usize> {
+            let available = ContractType::all_operations();
+            for (i, (op, avail)) in operations.iter().zip(available.iter()).enumerate() {
+                let undefined = op & !avail;
+                if undefined != 0 {
+                    return Err(i * 8 + undefined.trailing_zeros() as usize);
+                }
+            }
+            Ok(())
+        }
+    }
+
     impl transaction::Result {
         pub fn success() -> Self {
             use self::transaction::result::ContractStatus;
@@ -78,3 +116,33 @@ pub mod contract {
 }
 
 pub mod state;
+
+pub mod json;
+
+#[cfg(test)]
+mod tests {
+    use super::chain::ContractType;
+
+    #[test]
+    fn test_operation_bits() {
+        let mut operations = [0u8; 32];
+        operations[0] = 0b0000_0011;
+        operations[5] = 0b0100_0000;
+        assert!(ContractType::AccountCreateContract.is_operation_enabled(&operations));
+        assert!(ContractType::TransferContract.is_operation_enabled(&operations));
+        assert!(ContractType::AccountPermissionUpdateContract.is_operation_enabled(&operations));
+        assert!(!ContractType::TriggerSmartContract.is_operation_enabled(&operations));
+        assert!(!ContractType::TransferContract.is_operation_enabled(&[]));
+        assert_eq!(ContractType::validate_operations(&operations), Ok(()));
+
+        let all = ContractType::all_operations();
+        assert_eq!(ContractType::validate_operations(&all), Ok(()));
+        assert!(ContractType::ObsoleteShieldedTransferContract.is_operation_enabled(&all));
+        // 7 is not defined
+        operations[0] = 0b1000_0000;
+        assert_eq!(ContractType::validate_operations(&operations), Err(7));
+        operations[0] = 0;
+        operations[31] = 0b1000_0000;
+        assert_eq!(ContractType::validate_operations(&operations), Err(255));
+    }
+}
@@ -58,16 +58,31 @@ impl Account {
         Err(())
     }
 
+    /// TRON Power, 1_TRX for 1_TP. Counts frozen amounts of the owner side, including those delegated to others,
+    /// but not resource delegated from others.
     pub fn tron_power(&self) -> i64 {
-        (self.frozen_amount_for_bandwidth + self.frozen_amount_for_energy + self.delegated_out_amount) / 1_000_000
+        (self.frozen_amount_for_bandwidth +
+            self.frozen_amount_for_energy +
+            self.delegated_out_amount +
+            self.frozen_v2_amount_for_bandwidth +
+            self.frozen_v2_amount_for_energy +
+            self.delegated_v2_out_amount_for_bandwidth +
+            self.delegated_v2_out_amount_for_energy) /
+            1_000_000
     }
 
     pub fn amount_for_bandwidth(&self) -> i64 {
-        self.frozen_amount_for_bandwidth + self.delegated_frozen_amount_for_bandwidth
+        self.frozen_amount_for_bandwidth +
+            self.delegated_frozen_amount_for_bandwidth +
+            self.frozen_v2_amount_for_bandwidth +
+            self.delegated_v2_frozen_amount_for_bandwidth
     }
 
     pub fn amount_for_energy(&self) -> i64 {
-        self.frozen_amount_for_energy + self.delegated_frozen_amount_for_energy
+        self.frozen_amount_for_energy +
+            self.delegated_frozen_amount_for_energy +
+            self.frozen_v2_amount_for_energy +
+            self.delegated_v2_frozen_amount_for_energy
     }
 
     pub fn resource(&self) -> &AccountResource {
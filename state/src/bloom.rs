@@ -0,0 +1,95 @@
+//! Per-block log bloom filters, for fast log queries over block ranges.
+//!
+//! Same as the 2048-bit bloom of Ethereum, 3 bits for each of contract address and topics of a log.
+
+use proto2::state::TransactionLog;
+
+pub const BLOOM_SIZE: usize = 256;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct LogBloom(pub [u8; BLOOM_SIZE]);
+
+impl Default for LogBloom {
+    fn default() -> Self {
+        LogBloom([0u8; BLOOM_SIZE])
+    }
+}
+
+impl std::fmt::Debug for LogBloom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "LogBloom({} bits)", self.0.iter().map(|b| b.count_ones()).sum::<u32>())
+    }
+}
+
+impl LogBloom {
+    pub fn from_slice(raw: &[u8]) -> Self {
+        let mut bloom = LogBloom::default();
+        bloom.0.copy_from_slice(raw);
+        bloom
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&b| b == 0)
+    }
+
+    /// Bit positions of an input, 3 * 11 bits from its keccak256 hash.
+    fn positions(input: &[u8]) -> [(usize, u8); 3] {
+        let hash = crypto::keccak256(input);
+        let hash = hash.as_bytes();
+        let mut positions = [(0, 0); 3];
+        for (i, pos) in positions.iter_mut().enumerate() {
+            let bit = ((hash[2 * i] as usize) << 8 | hash[2 * i + 1] as usize) & (BLOOM_SIZE * 8 - 1);
+            *pos = (BLOOM_SIZE - 1 - bit / 8, 1 << (bit % 8));
+        }
+        positions
+    }
+
+    pub fn accrue(&mut self, input: &[u8]) {
+        for &(idx, mask) in &Self::positions(input) {
+            self.0[idx] |= mask;
+        }
+    }
+
+    pub fn accrue_log(&mut self, log: &TransactionLog) {
+        self.accrue(&log.address);
+        for topic in &log.topics {
+            self.accrue(topic);
+        }
+    }
+
+    pub fn accrue_bloom(&mut self, other: &LogBloom) {
+        for (b, o) in self.0.iter_mut().zip(other.0.iter()) {
+            *b |= o;
+        }
+    }
+
+    /// False positives are possible, false negatives are not.
+    pub fn contains(&self, input: &[u8]) -> bool {
+        Self::positions(input).iter().all(|&(idx, mask)| self.0[idx] & mask != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_bloom() {
+        let log = TransactionLog {
+            address: vec![0x41; 21],
+            topics: vec![vec![0xdd; 32], vec![0x01; 32]],
+            data: vec![],
+        };
+        let mut bloom = LogBloom::default();
+        assert!(bloom.is_empty());
+        bloom.accrue_log(&log);
+        assert!(!bloom.is_empty());
+        assert!(bloom.contains(&log.address));
+        assert!(bloom.contains(&log.topics[0]));
+        assert!(bloom.contains(&log.topics[1]));
+        assert!(!bloom.contains(&[0x42; 21]));
+
+        let restored = LogBloom::from_slice(&bloom.0);
+        assert_eq!(restored, bloom);
+    }
+}
@@ -0,0 +1,103 @@
+//! Deterministic commitment of state changes, chained block by block.
+//!
+//! `commitment(n) = sha256(commitment(n - 1), n, changes(n))`, where `changes(n)` is the sorted key/value diff of
+//! state-db by block n, zero hash before the first block. Two nodes with the same commitment at a block have gone
+//! through the same state changes, which is much cheaper to compare than the state itself.
+//!
+//! Node-local data, i.e. optional indexes and the commitment itself, is excluded.
+
+use crypto::sha256;
+use primitive_types::H256;
+
+use super::db::{
+    COL_ACCOUNT_TRANSACTION, COL_BLOCK_RESOURCE_USAGE, COL_DEFAULT, COL_LOG_BLOOM, COL_NFT,
+    COL_RESOURCE_DELEGATION_INDEX, COL_RESOURCE_DELEGATION_REVERSE_INDEX, COL_RESOURCE_DELEGATION_V2_REVERSE_INDEX,
+    COL_SHIELDED_NOTE,
+};
+use super::keys::{self, Key};
+
+/// A state-db change, as `(column, key, old value, new value)`. None for non-existent or deleted.
+pub type Change = (usize, Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Is the change of a key a part of the commitment.
+pub fn is_committed(col: usize, key: &[u8]) -> bool {
+    match col {
+        COL_RESOURCE_DELEGATION_INDEX |
+        COL_RESOURCE_DELEGATION_REVERSE_INDEX |
+        COL_RESOURCE_DELEGATION_V2_REVERSE_INDEX |
+        COL_LOG_BLOOM |
+        COL_NFT |
+        COL_ACCOUNT_TRANSACTION |
+        COL_BLOCK_RESOURCE_USAGE |
+        COL_SHIELDED_NOTE => false,
+        COL_DEFAULT => {
+            key != keys::StateCommitment.key().as_bytes() && key != keys::LogBloomIndexedFrom.key().as_bytes()
+        }
+        _ => true,
+    }
+}
+
+/// Commitment of a block, chained from the previous one.
+pub fn next_commitment(prev: &H256, block_number: i64, changes: &[Change]) -> H256 {
+    let mut changes: Vec<_> = changes
+        .iter()
+        .filter(|(col, key, _, _)| is_committed(*col, key))
+        .collect();
+    changes.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+    let mut buf = Vec::with_capacity(32 + 8 + changes.len() * 64);
+    buf.extend_from_slice(prev.as_bytes());
+    buf.extend_from_slice(&block_number.to_be_bytes());
+    for (col, key, _, new_value) in changes {
+        buf.push(*col as u8);
+        write_bytes(&mut buf, key);
+        match new_value {
+            Some(value) => {
+                buf.push(1);
+                write_bytes(&mut buf, value);
+            }
+            None => buf.push(0),
+        }
+    }
+    sha256(&buf)
+}
+
+/// Length-prefixed, so that concatenation is unambiguous.
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::COL_ACCOUNT;
+
+    #[test]
+    fn test_next_commitment() {
+        let changes = vec![
+            (COL_ACCOUNT, b"b".to_vec(), None, Some(b"2".to_vec())),
+            (COL_ACCOUNT, b"a".to_vec(), Some(b"0".to_vec()), Some(b"1".to_vec())),
+            (COL_DEFAULT, b"kStateCommitment".to_vec(), None, Some(vec![0; 32])),
+        ];
+        let prev = H256::zero();
+        let commitment = next_commitment(&prev, 1, &changes);
+
+        // Order and excluded keys do not matter, old values are not committed.
+        let mut reordered = changes.clone();
+        reordered.reverse();
+        reordered[1].2 = Some(b"x".to_vec());
+        reordered.push((COL_NFT, b"n".to_vec(), None, Some(b"3".to_vec())));
+        assert_eq!(next_commitment(&prev, 1, &reordered), commitment);
+
+        assert_ne!(next_commitment(&prev, 2, &changes), commitment);
+        assert_ne!(next_commitment(&commitment, 1, &changes), commitment);
+        let deleted = vec![(COL_ACCOUNT, b"a".to_vec(), Some(b"0".to_vec()), None)];
+        let emptied = vec![(COL_ACCOUNT, b"a".to_vec(), Some(b"0".to_vec()), Some(vec![]))];
+        assert_ne!(next_commitment(&prev, 1, &deleted), next_commitment(&prev, 1, &emptied));
+        // Length prefix, no ambiguity when moving bytes from key to value.
+        let split1 = vec![(COL_ACCOUNT, b"ab".to_vec(), None, Some(b"c".to_vec()))];
+        let split2 = vec![(COL_ACCOUNT, b"a".to_vec(), None, Some(b"bc".to_vec()))];
+        assert_ne!(next_commitment(&prev, 1, &split1), next_commitment(&prev, 1, &split2));
+    }
+}
@@ -1,9 +1,11 @@
 //! The state-db implementation.
 
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::io;
 use std::iter;
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use ::keys::Address;
 use config::genesis::GenesisConfig;
@@ -13,7 +15,9 @@ use proto2::common::AccountType;
 use proto2::state as state_pb;
 use rocks::prelude::*;
 
+use super::commitment::Change;
 use super::keys;
+use super::migration::run_migrations;
 use super::parameter::default_parameters_from_config;
 use super::DynamicProperty;
 
@@ -281,6 +285,44 @@ impl OverlayDB {
         }
     }
 
+    /// Like `for_each`, but only visits keys starting with `prefix`.
+    pub fn for_each_with_prefix<F>(&self, col: &ColumnFamilyHandle, prefix: &[u8], mut func: F)
+    where
+        F: FnMut(&[u8], &[u8]) -> (),
+    {
+        let mut visited: HashSet<&[u8]> = HashSet::new();
+
+        for layer in self.layers.iter().rev() {
+            if let Some(cache) = layer.cache.get(&col.id()) {
+                for (key, value) in cache.range::<[u8], _>(prefix..) {
+                    if !key.starts_with(prefix) {
+                        break;
+                    }
+                    if visited.contains(&**key) {
+                        continue;
+                    }
+                    visited.insert(key);
+                    if let Some(val) = value {
+                        func(key, val);
+                    }
+                }
+            }
+        }
+
+        for (key, value) in self
+            .inner
+            .new_iterator_cf(&ReadOptions::default().iterate_lower_bound(prefix), col)
+        {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if visited.contains(key) {
+                continue;
+            }
+            func(key, value);
+        }
+    }
+
     /// Iterate over the data for a given column, returning all key/value pairs
     /// where the key starts with the given prefix.
     pub fn iter_with_prefix<'a>(
@@ -310,15 +352,66 @@ pub const COL_INTERNAL_TRANSACTION: usize = 12;
 pub const COL_TRANSACTION_LOG: usize = 13;
 pub const COL_ACCOUNT_INDEX: usize = 14;
 pub const COL_VOTER_REWARD: usize = 15;
+/// Token balances of accounts.
+pub const COL_ACCOUNT_ASSET: usize = 16;
+/// Votes of accounts, snapshotted for reward epochs.
+pub const COL_VOTE_SNAPSHOT: usize = 17;
+/// Stake 2.0 resource delegations.
+pub const COL_RESOURCE_DELEGATION_V2: usize = 18;
+/// Log bloom filters of blocks with logs.
+pub const COL_LOG_BLOOM: usize = 19;
+/// TRC721/TRC1155 ownership and transfers, optional.
+pub const COL_NFT: usize = 20;
+/// Transactions of accounts, optional.
+pub const COL_ACCOUNT_TRANSACTION: usize = 21;
+/// Delegators of accounts, reverse of `COL_RESOURCE_DELEGATION_INDEX`.
+pub const COL_RESOURCE_DELEGATION_REVERSE_INDEX: usize = 22;
+/// Resource usage of blocks with transactions.
+pub const COL_BLOCK_RESOURCE_USAGE: usize = 23;
+/// Shielded TRC20 notes of configured viewing keys, optional.
+pub const COL_SHIELDED_NOTE: usize = 24;
+/// Stake 2.0 delegators of accounts, reverse of `COL_RESOURCE_DELEGATION_V2`.
+pub const COL_RESOURCE_DELEGATION_V2_REVERSE_INDEX: usize = 25;
+
+/// Durability of layers solidified to state-db.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Written to WAL, synced by the OS. Survives process crashes, not power loss.
+    Default,
+    /// WAL synced on every write.
+    Sync,
+    /// No WAL, only in memtables until flushed by `checkpoint`. A crash loses writes after the last checkpoint.
+    NoWal,
+}
+
+impl WritePolicy {
+    fn write_options(self) -> WriteOptions {
+        match self {
+            WritePolicy::Default => WriteOptions::default(),
+            WritePolicy::Sync => WriteOptions::default().sync(true),
+            WritePolicy::NoWal => WriteOptions::default().disable_wal(true),
+        }
+    }
+}
 
 /// The State DB derived from Chain DB.
 pub struct StateDB {
     db: OverlayDB,
     cols: Vec<ColumnFamily>,
+    write_policy: WritePolicy,
+    /// Written without WAL since the last checkpoint.
+    unflushed: bool,
+    /// Opened by `new_secondary`, read-only.
+    secondary: bool,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<super::fault::FaultInjector>,
 }
 
 impl Drop for StateDB {
     fn drop(&mut self) {
+        if self.unflushed {
+            info!("flush state-db written without WAL ... {:?}", self.checkpoint());
+        }
         info!("state-db closed successfully, all cached layers will be droped");
     }
 }
@@ -345,7 +438,7 @@ fn col_descs_for_state_db() -> Vec<ColumnFamilyDescriptor> {
             "resource-delegation",
             ColumnFamilyOptions::default().optimize_for_point_lookup(128),
         ),
-        // to_address => [from_address]
+        // from_address => [to_address]
         ColumnFamilyDescriptor::new(
             "resource-delegation-index",
             ColumnFamilyOptions::default().optimize_for_point_lookup(128),
@@ -419,6 +512,54 @@ fn col_descs_for_state_db() -> Vec<ColumnFamilyDescriptor> {
                 .optimize_for_small_db()
                 .optimize_for_point_lookup(16),
         ),
+        // <<Address, token_id: i64>> => i64
+        ColumnFamilyDescriptor::new(
+            "account-asset",
+            ColumnFamilyOptions::default()
+                .optimize_for_point_lookup(128)
+                .prefix_extractor_fixed(21),
+        ),
+        // <<epoch: i64, Address>> => Votes
+        ColumnFamilyDescriptor::new(
+            "vote-snapshot",
+            ColumnFamilyOptions::default().optimize_for_point_lookup(16),
+        ),
+        // <<from_address, to_address, locked: u8>> => ResourceDelegation
+        ColumnFamilyDescriptor::new(
+            "resource-delegation-v2",
+            ColumnFamilyOptions::default()
+                .optimize_for_point_lookup(128)
+                .prefix_extractor_fixed(21),
+        ),
+        // <<block_number: i64>> => LogBloom
+        ColumnFamilyDescriptor::new(
+            "log-bloom",
+            ColumnFamilyOptions::default().compression(CompressionType::NoCompression),
+        ),
+        // <<'b', owner, contract, token_id>> => amount
+        // <<'o', contract, token_id>> => owner
+        // <<'t', contract, block_number: i64, txn_index: u32, log_index: u32, batch_index: u32>> => NftTransfer
+        ColumnFamilyDescriptor::new("nft", ColumnFamilyOptions::default()),
+        // <<Address, to_this: u8, !block_number: u64, !txn_index: u32>> => txid
+        ColumnFamilyDescriptor::new(
+            "account-transaction",
+            ColumnFamilyOptions::default().prefix_extractor_fixed(22),
+        ),
+        // to_address => [from_address]
+        ColumnFamilyDescriptor::new(
+            "resource-delegation-reverse-index",
+            ColumnFamilyOptions::default().optimize_for_point_lookup(128),
+        ),
+        // <<block_number: i64>> => BlockResourceUsage
+        ColumnFamilyDescriptor::new("block-resource-usage", ColumnFamilyOptions::default()),
+        // <<'n', key_id: H256, contract, position: u64>> => ShieldedNote
+        // <<'s', nullifier: H256>> => <<key_id: H256, contract, position: u64>>
+        ColumnFamilyDescriptor::new("shielded-note", ColumnFamilyOptions::default()),
+        // to_address => [from_address]
+        ColumnFamilyDescriptor::new(
+            "resource-delegation-v2-reverse-index",
+            ColumnFamilyOptions::default().optimize_for_point_lookup(128),
+        ),
     ]
 }
 
@@ -429,7 +570,9 @@ impl StateDB {
             .create_missing_column_families(true)
             .increase_parallelism(num_cpus::get() as _)
             .allow_mmap_reads(true) // for Cuckoo table
-            .max_open_files(1024);
+            .max_open_files(1024)
+            // Column families written without WAL are flushed together by `checkpoint`.
+            .atomic_flush(true);
 
         let column_families = col_descs_for_state_db();
 
@@ -438,8 +581,47 @@ impl StateDB {
         StateDB {
             db: OverlayDB::new(db),
             cols,
+            write_policy: WritePolicy::Default,
+            unflushed: false,
+            secondary: false,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        }
+    }
+
+    /// Open as a RocksDB secondary instance, following a state-db written by another process.
+    ///
+    /// Writes go to layers only, solidifying a layer fails.
+    pub fn new_secondary<P1: AsRef<Path>, P2: AsRef<Path>>(db_path: P1, tmp_path: P2) -> StateDB {
+        let db_options = DBOptions::default()
+            .increase_parallelism(num_cpus::get() as _)
+            .allow_mmap_reads(true) // for Cuckoo table
+            .max_open_files(1024);
+
+        let column_families = col_descs_for_state_db();
+
+        let (db, cols) =
+            DB::open_as_secondary_with_column_families(&db_options, db_path, tmp_path, column_families).unwrap();
+
+        StateDB {
+            db: OverlayDB::new(db),
+            cols,
+            write_policy: WritePolicy::Default,
+            unflushed: false,
+            secondary: true,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
         }
     }
+
+    /// Catch up with the primary, only for a secondary instance.
+    pub fn catch_up_with_primary(&self) -> Result<(), BoxError> {
+        self.db.inner.try_catch_up_with_primary().map_err(|e| e.into())
+    }
+
+    pub fn is_secondary(&self) -> bool {
+        self.secondary
+    }
 }
 
 impl StateDB {
@@ -449,10 +631,39 @@ impl StateDB {
     }
 
     pub fn solidify_layer(&mut self) {
-        self.db
-            .layers
-            .pop_front()
-            .map(|wb| self.db.inner.write(WriteOptions::default_instance(), &wb));
+        if let Some(wb) = self.db.layers.pop_front() {
+            let _ = self.db.inner.write(&self.write_policy.write_options(), &wb);
+            self.unflushed |= self.write_policy == WritePolicy::NoWal;
+        }
+    }
+
+    pub fn write_policy(&self) -> WritePolicy {
+        self.write_policy
+    }
+
+    /// Change durability of following writes. Writes without WAL are flushed first when WAL is turned back on, or
+    /// they could be lost after WAL-protected writes on a crash.
+    pub fn set_write_policy(&mut self, policy: WritePolicy) -> Result<(), BoxError> {
+        if policy != WritePolicy::NoWal && self.unflushed {
+            self.checkpoint()?;
+        }
+        self.write_policy = policy;
+        Ok(())
+    }
+
+    /// Flush memtables of all columns atomically and sync WAL, so that all solidified layers are on disk.
+    pub fn checkpoint(&mut self) -> Result<(), BoxError> {
+        let cols = self.cols.iter().map(|col| &**col).collect::<Vec<_>>();
+        self.db.inner.flush_cfs(&FlushOptions::default(), &cols)?;
+        self.db.inner.sync_wal()?;
+        self.unflushed = false;
+        Ok(())
+    }
+
+    /// Sync WAL to disk, for writes of `WritePolicy::Default`.
+    pub fn sync_wal(&self) -> Result<(), BoxError> {
+        self.db.inner.sync_wal()?;
+        Ok(())
     }
 
     pub fn discard_last_layer(&mut self) -> io::Result<()> {
@@ -463,7 +674,33 @@ impl StateDB {
         Ok(())
     }
 
+    /// Make following `put_key` and `delete_key` calls fail randomly, or stop it with `None`.
+    #[cfg(feature = "fault-injection")]
+    pub fn set_fault_injector(&mut self, faults: Option<super::fault::FaultInjector>) {
+        self.faults = faults;
+    }
+
+    #[cfg(feature = "fault-injection")]
+    pub fn fault_injector(&self) -> Option<&super::fault::FaultInjector> {
+        self.faults.as_ref()
+    }
+
+    #[cfg(feature = "fault-injection")]
+    fn inject_fault(&mut self, op: &str, col: usize) -> Result<(), BoxError> {
+        match self.faults {
+            Some(ref mut faults) => Ok(faults.check(op, col)?),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "fault-injection"))]
+    #[inline]
+    fn inject_fault(&mut self, _op: &str, _col: usize) -> Result<(), BoxError> {
+        Ok(())
+    }
+
     pub fn put_key<T, K: keys::Key<T>>(&mut self, key: K, value: T) -> Result<(), BoxError> {
+        self.inject_fault("put", K::COL)?;
         let wb = self
             .db
             .layers
@@ -474,6 +711,7 @@ impl StateDB {
     }
 
     pub fn delete_key<T, K: keys::Key<T>>(&mut self, key: &K) -> Result<(), BoxError> {
+        self.inject_fault("delete", K::COL)?;
         let wb = self
             .db
             .layers
@@ -520,6 +758,58 @@ impl StateDB {
         Ok(old_val + 1)
     }
 
+    /// Number of column families.
+    pub fn num_columns(&self) -> usize {
+        self.cols.len()
+    }
+
+    /// Scan raw key-values of a column from `start_key`, at most `limit` entries.
+    ///
+    /// Only solidified data is visible, cached layers are ignored.
+    pub fn scan_solid(&self, col: usize, start_key: &[u8], limit: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.db
+            .inner
+            .new_iterator_cf(&ReadOptions::default().iterate_lower_bound(start_key), &self.cols[col])
+            .take(limit)
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect()
+    }
+
+    /// Changes of the top n layers, as `(column, key, old value, new value)`. None for non-existent or deleted.
+    pub fn layers_diff(&self, n: usize) -> Vec<Change> {
+        let mut diff = vec![];
+        for (col, cf) in self.cols.iter().enumerate() {
+            let changed_keys: BTreeSet<&Vec<u8>> = self
+                .db
+                .layers
+                .iter()
+                .rev()
+                .take(n)
+                .filter_map(|layer| layer.cache.get(&cf.id()))
+                .flat_map(|cache| cache.keys())
+                .collect();
+            for key in changed_keys {
+                let old_value = self.db.get_skipped(n, cf, key).expect("corrupted db");
+                let new_value = self.db.get(cf, key).expect("corrupted db");
+                if old_value != new_value {
+                    diff.push((col, key.clone(), old_value, new_value));
+                }
+            }
+        }
+        diff
+    }
+
+    /// Put a raw key-value to current layer.
+    pub fn put_raw(&mut self, col: usize, key: &[u8], value: &[u8]) -> Result<(), BoxError> {
+        let wb = self
+            .db
+            .layers
+            .back_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no db layers found"))?;
+        wb.put(&self.cols[col], key, value);
+        Ok(())
+    }
+
     pub fn for_each<T, K: keys::Key<T>, F>(&self, mut func: F)
     where
         F: FnMut(&K, &T) -> (),
@@ -529,10 +819,40 @@ impl StateDB {
         });
     }
 
+    /// Like `for_each`, but only visits keys starting with `prefix`.
+    pub fn for_each_with_prefix<T, K: keys::Key<T>, F>(&self, prefix: &[u8], mut func: F)
+    where
+        F: FnMut(&K, &T) -> (),
+    {
+        self.db
+            .for_each_with_prefix(&self.cols[K::COL], prefix, move |key, value| {
+                func(&K::parse_key(key), &K::parse_value(value))
+            });
+    }
+
+    /// Iterate over all entries of a key family, i.e. `db.iter::<_, keys::Witness>()`.
+    ///
+    /// Entries are collected eagerly, use `for_each` for big columns like accounts.
+    pub fn iter<T, K: keys::Key<T>>(&self) -> impl Iterator<Item = (K, T)> {
+        let mut entries = vec![];
+        self.db.for_each(&self.cols[K::COL], |key, value| {
+            entries.push((K::parse_key(key), K::parse_value(value)))
+        });
+        entries.into_iter()
+    }
+
+    /// Visits all keys under a typed prefix, i.e. `ResourceDelegation(from, *)`.
+    pub fn scan_prefix<T, K: keys::KeyPrefix<T>, F>(&self, prefix: &K::Prefix, func: F)
+    where
+        F: FnMut(&K, &T) -> (),
+    {
+        self.for_each_with_prefix(&K::prefix(prefix), func)
+    }
+
     pub fn init_genesis(&mut self, genesis: &GenesisConfig, chain: &ChainConfig) -> Result<(), BoxError> {
         if let Some(ver) = self.get(&keys::DynamicProperty::DbVersion)? {
             info!("state-db is already inited, ver: {}", ver);
-            // TODO: check migration here
+            run_migrations(self, /* dry_run */ false)?;
             let latest_block_hash = self.must_get(&keys::LatestBlockHash);
             info!("latest block hash {:?}", latest_block_hash);
             info!("block num {:?}", self.must_get(&DynamicProperty::LatestBlockNumber));
@@ -545,6 +865,9 @@ impl StateDB {
         for (k, v) in default_parameters_from_config(&chain.parameter) {
             self.put_key(k, v)?;
         }
+        if let Some(interval) = genesis.maintenance_interval {
+            self.put_key(keys::ChainParameter::MaintenanceInterval, interval)?;
+        }
         for (k, v) in DynamicProperty::default_properties() {
             self.put_key(k, v)?;
         }
@@ -588,20 +911,36 @@ impl StateDB {
             witnesses.push((addr, witness.votes));
         }
 
-        for alloc in &genesis.allocs {
+        let mut total_bandwidth_weight = 0;
+        let mut total_energy_weight = 0;
+        let dev_allocs = genesis.dev_allocs();
+        for alloc in genesis.allocs.iter().chain(&dev_allocs) {
             let addr: Address = alloc.address.parse()?;
-            println!("{:?}", alloc);
+            // Snapshot allocs are unnamed, and might be too many to print.
+            if !alloc.name.is_empty() {
+                println!("{:?}", alloc);
+            }
             let acct = state_pb::Account {
                 name: alloc.name.clone(),
                 balance: alloc.balance,
                 creation_time: genesis.timestamp,
                 r#type: AccountType::Normal as i32,
                 resource: Some(Default::default()),
+                frozen_v2_amount_for_bandwidth: alloc.frozen_for_bandwidth,
+                frozen_v2_amount_for_energy: alloc.frozen_for_energy,
                 ..Default::default()
             };
 
             self.put_key(keys::Account(addr), acct)?;
-            self.put_key(keys::AccountIndex(alloc.name.clone()), addr)?;
+            if !alloc.name.is_empty() {
+                self.put_key(keys::AccountIndex(alloc.name.clone()), addr)?;
+            }
+            total_bandwidth_weight += alloc.frozen_for_bandwidth / 1_000_000;
+            total_energy_weight += alloc.frozen_for_energy / 1_000_000;
+        }
+        if total_bandwidth_weight > 0 || total_energy_weight > 0 {
+            self.put_key(DynamicProperty::TotalBandwidthWeight, total_bandwidth_weight)?;
+            self.put_key(DynamicProperty::TotalEnergyWeight, total_energy_weight)?;
         }
 
         let genesis_block = genesis.to_indexed_block()?;
@@ -623,13 +962,15 @@ impl StateDB {
 pub struct ReadOnlySolidStateDB {
     db: DB,
     cols: Vec<ColumnFamily>,
+    /// When the secondary instance last caught up with the primary.
+    caught_up_at: Mutex<Instant>,
 }
 
 unsafe impl Send for ReadOnlySolidStateDB {}
 unsafe impl Sync for ReadOnlySolidStateDB {}
 
 impl ReadOnlySolidStateDB {
-    pub fn new<P1: AsRef<Path>, P2: AsRef<Path>>(db_path: P1, tmp_path: P2) -> StateDB {
+    pub fn new<P1: AsRef<Path>, P2: AsRef<Path>>(db_path: P1, tmp_path: P2) -> ReadOnlySolidStateDB {
         let db_options = DBOptions::default()
             .increase_parallelism(num_cpus::get() as _)
             .allow_mmap_reads(true) // for Cuckoo table
@@ -640,9 +981,10 @@ impl ReadOnlySolidStateDB {
         let (db, cols) =
             DB::open_as_secondary_with_column_families(&db_options, db_path, tmp_path, column_families).unwrap();
 
-        StateDB {
-            db: OverlayDB::new(db),
+        ReadOnlySolidStateDB {
+            db,
             cols,
+            caught_up_at: Mutex::new(Instant::now()),
         }
     }
 
@@ -654,7 +996,61 @@ impl ReadOnlySolidStateDB {
             .map_err(|e| e.into())
     }
 
+    pub fn for_each<T, K: keys::Key<T>, F>(&self, mut func: F)
+    where
+        F: FnMut(&K, &T) -> (),
+    {
+        for (key, value) in self.db.new_iterator_cf(&ReadOptions::default(), &self.cols[K::COL]) {
+            func(&K::parse_key(key), &K::parse_value(value));
+        }
+    }
+
+    /// Iterate over keys starting with `prefix`, from `start`(inclusive) in lexicographic order.
+    ///
+    /// Iteration stops when `func` returns false.
+    pub fn for_each_with_prefix_from<T, K: keys::Key<T>, F>(&self, prefix: &[u8], start: &[u8], mut func: F)
+    where
+        F: FnMut(&K, &T) -> bool,
+    {
+        let lower_bound = if start > prefix { start } else { prefix };
+        for (key, value) in self.db.new_iterator_cf(
+            &ReadOptions::default().iterate_lower_bound(lower_bound),
+            &self.cols[K::COL],
+        ) {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if !func(&K::parse_key(key), &K::parse_value(value)) {
+                break;
+            }
+        }
+    }
+
+    /// Iterate over all entries of a key family, collected eagerly.
+    pub fn iter<T, K: keys::Key<T>>(&self) -> impl Iterator<Item = (K, T)> {
+        self.db
+            .new_iterator_cf(&ReadOptions::default(), &self.cols[K::COL])
+            .map(|(key, value)| (K::parse_key(key), K::parse_value(value)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Like `for_each_with_prefix_from`, with a typed prefix of the key family.
+    pub fn scan_prefix_from<T, K: keys::KeyPrefix<T>, F>(&self, prefix: &K::Prefix, start: &[u8], func: F)
+    where
+        F: FnMut(&K, &T) -> bool,
+    {
+        self.for_each_with_prefix_from(&K::prefix(prefix), start, func)
+    }
+
     pub fn catch_up_with_primary(&self) {
-        let _ = self.db.try_catch_up_with_primary();
+        if self.db.try_catch_up_with_primary().is_ok() {
+            *self.caught_up_at.lock().unwrap() = Instant::now();
+        }
+    }
+
+    /// Time since the last successful catch-up, i.e. how stale reads might be.
+    pub fn snapshot_age(&self) -> Duration {
+        self.caught_up_at.lock().unwrap().elapsed()
     }
 }
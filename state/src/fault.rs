@@ -0,0 +1,86 @@
+//! Randomized fault injection in state-db writes, for soak tests. Built only with the `fault-injection` feature.
+//!
+//! When enabled on a `StateDB`, `put_key` and `delete_key` fail at the given rate, in an order fixed by the seed, so
+//! that a failing run can be reproduced.
+
+use std::io;
+
+/// Seeded generator of write failures.
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    seed: u64,
+    /// xorshift64* state, never zero.
+    state: u64,
+    /// Failure rate, in parts per million of writes.
+    rate_ppm: u32,
+    /// Number of writes checked.
+    pub num_writes: u64,
+    /// Number of failed writes.
+    pub num_faults: u64,
+}
+
+impl FaultInjector {
+    /// `rate` is the probability of each write to fail, from 0.0 to 1.0.
+    pub fn new(seed: u64, rate: f64) -> FaultInjector {
+        assert!(rate >= 0.0 && rate <= 1.0, "fault rate must be in [0, 1]");
+        FaultInjector {
+            seed,
+            // Mixed, so that nearby seeds do not start with similar sequences.
+            state: (seed ^ 0x9e37_79b9_7f4a_7c15) | 1,
+            rate_ppm: (rate * 1_000_000.0) as u32,
+            num_writes: 0,
+            num_faults: 0,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Check a write, returns an error if it is chosen to fail.
+    pub fn check(&mut self, op: &str, col: usize) -> io::Result<()> {
+        self.num_writes += 1;
+        if self.next_u64() % 1_000_000 < self.rate_ppm as u64 {
+            self.num_faults += 1;
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("injected fault #{} in {} of column {}", self.num_faults, op, col),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_injector() {
+        let failures = |seed| {
+            let mut faults = FaultInjector::new(seed, 0.1);
+            (0..1_000).map(|_| faults.check("put", 0).is_err()).collect::<Vec<_>>()
+        };
+        // Reproducible by seed.
+        assert_eq!(failures(42), failures(42));
+        assert_ne!(failures(42), failures(43));
+
+        let num_faults = failures(42).into_iter().filter(|&failed| failed).count();
+        assert!(num_faults > 50 && num_faults < 150);
+
+        let mut never = FaultInjector::new(42, 0.0);
+        let mut always = FaultInjector::new(42, 1.0);
+        for _ in 0..1_000 {
+            assert!(never.check("put", 0).is_ok());
+            assert!(always.check("delete", 0).is_err());
+        }
+        assert_eq!(always.num_faults, 1_000);
+    }
+}
@@ -9,6 +9,8 @@ use primitive_types::H256;
 use prost::Message;
 use proto2::state as pb;
 
+use super::bloom;
+
 pub use super::parameter::ChainParameter;
 pub use super::property::DynamicProperty;
 
@@ -33,6 +35,14 @@ pub trait Key<T>: Sized {
     }
 }
 
+/// A key family scannable by a typed prefix, i.e. `ResourceDelegation(from, *)`.
+pub trait KeyPrefix<T>: Key<T> {
+    type Prefix;
+
+    /// Returns the db key prefix shared by all keys under `prefix`.
+    fn prefix(prefix: &Self::Prefix) -> Vec<u8>;
+}
+
 impl Key<i64> for ChainParameter {
     type Target = Vec<u8>;
     const COL: usize = super::db::COL_DEFAULT;
@@ -134,6 +144,31 @@ impl Key<H256> for LatestBlockHash {
     }
 }
 
+/// Commitment of all state changes, chained block by block. See `state::commitment`.
+#[derive(Debug)]
+pub struct StateCommitment;
+
+impl Key<H256> for StateCommitment {
+    type Target = &'static str;
+    const COL: usize = super::db::COL_DEFAULT;
+
+    // Same as DynamicProperty
+    fn key(&self) -> Self::Target {
+        "kStateCommitment"
+    }
+
+    fn value(val: &H256) -> Cow<[u8]> {
+        val.as_bytes().into()
+    }
+
+    fn parse_value(raw: &[u8]) -> H256 {
+        if raw.len() != 32 {
+            panic!("malformed kStateCommitment");
+        }
+        H256::from_slice(raw)
+    }
+}
+
 #[derive(Debug)]
 pub struct BlockFilledSlots;
 
@@ -156,6 +191,133 @@ impl Key<Vec<u8>> for BlockFilledSlots {
     }
 }
 
+/// The first block with log bloom indexed, blocks before it are not indexed.
+#[derive(Debug)]
+pub struct LogBloomIndexedFrom;
+
+impl Key<i64> for LogBloomIndexedFrom {
+    type Target = &'static str;
+    const COL: usize = super::db::COL_DEFAULT;
+
+    fn key(&self) -> Self::Target {
+        "kLogBloomIndexedFrom"
+    }
+
+    fn value(val: &i64) -> Cow<[u8]> {
+        Cow::Owned(val.to_be_bytes().to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> i64 {
+        BE::read_u64(raw) as _
+    }
+}
+
+/// Last visited key of a migration applied in batches, removed when the migration is done. See `state::migration`.
+#[derive(Debug)]
+pub struct MigrationCursor;
+
+impl Key<Vec<u8>> for MigrationCursor {
+    type Target = &'static str;
+    const COL: usize = super::db::COL_DEFAULT;
+
+    fn key(&self) -> Self::Target {
+        "kMigrationCursor"
+    }
+
+    fn value(val: &Vec<u8>) -> Cow<[u8]> {
+        (&val[..]).into()
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<u8> {
+        raw.to_vec()
+    }
+}
+
+/// `total_missed` of active witnesses at the last maintenance, for the missed blocks penalty rule.
+/// `kWitnessMissedSnapshot => [<<address, total_missed: i64>>]`
+#[derive(Debug)]
+pub struct WitnessMissedSnapshot;
+
+impl Key<Vec<(Address, i64)>> for WitnessMissedSnapshot {
+    type Target = &'static str;
+    const COL: usize = super::db::COL_DEFAULT;
+
+    fn key(&self) -> Self::Target {
+        "kWitnessMissedSnapshot"
+    }
+
+    fn value(val: &Vec<(Address, i64)>) -> Cow<[u8]> {
+        let mut raw = Vec::with_capacity(val.len() * 29);
+        for (addr, total_missed) in val {
+            raw.extend_from_slice(addr.as_bytes());
+            raw.extend_from_slice(&total_missed.to_be_bytes());
+        }
+        raw.into()
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<(Address, i64)> {
+        if raw.len() % 29 != 0 {
+            panic!("malformed kWitnessMissedSnapshot");
+        }
+        raw.chunks(29)
+            .map(|chunk| (*Address::from_bytes(&chunk[..21]), BE::read_u64(&chunk[21..]) as i64))
+            .collect()
+    }
+}
+
+/// History of a price parameter, `EnergyFee` or `BandwidthFee`, appended when changed by a proposal.
+/// `kPriceHistory<ChainParameter> => <<block_number: i64, timestamp: i64, price: i64>>`
+///
+/// Timestamp is the expiration time of the proposal, same as java-tron's `energyPriceHistory`.
+#[derive(Debug)]
+pub struct PriceHistory(pub ChainParameter);
+
+impl PriceHistory {
+    /// Format as java-tron's price history string, i.e. `timestamp:price,timestamp:price`.
+    pub fn to_java_string(history: &[(i64, i64, i64)]) -> String {
+        history
+            .iter()
+            .map(|(_, timestamp, price)| format!("{}:{}", timestamp, price))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl Key<Vec<(i64, i64, i64)>> for PriceHistory {
+    type Target = String;
+    const COL: usize = super::db::COL_DEFAULT;
+
+    // Same as DynamicProperty
+    fn key(&self) -> Self::Target {
+        format!("kPriceHistory{:?}", self.0)
+    }
+
+    fn value(val: &Vec<(i64, i64, i64)>) -> Cow<[u8]> {
+        val.iter()
+            .map(|(block_number, timestamp, price)| {
+                [&block_number.to_be_bytes()[..], &timestamp.to_be_bytes()[..], &price.to_be_bytes()[..]].concat()
+            })
+            .collect::<Vec<_>>()
+            .concat()
+            .into()
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<(i64, i64, i64)> {
+        if raw.len() % 24 != 0 {
+            panic!("malformed kPriceHistory");
+        }
+        raw.chunks(24)
+            .map(|entry| {
+                (
+                    BE::read_u64(&entry[..8]) as i64,
+                    BE::read_u64(&entry[8..16]) as i64,
+                    BE::read_u64(&entry[16..]) as i64,
+                )
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct Witness(pub Address);
 
@@ -235,6 +397,40 @@ impl Key<pb::Account> for Account {
     }
 }
 
+/// Token balance of an account, split out of `Account.token_balance`.
+/// `<<Address, token_id: i64>> => balance: i64`
+#[derive(Debug)]
+pub struct AccountAsset(pub Address, pub i64);
+
+impl KeyPrefix<i64> for AccountAsset {
+    type Prefix = Address;
+
+    fn prefix(addr: &Address) -> Vec<u8> {
+        addr.as_bytes().to_vec()
+    }
+}
+
+impl Key<i64> for AccountAsset {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_ACCOUNT_ASSET;
+
+    fn key(&self) -> Self::Target {
+        [self.0.as_bytes(), &self.1.to_be_bytes()[..]].concat()
+    }
+
+    fn value(val: &i64) -> Cow<[u8]> {
+        Cow::Owned(val.to_be_bytes().to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> i64 {
+        BE::read_u64(raw) as _
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        AccountAsset(*Address::from_bytes(&raw[..21]), BE::read_u64(&raw[21..]) as _)
+    }
+}
+
 #[derive(Debug)]
 pub struct AccountIndex(pub String);
 
@@ -253,12 +449,24 @@ impl Key<Address> for AccountIndex {
     fn parse_value(raw: &[u8]) -> Address {
         *Address::from_bytes(raw)
     }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        AccountIndex(String::from_utf8_lossy(raw).into_owned())
+    }
 }
 
 /// Resource delegation, from_address, to_address.
 #[derive(Debug)]
 pub struct ResourceDelegation(pub Address, pub Address);
 
+impl KeyPrefix<pb::ResourceDelegation> for ResourceDelegation {
+    type Prefix = Address;
+
+    fn prefix(from: &Address) -> Vec<u8> {
+        from.as_bytes().to_vec()
+    }
+}
+
 impl Key<pb::ResourceDelegation> for ResourceDelegation {
     type Target = Vec<u8>;
     const COL: usize = super::db::COL_RESOURCE_DELEGATION;
@@ -276,9 +484,54 @@ impl Key<pb::ResourceDelegation> for ResourceDelegation {
     fn parse_value(raw: &[u8]) -> pb::ResourceDelegation {
         pb::ResourceDelegation::decode(raw).unwrap()
     }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        ResourceDelegation(*Address::from_bytes(&raw[..21]), *Address::from_bytes(&raw[21..]))
+    }
 }
 
-/// Reverse index for resource delegation info, to_address.
+/// Stake 2.0 resource delegation, from_address, to_address, locked.
+///
+/// `<<from_address, to_address, locked: u8>> => ResourceDelegation`
+#[derive(Debug)]
+pub struct ResourceDelegationV2(pub Address, pub Address, pub bool);
+
+impl KeyPrefix<pb::ResourceDelegation> for ResourceDelegationV2 {
+    type Prefix = Address;
+
+    fn prefix(from: &Address) -> Vec<u8> {
+        from.as_bytes().to_vec()
+    }
+}
+
+impl Key<pb::ResourceDelegation> for ResourceDelegationV2 {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_RESOURCE_DELEGATION_V2;
+
+    fn key(&self) -> Self::Target {
+        [self.0.as_bytes(), self.1.as_bytes(), &[self.2 as u8]].concat()
+    }
+
+    fn value(val: &pb::ResourceDelegation) -> Cow<[u8]> {
+        let mut buf = BytesMut::with_capacity(val.encoded_len());
+        val.encode(&mut buf).unwrap();
+        Cow::from(buf.to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> pb::ResourceDelegation {
+        pb::ResourceDelegation::decode(raw).unwrap()
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        ResourceDelegationV2(
+            *Address::from_bytes(&raw[..21]),
+            *Address::from_bytes(&raw[21..42]),
+            raw[42] != 0,
+        )
+    }
+}
+
+/// Index for resource delegation info, from_address => [to_address].
 #[derive(Debug)]
 pub struct ResourceDelegationIndex(pub Address);
 
@@ -291,24 +544,88 @@ impl Key<Vec<Address>> for ResourceDelegationIndex {
     }
 
     fn value(val: &Vec<Address>) -> Cow<[u8]> {
-        val.iter()
-            .map(|addr| addr.as_bytes())
-            .collect::<Vec<_>>()
-            .concat()
-            .into()
+        encode_address_list(val)
     }
 
     fn parse_value(raw: &[u8]) -> Vec<Address> {
-        if raw.len() % 21 != 0 {
-            panic!("malformed ResourceDelegationIndex db")
-        }
-        raw.chunks(21)
-            .map(Address::try_from)
-            .collect::<Result<Vec<_>, _>>()
-            .unwrap()
+        parse_address_list(raw, "ResourceDelegationIndex")
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        ResourceDelegationIndex(*Address::from_bytes(raw))
     }
 }
 
+/// Reverse index for resource delegation info, to_address => [from_address].
+#[derive(Debug)]
+pub struct ResourceDelegationReverseIndex(pub Address);
+
+impl Key<Vec<Address>> for ResourceDelegationReverseIndex {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_RESOURCE_DELEGATION_REVERSE_INDEX;
+
+    fn key(&self) -> Self::Target {
+        self.0.as_bytes().to_vec()
+    }
+
+    fn value(val: &Vec<Address>) -> Cow<[u8]> {
+        encode_address_list(val)
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<Address> {
+        parse_address_list(raw, "ResourceDelegationReverseIndex")
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        ResourceDelegationReverseIndex(*Address::from_bytes(raw))
+    }
+}
+
+/// Reverse index for Stake 2.0 resource delegation info, to_address => [from_address].
+///
+/// Locked and unlocked delegations of the same pair share one entry.
+#[derive(Debug)]
+pub struct ResourceDelegationV2ReverseIndex(pub Address);
+
+impl Key<Vec<Address>> for ResourceDelegationV2ReverseIndex {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_RESOURCE_DELEGATION_V2_REVERSE_INDEX;
+
+    fn key(&self) -> Self::Target {
+        self.0.as_bytes().to_vec()
+    }
+
+    fn value(val: &Vec<Address>) -> Cow<[u8]> {
+        encode_address_list(val)
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<Address> {
+        parse_address_list(raw, "ResourceDelegationV2ReverseIndex")
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        ResourceDelegationV2ReverseIndex(*Address::from_bytes(raw))
+    }
+}
+
+fn encode_address_list(val: &[Address]) -> Cow<[u8]> {
+    val.iter()
+        .map(|addr| addr.as_bytes())
+        .collect::<Vec<_>>()
+        .concat()
+        .into()
+}
+
+fn parse_address_list(raw: &[u8], name: &str) -> Vec<Address> {
+    if raw.len() % 21 != 0 {
+        panic!("malformed {} db", name)
+    }
+    raw.chunks(21)
+        .map(Address::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
 /// `<<Address>> => Votes { epoch: i64, votes: [Votes] }`
 #[derive(Debug)]
 pub struct Votes(pub Address);
@@ -332,6 +649,42 @@ impl Key<pb::Votes> for Votes {
     }
 }
 
+/// Votes of an account in an epoch, saved before the votes are changed in that epoch.
+/// Votes take effect from the next epoch, so the reward of this epoch is computed with the snapshot.
+/// `<<epoch: i64, Address>> => Votes { epoch: i64, votes: [Votes] }`
+#[derive(Debug)]
+pub struct VoteSnapshot(pub i64, pub Address);
+
+impl KeyPrefix<pb::Votes> for VoteSnapshot {
+    type Prefix = i64;
+
+    fn prefix(epoch: &i64) -> Vec<u8> {
+        epoch.to_be_bytes().to_vec()
+    }
+}
+
+impl Key<pb::Votes> for VoteSnapshot {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_VOTE_SNAPSHOT;
+
+    fn key(&self) -> Self::Target {
+        let mut raw = vec![0u8; 8 + 21];
+        raw[0..8].copy_from_slice(&self.0.to_be_bytes()[..]);
+        raw[8..].copy_from_slice(self.1.as_bytes());
+        raw
+    }
+
+    fn value(val: &pb::Votes) -> Cow<[u8]> {
+        let mut buf = BytesMut::with_capacity(val.encoded_len());
+        val.encode(&mut buf).unwrap();
+        Cow::from(buf.to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> pb::Votes {
+        pb::Votes::decode(raw).unwrap()
+    }
+}
+
 /// `Address => pb::SmartContract`
 #[derive(Debug)]
 pub struct Contract(pub Address);
@@ -380,6 +733,14 @@ impl Key<Vec<u8>> for ContractCode {
 #[derive(Debug)]
 pub struct ContractStorage(pub Address, pub H256);
 
+impl KeyPrefix<H256> for ContractStorage {
+    type Prefix = Address;
+
+    fn prefix(addr: &Address) -> Vec<u8> {
+        addr.as_bytes().to_vec()
+    }
+}
+
 impl Key<H256> for ContractStorage {
     type Target = Vec<u8>;
     const COL: usize = super::db::COL_CONTRACT_STORAGE;
@@ -395,6 +756,10 @@ impl Key<H256> for ContractStorage {
     fn parse_value(raw: &[u8]) -> H256 {
         H256::from_slice(raw)
     }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        ContractStorage(*Address::from_bytes(&raw[..21]), H256::from_slice(&raw[21..]))
+    }
 }
 
 #[derive(Debug)]
@@ -417,6 +782,12 @@ impl Key<pb::Proposal> for Proposal {
     fn parse_value(raw: &[u8]) -> pb::Proposal {
         pb::Proposal::decode(raw).unwrap()
     }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(raw);
+        Proposal(u64::from_be_bytes(bytes) as i64)
+    }
 }
 
 /// TRC10.
@@ -492,6 +863,308 @@ impl Key<pb::InternalTransaction> for InternalTransaction {
     }
 }
 
+/// Log bloom of a block, only for blocks with logs.
+/// `<<block_number: i64>> => LogBloom`
+#[derive(Debug)]
+pub struct LogBloom(pub i64);
+
+impl Key<bloom::LogBloom> for LogBloom {
+    type Target = [u8; 8];
+    const COL: usize = super::db::COL_LOG_BLOOM;
+
+    fn key(&self) -> Self::Target {
+        (self.0 as u64).to_be_bytes()
+    }
+
+    fn value(val: &bloom::LogBloom) -> Cow<[u8]> {
+        Cow::from(&val.0[..])
+    }
+
+    fn parse_value(raw: &[u8]) -> bloom::LogBloom {
+        bloom::LogBloom::from_slice(raw)
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(raw);
+        LogBloom(u64::from_be_bytes(bytes) as i64)
+    }
+}
+
+/// Resource usage of a block, only for blocks with transactions.
+/// `<<block_number: i64>> => BlockResourceUsage`
+#[derive(Debug)]
+pub struct BlockResourceUsage(pub i64);
+
+impl Key<pb::BlockResourceUsage> for BlockResourceUsage {
+    type Target = [u8; 8];
+    const COL: usize = super::db::COL_BLOCK_RESOURCE_USAGE;
+
+    fn key(&self) -> Self::Target {
+        (self.0 as u64).to_be_bytes()
+    }
+
+    fn value(val: &pb::BlockResourceUsage) -> Cow<[u8]> {
+        let mut buf = BytesMut::with_capacity(val.encoded_len());
+        val.encode(&mut buf).unwrap();
+        Cow::from(buf.to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> pb::BlockResourceUsage {
+        pb::BlockResourceUsage::decode(raw).unwrap()
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(raw);
+        BlockResourceUsage(u64::from_be_bytes(bytes) as i64)
+    }
+}
+
+/// TRC721/TRC1155 balance of an owner, owner, contract, token_id.
+/// `<<'b', owner, contract, token_id: H256>> => amount: H256`
+#[derive(Debug)]
+pub struct NftBalance(pub Address, pub Address, pub H256);
+
+impl NftBalance {
+    pub fn prefix_of(owner: &Address) -> Vec<u8> {
+        [&b"b"[..], owner.as_bytes()].concat()
+    }
+}
+
+impl KeyPrefix<H256> for NftBalance {
+    type Prefix = Address;
+
+    fn prefix(owner: &Address) -> Vec<u8> {
+        Self::prefix_of(owner)
+    }
+}
+
+impl Key<H256> for NftBalance {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_NFT;
+
+    fn key(&self) -> Self::Target {
+        [&b"b"[..], self.0.as_bytes(), self.1.as_bytes(), self.2.as_bytes()].concat()
+    }
+
+    fn value(val: &H256) -> Cow<[u8]> {
+        Cow::from(val.as_bytes())
+    }
+
+    fn parse_value(raw: &[u8]) -> H256 {
+        H256::from_slice(raw)
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        NftBalance(
+            *Address::from_bytes(&raw[1..22]),
+            *Address::from_bytes(&raw[22..43]),
+            H256::from_slice(&raw[43..]),
+        )
+    }
+}
+
+/// Owner of a TRC721 token, contract, token_id.
+/// `<<'o', contract, token_id: H256>> => owner`
+#[derive(Debug)]
+pub struct NftOwner(pub Address, pub H256);
+
+impl Key<Address> for NftOwner {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_NFT;
+
+    fn key(&self) -> Self::Target {
+        [&b"o"[..], self.0.as_bytes(), self.1.as_bytes()].concat()
+    }
+
+    fn value(val: &Address) -> Cow<[u8]> {
+        Cow::from(val.as_bytes())
+    }
+
+    fn parse_value(raw: &[u8]) -> Address {
+        *Address::from_bytes(raw)
+    }
+}
+
+/// A TRC721/TRC1155 transfer, contract, block_number, txn_index, log_index, batch_index.
+/// `<<'t', contract, block_number: i64, txn_index: u32, log_index: u32, batch_index: u32>> => NftTransfer`
+#[derive(Debug)]
+pub struct NftTransfer(pub Address, pub i64, pub u32, pub u32, pub u32);
+
+impl NftTransfer {
+    pub fn prefix_of(contract: &Address) -> Vec<u8> {
+        [&b"t"[..], contract.as_bytes()].concat()
+    }
+}
+
+impl KeyPrefix<pb::NftTransfer> for NftTransfer {
+    type Prefix = Address;
+
+    fn prefix(contract: &Address) -> Vec<u8> {
+        Self::prefix_of(contract)
+    }
+}
+
+impl Key<pb::NftTransfer> for NftTransfer {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_NFT;
+
+    fn key(&self) -> Self::Target {
+        let mut raw = Self::prefix_of(&self.0);
+        raw.extend_from_slice(&(self.1 as u64).to_be_bytes());
+        raw.extend_from_slice(&self.2.to_be_bytes());
+        raw.extend_from_slice(&self.3.to_be_bytes());
+        raw.extend_from_slice(&self.4.to_be_bytes());
+        raw
+    }
+
+    fn value(val: &pb::NftTransfer) -> Cow<[u8]> {
+        let mut buf = BytesMut::with_capacity(val.encoded_len());
+        val.encode(&mut buf).unwrap();
+        Cow::from(buf.to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> pb::NftTransfer {
+        pb::NftTransfer::decode(raw).unwrap()
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        NftTransfer(
+            *Address::from_bytes(&raw[1..22]),
+            BE::read_u64(&raw[22..30]) as _,
+            BE::read_u32(&raw[30..34]),
+            BE::read_u32(&raw[34..38]),
+            BE::read_u32(&raw[38..42]),
+        )
+    }
+}
+
+/// A transaction of an account, address, to_this, block_number, txn_index.
+///
+/// `to_this` is false when the account is the sender, true when the account is a receiver, the called contract or a
+/// token counterparty. Block number and index are complemented, so that the latest transactions come first.
+/// `<<Address, to_this: u8, !block_number: u64, !txn_index: u32>> => txid`
+#[derive(Debug)]
+pub struct AccountTransaction(pub Address, pub bool, pub i64, pub u32);
+
+impl AccountTransaction {
+    pub fn prefix_of(addr: &Address, to_this: bool) -> Vec<u8> {
+        [addr.as_bytes(), &[to_this as u8]].concat()
+    }
+}
+
+impl KeyPrefix<H256> for AccountTransaction {
+    /// `(address, to_this)`
+    type Prefix = (Address, bool);
+
+    fn prefix(&(addr, to_this): &(Address, bool)) -> Vec<u8> {
+        Self::prefix_of(&addr, to_this)
+    }
+}
+
+impl Key<H256> for AccountTransaction {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_ACCOUNT_TRANSACTION;
+
+    fn key(&self) -> Self::Target {
+        let mut raw = Self::prefix_of(&self.0, self.1);
+        raw.extend_from_slice(&(!(self.2 as u64)).to_be_bytes());
+        raw.extend_from_slice(&(!self.3).to_be_bytes());
+        raw
+    }
+
+    fn value(val: &H256) -> Cow<[u8]> {
+        Cow::from(val.as_bytes())
+    }
+
+    fn parse_value(raw: &[u8]) -> H256 {
+        H256::from_slice(raw)
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        AccountTransaction(
+            *Address::from_bytes(&raw[..21]),
+            raw[21] != 0,
+            !BE::read_u64(&raw[22..30]) as _,
+            !BE::read_u32(&raw[30..34]),
+        )
+    }
+}
+
+/// A shielded TRC20 note of a viewing key, key_id, contract, position.
+///
+/// `key_id` is the sha256 of the full viewing key, so that notes of a key can be listed without the key in db.
+/// `<<'n', key_id: H256, contract, position: u64>> => ShieldedNote`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShieldedNote(pub H256, pub Address, pub u64);
+
+impl ShieldedNote {
+    pub fn prefix_of(key_id: &H256) -> Vec<u8> {
+        [&b"n"[..], key_id.as_bytes()].concat()
+    }
+}
+
+impl KeyPrefix<pb::ShieldedNote> for ShieldedNote {
+    type Prefix = H256;
+
+    fn prefix(key_id: &H256) -> Vec<u8> {
+        Self::prefix_of(key_id)
+    }
+}
+
+impl Key<pb::ShieldedNote> for ShieldedNote {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_SHIELDED_NOTE;
+
+    fn key(&self) -> Self::Target {
+        let mut raw = Self::prefix_of(&self.0);
+        raw.extend_from_slice(self.1.as_bytes());
+        raw.extend_from_slice(&self.2.to_be_bytes());
+        raw
+    }
+
+    fn value(val: &pb::ShieldedNote) -> Cow<[u8]> {
+        let mut buf = BytesMut::with_capacity(val.encoded_len());
+        val.encode(&mut buf).unwrap();
+        Cow::from(buf.to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> pb::ShieldedNote {
+        pb::ShieldedNote::decode(raw).unwrap()
+    }
+
+    fn parse_key(raw: &[u8]) -> Self {
+        ShieldedNote(
+            H256::from_slice(&raw[1..33]),
+            *Address::from_bytes(&raw[33..54]),
+            BE::read_u64(&raw[54..62]),
+        )
+    }
+}
+
+/// Nullifier of a detected shielded note, to find the note when it is spent.
+/// `<<'s', nullifier: H256>> => <<key_id: H256, contract, position: u64>>`
+#[derive(Debug)]
+pub struct ShieldedNullifier(pub H256);
+
+impl Key<ShieldedNote> for ShieldedNullifier {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_SHIELDED_NOTE;
+
+    fn key(&self) -> Self::Target {
+        [&b"s"[..], self.0.as_bytes()].concat()
+    }
+
+    fn value(val: &ShieldedNote) -> Cow<[u8]> {
+        Cow::from(val.key())
+    }
+
+    fn parse_value(raw: &[u8]) -> ShieldedNote {
+        ShieldedNote::parse_key(raw)
+    }
+}
+
 #[derive(Debug)]
 pub struct TransactionLog(pub Address, Vec<H256>);
 
@@ -1,7 +1,16 @@
 pub use parameter::ChainParameter;
 pub use property::DynamicProperty;
 
+pub mod bloom;
+pub mod commitment;
 pub mod db;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
 pub mod keys;
+pub mod migration;
 pub mod parameter;
 mod property;
+pub mod reconcile;
+pub mod reindex;
+pub mod snapshot;
+pub mod stats;
@@ -0,0 +1,355 @@
+//! State-db migrations.
+//!
+//! Each migration upgrades the state layout from `version - 1` to `version`, and is applied in order on startup.
+//! The applied version is saved as `DynamicProperty::DbVersion`.
+
+use std::collections::HashMap;
+use std::io;
+use std::time::Instant;
+
+use ::keys::Address;
+use log::{info, warn};
+
+use proto2::state::{Account, ResourceDelegation};
+
+use super::db::{BoxError, StateDB, COL_ACCOUNT};
+use super::keys::{self, Key};
+use super::property::CURRENT_DB_VERSION;
+use super::{ChainParameter, DynamicProperty};
+
+/// Number of entries per layer of migrations applied in batches.
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
+/// A state-db migration.
+pub struct Migration {
+    /// Target db version after applying.
+    pub version: i64,
+    pub description: &'static str,
+    pub apply: fn(&mut StateDB) -> Result<(), BoxError>,
+}
+
+/// All migrations, ordered by version. The last one must match `CURRENT_DB_VERSION`.
+///
+/// NOTE: Never modify a released migration, add a new one instead.
+pub fn migrations() -> Vec<Migration> {
+    // Version 1 is the initial layout.
+    vec![
+        Migration {
+            version: 2,
+            description: "add MaxFeeLimit chain parameter",
+            apply: add_max_fee_limit_parameter,
+        },
+        Migration {
+            version: 3,
+            description: "split account token balances into account-asset column",
+            apply: split_account_asset,
+        },
+        Migration {
+            version: 4,
+            description: "add MemoFee chain parameter",
+            apply: add_memo_fee_parameter,
+        },
+        Migration {
+            version: 5,
+            description: "add Stake 2.0 chain parameters",
+            apply: add_stake_v2_parameters,
+        },
+        Migration {
+            version: 6,
+            description: "add AllowCancelAllUnfreezeV2 chain parameter",
+            apply: add_allow_cancel_all_unfreeze_v2_parameter,
+        },
+        Migration {
+            version: 7,
+            description: "prune zero token balances from account-asset column",
+            apply: prune_empty_account_assets,
+        },
+        Migration {
+            version: 8,
+            description: "build resource delegation reverse index",
+            apply: build_delegation_reverse_index,
+        },
+        Migration {
+            version: 9,
+            description: "build Stake 2.0 resource delegation reverse index",
+            apply: build_delegation_v2_reverse_index,
+        },
+    ]
+}
+
+fn add_max_fee_limit_parameter(db: &mut StateDB) -> Result<(), BoxError> {
+    if db.get(&ChainParameter::MaxFeeLimit)?.is_none() {
+        db.put_key(ChainParameter::MaxFeeLimit, constants::DEFAULT_MAX_FEE_LIMIT)?;
+    }
+    Ok(())
+}
+
+fn add_memo_fee_parameter(db: &mut StateDB) -> Result<(), BoxError> {
+    if db.get(&ChainParameter::MemoFee)?.is_none() {
+        db.put_key(ChainParameter::MemoFee, 0)?;
+    }
+    Ok(())
+}
+
+fn add_stake_v2_parameters(db: &mut StateDB) -> Result<(), BoxError> {
+    if db.get(&ChainParameter::UnfreezeDelayDays)?.is_none() {
+        db.put_key(ChainParameter::UnfreezeDelayDays, 0)?;
+    }
+    if db.get(&ChainParameter::MaxDelegateLockPeriod)?.is_none() {
+        db.put_key(ChainParameter::MaxDelegateLockPeriod, 86_400)?;
+    }
+    Ok(())
+}
+
+fn add_allow_cancel_all_unfreeze_v2_parameter(db: &mut StateDB) -> Result<(), BoxError> {
+    if db.get(&ChainParameter::AllowCancelAllUnfreezeV2)?.is_none() {
+        db.put_key(ChainParameter::AllowCancelAllUnfreezeV2, 0)?;
+    }
+    Ok(())
+}
+
+fn split_account_asset(db: &mut StateDB) -> Result<(), BoxError> {
+    split_account_asset_in_batches(db, MIGRATION_BATCH_SIZE)
+}
+
+/// Accounts are visited in batches, each solidified along with `MigrationCursor`, so that an interrupted migration
+/// resumes after the last solidified batch.
+fn split_account_asset_in_batches(db: &mut StateDB, batch_size: usize) -> Result<(), BoxError> {
+    let mut cursor = db.get(&keys::MigrationCursor)?;
+    if let Some(ref last_key) = cursor {
+        info!("resuming token balance split after account {}", keys::Account::parse_key(last_key).0);
+    }
+    let mut num_accounts = 0;
+    loop {
+        // The key right after the cursor, lower bounds are inclusive.
+        let start_key = cursor
+            .as_ref()
+            .map(|last_key| [&last_key[..], &[0]].concat())
+            .unwrap_or_default();
+        let batch = db.scan_solid(COL_ACCOUNT, &start_key, batch_size);
+        let last_key = match batch.last() {
+            Some((key, _)) => key.clone(),
+            None => break,
+        };
+
+        for (raw_key, raw_value) in batch {
+            let addr = keys::Account::parse_key(&raw_key).0;
+            let mut acct = <keys::Account as Key<Account>>::parse_value(&raw_value);
+            if acct.token_balance.is_empty() {
+                continue;
+            }
+            for (&token_id, &balance) in acct.token_balance.iter().filter(|(_, &balance)| balance != 0) {
+                db.put_key(keys::AccountAsset(addr, token_id), balance)?;
+            }
+            acct.token_balance.clear();
+            db.put_key(keys::Account(addr), acct)?;
+            num_accounts += 1;
+        }
+
+        db.put_key(keys::MigrationCursor, last_key.clone())?;
+        db.solidify_layer();
+        db.new_layer();
+        cursor = Some(last_key);
+        info!("split token balances of {} accounts", num_accounts);
+    }
+
+    // Removed along with the version bump.
+    db.delete_key(&keys::MigrationCursor)?;
+    Ok(())
+}
+
+/// A missing token balance reads as zero, so empty entries are pruned on write.
+fn prune_empty_account_assets(db: &mut StateDB) -> Result<(), BoxError> {
+    let mut empty_keys = vec![];
+    db.for_each(|key: &keys::AccountAsset, balance: &i64| {
+        if *balance == 0 {
+            empty_keys.push(keys::AccountAsset(key.0, key.1));
+        }
+    });
+
+    info!("pruning {} zero token balances", empty_keys.len());
+    for key in empty_keys {
+        db.delete_key(&key)?;
+    }
+    Ok(())
+}
+
+/// Reverse of `ResourceDelegationIndex`, delegators are ordered by address.
+fn build_delegation_reverse_index(db: &mut StateDB) -> Result<(), BoxError> {
+    let mut reverse_index: HashMap<Address, Vec<Address>> = HashMap::new();
+    db.for_each(|key: &keys::ResourceDelegationIndex, to_addrs: &Vec<Address>| {
+        for to in to_addrs {
+            reverse_index.entry(*to).or_default().push(key.0);
+        }
+    });
+
+    info!("indexing delegators of {} accounts", reverse_index.len());
+    for (to, from_addrs) in reverse_index {
+        db.put_key(keys::ResourceDelegationReverseIndex(to), from_addrs)?;
+    }
+    Ok(())
+}
+
+/// Reverse of `ResourceDelegationV2`, delegators are ordered by address.
+fn build_delegation_v2_reverse_index(db: &mut StateDB) -> Result<(), BoxError> {
+    let mut reverse_index: HashMap<Address, Vec<Address>> = HashMap::new();
+    db.for_each(|key: &keys::ResourceDelegationV2, _: &ResourceDelegation| {
+        let from_addrs = reverse_index.entry(key.1).or_default();
+        if !from_addrs.contains(&key.0) {
+            from_addrs.push(key.0);
+        }
+    });
+
+    info!("indexing Stake 2.0 delegators of {} accounts", reverse_index.len());
+    for (to, from_addrs) in reverse_index {
+        db.put_key(keys::ResourceDelegationV2ReverseIndex(to), from_addrs)?;
+    }
+    Ok(())
+}
+
+/// Returns migrations to be applied to a db of `from_version`.
+pub fn pending_migrations(from_version: i64) -> Result<Vec<Migration>, BoxError> {
+    if from_version > CURRENT_DB_VERSION {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "state-db version {} is newer than supported version {}, please upgrade",
+                from_version, CURRENT_DB_VERSION
+            ),
+        )));
+    }
+
+    let all = migrations();
+    let mut last_version = 1;
+    for migration in &all {
+        assert!(migration.version > last_version, "migrations must be ordered by version");
+        last_version = migration.version;
+    }
+    assert!(
+        last_version == CURRENT_DB_VERSION,
+        "last migration version must match CURRENT_DB_VERSION"
+    );
+
+    Ok(all.into_iter().filter(|m| m.version > from_version).collect())
+}
+
+/// Apply all pending migrations, returns db version after migration.
+///
+/// In dry-run mode, only the migration plan is logged.
+pub fn run_migrations(db: &mut StateDB, dry_run: bool) -> Result<i64, BoxError> {
+    let from_version = db.get(&DynamicProperty::DbVersion)?.unwrap_or(1);
+    let pending = pending_migrations(from_version)?;
+
+    if pending.is_empty() {
+        info!("state-db version {} is up to date", from_version);
+        return Ok(from_version);
+    }
+    // Writes of a secondary instance never reach the disk, migrations are left to the primary.
+    if db.is_secondary() && !dry_run {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::Other,
+            format!("state-db version {} is not migrated, migrate it with the primary", from_version),
+        )));
+    }
+
+    info!(
+        "state-db version {} => {}, {} migration(s) pending",
+        from_version,
+        CURRENT_DB_VERSION,
+        pending.len()
+    );
+
+    let total = pending.len();
+    for (i, migration) in pending.into_iter().enumerate() {
+        if dry_run {
+            info!(
+                "[{}/{}] (dry-run) migration v{}: {}",
+                i + 1,
+                total,
+                migration.version,
+                migration.description
+            );
+            continue;
+        }
+
+        info!(
+            "[{}/{}] applying migration v{}: {}",
+            i + 1,
+            total,
+            migration.version,
+            migration.description
+        );
+        let started_at = Instant::now();
+
+        // A migration and its version bump are written in one layer.
+        db.new_layer();
+        if let Err(e) = (migration.apply)(db) {
+            warn!("migration v{} failed: {}", migration.version, e);
+            db.discard_last_layer()?;
+            return Err(e);
+        }
+        db.put_key(DynamicProperty::DbVersion, migration.version)?;
+        db.solidify_layer();
+
+        info!(
+            "[{}/{}] migration v{} done in {:?}",
+            i + 1,
+            total,
+            migration.version,
+            started_at.elapsed()
+        );
+    }
+
+    if dry_run {
+        Ok(from_version)
+    } else {
+        Ok(CURRENT_DB_VERSION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_address(i: u8) -> Address {
+        let mut raw = [0u8; 21];
+        raw[0] = 0x41;
+        raw[20] = i;
+        *Address::from_bytes(&raw)
+    }
+
+    #[test]
+    fn test_split_account_asset_in_batches() {
+        let dir = std::env::temp_dir().join(format!("opentron-migration-test-{}", std::process::id()));
+        {
+            let mut db = StateDB::new(&dir);
+            db.new_layer();
+            for i in 0..5 {
+                let mut acct = Account::default();
+                acct.token_balance.insert(1_000_001, 100 + i as i64);
+                db.put_key(keys::Account(test_address(i)), acct).unwrap();
+            }
+            // As if the first 3 accounts were split by an interrupted run.
+            db.put_key(keys::MigrationCursor, test_address(2).as_bytes().to_vec()).unwrap();
+            db.solidify_layer();
+
+            db.new_layer();
+            split_account_asset_in_batches(&mut db, 2).unwrap();
+            db.solidify_layer();
+
+            for i in 0..5 {
+                let acct = db.must_get(&keys::Account(test_address(i)));
+                let balance = db.get(&keys::AccountAsset(test_address(i), 1_000_001)).unwrap();
+                if i <= 2 {
+                    assert!(!acct.token_balance.is_empty());
+                    assert_eq!(balance, None);
+                } else {
+                    assert!(acct.token_balance.is_empty());
+                    assert_eq!(balance, Some(100 + i as i64));
+                }
+            }
+            assert_eq!(db.get(&keys::MigrationCursor).unwrap(), None);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -11,6 +11,8 @@ pub fn default_parameters() -> impl IntoIterator<Item = (ChainParameter, i64)> {
     return vec![
         (MaintenanceInterval, 21600_000),
         (MaxCpuTimeOfOneTxn, 50),
+        (MaxFeeLimit, constants::DEFAULT_MAX_FEE_LIMIT),
+        (MemoFee, 0),
         (RemovePowerOfGr, 0),
         (AllowUpdateAccountName, 0),
         (AllowSameTokenName, 0),
@@ -20,14 +22,14 @@ pub fn default_parameters() -> impl IntoIterator<Item = (ChainParameter, i64)> {
         (AllowChangeDelegation, 0),
         (AllowTvm, 0),
         (ForbidTransferToContract, 0),
-        (BandwidthFee, 10),
-        (EnergyFee, 100),
-        (WitnessCreateFee, 9999_000_000),
-        (AccountCreateFee, 100_000),
-        (AccountPermissionUpdateFee, 100_000_000),
-        (AssetIssueFee, 1024_000_000),
-        (ExchangeCreateFee, 1024_000_000),
-        (MultisigFee, 1_000_000),
+        (BandwidthFee, constants::DEFAULT_BANDWIDTH_FEE),
+        (EnergyFee, constants::DEFAULT_ENERGY_FEE),
+        (WitnessCreateFee, constants::DEFAULT_WITNESS_CREATE_FEE),
+        (AccountCreateFee, constants::DEFAULT_ACCOUNT_CREATE_FEE),
+        (AccountPermissionUpdateFee, constants::DEFAULT_ACCOUNT_PERMISSION_UPDATE_FEE),
+        (AssetIssueFee, constants::DEFAULT_ASSET_ISSUE_FEE),
+        (ExchangeCreateFee, constants::DEFAULT_EXCHANGE_CREATE_FEE),
+        (MultisigFee, constants::DEFAULT_MULTISIG_FEE),
         (CreateNewAccountFeeInSystemContract, 0),
         (CreateNewAccountBandwidthRate, 1),
         (TotalEnergyLimit, 50_000_000_000),
@@ -45,6 +47,9 @@ pub fn default_parameters() -> impl IntoIterator<Item = (ChainParameter, i64)> {
         (AllowTvmSolidity059Upgrade, 0),
         (AllowTvmShieldedUpgrade, 0),
         (AllowProtoFilterNum, 0),
+        (UnfreezeDelayDays, 0),
+        (MaxDelegateLockPeriod, 86_400),
+        (AllowCancelAllUnfreezeV2, 0),
     ];
 }
 
@@ -56,6 +61,8 @@ pub fn default_parameters_from_config(
     return vec![
         (MaintenanceInterval, config.maintenance_interval),
         (MaxCpuTimeOfOneTxn, 50),
+        (MaxFeeLimit, constants::DEFAULT_MAX_FEE_LIMIT),
+        (MemoFee, 0),
         (RemovePowerOfGr, 0),
         (AllowUpdateAccountName, 0),
         (AllowSameTokenName, config.allow_duplicate_asset_names as i64),
@@ -65,14 +72,14 @@ pub fn default_parameters_from_config(
         (AllowChangeDelegation, 0),
         (AllowTvm, config.allow_tvm as i64),
         (ForbidTransferToContract, 0),
-        (BandwidthFee, 10),
+        (BandwidthFee, constants::DEFAULT_BANDWIDTH_FEE),
         (EnergyFee, config.energy_fee),
-        (WitnessCreateFee, 9999_000_000),
-        (AccountCreateFee, 100_000),
-        (AccountPermissionUpdateFee, 100_000_000),
-        (AssetIssueFee, 1024_000_000),
-        (ExchangeCreateFee, 1024_000_000),
-        (MultisigFee, 1_000_000),
+        (WitnessCreateFee, constants::DEFAULT_WITNESS_CREATE_FEE),
+        (AccountCreateFee, constants::DEFAULT_ACCOUNT_CREATE_FEE),
+        (AccountPermissionUpdateFee, constants::DEFAULT_ACCOUNT_PERMISSION_UPDATE_FEE),
+        (AssetIssueFee, constants::DEFAULT_ASSET_ISSUE_FEE),
+        (ExchangeCreateFee, constants::DEFAULT_EXCHANGE_CREATE_FEE),
+        (MultisigFee, constants::DEFAULT_MULTISIG_FEE),
         (CreateNewAccountFeeInSystemContract, 0),
         (CreateNewAccountBandwidthRate, 1),
         (TotalEnergyLimit, 50_000_000_000),
@@ -95,5 +102,8 @@ pub fn default_parameters_from_config(
         (AllowTvmSolidity059Upgrade, config.allow_tvm_solidity_059_upgrade as i64),
         (AllowTvmShieldedUpgrade, config.allow_tvm_shielded_upgrade as i64),
         (AllowProtoFilterNum, 0),
+        (UnfreezeDelayDays, 0),
+        (MaxDelegateLockPeriod, 86_400),
+        (AllowCancelAllUnfreezeV2, 0),
     ];
 }
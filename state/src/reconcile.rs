@@ -0,0 +1,260 @@
+//! Reconcile account fields derived from other columns, i.e. delegated amounts, votes and witness vote counts.
+//!
+//! Stored values might drift from their sources while resource and vote actuators are still maturing. Each check
+//! recomputes expected values from source columns, reports mismatched accounts, and writes fixes unless in dry-run
+//! mode.
+
+use std::collections::HashMap;
+
+use ::keys::Address;
+use config::genesis::GenesisConfig;
+use log::warn;
+use proto2::common::AccountType;
+use proto2::state::{Account, ResourceDelegation, Votes, Witness};
+
+use super::db::{BoxError, StateDB};
+use super::keys;
+
+/// A stored value which differs from the recomputed one.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub address: Address,
+    pub field: &'static str,
+    pub stored: i64,
+    pub expected: i64,
+}
+
+/// Mismatches found when reconciling.
+#[derive(Debug, Default)]
+pub struct ReconcileResult {
+    /// Number of source entries checked.
+    pub checked: u64,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ReconcileResult {
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn check(&mut self, address: Address, field: &'static str, stored: i64, expected: i64) -> bool {
+        if stored != expected {
+            warn!("{} of {} is {}, expected {}", field, address, stored, expected);
+            self.mismatches.push(Mismatch {
+                address,
+                field,
+                stored,
+                expected,
+            });
+            return false;
+        }
+        true
+    }
+}
+
+/// Write fixes in a new layer, and solidify it.
+fn apply_fixes<F>(db: &mut StateDB, dry_run: bool, result: &ReconcileResult, fix: F) -> Result<(), BoxError>
+where
+    F: FnOnce(&mut StateDB) -> Result<(), BoxError>,
+{
+    if dry_run || result.is_consistent() {
+        return Ok(());
+    }
+    db.new_layer();
+    fix(db)?;
+    db.solidify_layer();
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct DelegatedAmounts {
+    out: i64,
+    in_bandwidth: i64,
+    in_energy: i64,
+    v2_out_bandwidth: i64,
+    v2_out_energy: i64,
+    v2_in_bandwidth: i64,
+    v2_in_energy: i64,
+}
+
+/// Recompute delegated amounts of accounts, from `ResourceDelegation`s and `ResourceDelegationV2`s.
+///
+/// Delegated amounts of Constantinople contract receivers might be cleared while the delegation record is kept,
+/// so a smaller stored amount of a contract receiver is accepted.
+pub fn reconcile_delegations(db: &mut StateDB, dry_run: bool) -> Result<ReconcileResult, BoxError> {
+    let mut result = ReconcileResult::default();
+
+    let mut expected: HashMap<Address, DelegatedAmounts> = HashMap::new();
+    db.for_each(|key: &keys::ResourceDelegation, del: &ResourceDelegation| {
+        result.checked += 1;
+        expected.entry(key.0).or_default().out += del.amount_for_bandwidth + del.amount_for_energy;
+        let to = expected.entry(key.1).or_default();
+        to.in_bandwidth += del.amount_for_bandwidth;
+        to.in_energy += del.amount_for_energy;
+    });
+    db.for_each(|key: &keys::ResourceDelegationV2, del: &ResourceDelegation| {
+        result.checked += 1;
+        let from = expected.entry(key.0).or_default();
+        from.v2_out_bandwidth += del.amount_for_bandwidth;
+        from.v2_out_energy += del.amount_for_energy;
+        let to = expected.entry(key.1).or_default();
+        to.v2_in_bandwidth += del.amount_for_bandwidth;
+        to.v2_in_energy += del.amount_for_energy;
+    });
+
+    let mut accounts = vec![];
+    db.for_each(|key: &keys::Account, acct: &Account| {
+        let has_delegation = acct.delegated_out_amount != 0 ||
+            acct.delegated_frozen_amount_for_bandwidth != 0 ||
+            acct.delegated_frozen_amount_for_energy != 0 ||
+            acct.delegated_v2_out_amount_for_bandwidth != 0 ||
+            acct.delegated_v2_out_amount_for_energy != 0 ||
+            acct.delegated_v2_frozen_amount_for_bandwidth != 0 ||
+            acct.delegated_v2_frozen_amount_for_energy != 0;
+        if has_delegation || expected.contains_key(&key.0) {
+            accounts.push((key.0, acct.clone()));
+        }
+    });
+
+    let mut updates = vec![];
+    for (addr, mut acct) in accounts {
+        let amounts = expected.remove(&addr).unwrap_or_default();
+        let is_contract = acct.r#type == AccountType::Contract as i32;
+        let n = result.mismatches.len();
+
+        result.check(addr, "delegated_out_amount", acct.delegated_out_amount, amounts.out);
+        acct.delegated_out_amount = amounts.out;
+        if !is_contract || acct.delegated_frozen_amount_for_bandwidth > amounts.in_bandwidth {
+            result.check(
+                addr,
+                "delegated_frozen_amount_for_bandwidth",
+                acct.delegated_frozen_amount_for_bandwidth,
+                amounts.in_bandwidth,
+            );
+            acct.delegated_frozen_amount_for_bandwidth = amounts.in_bandwidth;
+        }
+        if !is_contract || acct.delegated_frozen_amount_for_energy > amounts.in_energy {
+            result.check(
+                addr,
+                "delegated_frozen_amount_for_energy",
+                acct.delegated_frozen_amount_for_energy,
+                amounts.in_energy,
+            );
+            acct.delegated_frozen_amount_for_energy = amounts.in_energy;
+        }
+        result.check(
+            addr,
+            "delegated_v2_out_amount_for_bandwidth",
+            acct.delegated_v2_out_amount_for_bandwidth,
+            amounts.v2_out_bandwidth,
+        );
+        acct.delegated_v2_out_amount_for_bandwidth = amounts.v2_out_bandwidth;
+        result.check(
+            addr,
+            "delegated_v2_out_amount_for_energy",
+            acct.delegated_v2_out_amount_for_energy,
+            amounts.v2_out_energy,
+        );
+        acct.delegated_v2_out_amount_for_energy = amounts.v2_out_energy;
+        result.check(
+            addr,
+            "delegated_v2_frozen_amount_for_bandwidth",
+            acct.delegated_v2_frozen_amount_for_bandwidth,
+            amounts.v2_in_bandwidth,
+        );
+        acct.delegated_v2_frozen_amount_for_bandwidth = amounts.v2_in_bandwidth;
+        result.check(
+            addr,
+            "delegated_v2_frozen_amount_for_energy",
+            acct.delegated_v2_frozen_amount_for_energy,
+            amounts.v2_in_energy,
+        );
+        acct.delegated_v2_frozen_amount_for_energy = amounts.v2_in_energy;
+
+        if result.mismatches.len() > n {
+            updates.push((addr, acct));
+        }
+    }
+    // Delegations of non-existent accounts can't be fixed here.
+    for addr in expected.keys() {
+        warn!("delegation of non-existent account {}", addr);
+    }
+
+    apply_fixes(db, dry_run, &result, |db| {
+        for (addr, acct) in updates {
+            db.put_key(keys::Account(addr), acct)?;
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Recompute votes of accounts against their TRON Power, and vote counts of witnesses from `Votes`.
+///
+/// Votes exceeding TRON Power are cleared, as the unfreeze actuators do. Vote counts from genesis config are
+/// included until RemovePowerOfGr is executed. Run after `reconcile_delegations`, since TRON Power counts delegated
+/// amounts.
+///
+/// NOTE: Witness schedule is not touched, it's refreshed in the next maintenance with new votes.
+pub fn reconcile_votes(db: &mut StateDB, genesis: &GenesisConfig, dry_run: bool) -> Result<ReconcileResult, BoxError> {
+    let mut result = ReconcileResult::default();
+
+    let mut expected_vote_counts: HashMap<Address, i64> = HashMap::new();
+    if db.get(&keys::ChainParameter::RemovePowerOfGr)? != Some(-1) {
+        for gr_wit in &genesis.witnesses {
+            let addr = gr_wit.address.parse::<Address>()?;
+            *expected_vote_counts.entry(addr).or_default() += gr_wit.votes;
+        }
+    }
+
+    let mut all_votes = vec![];
+    db.for_each(|key: &keys::Votes, votes: &Votes| {
+        all_votes.push((key.0, votes.clone()));
+    });
+
+    let mut cleared = vec![];
+    for (addr, votes) in all_votes {
+        result.checked += 1;
+        let total_votes: i64 = votes.votes.iter().map(|vote| vote.vote_count).sum();
+        let tron_power = db.get(&keys::Account(addr))?.map(|acct| acct.tron_power()).unwrap_or(0);
+        if total_votes > tron_power {
+            warn!("votes of {} exceed TRON Power, votes={} tp={}", addr, total_votes, tron_power);
+            result.mismatches.push(Mismatch {
+                address: addr,
+                field: "votes",
+                stored: total_votes,
+                expected: 0,
+            });
+            cleared.push(addr);
+            continue;
+        }
+        for vote in &votes.votes {
+            let wit_addr = *Address::from_bytes(&vote.vote_address);
+            *expected_vote_counts.entry(wit_addr).or_default() += vote.vote_count;
+        }
+    }
+
+    let mut updates = vec![];
+    for (key, mut wit) in db.iter::<Witness, keys::Witness>() {
+        let addr = key.0;
+        let expected = expected_vote_counts.remove(&addr).unwrap_or(0);
+        if !result.check(addr, "witness.vote_count", wit.vote_count, expected) {
+            wit.vote_count = expected;
+            updates.push((addr, wit));
+        }
+    }
+    for addr in expected_vote_counts.keys() {
+        warn!("votes for non-existent witness {}", addr);
+    }
+
+    apply_fixes(db, dry_run, &result, |db| {
+        for addr in cleared {
+            db.delete_key(&keys::Votes(addr))?;
+        }
+        for (addr, wit) in updates {
+            db.put_key(keys::Witness(addr), wit)?;
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
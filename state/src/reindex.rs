@@ -0,0 +1,262 @@
+//! Rebuild secondary indexes of state-db from their source columns.
+//!
+//! Indexes might be inconsistent after a crash, or missing when an index is introduced to an existing db. Each
+//! rebuild verifies existing entries, reports inconsistencies, and writes fixes unless in dry-run mode.
+
+use std::collections::HashMap;
+
+use ::keys::Address;
+use log::warn;
+use proto2::state::{Account, Asset, ResourceDelegation};
+
+use super::db::{BoxError, StateDB};
+use super::keys;
+
+/// Inconsistencies found when rebuilding an index.
+#[derive(Debug, Default)]
+pub struct ReindexResult {
+    /// Number of source entries checked.
+    pub checked: u64,
+    /// Index entries absent for a source entry.
+    pub missing: u64,
+    /// Index entries pointing to a wrong source entry.
+    pub mismatched: u64,
+    /// Index entries without any source entry.
+    pub stale: u64,
+}
+
+impl ReindexResult {
+    pub fn is_consistent(&self) -> bool {
+        self.missing == 0 && self.mismatched == 0 && self.stale == 0
+    }
+}
+
+/// Write fixes in a new layer, and solidify it.
+fn apply_fixes<F>(db: &mut StateDB, dry_run: bool, result: &ReindexResult, fix: F) -> Result<(), BoxError>
+where
+    F: FnOnce(&mut StateDB) -> Result<(), BoxError>,
+{
+    if dry_run || result.is_consistent() {
+        return Ok(());
+    }
+    db.new_layer();
+    fix(db)?;
+    db.solidify_layer();
+    Ok(())
+}
+
+/// Rebuild `AccountIndex`, account name => address.
+///
+/// Old names of a renamed account(after AllowUpdateAccountName) are kept in the index as java-tron does, so only
+/// entries pointing to non-existent accounts are stale.
+pub fn reindex_account_names(db: &mut StateDB, dry_run: bool) -> Result<ReindexResult, BoxError> {
+    let mut result = ReindexResult::default();
+
+    let mut named_accounts = vec![];
+    db.for_each(|key: &keys::Account, acct: &Account| {
+        if !acct.name.is_empty() {
+            named_accounts.push((acct.name.clone(), key.0));
+        }
+    });
+
+    let mut missing = vec![];
+    for (name, addr) in named_accounts {
+        result.checked += 1;
+        if db.get(&keys::AccountIndex(name.clone()))?.is_none() {
+            warn!("account name {:?} of {} is not indexed", name, addr);
+            missing.push((name, addr));
+        }
+    }
+    result.missing = missing.len() as u64;
+
+    let mut indexed = vec![];
+    db.for_each(|key: &keys::AccountIndex, addr: &Address| {
+        indexed.push((key.0.clone(), *addr));
+    });
+    let mut stale = vec![];
+    for (name, addr) in indexed {
+        if db.get(&keys::Account(addr))?.is_none() {
+            warn!("account name {:?} is indexed to non-existent account {}", name, addr);
+            stale.push(name);
+        }
+    }
+    result.stale = stale.len() as u64;
+
+    apply_fixes(db, dry_run, &result, |db| {
+        for (name, addr) in missing {
+            db.put_key(keys::AccountIndex(name), addr)?;
+        }
+        for name in stale {
+            db.delete_key(&keys::AccountIndex(name))?;
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Rebuild `ResourceDelegationIndex`, from_address => [to_address], from non-empty `ResourceDelegation`s.
+///
+/// Order of existing entries is kept, missing ones are appended.
+pub fn reindex_delegations(db: &mut StateDB, dry_run: bool) -> Result<ReindexResult, BoxError> {
+    let mut result = ReindexResult::default();
+
+    let mut expected: HashMap<Address, Vec<Address>> = HashMap::new();
+    db.for_each(|key: &keys::ResourceDelegation, del: &ResourceDelegation| {
+        result.checked += 1;
+        if del.amount_for_bandwidth != 0 || del.amount_for_energy != 0 {
+            expected.entry(key.0).or_default().push(key.1);
+        }
+    });
+
+    let indexed: HashMap<Address, Vec<Address>> = db
+        .iter::<_, keys::ResourceDelegationIndex>()
+        .map(|(key, to_addrs)| (key.0, to_addrs))
+        .collect();
+    let updates = rebuild_address_lists("delegation", &indexed, expected, &mut result);
+
+    apply_fixes(db, dry_run, &result, |db| {
+        for (from, to_addrs) in updates {
+            if to_addrs.is_empty() {
+                db.delete_key(&keys::ResourceDelegationIndex(from))?;
+            } else {
+                db.put_key(keys::ResourceDelegationIndex(from), to_addrs)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Rebuild `ResourceDelegationReverseIndex`, to_address => [from_address], from `ResourceDelegationIndex`.
+///
+/// Run after `reindex_delegations`, in dry-run mode inconsistencies of the forward index are not accounted.
+pub fn reindex_delegation_reverse_index(db: &mut StateDB, dry_run: bool) -> Result<ReindexResult, BoxError> {
+    let mut result = ReindexResult::default();
+
+    let mut expected: HashMap<Address, Vec<Address>> = HashMap::new();
+    db.for_each(|key: &keys::ResourceDelegationIndex, to_addrs: &Vec<Address>| {
+        for to in to_addrs {
+            result.checked += 1;
+            expected.entry(*to).or_default().push(key.0);
+        }
+    });
+
+    let indexed: HashMap<Address, Vec<Address>> = db
+        .iter::<_, keys::ResourceDelegationReverseIndex>()
+        .map(|(key, from_addrs)| (key.0, from_addrs))
+        .collect();
+    let updates = rebuild_address_lists("delegator", &indexed, expected, &mut result);
+
+    apply_fixes(db, dry_run, &result, |db| {
+        for (to, from_addrs) in updates {
+            if from_addrs.is_empty() {
+                db.delete_key(&keys::ResourceDelegationReverseIndex(to))?;
+            } else {
+                db.put_key(keys::ResourceDelegationReverseIndex(to), from_addrs)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Rebuild `ResourceDelegationV2ReverseIndex`, to_address => [from_address], from `ResourceDelegationV2`s.
+pub fn reindex_delegation_v2_reverse_index(db: &mut StateDB, dry_run: bool) -> Result<ReindexResult, BoxError> {
+    let mut result = ReindexResult::default();
+
+    let mut expected: HashMap<Address, Vec<Address>> = HashMap::new();
+    db.for_each(|key: &keys::ResourceDelegationV2, _: &ResourceDelegation| {
+        result.checked += 1;
+        let from_addrs = expected.entry(key.1).or_default();
+        if !from_addrs.contains(&key.0) {
+            from_addrs.push(key.0);
+        }
+    });
+
+    let indexed: HashMap<Address, Vec<Address>> = db
+        .iter::<_, keys::ResourceDelegationV2ReverseIndex>()
+        .map(|(key, from_addrs)| (key.0, from_addrs))
+        .collect();
+    let updates = rebuild_address_lists("Stake 2.0 delegator", &indexed, expected, &mut result);
+
+    apply_fixes(db, dry_run, &result, |db| {
+        for (to, from_addrs) in updates {
+            if from_addrs.is_empty() {
+                db.delete_key(&keys::ResourceDelegationV2ReverseIndex(to))?;
+            } else {
+                db.put_key(keys::ResourceDelegationV2ReverseIndex(to), from_addrs)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Compare indexed address lists with expected ones, returns lists to be updated, empty ones to be deleted.
+///
+/// Order of existing entries is kept, missing ones are appended.
+fn rebuild_address_lists(
+    what: &str,
+    indexed: &HashMap<Address, Vec<Address>>,
+    mut expected: HashMap<Address, Vec<Address>>,
+    result: &mut ReindexResult,
+) -> Vec<(Address, Vec<Address>)> {
+    let mut updates: Vec<(Address, Vec<Address>)> = vec![];
+    for (key, addrs) in indexed {
+        let expected_addrs = expected.remove(key).unwrap_or_default();
+
+        let mut rebuilt: Vec<Address> = vec![];
+        for addr in addrs {
+            if !expected_addrs.contains(addr) {
+                warn!("{} {} => {} is indexed but empty", what, key, addr);
+                result.stale += 1;
+            } else if rebuilt.contains(addr) {
+                warn!("{} {} => {} is indexed more than once", what, key, addr);
+                result.mismatched += 1;
+            } else {
+                rebuilt.push(*addr);
+            }
+        }
+        for addr in expected_addrs {
+            if !rebuilt.contains(&addr) {
+                warn!("{} {} => {} is not indexed", what, key, addr);
+                result.missing += 1;
+                rebuilt.push(addr);
+            }
+        }
+
+        if &rebuilt != addrs {
+            updates.push((*key, rebuilt));
+        }
+    }
+    // Keys not indexed at all.
+    for (key, addrs) in expected {
+        for addr in &addrs {
+            warn!("{} {} => {} is not indexed", what, key, addr);
+        }
+        result.missing += addrs.len() as u64;
+        updates.push((key, addrs));
+    }
+    updates
+}
+
+/// Verify asset names used by legacy asset lookups.
+///
+/// Asset names are resolved by scanning assets with an in-memory cache, there is no persisted index to rebuild.
+/// Before AllowSameTokenName, names must be unique, duplicates are reported as mismatched.
+pub fn check_asset_names(db: &StateDB) -> Result<ReindexResult, BoxError> {
+    let mut result = ReindexResult::default();
+    if db.get(&keys::ChainParameter::AllowSameTokenName)? == Some(1) {
+        return Ok(result);
+    }
+
+    let mut names: HashMap<String, i64> = HashMap::new();
+    db.for_each(|key: &keys::Asset, asset: &Asset| {
+        result.checked += 1;
+        if let Some(token_id) = names.insert(asset.name.clone(), key.0) {
+            warn!("asset name {:?} is used by both {} and {}", asset.name, token_id, key.0);
+            result.mismatched += 1;
+        }
+    });
+    Ok(result)
+}
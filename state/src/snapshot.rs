@@ -0,0 +1,128 @@
+//! State snapshots, used to sync state between OpenTron nodes without replaying all blocks.
+//!
+//! A snapshot is a list of chunks, each covering a key range of a column, anchored to a solidified block.
+//! See docs/state-sync.md.
+
+use std::io;
+
+use primitive_types::H256;
+use proto2::channel::{state_chunk::Entry as StateChunkEntry, StateChunk, StateChunkInfo, StateSnapshotManifest};
+use proto2::common::BlockId;
+
+use super::db::{BoxError, StateDB};
+use super::DynamicProperty;
+
+/// Max number of key-values in a chunk.
+pub const SNAPSHOT_CHUNK_SIZE: usize = 10_000;
+
+/// Hash of key-values in a chunk.
+///
+/// `sha256(concat(len(key): u32_be, key, len(value): u32_be, value) for all entries)`
+pub fn chunk_hash<'a, I: IntoIterator<Item = (&'a [u8], &'a [u8])>>(entries: I) -> H256 {
+    let mut raw = Vec::with_capacity(64 * 1024);
+    for (key, value) in entries {
+        raw.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        raw.extend_from_slice(key);
+        raw.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        raw.extend_from_slice(value);
+    }
+    crypto::sha256(&raw)
+}
+
+/// Root hash of a snapshot, `sha256(concat(chunk hashes))`.
+pub fn root_hash(chunks: &[StateChunkInfo]) -> H256 {
+    let raw = chunks.iter().map(|chunk| &chunk.hash[..]).collect::<Vec<_>>().concat();
+    crypto::sha256(&raw)
+}
+
+/// Build the snapshot manifest of solidified state.
+///
+/// The caller must ensure there's no pending layer, so that `block_id` is the latest solidified block.
+pub fn build_manifest(db: &StateDB, block_id: BlockId) -> Result<StateSnapshotManifest, BoxError> {
+    let mut chunks = vec![];
+
+    for col in 0..db.num_columns() {
+        let mut start_key: Vec<u8> = vec![];
+        loop {
+            // Fetch one more entry as the exclusive end key.
+            let mut entries = db.scan_solid(col, &start_key, SNAPSHOT_CHUNK_SIZE + 1);
+            if entries.is_empty() {
+                break;
+            }
+            let end_key = if entries.len() > SNAPSHOT_CHUNK_SIZE {
+                entries.pop().map(|(key, _)| key).unwrap()
+            } else {
+                vec![]
+            };
+            let hash = chunk_hash(entries.iter().map(|(k, v)| (&k[..], &v[..])));
+            chunks.push(StateChunkInfo {
+                column: col as u32,
+                start_key: start_key.clone(),
+                end_key: end_key.clone(),
+                num_entries: entries.len() as u32,
+                hash: hash.as_bytes().to_vec(),
+            });
+            if end_key.is_empty() {
+                break;
+            }
+            start_key = end_key;
+        }
+    }
+
+    let db_version = db.get(&DynamicProperty::DbVersion)?.unwrap_or(1);
+    Ok(StateSnapshotManifest {
+        block_id: Some(block_id),
+        db_version: db_version as u32,
+        root_hash: root_hash(&chunks).as_bytes().to_vec(),
+        chunks,
+    })
+}
+
+/// Read a chunk described by the manifest.
+pub fn read_chunk(db: &StateDB, block_id: &BlockId, info: &StateChunkInfo) -> StateChunk {
+    let entries = db
+        .scan_solid(info.column as usize, &info.start_key, info.num_entries as usize)
+        .into_iter()
+        .map(|(key, value)| StateChunkEntry { key, value })
+        .collect();
+    StateChunk {
+        block_id: Some(block_id.clone()),
+        column: info.column,
+        start_key: info.start_key.clone(),
+        entries,
+    }
+}
+
+/// Verify a received chunk against its info in the manifest.
+pub fn verify_chunk(info: &StateChunkInfo, chunk: &StateChunk) -> bool {
+    if chunk.column != info.column || chunk.start_key != info.start_key {
+        return false;
+    }
+    if chunk.entries.len() != info.num_entries as usize {
+        return false;
+    }
+    let in_range = chunk.entries.iter().all(|entry| {
+        entry.key >= info.start_key && (info.end_key.is_empty() || entry.key < info.end_key)
+    });
+    if !in_range {
+        return false;
+    }
+    let hash = chunk_hash(chunk.entries.iter().map(|e| (&e.key[..], &e.value[..])));
+    hash.as_bytes() == &info.hash[..]
+}
+
+/// Write a verified chunk to state-db.
+pub fn apply_chunk(db: &mut StateDB, info: &StateChunkInfo, chunk: &StateChunk) -> Result<(), BoxError> {
+    if !verify_chunk(info, chunk) {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "state chunk verification failed",
+        )));
+    }
+    db.new_layer();
+    for entry in &chunk.entries {
+        db.put_raw(chunk.column as usize, &entry.key, &entry.value)?;
+    }
+    db.solidify_layer();
+    Ok(())
+}
@@ -0,0 +1,276 @@
+//! Size statistics of accounts, and the rich list.
+//!
+//! Accounts holding many TRC10 tokens, i.e. exchanges, are written by most of their transactions. The report helps
+//! to find them, and to check that no empty token balance is left in the account-asset column.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use ::keys::Address;
+use proto2::state::Account;
+
+use super::db::StateDB;
+use super::keys::{self, Key};
+
+/// Upper bounds of asset count buckets, the last bucket is unbounded.
+pub const ASSET_COUNT_BUCKETS: [usize; 4] = [0, 1, 9, 99];
+
+/// Size of an account, including its token balances.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AccountSize {
+    /// Serialized size of the account and its account-asset entries, in bytes.
+    pub size: usize,
+    pub num_assets: usize,
+    pub address: Address,
+}
+
+#[derive(Debug, Default)]
+pub struct AccountStats {
+    pub num_accounts: u64,
+    pub num_asset_entries: u64,
+    /// Token balances of zero, should be pruned on write.
+    pub num_empty_asset_entries: u64,
+    /// Serialized size of all accounts and account-asset entries, in bytes.
+    pub total_size: u64,
+    /// Number of accounts by asset count, bucketed by `ASSET_COUNT_BUCKETS`.
+    pub by_asset_count: [u64; 5],
+    /// Biggest accounts, by size in descending order.
+    pub biggest: Vec<AccountSize>,
+}
+
+impl AccountStats {
+    fn add(&mut self, account: AccountSize, top: usize, heap: &mut BinaryHeap<Reverse<AccountSize>>) {
+        self.num_accounts += 1;
+        self.total_size += account.size as u64;
+        let bucket = ASSET_COUNT_BUCKETS
+            .iter()
+            .position(|&upper| account.num_assets <= upper)
+            .unwrap_or(ASSET_COUNT_BUCKETS.len());
+        self.by_asset_count[bucket] += 1;
+
+        if top == 0 {
+            return;
+        }
+        if heap.len() < top {
+            heap.push(Reverse(account));
+        } else if heap.peek().map(|smallest| account > smallest.0).unwrap_or(false) {
+            heap.pop();
+            heap.push(Reverse(account));
+        }
+    }
+}
+
+/// Collect size statistics of all accounts, with the `top` biggest ones.
+pub fn account_stats(db: &StateDB, top: usize) -> AccountStats {
+    let mut stats = AccountStats::default();
+
+    // address => (number of assets, size of account-asset entries)
+    let mut assets: HashMap<Address, (usize, usize)> = HashMap::new();
+    db.for_each(|key: &keys::AccountAsset, balance: &i64| {
+        stats.num_asset_entries += 1;
+        if *balance == 0 {
+            stats.num_empty_asset_entries += 1;
+        }
+        let entry = assets.entry(key.0).or_default();
+        entry.0 += 1;
+        entry.1 += key.key().len() + keys::AccountAsset::value(balance).len();
+    });
+
+    let mut heap = BinaryHeap::with_capacity(top + 1);
+    db.for_each(|key: &keys::Account, acct: &Account| {
+        let (num_assets, assets_size) = assets.get(&key.0).cloned().unwrap_or_default();
+        let account = AccountSize {
+            size: key.key().len() + keys::Account::value(acct).len() + assets_size,
+            num_assets,
+            address: key.0,
+        };
+        stats.add(account, top, &mut heap);
+    });
+
+    stats.biggest = heap.into_sorted_vec().into_iter().map(|Reverse(account)| account).collect();
+    stats
+}
+
+/// An account of the rich list, amounts in SUN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RichAccount {
+    pub address: Address,
+    pub balance: i64,
+    /// Frozen by Stake 1.0 and 2.0, including amounts delegated out and pending unfreezes.
+    pub frozen: i64,
+    /// Non-zero token balances, by token id.
+    pub assets: Vec<(i64, i64)>,
+}
+
+impl RichAccount {
+    /// Without token balances.
+    pub fn new(address: Address, acct: &Account) -> Self {
+        RichAccount {
+            address,
+            balance: acct.balance,
+            frozen: acct.frozen_amount_for_bandwidth +
+                acct.frozen_amount_for_energy +
+                acct.delegated_out_amount +
+                acct.frozen_v2_amount_for_bandwidth +
+                acct.frozen_v2_amount_for_energy +
+                acct.delegated_v2_out_amount_for_bandwidth +
+                acct.delegated_v2_out_amount_for_energy +
+                acct.unfrozen_v2.iter().map(|unfreeze| unfreeze.amount).sum::<i64>(),
+            assets: vec![],
+        }
+    }
+
+    /// TRX owned, balance plus frozen.
+    pub fn total(&self) -> i64 {
+        self.balance + self.frozen
+    }
+
+    /// Order of the rich list, richest first, then by address.
+    pub fn rank(&self) -> (Reverse<i64>, Address) {
+        (Reverse(self.total()), self.address)
+    }
+}
+
+/// The first `limit` accounts by `RichAccount::rank`, in a bounded heap, so that the memory used does not grow with
+/// the number of accounts scanned.
+pub struct RichList {
+    limit: usize,
+    /// Max-heap of rank, the last entry of the list on top.
+    heap: BinaryHeap<Ranked>,
+}
+
+struct Ranked(RichAccount);
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.rank() == other.0.rank()
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.rank().cmp(&other.0.rank())
+    }
+}
+
+impl RichList {
+    pub fn new(limit: usize) -> Self {
+        RichList {
+            limit,
+            heap: BinaryHeap::with_capacity(limit + 1),
+        }
+    }
+
+    pub fn add(&mut self, account: RichAccount) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.heap.len() < self.limit {
+            self.heap.push(Ranked(account));
+        } else if self.heap.peek().map(|last| account.rank() < last.0.rank()).unwrap_or(false) {
+            self.heap.pop();
+            self.heap.push(Ranked(account));
+        }
+    }
+
+    /// Richest first.
+    pub fn into_sorted_vec(self) -> Vec<RichAccount> {
+        self.heap.into_sorted_vec().into_iter().map(|Ranked(account)| account).collect()
+    }
+}
+
+/// The `top` accounts owning at least `min_balance` TRX, balance plus frozen, ordered by `RichAccount::rank`.
+pub fn rich_list(db: &StateDB, min_balance: i64, top: usize) -> Vec<RichAccount> {
+    let mut list = RichList::new(top);
+    db.for_each(|key: &keys::Account, acct: &Account| {
+        let account = RichAccount::new(key.0, acct);
+        if account.total() >= min_balance {
+            list.add(account);
+        }
+    });
+    let mut accounts = list.into_sorted_vec();
+
+    for account in accounts.iter_mut() {
+        db.scan_prefix(&account.address, |key: &keys::AccountAsset, balance: &i64| {
+            if *balance != 0 {
+                account.assets.push((key.1, *balance));
+            }
+        });
+    }
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_stats() {
+        let mut stats = AccountStats::default();
+        let mut heap = BinaryHeap::new();
+        for (i, num_assets) in [0, 1, 5, 50, 500, 2].iter().enumerate() {
+            let account = AccountSize {
+                size: 100 + num_assets * 37,
+                num_assets: *num_assets,
+                address: *Address::from_bytes(&[0x41; 21]),
+            };
+            stats.add(account, 3, &mut heap);
+            assert_eq!(heap.len(), (i + 1).min(3));
+        }
+        stats.biggest = heap.into_sorted_vec().into_iter().map(|Reverse(account)| account).collect();
+
+        assert_eq!(stats.num_accounts, 6);
+        assert_eq!(stats.by_asset_count, [1, 1, 2, 1, 1]);
+        assert_eq!(
+            stats.biggest.iter().map(|account| account.num_assets).collect::<Vec<_>>(),
+            vec![500, 50, 5]
+        );
+    }
+
+    #[test]
+    fn test_rich_account() {
+        let mut acct = Account {
+            balance: 1_000,
+            frozen_amount_for_energy: 200,
+            delegated_out_amount: 30,
+            frozen_v2_amount_for_bandwidth: 4,
+            ..Default::default()
+        };
+        let rich = RichAccount::new(*Address::from_bytes(&[0x41; 21]), &acct);
+        assert_eq!(rich.frozen, 234);
+        assert_eq!(rich.total(), 1_234);
+
+        acct.balance = 2_000;
+        let richer = RichAccount::new(*Address::from_bytes(&[0x42; 21]), &acct);
+        let mut accounts = vec![rich.clone(), richer.clone()];
+        accounts.sort_by_key(RichAccount::rank);
+        assert_eq!(accounts, vec![richer, rich]);
+    }
+
+    #[test]
+    fn test_rich_list() {
+        let mut list = RichList::new(3);
+        for (i, &balance) in [5, 1, 9, 3, 7, 9].iter().enumerate() {
+            let acct = Account {
+                balance,
+                ..Default::default()
+            };
+            list.add(RichAccount::new(*Address::from_bytes(&[i as u8; 21]), &acct));
+        }
+        let accounts = list.into_sorted_vec();
+        assert_eq!(
+            accounts.iter().map(|account| account.balance).collect::<Vec<_>>(),
+            vec![9, 9, 7]
+        );
+        // Ties are ordered by address.
+        assert_eq!(accounts[0].address, *Address::from_bytes(&[2; 21]));
+    }
+}
@@ -1,5 +1,6 @@
 pub use evm::executor::StackExecutor;
-pub use evm::{Config, Context, Runtime, ExitReason, ExitSucceed, ExitError};
+pub use evm::{Capture, Config, Context, Handler, Runtime, ExitReason, ExitSucceed, ExitError};
 
 pub mod backend;
+pub mod limits;
 pub mod precompile;
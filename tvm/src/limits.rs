@@ -0,0 +1,72 @@
+//! TVM execution limits, same as java-tron.
+
+/// Max depth of nested calls and creates.
+pub const MAX_CALL_DEPTH: usize = 64;
+
+/// Max number of stack items.
+pub const STACK_LIMIT: usize = 1024;
+
+/// Energy per word of memory.
+pub const MEMORY_ENERGY: usize = 3;
+
+/// Divisor of the quadratic memory energy.
+pub const MEMORY_QUAD_COEFF_DIV: usize = 512;
+
+/// TVM has no EIP-170 limit, contract size is bounded by transaction size.
+pub const MAX_CONTRACT_SIZE: Option<usize> = None;
+
+/// Max number of interpreter steps of the top-level frame, a safety net of block application beyond energy.
+///
+/// Not a java-tron limit. Every opcode that does not exit costs at least 1 energy, so the limit is never reached unless
+/// energy accounting is broken. Derived from the energy limit, i.e. from fee_limit, it is the same on all nodes.
+pub fn max_steps(energy_limit: usize) -> usize {
+    energy_limit.saturating_add(1)
+}
+
+/// Total energy of memory with `size` bytes, the expansion cost is the difference of two sizes.
+pub fn memory_energy(size: usize) -> usize {
+    let words = (size + 31) / 32;
+    MEMORY_ENERGY * words + words * words / MEMORY_QUAD_COEFF_DIV
+}
+
+/// Check limits of a VM config.
+pub fn check_config(config: &crate::Config) -> Result<(), String> {
+    if config.call_stack_limit != MAX_CALL_DEPTH {
+        return Err(format!("call depth limit {} != {}", config.call_stack_limit, MAX_CALL_DEPTH));
+    }
+    if config.stack_limit != STACK_LIMIT {
+        return Err(format!("stack limit {} != {}", config.stack_limit, STACK_LIMIT));
+    }
+    if config.create_contract_limit != MAX_CONTRACT_SIZE {
+        return Err(format!(
+            "contract size limit {:?} != {:?}",
+            config.create_contract_limit, MAX_CONTRACT_SIZE
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_energy() {
+        assert_eq!(memory_energy(0), 0);
+        assert_eq!(memory_energy(1), 3);
+        assert_eq!(memory_energy(1024), 98);
+        assert_eq!(memory_energy(32 * 1024), 5120);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        assert_eq!(max_steps(0), 1);
+        assert_eq!(max_steps(10_000_000), 10_000_001);
+        assert_eq!(max_steps(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn test_odyssey_config_limits() {
+        assert_eq!(check_config(&crate::Config::odyssey_3_7()), Ok(()));
+    }
+}
@@ -0,0 +1,189 @@
+//! TVM conformance tests, running VM test vectors.
+//!
+//! Run with `cargo test -p tvm --features vm-conformance`. Vectors are loaded from `tests/vectors`, or from
+//! `TVM_VECTORS_DIR`, e.g. vectors exported from java-tron's VM tests.
+//!
+//! Each vector file is a JSON list of:
+//!
+//! ```json
+//! {
+//!   "name": "stack-limit-1025",
+//!   "code": "<hex>",
+//!   "data": "<hex>",
+//!   "energy": 100000,
+//!   "expect": { "exit": "Error(StackOverflow)", "energy_used": 3072, "out": "<hex>" }
+//! }
+//! ```
+#![cfg(feature = "vm-conformance")]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use primitive_types::{H160, H256, U256};
+use serde::Deserialize;
+use tvm::backend::{Backend, Basic};
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    #[serde(default)]
+    description: String,
+    code: String,
+    #[serde(default)]
+    data: String,
+    energy: usize,
+    expect: Expect,
+}
+
+#[derive(Deserialize)]
+struct Expect {
+    exit: String,
+    energy_used: Option<usize>,
+    out: Option<String>,
+}
+
+/// An empty world, only the executing contract has code.
+struct VectorBackend {
+    address: H160,
+    code: Vec<u8>,
+}
+
+impl Backend for VectorBackend {
+    fn gas_price(&self) -> U256 {
+        U256::zero()
+    }
+    fn origin(&self) -> H160 {
+        H160::zero()
+    }
+    fn block_hash(&self, _number: U256) -> H256 {
+        H256::zero()
+    }
+    fn block_number(&self) -> U256 {
+        U256::zero()
+    }
+    fn block_coinbase(&self) -> H160 {
+        H160::zero()
+    }
+    fn block_timestamp(&self) -> U256 {
+        U256::zero()
+    }
+    fn block_difficulty(&self) -> U256 {
+        U256::zero()
+    }
+    fn block_gas_limit(&self) -> U256 {
+        U256::zero()
+    }
+    fn chain_id(&self) -> U256 {
+        U256::zero()
+    }
+    fn exists(&self, address: H160) -> bool {
+        address == self.address
+    }
+    fn basic(&self, _address: H160) -> Basic {
+        Basic::default()
+    }
+    fn code_hash(&self, _address: H160) -> H256 {
+        H256::zero()
+    }
+    fn code_size(&self, address: H160) -> usize {
+        self.code(address).len()
+    }
+    fn code(&self, address: H160) -> Vec<u8> {
+        if address == self.address {
+            self.code.clone()
+        } else {
+            vec![]
+        }
+    }
+    fn storage(&self, _address: H160, _index: H256) -> Option<H256> {
+        None
+    }
+}
+
+fn vectors_dir() -> PathBuf {
+    env::var("TVM_VECTORS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/vectors")))
+}
+
+fn load_vectors() -> Vec<Vector> {
+    let mut paths: Vec<_> = fs::read_dir(vectors_dir())
+        .expect("vectors dir")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .flat_map(|path| {
+            let content = fs::read_to_string(path).unwrap();
+            serde_json::from_str::<Vec<Vector>>(&content)
+                .unwrap_or_else(|e| panic!("invalid vector file {:?}: {}", path, e))
+        })
+        .collect()
+}
+
+/// Run a vector, returns the mismatch if any.
+fn run_vector(vector: &Vector) -> Result<(), String> {
+    let address = H160::from_low_u64_be(0x1000);
+    let backend = VectorBackend {
+        address,
+        code: hex::decode(&vector.code).map_err(|e| e.to_string())?,
+    };
+    let data = hex::decode(&vector.data).map_err(|e| e.to_string())?;
+
+    let config = tvm::Config::odyssey_3_7();
+    let mut executor = tvm::StackExecutor::new(&backend, vector.energy, &config);
+    let ctx = tvm::Context {
+        address,
+        caller: H160::from_low_u64_be(0x2000),
+        call_value: U256::zero(),
+        call_token_id: U256::zero(),
+        call_token_value: U256::zero(),
+    };
+    let mut rt = tvm::Runtime::new(Rc::new(backend.code.clone()), Rc::new(data), ctx, &config);
+    let exit_reason = format!("{:?}", executor.execute(&mut rt));
+
+    if exit_reason != vector.expect.exit {
+        return Err(format!("exit {} != {}", exit_reason, vector.expect.exit));
+    }
+    if let Some(energy_used) = vector.expect.energy_used {
+        if executor.used_gas() != energy_used {
+            return Err(format!("energy used {} != {}", executor.used_gas(), energy_used));
+        }
+    }
+    if let Some(ref out) = vector.expect.out {
+        let ret = hex::encode(rt.machine().return_value());
+        if &ret != out {
+            return Err(format!("out {} != {}", ret, out));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_config_limits() {
+    assert_eq!(tvm::limits::check_config(&tvm::Config::odyssey_3_7()), Ok(()));
+}
+
+#[test]
+fn test_vectors() {
+    let vectors = load_vectors();
+    assert!(!vectors.is_empty(), "no vectors found in {:?}", vectors_dir());
+
+    let failures: Vec<_> = vectors
+        .iter()
+        .filter_map(|vector| {
+            run_vector(vector)
+                .err()
+                .map(|e| format!("{} ({}): {}", vector.name, vector.description, e))
+        })
+        .collect();
+    for failure in &failures {
+        eprintln!("FAILED {}", failure);
+    }
+    assert!(failures.is_empty(), "{}/{} vectors failed", failures.len(), vectors.len());
+}
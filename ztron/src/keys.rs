@@ -58,6 +58,12 @@ impl FromStr for ZAddress {
     }
 }
 
+impl From<PaymentAddress<Bls12>> for ZAddress {
+    fn from(addr: PaymentAddress<Bls12>) -> Self {
+        ZAddress(addr)
+    }
+}
+
 impl Hash for ZAddress {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.0.to_bytes().hash(state);
@@ -1,3 +1,4 @@
 pub mod keys;
 pub mod builder;
 pub mod precompiles;
+pub mod scan;
@@ -0,0 +1,75 @@
+//! Detection of incoming notes of shielded TRC20 contracts, by trial decryption with viewing keys.
+//!
+//! Same as `scanShieldedTRC20NotesByIvk` of java-tron, but for one output at a time, so that a node can scan blocks
+//! while applying them.
+
+use ff::PrimeField;
+use pairing::bls12_381::{Bls12, Fr, FrRepr};
+use zcash_primitives::jubjub::{edwards, fs::Fs, Unknown};
+use zcash_primitives::keys::FullViewingKey;
+use zcash_primitives::note_encryption::try_sapling_note_decryption;
+use zcash_primitives::primitives::Note;
+use zcash_primitives::JUBJUB;
+
+use crate::keys::ZAddress;
+
+/// Size of an encrypted note plaintext, `enc_ciphertext` of an output.
+pub const ENC_CIPHERTEXT_SIZE: usize = 580;
+
+/// Full viewing key, to detect incoming notes and to compute their nullifiers.
+pub struct ViewingKey {
+    fvk: FullViewingKey<Bls12>,
+    ivk: Fs,
+}
+
+impl ViewingKey {
+    /// Parse a full viewing key, `ak || nk || ovk` in 96 bytes.
+    pub fn from_slice(raw: &[u8]) -> Option<Self> {
+        if raw.len() != 96 {
+            return None;
+        }
+        let fvk = FullViewingKey::<Bls12>::read(raw, &JUBJUB).ok()?;
+        let ivk = fvk.vk.ivk();
+        Some(ViewingKey { fvk, ivk })
+    }
+
+    pub fn ivk(&self) -> Vec<u8> {
+        self.ivk.to_repr().as_ref().to_vec()
+    }
+}
+
+/// A note decrypted from an output.
+#[derive(Debug, Clone)]
+pub struct DecryptedNote {
+    /// In units of the scaling factor of the contract.
+    pub value: u64,
+    pub rcm: Vec<u8>,
+    pub payment_address: ZAddress,
+    pub memo: Vec<u8>,
+    /// Nullifier revealed when the note is spent.
+    pub nullifier: Vec<u8>,
+}
+
+/// Try to decrypt an output at `position` of the note commitment tree. `cm` and `epk` are 32-byte encodings, as in
+/// `NewLeaf` events, `c_enc` is the encrypted note plaintext.
+pub fn try_decrypt_note(key: &ViewingKey, position: u64, cm: &[u8], epk: &[u8], c_enc: &[u8]) -> Option<DecryptedNote> {
+    if cm.len() != 32 || epk.len() != 32 || c_enc.len() < ENC_CIPHERTEXT_SIZE {
+        return None;
+    }
+    let mut cmu = FrRepr::default();
+    cmu.as_mut().copy_from_slice(cm);
+    let cmu = Fr::from_repr(cmu)?;
+    let epk = edwards::Point::<Bls12, Unknown>::read(epk, &JUBJUB)
+        .ok()?
+        .as_prime_order(&JUBJUB)?;
+
+    let (note, payment_address, memo): (Note<Bls12>, _, _) =
+        try_sapling_note_decryption(&key.ivk, &epk, &cmu, &c_enc[..ENC_CIPHERTEXT_SIZE])?;
+    Some(DecryptedNote {
+        value: note.value,
+        rcm: note.r.to_repr().as_ref().to_vec(),
+        payment_address: payment_address.into(),
+        memo: memo.as_bytes().to_vec(),
+        nullifier: note.nf(&key.fvk.vk, position, &JUBJUB),
+    })
+}